@@ -1,12 +1,105 @@
 use serde::Deserialize;
-use serde_json::{Value, json};
+use serde_json::{Map, Value, json};
 
-use crate::build_prompt::{Message, format_context_from_hits};
+use crate::build_prompt::{
+    CONTEXT_LINE_MARKER, Message, answer_style_instructions, answer_system_prompt, context_budget_tokens, fit_hits_to_budget, format_context_from_hits,
+    format_display_context_from_hits, no_relevant_context_message,
+};
+use crate::answer::{AnswerResult, FinalDetails, SourceHit};
 use crate::config::Config;
-use crate::embed_query::embed_query;
-use crate::generate::{generate_answer, generate_json};
+use crate::deadline::{Deadline, call_with_timeout};
+use crate::best_of::generate_best_of;
+use crate::generate::{generate_json, generate_json_schema};
+use crate::local_tools::LocalToolRegistry;
 use crate::mcp::{McpCapabilities, McpClient};
-use crate::retrieve_chunks::retrieve_top;
+use crate::mcp_health::McpConnection;
+use crate::mcp_cache::discover_capabilities_cached;
+use crate::memory::compact_conversation;
+use crate::tool::Tool;
+use crate::tool_schema::validate_tool_args;
+use crate::query_expansion::{annotate_broadened, retrieval_broadening_async};
+use crate::rerank::add_rerank_time;
+use crate::retrieve_chunks::{Hit, Retrieval};
+use crate::runtime::block_on;
+use crate::trace::{start_trace, trace_event};
+use crate::usage::{Usage, current_usage};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+const EVENT_PREVIEW_CHARS: usize = 200;
+
+/// What the agent loop is doing, sent as it happens so a UI can show a live
+/// trace and headless runs can log each step.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentEvent {
+    DecisionMade { step: usize, action: String },
+    /// `kind` is `tool`, `local` (a built-in tool), `prompt`, or `resource`.
+    ToolCalled { kind: &'static str, name: String, args: Value },
+    /// `output` is cut to a short preview.
+    ToolResult { name: String, ok: bool, output: String },
+    RetrieveDone { query: String, hits: usize },
+    Error { step: usize, message: String },
+    /// The numbered plan of the `plan` strategy, before its steps run.
+    Planned { steps: Vec<String> },
+    /// The oldest `messages` were replaced by one summary message.
+    MemoryCompacted { messages: usize, tokens_before: usize, tokens_after: usize },
+    /// The controller reply of `step` was not a valid action; attempt
+    /// `attempt` asks for it again with the parse `error`.
+    RepairAttempted { step: usize, attempt: usize, error: String },
+    /// Model tokens one step used, and the query's running total.
+    TokensUsed { step: usize, used: Usage, total: Usage },
+    Final { answer: String, forced: bool },
+}
+
+impl fmt::Display for AgentEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentEvent::DecisionMade { step, action } => write!(f, "step {}: {}", step + 1, action),
+            AgentEvent::ToolCalled { kind, name, args } => write!(f, "  {} {} {}", kind, name, args),
+            AgentEvent::ToolResult { name, ok, output } => {
+                write!(f, "  {} {}: {}", name, if *ok { "ok" } else { "failed" }, output)
+            }
+            AgentEvent::RetrieveDone { query, hits } => write!(f, "  retrieved {} chunks for {:?}", hits, query),
+            AgentEvent::Error { step, message } => write!(f, "step {}: error: {}", step + 1, message),
+            AgentEvent::Planned { steps } => {
+                write!(f, "plan:")?;
+                for (i, step) in steps.iter().enumerate() {
+                    write!(f, "\n  {}. {}", i + 1, step)?;
+                }
+                Ok(())
+            }
+            AgentEvent::MemoryCompacted { messages, tokens_before, tokens_after } => write!(
+                f,
+                "  compacted {} messages into a summary ({} → {} tokens)",
+                messages, tokens_before, tokens_after
+            ),
+            AgentEvent::RepairAttempted { attempt, error, .. } => {
+                write!(f, "  invalid controller JSON, repair {}: {}", attempt, error)
+            }
+            AgentEvent::TokensUsed { used, total, .. } => write!(
+                f,
+                "  tokens: {} prompt + {} completion (query total {})",
+                used.prompt_tokens,
+                used.completion_tokens,
+                total.total_tokens()
+            ),
+            AgentEvent::Final { forced: false, .. } => write!(f, "final answer"),
+            AgentEvent::Final { forced: true, .. } => write!(f, "step limit reached: final answer forced"),
+        }
+    }
+}
+
+fn preview(text: &str) -> String {
+    let mut out: String = text.chars().take(EVENT_PREVIEW_CHARS).collect();
+    if text.chars().count() > EVENT_PREVIEW_CHARS {
+        out.push('…');
+    }
+    out
+}
 
 #[derive(Clone, Debug)]
 pub struct AgentState {
@@ -14,6 +107,38 @@ pub struct AgentState {
     pub current_step: usize,
     pub max_steps: usize,
     pub context_log: Vec<String>,
+    pub hits: Vec<Hit>,
+    pub last_prompt: Vec<Message>,
+    /// Structured fields of the final action, when valid ones were given.
+    pub final_details: Option<FinalDetails>,
+    /// Set when a retrieval found nothing and was repeated with a broader scope.
+    pub broadened: Option<String>,
+    /// Receives an `AgentEvent` for every step, when set.
+    pub events: Option<Sender<AgentEvent>>,
+    pub mode: AgentMode,
+    /// Input schemas from `tools/list`; tool arguments are checked against them.
+    pub tool_schemas: HashMap<String, Value>,
+    /// Built-in and registered Rust tools offered next to the MCP server's.
+    pub local_tools: LocalToolRegistry,
+    /// JSONL file every message, decision, and tool call is appended to.
+    pub trace_file: Option<PathBuf>,
+    /// Facts every memory summary must keep, listed verbatim after it.
+    pub pinned: Vec<String>,
+    /// When it passes, the run stops and returns the context gathered so far.
+    pub deadline: Deadline,
+    /// `RAG_AGENT_TOTAL_TIMEOUT`: when it passes, no more steps are taken
+    /// and the answer is written from what was gathered.
+    pub budget: Deadline,
+    /// `RAG_AGENT_STEP_TIMEOUT`: the longest one model or tool call may take.
+    pub step_timeout: Option<Duration>,
+    /// Repair calls made for malformed controller replies. They do not
+    /// count as steps.
+    pub repairs: usize,
+    /// Time the reranker took over all retrievals, if one ran.
+    pub rerank_time: Option<Duration>,
+    /// The first reranker failure; those hits kept their vector order.
+    pub rerank_error: Option<String>,
+    traced_messages: usize,
 }
 
 impl AgentState {
@@ -23,6 +148,119 @@ impl AgentState {
             current_step: 0,
             max_steps,
             context_log: Vec::new(),
+            hits: Vec::new(),
+            last_prompt: Vec::new(),
+            final_details: None,
+            broadened: None,
+            events: None,
+            mode: AgentMode::Hybrid,
+            tool_schemas: HashMap::new(),
+            local_tools: LocalToolRegistry::default(),
+            trace_file: None,
+            pinned: Vec::new(),
+            deadline: Deadline::none(),
+            budget: Deadline::none(),
+            step_timeout: None,
+            repairs: 0,
+            rerank_time: None,
+            rerank_error: None,
+            traced_messages: 0,
+        }
+    }
+
+    /// Send `event` to the listener, if any. A listener that went away is
+    /// not an error; the loop keeps running.
+    pub fn emit(&self, event: AgentEvent) {
+        match &event {
+            AgentEvent::DecisionMade { step, action } => self.trace("decision", json!({ "step": step, "action": action })),
+            AgentEvent::ToolCalled { kind, name, args } => {
+                self.trace("tool_call", json!({ "type": kind, "name": name, "arguments": args }))
+            }
+            AgentEvent::Error { step, message } => self.trace("error", json!({ "step": step, "message": message })),
+            AgentEvent::Planned { steps } => self.trace("plan", json!({ "steps": steps })),
+            AgentEvent::MemoryCompacted { messages, tokens_before, tokens_after } => self.trace(
+                "compact",
+                json!({ "messages": messages, "tokens_before": tokens_before, "tokens_after": tokens_after }),
+            ),
+            AgentEvent::RepairAttempted { step, attempt, error } => {
+                self.trace("repair", json!({ "step": step, "attempt": attempt, "error": error }))
+            }
+            AgentEvent::TokensUsed { step, used, total } => self.trace(
+                "tokens",
+                json!({
+                    "step": step,
+                    "prompt_tokens": used.prompt_tokens,
+                    "completion_tokens": used.completion_tokens,
+                    "cost_usd": used.cost_usd,
+                    "total_tokens": total.total_tokens(),
+                }),
+            ),
+            AgentEvent::Final { answer, forced } => self.trace("final", json!({ "answer": answer, "forced": forced })),
+            // Traced where they happen, with the full output and latency.
+            AgentEvent::ToolResult { .. } | AgentEvent::RetrieveDone { .. } => {}
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Append one line to the trace file, if tracing. A trace that cannot
+    /// be written never fails the run.
+    pub fn trace(&self, kind: &str, fields: Value) {
+        if let Some(path) = &self.trace_file {
+            let _ = trace_event(path, kind, fields);
+        }
+    }
+
+    /// Trace conversation messages added since the last call.
+    fn trace_messages(&mut self) {
+        if self.trace_file.is_none() {
+            return;
+        }
+        for message in &self.conversation[self.traced_messages.min(self.conversation.len())..] {
+            self.trace("message", json!({ "role": message.role, "content": message.content }));
+        }
+        self.traced_messages = self.conversation.len();
+    }
+
+    /// How long the next model or tool call may take: the step timeout,
+    /// cut to what is left of the query deadline and the agent budget.
+    pub fn call_limit(&self) -> Option<Duration> {
+        shortest(&[self.step_timeout, self.deadline.remaining(), self.budget.remaining()])
+    }
+
+    /// Keep `fact` through memory compaction.
+    pub fn pin(&mut self, fact: impl Into<String>) {
+        let fact = fact.into();
+        if !self.pinned.contains(&fact) {
+            self.pinned.push(fact);
+        }
+    }
+
+    /// `RAG_MEMORY_COMPACT`: once the conversation outgrows the memory
+    /// threshold, fold its oldest turns into a summary. A failed summary
+    /// leaves the conversation as it was.
+    fn compact_memory(&mut self, cfg: &Config) {
+        if !cfg.memory_compact {
+            return;
+        }
+        // Everything about to be summarized is traced verbatim first.
+        self.trace_messages();
+        match compact_conversation(cfg, &mut self.conversation, &self.pinned) {
+            Ok(Some(compaction)) => {
+                self.traced_messages = self.conversation.len();
+                self.trace("summary", json!({ "content": compaction.summary }));
+                self.emit(AgentEvent::MemoryCompacted {
+                    messages: compaction.messages,
+                    tokens_before: compaction.tokens_before,
+                    tokens_after: compaction.tokens_after,
+                });
+            }
+            Ok(None) => {}
+            Err(err) => self.emit(AgentEvent::Error {
+                step: self.current_step,
+                message: format!("memory compaction failed: {}", err),
+            }),
         }
     }
 
@@ -33,6 +271,13 @@ impl AgentState {
         });
     }
 
+    /// Offer a Rust tool to `run_agent`. Its name, description, and schema
+    /// still have to be described to the model in the conversation.
+    pub fn register_tool(&mut self, tool: Arc<dyn Tool>) {
+        self.tool_schemas.insert(tool.name().to_string(), tool.input_schema());
+        self.local_tools.register(tool);
+    }
+
     pub fn append_system(&mut self, text: String) {
         self.conversation.push(Message {
             role: "system".to_string(),
@@ -48,6 +293,15 @@ impl AgentState {
         });
     }
 
+    /// Send `text` to the model but show `display` in the context log.
+    pub fn append_context_with_display(&mut self, text: String, display: String) {
+        self.context_log.push(display);
+        self.conversation.push(Message {
+            role: "system".to_string(),
+            content: text,
+        });
+    }
+
     pub fn append_tool(&mut self, text: String) {
         self.context_log.push(text.clone());
         self.conversation.push(Message {
@@ -87,190 +341,974 @@ struct DecisionEnvelope {
     uri: Option<String>,
 }
 
-pub fn answer_query_hybrid(cfg: &Config, question: &str) -> Result<(String, String), String> {
+/// Which actions the agent may choose.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgentMode {
+    /// Retrieval plus MCP tools, prompts, and resources.
+    #[default]
+    Hybrid,
+    /// Retrieval only; MCP actions are answered with a retrieval instead.
+    RagOnly,
+    /// MCP only; retrieval is refused.
+    McpOnly,
+}
+
+impl AgentMode {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "hybrid" => Some(Self::Hybrid),
+            "rag" | "rag-only" => Some(Self::RagOnly),
+            "mcp" | "mcp-only" => Some(Self::McpOnly),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Hybrid => "hybrid",
+            Self::RagOnly => "rag-only",
+            Self::McpOnly => "mcp-only",
+        }
+    }
+
+    /// The next mode in the TUI toggle.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Hybrid => Self::RagOnly,
+            Self::RagOnly => Self::McpOnly,
+            Self::McpOnly => Self::Hybrid,
+        }
+    }
+}
+
+/// How the agent works through a question.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgentStrategy {
+    /// One controller loop choosing an action per step.
+    #[default]
+    Loop,
+    /// A numbered plan first, then each plan step in turn, then a synthesis
+    /// of the step findings.
+    Plan,
+}
+
+impl AgentStrategy {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "loop" | "react" => Some(Self::Loop),
+            "plan" | "plan-execute" => Some(Self::Plan),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Loop => "loop",
+            Self::Plan => "plan",
+        }
+    }
+}
+
+/// Per-call agent settings.
+#[derive(Clone, Debug, Default)]
+pub struct AgentOptions {
+    pub mode: AgentMode,
+    pub strategy: AgentStrategy,
+    /// Rust tools offered to the agent; one with the same name as an MCP
+    /// tool is used instead of it.
+    pub tools: Vec<Arc<dyn Tool>>,
+}
+
+impl AgentOptions {
+    /// Defaults from `RAG_AGENT_MODE` and `RAG_AGENT_STRATEGY`, with no
+    /// registered tools.
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            mode: AgentMode::parse(&cfg.agent_mode).unwrap_or_default(),
+            strategy: AgentStrategy::parse(&cfg.agent_strategy).unwrap_or_default(),
+            tools: Vec::new(),
+        }
+    }
+}
+
+pub fn answer_query_hybrid(cfg: &Config, question: &str, options: &AgentOptions) -> Result<(String, String), String> {
+    answer_query_hybrid_with_history(cfg, &[], question, options)
+}
+
+pub fn answer_query_hybrid_with_history(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+) -> Result<(String, String), String> {
+    let result = answer_query_hybrid_detailed(cfg, history, question, options)?;
+    Ok((result.context, result.answer))
+}
+
+pub fn answer_query_hybrid_detailed(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+) -> Result<AnswerResult, String> {
+    answer_query_hybrid_with_events(cfg, history, question, options, None)
+}
+
+/// Like `answer_query_hybrid_detailed`, sending an `AgentEvent` to `events`
+/// for every step of the agent loop.
+pub fn answer_query_hybrid_with_events(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+    events: Option<Sender<AgentEvent>>,
+) -> Result<AnswerResult, String> {
+    let started = Instant::now();
+    let deadline = Deadline::from_config(cfg);
     let mcp = McpClient::from_config(cfg);
     let mcp_enabled = mcp.is_enabled();
-    let caps = mcp.discover_capabilities();
+    let mut caps = discover_capabilities_cached(cfg, &mcp);
+    // Cached capabilities can list a server that is down; leave its tools,
+    // prompts, and resources out so the model does not keep trying them.
+    let connections = mcp.connections();
+    for (server, connection) in &connections {
+        if matches!(connection, McpConnection::Down(_)) {
+            drop_server_capabilities(&mut caps, server);
+        }
+    }
+    // A server tool with the same name as a built-in one takes precedence;
+    // registered Rust tools replace server tools of the same name.
+    let mut local_tools = LocalToolRegistry::from_config(cfg).without(&caps.tools);
+    for tool in &options.tools {
+        local_tools.register(tool.clone());
+    }
+    let local_specs = local_tools.specs();
+    caps.tools.retain(|name| !local_specs.iter().any(|s| &s.name == name));
+    caps.tool_specs.retain(|t| !local_specs.iter().any(|s| s.name == t.name));
+    for spec in local_specs {
+        caps.tools.push(spec.name.clone());
+        caps.tool_specs.push(spec);
+    }
     let mut state = AgentState::new(cfg.agent_max_steps.max(1));
-    state.append_system(build_hybrid_system_prompt(cfg, &caps, mcp_enabled));
-    if is_rag_only_query(question) {
-        state.append_system(
-            "User requested RAG-only mode for this query. Do not use MCP tool/prompt/resource actions. Use retrieve and final only."
+    state.events = events;
+    state.deadline = deadline;
+    state.budget = Deadline::after_secs(cfg.agent_total_timeout_secs);
+    state.step_timeout = (cfg.agent_step_timeout_secs > 0).then(|| Duration::from_secs(cfg.agent_step_timeout_secs));
+    state.mode = options.mode;
+    state.tool_schemas = caps
+        .tool_specs
+        .iter()
+        .filter(|t| !t.input_schema.is_null())
+        .map(|t| (t.name.clone(), t.input_schema.clone()))
+        .collect();
+    state.trace_file = start_trace(cfg).unwrap_or(None);
+    state.append_system(build_hybrid_system_prompt(cfg, &caps, mcp_enabled, &local_tools));
+    for (server, connection) in &connections {
+        if let Some(note) = connection.prompt_note(server) {
+            state.append_system(note);
+        }
+    }
+    state.local_tools = local_tools;
+    match options.mode {
+        AgentMode::Hybrid => {}
+        AgentMode::RagOnly => state.append_system(
+            "RAG-only mode is on for this query. Do not use MCP tool/prompt/resource actions. Use retrieve and final only."
                 .to_string(),
-        );
+        ),
+        AgentMode::McpOnly => state.append_system(
+            "MCP-only mode is on for this query. Do not use retrieve. Use tool, prompt, resource, or final.".to_string(),
+        ),
     }
+    state.conversation.extend(history.iter().cloned());
     state.append_user(question.to_string());
-    let answer = run_agent(&mut state, cfg, &mcp)?;
-    Ok((state.context_text(), answer))
+    let answer = match options.strategy {
+        AgentStrategy::Loop => run_agent(&mut state, cfg, &mcp)?,
+        AgentStrategy::Plan => run_agent_with_plan(&mut state, cfg, &mcp)?,
+    };
+    let answer = annotate_broadened(answer, state.broadened.as_deref());
+    Ok(AnswerResult {
+        context: state.context_text(),
+        answer,
+        hits: state.hits.iter().map(SourceHit::from).collect(),
+        prompt: state.last_prompt.clone(),
+        model: cfg.chat_model.clone(),
+        elapsed: started.elapsed(),
+        rerank_time: state.rerank_time,
+        rerank_error: state.rerank_error.clone(),
+        details: state.final_details.clone(),
+        usage: current_usage(),
+    })
 }
 
 pub fn run_agent(state: &mut AgentState, cfg: &Config, mcp: &McpClient) -> Result<String, String> {
     while state.current_step < state.max_steps {
-        let raw = generate_json(cfg, &state.conversation)?;
-        let decision = match parse_decision(&raw) {
-            Ok(d) => d,
-            Err(err) => {
-                state.append_system(format!(
-                    "Invalid controller JSON output: {}. Return valid JSON with one action and required fields.",
-                    err
-                ));
-                state.current_step += 1;
-                continue;
+        if state.deadline.expired() {
+            return Ok(timed_out(state));
+        }
+        if state.budget.expired() {
+            budget_spent(state);
+            break;
+        }
+        let step_cfg = state.deadline.limit_config(cfg);
+        match agent_step(state, &step_cfg, mcp) {
+            Ok(Some(answer)) => {
+                let answer = if cfg.agent_reflect { reflect(state, &step_cfg, answer) } else { answer };
+                state.emit(AgentEvent::Final { answer: answer.clone(), forced: false });
+                return Ok(answer);
+            }
+            Ok(None) => {}
+            Err(_) if state.deadline.expired() => return Ok(timed_out(state)),
+            Err(_) if state.budget.expired() => {
+                budget_spent(state);
+                break;
             }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if state.deadline.expired() {
+        return Ok(timed_out(state));
+    }
+    let final_cfg = state.deadline.limit_config(cfg);
+    match force_final_answer(state, &final_cfg) {
+        Ok(answer) => {
+            state.emit(AgentEvent::Final { answer: answer.clone(), forced: true });
+            Ok(answer)
+        }
+        Err(_) if state.deadline.expired() => Ok(timed_out(state)),
+        Err(fallback_err) => {
+            let err = format!(
+                "Max steps exceeded (limit: {}) before final answer; fallback generation failed: {}",
+                state.max_steps, fallback_err
+            );
+            state.emit(AgentEvent::Error { step: state.current_step, message: err.clone() });
+            Err(err)
+        }
+    }
+}
+
+/// The answer of a run stopped by its deadline: the timeout message, with
+/// the context gathered so far left in `context_log` for the caller.
+fn timed_out(state: &mut AgentState) -> String {
+    let message = state.deadline.timeout_message();
+    state.final_details = None;
+    state.emit(AgentEvent::Error { step: state.current_step, message: message.clone() });
+    message
+}
+
+// The agent budget ran out; the loop stops and the answer is forced from
+// what was gathered.
+fn budget_spent(state: &AgentState) {
+    state.emit(AgentEvent::Error {
+        step: state.current_step,
+        message: format!(
+            "agent time budget of {}s (RAG_AGENT_TOTAL_TIMEOUT) spent; answering from what was gathered",
+            state.budget.secs()
+        ),
+    });
+}
+
+fn shortest(limits: &[Option<Duration>]) -> Option<Duration> {
+    limits.iter().flatten().min().copied()
+}
+
+/// `RAG_AGENT_REFLECT`: one extra controller call that checks `answer`
+/// against the context and tool results in the conversation and either
+/// confirms it or returns a revision. Runs even when the step budget is
+/// spent; any failure keeps the original answer.
+fn reflect(state: &mut AgentState, cfg: &Config, answer: String) -> String {
+    let mut messages = state.conversation.clone();
+    messages.push(Message {
+        role: "system".to_string(),
+        content: format!(
+            "Proposed final answer:\n{}\n\nCheck it against the retrieved context and tool results above. Every claim must be supported by them and the question must be fully answered. Return JSON only: {{\"verdict\": \"confirm\"}} if it holds, or {{\"verdict\": \"revise\", \"answer\": \"<corrected answer>\"}} if it does not.",
+            answer
+        ),
+    });
+    state.last_prompt = messages.clone();
+    let asked = Instant::now();
+    let raw = match generate_json(cfg, &messages) {
+        Ok(raw) => raw,
+        Err(err) => {
+            state.emit(AgentEvent::Error { step: state.current_step, message: format!("reflection failed: {}", err) });
+            return answer;
+        }
+    };
+    state.trace("json", json!({ "response": raw, "latency_ms": elapsed_ms(asked) }));
+    let revised = parse_reflection(&raw);
+    state.emit(AgentEvent::DecisionMade {
+        step: state.current_step,
+        action: if revised.is_some() { "reflect: revised" } else { "reflect: confirmed" }.to_string(),
+    });
+    revised.unwrap_or(answer)
+}
+
+/// The revised answer from a reflection reply, or `None` when the answer
+/// was confirmed or the reply cannot be read.
+pub fn parse_reflection(raw: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(raw.trim()).ok()?;
+    let verdict = value.get("verdict").and_then(Value::as_str)?.trim().to_ascii_lowercase();
+    let answer = value.get("answer").and_then(Value::as_str).map(str::trim).unwrap_or_default();
+    (verdict == "revise" && !answer.is_empty()).then(|| answer.to_string())
+}
+
+/// Plan-then-execute: ask for a numbered plan, give each plan step an equal
+/// share of `max_steps` to retrieve or call tools and end with a `final`
+/// finding, then write the answer from the findings and gathered context.
+/// A plan that cannot be read becomes a single step, the question itself.
+pub fn run_agent_with_plan(state: &mut AgentState, cfg: &Config, mcp: &McpClient) -> Result<String, String> {
+    let question = latest_user_query(state).unwrap_or_default();
+    let plan_cfg = state.deadline.limit_config(cfg);
+    let mut steps = match make_plan(state, &plan_cfg) {
+        Ok(steps) => steps,
+        Err(_) if state.deadline.expired() => return Ok(timed_out(state)),
+        Err(err) => return Err(err),
+    };
+    if steps.is_empty() {
+        steps.push(question.clone());
+    }
+    state.emit(AgentEvent::Planned { steps: steps.clone() });
+    state.append_system(format!("Plan for this question:\n{}", numbered(&steps)));
+
+    let per_step = (state.max_steps / steps.len()).max(1);
+    let mut findings = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        state.append_system(format!(
+            "Work on step {} of {} now: {}\nUse retrieve or a tool to gather what this step needs, then choose final with a short finding for this step only.",
+            i + 1,
+            steps.len(),
+            step
+        ));
+        let limit = (state.current_step + per_step).min(state.max_steps);
+        let mut finding = None;
+        while finding.is_none() && state.current_step < limit && !state.deadline.expired() && !state.budget.expired() {
+            let step_cfg = state.deadline.limit_config(cfg);
+            match agent_step(state, &step_cfg, mcp) {
+                Ok(found) => finding = found,
+                Err(_) if state.deadline.expired() || state.budget.expired() => break,
+                Err(err) => return Err(err),
+            }
+        }
+        if state.deadline.expired() {
+            return Ok(timed_out(state));
+        }
+        let finding = finding.unwrap_or_else(|| "No finding within this step's budget.".to_string());
+        state.append_tool(format!("Finding for step {} ({}): {}", i + 1, step, finding));
+        findings.push(finding);
+    }
+
+    // Step findings are partial; the answer comes from the synthesis.
+    state.final_details = None;
+    let synth_cfg = state.deadline.limit_config(cfg);
+    let answer = match synthesize_plan(state, &synth_cfg, &question, &steps, &findings) {
+        Ok(answer) => answer,
+        Err(_) if state.deadline.expired() => return Ok(timed_out(state)),
+        Err(err) => return Err(err),
+    };
+    state.emit(AgentEvent::Final { answer: answer.clone(), forced: false });
+    Ok(answer)
+}
+
+const PLAN_MAX_STEPS: usize = 5;
+
+fn make_plan(state: &mut AgentState, cfg: &Config) -> Result<Vec<String>, String> {
+    let mut messages = state.conversation.clone();
+    messages.push(Message {
+        role: "system".to_string(),
+        content: format!(
+            "Before acting, break the user's question into at most {} short steps that can each be answered with one retrieval or tool call. Return JSON only: {{\"plan\": [\"step\", ...]}}. A simple question needs a single step.",
+            PLAN_MAX_STEPS
+        ),
+    });
+    state.last_prompt = messages.clone();
+    let asked = Instant::now();
+    let raw = generate_json(cfg, &messages).inspect_err(|err| {
+        state.emit(AgentEvent::Error { step: state.current_step, message: err.clone() });
+    })?;
+    state.trace("json", json!({ "response": raw, "latency_ms": elapsed_ms(asked) }));
+    Ok(parse_plan(&raw))
+}
+
+/// Plan steps from the planner's reply: a `{"plan": [...]}` object, a bare
+/// JSON array, or numbered lines. At most five steps are kept.
+pub fn parse_plan(raw: &str) -> Vec<String> {
+    let json_steps = serde_json::from_str::<Value>(raw.trim()).ok().and_then(|value| {
+        let list = match &value {
+            Value::Array(items) => items.clone(),
+            Value::Object(obj) => obj.get("plan").or_else(|| obj.get("steps"))?.as_array()?.clone(),
+            _ => return None,
         };
+        Some(
+            list.iter()
+                .filter_map(|item| match item {
+                    Value::String(s) => Some(s.clone()),
+                    Value::Object(obj) => obj.get("step").or_else(|| obj.get("description"))?.as_str().map(str::to_string),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        )
+    });
+    let steps = json_steps.unwrap_or_else(|| {
+        raw.lines()
+            .map(str::trim)
+            .filter(|l| l.starts_with(|c: char| c.is_ascii_digit()) || l.starts_with("- "))
+            .map(|l| match l.strip_prefix("- ") {
+                Some(rest) => rest.to_string(),
+                None => l.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches(['.', ')']).to_string(),
+            })
+            .collect()
+    });
+    steps
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .take(PLAN_MAX_STEPS)
+        .collect()
+}
 
-        match decision {
-            Decision::Retrieve { query } => match run_retrieve(cfg, &query) {
-                Ok(ctx) => state.append_context(format!("RAG retrieve for query: {}\n{}", query, ctx)),
-                Err(err) => state.append_tool(format!("RAG retrieve error: {}", err)),
-            },
-            Decision::ToolCall { name, args } => {
-                if is_rag_only_state(state) {
-                    let fallback_query = latest_user_query(state).unwrap_or_else(|| name.clone());
-                    match run_retrieve(cfg, &fallback_query) {
-                        Ok(ctx) => state.append_context(format!(
-                            "RAG retrieve fallback (RAG-only mode) for query: {}\n{}",
-                            fallback_query, ctx
-                        )),
-                        Err(err) => state.append_tool(format!(
-                            "RAG retrieve fallback error (RAG-only mode): {}",
-                            err
-                        )),
-                    }
-                    state.current_step += 1;
-                    continue;
+fn numbered(steps: &[String]) -> String {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("{}. {}", i + 1, s))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn synthesize_plan(
+    state: &mut AgentState,
+    cfg: &Config,
+    question: &str,
+    steps: &[String],
+    findings: &[String],
+) -> Result<String, String> {
+    let context = state
+        .context_text()
+        .lines()
+        .filter(|l| !l.starts_with(CONTEXT_LINE_MARKER))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let results = steps
+        .iter()
+        .zip(findings)
+        .enumerate()
+        .map(|(i, (step, finding))| format!("{}. {}\n   Finding: {}", i + 1, step, finding))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let messages = vec![
+        Message {
+            role: "system".to_string(),
+            content: answer_system_prompt(cfg),
+        },
+        Message {
+            role: "user".to_string(),
+            content: format!(
+                "Answer the question using the plan results and context below. Cover every part of the question.\n\nPlan results:\n{}\n\nContext:\n{}\n\nQuestion: {}\n\nReturn only the final answer in plain text. Do not return JSON.",
+                results, context, question
+            ),
+        },
+    ];
+    state.last_prompt = messages.clone();
+    let asked = Instant::now();
+    let answer = generate_best_of(cfg, &messages)?;
+    state.trace("chat", json!({ "response": answer, "latency_ms": elapsed_ms(asked) }));
+    if answer.trim().is_empty() {
+        return Err("Model returned an empty answer for the plan".to_string());
+    }
+    Ok(answer)
+}
+
+/// One controller decision and its action. `Some` carries the answer of a
+/// `final` action; every other action advances `current_step`.
+// One step, followed by the tokens its model calls used.
+fn agent_step(state: &mut AgentState, cfg: &Config, mcp: &McpClient) -> Result<Option<String>, String> {
+    let step = state.current_step;
+    let before = current_usage();
+    let result = run_step(state, cfg, mcp);
+    let total = current_usage();
+    let used = total.since(&before);
+    if used.calls > 0 {
+        state.emit(AgentEvent::TokensUsed { step, used, total });
+    }
+    result
+}
+
+fn run_step(state: &mut AgentState, cfg: &Config, mcp: &McpClient) -> Result<Option<String>, String> {
+    state.compact_memory(cfg);
+    state.last_prompt = state.conversation.clone();
+    state.trace_messages();
+    let asked = Instant::now();
+    let call_cfg = cfg.clone();
+    let messages = state.conversation.clone();
+    let mut raw = match call_with_timeout(state.call_limit(), move || {
+        if call_cfg.agent_json_schema {
+            generate_json_schema(&call_cfg, &messages, &decision_schema())
+        } else {
+            generate_json(&call_cfg, &messages)
+        }
+    }) {
+        Ok(raw) => {
+            state.trace("json", json!({ "response": raw, "latency_ms": elapsed_ms(asked) }));
+            raw
+        }
+        Err(err) => {
+            state.emit(AgentEvent::Error { step: state.current_step, message: err.clone() });
+            return Err(err);
+        }
+    };
+    let parsed = match parse_decision(&raw) {
+        Ok(d) => Ok(d),
+        Err(err) => repair_decision(state, cfg, &raw, err).map(|(d, repaired)| {
+            raw = repaired;
+            d
+        }),
+    };
+    let decision = match parsed {
+        Ok(d) => d,
+        Err(err) => {
+            state.emit(AgentEvent::Error {
+                step: state.current_step,
+                message: format!("invalid controller JSON: {}", err),
+            });
+            state.append_system(format!(
+                "Invalid controller JSON output: {}. Return valid JSON with one action and required fields.",
+                err
+            ));
+            state.current_step += 1;
+            return Ok(None);
+        }
+    };
+
+    state.emit(AgentEvent::DecisionMade {
+        step: state.current_step,
+        action: decision_summary(&decision),
+    });
+
+    match decision {
+        Decision::Retrieve { .. } if state.mode == AgentMode::McpOnly => state.append_system(
+            "MCP-only mode is on: retrieve is disabled. Use tool, prompt, resource, or final.".to_string(),
+        ),
+        Decision::Retrieve { query } => match run_retrieve(state, cfg, &query) {
+            Ok(ctx) => append_retrieved(state, format!("RAG retrieve for query: {}", query), ctx),
+            Err(err) => state.append_tool(format!("RAG retrieve error: {}", err)),
+        },
+        Decision::ToolCall { name, args } => {
+            if state.mode == AgentMode::RagOnly {
+                let fallback_query = latest_user_query(state).unwrap_or_else(|| name.clone());
+                match run_retrieve(state, cfg, &fallback_query) {
+                    Ok(ctx) => append_retrieved(
+                        state,
+                        format!("RAG retrieve fallback (RAG-only mode) for query: {}", fallback_query),
+                        ctx,
+                    ),
+                    Err(err) => state.append_tool(format!(
+                        "RAG retrieve fallback error (RAG-only mode): {}",
+                        err
+                    )),
                 }
-                if !mcp.is_enabled() {
-                    state.append_system(
-                        "MCP is unavailable in this session. Choose only: retrieve or final."
-                            .to_string(),
+                state.current_step += 1;
+                return Ok(None);
+            }
+            let local = state.local_tools.has(&name);
+            if !local && !mcp.is_enabled() {
+                state.append_system(
+                    "MCP is unavailable in this session. Choose only: retrieve or final."
+                        .to_string(),
+                );
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if !local && !mcp.tools_allowed() {
+                state.append_system(
+                    "Safe mode is on: MCP tool calls are disabled. Use retrieve, prompt, resource, or final."
+                        .to_string(),
+                );
+                state.current_step += 1;
+                return Ok(None);
+            }
+            let down = if local { None } else { mcp_down_note(&mcp, &name) };
+            if let Some(note) = down {
+                state.emit(AgentEvent::Error { step: state.current_step, message: note.clone() });
+                state.append_system(note);
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if !local && !mcp.tool_permitted(&name) {
+                let message = mcp.tool_filter().blocked_message(&name);
+                state.emit(AgentEvent::Error { step: state.current_step, message: message.clone() });
+                state.append_system(message);
+                state.current_step += 1;
+                return Ok(None);
+            }
+            let normalized_args = normalize_tool_args(&name, args, state);
+            if let Some(schema) = state.tool_schemas.get(&name) {
+                if let Err(err) = validate_tool_args(schema, &normalized_args) {
+                    let message = format!(
+                        "Arguments for tool {} do not match its input schema: {}. Schema: {}. Call it again with arguments that match.",
+                        name, err, schema
                     );
+                    state.emit(AgentEvent::Error { step: state.current_step, message: err });
+                    state.append_system(message);
                     state.current_step += 1;
-                    continue;
+                    return Ok(None);
                 }
-                let normalized_args = normalize_tool_args(&name, args, state);
-                let result = mcp
-                    .call_tool(&name, normalized_args)
-                    .map(|v| v.to_string())
-                    .unwrap_or_else(|e| format!("Tool call failed for {}: {}", name, e));
-                state.append_tool(format!("Tool result [{}]: {}", name, result));
-            }
-            Decision::PromptCall { name, args } => {
-                if is_rag_only_state(state) {
-                    let fallback_query = latest_user_query(state).unwrap_or_else(|| name.clone());
-                    match run_retrieve(cfg, &fallback_query) {
-                        Ok(ctx) => state.append_context(format!(
-                            "RAG retrieve fallback (RAG-only mode) for query: {}\n{}",
-                            fallback_query, ctx
-                        )),
-                        Err(err) => state.append_tool(format!(
-                            "RAG retrieve fallback error (RAG-only mode): {}",
-                            err
-                        )),
-                    }
-                    state.current_step += 1;
-                    continue;
+            }
+            state.emit(AgentEvent::ToolCalled {
+                kind: if local { "local" } else { "tool" },
+                name: name.clone(),
+                args: normalized_args.clone(),
+            });
+            let called = Instant::now();
+            let result = if local {
+                let tools = state.local_tools.clone();
+                let name = name.clone();
+                call_with_timeout(state.call_limit(), move || tools.call(&name, &normalized_args))
+            } else {
+                let mcp = mcp.clone();
+                let name = name.clone();
+                call_with_timeout(state.call_limit(), move || mcp.call_tool(&name, normalized_args).map(|v| v.to_string()))
+            };
+            trace_tool_result(state, &name, called, &result);
+            state.emit(tool_result_event(&name, &result));
+            let result = result.unwrap_or_else(|e| format!("Tool call failed for {}: {}", name, e));
+            state.append_tool(format!("Tool result [{}]: {}", name, result));
+        }
+        Decision::PromptCall { name, args } => {
+            if state.mode == AgentMode::RagOnly {
+                let fallback_query = latest_user_query(state).unwrap_or_else(|| name.clone());
+                match run_retrieve(state, cfg, &fallback_query) {
+                    Ok(ctx) => append_retrieved(
+                        state,
+                        format!("RAG retrieve fallback (RAG-only mode) for query: {}", fallback_query),
+                        ctx,
+                    ),
+                    Err(err) => state.append_tool(format!(
+                        "RAG retrieve fallback error (RAG-only mode): {}",
+                        err
+                    )),
                 }
-                if !mcp.is_enabled() {
-                    state.append_system(
-                        "MCP is unavailable in this session. Choose only: retrieve or final."
-                            .to_string(),
-                    );
-                    state.current_step += 1;
-                    continue;
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if !mcp.is_enabled() {
+                state.append_system(
+                    "MCP is unavailable in this session. Choose only: retrieve or final."
+                        .to_string(),
+                );
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if let Some(note) = mcp_down_note(&mcp, &name) {
+                state.emit(AgentEvent::Error { step: state.current_step, message: note.clone() });
+                state.append_system(note);
+                state.current_step += 1;
+                return Ok(None);
+            }
+            state.emit(AgentEvent::ToolCalled {
+                kind: "prompt",
+                name: name.clone(),
+                args: args.clone(),
+            });
+            let called = Instant::now();
+            let result = {
+                let mcp = mcp.clone();
+                let name = name.clone();
+                call_with_timeout(state.call_limit(), move || mcp.get_prompt(&name, args).map(|v| v.to_string()))
+            };
+            trace_tool_result(state, &name, called, &result);
+            state.emit(tool_result_event(&name, &result));
+            let result = result.unwrap_or_else(|e| format!("Prompt fetch failed for {}: {}", name, e));
+            state.append_tool(format!("Prompt result [{}]: {}", name, result));
+        }
+        Decision::ResourceRead { uri } => {
+            if state.mode == AgentMode::RagOnly {
+                let fallback_query = latest_user_query(state).unwrap_or_else(|| uri.clone());
+                match run_retrieve(state, cfg, &fallback_query) {
+                    Ok(ctx) => append_retrieved(
+                        state,
+                        format!("RAG retrieve fallback (RAG-only mode) for query: {}", fallback_query),
+                        ctx,
+                    ),
+                    Err(err) => state.append_tool(format!(
+                        "RAG retrieve fallback error (RAG-only mode): {}",
+                        err
+                    )),
                 }
-                let result = mcp
-                    .get_prompt(&name, args)
-                    .map(|v| v.to_string())
-                    .unwrap_or_else(|e| format!("Prompt fetch failed for {}: {}", name, e));
-                state.append_tool(format!("Prompt result [{}]: {}", name, result));
-            }
-            Decision::ResourceRead { uri } => {
-                if is_rag_only_state(state) {
-                    let fallback_query = latest_user_query(state).unwrap_or_else(|| uri.clone());
-                    match run_retrieve(cfg, &fallback_query) {
-                        Ok(ctx) => state.append_context(format!(
-                            "RAG retrieve fallback (RAG-only mode) for query: {}\n{}",
-                            fallback_query, ctx
-                        )),
-                        Err(err) => state.append_tool(format!(
-                            "RAG retrieve fallback error (RAG-only mode): {}",
-                            err
-                        )),
-                    }
-                    state.current_step += 1;
-                    continue;
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if !mcp.is_enabled() && state.mode == AgentMode::McpOnly {
+                state.append_system("MCP is unavailable in this session. Choose only: final.".to_string());
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if !mcp.is_enabled() {
+                let fallback_query = latest_user_query(state).unwrap_or_else(|| uri.clone());
+                match run_retrieve(state, cfg, &fallback_query) {
+                    Ok(ctx) => append_retrieved(
+                        state,
+                        format!("RAG retrieve fallback (MCP disabled) for query: {}", fallback_query),
+                        ctx,
+                    ),
+                    Err(err) => state.append_tool(format!(
+                        "RAG retrieve fallback error (MCP disabled): {}",
+                        err
+                    )),
                 }
-                if !mcp.is_enabled() {
+                state.current_step += 1;
+                return Ok(None);
+            }
+            if let Some(note) = mcp_down_note(&mcp, &uri) {
+                state.emit(AgentEvent::Error { step: state.current_step, message: note.clone() });
+                state.append_system(note);
+                state.current_step += 1;
+                return Ok(None);
+            }
+            state.emit(AgentEvent::ToolCalled {
+                kind: "resource",
+                name: uri.clone(),
+                args: Value::Null,
+            });
+            let called = Instant::now();
+            let result = {
+                let mcp = mcp.clone();
+                let uri = uri.clone();
+                call_with_timeout(state.call_limit(), move || mcp.read_resource(&uri))
+            };
+            let outcome = result.as_ref().map(Value::to_string).map_err(String::clone);
+            trace_tool_result(state, &uri, called, &outcome);
+            state.emit(tool_result_event(&uri, &outcome));
+            match result {
+                Ok(value) => {
+                    state.append_tool(format!("Resource result [{}]: {}", uri, value));
+                }
+                Err(err) if state.mode == AgentMode::McpOnly => {
+                    state.append_tool(format!("Resource read failed for {}: {}", uri, err));
+                }
+                Err(err) => {
+                    state.append_tool(format!("Resource read failed for {}: {}", uri, err));
                     let fallback_query = latest_user_query(state).unwrap_or_else(|| uri.clone());
-                    match run_retrieve(cfg, &fallback_query) {
-                        Ok(ctx) => state.append_context(format!(
-                            "RAG retrieve fallback (MCP disabled) for query: {}\n{}",
-                            fallback_query, ctx
+                    match run_retrieve(state, cfg, &fallback_query) {
+                        Ok(ctx) => append_retrieved(
+                            state,
+                            format!("RAG retrieve fallback (resource read failed) for query: {}", fallback_query),
+                            ctx,
+                        ),
+                        Err(retrieve_err) => state.append_tool(format!(
+                            "RAG retrieve fallback error (resource read failed): {}",
+                            retrieve_err
                         )),
-                        Err(err) => state.append_tool(format!(
-                            "RAG retrieve fallback error (MCP disabled): {}",
-                            err
-                        )),
-                    }
-                    state.current_step += 1;
-                    continue;
-                }
-                match mcp.read_resource(&uri) {
-                    Ok(value) => {
-                        state.append_tool(format!("Resource result [{}]: {}", uri, value));
-                    }
-                    Err(err) => {
-                        state.append_tool(format!("Resource read failed for {}: {}", uri, err));
-                        let fallback_query = latest_user_query(state).unwrap_or_else(|| uri.clone());
-                        match run_retrieve(cfg, &fallback_query) {
-                            Ok(ctx) => state.append_context(format!(
-                                "RAG retrieve fallback (resource read failed) for query: {}\n{}",
-                                fallback_query, ctx
-                            )),
-                            Err(retrieve_err) => state.append_tool(format!(
-                                "RAG retrieve fallback error (resource read failed): {}",
-                                retrieve_err
-                            )),
-                        }
                     }
                 }
             }
-            Decision::FinalAnswer(answer) => return Ok(answer),
         }
+        Decision::FinalAnswer(answer) => {
+            // The answer stands on its own; malformed extras are dropped.
+            state.final_details = parse_final_details(&raw)
+                .ok()
+                .map(|mut details| {
+                    details.sources_used.retain(|&n| n <= state.hits.len());
+                    details
+                })
+                .filter(|details| !details.is_empty());
+            return Ok(Some(answer));
+        }
+    }
 
-        state.current_step += 1;
+    state.current_step += 1;
+    Ok(None)
+}
+
+fn decision_summary(decision: &Decision) -> String {
+    match decision {
+        Decision::Retrieve { query } => format!("retrieve {:?}", query),
+        Decision::ToolCall { name, .. } => format!("tool {}", name),
+        Decision::PromptCall { name, .. } => format!("prompt {}", name),
+        Decision::ResourceRead { uri } => format!("resource {}", uri),
+        Decision::FinalAnswer(_) => "final".to_string(),
     }
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
 
-    force_final_answer(state, cfg).or_else(|fallback_err| {
-        Err(format!(
-            "Max steps exceeded (limit: {}) before final answer; fallback generation failed: {}",
-            state.max_steps, fallback_err
-        ))
+fn trace_tool_result(state: &AgentState, name: &str, started: Instant, result: &Result<String, String>) {
+    let (ok, output) = match result {
+        Ok(output) => (true, output),
+        Err(err) => (false, err),
+    };
+    state.trace(
+        "tool_result",
+        json!({ "name": name, "ok": ok, "result": output, "latency_ms": elapsed_ms(started) }),
+    );
+}
+
+fn tool_result_event(name: &str, result: &Result<String, String>) -> AgentEvent {
+    match result {
+        Ok(output) => AgentEvent::ToolResult {
+            name: name.to_string(),
+            ok: true,
+            output: preview(output),
+        },
+        Err(err) => AgentEvent::ToolResult {
+            name: name.to_string(),
+            ok: false,
+            output: preview(err),
+        },
+    }
+}
+
+fn append_retrieved(state: &mut AgentState, header: String, ctx: Retrieved) {
+    state.hits.extend(ctx.hits);
+    state.rerank_time = add_rerank_time(state.rerank_time, ctx.rerank_time);
+    if state.rerank_error.is_none() {
+        state.rerank_error = ctx.rerank_error;
+    }
+    if ctx.broadened.is_some() && state.broadened.is_none() {
+        state.broadened = ctx.broadened;
+    }
+    state.append_context_with_display(
+        format!("{}\n{}", header, ctx.text),
+        format!("{}\n{}", header, ctx.display),
+    );
+}
+
+struct Retrieved {
+    text: String,
+    display: String,
+    hits: Vec<Hit>,
+    broadened: Option<String>,
+    rerank_time: Option<Duration>,
+    rerank_error: Option<String>,
+}
+
+fn run_retrieve(state: &AgentState, cfg: &Config, query: &str) -> Result<Retrieved, String> {
+    let started = Instant::now();
+    let found = match block_on(retrieval_broadening_async(cfg, query)) {
+        Ok(found) => found,
+        Err(err) => {
+            state.emit(AgentEvent::Error {
+                step: state.current_step,
+                message: format!("retrieve failed: {}", err),
+            });
+            return Err(err);
+        }
+    };
+    let Retrieval { hits, broadened, rerank_time, rerank_error } = found;
+    state.trace(
+        "retrieve",
+        json!({
+            "query": query,
+            "hits": hits
+                .iter()
+                .map(|h| json!({
+                    "path": h.payload.as_ref().and_then(|p| p.path.clone()),
+                    "score": h.score,
+                    "rerank_score": h.rerank_score,
+                }))
+                .collect::<Vec<_>>(),
+            "latency_ms": elapsed_ms(started),
+            "rerank_ms": rerank_time.map(|t| t.as_millis() as u64),
+            "rerank_error": rerank_error.as_deref(),
+        }),
+    );
+    state.emit(AgentEvent::RetrieveDone {
+        query: query.to_string(),
+        hits: hits.len(),
+    });
+    if hits.is_empty() {
+        let text = format!(
+            "{}\nNothing relevant was retrieved. Do not invent context; say so in the final answer{}.",
+            no_relevant_context_message(cfg),
+            if cfg.empty_context_fallback { " or answer from general knowledge" } else { "" }
+        );
+        return Ok(Retrieved {
+            display: text.clone(),
+            text,
+            hits: Vec::new(),
+            broadened,
+            rerank_time,
+            rerank_error,
+        });
+    }
+    let hits = fit_hits_to_budget(&hits, context_budget_tokens(cfg, query));
+    let (text, display) = match &broadened {
+        Some(note) => (
+            format!("{}\n{}", note, format_context_from_hits(&hits)),
+            format!("{}\n{}", note, format_display_context_from_hits(&hits)),
+        ),
+        None => (format_context_from_hits(&hits), format_display_context_from_hits(&hits)),
+    };
+    Ok(Retrieved {
+        text,
+        display,
+        hits,
+        broadened,
+        rerank_time,
+        rerank_error,
     })
 }
 
-fn run_retrieve(cfg: &Config, query: &str) -> Result<String, String> {
-    let query_vec = embed_query(cfg, query)?;
-    let hits = retrieve_top(cfg, &query_vec)?;
-    Ok(format_context_from_hits(&hits))
+// Remove what `server` offers from `caps`; the only server's name is empty.
+fn drop_server_capabilities(caps: &mut McpCapabilities, server: &str) {
+    let prefix = format!("{}/", server);
+    let keep = |name: &str| !server.is_empty() && !name.starts_with(&prefix);
+    caps.tools.retain(|n| keep(n));
+    caps.tool_specs.retain(|t| keep(&t.name));
+    caps.prompts.retain(|n| keep(n));
+    caps.resources.retain(|n| keep(n));
+}
+
+// Tells the model to stop using a server that is down instead of calling it.
+fn mcp_down_note(mcp: &McpClient, name: &str) -> Option<String> {
+    let reason = mcp.down_reason(name)?;
+    Some(format!(
+        "Not calling {}: its MCP server is down ({}). Use retrieve, other tools, or final instead.",
+        name, reason
+    ))
 }
 
-fn build_hybrid_system_prompt(cfg: &Config, caps: &McpCapabilities, mcp_enabled: bool) -> String {
+fn build_hybrid_system_prompt(
+    cfg: &Config,
+    caps: &McpCapabilities,
+    mcp_enabled: bool,
+    local_tools: &LocalToolRegistry,
+) -> String {
     let mut prompt = format!(
         "{}\n\nAvailable Tools:\n{}\n\nAvailable Prompts:\n{}\n\nAvailable Resources:\n{}",
         cfg.hybrid_system_prompt,
-        list_or_none(&caps.tools),
+        list_tools_or_none(caps),
         list_or_none(&caps.prompts),
         list_or_none(&caps.resources),
     );
 
+    let style = answer_style_instructions(cfg);
+    if !style.is_empty() {
+        prompt.push_str("\n\nFor the final answer:\n");
+        prompt.push_str(&style);
+    }
+
     if !caps.diagnostics.is_empty() {
         prompt.push_str("\n\nMCP Diagnostics:\n");
         prompt.push_str(&caps.diagnostics.join("\n"));
     }
 
-    if !mcp_enabled {
+    prompt.push_str(
+        "\n\nThe final action may also include \"confidence\" (a number from 0 to 1), \"sources_used\" (the [n] numbers of the context passages you relied on), and \"follow_ups\" (up to 3 short follow-up questions).",
+    );
+
+    if cfg.safe_mode && local_tools.is_empty() {
+        prompt.push_str("\n\nSafe mode is on: do not choose the tool action.");
+    } else if cfg.safe_mode {
+        prompt.push_str("\n\nSafe mode is on: the tool action may only use the built-in and registered tools, not MCP tools.");
+    }
+
+    if !mcp_enabled && !local_tools.is_empty() {
+        prompt.push_str(
+            "\n\nMCP is currently unavailable. Do not choose prompt/resource. Use retrieve, the tools listed above, or final.",
+        );
+    } else if !mcp_enabled {
         prompt.push_str(
             "\n\nMCP is currently unavailable. Do not choose tool/prompt/resource. Use retrieve and final only.",
         );
@@ -282,6 +1320,28 @@ fn build_hybrid_system_prompt(cfg: &Config, caps: &McpCapabilities, mcp_enabled:
     prompt
 }
 
+// Each tool with its description and input schema, so the model can build
+// arguments of the right shape.
+fn list_tools_or_none(caps: &McpCapabilities) -> String {
+    if caps.tool_specs.is_empty() {
+        return list_or_none(&caps.tools);
+    }
+    caps.tool_specs
+        .iter()
+        .map(|tool| {
+            let mut line = format!("- {}", tool.name);
+            if !tool.description.trim().is_empty() {
+                line.push_str(&format!(": {}", tool.description.trim()));
+            }
+            if !tool.input_schema.is_null() {
+                line.push_str(&format!("\n  arguments schema: {}", tool.input_schema));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn list_or_none(items: &[String]) -> String {
     if items.is_empty() {
         "- (none)".to_string()
@@ -294,6 +1354,123 @@ fn list_or_none(items: &[String]) -> String {
     }
 }
 
+/// `RAG_AGENT_MAX_REPAIRS`: ask again for a reply that failed to parse,
+/// showing the model its output and the error, with the reply constrained
+/// to `decision_schema`. The exchange is not added to the conversation.
+/// Returns the decision and the reply it came from, or the last error.
+fn repair_decision(state: &mut AgentState, cfg: &Config, raw: &str, err: String) -> Result<(Decision, String), String> {
+    let mut invalid = raw.to_string();
+    let mut err = err;
+    for attempt in 1..=cfg.agent_max_repairs {
+        if state.deadline.expired() || state.budget.expired() {
+            break;
+        }
+        state.repairs += 1;
+        state.emit(AgentEvent::RepairAttempted { step: state.current_step, attempt, error: err.clone() });
+        let mut messages = state.conversation.clone();
+        messages.push(Message {
+            role: "assistant".to_string(),
+            content: invalid.clone(),
+        });
+        messages.push(Message {
+            role: "system".to_string(),
+            content: format!(
+                "That reply is not a valid action: {}. Reply again with only one JSON object that matches this schema, with the fields its action requires: {}",
+                err,
+                decision_schema()
+            ),
+        });
+        let asked = Instant::now();
+        let call_cfg = cfg.clone();
+        let reply = call_with_timeout(state.call_limit(), move || {
+            generate_json_schema(&call_cfg, &messages, &decision_schema())
+        })?;
+        state.trace("json", json!({ "response": reply, "latency_ms": elapsed_ms(asked), "repair": attempt }));
+        match parse_decision(&reply) {
+            Ok(decision) => return Ok((decision, reply)),
+            Err(next) => {
+                err = next;
+                invalid = reply;
+            }
+        }
+    }
+    Err(err)
+}
+
+/// JSON schema of one controller action, sent as Ollama's structured output
+/// `format` for controller replies (`RAG_AGENT_JSON_SCHEMA`) and repairs.
+/// The top level lists every field `parse_decision` reads; `anyOf` holds one
+/// alternative per `Decision` variant with the fields that variant requires,
+/// so constrained decoding cannot produce an unknown action or leave out a
+/// required field.
+pub fn decision_schema() -> Value {
+    let final_fields = [
+        ("answer", json!({ "type": "string", "minLength": 1 })),
+        ("confidence", json!({ "type": "number", "minimum": 0, "maximum": 1 })),
+        ("sources_used", json!({ "type": "array", "items": { "type": "integer", "minimum": 1 } })),
+        ("follow_ups", json!({ "type": "array", "items": { "type": "string" } })),
+    ];
+    let mut properties = Map::new();
+    properties.insert(
+        "action".to_string(),
+        json!({ "type": "string", "enum": ["retrieve", "tool", "prompt", "resource", "final"] }),
+    );
+    properties.insert("name".to_string(), json!({ "type": "string" }));
+    properties.insert("arguments".to_string(), json!({ "type": "object" }));
+    properties.insert("uri".to_string(), json!({ "type": "string" }));
+    let mut final_properties = Map::new();
+    final_properties.insert("action".to_string(), json!({ "const": "final" }));
+    for (field, schema) in final_fields {
+        properties.insert(field.to_string(), schema.clone());
+        final_properties.insert(field.to_string(), schema);
+    }
+    let named = |action: &str| {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": { "const": action },
+                "name": { "type": "string", "minLength": 1 },
+                "arguments": { "type": "object" }
+            },
+            "required": ["action", "name", "arguments"]
+        })
+    };
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": ["action"],
+        "anyOf": [
+            {
+                "type": "object",
+                "properties": {
+                    "action": { "const": "retrieve" },
+                    "arguments": {
+                        "type": "object",
+                        "properties": { "query": { "type": "string", "minLength": 1 } },
+                        "required": ["query"]
+                    }
+                },
+                "required": ["action", "arguments"]
+            },
+            named("tool"),
+            named("prompt"),
+            {
+                "type": "object",
+                "properties": {
+                    "action": { "const": "resource" },
+                    "uri": { "type": "string", "minLength": 1 }
+                },
+                "required": ["action", "uri"]
+            },
+            {
+                "type": "object",
+                "properties": final_properties,
+                "required": ["action", "answer"]
+            }
+        ]
+    })
+}
+
 pub fn parse_decision(raw: &str) -> Result<Decision, String> {
     let data = parse_json_object(raw)?;
     let env: DecisionEnvelope = serde_json::from_value(data).map_err(|e| e.to_string())?;
@@ -393,6 +1570,53 @@ pub fn parse_decision(raw: &str) -> Result<Decision, String> {
     }
 }
 
+const MAX_FOLLOW_UPS: usize = 3;
+
+/// Validate the optional structured fields of a `final` decision, read from
+/// the top level or from `arguments`. Missing fields are fine; a field of the
+/// wrong type or a confidence outside 0..=1 is an error.
+pub fn parse_final_details(raw: &str) -> Result<FinalDetails, String> {
+    let data = parse_json_object(raw)?;
+    let field = |name: &str| {
+        data.get(name)
+            .or_else(|| data.get("arguments").and_then(|a| a.get(name)))
+            .filter(|v| !v.is_null())
+    };
+    let mut details = FinalDetails::default();
+    if let Some(value) = field("confidence") {
+        let confidence = value
+            .as_f64()
+            .filter(|c| (0.0..=1.0).contains(c))
+            .ok_or_else(|| format!("confidence must be a number between 0 and 1, got {}", value))?;
+        details.confidence = Some(confidence as f32);
+    }
+    if let Some(value) = field("sources_used") {
+        let ids = value.as_array().ok_or("sources_used must be an array of passage numbers")?;
+        for id in ids {
+            let n = id
+                .as_u64()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("sources_used entries must be positive integers, got {}", id))?;
+            if !details.sources_used.contains(&(n as usize)) {
+                details.sources_used.push(n as usize);
+            }
+        }
+    }
+    if let Some(value) = field("follow_ups") {
+        let items = value.as_array().ok_or("follow_ups must be an array of strings")?;
+        for item in items {
+            let question = item
+                .as_str()
+                .ok_or_else(|| format!("follow_ups entries must be strings, got {}", item))?
+                .trim();
+            if !question.is_empty() && details.follow_ups.len() < MAX_FOLLOW_UPS {
+                details.follow_ups.push(question.to_string());
+            }
+        }
+    }
+    Ok(details)
+}
+
 fn parse_json_object(raw: &str) -> Result<Value, String> {
     if let Ok(v) = serde_json::from_str::<Value>(raw) {
         return Ok(v);
@@ -498,29 +1722,19 @@ fn clean_city_candidate(raw: &str) -> String {
     out.trim().trim_matches('"').trim_matches('\'').to_string()
 }
 
-fn is_rag_only_query(question: &str) -> bool {
-    let q = question.to_ascii_lowercase();
-    q.contains("use rag")
-        || q.contains("rag only")
-        || q.contains("only rag")
-        || q.contains("do not use mcp")
-        || q.contains("don't use mcp")
-        || q.contains("without mcp")
-}
-
-fn is_rag_only_state(state: &AgentState) -> bool {
-    latest_user_query(state)
-        .map(|q| is_rag_only_query(&q))
-        .unwrap_or(false)
-}
-
-fn force_final_answer(state: &AgentState, cfg: &Config) -> Result<String, String> {
+fn force_final_answer(state: &mut AgentState, cfg: &Config) -> Result<String, String> {
     let question = latest_user_query(state).unwrap_or_default();
-    let context = state.context_text();
+    // The context log holds display text; drop the surrounding-line annotations.
+    let context = state
+        .context_text()
+        .lines()
+        .filter(|l| !l.starts_with(CONTEXT_LINE_MARKER))
+        .collect::<Vec<_>>()
+        .join("\n");
     let messages = vec![
         Message {
             role: "system".to_string(),
-            content: cfg.system_prompt.clone(),
+            content: answer_system_prompt(cfg),
         },
         Message {
             role: "user".to_string(),
@@ -530,7 +1744,18 @@ fn force_final_answer(state: &AgentState, cfg: &Config) -> Result<String, String
             ),
         },
     ];
-    let answer = generate_answer(cfg, &messages)?;
+    state.last_prompt = messages.clone();
+    state.trace_messages();
+    for message in &messages {
+        state.trace("message", json!({ "role": message.role, "content": message.content }));
+    }
+    let asked = Instant::now();
+    // Runs after the agent budget is spent, so only the step timeout and
+    // the query deadline bound it.
+    let limit = shortest(&[state.step_timeout, state.deadline.remaining()]);
+    let call_cfg = cfg.clone();
+    let answer = call_with_timeout(limit, move || generate_best_of(&call_cfg, &messages))?;
+    state.trace("chat", json!({ "response": answer, "latency_ms": elapsed_ms(asked) }));
     if answer.trim().is_empty() {
         return Err("Model returned an empty fallback final answer".to_string());
     }
@@ -1,81 +1,369 @@
 use mcp_client_rust::client::MCPClient;
-use mcp_client_rust::transport::{HttpSSETransport, StdioTransport, Transport};
+use mcp_client_rust::transport::{HttpSSETransport, Transport};
 use mcp_client_rust::types::{ClientInfo, ContentItem, MessageContent, ToolResultContent};
 use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::runtime::Builder;
 
 use crate::config::Config;
+use crate::mcp_auth::McpAuth;
+use crate::mcp_health::{McpConnection, connect_with_retries, http_connection};
+use crate::mcp_http::McpHttpClient;
+use crate::mcp_servers::{McpServerSpec, load_mcp_servers};
+use crate::mcp_stdio::{
+    McpServerState, McpServerStatus, McpSupervisor, SupervisorOptions, supervisor_for, supervisor_status,
+};
+use crate::runtime::try_block_on;
+use crate::tool_filter::ToolFilter;
 
 #[derive(Clone, Debug)]
 pub struct McpCapabilities {
     pub tools: Vec<String>,
+    /// Description and JSON input schema of each tool in `tools`.
+    pub tool_specs: Vec<McpTool>,
     pub prompts: Vec<String>,
     pub resources: Vec<String>,
     pub diagnostics: Vec<String>,
 }
 
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct McpTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
+
+impl McpTool {
+    /// Read a `tools/list` entry. Servers send `inputSchema`; the
+    /// snake_case spelling is accepted as well.
+    pub fn from_value(value: &Value) -> Self {
+        let text = |key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+        Self {
+            name: text("name"),
+            description: text("description"),
+            input_schema: value
+                .get("inputSchema")
+                .or_else(|| value.get("input_schema"))
+                .cloned()
+                .unwrap_or(Value::Null),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct McpClient {
     transport: McpTransport,
+    safe_mode: bool,
+    tool_filter: ToolFilter,
+    supervision: SupervisorOptions,
+    // Why `MCP_CONFIG` could not be used, reported in place of "not configured".
+    config_error: Option<String>,
 }
 
 #[derive(Clone, Debug)]
 enum McpTransport {
     Http { endpoint: String },
-    Stdio { command: String, args: Vec<String> },
+    /// An HTTP server that needs `MCP_AUTH_TOKEN` or another credential.
+    AuthHttp(McpHttpClient),
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    },
+    /// Several servers from `MCP_CONFIG`. Their tools, prompts, and
+    /// resources are named `server/name`, and calls go to that server.
+    Servers(Vec<(String, McpClient)>),
     Disabled,
 }
 
 impl McpClient {
+    /// The servers in `MCP_CONFIG` when it is set, else the one server
+    /// named by `MCP_URL` or `MCP_COMMAND`.
     pub fn from_config(cfg: &Config) -> Self {
-        if !cfg.mcp_url.trim().is_empty() {
-            return Self {
-                transport: McpTransport::Http {
-                    endpoint: cfg.mcp_url.clone(),
-                },
-            };
+        let mut config_error = None;
+        let transport = if !cfg.mcp_config.trim().is_empty() {
+            match load_mcp_servers(cfg.mcp_config.trim()) {
+                Ok(servers) if servers.len() == 1 => Self::server_transport(cfg, servers[0].spec.clone()),
+                Ok(servers) if servers.is_empty() => McpTransport::Disabled,
+                Ok(servers) => McpTransport::Servers(
+                    servers
+                        .into_iter()
+                        .map(|server| {
+                            let client = Self {
+                                transport: Self::server_transport(cfg, server.spec),
+                                safe_mode: cfg.safe_mode,
+                                // The combined client filters the qualified names.
+                                tool_filter: ToolFilter::default(),
+                                supervision: SupervisorOptions::from_config(cfg),
+                                config_error: None,
+                            };
+                            (server.name, client)
+                        })
+                        .collect(),
+                ),
+                Err(err) => {
+                    config_error = Some(err);
+                    McpTransport::Disabled
+                }
+            }
+        } else if !cfg.mcp_url.trim().is_empty() && !cfg.mcp_auth.is_none() {
+            McpTransport::AuthHttp(McpHttpClient::new(cfg, &cfg.mcp_url, cfg.mcp_auth.clone()))
+        } else if !cfg.mcp_url.trim().is_empty() {
+            McpTransport::Http {
+                endpoint: cfg.mcp_url.clone(),
+            }
+        } else if !cfg.mcp_command.trim().is_empty() {
+            McpTransport::Stdio {
+                command: cfg.mcp_command.clone(),
+                args: cfg.mcp_args.clone(),
+                env: Vec::new(),
+            }
+        } else {
+            McpTransport::Disabled
+        };
+        Self {
+            transport,
+            safe_mode: cfg.safe_mode,
+            tool_filter: ToolFilter::from_config(cfg),
+            supervision: SupervisorOptions::from_config(cfg),
+            config_error,
         }
-        if !cfg.mcp_command.trim().is_empty() {
-            return Self {
-                transport: McpTransport::Stdio {
-                    command: cfg.mcp_command.clone(),
-                    args: cfg.mcp_args.clone(),
+    }
+
+    fn server_transport(cfg: &Config, spec: McpServerSpec) -> McpTransport {
+        match spec {
+            McpServerSpec::Stdio { command, args, env } => McpTransport::Stdio { command, args, env },
+            McpServerSpec::Http { url, auth } if auth.is_none() => McpTransport::Http { endpoint: url },
+            McpServerSpec::Http { url, auth } => McpTransport::AuthHttp(McpHttpClient::new(cfg, &url, auth)),
+        }
+    }
+
+    /// How the stdio server is doing, once it has been used in this
+    /// process. `None` for HTTP servers and before the first call. With
+    /// several servers, the first one in trouble (or else the first used).
+    pub fn server_status(&self) -> Option<McpServerStatus> {
+        match &self.transport {
+            McpTransport::Stdio { command, args, env } => supervisor_status(command, args, env),
+            McpTransport::Servers(servers) => {
+                let statuses: Vec<McpServerStatus> = servers.iter().filter_map(|(_, c)| c.server_status()).collect();
+                let troubled = statuses.iter().position(|s| s.summary().is_some());
+                statuses.into_iter().nth(troubled.unwrap_or(0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the server can be reached: the supervisor's view of a stdio
+    /// server, or how the last connections to an HTTP server went.
+    pub fn connection(&self) -> McpConnection {
+        match &self.transport {
+            McpTransport::Stdio { command, args, env } => match supervisor_status(command, args, env) {
+                None => McpConnection::Unknown,
+                Some(status) => match status.state {
+                    McpServerState::NotStarted => McpConnection::Unknown,
+                    McpServerState::Running => McpConnection::Up,
+                    McpServerState::Exited => {
+                        McpConnection::Reconnecting(status.last_exit.unwrap_or_else(|| "server exited".to_string()))
+                    }
+                    McpServerState::Failed => McpConnection::Down(format!(
+                        "gave up after {} restarts, last exit: {}",
+                        status.restarts,
+                        status.last_exit.as_deref().unwrap_or("unknown")
+                    )),
                 },
-            };
+            },
+            McpTransport::Http { .. } | McpTransport::AuthHttp(_) => {
+                http_connection(&self.server_identity().unwrap_or_default(), self.supervision)
+            }
+            McpTransport::Servers(_) | McpTransport::Disabled => McpConnection::Unknown,
         }
-        Self {
-            transport: McpTransport::Disabled,
+    }
+
+    /// Each server's connection, by name; the only server's name is empty.
+    pub fn connections(&self) -> Vec<(String, McpConnection)> {
+        match &self.transport {
+            McpTransport::Servers(servers) => {
+                servers.iter().map(|(name, client)| (name.clone(), client.connection())).collect()
+            }
+            McpTransport::Disabled => Vec::new(),
+            _ => vec![(String::new(), self.connection())],
+        }
+    }
+
+    /// Why the server behind tool, prompt, or resource `name` should not be
+    /// called right now, if it is down.
+    pub fn down_reason(&self, name: &str) -> Option<String> {
+        let server = match &self.transport {
+            McpTransport::Servers(_) => self.route(name).ok()?.0,
+            _ => self,
+        };
+        match server.connection() {
+            McpConnection::Down(reason) => Some(reason),
+            _ => None,
         }
     }
 
+    /// The configured server names; empty unless `MCP_CONFIG` lists several.
+    pub fn server_names(&self) -> Vec<String> {
+        match &self.transport {
+            McpTransport::Servers(servers) => servers.iter().map(|(name, _)| name.clone()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The server a qualified `server/name` belongs to, and the bare name.
+    fn route<'a>(&self, qualified: &'a str) -> Result<(&McpClient, &'a str), String> {
+        let McpTransport::Servers(servers) = &self.transport else {
+            return Ok((self, qualified));
+        };
+        let (server, name) = qualified
+            .split_once('/')
+            .ok_or_else(|| format!("'{}' does not name a server; expected server/name", qualified))?;
+        servers
+            .iter()
+            .find(|(n, _)| n == server)
+            .map(|(_, client)| (client, name))
+            .ok_or_else(|| format!("Unknown MCP server '{}' in '{}'", server, qualified))
+    }
+
+    // Every server's items, with names qualified by the server's name.
+    fn gather<T>(
+        servers: &[(String, McpClient)],
+        list: impl Fn(&McpClient) -> Result<Vec<T>, String>,
+        qualify: impl Fn(&str, T) -> T,
+    ) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        for (name, client) in servers {
+            let listed = list(client).map_err(|e| format!("{}: {}", name, e))?;
+            items.extend(listed.into_iter().map(|item| qualify(name, item)));
+        }
+        Ok(items)
+    }
+
+    fn not_configured(&self) -> String {
+        self.config_error
+            .clone()
+            .unwrap_or_else(|| "MCP is not configured. Set MCP_URL, MCP_COMMAND, or MCP_CONFIG.".to_string())
+    }
+
+    // Servers aicli speaks JSON-RPC to itself. Stdio servers run once per
+    // process under a supervisor; HTTP servers are connected to per call.
+    fn rpc_server(&self) -> Option<RpcServer> {
+        match &self.transport {
+            McpTransport::Stdio { command, args, env } => {
+                Some(RpcServer::Stdio(supervisor_for(command, args, env, self.supervision)))
+            }
+            McpTransport::AuthHttp(client) => Some(RpcServer::Http(client.clone())),
+            _ => None,
+        }
+    }
+
+    /// Whether `MCP_TOOL_ALLOWLIST` / `MCP_TOOL_DENYLIST` let the agent use
+    /// server tool `name`.
+    pub fn tool_permitted(&self, name: &str) -> bool {
+        self.tool_filter.permits(name)
+    }
+
+    pub fn tool_filter(&self) -> &ToolFilter {
+        &self.tool_filter
+    }
+
+    pub fn tools_allowed(&self) -> bool {
+        self.is_enabled() && !self.safe_mode
+    }
+
     pub fn is_enabled(&self) -> bool {
         !matches!(self.transport, McpTransport::Disabled)
     }
 
+    /// What identifies the server for caching: the endpoint URL, or the
+    /// command line for stdio with the names of its `env` variables. Their
+    /// values are left out, since they often hold tokens. `None` when MCP
+    /// is disabled.
+    pub fn server_identity(&self) -> Option<String> {
+        match &self.transport {
+            McpTransport::Http { endpoint } => Some(format!("http {}", endpoint.trim())),
+            McpTransport::AuthHttp(client) => Some(format!("http {}", client.endpoint())),
+            McpTransport::Stdio { command, args, env } => {
+                let mut identity = format!("stdio {} {}", command.trim(), args.join(" "));
+                for (name, _) in env {
+                    identity.push_str(&format!(" {}", name));
+                }
+                Some(identity)
+            }
+            McpTransport::Servers(servers) => Some(
+                servers
+                    .iter()
+                    .filter_map(|(name, client)| Some(format!("{}: {}", name, client.server_identity()?)))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            McpTransport::Disabled => None,
+        }
+    }
+
     pub fn list_tools(&self) -> Result<Vec<String>, String> {
-        self.run_with_client(|rt, client| {
-            let tools = rt
-                .block_on(client.list_tools())
+        Ok(self.list_tool_specs()?.into_iter().map(|t| t.name).collect())
+    }
+
+    pub fn list_tool_specs(&self) -> Result<Vec<McpTool>, String> {
+        if let McpTransport::Servers(servers) = &self.transport {
+            return Self::gather(servers, McpClient::list_tool_specs, |server, tool| McpTool {
+                name: format!("{}/{}", server, tool.name),
+                ..tool
+            });
+        }
+        if let Some(server) = self.rpc_server() {
+            let tools = server
+                .list("tools/list", "tools")
+                .map_err(|e| format!("tools/list failed: {}", e))?;
+            return Ok(tools.iter().map(McpTool::from_value).collect());
+        }
+        self.run_with_client(|client| {
+            let tools = try_block_on(client.list_tools())?
                 .map_err(|e| format!("tools/list failed: {}", e))?;
-            Ok(tools.into_iter().map(|t| t.name).collect())
+            Ok(tools
+                .iter()
+                .map(|t| McpTool::from_value(&serde_json::to_value(t).unwrap_or_default()))
+                .collect())
         })
     }
 
     pub fn list_prompts(&self) -> Result<Vec<String>, String> {
-        self.run_with_client(|rt, client| {
-            let prompts = rt
-                .block_on(client.list_prompts())
+        if let McpTransport::Servers(servers) = &self.transport {
+            return Self::gather(servers, McpClient::list_prompts, |server, name| format!("{}/{}", server, name));
+        }
+        if let Some(server) = self.rpc_server() {
+            let prompts = server
+                .list("prompts/list", "prompts")
+                .map_err(|e| format!("prompts/list failed: {}", e))?;
+            return Ok(prompts.iter().filter_map(|p| p.get("name")?.as_str().map(str::to_string)).collect());
+        }
+        self.run_with_client(|client| {
+            let prompts = try_block_on(client.list_prompts())?
                 .map_err(|e| format!("prompts/list failed: {}", e))?;
             Ok(prompts.into_iter().map(|p| p.name).collect())
         })
     }
 
     pub fn list_resources(&self) -> Result<Vec<String>, String> {
-        self.run_with_client(|rt, client| {
-            let (resources, templates) = rt
-                .block_on(client.list_resources())
+        if let McpTransport::Servers(servers) = &self.transport {
+            return Self::gather(servers, McpClient::list_resources, |server, uri| format!("{}/{}", server, uri));
+        }
+        if let Some(server) = self.rpc_server() {
+            let resources = server
+                .list("resources/list", "resources")
+                .map_err(|e| format!("resources/list failed: {}", e))?;
+            let mut items: Vec<String> =
+                resources.iter().filter_map(|r| r.get("uri")?.as_str().map(str::to_string)).collect();
+            // Servers without templates answer "method not found".
+            if let Ok(templates) = server.list("resources/templates/list", "resourceTemplates") {
+                items.extend(templates.iter().filter_map(|t| t.get("uriTemplate")?.as_str().map(str::to_string)));
+            }
+            return Ok(items);
+        }
+        self.run_with_client(|client| {
+            let (resources, templates) = try_block_on(client.list_resources())?
                 .map_err(|e| format!("resources/list failed: {}", e))?;
 
             let mut items: Vec<String> = resources.into_iter().map(|r| r.uri).collect();
@@ -84,8 +372,40 @@ impl McpClient {
         })
     }
 
+    /// The server's tools, prompts, and resources, without the tools
+    /// `MCP_TOOL_ALLOWLIST` / `MCP_TOOL_DENYLIST` filter out.
     pub fn discover_capabilities(&self) -> McpCapabilities {
-        let (tools, tool_diag) = match self.list_tools() {
+        let mut caps = self.discover_all_capabilities();
+        self.tool_filter.apply(&mut caps);
+        caps
+    }
+
+    /// Everything the server offers, before tool filtering. With several
+    /// servers, one that fails is reported in the diagnostics and the
+    /// others are still used.
+    pub fn discover_all_capabilities(&self) -> McpCapabilities {
+        if let McpTransport::Servers(servers) = &self.transport {
+            let mut all = McpCapabilities {
+                tools: Vec::new(),
+                tool_specs: Vec::new(),
+                prompts: Vec::new(),
+                resources: Vec::new(),
+                diagnostics: Vec::new(),
+            };
+            for (server, client) in servers {
+                let caps = client.discover_all_capabilities();
+                all.tool_specs.extend(caps.tool_specs.into_iter().map(|tool| McpTool {
+                    name: format!("{}/{}", server, tool.name),
+                    ..tool
+                }));
+                all.prompts.extend(caps.prompts.iter().map(|p| format!("{}/{}", server, p)));
+                all.resources.extend(caps.resources.iter().map(|r| format!("{}/{}", server, r)));
+                all.diagnostics.extend(caps.diagnostics.iter().map(|d| format!("{}: {}", server, d)));
+            }
+            all.tools = all.tool_specs.iter().map(|t| t.name.clone()).collect();
+            return all;
+        }
+        let (tool_specs, tool_diag) = match self.list_tool_specs() {
             Ok(v) => (v, None),
             Err(e) => (Vec::new(), Some(format!("tools/list error: {}", e))),
         };
@@ -98,7 +418,10 @@ impl McpClient {
             Err(e) => (Vec::new(), Some(format!("resources/list error: {}", e))),
         };
 
-        let mut diagnostics = Vec::new();
+        let mut diagnostics: Vec<String> = self.config_error.iter().cloned().collect();
+        if let Some(status) = self.server_status() {
+            diagnostics.extend(status.diagnostics());
+        }
         if let Some(d) = tool_diag {
             diagnostics.push(d);
         }
@@ -110,7 +433,8 @@ impl McpClient {
         }
 
         McpCapabilities {
-            tools,
+            tools: tool_specs.iter().map(|t| t.name.clone()).collect(),
+            tool_specs,
             prompts,
             resources,
             diagnostics,
@@ -119,12 +443,30 @@ impl McpClient {
 
     pub fn call_tool(&self, name: &str, args: Value) -> Result<Value, String> {
         if !self.is_enabled() {
-            return Err("MCP is not configured. Set MCP_URL or MCP_COMMAND.".to_string());
+            return Err(self.not_configured());
+        }
+        if self.safe_mode {
+            return Err(format!("Safe mode is on: MCP tool '{}' was not called.", name));
+        }
+        if !self.tool_filter.permits(name) {
+            return Err(self.tool_filter.blocked_message(name));
+        }
+        if let McpTransport::Servers(_) = &self.transport {
+            let (server, name) = self.route(name)?;
+            return server.call_tool(name, args);
+        }
+        if let Some(server) = self.rpc_server() {
+            let result = server
+                .request("tools/call", json!({ "name": name, "arguments": args }))
+                .map_err(|e| format!("tools/call failed for {}: {}", name, e))?;
+            return Ok(json!({
+                "content": content_items(result.get("content")),
+                "isError": result.get("isError").and_then(Value::as_bool).unwrap_or(false)
+            }));
         }
 
-        self.run_with_client(move |rt, client| {
-            let result = rt
-                .block_on(client.call_tool(name, args))
+        self.run_with_client(move |client| {
+            let result = try_block_on(client.call_tool(name, args))?
                 .map_err(|e| format!("tools/call failed for {}: {}", name, e))?;
             Ok(tool_result_to_value(result))
         })
@@ -132,13 +474,37 @@ impl McpClient {
 
     pub fn get_prompt(&self, name: &str, args: Value) -> Result<Value, String> {
         if !self.is_enabled() {
-            return Err("MCP is not configured. Set MCP_URL or MCP_COMMAND.".to_string());
+            return Err(self.not_configured());
+        }
+        if let McpTransport::Servers(_) = &self.transport {
+            let (server, name) = self.route(name)?;
+            return server.get_prompt(name, args);
         }
 
         let prompt_args = value_to_prompt_args(args);
-        self.run_with_client(move |rt, client| {
-            let result = rt
-                .block_on(client.get_prompt(name, prompt_args))
+        if let Some(server) = self.rpc_server() {
+            let result = server
+                .request("prompts/get", json!({ "name": name, "arguments": prompt_args.unwrap_or_default() }))
+                .map_err(|e| format!("prompts/get failed for {}: {}", name, e))?;
+            let messages = result
+                .get("messages")
+                .and_then(Value::as_array)
+                .map(|messages| {
+                    messages
+                        .iter()
+                        .map(|m| {
+                            json!({
+                                "role": m.get("role").cloned().unwrap_or(Value::Null),
+                                "content": content_items(m.get("content")),
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            return Ok(json!({ "messages": messages }));
+        }
+        self.run_with_client(move |client| {
+            let result = try_block_on(client.get_prompt(name, prompt_args))?
                 .map_err(|e| format!("prompts/get failed for {}: {}", name, e))?;
             Ok(prompt_result_to_value(result))
         })
@@ -146,12 +512,21 @@ impl McpClient {
 
     pub fn read_resource(&self, uri: &str) -> Result<Value, String> {
         if !self.is_enabled() {
-            return Err("MCP is not configured. Set MCP_URL or MCP_COMMAND.".to_string());
+            return Err(self.not_configured());
+        }
+        if let McpTransport::Servers(_) = &self.transport {
+            let (server, uri) = self.route(uri)?;
+            return server.read_resource(uri);
+        }
+        if let Some(server) = self.rpc_server() {
+            let result = server
+                .request("resources/read", json!({ "uri": uri }))
+                .map_err(|e| format!("resources/read failed for {}: {}", uri, e))?;
+            return Ok(json!({ "contents": content_items(result.get("contents")) }));
         }
 
-        self.run_with_client(move |rt, client| {
-            let result = rt
-                .block_on(client.read_resource(uri))
+        self.run_with_client(move |client| {
+            let result = try_block_on(client.read_resource(uri))?
                 .map_err(|e| format!("resources/read failed for {}: {}", uri, e))?;
             Ok(resource_content_to_value(result))
         })
@@ -159,24 +534,22 @@ impl McpClient {
 
     fn run_with_client<T, F>(&self, f: F) -> Result<T, String>
     where
-        F: FnOnce(&tokio::runtime::Runtime, &mut MCPClient) -> Result<T, String>,
+        F: FnOnce(&mut MCPClient) -> Result<T, String>,
     {
-        let transport = self.build_transport()?;
-        let rt = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .map_err(|e| format!("Failed to build async runtime for MCP client: {}", e))?;
-
-        let client_info = ClientInfo {
-            name: "aicli".to_string(),
-            version: "0.1.0".to_string(),
-        };
-        let mut client = MCPClient::new(transport, client_info);
-        rt.block_on(client.initialize())
-            .map_err(|e| format!("MCP initialize failed: {}", e))?;
+        let key = self.server_identity().unwrap_or_default();
+        let mut client = connect_with_retries(&key, self.supervision, || {
+            let transport = self.build_transport()?;
+            let client_info = ClientInfo {
+                name: "aicli".to_string(),
+                version: "0.1.0".to_string(),
+            };
+            let mut client = MCPClient::new(transport, client_info);
+            try_block_on(client.initialize())?.map_err(|e| format!("MCP initialize failed: {}", e))?;
+            Ok(client)
+        })?;
 
-        let out = f(&rt, &mut client);
-        let _ = rt.block_on(client.close());
+        let out = f(&mut client);
+        let _ = try_block_on(client.close());
         out
     }
 
@@ -185,17 +558,35 @@ impl McpClient {
             McpTransport::Http { endpoint } => {
                 Ok(Arc::new(HttpSSETransport::new(endpoint)) as Arc<dyn Transport>)
             }
-            McpTransport::Stdio { command, args } => {
-                let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
-                let t = StdioTransport::new(command, &arg_refs)
-                    .map_err(|e| format!("Failed to create MCP stdio transport '{}': {}", command, e))?;
-                Ok(Arc::new(t) as Arc<dyn Transport>)
+            McpTransport::Stdio { .. } | McpTransport::AuthHttp(_) | McpTransport::Servers(_) => {
+                Err("This MCP server is reached without the client crate".to_string())
             }
             McpTransport::Disabled => Err("MCP transport is disabled".to_string()),
         }
     }
 }
 
+enum RpcServer {
+    Stdio(Arc<McpSupervisor>),
+    Http(McpHttpClient),
+}
+
+impl RpcServer {
+    fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        match self {
+            RpcServer::Stdio(server) => server.request(method, params),
+            RpcServer::Http(client) => client.request(method, params),
+        }
+    }
+
+    fn list(&self, method: &str, key: &str) -> Result<Vec<Value>, String> {
+        match self {
+            RpcServer::Stdio(server) => server.list(method, key),
+            RpcServer::Http(client) => client.list(method, key),
+        }
+    }
+}
+
 fn value_to_prompt_args(args: Value) -> Option<HashMap<String, String>> {
     let obj = args.as_object()?;
     let mut map = HashMap::new();
@@ -209,6 +600,29 @@ fn value_to_prompt_args(args: Value) -> Option<HashMap<String, String>> {
     Some(map)
 }
 
+// Tool, prompt, and resource content in the shape the HTTP client gives:
+// `{"type": "text", "text"}` or `{"type": "blob", "blob"}` items. A single
+// item is accepted where a list is expected.
+fn content_items(content: Option<&Value>) -> Vec<Value> {
+    let items = match content {
+        Some(Value::Array(items)) => items.clone(),
+        Some(item @ Value::Object(_)) => vec![item.clone()],
+        _ => Vec::new(),
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            if let Some(text) = item.get("text").and_then(Value::as_str) {
+                json!({ "type": "text", "text": text })
+            } else if let Some(blob) = item.get("blob").or_else(|| item.get("data")).and_then(Value::as_str) {
+                json!({ "type": "blob", "blob": blob })
+            } else {
+                item
+            }
+        })
+        .collect()
+}
+
 fn tool_result_to_value(result: mcp_client_rust::types::ToolResult) -> Value {
     let content = result
         .content
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::answer::{AnswerResult, SourceHit};
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+use crate::timestamps::format_local;
+
+/// Which chunks of a collection were retrieved for answers and which of
+/// them the answers cited, with the last time of each. Only paths and chunk
+/// numbers are stored, never questions or answers.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessLog {
+    /// Unix time of the first recorded answer.
+    #[serde(default, with = "crate::timestamps::rfc3339_secs")]
+    pub since: u64,
+    #[serde(default)]
+    pub questions: u64,
+    #[serde(default)]
+    pub files: BTreeMap<String, FileAccess>,
+}
+
+/// Counts are of questions, so a file retrieved three times for one answer
+/// counts once.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileAccess {
+    pub retrieved: u64,
+    pub cited: u64,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub last_retrieved: u64,
+    #[serde(default, with = "crate::timestamps::rfc3339_secs_opt")]
+    pub last_cited: Option<u64>,
+    /// Keyed by chunk index.
+    #[serde(default)]
+    pub chunks: BTreeMap<usize, ChunkAccess>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkAccess {
+    pub retrieved: u64,
+    pub cited: u64,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub last_retrieved: u64,
+    #[serde(default, with = "crate::timestamps::rfc3339_secs_opt")]
+    pub last_cited: Option<u64>,
+}
+
+impl AccessLog {
+    /// Count one answered question that had `hits` in its context and cited
+    /// the hits at `cited` (indexes into `hits`).
+    pub fn record(&mut self, hits: &[SourceHit], cited: &[usize], now: u64) {
+        if hits.is_empty() {
+            return;
+        }
+        if self.since == 0 {
+            self.since = now;
+        }
+        self.questions += 1;
+        let mut seen_files = BTreeSet::new();
+        let mut cited_files = BTreeSet::new();
+        let mut seen_chunks = BTreeSet::new();
+        let mut cited_chunks = BTreeSet::new();
+        for (i, hit) in hits.iter().enumerate() {
+            let was_cited = cited.contains(&i);
+            let file = self.files.entry(hit.path.clone()).or_default();
+            if seen_files.insert(hit.path.as_str()) {
+                file.retrieved += 1;
+                file.last_retrieved = now;
+            }
+            if was_cited && cited_files.insert(hit.path.as_str()) {
+                file.cited += 1;
+                file.last_cited = Some(now);
+            }
+            let Some(index) = hit.chunk_index else {
+                continue;
+            };
+            let chunk = file.chunks.entry(index).or_default();
+            if seen_chunks.insert((hit.path.as_str(), index)) {
+                chunk.retrieved += 1;
+                chunk.last_retrieved = now;
+            }
+            if was_cited && cited_chunks.insert((hit.path.as_str(), index)) {
+                chunk.cited += 1;
+                chunk.last_cited = Some(now);
+            }
+        }
+    }
+
+    /// Files by how many answers cited them, then how many retrieved them.
+    pub fn most_useful(&self) -> Vec<(&str, &FileAccess)> {
+        let mut files: Vec<(&str, &FileAccess)> = self.files.iter().map(|(p, f)| (p.as_str(), f)).collect();
+        files.sort_by(|a, b| {
+            b.1.cited
+                .cmp(&a.1.cited)
+                .then(b.1.retrieved.cmp(&a.1.retrieved))
+                .then(a.0.cmp(b.0))
+        });
+        files
+    }
+
+    /// Paths in `indexed` that no answer has retrieved.
+    pub fn never_retrieved<'a>(&self, indexed: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+        indexed
+            .into_iter()
+            .filter(|path| !self.files.contains_key(*path))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Indexes into `result.hits` of the passages the answer relied on: the
+/// agent's `sources_used` when it gave them, otherwise the `[n]` markers in
+/// the answer text.
+pub fn cited_hits(result: &AnswerResult) -> Vec<usize> {
+    let numbers = match result.details.as_ref().filter(|d| !d.sources_used.is_empty()) {
+        Some(details) => details.sources_used.clone(),
+        None => citation_markers(&result.answer),
+    };
+    let mut cited: Vec<usize> = numbers
+        .into_iter()
+        .filter(|n| (1..=result.hits.len()).contains(n))
+        .map(|n| n - 1)
+        .collect();
+    cited.sort_unstable();
+    cited.dedup();
+    cited
+}
+
+// `[n]` numbers in `text`, e.g. `[2]` in "retries twice [2]".
+fn citation_markers(text: &str) -> Vec<usize> {
+    text.split('[')
+        .skip(1)
+        .filter_map(|rest| rest.split_once(']'))
+        .filter_map(|(inside, _)| inside.trim().parse().ok())
+        .collect()
+}
+
+/// Add one answer to the collection's access log when `RAG_ACCESS_LOG` is on.
+/// The log is locked while it is read and rewritten, so answers recorded at
+/// once (such as by `aicli serve` workers) all count. Safe mode records nothing.
+pub fn record_access(cfg: &Config, result: &AnswerResult) -> Result<(), String> {
+    if cfg.safe_mode || !cfg.access_log || result.hits.is_empty() {
+        return Ok(());
+    }
+    let _lock = lock_log(cfg)?;
+    let mut log = access_log(cfg)?;
+    log.record(&result.hits, &cited_hits(result), now());
+    save(cfg, &log)
+}
+
+/// The collection's access log. A file that does not parse counts as an
+/// empty log, which the next recorded answer replaces.
+pub fn access_log(cfg: &Config) -> Result<AccessLog, String> {
+    let path = access_log_path(cfg);
+    if !path.exists() {
+        return Ok(AccessLog::default());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&text).unwrap_or_default())
+}
+
+pub fn clear_access_log(cfg: &Config) -> Result<(), String> {
+    let path = access_log_path(cfg);
+    if path.exists() {
+        let _lock = lock_log(cfg)?;
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// The most useful files of the collection, then the indexed files no
+/// answer has retrieved yet.
+pub fn access_report(cfg: &Config, top: usize) -> Result<Vec<String>, String> {
+    let log = access_log(cfg)?;
+    let mut lines = Vec::new();
+    if log.questions == 0 {
+        lines.push(format!("No answers recorded for {}.", cfg.collection));
+    } else {
+        lines.push(format!(
+            "{} answers recorded for {} since {}",
+            log.questions,
+            cfg.collection,
+            format_local(cfg, log.since)
+        ));
+        lines.push(format!("{:>6} {:>9}  {}", "cited", "retrieved", "file"));
+        for (path, file) in log.most_useful().into_iter().take(top) {
+            lines.push(format!("{:>6} {:>9}  {}", file.cited, file.retrieved, path));
+        }
+    }
+    let manifest = IndexManifest::load(cfg)?;
+    let unused = log.never_retrieved(manifest.files.keys());
+    if !unused.is_empty() {
+        lines.push(format!("Never retrieved ({} of {} indexed files):", unused.len(), manifest.files.len()));
+        lines.extend(unused.into_iter().map(|path| format!("  {}", path)));
+    }
+    Ok(lines)
+}
+
+pub fn access_log_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir)
+        .join("access")
+        .join(format!("{}.json", cfg.collection))
+}
+
+// An exclusive lock on the collection's log, held until the file is dropped.
+// It is taken on a side file, since `save` replaces the log itself.
+fn lock_log(cfg: &Config) -> Result<File, String> {
+    let path = access_log_path(cfg).with_extension("lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    file.lock().map_err(|e| format!("Failed to lock {}: {}", path.display(), e))?;
+    Ok(file)
+}
+
+// Written to a temporary file and renamed over the log, so a reader never
+// sees half of it.
+fn save(cfg: &Config, log: &AccessLog) -> Result<(), String> {
+    let path = access_log_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(log).map_err(|e| e.to_string())?;
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, text).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    fs::rename(&temp, &path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
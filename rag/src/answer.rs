@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::build_prompt::Message;
+use crate::retrieve_chunks::Hit;
+use crate::usage::Usage;
+
+/// One retrieved chunk that was available to the model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceHit {
+    pub path: String,
+    /// Original URL (with `#anchor` when known) for web-sourced chunks.
+    pub url: Option<String>,
+    pub score: f32,
+    pub chunk_index: Option<usize>,
+    pub text: String,
+}
+
+impl From<&Hit> for SourceHit {
+    fn from(hit: &Hit) -> Self {
+        let payload = hit.payload.as_ref();
+        Self {
+            path: payload
+                .and_then(|p| p.path.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+            url: payload.filter(|p| p.url.is_some()).and_then(|p| p.citation()),
+            score: hit.score,
+            chunk_index: payload.and_then(|p| p.index),
+            text: payload.and_then(|p| p.chunk.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Optional structured fields the agent may attach to its final action.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FinalDetails {
+    /// Self-reported confidence between 0 and 1.
+    pub confidence: Option<f32>,
+    /// `[n]` numbers of the context passages the answer relied on.
+    pub sources_used: Vec<usize>,
+    pub follow_ups: Vec<String>,
+}
+
+impl FinalDetails {
+    pub fn is_empty(&self) -> bool {
+        self.confidence.is_none() && self.sources_used.is_empty() && self.follow_ups.is_empty()
+    }
+
+    /// Lines for the footer under the answer: confidence and sources on the
+    /// first line, then one line per suggested follow-up.
+    pub fn footer_lines(&self) -> Vec<String> {
+        let mut summary = Vec::new();
+        if let Some(confidence) = self.confidence {
+            summary.push(format!("Confidence: {:.0}%", confidence * 100.0));
+        }
+        if !self.sources_used.is_empty() {
+            let ids = self.sources_used.iter().map(|n| format!("[{}]", n)).collect::<Vec<_>>();
+            summary.push(format!("Sources used: {}", ids.join(" ")));
+        }
+        let mut lines = Vec::new();
+        if !summary.is_empty() {
+            lines.push(summary.join("  |  "));
+        }
+        lines.extend(self.follow_ups.iter().map(|q| format!("Follow-up: {}", q)));
+        lines
+    }
+}
+
+/// Everything about one answered question: the display context and answer
+/// text, the sources behind it, the exact prompt sent, the model, timing,
+/// and tokens used.
+#[derive(Clone, Debug)]
+pub struct AnswerResult {
+    pub context: String,
+    pub answer: String,
+    pub hits: Vec<SourceHit>,
+    pub prompt: Vec<Message>,
+    pub model: String,
+    pub elapsed: Duration,
+    /// Time spent reranking candidates, when a reranker is configured.
+    pub rerank_time: Option<Duration>,
+    /// Why reranking failed, when it did; the hits then keep their vector order.
+    pub rerank_error: Option<String>,
+    /// Structured fields from the agent's final action, when it gave any.
+    pub details: Option<FinalDetails>,
+    /// Model calls made for this question on the calling thread's meter,
+    /// counted since its last `reset_usage`.
+    pub usage: Usage,
+}
+
+impl AnswerResult {
+    /// `[n] source (score)` lines, deduplicated by path and chunk. The source
+    /// is the URL for web content so exported answers keep working links.
+    pub fn sources_text(&self) -> String {
+        let mut seen: Vec<(&str, Option<usize>)> = Vec::new();
+        let mut lines = Vec::new();
+        for hit in &self.hits {
+            let key = (hit.path.as_str(), hit.chunk_index);
+            if seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            let source = hit.url.as_deref().unwrap_or(&hit.path);
+            lines.push(format!("[{}] {} ({:.3})", lines.len() + 1, source, hit.score));
+        }
+        lines.join("\n")
+    }
+}
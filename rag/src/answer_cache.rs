@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+
+// Fixed namespace for answer cache keys and index versions.
+const ANSWER_NAMESPACE: Uuid = Uuid::from_u128(0x8c41_d2e7_3b5a_4f09_a6c3_51e8_9d27_b40f);
+
+// Entries kept per collection; the oldest are dropped first.
+const MAX_ENTRIES: usize = 500;
+
+/// An answer as first generated, stored under `$AICLI_CACHE_DIR/answer_cache/`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CachedAnswer {
+    pub question: String,
+    pub context: String,
+    pub answer: String,
+    /// The `Sources:` lines shown under the answer.
+    pub sources: String,
+    pub model: String,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub created_at: u64,
+}
+
+impl CachedAnswer {
+    pub fn age_secs(&self, now: u64) -> u64 {
+        now.saturating_sub(self.created_at)
+    }
+
+    /// The answer pane's badge, e.g. `cached 5m ago, qwen2.5-coder:14b`.
+    pub fn badge(&self) -> String {
+        format!("cached {} ago, {}", format_age(self.age_secs(unix_secs())), self.model)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct AnswerCacheFile {
+    #[serde(default)]
+    entries: BTreeMap<String, CachedAnswer>,
+}
+
+/// The question as a cache key sees it: lowercase, whitespace collapsed,
+/// and trailing `?`, `.`, and `!` dropped.
+pub fn normalize_question(question: &str) -> String {
+    let collapsed = question.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    collapsed.trim_end_matches(['?', '.', '!', ' ']).to_string()
+}
+
+/// A hash of the collection's manifest: it changes whenever a file is
+/// indexed, re-indexed, or forgotten. Collections without a local manifest
+/// (indexed elsewhere) all share one version, so only the TTL expires them.
+pub fn index_version(cfg: &Config) -> String {
+    let files = IndexManifest::load(cfg).map(|m| m.files).unwrap_or_default();
+    let text = serde_json::to_string(&files).unwrap_or_default();
+    Uuid::new_v5(&ANSWER_NAMESPACE, text.as_bytes()).to_string()
+}
+
+/// The cache key for `question`: the normalized question, the collection
+/// and its index version, and the settings that shape the answer (model,
+/// pipeline, language, tone, focus, attachments, and `mode`).
+pub fn answer_cache_key(cfg: &Config, question: &str, mode: &str) -> String {
+    let parts = [
+        normalize_question(question),
+        cfg.collection.clone(),
+        index_version(cfg),
+        cfg.chat_model.clone(),
+        cfg.answer_pipeline.clone(),
+        cfg.answer_language.clone(),
+        cfg.answer_tone.clone(),
+        cfg.focus_paths.join(","),
+        cfg.attachments.join(","),
+        mode.to_string(),
+    ];
+    Uuid::new_v5(&ANSWER_NAMESPACE, parts.join("\u{1f}").as_bytes()).to_string()
+}
+
+pub fn answer_cache_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.cache_dir)
+        .join("answer_cache")
+        .join(format!("{}.json", cfg.collection))
+}
+
+fn load_file(cfg: &Config) -> AnswerCacheFile {
+    fs::read_to_string(answer_cache_path(cfg))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// The cached answer for `key`, unless caching is off (as it is in safe
+/// mode) or it is older than `RAG_ANSWER_CACHE_TTL_SECS`.
+pub fn lookup_answer(cfg: &Config, key: &str) -> Option<CachedAnswer> {
+    if cfg.safe_mode || !cfg.answer_cache {
+        return None;
+    }
+    let entry = load_file(cfg).entries.remove(key)?;
+    (entry.age_secs(unix_secs()) < cfg.answer_cache_ttl_secs).then_some(entry)
+}
+
+/// Store `answer` under `key`, stamped with the current time, replacing
+/// any earlier answer and dropping expired entries. Nothing is stored in
+/// safe mode.
+pub fn store_answer(cfg: &Config, key: &str, mut answer: CachedAnswer) -> Result<(), String> {
+    if cfg.safe_mode || !cfg.answer_cache {
+        return Ok(());
+    }
+    let now = unix_secs();
+    answer.created_at = now;
+    let mut file = load_file(cfg);
+    file.entries.retain(|_, e| e.age_secs(now) < cfg.answer_cache_ttl_secs);
+    file.entries.insert(key.to_string(), answer);
+    while file.entries.len() > MAX_ENTRIES {
+        let oldest = file
+            .entries
+            .iter()
+            .min_by_key(|(_, e)| e.created_at)
+            .map(|(k, _)| k.clone())
+            .unwrap_or_default();
+        file.entries.remove(&oldest);
+    }
+    let path = answer_cache_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// `42s`, `5m`, `3h`, or `2d`, for the cached badge.
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::chunk_text::chunk_text;
+use crate::config::Config;
+use crate::doc_type::classify_chunk;
+use crate::embed_chunks::embed_texts_async;
+use crate::mmr::cosine;
+use crate::retrieve_chunks::{Hit, Payload};
+use crate::runtime::block_on;
+
+// One attached file, chunked and embedded. Never written to Qdrant.
+struct Attached {
+    modified: Option<SystemTime>,
+    embed_model: String,
+    chunks: Vec<(String, Vec<f32>)>,
+}
+
+fn store() -> &'static Mutex<HashMap<String, Attached>> {
+    static STORE: OnceLock<Mutex<HashMap<String, Attached>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Chunk and embed `path` into the in-memory attachment store, returning
+/// its chunk count. Queries search it next to the collection while the
+/// path is listed in `cfg.attachments`; nothing is indexed. A file already
+/// attached is embedded again only when it changed.
+pub fn attach_file(cfg: &Config, path: &str) -> Result<usize, String> {
+    block_on(attach_file_async(cfg, path))
+}
+
+pub async fn attach_file_async(cfg: &Config, path: &str) -> Result<usize, String> {
+    let modified = fs::metadata(path)
+        .map_err(|e| format!("Cannot attach {}: {}", path, e))?
+        .modified()
+        .ok();
+    if let Some(count) = cached_chunks(cfg, path, modified) {
+        return Ok(count);
+    }
+    let bytes = fs::read(path).map_err(|e| format!("Cannot attach {}: {}", path, e))?;
+    let text = String::from_utf8(bytes).map_err(|_| format!("Cannot attach {}: not a text file", path))?;
+    let texts: Vec<String> = chunk_text(&text, cfg.chunk_params(path), cfg)
+        .into_iter()
+        .map(|c| c.text)
+        .filter(|t| !t.trim().is_empty())
+        .collect();
+    let vectors = embed_texts_async(cfg, &texts).await?;
+    if vectors.len() != texts.len() {
+        return Err(format!("Embedding returned {} vectors for {} chunks of {}", vectors.len(), texts.len(), path));
+    }
+    let count = texts.len();
+    if let Ok(mut store) = store().lock() {
+        store.insert(
+            path.to_string(),
+            Attached {
+                modified,
+                embed_model: cfg.embed_model.clone(),
+                chunks: texts.into_iter().zip(vectors).collect(),
+            },
+        );
+    }
+    Ok(count)
+}
+
+fn cached_chunks(cfg: &Config, path: &str, modified: Option<SystemTime>) -> Option<usize> {
+    let store = store().lock().ok()?;
+    let attached = store.get(path)?;
+    (attached.modified == modified && modified.is_some() && attached.embed_model == cfg.embed_model)
+        .then_some(attached.chunks.len())
+}
+
+/// Forget an attached file's embeddings.
+pub fn detach_file(path: &str) {
+    if let Ok(mut store) = store().lock() {
+        store.remove(path);
+    }
+}
+
+/// Chunks of the files in `cfg.attachments` nearest to `vector`, scored with
+/// the collection's distance metric so they rank alongside its hits. Files
+/// not yet in the store are attached first.
+pub async fn search_attachments_async(cfg: &Config, vector: &[f32], limit: usize) -> Result<Vec<Hit>, String> {
+    if cfg.attachments.is_empty() || vector.is_empty() {
+        return Ok(Vec::new());
+    }
+    for path in &cfg.attachments {
+        attach_file_async(cfg, path).await?;
+    }
+    let lower_is_better = matches!(cfg.distance.to_ascii_lowercase().as_str(), "euclid" | "manhattan");
+    let mut hits = Vec::new();
+    let store = store().lock().map_err(|_| "Attachment store lock poisoned".to_string())?;
+    for path in &cfg.attachments {
+        let Some(attached) = store.get(path) else {
+            continue;
+        };
+        for (index, (text, chunk_vector)) in attached.chunks.iter().enumerate() {
+            hits.push(Hit {
+                score: score(&cfg.distance, vector, chunk_vector),
+                payload: Some(attachment_payload(path, index, text)),
+                vector: cfg.mmr_lambda.is_some().then(|| chunk_vector.clone()),
+                rerank_score: None,
+            });
+        }
+    }
+    if lower_is_better {
+        hits.sort_by(|a, b| a.score.total_cmp(&b.score));
+    } else {
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+// The score Qdrant would give for `distance`.
+fn score(distance: &str, a: &[f32], b: &[f32]) -> f32 {
+    match distance.to_ascii_lowercase().as_str() {
+        "dot" => a.iter().zip(b).map(|(x, y)| x * y).sum(),
+        "euclid" => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt(),
+        "manhattan" => a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum(),
+        _ => cosine(a, b),
+    }
+}
+
+fn attachment_payload(path: &str, index: usize, text: &str) -> Payload {
+    Payload {
+        path: Some(path.to_string()),
+        url: None,
+        anchor: None,
+        index: Some(index),
+        chunk: Some(text.to_string()),
+        before: None,
+        after: None,
+        cell_index: None,
+        cell_type: None,
+        commit: None,
+        doc_type: Some(classify_chunk(path, text).to_string()),
+    }
+}
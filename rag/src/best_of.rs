@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use tokio::task::JoinSet;
+
+use crate::build_prompt::Message;
+use crate::config::{Config, GenerationOptions};
+use crate::generate::{generate_answer_async, generate_answer_with_async};
+use crate::runtime::block_on;
+
+/// Answer `messages` with self-consistency voting when `RAG_BEST_OF` is
+/// above 1: that many drafts are generated in parallel at
+/// `RAG_BEST_OF_TEMPERATURE`, then a judge pass picks the best grounded one
+/// (`RAG_BEST_OF_JUDGE=select`) or writes one answer from them (`merge`).
+/// With `RAG_BEST_OF` at 1 this is a single ordinary generation.
+pub fn generate_best_of(cfg: &Config, messages: &[Message]) -> Result<String, String> {
+    block_on(generate_best_of_async(cfg, messages))
+}
+
+pub async fn generate_best_of_async(cfg: &Config, messages: &[Message]) -> Result<String, String> {
+    if cfg.best_of <= 1 {
+        return generate_answer_async(cfg, messages).await;
+    }
+    let drafts = generate_drafts(cfg, messages).await?;
+    if drafts.len() == 1 {
+        return Ok(drafts.into_iter().next().unwrap_or_default());
+    }
+    let merge = cfg.best_of_judge.trim().eq_ignore_ascii_case("merge");
+    let judge = judge_messages(messages, &drafts, merge);
+    let deterministic = GenerationOptions {
+        temperature: Some(0.0),
+        ..GenerationOptions::default()
+    };
+    // A failed or unreadable verdict falls back to the most agreed-upon draft.
+    let verdict = generate_answer_with_async(cfg, &judge, &deterministic).await.unwrap_or_default();
+    if merge && !verdict.trim().is_empty() {
+        return Ok(verdict.trim().to_string());
+    }
+    let pick = parse_choice(&verdict, drafts.len()).unwrap_or_else(|| consensus_pick(&drafts));
+    Ok(drafts.into_iter().nth(pick).unwrap_or_default())
+}
+
+/// Options for each draft: the best-of temperature, a seed per draft when
+/// one is configured so the drafts differ, and an equal share of
+/// `RAG_BEST_OF_BUDGET` as the token limit.
+pub fn draft_options(cfg: &Config, draft: usize) -> GenerationOptions {
+    let k = cfg.best_of.max(1);
+    GenerationOptions {
+        temperature: Some(cfg.best_of_temperature),
+        seed: cfg.generation.seed.map(|s| s + draft as i64),
+        num_predict: cfg.best_of_budget.map(|budget| (budget / k).max(1)),
+        ..GenerationOptions::default()
+    }
+}
+
+// Drafts in the order they were requested; failed and empty ones are
+// dropped, and the first error is returned only when none succeeded.
+async fn generate_drafts(cfg: &Config, messages: &[Message]) -> Result<Vec<String>, String> {
+    let mut set = JoinSet::new();
+    for draft in 0..cfg.best_of {
+        let cfg = cfg.clone();
+        let messages = messages.to_vec();
+        set.spawn(async move {
+            let options = draft_options(&cfg, draft);
+            (draft, generate_answer_with_async(&cfg, &messages, &options).await)
+        });
+    }
+    let mut drafts = Vec::new();
+    let mut first_error = None;
+    while let Some(joined) = set.join_next().await {
+        match joined {
+            Ok((draft, Ok(text))) if !text.trim().is_empty() => drafts.push((draft, text.trim().to_string())),
+            Ok((_, Ok(_))) => {}
+            Ok((_, Err(e))) => {
+                first_error.get_or_insert(e);
+            }
+            Err(e) => {
+                first_error.get_or_insert(format!("Draft task failed: {}", e));
+            }
+        }
+    }
+    if drafts.is_empty() {
+        return Err(first_error.unwrap_or_else(|| "Model returned only empty drafts".to_string()));
+    }
+    drafts.sort_by_key(|(draft, _)| *draft);
+    Ok(drafts.into_iter().map(|(_, text)| text).collect())
+}
+
+// The original conversation followed by the drafts, so the judge sees the
+// same context the drafts were grounded in.
+fn judge_messages(messages: &[Message], drafts: &[String], merge: bool) -> Vec<Message> {
+    let listed = drafts
+        .iter()
+        .enumerate()
+        .map(|(i, d)| format!("Draft {}:\n{}", i + 1, d))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let task = if merge {
+        "Write one final answer from these drafts. Keep only claims supported by the context above, prefer points most drafts agree on, and drop anything the context does not support. Reply with the answer only."
+    } else {
+        "Pick the draft that is best supported by the context above and most complete. Reply with the draft number only."
+    };
+    let mut out = messages.to_vec();
+    out.push(Message {
+        role: "user".to_string(),
+        content: format!("Several draft answers were written for the question above.\n\n{}\n\n{}", listed, task),
+    });
+    out
+}
+
+/// The zero-based draft named in a judge reply such as `2` or `Draft 2.`;
+/// `None` when no number in range appears.
+pub fn parse_choice(reply: &str, drafts: usize) -> Option<usize> {
+    reply
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse::<usize>().ok())
+        .find(|n| (1..=drafts).contains(n))
+        .map(|n| n - 1)
+}
+
+/// The draft that shares the most words with the others, on average: the
+/// self-consistency vote used when the judge gives no usable answer. Ties
+/// go to the earlier draft.
+pub fn consensus_pick(drafts: &[String]) -> usize {
+    let words: Vec<HashSet<String>> = drafts
+        .iter()
+        .map(|d| {
+            d.split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(str::to_lowercase)
+                .collect()
+        })
+        .collect();
+    let agreement = |i: usize| -> f32 {
+        words
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, other)| {
+                let union = words[i].union(other).count();
+                if union == 0 {
+                    0.0
+                } else {
+                    words[i].intersection(other).count() as f32 / union as f32
+                }
+            })
+            .sum()
+    };
+    let mut best = 0;
+    let mut best_score = f32::MIN;
+    for i in 0..drafts.len() {
+        let score = agreement(i);
+        if score > best_score {
+            best = i;
+            best_score = score;
+        }
+    }
+    best
+}
@@ -1,18 +1,64 @@
 use crate::config::Config;
+use crate::model_info::context_window;
 use crate::retrieve_chunks::Hit;
+use crate::tokens::{estimate_tokens, truncate_to_tokens};
 
-#[derive(Clone, Debug, serde::Serialize)]
+// Tokens kept free for the question, the chat template, and the model's answer.
+const RESERVED_TOKENS: usize = 1024;
+// Estimated cost of the `[n] path (chunk i, score s)` header on each hit.
+const HIT_HEADER_TOKENS: usize = 24;
+// Below this many tokens a trimmed chunk is too short to be worth including.
+const MIN_TRIMMED_TOKENS: usize = 64;
+/// Appended to a chunk that was cut to fit the context budget.
+pub const TRIMMED_MARKER: &str = "\n[…trimmed]";
+
+/// Prefix for surrounding-context lines in display text, so the UI can dim them.
+pub const CONTEXT_LINE_MARKER: &str = "┆ ";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
+/// The configured system prompt plus any answer language and tone instructions.
+pub fn answer_system_prompt(cfg: &Config) -> String {
+    let style = answer_style_instructions(cfg);
+    if style.is_empty() {
+        cfg.system_prompt.clone()
+    } else {
+        format!("{}\n\n{}", cfg.system_prompt, style)
+    }
+}
+
+/// Instructions for `RAG_ANSWER_LANGUAGE` and `RAG_ANSWER_TONE`, empty when unset.
+pub fn answer_style_instructions(cfg: &Config) -> String {
+    let mut lines = Vec::new();
+    let language = cfg.answer_language.trim();
+    if !language.is_empty() {
+        lines.push(format!(
+            "Always write the answer in {}, regardless of the language of the question or the context.",
+            language
+        ));
+    }
+    match cfg.answer_tone.trim().to_ascii_lowercase().as_str() {
+        "" => {}
+        "concise" => lines.push("Keep the answer concise: a few sentences at most.".to_string()),
+        "detailed" => lines.push("Give a detailed, thorough answer with explanations and examples.".to_string()),
+        "bullet" | "bullets" => lines.push("Format the answer as a bulleted list.".to_string()),
+        other => lines.push(format!("Use a {} tone.", other)),
+    }
+    lines.join("\n")
+}
+
 pub fn build_prompt_with_context(
     cfg: &Config,
     question: &str,
     hits: &[Hit],
 ) -> (Vec<Message>, String) {
-    let context = format_context_from_hits(hits);
+    let budget = context_budget_tokens(cfg, question);
+    let hits = fit_hits_to_budget(hits, budget);
+    let context = format_context_from_hits(&hits);
 
     let user_content = format!(
         "Use the context below to answer the question.\n\nContext:\n{}\n\nQuestion: {}",
@@ -20,11 +66,83 @@ pub fn build_prompt_with_context(
     );
 
     let messages = vec![
-        Message { role: "system".to_string(), content: cfg.system_prompt.clone() },
+        Message { role: "system".to_string(), content: answer_system_prompt(cfg) },
         Message { role: "user".to_string(), content: user_content },
     ];
 
-    (messages, context)
+    (messages, format_display_context_from_hits(&hits))
+}
+
+pub fn build_prompt_without_context(cfg: &Config, question: &str) -> Vec<Message> {
+    vec![
+        Message { role: "system".to_string(), content: answer_system_prompt(cfg) },
+        Message {
+            role: "user".to_string(),
+            content: format!(
+                "No relevant context was found in the indexed documents. Answer from general knowledge and say that the answer is not grounded in the indexed documents.\n\nQuestion: {}",
+                question
+            ),
+        },
+    ]
+}
+
+pub fn no_relevant_context_message(cfg: &Config) -> String {
+    match cfg.min_score {
+        Some(min) => format!("(no chunks scored above the minimum relevance threshold {})", min),
+        None => "(no context found)".to_string(),
+    }
+}
+
+/// Tokens available for retrieved context: the model window minus the
+/// system prompt, `extra` (usually the question), and a reserve for the
+/// answer, capped by `RAG_CONTEXT_BUDGET` when set.
+pub fn context_budget_tokens(cfg: &Config, extra: &str) -> usize {
+    let available = context_window(cfg)
+        .saturating_sub(RESERVED_TOKENS)
+        .saturating_sub(estimate_tokens(&answer_system_prompt(cfg)))
+        .saturating_sub(estimate_tokens(extra));
+    match cfg.context_budget {
+        Some(budget) => budget.min(available),
+        None => available,
+    }
+}
+
+/// The hits that fit in `budget_tokens`, filled best-score first. A hit that
+/// does not fit is skipped so smaller, lower-scored hits can still use the
+/// space; the first hit that overflows is trimmed instead when enough room
+/// is left. The top hit is always kept, trimmed if necessary, and the
+/// result keeps the input order.
+pub fn fit_hits_to_budget(hits: &[Hit], budget_tokens: usize) -> Vec<Hit> {
+    let mut order: Vec<usize> = (0..hits.len()).collect();
+    order.sort_by(|&a, &b| hits[b].score.total_cmp(&hits[a].score));
+
+    let mut used = 0usize;
+    let mut trimmed_one = false;
+    let mut kept: Vec<(usize, Hit)> = Vec::new();
+    for idx in order {
+        let hit = &hits[idx];
+        let chunk = hit.payload.as_ref().and_then(|p| p.chunk.as_deref()).unwrap_or("");
+        let cost = estimate_tokens(chunk) + HIT_HEADER_TOKENS;
+        if used + cost <= budget_tokens {
+            used += cost;
+            kept.push((idx, hit.clone()));
+            continue;
+        }
+        let room = budget_tokens.saturating_sub(used + HIT_HEADER_TOKENS);
+        if trimmed_one || (!kept.is_empty() && room < MIN_TRIMMED_TOKENS) {
+            continue;
+        }
+        let mut trimmed = hit.clone();
+        if let Some(payload) = trimmed.payload.as_mut() {
+            let cut = truncate_to_tokens(chunk, room);
+            payload.chunk = Some(format!("{}{}", cut, TRIMMED_MARKER));
+        }
+        used += room + HIT_HEADER_TOKENS;
+        trimmed_one = true;
+        kept.push((idx, trimmed));
+    }
+    kept.sort_by_key(|(idx, _)| *idx);
+    kept.into_iter().map(|(_, hit)| hit).collect()
 }
 
 pub fn format_context_from_hits(hits: &[Hit]) -> String {
@@ -32,14 +150,66 @@ pub fn format_context_from_hits(hits: &[Hit]) -> String {
     for (i, hit) in hits.iter().enumerate() {
         let payload = hit.payload.as_ref();
         let path = payload
-            .and_then(|p| p.path.clone())
+            .and_then(|p| p.citation())
             .unwrap_or_else(|| "unknown".to_string());
         let index = payload
             .and_then(|p| p.index)
             .map(|v| v.to_string())
             .unwrap_or_else(|| "?".to_string());
         let chunk = payload.and_then(|p| p.chunk.clone()).unwrap_or_default();
-        context_lines.push(format!("[{}] {} (chunk {})\n{}", i + 1, path, index, chunk));
+        let cell = payload
+            .and_then(|p| p.cell_index)
+            .map(|c| format!(", cell {}", c))
+            .unwrap_or_default();
+        context_lines.push(format!(
+            "[{}] {} (chunk {}{}, score {:.3})\n{}",
+            i + 1,
+            path,
+            index,
+            cell,
+            hit.score,
+            chunk
+        ));
+    }
+
+    if context_lines.is_empty() {
+        "(no context found)".to_string()
+    } else {
+        context_lines.join("\n\n")
+    }
+}
+
+/// Like `format_context_from_hits`, with the stored surrounding lines shown
+/// around each chunk behind `CONTEXT_LINE_MARKER`.
+pub fn format_display_context_from_hits(hits: &[Hit]) -> String {
+    let mut context_lines = Vec::new();
+    for (i, hit) in hits.iter().enumerate() {
+        let payload = hit.payload.as_ref();
+        let path = payload
+            .and_then(|p| p.citation())
+            .unwrap_or_else(|| "unknown".to_string());
+        let index = payload
+            .and_then(|p| p.index)
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        let chunk = payload.and_then(|p| p.chunk.clone()).unwrap_or_default();
+        let before = payload.and_then(|p| p.before.clone()).unwrap_or_default();
+        let after = payload.and_then(|p| p.after.clone()).unwrap_or_default();
+
+        let cell = payload
+            .and_then(|p| p.cell_index)
+            .map(|c| format!(", cell {}", c))
+            .unwrap_or_default();
+        let mut block = format!("[{}] {} (chunk {}{}, score {:.3})", i + 1, path, index, cell, hit.score);
+        for line in before.lines() {
+            block.push_str(&format!("\n{}{}", CONTEXT_LINE_MARKER, line));
+        }
+        block.push('\n');
+        block.push_str(&chunk);
+        for line in after.lines() {
+            block.push_str(&format!("\n{}{}", CONTEXT_LINE_MARKER, line));
+        }
+        context_lines.push(block);
     }
 
     if context_lines.is_empty() {
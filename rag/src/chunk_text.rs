@@ -1,27 +1,45 @@
-use crate::config::Config;
+use crate::config::{ChunkParams, Config};
 
-pub fn chunk_text(text: &str, cfg: &Config) -> Vec<String> {
-    let size = cfg.chunk_size;
-    let mut overlap = cfg.chunk_overlap;
+/// A chunk plus a few lines of surrounding text. Only `text` is embedded;
+/// `before` and `after` are stored for display.
+#[derive(Clone, Debug, Default)]
+pub struct TextChunk {
+    pub text: String,
+    /// Byte offset of `text` in the text it was cut from.
+    pub offset: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Split `text` into `params.size`-character chunks overlapping by
+/// `params.overlap`; a size of 0 keeps the text whole.
+pub fn chunk_text(text: &str, params: ChunkParams, cfg: &Config) -> Vec<TextChunk> {
+    let ChunkParams { size, overlap } = params.effective();
 
     if size == 0 {
-        return vec![text.to_string()];
-    }
-    if overlap >= size {
-        overlap = size / 4;
+        return vec![TextChunk {
+            text: text.to_string(),
+            ..TextChunk::default()
+        }];
     }
 
     let mut chunks = Vec::new();
     let mut start = 0usize;
     let chars: Vec<char> = text.chars().collect();
     let len_chars = chars.len();
+    let byte_offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
 
     while start < len_chars {
         let end = (start + size).min(len_chars);
         let chunk_str: String = chars[start..end].iter().collect();
         let trimmed = chunk_str.trim();
         if !trimmed.is_empty() {
-            chunks.push(trimmed.to_string());
+            chunks.push(TextChunk {
+                text: trimmed.to_string(),
+                offset: byte_offsets[start] + chunk_str.len() - chunk_str.trim_start().len(),
+                before: last_lines(&chars[..start], cfg.chunk_context_lines),
+                after: first_lines(&chars[end..], cfg.chunk_context_lines),
+            });
         }
         if end == len_chars {
             break;
@@ -31,3 +49,21 @@ pub fn chunk_text(text: &str, cfg: &Config) -> Vec<String> {
 
     chunks
 }
+
+fn last_lines(chars: &[char], n: usize) -> String {
+    if n == 0 || chars.is_empty() {
+        return String::new();
+    }
+    let text: String = chars.iter().collect();
+    let lines: Vec<&str> = text.lines().collect();
+    let from = lines.len().saturating_sub(n);
+    lines[from..].join("\n").trim_end().to_string()
+}
+
+fn first_lines(chars: &[char], n: usize) -> String {
+    if n == 0 || chars.is_empty() {
+        return String::new();
+    }
+    let text: String = chars.iter().collect();
+    text.lines().take(n).collect::<Vec<_>>().join("\n").trim_end().to_string()
+}
@@ -0,0 +1,215 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+// Longest a single syntax check may run before it is killed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fenced code block from an answer, in a language that can be checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeSnippet {
+    /// `rust` or `python`.
+    pub language: &'static str,
+    pub code: String,
+}
+
+/// The outcome of checking one snippet: `Ok` when it parses, or the first
+/// error with its line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnippetCheck {
+    /// 1-based position among the answer's checkable snippets.
+    pub index: usize,
+    pub language: &'static str,
+    pub result: Result<(), String>,
+}
+
+/// Rust and Python fenced blocks in `answer`, in order. Blocks in other
+/// languages or without a language tag are skipped.
+pub fn extract_snippets(answer: &str) -> Vec<CodeSnippet> {
+    let mut snippets = Vec::new();
+    let mut open: Option<(Option<&'static str>, Vec<&str>)> = None;
+    for line in answer.lines() {
+        let trimmed = line.trim_start();
+        match open.take() {
+            None => {
+                if let Some(tag) = trimmed.strip_prefix("```") {
+                    open = Some((checked_language(tag), Vec::new()));
+                }
+            }
+            Some((language, body)) if trimmed.starts_with("```") => {
+                if let Some(language) = language {
+                    snippets.push(CodeSnippet {
+                        language,
+                        code: body.join("\n"),
+                    });
+                }
+            }
+            Some((language, mut body)) => {
+                body.push(line);
+                open = Some((language, body));
+            }
+        }
+    }
+    snippets
+}
+
+fn checked_language(tag: &str) -> Option<&'static str> {
+    let tag = tag.trim().split([' ', ',', '{']).next().unwrap_or_default().to_ascii_lowercase();
+    match tag.as_str() {
+        "rust" | "rs" => Some("rust"),
+        "python" | "py" | "python3" => Some("python"),
+        _ => None,
+    }
+}
+
+/// `RAG_VERIFY_CODE`: syntax-check each Rust and Python snippet in
+/// `answer`. Snippets whose checker is not installed are left out.
+pub fn check_answer_code(cfg: &Config, answer: &str) -> Vec<SnippetCheck> {
+    if !cfg.verify_code {
+        return Vec::new();
+    }
+    extract_snippets(answer)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, snippet)| {
+            check_snippet(&snippet).map(|result| SnippetCheck {
+                index: i + 1,
+                language: snippet.language,
+                result,
+            })
+        })
+        .collect()
+}
+
+/// Parse `snippet` with `rustfmt` or Python's `compile`. `None` when the
+/// checker could not be started. Rust that is not a list of items is tried
+/// again as a function body, so statement snippets pass too.
+pub fn check_snippet(snippet: &CodeSnippet) -> Option<Result<(), String>> {
+    match snippet.language {
+        "rust" => {
+            let as_items = run_check(rustfmt(), &snippet.code)?;
+            if as_items.is_ok() {
+                return Some(Ok(()));
+            }
+            let wrapped = format!("fn snippet() {{\n{}\n}}", snippet.code);
+            Some(run_check(rustfmt(), &wrapped)?.map_err(|err| rust_error(&err, 1)))
+        }
+        "python" => Some(run_check(python(), &snippet.code)?.map_err(|err| python_error(&err))),
+        _ => None,
+    }
+}
+
+/// `answer` followed by one line per check.
+pub fn annotate_code_checks(answer: String, checks: &[SnippetCheck]) -> String {
+    if checks.is_empty() {
+        return answer;
+    }
+    let mut out = format!("{}\n\nCode check:", answer.trim_end());
+    for check in checks {
+        let outcome = match &check.result {
+            Ok(()) => "snippet compiles".to_string(),
+            Err(err) => err.clone(),
+        };
+        out.push_str(&format!("\n- {} snippet {}: {}", check.language, check.index, outcome));
+    }
+    out
+}
+
+fn rustfmt() -> Command {
+    let mut command = Command::new("rustfmt");
+    command.args(["--edition", "2021", "--emit", "stdout"]);
+    command
+}
+
+fn python() -> Command {
+    let mut command = Command::new(if cfg!(windows) { "python" } else { "python3" });
+    command.args(["-c", "import sys; compile(sys.stdin.read(), '<snippet>', 'exec')"]);
+    command
+}
+
+// Run a checker on `code` from stdin, with a scrubbed environment, the temp
+// directory as its working directory, and a time limit. The checkers only
+// parse; nothing is built or executed.
+fn run_check(mut command: Command, code: &str) -> Option<Result<(), String>> {
+    command.env_clear().current_dir(std::env::temp_dir());
+    for var in ["PATH", "SYSTEMROOT", "HOME", "RUSTUP_HOME", "RUSTUP_TOOLCHAIN", "CARGO_HOME"] {
+        if let Some(value) = std::env::var_os(var) {
+            command.env(var, value);
+        }
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(code.as_bytes());
+    }
+    let mut stderr = child.stderr.take();
+    let drain = std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(pipe) = stderr.as_mut() {
+            let _ = pipe.read_to_string(&mut text);
+        }
+        text
+    });
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > CHECK_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Some(Err(format!("check timed out after {}s", CHECK_TIMEOUT.as_secs())));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(_) => return None,
+        }
+    };
+    let stderr = drain.join().unwrap_or_default();
+    Some(if status.success() { Ok(()) } else { Err(stderr) })
+}
+
+// First `error...` line of rustfmt output with its line number, shifted up
+// by the `offset` lines added around the snippet.
+fn rust_error(stderr: &str, offset: usize) -> String {
+    let message = stderr
+        .lines()
+        .find(|l| l.starts_with("error"))
+        .map(|l| l.trim_start_matches("error:").trim().to_string())
+        .unwrap_or_else(|| "does not parse".to_string());
+    let line = stderr
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("--> "))
+        .and_then(|location| location.rsplit(':').nth(1))
+        .and_then(|n| n.parse::<usize>().ok())
+        .map(|n| n.saturating_sub(offset).max(1));
+    match line {
+        Some(line) => format!("{} (line {})", message, line),
+        None => message,
+    }
+}
+
+// The exception line of a Python traceback with the snippet line it names.
+fn python_error(stderr: &str) -> String {
+    let message = stderr
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("does not compile")
+        .to_string();
+    let line = stderr
+        .lines()
+        .filter(|l| l.contains("\"<snippet>\""))
+        .filter_map(|l| l.split("line ").nth(1))
+        .filter_map(|n| n.trim().split(|c: char| !c.is_ascii_digit()).next()?.parse::<usize>().ok())
+        .last();
+    match line {
+        Some(line) => format!("{} (line {})", message, line),
+        None => message,
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::embed_query::embed_query_async;
+use crate::mmr::cosine;
+use crate::runtime::block_on;
+use crate::timestamps::now_secs;
+
+/// What a collection holds, embedded once so questions can be routed to it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CollectionDescription {
+    pub description: String,
+    /// `OLLAMA_EMBED_MODEL` when it was described; routing skips
+    /// descriptions embedded by another model.
+    pub embed_model: String,
+    pub vector: Vec<f32>,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub described_at: u64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct DescriptionFile {
+    #[serde(default)]
+    collections: BTreeMap<String, CollectionDescription>,
+}
+
+/// Where a question should be asked.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Route {
+    /// Described collections by similarity to the question, best first.
+    pub scores: Vec<(String, f32)>,
+    /// The best collection when it wins clearly; `None` means ask the user.
+    pub chosen: Option<String>,
+}
+
+pub fn collection_descriptions_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.config_dir).join("collections.json")
+}
+
+/// Every described collection by name.
+pub fn collection_descriptions(cfg: &Config) -> BTreeMap<String, CollectionDescription> {
+    fs::read_to_string(collection_descriptions_path(cfg))
+        .ok()
+        .and_then(|text| serde_json::from_str::<DescriptionFile>(&text).ok())
+        .map(|file| file.collections)
+        .unwrap_or_default()
+}
+
+fn save(cfg: &Config, collections: BTreeMap<String, CollectionDescription>) -> Result<(), String> {
+    let path = collection_descriptions_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(&DescriptionFile { collections }).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Embed `description` and store it for `collection`, replacing any earlier
+/// description.
+pub fn describe_collection(cfg: &Config, collection: &str, description: &str) -> Result<CollectionDescription, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: describing collections is disabled.".to_string());
+    }
+    let description = description.trim();
+    if collection.trim().is_empty() || description.is_empty() {
+        return Err("A collection name and a description are required".to_string());
+    }
+    let vector = block_on(embed_query_async(cfg, description))?;
+    if vector.is_empty() {
+        return Err(format!("{} returned no embedding", cfg.embed_model));
+    }
+    let entry = CollectionDescription {
+        description: description.to_string(),
+        embed_model: cfg.embed_model.clone(),
+        vector,
+        described_at: now_secs(),
+    };
+    let mut collections = collection_descriptions(cfg);
+    collections.insert(collection.to_string(), entry.clone());
+    save(cfg, collections)?;
+    Ok(entry)
+}
+
+/// Forget `collection`'s description, returning whether it had one.
+pub fn remove_collection_description(cfg: &Config, collection: &str) -> Result<bool, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: describing collections is disabled.".to_string());
+    }
+    let mut collections = collection_descriptions(cfg);
+    if collections.remove(collection).is_none() {
+        return Ok(false);
+    }
+    save(cfg, collections)?;
+    Ok(true)
+}
+
+/// The best of `scores` (sorted best first) when it is at least `min_score`
+/// and leads the runner-up by at least `margin`.
+pub fn pick_collection(scores: &[(String, f32)], min_score: f32, margin: f32) -> Option<String> {
+    let (best, score) = scores.first()?;
+    let runner_up = scores.get(1).map(|(_, s)| *s).unwrap_or(f32::NEG_INFINITY);
+    (*score >= min_score && score - runner_up >= margin).then(|| best.clone())
+}
+
+/// Score `question` against every collection described with the current
+/// embedding model, and choose one when `RAG_ROUTER_MIN_SCORE` and
+/// `RAG_ROUTER_MARGIN` say it is a clear winner.
+pub fn route_question(cfg: &Config, question: &str) -> Result<Route, String> {
+    let collections: Vec<(String, CollectionDescription)> = collection_descriptions(cfg)
+        .into_iter()
+        .filter(|(_, d)| d.embed_model == cfg.embed_model)
+        .collect();
+    if collections.len() < 2 {
+        return Err(format!(
+            "Routing needs at least two collections described with {}; run `aicli collections describe`",
+            cfg.embed_model
+        ));
+    }
+    let vector = block_on(embed_query_async(cfg, question))?;
+    let mut scores: Vec<(String, f32)> =
+        collections.into_iter().map(|(name, d)| (name, cosine(&vector, &d.vector))).collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let chosen = pick_collection(&scores, cfg.router_min_score, cfg.router_margin);
+    Ok(Route { scores, chosen })
+}
@@ -1,35 +1,328 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
 use std::env;
 
+use crate::context_split::{parse_context_split, ContextSplit};
+use crate::distance::normalize_distance;
+use crate::git_source::{collection_for_remote, git_remote_and_branch};
+use crate::mcp_auth::{McpAuth, OAuthDevice};
+use crate::ollama_pool::parse_ollama_urls;
+use crate::panels::{parse_panels, PanelSpec};
+use crate::paths::{default_dir, DirKind};
+use crate::platform::default_shell;
+use crate::share::{parse_share_targets, ShareTarget};
+use crate::viewer::{parse_viewers, Viewer};
+
+/// USD prices per million tokens for one chat model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Ollama sampling options, sent as the chat request's `options`. Unset
+/// fields fall back to the model's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GenerationOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Most tokens the model may generate for one reply.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
+impl GenerationOptions {
+    /// `self` with every field set in `overrides` replaced.
+    pub fn merged(&self, overrides: &GenerationOptions) -> Self {
+        Self {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            num_ctx: overrides.num_ctx.or(self.num_ctx),
+            seed: overrides.seed.or(self.seed),
+            num_predict: overrides.num_predict.or(self.num_predict),
+            stop: if overrides.stop.is_empty() { self.stop.clone() } else { overrides.stop.clone() },
+        }
+    }
+}
+
+/// Chunk size and overlap in characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkParams {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl ChunkParams {
+    /// The parameters the chunker actually uses: an overlap that would not
+    /// advance is cut to a quarter of the size.
+    pub fn effective(self) -> Self {
+        if self.size > 0 && self.overlap >= self.size {
+            Self { size: self.size, overlap: self.size / 4 }
+        } else {
+            self
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub source_dir: String,
     pub include_exts: Vec<String>,
     pub exclude_dirs: Vec<String>,
     pub max_file_bytes: u64,
+    pub skip_generated: bool,
+    pub keep_generated: Vec<String>,
+    pub max_chunks_per_file: Option<usize>,
+    pub max_chunks_per_dir: Option<usize>,
+    pub max_chunks_total: Option<usize>,
+    /// `RAG_EMBED_RETRIES`: extra passes at the end of an indexing run over
+    /// files whose embeddings failed (default 1); what still fails stays
+    /// quarantined in the manifest.
+    pub embed_retries: usize,
+    pub crawl_depth: usize,
+    pub crawl_max_pages: usize,
+    pub crawl_same_domain: bool,
     pub chunk_size: usize,
     pub chunk_overlap: usize,
+    /// Per-extension chunk parameters keyed by lowercase `.ext`.
+    pub chunk_overrides: HashMap<String, ChunkParams>,
+    pub chunk_context_lines: usize,
+    pub context_budget: Option<usize>,
+    /// `RAG_CONTEXT_SPLIT`: shares of the context budget for retrieved
+    /// chunks, pinned files, and conversation memory; see `allocate_context`.
+    pub context_split: Option<ContextSplit>,
     pub ollama_url: String,
+    pub ollama_urls: Vec<String>,
+    pub ollama_balance: String,
+    pub ollama_health_interval_secs: u64,
+    pub ollama_api_key: String,
+    pub ollama_headers: Vec<(String, String)>,
     pub embed_model: String,
     pub chat_model: String,
+    pub title_model: String,
+    pub generation: GenerationOptions,
+    pub controller_generation: GenerationOptions,
+    pub llm_provider: String,
+    pub price_table: HashMap<String, ModelPrice>,
+    pub daily_budget_usd: Option<f64>,
+    pub replay_file: String,
+    pub record_file: String,
+    pub trace: bool,
+    pub http_timeout_secs: u64,
+    pub embed_timeout_secs: u64,
+    pub generate_timeout_secs: u64,
+    pub query_timeout_secs: u64,
+    pub http_retries: u32,
+    pub stream: bool,
     pub qdrant_url: String,
+    pub qdrant_api_key: String,
+    pub qdrant_headers: Vec<(String, String)>,
+    /// `QDRANT_READ_ONLY`: the index is maintained elsewhere and only read,
+    /// so indexing and other writes are refused. Also set at startup when
+    /// Qdrant refuses writes; see `store_read_only`.
+    pub qdrant_read_only: bool,
     pub collection: String,
     pub distance: String,
+    pub recreate_collection: bool,
+    pub qdrant_on_disk_payload: bool,
+    pub qdrant_vectors_on_disk: bool,
+    pub qdrant_quantization: String,
+    pub qdrant_quantile: f32,
+    pub qdrant_pq_compression: String,
+    pub qdrant_hnsw_m: Option<usize>,
+    pub qdrant_hnsw_ef_construct: Option<usize>,
+    pub payload_indexes: Vec<(String, String)>,
     pub top_k: usize,
+    pub min_score: Option<f32>,
+    pub mmr_lambda: Option<f32>,
+    pub doc_type_boost: f32,
+    pub reranker: String,
+    pub rerank_model: String,
+    pub query_expansion: String,
+    /// `RAG_PIPELINE`: retrieval stages to run in place of the fixed order;
+    /// see `parse_pipeline`. Empty keeps the fixed order.
+    pub retrieval_pipeline: String,
+    pub empty_context_fallback: bool,
+    pub broaden_on_empty: bool,
+    pub answer_pipeline: String,
+    pub best_of: usize,
+    pub best_of_temperature: f32,
+    pub best_of_budget: Option<usize>,
+    pub best_of_judge: String,
     pub system_prompt: String,
     pub hybrid_system_prompt: String,
+    pub answer_language: String,
+    pub answer_tone: String,
+    /// `RAG_ANSWER_CACHE`: serve repeated first questions from the answer
+    /// cache; entries expire after `RAG_ANSWER_CACHE_TTL_SECS`.
+    pub answer_cache: bool,
+    pub answer_cache_ttl_secs: u64,
+    /// `RAG_BACKGROUND_SUMMARIES`: summarize indexed files while the TUI is
+    /// idle for `RAG_SUMMARY_IDLE_SECS`, spending at most
+    /// `RAG_SUMMARY_TOKENS_PER_MIN` estimated tokens a minute.
+    pub background_summaries: bool,
+    pub summary_tokens_per_min: usize,
+    pub summary_idle_secs: u64,
+    /// `RAG_COLLECTION_ROUTER`: before each TUI question, score it against
+    /// the collections described with `aicli collections describe`. The best
+    /// is used when it reaches `RAG_ROUTER_MIN_SCORE` and leads the next by
+    /// `RAG_ROUTER_MARGIN`; otherwise the user picks.
+    pub collection_router: bool,
+    pub router_min_score: f32,
+    pub router_margin: f32,
     pub mcp_url: String,
     pub mcp_command: String,
     pub mcp_args: Vec<String>,
+    /// `MCP_CONFIG`: path to an `mcp.json` with an `mcpServers` object, used
+    /// in place of `MCP_URL` and `MCP_COMMAND`; see `parse_mcp_servers`.
+    pub mcp_config: String,
+    pub mcp_cache: bool,
+    /// `MCP_TOOL_ALLOWLIST` / `MCP_TOOL_DENYLIST`: globs naming the server
+    /// tools the agent may use; see `ToolFilter`.
+    pub mcp_tool_allowlist: Vec<String>,
+    pub mcp_tool_denylist: Vec<String>,
+    /// `MCP_MAX_RESTARTS`: how often a stdio server that exits is restarted
+    /// before aicli gives up on it.
+    pub mcp_max_restarts: u32,
+    /// `MCP_HEARTBEAT_SECS`: ping interval for a running stdio server; `0`
+    /// turns the heartbeat off.
+    pub mcp_heartbeat_secs: u64,
+    /// Credentials for the `MCP_URL` server: `MCP_AUTH_TOKEN`,
+    /// `MCP_AUTH_TOKEN_COMMAND`, or the `MCP_OAUTH_*` device flow.
+    pub mcp_auth: McpAuth,
     pub agent_max_steps: usize,
+    pub agent_mode: String,
+    pub agent_strategy: String,
+    pub agent_reflect: bool,
+    /// `RAG_AGENT_MAX_REPAIRS`: how many times a malformed controller reply
+    /// is sent back for correction before the step is lost.
+    pub agent_max_repairs: usize,
+    /// `RAG_AGENT_JSON_SCHEMA`: constrain controller replies to
+    /// `decision_schema` rather than plain JSON mode. Turn off for Ollama
+    /// versions without structured output (before 0.5).
+    pub agent_json_schema: bool,
+    pub agent_step_timeout_secs: u64,
+    pub agent_total_timeout_secs: u64,
+    pub memory_compact: bool,
+    pub memory_max_tokens: Option<usize>,
+    pub memory_keep_recent: usize,
+    /// `AICLI_DATA_DIR`: sessions, manifests, snapshots, history, and logs.
+    /// Defaults to the platform data directory (`~/.local/share/aicli` on
+    /// Linux).
+    pub data_dir: String,
+    /// `AICLI_CONFIG_DIR`: snippets, templates, and collection descriptions.
+    /// Defaults to `AICLI_DATA_DIR` when that is set, so a single directory
+    /// keeps working, else the platform config directory.
+    pub config_dir: String,
+    /// `AICLI_CACHE_DIR`: the answer, MCP, and summary caches. Defaults like
+    /// `config_dir`, with the platform cache directory.
+    pub cache_dir: String,
+    pub safe_mode: bool,
+    pub telemetry: bool,
+    /// `AICLI_SHELL`: the shell that runs Command mode and share commands.
+    pub shell: String,
+    /// `RAG_ACCESS_LOG`: record which files and chunks answers retrieve and cite.
+    pub access_log: bool,
+    /// `RAG_VERIFY_CODE`: syntax-check Rust and Python snippets in answers
+    /// and note the result under the answer.
+    pub verify_code: bool,
+    pub share_targets: Vec<ShareTarget>,
+    /// `AICLI_VIEWERS`: external programs `/view` can open the answer in.
+    pub viewers: Vec<Viewer>,
+    /// `AICLI_PANELS`: read-only TUI panels filled by external commands.
+    pub panels: Vec<PanelSpec>,
+    /// `AICLI_CLIPBOARD_MAX_BYTES`: the most of the clipboard `/paste-context`
+    /// pins for the next question; longer text keeps its end.
+    pub clipboard_max_bytes: usize,
+    /// `AICLI_HISTORY_SIZE`: prompts kept in the TUI's input history;
+    /// `0` keeps none and writes nothing.
+    pub history_size: usize,
+    /// `AICLI_PROMPT_LINT`: what the TUI does with a prompt that usually
+    /// fails (no words, only a path, a pasted log with no question): `hint`
+    /// (default) explains and sends it on a second Enter, `wrap` rewrites it
+    /// into a question, `off` sends it as typed.
+    pub prompt_lint: String,
+    /// `AICLI_TIME_ZONE`: `local` (default), `UTC`, or an offset such as
+    /// `+05:30` for times shown in the TUI, reports, and exports. Stored
+    /// times are always RFC3339 UTC.
+    pub time_zone: String,
+    /// `AICLI_LOCALE`, else `LC_ALL`, `LC_TIME`, or `LANG`: how shown dates
+    /// are written; see `date_style`.
+    pub locale: String,
+    pub focus_paths: Vec<String>,
+    /// Files searched next to the collection without being indexed; see
+    /// `attach_file`.
+    pub attachments: Vec<String>,
+    pub local_tools: Vec<String>,
+    pub shell_allow: Vec<String>,
+    /// `AICLI_FETCH_ALLOW`: hosts `fetch_url` may reach even though they
+    /// resolve to a loopback, private, or link-local address.
+    pub fetch_allow: Vec<String>,
+    /// Variables set with the TUI's `/env KEY=VALUE` for this session;
+    /// Command mode and the shell tool run with them. Never read from the
+    /// environment.
+    pub session_env: BTreeMap<String, String>,
+    /// Sandbox root for local tools; empty means the git root of
+    /// `source_dir`, or `source_dir` itself outside a repository.
+    pub workspace_dir: String,
+    /// Extra directories local tools may use besides the sandbox root.
+    pub sandbox_extra: Vec<String>,
+    /// `AICLI_SERVE_WORKERS`: connections `aicli serve` handles at once;
+    /// more wait in a short queue, and the rest get a 503.
+    pub serve_workers: usize,
+    /// `AICLI_SERVE_TIMEOUT_SECS`: how long `aicli serve` waits on a socket
+    /// read or write before dropping the connection.
+    pub serve_timeout_secs: u64,
+    /// `AICLI_SERVE_TOKEN`: bearer token `aicli serve` requires; needed to
+    /// listen on anything but a loopback address.
+    pub serve_token: String,
+    /// `AICLI_SERVE_CORS_ORIGIN`: origin browsers may call `aicli serve`
+    /// from, such as `https://app.example.com` or `*`; none when empty.
+    pub serve_cors_origin: String,
+    /// `AICLI_SERVE_INDEX_ROOTS`: directories (and `http(s)://` prefixes)
+    /// the REST `/index` endpoint may index; `source_dir` when empty.
+    pub serve_index_roots: Vec<String>,
+    /// `AICLI_SERVE_LOCAL_TOOLS`: let agents run for `aicli serve` clients
+    /// use the local tools (`read_file`, `grep`, `shell`, ...); off by
+    /// default, since every client could then use them.
+    pub serve_local_tools: bool,
+    /// `AICLI_SERVE_MCP_TOOLS`: let agents run for `aicli serve` clients
+    /// use the configured MCP servers; off by default, for the same reason.
+    pub serve_mcp_tools: bool,
 }
 
 impl Config {
+    /// Chunk parameters for `path`: its extension's `RAG_CHUNK_OVERRIDES`
+    /// entry, or `RAG_CHUNK_SIZE`/`RAG_CHUNK_OVERLAP`.
+    pub fn chunk_params(&self, path: &str) -> ChunkParams {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let params = name
+            .rfind('.')
+            .and_then(|i| self.chunk_overrides.get(&name[i..].to_ascii_lowercase()))
+            .copied()
+            .unwrap_or(ChunkParams {
+                size: self.chunk_size,
+                overlap: self.chunk_overlap,
+            });
+        params.effective()
+    }
+
     pub fn from_env() -> Self {
         // Load .env if present so MCP and model config work without manual `source .env`.
         let _ = dotenvy::dotenv();
         let include_exts = env::var("RAG_INCLUDE_EXTS").unwrap_or_else(|_| {
-            ".rs,.md,.txt,.toml,.json,.yaml,.yml,.py,.js,.ts,.tsx,.html,.css".to_string()
+            ".rs,.md,.txt,.toml,.json,.yaml,.yml,.py,.js,.ts,.tsx,.html,.css,.ipynb".to_string()
         });
+        let ollama_urls = parse_ollama_urls(&env::var("OLLAMA_URL").unwrap_or_default());
+        let data_dir = env::var("AICLI_DATA_DIR").ok();
         Self {
             source_dir: env::var("RAG_SOURCE_DIR").unwrap_or_else(|_| "./".to_string()),
             include_exts: include_exts.split(',').map(|s| s.trim().to_string()).collect(),
@@ -42,6 +335,33 @@ impl Config {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(500_000),
+            skip_generated: env::var("RAG_SKIP_GENERATED")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            keep_generated: env::var("RAG_KEEP_GENERATED")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            max_chunks_per_file: env::var("RAG_MAX_CHUNKS_PER_FILE").ok().and_then(|v| v.parse().ok()),
+            max_chunks_per_dir: env::var("RAG_MAX_CHUNKS_PER_DIR").ok().and_then(|v| v.parse().ok()),
+            max_chunks_total: env::var("RAG_MAX_CHUNKS_TOTAL").ok().and_then(|v| v.parse().ok()),
+            embed_retries: env::var("RAG_EMBED_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1),
+            crawl_depth: env::var("RAG_CRAWL_DEPTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            crawl_max_pages: env::var("RAG_CRAWL_MAX_PAGES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+            crawl_same_domain: env::var("RAG_CRAWL_SAME_DOMAIN")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
             chunk_size: env::var("RAG_CHUNK_SIZE")
                 .ok()
                 .and_then(|v| v.parse().ok())
@@ -50,22 +370,162 @@ impl Config {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(200),
-            ollama_url: env::var("OLLAMA_URL").unwrap_or_else(|_| "http://localhost:11434".to_string()),
+            chunk_overrides: parse_chunk_overrides(&env::var("RAG_CHUNK_OVERRIDES").unwrap_or_default()),
+            chunk_context_lines: env::var("RAG_CHUNK_CONTEXT_LINES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            context_budget: env::var("RAG_CONTEXT_BUDGET").ok().and_then(|v| v.parse().ok()),
+            context_split: parse_context_split(&env::var("RAG_CONTEXT_SPLIT").unwrap_or_default()),
+            ollama_url: ollama_urls.first().cloned().unwrap_or_else(|| "http://localhost:11434".to_string()),
+            ollama_urls,
+            ollama_balance: env::var("OLLAMA_BALANCE").unwrap_or_else(|_| "round-robin".to_string()),
+            ollama_health_interval_secs: env::var("OLLAMA_HEALTH_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            ollama_api_key: env::var("OLLAMA_API_KEY").unwrap_or_default(),
+            ollama_headers: parse_headers(&env::var("OLLAMA_HEADERS").unwrap_or_default()),
             embed_model: env::var("OLLAMA_EMBED_MODEL").unwrap_or_else(|_| "nomic-embed-text".to_string()),
             chat_model: env::var("OLLAMA_CHAT_MODEL").unwrap_or_else(|_| "qwen2.5-coder:14b".to_string()),
+            title_model: env::var("RAG_TITLE_MODEL")
+                .or_else(|_| env::var("OLLAMA_CHAT_MODEL"))
+                .unwrap_or_else(|_| "qwen2.5-coder:14b".to_string()),
+            generation: GenerationOptions {
+                temperature: env::var("RAG_TEMPERATURE").ok().and_then(|v| v.parse().ok()),
+                top_p: env::var("RAG_TOP_P").ok().and_then(|v| v.parse().ok()),
+                num_ctx: env::var("RAG_NUM_CTX").ok().and_then(|v| v.parse().ok()),
+                seed: env::var("RAG_SEED").ok().and_then(|v| v.parse().ok()),
+                num_predict: env::var("RAG_NUM_PREDICT").ok().and_then(|v| v.parse().ok()),
+                stop: env::var("RAG_STOP")
+                    .unwrap_or_default()
+                    .split('|')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect(),
+            },
+            controller_generation: GenerationOptions {
+                temperature: env::var("RAG_CONTROLLER_TEMPERATURE").ok().and_then(|v| v.parse().ok()),
+                ..GenerationOptions::default()
+            },
+            llm_provider: env::var("RAG_LLM_PROVIDER").unwrap_or_else(|_| "ollama".to_string()),
+            price_table: parse_price_table(&env::var("RAG_PRICE_TABLE").unwrap_or_default()),
+            daily_budget_usd: env::var("RAG_COST_BUDGET").ok().and_then(|v| v.parse().ok()),
+            replay_file: env::var("RAG_REPLAY_FILE").unwrap_or_default(),
+            record_file: env::var("RAG_RECORD_FILE").unwrap_or_default(),
+            trace: env::var("RAG_TRACE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            http_timeout_secs: env::var("RAG_HTTP_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(120),
+            embed_timeout_secs: env::var("RAG_EMBED_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            generate_timeout_secs: env::var("RAG_GENERATE_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            query_timeout_secs: env::var("RAG_QUERY_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+            http_retries: env::var("RAG_HTTP_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            stream: env::var("RAG_STREAM")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
             qdrant_url: env::var("QDRANT_URL").unwrap_or_else(|_| "http://localhost:6333".to_string()),
-            collection: env::var("QDRANT_COLLECTION").unwrap_or_else(|_| {
-                let repo = current_folder_name().unwrap_or_else(|| "default".to_string());
-                format!("{}_rag_chunks", sanitize_collection_name(&repo))
-            }),
-            distance: env::var("QDRANT_DISTANCE").unwrap_or_else(|_| "Cosine".to_string()),
+            qdrant_api_key: env::var("QDRANT_API_KEY").unwrap_or_default(),
+            qdrant_headers: parse_headers(&env::var("QDRANT_HEADERS").unwrap_or_default()),
+            qdrant_read_only: env::var("QDRANT_READ_ONLY")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            collection: env::var("QDRANT_COLLECTION").unwrap_or_else(|_| default_collection()),
+            distance: env::var("QDRANT_DISTANCE")
+                .map(|v| normalize_distance(&v).map(str::to_string).unwrap_or(v))
+                .unwrap_or_else(|_| "Cosine".to_string()),
+            recreate_collection: env::var("RAG_RECREATE_COLLECTION")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            qdrant_on_disk_payload: env::var("QDRANT_ON_DISK_PAYLOAD")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            qdrant_vectors_on_disk: env::var("QDRANT_VECTORS_ON_DISK")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            qdrant_quantization: env::var("QDRANT_QUANTIZATION").unwrap_or_default(),
+            qdrant_quantile: env::var("QDRANT_QUANTILE").ok().and_then(|v| v.parse().ok()).unwrap_or(0.99),
+            qdrant_pq_compression: env::var("QDRANT_PQ_COMPRESSION").unwrap_or_else(|_| "x16".to_string()),
+            qdrant_hnsw_m: env::var("QDRANT_HNSW_M").ok().and_then(|v| v.parse().ok()),
+            qdrant_hnsw_ef_construct: env::var("QDRANT_HNSW_EF_CONSTRUCT").ok().and_then(|v| v.parse().ok()),
+            payload_indexes: parse_payload_indexes(&env::var("RAG_PAYLOAD_INDEXES").unwrap_or_default()),
             top_k: env::var("RAG_TOP_K").ok().and_then(|v| v.parse().ok()).unwrap_or(5),
+            min_score: env::var("RAG_MIN_SCORE").ok().and_then(|v| v.parse().ok()),
+            mmr_lambda: env::var("RAG_MMR_LAMBDA").ok().and_then(|v| v.parse().ok()),
+            doc_type_boost: env::var("RAG_DOC_TYPE_BOOST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.05),
+            reranker: env::var("RAG_RERANKER").unwrap_or_else(|_| "off".to_string()),
+            rerank_model: env::var("RAG_RERANK_MODEL").unwrap_or_else(|_| "bge-reranker-base".to_string()),
+            query_expansion: env::var("RAG_QUERY_EXPANSION").unwrap_or_else(|_| "off".to_string()),
+            retrieval_pipeline: env::var("RAG_PIPELINE").unwrap_or_default(),
+            empty_context_fallback: env::var("RAG_EMPTY_CONTEXT")
+                .map(|v| v.trim().eq_ignore_ascii_case("fallback"))
+                .unwrap_or(false),
+            broaden_on_empty: env::var("RAG_BROADEN_ON_EMPTY")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            answer_pipeline: env::var("RAG_ANSWER_PIPELINE").unwrap_or_else(|_| "agent".to_string()),
+            best_of: env::var("RAG_BEST_OF").ok().and_then(|v| v.parse().ok()).unwrap_or(1),
+            best_of_temperature: env::var("RAG_BEST_OF_TEMPERATURE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.9),
+            best_of_budget: env::var("RAG_BEST_OF_BUDGET").ok().and_then(|v| v.parse().ok()),
+            best_of_judge: env::var("RAG_BEST_OF_JUDGE").unwrap_or_else(|_| "select".to_string()),
             system_prompt: env::var("RAG_SYSTEM_PROMPT").unwrap_or_else(|_| {
                 "You are a helpful coding assistant. Use only the provided context.".to_string()
             }),
             hybrid_system_prompt: env::var("RAG_HYBRID_SYSTEM_PROMPT").unwrap_or_else(|_| {
                 "You are a hybrid AI agent.\n\nYou can:\n- Retrieve knowledge from documents.\n- Call MCP tools.\n- Fetch MCP prompts.\n- Read MCP resources.\n- Answer directly if no external action is required.\n\nAlways respond in valid JSON with one action:\nretrieve | tool | prompt | resource | final\n\nDo not output plain text.".to_string()
             }),
+            answer_language: env::var("RAG_ANSWER_LANGUAGE").unwrap_or_default(),
+            answer_tone: env::var("RAG_ANSWER_TONE").unwrap_or_default(),
+            answer_cache: env::var("RAG_ANSWER_CACHE")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            answer_cache_ttl_secs: env::var("RAG_ANSWER_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(86_400),
+            background_summaries: env::var("RAG_BACKGROUND_SUMMARIES")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            summary_tokens_per_min: env::var("RAG_SUMMARY_TOKENS_PER_MIN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000),
+            summary_idle_secs: env::var("RAG_SUMMARY_IDLE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            collection_router: env::var("RAG_COLLECTION_ROUTER")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            router_min_score: env::var("RAG_ROUTER_MIN_SCORE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.3),
+            router_margin: env::var("RAG_ROUTER_MARGIN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.05),
             mcp_url: env::var("MCP_URL").unwrap_or_default(),
             mcp_command: env::var("MCP_COMMAND").unwrap_or_default(),
             mcp_args: env::var("MCP_ARGS")
@@ -73,12 +533,270 @@ impl Config {
                 .split_whitespace()
                 .map(|s| s.to_string())
                 .collect(),
+            mcp_config: env::var("MCP_CONFIG").unwrap_or_default(),
+            mcp_cache: env::var("MCP_CACHE")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            mcp_tool_allowlist: env::var("MCP_TOOL_ALLOWLIST")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            mcp_tool_denylist: env::var("MCP_TOOL_DENYLIST")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            mcp_max_restarts: env::var("MCP_MAX_RESTARTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            mcp_heartbeat_secs: env::var("MCP_HEARTBEAT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            mcp_auth: McpAuth::from_parts(
+                &env::var("MCP_AUTH_TOKEN").unwrap_or_default(),
+                &env::var("MCP_AUTH_TOKEN_COMMAND").unwrap_or_default(),
+                OAuthDevice {
+                    device_url: env::var("MCP_OAUTH_DEVICE_URL").unwrap_or_default(),
+                    token_url: env::var("MCP_OAUTH_TOKEN_URL").unwrap_or_default(),
+                    client_id: env::var("MCP_OAUTH_CLIENT_ID").unwrap_or_default(),
+                    scope: env::var("MCP_OAUTH_SCOPE").unwrap_or_default(),
+                },
+            ),
             agent_max_steps: env::var("RAG_AGENT_MAX_STEPS")
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(10),
+            agent_mode: env::var("RAG_AGENT_MODE").unwrap_or_else(|_| "hybrid".to_string()),
+            agent_strategy: env::var("RAG_AGENT_STRATEGY").unwrap_or_else(|_| "loop".to_string()),
+            agent_reflect: env::var("RAG_AGENT_REFLECT")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            agent_max_repairs: env::var("RAG_AGENT_MAX_REPAIRS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+            agent_json_schema: env::var("RAG_AGENT_JSON_SCHEMA")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            agent_step_timeout_secs: env::var("RAG_AGENT_STEP_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            agent_total_timeout_secs: env::var("RAG_AGENT_TOTAL_TIMEOUT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+            memory_compact: env::var("RAG_MEMORY_COMPACT")
+                .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            memory_max_tokens: env::var("RAG_MEMORY_MAX_TOKENS").ok().and_then(|v| v.parse().ok()),
+            memory_keep_recent: env::var("RAG_MEMORY_KEEP_RECENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(6),
+            config_dir: env::var("AICLI_CONFIG_DIR")
+                .ok()
+                .or_else(|| data_dir.clone())
+                .unwrap_or_else(|| default_dir(DirKind::Config)),
+            cache_dir: env::var("AICLI_CACHE_DIR")
+                .ok()
+                .or_else(|| data_dir.clone())
+                .unwrap_or_else(|| default_dir(DirKind::Cache)),
+            data_dir: data_dir.unwrap_or_else(|| default_dir(DirKind::Data)),
+            safe_mode: env::var("AICLI_SAFE_MODE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            telemetry: env::var("AICLI_TELEMETRY")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            access_log: env::var("RAG_ACCESS_LOG")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            verify_code: env::var("RAG_VERIFY_CODE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            shell: env::var("AICLI_SHELL")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(default_shell),
+            share_targets: parse_share_targets(&env::var("AICLI_SHARE_TARGETS").unwrap_or_default()),
+            viewers: parse_viewers(&env::var("AICLI_VIEWERS").unwrap_or_default()),
+            panels: parse_panels(&env::var("AICLI_PANELS").unwrap_or_default()),
+            clipboard_max_bytes: env::var("AICLI_CLIPBOARD_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(16_000),
+            history_size: env::var("AICLI_HISTORY_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+            prompt_lint: env::var("AICLI_PROMPT_LINT")
+                .map(|v| v.trim().to_ascii_lowercase())
+                .unwrap_or_else(|_| "hint".to_string()),
+            time_zone: env::var("AICLI_TIME_ZONE").unwrap_or_else(|_| "local".to_string()),
+            locale: ["AICLI_LOCALE", "LC_ALL", "LC_TIME", "LANG"]
+                .iter()
+                .filter_map(|var| env::var(var).ok())
+                .find(|v| !v.trim().is_empty())
+                .unwrap_or_default(),
+            focus_paths: env::var("RAG_FOCUS_PATHS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            attachments: env::var("RAG_ATTACH")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            local_tools: env::var("AICLI_LOCAL_TOOLS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty() && s != "none")
+                .collect(),
+            shell_allow: env::var("AICLI_SHELL_ALLOW")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            fetch_allow: env::var("AICLI_FETCH_ALLOW")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            session_env: BTreeMap::new(),
+            workspace_dir: env::var("AICLI_WORKSPACE").unwrap_or_default(),
+            sandbox_extra: env::var("AICLI_SANDBOX_EXTRA")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            serve_workers: env::var("AICLI_SERVE_WORKERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(8),
+            serve_timeout_secs: env::var("AICLI_SERVE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            serve_token: env::var("AICLI_SERVE_TOKEN").unwrap_or_default(),
+            serve_cors_origin: env::var("AICLI_SERVE_CORS_ORIGIN").unwrap_or_default(),
+            serve_index_roots: env::var("AICLI_SERVE_INDEX_ROOTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            serve_local_tools: env::var("AICLI_SERVE_LOCAL_TOOLS")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            serve_mcp_tools: env::var("AICLI_SERVE_MCP_TOOLS")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Parse `.ext=size/overlap,...`, e.g. `.md=800/120,.rs=1600/240`. The
+/// dot is optional; an entry without `/overlap` keeps the size only, with
+/// the overlap at a sixth of it. Malformed entries are skipped.
+pub fn parse_chunk_overrides(raw: &str) -> HashMap<String, ChunkParams> {
+    let mut overrides = HashMap::new();
+    for entry in raw.split(',') {
+        let Some((ext, params)) = entry.split_once('=') else {
+            continue;
+        };
+        let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+        if ext.is_empty() {
+            continue;
         }
+        let (size, overlap) = match params.split_once('/') {
+            Some((size, overlap)) => (size.trim().parse().ok(), overlap.trim().parse().ok()),
+            None => {
+                let size: Option<usize> = params.trim().parse().ok();
+                (size, size.map(|s| s / 6))
+            }
+        };
+        if let (Some(size), Some(overlap)) = (size, overlap) {
+            overrides.insert(format!(".{}", ext), ChunkParams { size, overlap });
+        }
+    }
+    overrides
+}
+
+/// Parse `model=input:output,...` where prices are USD per million tokens.
+pub fn parse_price_table(raw: &str) -> HashMap<String, ModelPrice> {
+    let mut table = HashMap::new();
+    for entry in raw.split(',') {
+        let Some((model, prices)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some((input, output)) = prices.split_once(':') else {
+            continue;
+        };
+        if let (Ok(input), Ok(output)) = (input.trim().parse(), output.trim().parse()) {
+            table.insert(
+                model.trim().to_string(),
+                ModelPrice {
+                    input_per_mtok: input,
+                    output_per_mtok: output,
+                },
+            );
+        }
+    }
+    table
+}
+
+/// Parse `Name=value,Other=value` into header pairs, skipping malformed entries.
+pub fn parse_headers(raw: &str) -> Vec<(String, String)> {
+    raw.split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+const PAYLOAD_SCHEMAS: &[&str] = &["keyword", "integer", "float", "bool", "text", "datetime", "uuid"];
+
+/// Parse `field[:schema],...` into payload indexes to create. `path` is
+/// always indexed as a keyword; fields default to `keyword`, and entries with
+/// an unknown schema are skipped.
+pub fn parse_payload_indexes(raw: &str) -> Vec<(String, String)> {
+    let mut indexes = vec![("path".to_string(), "keyword".to_string())];
+    for entry in raw.split(',') {
+        let (field, schema) = entry.split_once(':').unwrap_or((entry, "keyword"));
+        let (field, schema) = (field.trim(), schema.trim().to_ascii_lowercase());
+        if field.is_empty() || !PAYLOAD_SCHEMAS.contains(&schema.as_str()) {
+            continue;
+        }
+        if !indexes.iter().any(|(f, _)| f == field) {
+            indexes.push((field.to_string(), schema));
+        }
+    }
+    indexes
+}
+
+/// Named from the git remote and branch when the current directory is in
+/// a repository with a remote, otherwise `<folder>_rag_chunks`.
+fn default_collection() -> String {
+    let cwd = env::current_dir().ok();
+    if let Some((remote, branch)) = cwd.as_deref().and_then(git_remote_and_branch) {
+        return collection_for_remote(&remote, branch.as_deref());
     }
+    let repo = current_folder_name().unwrap_or_else(|| "default".to_string());
+    format!("{}_rag_chunks", sanitize_collection_name(&repo))
 }
 
 fn current_folder_name() -> Option<String> {
@@ -88,7 +806,7 @@ fn current_folder_name() -> Option<String> {
         .map(|s| s.to_string())
 }
 
-fn sanitize_collection_name(name: &str) -> String {
+pub(crate) fn sanitize_collection_name(name: &str) -> String {
     let mut out = String::new();
     for c in name.chars() {
         if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
@@ -0,0 +1,158 @@
+use crate::build_prompt::{context_budget_tokens, Message, TRIMMED_MARKER};
+use crate::config::Config;
+use crate::tokens::{estimate_tokens, truncate_to_tokens};
+
+/// Percent of the context budget for each kind of context, from
+/// `RAG_CONTEXT_SPLIT` (e.g. `chunks=60,pinned=20,memory=20`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContextSplit {
+    pub chunks: u32,
+    pub pinned: u32,
+    pub memory: u32,
+}
+
+/// Parse `chunks=N,pinned=N,memory=N`. Missing kinds get nothing and the
+/// shares are scaled to add up to 100. `None` when no share is positive.
+pub fn parse_context_split(raw: &str) -> Option<ContextSplit> {
+    let mut split = ContextSplit { chunks: 0, pinned: 0, memory: 0 };
+    for entry in raw.split(',') {
+        let Some((kind, share)) = entry.split_once('=') else {
+            continue;
+        };
+        let Ok(share) = share.trim().trim_end_matches('%').parse::<u32>() else {
+            continue;
+        };
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "chunks" => split.chunks = share,
+            "pinned" => split.pinned = share,
+            "memory" => split.memory = share,
+            _ => {}
+        }
+    }
+    let total = split.chunks + split.pinned + split.memory;
+    if total == 0 {
+        return None;
+    }
+    Some(ContextSplit {
+        chunks: split.chunks * 100 / total,
+        pinned: split.pinned * 100 / total,
+        memory: split.memory * 100 / total,
+    })
+}
+
+/// Tokens set aside for one kind of context and how many it took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Share {
+    pub budget: usize,
+    pub used: usize,
+    pub trimmed: bool,
+}
+
+/// How the context budget of one question was divided.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContextAllocation {
+    pub total: usize,
+    pub chunks: Share,
+    pub pinned: Share,
+    pub memory: Share,
+}
+
+impl ContextAllocation {
+    /// One line for the Context pane title, e.g.
+    /// `chunks 2100/3000 | pinned 900/1000 | memory 400/1000 (trimmed)`.
+    pub fn summary(&self) -> String {
+        let part = |name: &str, share: &Share| {
+            format!(
+                "{} {}/{}{}",
+                name,
+                share.used,
+                share.budget,
+                if share.trimmed { " (trimmed)" } else { "" }
+            )
+        };
+        format!(
+            "{} | {} | {}",
+            part("chunks", &self.chunks),
+            part("pinned", &self.pinned),
+            part("memory", &self.memory)
+        )
+    }
+}
+
+/// Pinned files and conversation history trimmed to their shares.
+#[derive(Clone, Debug)]
+pub struct SplitContext {
+    pub pinned: String,
+    pub history: Vec<Message>,
+    pub allocation: ContextAllocation,
+}
+
+/// Divide the context budget for `question` by `RAG_CONTEXT_SPLIT`. Pinned
+/// files are cut at the end of their share and the oldest history turns are
+/// dropped first. Retrieved chunks get their own share plus whatever the
+/// other two leave unused; `allocation.chunks.used` is for the caller to
+/// fill in once the answer is back. `None` when no split is configured.
+pub fn allocate_context(cfg: &Config, question: &str, pinned: &str, history: &[Message]) -> Option<SplitContext> {
+    let split = cfg.context_split?;
+    let total = context_budget_tokens(cfg, question);
+    let share_of = |percent: u32| total * percent as usize / 100;
+
+    let pinned_budget = share_of(split.pinned);
+    let pinned_cost = estimate_tokens(pinned);
+    let (pinned, pinned_share) = if pinned_cost <= pinned_budget {
+        (pinned.to_string(), Share { budget: pinned_budget, used: pinned_cost, trimmed: false })
+    } else {
+        let cut = truncate_to_tokens(pinned, pinned_budget.saturating_sub(estimate_tokens(TRIMMED_MARKER)));
+        let kept = if cut.is_empty() { String::new() } else { format!("{}{}", cut, TRIMMED_MARKER) };
+        let used = estimate_tokens(&kept);
+        (kept, Share { budget: pinned_budget, used, trimmed: true })
+    };
+
+    let memory_budget = share_of(split.memory);
+    let (history, memory_share) = trim_history(history, memory_budget);
+
+    let unused = pinned_share.budget.saturating_sub(pinned_share.used)
+        + memory_share.budget.saturating_sub(memory_share.used);
+    let chunks = Share {
+        budget: (share_of(split.chunks) + unused).min(total),
+        used: 0,
+        trimmed: false,
+    };
+    Some(SplitContext {
+        pinned,
+        history,
+        allocation: ContextAllocation {
+            total,
+            chunks,
+            pinned: pinned_share,
+            memory: memory_share,
+        },
+    })
+}
+
+// The most recent turns that fit in `budget`. When even the last turn is too
+// long it is cut rather than dropped, so a follow-up keeps what it refers to.
+fn trim_history(history: &[Message], budget: usize) -> (Vec<Message>, Share) {
+    let mut kept: Vec<Message> = Vec::new();
+    let mut used = 0usize;
+    let mut cut_last = false;
+    for message in history.iter().rev() {
+        let cost = estimate_tokens(&message.content);
+        if used + cost <= budget {
+            used += cost;
+            kept.push(message.clone());
+            continue;
+        }
+        let room = budget.saturating_sub(estimate_tokens(TRIMMED_MARKER));
+        if kept.is_empty() && room > 0 {
+            let content = format!("{}{}", truncate_to_tokens(&message.content, room), TRIMMED_MARKER);
+            used = estimate_tokens(&content);
+            kept.push(Message { role: message.role.clone(), content });
+            cut_last = true;
+        }
+        break;
+    }
+    kept.reverse();
+    let trimmed = cut_last || kept.len() < history.len();
+    (kept, Share { budget, used, trimmed })
+}
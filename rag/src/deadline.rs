@@ -0,0 +1,102 @@
+use std::future::Future;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::usage::{add_usage, take_usage};
+
+/// Overall time limit for answering one query (`RAG_QUERY_TIMEOUT`, in
+/// seconds; 0 turns it off). Backend calls made under it get their HTTP
+/// timeouts cut to the time left, so a stuck backend ends the query at the
+/// deadline instead of hanging it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Deadline {
+    at: Option<Instant>,
+    secs: u64,
+}
+
+impl Deadline {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self::after_secs(cfg.query_timeout_secs)
+    }
+
+    pub fn after_secs(secs: u64) -> Self {
+        Self {
+            at: (secs > 0).then(|| Instant::now() + Duration::from_secs(secs)),
+            secs,
+        }
+    }
+
+    /// No limit.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// The limit it was made with, in seconds; 0 for none.
+    pub fn secs(&self) -> u64 {
+        self.secs
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.at.map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_some_and(|left| left.is_zero())
+    }
+
+    /// Run `fut` until the deadline; `None` when the deadline came first.
+    pub async fn run<F: Future>(&self, fut: F) -> Option<F::Output> {
+        match self.remaining() {
+            Some(left) => tokio::time::timeout(left, fut).await.ok(),
+            None => Some(fut.await),
+        }
+    }
+
+    /// `cfg` with its HTTP, embedding, and generation timeouts capped at the
+    /// time left (at least one second).
+    pub fn limit_config(&self, cfg: &Config) -> Config {
+        let mut limited = cfg.clone();
+        if let Some(left) = self.remaining() {
+            let secs = left.as_secs_f64().ceil().max(1.0) as u64;
+            limited.http_timeout_secs = limited.http_timeout_secs.min(secs);
+            limited.embed_timeout_secs = limited.embed_timeout_secs.min(secs);
+            limited.generate_timeout_secs = limited.generate_timeout_secs.min(secs);
+        }
+        limited
+    }
+
+    /// Shown in place of (or after) the answer when the deadline passes.
+    pub fn timeout_message(&self) -> String {
+        format!(
+            "Timed out after {}s (RAG_QUERY_TIMEOUT) before the answer was complete. The context gathered so far is shown.",
+            self.secs
+        )
+    }
+}
+
+/// Run a blocking call on its own thread and stop waiting for it after
+/// `limit`. Tokens it used count toward the calling thread's query. A call
+/// that runs over keeps going in the background; its result and tokens are
+/// dropped.
+pub fn call_with_timeout<T: Send + 'static>(
+    limit: Option<Duration>,
+    call: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let Some(limit) = limit else {
+        return call();
+    };
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let result = call();
+        let _ = tx.send((result, take_usage()));
+    });
+    match rx.recv_timeout(limit) {
+        Ok((result, usage)) => {
+            add_usage(&usage);
+            result
+        }
+        Err(RecvTimeoutError::Timeout) => Err(format!("timed out after {}s", limit.as_secs_f64())),
+        Err(RecvTimeoutError::Disconnected) => Err("call ended without a result".to_string()),
+    }
+}
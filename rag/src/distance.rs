@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+use crate::runtime::block_on;
+use crate::store_access::check_writable;
+use crate::store_qdrant::{
+    collection_info, collection_info_async, create_collection, delete_collection, restore_points,
+    scroll_points_with_vectors, CollectionInfo,
+};
+
+/// Distance names as Qdrant spells them.
+pub const DISTANCES: &[&str] = &["Cosine", "Dot", "Euclid", "Manhattan"];
+
+/// Qdrant's spelling of `raw`, accepting any case and the common aliases.
+pub fn normalize_distance(raw: &str) -> Result<&'static str, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "cosine" | "cos" => Ok("Cosine"),
+        "dot" | "ip" | "inner" => Ok("Dot"),
+        "euclid" | "euclidean" | "l2" => Ok("Euclid"),
+        "manhattan" | "l1" => Ok("Manhattan"),
+        _ => Err(format!("Unknown distance '{}'; expected one of {}", raw, DISTANCES.join(", "))),
+    }
+}
+
+// Parameters of each collection seen so far, keyed by Qdrant URL and name,
+// so queries only ask Qdrant once per process.
+fn cache() -> &'static Mutex<HashMap<String, CollectionInfo>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CollectionInfo>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn cache_key(cfg: &Config) -> String {
+    format!("{}|{}", cfg.qdrant_url, cfg.collection)
+}
+
+/// Drop the cached parameters after the collection is deleted or recreated.
+pub(crate) fn forget_collection(cfg: &Config) {
+    if let Ok(mut c) = cache().lock() {
+        c.remove(&cache_key(cfg));
+    }
+}
+
+async fn cached_collection_info(cfg: &Config) -> Result<Option<CollectionInfo>, String> {
+    let key = cache_key(cfg);
+    if let Some(info) = cache().lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return Ok(Some(info));
+    }
+    let info = collection_info_async(cfg).await?;
+    if let (Some(info), Ok(mut c)) = (&info, cache().lock()) {
+        c.insert(key, info.clone());
+    }
+    Ok(info)
+}
+
+/// Fail when the collection exists with a different distance than
+/// `QDRANT_DISTANCE`; scores from a mismatched metric are meaningless. A
+/// missing collection passes, since indexing will create it.
+pub fn check_distance(cfg: &Config) -> Result<(), String> {
+    block_on(check_distance_async(cfg))
+}
+
+pub async fn check_distance_async(cfg: &Config) -> Result<(), String> {
+    let Some(info) = cached_collection_info(cfg).await? else {
+        return Ok(());
+    };
+    if info.distance.is_empty() || info.distance.eq_ignore_ascii_case(&cfg.distance) {
+        return Ok(());
+    }
+    Err(distance_mismatch_message(cfg, &info.distance))
+}
+
+pub(crate) fn distance_mismatch_message(cfg: &Config, stored: &str) -> String {
+    format!(
+        "Collection {} was built with {} distance but QDRANT_DISTANCE is {}. Set QDRANT_DISTANCE={} or run `aicli index migrate --distance {}` to rebuild it.",
+        cfg.collection, stored, cfg.distance, stored, cfg.distance
+    )
+}
+
+/// Fail when query vectors of `vector_size` cannot be searched against the
+/// collection, which happens after `OLLAMA_EMBED_MODEL` changes.
+pub async fn check_dimension_async(cfg: &Config, vector_size: usize) -> Result<(), String> {
+    match cached_collection_info(cfg).await? {
+        Some(info) if info.vector_size != 0 && info.vector_size != vector_size => {
+            Err(dimension_mismatch_message(cfg, info.vector_size, vector_size))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn dimension_mismatch_message(cfg: &Config, stored: usize, embedded: usize) -> String {
+    format!(
+        "Collection {} stores {}-dimensional vectors but {} produces {}-dimensional ones. Run `aicli index --recreate` to drop and rebuild the collection with the new model, or point QDRANT_COLLECTION at a new collection.",
+        cfg.collection, stored, cfg.embed_model, embedded
+    )
+}
+
+/// Outcome of `migrate_distance`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistanceMigration {
+    pub from: String,
+    pub to: String,
+    pub points: usize,
+}
+
+/// Rebuild the collection with a new distance. Qdrant cannot change the
+/// metric in place, so every point is read back with its vector, the
+/// collection is recreated, and the points are written again; embeddings do
+/// not depend on the metric, so nothing is re-embedded. The points are saved
+/// to a backup file first and it is only removed once the copy succeeds.
+pub fn migrate_distance(cfg: &Config, distance: &str) -> Result<DistanceMigration, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: index migration is disabled.".to_string());
+    }
+    check_writable(cfg, "index migration")?;
+    let to = normalize_distance(distance)?.to_string();
+    let info = collection_info(cfg)?.ok_or_else(|| format!("Collection {} does not exist", cfg.collection))?;
+    if info.distance.eq_ignore_ascii_case(&to) {
+        return Ok(DistanceMigration {
+            from: info.distance,
+            to,
+            points: 0,
+        });
+    }
+
+    let points = scroll_points_with_vectors(cfg)?;
+    let backup = backup_path(cfg);
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string(&points).map_err(|e| e.to_string())?;
+    fs::write(&backup, text).map_err(|e| format!("Failed to write backup {}: {}", backup.display(), e))?;
+
+    let mut target = cfg.clone();
+    target.distance = to.clone();
+    delete_collection(cfg)?;
+    forget_collection(cfg);
+    create_collection(&target, info.vector_size)
+        .and_then(|_| restore_points(&target, &points))
+        .map_err(|e| format!("{} (points were saved to {})", e, backup.display()))?;
+
+    let mut manifest = IndexManifest::load(cfg)?;
+    manifest.distance = to.clone();
+    manifest.save(cfg)?;
+    let _ = fs::remove_file(&backup);
+
+    Ok(DistanceMigration {
+        from: info.distance,
+        to,
+        points: points.len(),
+    })
+}
+
+fn backup_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir)
+        .join("backups")
+        .join(format!("{}.json", cfg.collection))
+}
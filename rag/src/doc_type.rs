@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use crate::rerank::sort_by_rerank_score;
+use crate::retrieve_chunks::Hit;
+
+pub const DOC_TYPES: &[&str] = &["code", "docs", "config", "test", "changelog"];
+
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "toml", "yaml", "yml", "json", "ini", "cfg", "conf", "env", "properties", "lock",
+];
+const CONFIG_FILES: &[&str] = &["dockerfile", "makefile", "docker-compose.yml", ".env", ".gitignore", ".editorconfig"];
+const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "txt", "adoc", "org", "html", "htm"];
+const CHANGELOG_STEMS: &[&str] = &["changelog", "changes", "history", "news", "releases", "release-notes"];
+
+/// Doc-type tag for a chunk, stored in the payload at index time: one of
+/// `code`, `docs`, `config`, `test`, or `changelog`. The path decides
+/// first; the chunk text only separates tests living next to the code
+/// (`#[test]`, `def test_`).
+pub fn classify_chunk(path: &str, text: &str) -> &'static str {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return "docs";
+    }
+    let lower = path.to_ascii_lowercase().replace('\\', "/");
+    let file = lower.rsplit('/').next().unwrap_or(&lower);
+    let stem = file.split('.').next().unwrap_or(file);
+    let ext = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    if CHANGELOG_STEMS.contains(&stem) {
+        return "changelog";
+    }
+    let test_path = lower.split('/').any(|part| matches!(part, "test" | "tests" | "spec" | "__tests__"))
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || file.contains(".test.")
+        || file.contains(".spec.");
+    if test_path {
+        return "test";
+    }
+    if CONFIG_FILES.contains(&file) || file.starts_with(".env") || CONFIG_EXTENSIONS.contains(&ext) {
+        return "config";
+    }
+    if DOC_EXTENSIONS.contains(&ext) || lower.split('/').any(|part| part == "docs" || part == "doc") {
+        return "docs";
+    }
+    if ["#[test]", "#[tokio::test]", "def test_", "@Test", "func Test"].iter().any(|m| text.contains(m)) {
+        return "test";
+    }
+    "code"
+}
+
+// Phrases checked against the question with words padded by spaces, so
+// `test` does not match `latest`. A trailing-space-free key is a stem.
+const INTENTS: &[(&[&str], &[&str])] = &[
+    (
+        &[" configur", " setting", " env var", " environment variable", " set up ", " setup ", " enable ", " disable ", " option"],
+        &["config", "docs"],
+    ),
+    (&[" test", " spec ", " coverage "], &["test"]),
+    (
+        &[" changelog", " release", " what changed ", " changed in ", " new in ", " breaking change"],
+        &["changelog"],
+    ),
+    (
+        &[" how do i ", " how to ", " how can i ", " explain ", " documentation ", " guide ", " tutorial ", " overview "],
+        &["docs"],
+    ),
+    (
+        &[" implement", " function ", " where is ", " defined ", " struct ", " method ", " source code ", " bug "],
+        &["code"],
+    ),
+];
+
+/// Doc types a question points at, most specific first; empty when
+/// nothing in it hints at one.
+pub fn doc_types_for_query(query: &str) -> Vec<&'static str> {
+    let words: String = query
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let padded = format!(" {} ", words.split_whitespace().collect::<Vec<_>>().join(" "));
+    let mut out: Vec<&'static str> = Vec::new();
+    for (phrases, types) in INTENTS {
+        if phrases.iter().any(|p| padded.contains(p)) {
+            for t in *types {
+                if !out.contains(t) {
+                    out.push(t);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Raise the score of hits tagged with one of `doc_types` by `boost`
+/// (lowering it for distance metrics) and re-sort. Reranked hits have their
+/// reranker score raised instead. Hits indexed before tagging have no doc
+/// type and keep their score.
+pub fn boost_doc_types(mut hits: Vec<Hit>, doc_types: &[&str], boost: f32, lower_is_better: bool) -> Vec<Hit> {
+    if doc_types.is_empty() || boost == 0.0 {
+        return hits;
+    }
+    for hit in &mut hits {
+        let tagged = hit.payload.as_ref().and_then(|p| p.doc_type.as_deref());
+        if tagged.is_some_and(|t| doc_types.contains(&t)) {
+            match &mut hit.rerank_score {
+                Some(score) => *score += boost,
+                None => hit.score += if lower_is_better { -boost } else { boost },
+            }
+        }
+    }
+    if hits.iter().any(|h| h.rerank_score.is_some()) {
+        sort_by_rerank_score(&mut hits);
+    } else if lower_is_better {
+        hits.sort_by(|a, b| a.score.total_cmp(&b.score));
+    } else {
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+    hits
+}
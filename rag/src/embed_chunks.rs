@@ -2,7 +2,8 @@ use serde::Serialize;
 use serde_json::Value;
 
 use crate::config::Config;
-use crate::http::post_json;
+use crate::http::{post_json_async, HttpOptions};
+use crate::ollama_pool::pick_ollama_host;
 
 #[derive(Serialize)]
 struct EmbedRequest<'a> {
@@ -16,24 +17,26 @@ struct EmbedLegacyRequest<'a> {
     prompt: &'a [String],
 }
 
-pub fn embed_texts(cfg: &Config, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+pub async fn embed_texts_async(cfg: &Config, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
     if texts.is_empty() {
         return Ok(vec![]);
     }
-    let url = format!("{}/api/embed", cfg.ollama_url);
+    let host = pick_ollama_host(cfg).await;
+    let url = format!("{}/api/embed", host.url());
     let req = EmbedRequest {
         model: &cfg.embed_model,
         input: texts,
     };
-    match post_json::<Value, _>(&url, &req) {
+    let opts = HttpOptions::for_embedding(cfg);
+    match post_json_async::<Value, _>(&url, &req, &opts).await {
         Ok(res) => parse_embeddings(res),
         Err(_) => {
-            let url = format!("{}/api/embeddings", cfg.ollama_url);
+            let url = format!("{}/api/embeddings", host.url());
             let req = EmbedLegacyRequest {
                 model: &cfg.embed_model,
                 prompt: texts,
             };
-            let res = post_json::<Value, _>(&url, &req)?;
+            let res = post_json_async::<Value, _>(&url, &req, &opts).await.inspect_err(|_| host.failed())?;
             parse_embeddings(res)
         }
     }
@@ -1,7 +1,7 @@
 use crate::config::Config;
-use crate::embed_chunks::embed_texts;
+use crate::embed_chunks::embed_texts_async;
 
-pub fn embed_query(cfg: &Config, text: &str) -> Result<Vec<f32>, String> {
-    let vecs = embed_texts(cfg, &[text.to_string()])?;
+pub async fn embed_query_async(cfg: &Config, text: &str) -> Result<Vec<f32>, String> {
+    let vecs = embed_texts_async(cfg, &[text.to_string()]).await?;
     Ok(vecs.into_iter().next().unwrap_or_default())
 }
@@ -0,0 +1,118 @@
+use serde::Deserialize;
+
+use crate::build_prompt::{answer_system_prompt, context_budget_tokens, fit_hits_to_budget, Message};
+use crate::config::Config;
+use crate::generate::generate_json_async;
+use crate::retrieve_chunks::Hit;
+
+/// A verbatim passage taken from one retrieved chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    /// 1-based position of the chunk in the hit list.
+    pub source: usize,
+    pub citation: String,
+    pub text: String,
+}
+
+#[derive(Deserialize)]
+struct RawQuotes {
+    #[serde(default)]
+    quotes: Vec<RawQuote>,
+}
+
+#[derive(Deserialize)]
+struct RawQuote {
+    source: usize,
+    text: String,
+}
+
+/// Ask the model for the passages of `hits` that answer `question`. Only
+/// quotes that really appear in the cited chunk are kept, so the synthesis
+/// step never sees text the model made up.
+pub async fn extract_quotes_async(cfg: &Config, question: &str, hits: &[Hit]) -> Result<Vec<Quote>, String> {
+    let hits = fit_hits_to_budget(hits, context_budget_tokens(cfg, question));
+    let passages = hits
+        .iter()
+        .enumerate()
+        .map(|(i, h)| format!("[{}]\n{}", i + 1, chunk_of(h)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: format!(
+            "Copy the sentences from the passages below that help answer the question. Quote them exactly, word for word; do not paraphrase, merge, or shorten them. Skip passages that do not help.\nReturn JSON: {{\"quotes\": [{{\"source\": <passage number>, \"text\": \"<exact quote>\"}}]}}\n\nQuestion: {}\n\n{}",
+            question, passages
+        ),
+    }];
+    let raw = generate_json_async(cfg, &messages).await?;
+    parse_quotes(&raw, &hits)
+}
+
+/// Parse the extractor's JSON, dropping quotes that are not verbatim
+/// (whitespace aside) in the chunk they cite.
+pub fn parse_quotes(raw: &str, hits: &[Hit]) -> Result<Vec<Quote>, String> {
+    let parsed = serde_json::from_str::<RawQuotes>(raw)
+        .map_err(|e| format!("Quote extraction returned invalid JSON: {} | {}", e, raw))?;
+    let mut quotes: Vec<Quote> = Vec::new();
+    for quote in parsed.quotes {
+        let text = quote.text.trim().trim_matches('"').trim().to_string();
+        let Some(hit) = quote.source.checked_sub(1).and_then(|i| hits.get(i)) else {
+            continue;
+        };
+        if text.is_empty() || !squash(chunk_of(hit)).contains(&squash(&text)) {
+            continue;
+        }
+        if quotes.iter().any(|q| q.source == quote.source && q.text == text) {
+            continue;
+        }
+        let citation = hit
+            .payload
+            .as_ref()
+            .and_then(|p| p.citation())
+            .unwrap_or_else(|| "unknown".to_string());
+        quotes.push(Quote {
+            source: quote.source,
+            citation,
+            text,
+        });
+    }
+    Ok(quotes)
+}
+
+/// Quotes as shown in the context pane and given to the synthesis prompt.
+pub fn format_quotes(quotes: &[Quote]) -> String {
+    if quotes.is_empty() {
+        return "(no verbatim passages found)".to_string();
+    }
+    quotes
+        .iter()
+        .map(|q| {
+            let body = q.text.lines().map(|l| format!("> {}", l)).collect::<Vec<_>>().join("\n");
+            format!("[{}] {}\n{}", q.source, q.citation, body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Prompt for the second stage: answer from the quotes alone.
+pub fn build_synthesis_prompt(cfg: &Config, question: &str, quotes: &[Quote]) -> Vec<Message> {
+    vec![
+        Message { role: "system".to_string(), content: answer_system_prompt(cfg) },
+        Message {
+            role: "user".to_string(),
+            content: format!(
+                "Answer the question using only the quoted passages below. Cite passages by their [n] number. If the quotes do not answer the question, say so instead of guessing.\n\nQuotes:\n{}\n\nQuestion: {}",
+                format_quotes(quotes),
+                question
+            ),
+        },
+    ]
+}
+
+fn chunk_of(hit: &Hit) -> &str {
+    hit.payload.as_ref().and_then(|p| p.chunk.as_deref()).unwrap_or("")
+}
+
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
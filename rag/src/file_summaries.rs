@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::build_prompt::Message;
+use crate::config::{Config, GenerationOptions};
+use crate::generate::generate_answer_with;
+use crate::manifest::{IndexManifest, ManifestFile};
+use crate::store_qdrant::scroll_points_for_path;
+use crate::tokens::truncate_to_tokens;
+
+// Fixed namespace for summary keys.
+const SUMMARY_NAMESPACE: Uuid = Uuid::from_u128(0x2f6d_91a4_c8e3_4b7d_95a0_6e1c_d3b8_7f42);
+
+// Most of a file the model reads to summarize it.
+const MAX_SUMMARY_INPUT_TOKENS: usize = 3000;
+
+/// A file's summary, stored under `$AICLI_CACHE_DIR/summaries/`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FileSummary {
+    pub summary: String,
+    /// The file's chunk ids when it was summarized; re-indexing the file
+    /// makes the summary stale.
+    pub key: String,
+    pub model: String,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub created_at: u64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SummaryFile {
+    #[serde(default)]
+    files: BTreeMap<String, FileSummary>,
+}
+
+pub fn summaries_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.cache_dir)
+        .join("summaries")
+        .join(format!("{}.json", cfg.collection))
+}
+
+fn load_file(cfg: &Config) -> SummaryFile {
+    fs::read_to_string(summaries_path(cfg))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// The key a summary of `file` is stored under: a hash of its chunk ids.
+pub fn summary_key(file: &ManifestFile) -> String {
+    let text = serde_json::to_string(&file.point_ids).unwrap_or_default();
+    Uuid::new_v5(&SUMMARY_NAMESPACE, text.as_bytes()).to_string()
+}
+
+/// The stored summary of `path`, unless the file was re-indexed or
+/// forgotten since.
+pub fn cached_summary(cfg: &Config, path: &str) -> Option<FileSummary> {
+    let manifest = IndexManifest::load(cfg).ok()?;
+    let key = summary_key(manifest.files.get(path)?);
+    load_file(cfg).files.remove(path).filter(|s| s.key == key)
+}
+
+/// Indexed files with no summary or a stale one, in manifest order, each
+/// with the estimated tokens summarizing it costs: its indexed text (chunks
+/// are `RAG_CHUNK_SIZE` characters, about four to a token), capped at what
+/// the model is shown.
+pub fn pending_summaries(cfg: &Config) -> Vec<(String, usize)> {
+    let Ok(manifest) = IndexManifest::load(cfg) else {
+        return Vec::new();
+    };
+    let stored = load_file(cfg).files;
+    manifest
+        .files
+        .iter()
+        .filter(|(path, file)| stored.get(*path).is_none_or(|s| s.key != summary_key(file)))
+        .map(|(path, file)| (path.clone(), (file.chunks * cfg.chunk_size / 4).min(MAX_SUMMARY_INPUT_TOKENS)))
+        .collect()
+}
+
+/// The indexed path `name` refers to: the path itself, or the one indexed
+/// path ending in `/name`.
+pub fn indexed_path(cfg: &Config, name: &str) -> Result<String, String> {
+    let manifest = IndexManifest::load(cfg)?;
+    if manifest.files.contains_key(name) {
+        return Ok(name.to_string());
+    }
+    let suffix = format!("/{}", name.trim_start_matches("./"));
+    let matches: Vec<&String> = manifest.files.keys().filter(|p| p.ends_with(&suffix)).collect();
+    match matches.as_slice() {
+        [path] => Ok(path.to_string()),
+        [] => Err(format!("Not an indexed file: {}", name)),
+        _ => Err(format!(
+            "'{}' matches {} indexed files; give more of the path",
+            name,
+            matches.len()
+        )),
+    }
+}
+
+/// Summarize `path` from its indexed chunks and store the summary, dropping
+/// summaries of files no longer in the index.
+pub fn summarize_file(cfg: &Config, path: &str) -> Result<FileSummary, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: summarizing is disabled.".to_string());
+    }
+    let manifest = IndexManifest::load(cfg)?;
+    let file = manifest
+        .files
+        .get(path)
+        .ok_or_else(|| format!("Not an indexed file: {}", path))?;
+    let text = scroll_points_for_path(cfg, path)?
+        .iter()
+        .filter_map(|p| p.payload.as_ref()?.get("chunk")?.as_str().map(str::to_string))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if text.trim().is_empty() {
+        return Err(format!("No indexed text for {}", path));
+    }
+    let request = vec![
+        Message {
+            role: "system".to_string(),
+            content: "You summarize one source file for a developer. In three to six sentences, say what the file is for, its main types, functions, or sections, and how it connects to the rest of the project. Do not invent details.".to_string(),
+        },
+        Message {
+            role: "user".to_string(),
+            content: format!(
+                "File: {}\n\n{}\n\nReturn only the summary.",
+                path,
+                truncate_to_tokens(&text, MAX_SUMMARY_INPUT_TOKENS)
+            ),
+        },
+    ];
+    let deterministic = GenerationOptions {
+        temperature: Some(0.0),
+        ..GenerationOptions::default()
+    };
+    let mut summary_cfg = cfg.clone();
+    summary_cfg.chat_model = cfg.title_model.clone();
+    let summary = generate_answer_with(&summary_cfg, &request, &deterministic)?;
+    if summary.trim().is_empty() {
+        return Err("Model returned an empty summary".to_string());
+    }
+    let entry = FileSummary {
+        summary: summary.trim().to_string(),
+        key: summary_key(file),
+        model: summary_cfg.chat_model,
+        created_at: unix_secs(),
+    };
+    let mut stored = load_file(cfg);
+    stored.files.retain(|p, _| manifest.files.contains_key(p));
+    stored.files.insert(path.to_string(), entry.clone());
+    let out = summaries_path(cfg);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+    fs::write(&out, text).map_err(|e| format!("Failed to write {}: {}", out.display(), e))?;
+    Ok(entry)
+}
+
+/// Paces background summaries: holds up to a minute's worth of estimated
+/// tokens and refills continuously, so idle time never turns into a burst.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_sec: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket refilling `per_min` tokens a minute.
+    pub fn new(per_min: usize, now: Instant) -> Self {
+        let capacity = per_min as f64;
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            per_sec: capacity / 60.0,
+            last: now,
+        }
+    }
+
+    /// Take `cost` tokens if the bucket holds them. A cost above the
+    /// capacity is charged as a full bucket, so a large file waits a minute
+    /// rather than forever.
+    pub fn try_take(&mut self, cost: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.last = now;
+        let cost = (cost as f64).min(self.capacity);
+        if self.capacity <= 0.0 || self.tokens < cost {
+            return false;
+        }
+        self.tokens -= cost;
+        true
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
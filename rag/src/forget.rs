@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+use crate::store_access::check_writable;
+use crate::store_qdrant::{delete_points_by_paths, scroll_paths};
+use crate::tool_filter::glob_match;
+
+/// The indexed files a `/forget` pattern matches, found without deleting
+/// anything so the count can be confirmed first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ForgetPlan {
+    pub pattern: String,
+    /// Matching paths with their point counts, sorted by path.
+    pub files: BTreeMap<String, usize>,
+}
+
+impl ForgetPlan {
+    pub fn points(&self) -> usize {
+        self.files.values().sum()
+    }
+
+    /// The dry-run report: totals and the files with the most points.
+    pub fn summary(&self, limit: usize) -> String {
+        if self.files.is_empty() {
+            return format!("No indexed files match {}.", self.pattern);
+        }
+        let mut largest: Vec<(&String, &usize)> = self.files.iter().collect();
+        largest.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let mut out = format!(
+            "{} matches {} points in {} files:\n",
+            self.pattern,
+            self.points(),
+            self.files.len()
+        );
+        for (path, points) in largest.iter().take(limit) {
+            out.push_str(&format!("  {} ({})\n", path, points));
+        }
+        if self.files.len() > limit {
+            out.push_str(&format!("  ... and {} more\n", self.files.len() - limit));
+        }
+        out
+    }
+}
+
+/// Whether `path` matches a `/forget` pattern. `*` and `?` are globs that
+/// also match `/`; a pattern matches a whole path or any run of its
+/// directories, so `node_modules` matches everything under a
+/// `node_modules` directory and `*.min.js` any minified file.
+pub fn forget_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('/');
+    if pattern.is_empty() {
+        return false;
+    }
+    let path = path.replace('\\', "/");
+    glob_match(pattern, &path)
+        || glob_match(&format!("*/{}", pattern), &path)
+        || glob_match(&format!("{}/*", pattern), &path)
+        || glob_match(&format!("*/{}/*", pattern), &path)
+}
+
+/// Count the points per indexed file matching `pattern`, deleting nothing.
+pub fn plan_forget(cfg: &Config, pattern: &str) -> Result<ForgetPlan, String> {
+    if pattern.trim().trim_end_matches('/').is_empty() {
+        return Err("Usage: /forget <glob>".to_string());
+    }
+    let mut files = BTreeMap::new();
+    for path in scroll_paths(cfg)? {
+        if forget_matches(pattern, &path) {
+            *files.entry(path).or_insert(0) += 1;
+        }
+    }
+    Ok(ForgetPlan {
+        pattern: pattern.trim().to_string(),
+        files,
+    })
+}
+
+/// Delete every point of the planned files with a Qdrant filter delete and
+/// drop them from the index manifest. Returns the number of points removed.
+pub fn forget(cfg: &Config, plan: &ForgetPlan) -> Result<usize, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: deleting points is disabled.".to_string());
+    }
+    check_writable(cfg, "deleting points")?;
+    let paths: Vec<String> = plan.files.keys().cloned().collect();
+    delete_points_by_paths(cfg, &paths)?;
+    let mut manifest = IndexManifest::load(cfg)?;
+    let before = manifest.files.len();
+    manifest.files.retain(|path, _| !plan.files.contains_key(path));
+    if manifest.files.len() != before {
+        manifest.save(cfg)?;
+    }
+    Ok(plan.points())
+}
@@ -1,8 +1,14 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::build_prompt::Message;
-use crate::config::Config;
-use crate::http::post_json;
+use crate::config::{Config, GenerationOptions};
+use crate::http::{post_json_async, post_ndjson_async, HttpOptions};
+use crate::model_info::context_window_async;
+use crate::ollama_pool::pick_ollama_host;
+use crate::replay::{next_response, record_response};
+use crate::runtime::block_on;
+use crate::usage::record_call;
 
 #[derive(Serialize)]
 struct ChatRequest<'a> {
@@ -10,12 +16,18 @@ struct ChatRequest<'a> {
     messages: &'a [Message],
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    format: Option<&'a str>,
+    /// `"json"` for JSON mode, or a JSON schema for structured output.
+    format: Option<&'a Value>,
+    options: GenerationOptions,
 }
 
 #[derive(Deserialize)]
 struct ChatResponse {
     message: Option<ChatMessage>,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
 }
 
 #[derive(Deserialize)]
@@ -23,22 +35,175 @@ struct ChatMessage {
     content: Option<String>,
 }
 
+/// One line of a streamed `/api/chat` response; counts arrive on the last.
+#[derive(Deserialize)]
+struct ChatChunk {
+    message: Option<ChatMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
 pub fn generate_answer(cfg: &Config, messages: &[Message]) -> Result<String, String> {
-    generate_chat(cfg, messages, None)
+    block_on(generate_chat(cfg, messages, None, &GenerationOptions::default()))
+}
+
+/// Like `generate_answer`, with `overrides` taking precedence over the
+/// configured generation options for this call only.
+pub fn generate_answer_with(
+    cfg: &Config,
+    messages: &[Message],
+    overrides: &GenerationOptions,
+) -> Result<String, String> {
+    block_on(generate_chat(cfg, messages, None, overrides))
 }
 
+/// JSON-mode generation for the agent controller, using
+/// `controller_generation` on top of the general options.
 pub fn generate_json(cfg: &Config, messages: &[Message]) -> Result<String, String> {
-    generate_chat(cfg, messages, Some("json"))
+    block_on(generate_json_async(cfg, messages))
+}
+
+/// Like `generate_json`, with Ollama structured output constraining the
+/// reply to `schema`.
+pub fn generate_json_schema(cfg: &Config, messages: &[Message], schema: &Value) -> Result<String, String> {
+    block_on(generate_chat(cfg, messages, Some(schema), &cfg.controller_generation))
+}
+
+pub async fn generate_answer_async(cfg: &Config, messages: &[Message]) -> Result<String, String> {
+    generate_chat(cfg, messages, None, &GenerationOptions::default()).await
+}
+
+pub async fn generate_answer_with_async(
+    cfg: &Config,
+    messages: &[Message],
+    overrides: &GenerationOptions,
+) -> Result<String, String> {
+    generate_chat(cfg, messages, None, overrides).await
+}
+
+pub async fn generate_json_async(cfg: &Config, messages: &[Message]) -> Result<String, String> {
+    let json_mode = Value::from("json");
+    generate_chat(cfg, messages, Some(&json_mode), &cfg.controller_generation).await
+}
+
+/// The configured options with `overrides` applied, and `num_ctx` resolved
+/// to the detected context window when neither sets it.
+async fn request_options(cfg: &Config, overrides: &GenerationOptions) -> GenerationOptions {
+    let mut options = cfg.generation.merged(overrides);
+    if options.num_ctx.is_none() {
+        options.num_ctx = Some(context_window_async(cfg).await);
+    }
+    options
+}
+
+/// Stream an answer, passing each piece of text to `on_token` as it arrives.
+/// Returning `false` from `on_token` stops generation; the text received so
+/// far is returned.
+pub fn generate_answer_stream(
+    cfg: &Config,
+    messages: &[Message],
+    on_token: &mut dyn FnMut(&str) -> bool,
+) -> Result<String, String> {
+    block_on(generate_stream_async(cfg, messages, on_token))
+}
+
+async fn generate_stream_async(
+    cfg: &Config,
+    messages: &[Message],
+    on_token: &mut dyn FnMut(&str) -> bool,
+) -> Result<String, String> {
+    if cfg.llm_provider.eq_ignore_ascii_case("replay") {
+        // A word at a time, so a stream stopped part way can be replayed.
+        let content = next_response(&cfg.replay_file, "chat")?;
+        let mut streamed = String::new();
+        for piece in content.split_inclusive(' ') {
+            streamed.push_str(piece);
+            if !on_token(piece) {
+                break;
+            }
+        }
+        return Ok(streamed);
+    }
+    let host = pick_ollama_host(cfg).await;
+    let url = format!("{}/api/chat", host.url());
+    let req = ChatRequest {
+        model: &cfg.chat_model,
+        messages,
+        stream: true,
+        format: None,
+        options: request_options(cfg, &GenerationOptions::default()).await,
+    };
+    let mut content = String::new();
+    let mut counts = (0, 0);
+    let mut error = None;
+    post_ndjson_async(&url, &req, &HttpOptions::for_generation(cfg), &mut |line| {
+        let chunk = match serde_json::from_str::<ChatChunk>(line) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error = Some(format!("Bad stream chunk: {} | {}", e, line));
+                return false;
+            }
+        };
+        if let Some(err) = chunk.error {
+            error = Some(err);
+            return false;
+        }
+        if chunk.done {
+            counts = (chunk.prompt_eval_count, chunk.eval_count);
+        }
+        let piece = chunk.message.and_then(|m| m.content).unwrap_or_default();
+        if piece.is_empty() {
+            return true;
+        }
+        content.push_str(&piece);
+        on_token(&piece)
+    })
+    .await
+    .inspect_err(|_| host.failed())?;
+    if let Some(err) = error {
+        return Err(err);
+    }
+    // An interrupted stream never sees the final chunk, so its tokens go
+    // unreported; the call itself is still counted.
+    record_call(cfg, counts.0, counts.1);
+    if !cfg.record_file.trim().is_empty() {
+        record_response(&cfg.record_file, "chat", &content)?;
+    }
+    Ok(content)
 }
 
-fn generate_chat(cfg: &Config, messages: &[Message], format: Option<&str>) -> Result<String, String> {
-    let url = format!("{}/api/chat", cfg.ollama_url);
+async fn generate_chat(
+    cfg: &Config,
+    messages: &[Message],
+    format: Option<&Value>,
+    overrides: &GenerationOptions,
+) -> Result<String, String> {
+    let kind = if format.is_some() { "json" } else { "chat" };
+    if cfg.llm_provider.eq_ignore_ascii_case("replay") {
+        return next_response(&cfg.replay_file, kind);
+    }
+    let host = pick_ollama_host(cfg).await;
+    let url = format!("{}/api/chat", host.url());
     let req = ChatRequest {
         model: &cfg.chat_model,
         messages,
         stream: false,
         format,
+        options: request_options(cfg, overrides).await,
     };
-    let res = post_json::<ChatResponse, _>(&url, &req)?;
-    Ok(res.message.and_then(|m| m.content).unwrap_or_default())
+    let res = post_json_async::<ChatResponse, _>(&url, &req, &HttpOptions::for_generation(cfg))
+        .await
+        .inspect_err(|_| host.failed())?;
+    record_call(cfg, res.prompt_eval_count, res.eval_count);
+    let content = res.message.and_then(|m| m.content).unwrap_or_default();
+    if !cfg.record_file.trim().is_empty() {
+        record_response(&cfg.record_file, kind, &content)?;
+    }
+    Ok(content)
 }
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use crate::quota::QuotaCap;
+
+/// Lockfiles and other machine-written files that are never worth embedding.
+const GENERATED_NAMES: &[&str] = &[
+    "cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "pipfile.lock",
+    "gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+];
+
+const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.css",
+    ".map",
+    ".pb.go",
+    ".pb.rs",
+    ".pb.h",
+    ".pb.cc",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+];
+
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "code generated by",
+    "auto-generated",
+    "autogenerated",
+];
+
+/// Markers only count near the top of a file, where generators put them.
+const MARKER_LINES: usize = 20;
+const MAX_AVERAGE_LINE: usize = 300;
+const MAX_LINE: usize = 5_000;
+/// Bits per character; source code sits around 4.5, base64 blobs near 6.
+const MAX_ENTROPY: f64 = 5.5;
+const ENTROPY_MIN_BYTES: usize = 1_024;
+
+/// Why `path` looks generated, or `None` if it should be indexed.
+pub fn generated_reason(path: &str, text: &str) -> Option<&'static str> {
+    let lower = path.replace('\\', "/").to_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or(&lower);
+    if GENERATED_NAMES.contains(&name) {
+        return Some("lockfile");
+    }
+    if GENERATED_SUFFIXES.iter().any(|s| name.ends_with(s)) || name.contains(".generated.") {
+        return Some("generated name");
+    }
+    let has_marker = text.lines().take(MARKER_LINES).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_MARKERS.iter().any(|m| line.contains(m))
+    });
+    if has_marker {
+        return Some("generated marker");
+    }
+    let lines = text.lines().count().max(1);
+    let longest = text.lines().map(str::len).max().unwrap_or(0);
+    if text.len() / lines > MAX_AVERAGE_LINE || longest > MAX_LINE {
+        return Some("minified");
+    }
+    if text.len() >= ENTROPY_MIN_BYTES && char_entropy(text) > MAX_ENTROPY {
+        return Some("high entropy");
+    }
+    None
+}
+
+/// Shannon entropy of the characters in `text`, in bits.
+fn char_entropy(text: &str) -> f64 {
+    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+    let mut total = 0usize;
+    for c in text.chars() {
+        *counts.entry(c).or_default() += 1;
+        total += 1;
+    }
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// True when `path` ends with one of the `RAG_KEEP_GENERATED` entries.
+pub fn keep_generated(path: &str, keep: &[String]) -> bool {
+    let path = path.replace('\\', "/");
+    keep.iter().any(|k| !k.is_empty() && path.ends_with(k.as_str()))
+}
+
+/// What one indexing run read and what it left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanStats {
+    pub indexed: usize,
+    /// `(path, reason)` for every file skipped as generated.
+    pub skipped: Vec<(String, String)>,
+    /// Files that stored fewer chunks than they produced because of a quota.
+    pub capped: Vec<QuotaCap>,
+    /// Files whose embeddings still failed after `RAG_EMBED_RETRIES`; see
+    /// `retry_quarantined`.
+    pub quarantined: Vec<String>,
+}
+
+impl ScanStats {
+    /// One line per skip or cap reason, e.g. `skipped 2 files (lockfile)`.
+    pub fn summary(&self) -> Vec<String> {
+        let mut by_reason: BTreeMap<&str, usize> = BTreeMap::new();
+        for (_, reason) in &self.skipped {
+            *by_reason.entry(reason.as_str()).or_default() += 1;
+        }
+        let mut lines: Vec<String> = by_reason
+            .into_iter()
+            .map(|(reason, n)| format!("skipped {} file{} ({})", n, plural(n), reason))
+            .collect();
+
+        let mut caps: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for cap in &self.capped {
+            let entry = caps.entry(cap.reason).or_default();
+            entry.0 += 1;
+            entry.1 += cap.wanted - cap.kept;
+        }
+        lines.extend(caps.into_iter().map(|(reason, (files, dropped))| {
+            format!("capped {} file{} ({}), {} chunk{} dropped", files, plural(files), reason, dropped, plural(dropped))
+        }));
+        if !self.quarantined.is_empty() {
+            let n = self.quarantined.len();
+            lines.push(format!(
+                "quarantined {} file{} (embedding failed); run `aicli index retry-failed`",
+                n,
+                plural(n)
+            ));
+        }
+        lines
+    }
+
+    /// These stats of a scan plus what the indexing run it fed reported.
+    pub(crate) fn with_run(mut self, run: ScanStats) -> Self {
+        self.skipped.extend(run.skipped);
+        self.capped.extend(run.capped);
+        self.quarantined.extend(run.quarantined);
+        self
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
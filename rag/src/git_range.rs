@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{sanitize_collection_name, Config};
+use crate::generated::{generated_reason, keep_generated, ScanStats};
+use crate::scan_files::is_text_file;
+
+/// Split `v1.2..HEAD` (or `v1.2...HEAD`) into its start and end; a missing
+/// end means `HEAD`.
+pub fn parse_git_range(range: &str) -> Result<(String, String), String> {
+    let range = range.trim();
+    let (start, end) = match range.split_once("...") {
+        Some(parts) => parts,
+        None => range
+            .split_once("..")
+            .ok_or_else(|| format!("Expected a range like v1.2..HEAD, got '{}'", range))?,
+    };
+    let end = if end.is_empty() { "HEAD" } else { end };
+    // Refs starting with `-` would be read by git as options.
+    if start.is_empty() || start.starts_with('-') || end.starts_with('-') {
+        return Err(format!("Expected a range like v1.2..HEAD, got '{}'", range));
+    }
+    Ok((start.to_string(), end.to_string()))
+}
+
+/// The collection holding only the files changed in `range`, next to
+/// `collection`: `v1.2..HEAD` on `app_rag_chunks` is
+/// `app_delta_v1_2__HEAD_rag_chunks`.
+pub fn collection_for_git_range(collection: &str, range: &str) -> String {
+    let stem = collection.trim_end_matches("_rag_chunks");
+    format!("{}_delta_{}_rag_chunks", stem, sanitize_collection_name(range.trim()))
+}
+
+/// The files added, modified, or renamed in `range` within the repository
+/// holding `dir`, as they are at the range's end, under the same include,
+/// size, and generated-file rules as a directory scan. Deleted files are
+/// reported as skipped.
+pub(crate) fn git_range_files(
+    cfg: &Config,
+    dir: &str,
+    range: &str,
+) -> Result<(Vec<(String, String)>, ScanStats), String> {
+    let (_, end) = parse_git_range(range)?;
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = toplevel.trim();
+    let changed = git(toplevel, &["diff", "--name-status", "--no-renames", "-z", range.trim(), "--"])?;
+    let mut files = Vec::new();
+    let mut stats = ScanStats::default();
+    let mut fields = changed.split('\0').filter(|f| !f.is_empty());
+    while let (Some(status), Some(rel)) = (fields.next(), fields.next()) {
+        let path = Path::new(toplevel).join(rel).to_string_lossy().to_string();
+        if status.starts_with('D') {
+            stats.skipped.push((path, "deleted in range".to_string()));
+            continue;
+        }
+        if !is_text_file(Path::new(rel), &cfg.include_exts) {
+            continue;
+        }
+        let Ok(text) = git(toplevel, &["show", &format!("{}:{}", end, rel)]) else {
+            continue;
+        };
+        if text.trim().is_empty() || text.len() as u64 > cfg.max_file_bytes {
+            continue;
+        }
+        if cfg.skip_generated && !keep_generated(&path, &cfg.keep_generated) {
+            if let Some(reason) = generated_reason(&path, &text) {
+                stats.skipped.push((path, reason.to_string()));
+                continue;
+            }
+        }
+        files.push((path, text));
+    }
+    stats.indexed = files.len();
+    Ok((files, stats))
+}
+
+fn git(dir: &str, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::{Config, sanitize_collection_name};
+use crate::scan_files::scan_files;
+
+/// A shallow clone in a temp dir, removed when dropped.
+pub struct ClonedRepo {
+    pub dir: PathBuf,
+    pub commit: String,
+}
+
+impl Drop for ClonedRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn clone_repo(url: &str) -> Result<ClonedRepo, String> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!("aicli-git-{}-{}", std::process::id(), nanos));
+    let status = Command::new("git")
+        .args(["clone", "--depth", "1", "--quiet", url])
+        .arg(&dir)
+        .status()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    // Constructed before checking the status so a partial clone is cleaned up.
+    let mut repo = ClonedRepo {
+        dir,
+        commit: String::new(),
+    };
+    if !status.success() {
+        return Err(format!("git clone {} failed with {}", url, status));
+    }
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(&repo.dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .map_err(|e| format!("Failed to run git rev-parse: {}", e))?;
+    if !out.status.success() {
+        return Err(format!("git rev-parse failed: {}", String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    repo.commit = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    Ok(repo)
+}
+
+/// `<repo>_rag_chunks` for a repository cloned by URL.
+pub fn collection_for_repo(url: &str) -> String {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or("default")
+        .trim_end_matches(".git");
+    format!("{}_rag_chunks", sanitize_collection_name(name))
+}
+
+/// `<repo>_<branch>_<hash>_rag_chunks` for a checkout of `remote` on
+/// `branch`. The hash covers the normalized remote and the branch, so two
+/// checkouts that share a folder name get different collections, while
+/// `https://` and `git@` clones of one repository share one.
+pub fn collection_for_remote(remote: &str, branch: Option<&str>) -> String {
+    let normalized = normalize_remote(remote);
+    let repo = normalized.rsplit('/').next().filter(|s| !s.is_empty()).unwrap_or("default");
+    let key = format!("{}#{}", normalized, branch.unwrap_or_default());
+    let hash = Uuid::new_v5(&Uuid::NAMESPACE_URL, key.as_bytes()).simple().to_string();
+    let mut name = sanitize_collection_name(repo);
+    if let Some(branch) = branch {
+        name.push('_');
+        name.push_str(&sanitize_collection_name(&branch.replace('/', "-")));
+    }
+    format!("{}_{}_rag_chunks", name, &hash[..8])
+}
+
+/// `host/owner/repo` for any form of clone URL: the scheme, credentials,
+/// a `.git` suffix, and letter case are dropped.
+pub fn normalize_remote(remote: &str) -> String {
+    let url = remote.trim().trim_end_matches('/').trim_end_matches(".git");
+    let url = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // scp-like `git@host:owner/repo`.
+        None => match url.split_once(':') {
+            Some((host, path)) if !host.contains('/') => format!("{}/{}", host, path),
+            _ => url.to_string(),
+        },
+    };
+    let host_end = url.find('/').unwrap_or(url.len());
+    let url = match url[..host_end].rfind('@') {
+        Some(at) => &url[at + 1..],
+        None => &url[..],
+    };
+    url.to_lowercase()
+}
+
+/// Remote URL (`origin`, else the first remote) and current branch of the
+/// repository containing `dir`, read from its `.git` without running git.
+/// `None` outside a repository or when it has no remote; the branch is
+/// `None` on a detached HEAD.
+pub fn git_remote_and_branch(dir: &Path) -> Option<(String, Option<String>)> {
+    let git_dir = find_git_dir(dir)?;
+    // Linked worktrees keep the shared config in the main repository.
+    let common = fs::read_to_string(git_dir.join("commondir"))
+        .map(|c| git_dir.join(c.trim()))
+        .unwrap_or_else(|_| git_dir.clone());
+    let remote = parse_remote_url(&fs::read_to_string(common.join("config")).ok()?)?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+    let branch = head.trim().strip_prefix("ref: refs/heads/").map(str::to_string);
+    Some((remote, branch))
+}
+
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            // Worktrees and submodules point at their git dir: `gitdir: <path>`.
+            let text = fs::read_to_string(&dot_git).ok()?;
+            return Some(ancestor.join(text.trim().strip_prefix("gitdir:")?.trim()));
+        }
+    }
+    None
+}
+
+/// The `url` of `[remote "origin"]` in a git config file, or of the first
+/// remote when there is no origin.
+pub fn parse_remote_url(config: &str) -> Option<String> {
+    let mut remote: Option<String> = None;
+    let mut first = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            remote = line
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .strip_prefix("remote ")
+                .map(|name| name.trim().trim_matches('"').to_string());
+            continue;
+        }
+        let Some(name) = &remote else {
+            continue;
+        };
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("url") {
+                let url = value.trim().to_string();
+                if name == "origin" {
+                    return Some(url);
+                }
+                first.get_or_insert(url);
+            }
+        }
+    }
+    first
+}
+
+/// Browsable base URL for a clone URL; `git@host:owner/repo.git` becomes
+/// `https://host/owner/repo`.
+pub fn repo_web_base(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/').trim_end_matches(".git");
+    if let Some(rest) = url.strip_prefix("git@") {
+        if let Some((host, path)) = rest.split_once(':') {
+            return format!("https://{}/{}", host, path);
+        }
+    }
+    if let Some(rest) = url.strip_prefix("ssh://git@") {
+        return format!("https://{}", rest);
+    }
+    url.to_string()
+}
+
+/// Files of the clone keyed by a `<base>/blob/<commit>/<path>` link, so
+/// citations point at the exact revision that was indexed.
+pub fn repo_files(cfg: &Config, url: &str, repo: &ClonedRepo) -> Vec<(String, String)> {
+    let base = repo_web_base(url);
+    let dir = repo.dir.to_string_lossy().to_string();
+    scan_files(cfg, Some(&dir))
+        .into_iter()
+        .map(|(path, text)| {
+            let rel = Path::new(&path)
+                .strip_prefix(&repo.dir)
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or(path);
+            (format!("{}/blob/{}/{}", base, repo.commit, rel), text)
+        })
+        .collect()
+}
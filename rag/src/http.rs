@@ -1,58 +1,399 @@
-use reqwest::blocking::Client;
 use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use reqwest::{Client, RequestBuilder, Response};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::from_str;
+use std::sync::OnceLock;
 use std::time::Duration;
 
-pub fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120))
+use crate::config::Config;
+use crate::runtime::block_on;
+
+const RETRY_BASE_DELAY_MS: u64 = 250;
+
+/// Per-operation timeout, retry budget, and extra headers. Embedding and
+/// generation get their own timeouts; everything else uses the general one.
+/// Credentials are only attached for the service they belong to, so crawled
+/// web pages never see an Ollama or Qdrant key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpOptions {
+    pub fn standard(cfg: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(cfg.http_timeout_secs),
+            retries: cfg.http_retries,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn for_qdrant(cfg: &Config) -> Self {
+        let mut headers = Vec::new();
+        if !cfg.qdrant_api_key.is_empty() {
+            headers.push(("api-key".to_string(), cfg.qdrant_api_key.clone()));
+        }
+        headers.extend(cfg.qdrant_headers.iter().cloned());
+        Self {
+            headers,
+            ..Self::standard(cfg)
+        }
+    }
+
+    pub fn for_ollama(cfg: &Config) -> Self {
+        Self {
+            headers: ollama_headers(cfg),
+            ..Self::standard(cfg)
+        }
+    }
+
+    pub fn for_embedding(cfg: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(cfg.embed_timeout_secs),
+            ..Self::for_ollama(cfg)
+        }
+    }
+
+    pub fn for_generation(cfg: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(cfg.generate_timeout_secs),
+            ..Self::for_ollama(cfg)
+        }
+    }
+}
+
+fn ollama_headers(cfg: &Config) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+    if !cfg.ollama_api_key.is_empty() {
+        headers.push(("Authorization".to_string(), format!("Bearer {}", cfg.ollama_api_key)));
+    }
+    headers.extend(cfg.ollama_headers.iter().cloned());
+    headers
+}
+
+// One client for the whole process so keep-alive connections to Ollama and
+// Qdrant are reused. Timeouts are set per request from `HttpOptions`.
+fn client() -> Result<&'static Client, String> {
+    static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| build_client(Policy::default()))
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+// A client that hands redirects back to the caller, for fetches that must
+// check each hop before following it.
+fn unredirected_client() -> Result<&'static Client, String> {
+    static CLIENT: OnceLock<Result<Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| build_client(Policy::none()))
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+fn build_client(redirects: Policy) -> Result<Client, String> {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(8)
+        .tcp_keepalive(Duration::from_secs(60))
+        .redirect(redirects)
         .build()
-        .map_err(|e| e.to_string())?;
-    let resp = client.get(url).send().map_err(|e| e.to_string())?;
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Delay before retry number `attempt` (0-based): 250ms, 500ms, 1s, ...
+pub fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(RETRY_BASE_DELAY_MS.saturating_mul(1u64 << attempt.min(16)))
+}
+
+/// Send the request built by `build`, retrying with exponential backoff on
+/// connection errors and 5xx responses. Other failures return immediately.
+async fn send_with_retry(
+    build: impl Fn(&Client) -> RequestBuilder,
+    opts: &HttpOptions,
+) -> Result<Response, String> {
+    send_with_retry_on(client()?, build, opts).await
+}
+
+async fn send_with_retry_on(
+    client: &Client,
+    build: impl Fn(&Client) -> RequestBuilder,
+    opts: &HttpOptions,
+) -> Result<Response, String> {
+    let mut attempt = 0;
+    loop {
+        let mut request = build(client).timeout(opts.timeout);
+        for (name, value) in &opts.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let result = request.send().await;
+        let retryable = match &result {
+            Ok(resp) => resp.status().is_server_error(),
+            Err(e) => e.is_connect(),
+        };
+        if !retryable || attempt >= opts.retries {
+            return result.map_err(|e| e.to_string());
+        }
+        tokio::time::sleep(retry_delay(attempt)).await;
+        attempt += 1;
+    }
+}
+
+pub async fn get_json_async<T: DeserializeOwned>(url: &str, opts: &HttpOptions) -> Result<T, String> {
+    let text = get_text_async(url, opts).await?;
+    from_str::<T>(&text).map_err(|e| format!("GET {} decode failed: {} | {}", url, e, text))
+}
+
+pub async fn get_text_async(url: &str, opts: &HttpOptions) -> Result<String, String> {
+    let resp = send_with_retry(|client| client.get(url), opts).await?;
     let status = resp.status();
-    let text = resp.text().unwrap_or_default();
+    let text = resp.text().await.unwrap_or_default();
     if !status.is_success() {
         return Err(format!("GET {} failed: {} {}", url, status, text));
     }
-    from_str::<T>(&text).map_err(|e| format!("GET {} decode failed: {} | {}", url, e, text))
+    Ok(text)
 }
 
-pub fn post_json<T: DeserializeOwned, B: Serialize>(url: &str, body: &B) -> Result<T, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let resp = client
-        .post(url)
-        .header(CONTENT_TYPE, "application/json")
-        .json(body)
-        .send()
-        .map_err(|e| e.to_string())?;
+/// GET a binary body, such as a Qdrant snapshot file.
+pub async fn get_bytes_async(url: &str, opts: &HttpOptions) -> Result<Vec<u8>, String> {
+    let resp = send_with_retry(|client| client.get(url), opts).await?;
     let status = resp.status();
-    let text = resp.text().unwrap_or_default();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("GET {} failed: {} {}", url, status, text));
+    }
+    resp.bytes().await.map(|b| b.to_vec()).map_err(|e| e.to_string())
+}
+
+/// POST a raw body with the given content type and return the response text.
+pub async fn post_bytes_async(url: &str, content_type: &str, body: &[u8], opts: &HttpOptions) -> Result<String, String> {
+    let resp = send_with_retry(
+        |client| client.post(url).header(CONTENT_TYPE, content_type).body(body.to_vec()),
+        opts,
+    )
+    .await?;
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
     if !status.is_success() {
         return Err(format!("POST {} failed: {} {}", url, status, text));
     }
+    Ok(text)
+}
+
+/// A response whose status the caller interprets itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RawResponse {
+    pub status: u16,
+    /// Header names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RawResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Send `method` with an optional `(content type, body)` and return the
+/// response whatever its status, for protocols that give 4xx answers a
+/// meaning of their own (OAuth polling, MCP sessions).
+pub async fn send_raw_async(
+    method: &str,
+    url: &str,
+    body: Option<(&str, &[u8])>,
+    opts: &HttpOptions,
+) -> Result<RawResponse, String> {
+    let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+    let resp = send_with_retry(
+        |client| {
+            let request = client.request(method.clone(), url);
+            match body {
+                Some((content_type, body)) => request.header(CONTENT_TYPE, content_type).body(body.to_vec()),
+                None => request,
+            }
+        },
+        opts,
+    )
+    .await?;
+    Ok(raw_response(resp).await)
+}
+
+async fn raw_response(resp: Response) -> RawResponse {
+    let status = resp.status().as_u16();
+    let headers = resp
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.as_str().to_string(), value.to_str().unwrap_or_default().to_string()))
+        .collect();
+    let body = resp.text().await.unwrap_or_default();
+    RawResponse { status, headers, body }
+}
+
+/// GET without following redirects: a 3xx comes back as it is, with its
+/// `location` header, for the caller to check and follow.
+pub async fn get_unredirected_async(url: &str, opts: &HttpOptions) -> Result<RawResponse, String> {
+    let resp = send_with_retry_on(unredirected_client()?, |client| client.get(url), opts).await?;
+    Ok(raw_response(resp).await)
+}
+
+pub async fn post_json_async<T: DeserializeOwned, B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+) -> Result<T, String> {
+    let text = post_text_async(url, body, opts).await?;
     from_str::<T>(&text).map_err(|e| format!("POST {} decode failed: {} | {}", url, e, text))
 }
 
-pub fn put_json<T: DeserializeOwned, B: Serialize>(url: &str, body: &B) -> Result<T, String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let resp = client
-        .put(url)
-        .header(CONTENT_TYPE, "application/json")
-        .json(body)
-        .send()
-        .map_err(|e| e.to_string())?;
+/// POST a JSON body and return the raw response text.
+pub async fn post_text_async<B: Serialize>(url: &str, body: &B, opts: &HttpOptions) -> Result<String, String> {
+    let resp = send_with_retry(
+        |client| client.post(url).header(CONTENT_TYPE, "application/json").json(body),
+        opts,
+    )
+    .await?;
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("POST {} failed: {} {}", url, status, text));
+    }
+    Ok(text)
+}
+
+pub async fn put_json_async<T: DeserializeOwned, B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+) -> Result<T, String> {
+    let resp = send_with_retry(
+        |client| client.put(url).header(CONTENT_TYPE, "application/json").json(body),
+        opts,
+    )
+    .await?;
     let status = resp.status();
-    let text = resp.text().unwrap_or_default();
+    let text = resp.text().await.unwrap_or_default();
     if !status.is_success() {
         return Err(format!("PUT {} failed: {} {}", url, status, text));
     }
     from_str::<T>(&text).map_err(|e| format!("PUT {} decode failed: {} | {}", url, e, text))
 }
+
+pub async fn patch_json_async<T: DeserializeOwned, B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+) -> Result<T, String> {
+    let resp = send_with_retry(
+        |client| client.patch(url).header(CONTENT_TYPE, "application/json").json(body),
+        opts,
+    )
+    .await?;
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("PATCH {} failed: {} {}", url, status, text));
+    }
+    from_str::<T>(&text).map_err(|e| format!("PATCH {} decode failed: {} | {}", url, e, text))
+}
+
+pub async fn delete_json_async<T: DeserializeOwned>(url: &str, opts: &HttpOptions) -> Result<T, String> {
+    let resp = send_with_retry(|client| client.delete(url), opts).await?;
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("DELETE {} failed: {} {}", url, status, text));
+    }
+    from_str::<T>(&text).map_err(|e| format!("DELETE {} decode failed: {} | {}", url, e, text))
+}
+
+/// POST `body` and pass each line of the newline-delimited JSON response to
+/// `on_line` as it arrives. Returning `false` stops reading and drops the
+/// connection, which tells the server to stop producing output. Only the
+/// initial request is retried; a stream that fails midway is an error.
+pub async fn post_ndjson_async<B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+    on_line: &mut dyn FnMut(&str) -> bool,
+) -> Result<(), String> {
+    let mut resp = send_with_retry(
+        |client| client.post(url).header(CONTENT_TYPE, "application/json").json(body),
+        opts,
+    )
+    .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("POST {} failed: {} {}", url, status, text));
+    }
+    let mut pending: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp.chunk().await.map_err(|e| e.to_string())? {
+        pending.extend_from_slice(&chunk);
+        while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() && !on_line(line.trim()) {
+                return Ok(());
+            }
+        }
+    }
+    let rest = String::from_utf8_lossy(&pending);
+    if !rest.trim().is_empty() {
+        on_line(rest.trim());
+    }
+    Ok(())
+}
+
+pub fn get_json<T: DeserializeOwned>(url: &str, opts: &HttpOptions) -> Result<T, String> {
+    block_on(get_json_async(url, opts))
+}
+
+pub fn get_bytes(url: &str, opts: &HttpOptions) -> Result<Vec<u8>, String> {
+    block_on(get_bytes_async(url, opts))
+}
+
+pub fn post_bytes(url: &str, content_type: &str, body: &[u8], opts: &HttpOptions) -> Result<String, String> {
+    block_on(post_bytes_async(url, content_type, body, opts))
+}
+
+pub fn send_raw(
+    method: &str,
+    url: &str,
+    body: Option<(&str, &[u8])>,
+    opts: &HttpOptions,
+) -> Result<RawResponse, String> {
+    block_on(send_raw_async(method, url, body, opts))
+}
+
+pub fn post_text<B: Serialize>(url: &str, body: &B, opts: &HttpOptions) -> Result<String, String> {
+    block_on(post_text_async(url, body, opts))
+}
+
+pub fn post_json<T: DeserializeOwned, B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+) -> Result<T, String> {
+    block_on(post_json_async(url, body, opts))
+}
+
+pub fn put_json<T: DeserializeOwned, B: Serialize>(
+    url: &str,
+    body: &B,
+    opts: &HttpOptions,
+) -> Result<T, String> {
+    block_on(put_json_async(url, body, opts))
+}
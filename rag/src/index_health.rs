@@ -0,0 +1,163 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+use crate::store_access::check_writable;
+use crate::store_qdrant::{collection_info, delete_points, scroll_points};
+
+/// A single inconsistency between the collection, the manifest, and disk.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IndexIssue {
+    MissingCollection,
+    DimensionMismatch { manifest: usize, collection: usize },
+    DistanceMismatch { config: String, collection: String },
+    MissingPayload { id: Value },
+    OrphanedPoint { id: Value, path: String },
+    MissingFile { path: String },
+    IncompleteFile { path: String, expected: usize, found: usize },
+}
+
+impl fmt::Display for IndexIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexIssue::MissingCollection => write!(f, "collection does not exist"),
+            IndexIssue::DimensionMismatch { manifest, collection } => write!(
+                f,
+                "vector size mismatch: manifest {} vs collection {} (re-index required)",
+                manifest, collection
+            ),
+            IndexIssue::DistanceMismatch { config, collection } => write!(
+                f,
+                "distance mismatch: config {} vs collection {}",
+                config, collection
+            ),
+            IndexIssue::MissingPayload { id } => write!(f, "point {} is missing path/index/chunk payload", id),
+            IndexIssue::OrphanedPoint { id, path } => {
+                write!(f, "point {} for {} is not tracked by the manifest", id, path)
+            }
+            IndexIssue::MissingFile { path } => write!(f, "{} no longer exists on disk", path),
+            IndexIssue::IncompleteFile { path, expected, found } => write!(
+                f,
+                "{} has {} of {} chunks stored (re-index the file)",
+                path, found, expected
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct IndexReport {
+    pub points_checked: usize,
+    pub issues: Vec<IndexIssue>,
+    pub repaired: Vec<String>,
+}
+
+impl IndexReport {
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Compare the collection against the manifest and the filesystem. With
+/// `repair`, delete broken and orphaned points and drop manifest entries for
+/// files that are gone; issues that need embeddings are only reported.
+pub fn verify_index(cfg: &Config, repair: bool) -> Result<IndexReport, String> {
+    if repair {
+        check_writable(cfg, "repair")?;
+    }
+    let mut report = IndexReport::default();
+    let mut manifest = IndexManifest::load(cfg)?;
+
+    let Some(info) = collection_info(cfg)? else {
+        report.issues.push(IndexIssue::MissingCollection);
+        return Ok(report);
+    };
+    if manifest.vector_size != 0 && manifest.vector_size != info.vector_size {
+        report.issues.push(IndexIssue::DimensionMismatch {
+            manifest: manifest.vector_size,
+            collection: info.vector_size,
+        });
+    }
+    if !info.distance.is_empty() && !info.distance.eq_ignore_ascii_case(&cfg.distance) {
+        report.issues.push(IndexIssue::DistanceMismatch {
+            config: cfg.distance.clone(),
+            collection: info.distance.clone(),
+        });
+    }
+
+    let points = scroll_points(cfg)?;
+    report.points_checked = points.len();
+
+    let tracked: HashSet<String> = manifest
+        .files
+        .values()
+        .flat_map(|f| f.point_ids.iter().map(|id| id.to_string()))
+        .collect();
+    let mut to_delete: Vec<Value> = Vec::new();
+    let mut found_per_file: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+
+    for point in points {
+        let payload = point.payload.as_ref();
+        let path = payload.and_then(|p| p.get("path")).and_then(|v| v.as_str());
+        let has_index = payload.and_then(|p| p.get("index")).map(|v| v.is_u64()).unwrap_or(false);
+        let has_chunk = payload.and_then(|p| p.get("chunk")).map(|v| v.is_string()).unwrap_or(false);
+        let Some(path) = path.filter(|_| has_index && has_chunk) else {
+            report.issues.push(IndexIssue::MissingPayload { id: point.id.clone() });
+            to_delete.push(point.id);
+            continue;
+        };
+        if !manifest.files.is_empty() && !tracked.contains(&point.id.to_string()) {
+            report.issues.push(IndexIssue::OrphanedPoint {
+                id: point.id.clone(),
+                path: path.to_string(),
+            });
+            to_delete.push(point.id);
+            continue;
+        }
+        *found_per_file.entry(path.to_string()).or_insert(0) += 1;
+    }
+
+    let mut missing_files = BTreeSet::new();
+    for (path, entry) in &manifest.files {
+        if !is_remote_source(path) && !Path::new(path).exists() {
+            report.issues.push(IndexIssue::MissingFile { path: path.clone() });
+            missing_files.insert(path.clone());
+            to_delete.extend(entry.point_ids.iter().cloned());
+            continue;
+        }
+        let found = found_per_file.get(path).copied().unwrap_or(0);
+        if found < entry.chunks {
+            report.issues.push(IndexIssue::IncompleteFile {
+                path: path.clone(),
+                expected: entry.chunks,
+                found,
+            });
+        }
+    }
+
+    if repair {
+        if !to_delete.is_empty() {
+            delete_points(cfg, &to_delete)?;
+            report.repaired.push(format!("deleted {} broken or orphaned points", to_delete.len()));
+        }
+        if !missing_files.is_empty() {
+            for path in &missing_files {
+                manifest.files.remove(path);
+            }
+            manifest.save(cfg)?;
+            report
+                .repaired
+                .push(format!("removed {} missing files from the manifest", missing_files.len()));
+        }
+    }
+
+    Ok(report)
+}
+
+fn is_remote_source(path: &str) -> bool {
+    path.contains("://")
+}
@@ -1,83 +1,993 @@
+mod access_log;
 mod agent {
     include!("../../agent/agent.rs");
 }
+mod answer;
+mod answer_cache;
+mod attachments;
+mod best_of;
 mod build_prompt;
 mod chunk_text;
+mod code_check;
+mod collection_router;
 mod config;
+mod context_split;
+mod deadline;
+mod distance;
+mod doc_type;
 mod embed_chunks;
 mod embed_query;
+mod extractive;
+mod file_summaries;
+mod forget;
 mod generate;
+mod generated;
+mod git_range;
+mod git_source;
 mod http;
+mod index_health;
+mod local_tools;
+mod manifest;
+mod markdown;
 mod mcp {
     include!("../../mcp/mcp.rs");
 }
+mod mcp_auth;
+mod mcp_cache;
+mod mcp_health;
+mod mcp_http;
+mod mcp_server;
+mod mcp_servers;
+mod mcp_stdio;
+mod memory;
+mod mmr;
+mod model_info;
+mod notebook;
+mod ollama_pool;
+mod panels;
+mod paths;
+mod pipeline;
+mod platform;
+mod prompt_history;
+mod prompt_lint;
+mod query_expansion;
+mod quota;
+mod replay;
+mod rerank;
+mod rest_server;
+mod retrieve;
 mod retrieve_chunks;
+mod runtime;
 mod scan_files;
+mod session;
+mod session_env;
+mod share;
+mod snapshot;
+mod snippets;
+mod stats;
+mod store_access;
 mod store_qdrant;
+mod telemetry;
+mod templates;
+mod time_travel;
+mod timestamps;
+mod tokens;
+mod tool;
+mod tool_filter;
+mod tool_schema;
+mod trace;
+mod usage;
+mod viewer;
+mod web;
 
-pub use agent::{AgentState, Decision, answer_query_hybrid, parse_decision, run_agent};
-pub use build_prompt::{build_prompt_with_context, Message};
-pub use config::Config;
-pub use mcp::{McpCapabilities, McpClient};
-
-use chunk_text::chunk_text;
-use embed_chunks::embed_texts;
-use embed_query::embed_query;
-use generate::generate_answer;
-use retrieve_chunks::retrieve_top;
-use scan_files::scan_files;
-use store_qdrant::{ensure_collection, store_points, Point, PointPayload};
-
-pub fn index_corpus(cfg: &Config, source: Option<&str>) -> Result<(), String> {
-    let files = scan_files(cfg, source);
+pub use access_log::{
+    AccessLog, ChunkAccess, FileAccess, access_log, access_log_path, access_report, cited_hits, clear_access_log,
+    record_access,
+};
+pub use agent::{
+    AgentEvent, AgentMode, AgentOptions, AgentState, AgentStrategy, Decision, answer_query_hybrid,
+    answer_query_hybrid_detailed, answer_query_hybrid_with_events, answer_query_hybrid_with_history,
+    decision_schema, parse_decision, parse_final_details, parse_plan, parse_reflection, run_agent, run_agent_with_plan,
+};
+pub use answer::{AnswerResult, FinalDetails, SourceHit};
+pub use answer_cache::{
+    CachedAnswer, answer_cache_key, answer_cache_path, format_age, index_version, lookup_answer, normalize_question,
+    store_answer,
+};
+pub use attachments::{attach_file, attach_file_async, detach_file, search_attachments_async};
+pub use best_of::{consensus_pick, draft_options, generate_best_of, generate_best_of_async, parse_choice};
+pub use build_prompt::{
+    build_prompt_with_context, fit_hits_to_budget, Message, CONTEXT_LINE_MARKER, TRIMMED_MARKER,
+};
+pub use code_check::{
+    CodeSnippet, SnippetCheck, annotate_code_checks, check_answer_code, check_snippet, extract_snippets,
+};
+pub use collection_router::{
+    CollectionDescription, Route, collection_descriptions, collection_descriptions_path, describe_collection,
+    pick_collection, remove_collection_description, route_question,
+};
+pub use config::{
+    ChunkParams, Config, GenerationOptions, ModelPrice, parse_chunk_overrides, parse_headers,
+    parse_payload_indexes, parse_price_table,
+};
+pub use context_split::{
+    ContextAllocation, ContextSplit, Share, SplitContext, allocate_context, parse_context_split,
+};
+pub use deadline::{Deadline, call_with_timeout};
+pub use distance::{
+    DISTANCES, DistanceMigration, check_distance, check_distance_async, migrate_distance,
+    normalize_distance,
+};
+pub use doc_type::{DOC_TYPES, boost_doc_types, classify_chunk, doc_types_for_query};
+pub use extractive::{Quote, build_synthesis_prompt, format_quotes, parse_quotes};
+pub use file_summaries::{
+    FileSummary, TokenBucket, cached_summary, indexed_path, pending_summaries, summaries_path, summarize_file,
+    summary_key,
+};
+pub use forget::{ForgetPlan, forget, forget_matches, plan_forget};
+pub use generate::generate_answer_with;
+pub use generated::{ScanStats, generated_reason};
+pub use git_range::{collection_for_git_range, parse_git_range};
+pub use git_source::{collection_for_remote, git_remote_and_branch, normalize_remote, parse_remote_url};
+pub use http::{HttpOptions, RawResponse, retry_delay};
+pub use index_health::{IndexIssue, IndexReport, verify_index};
+pub use local_tools::{LOCAL_TOOL_NAMES, LocalToolRegistry, sandbox_root};
+pub use manifest::{IndexManifest, ManifestFile, QuarantinedFile, manifest_path};
+pub use markdown::render_markdown;
+pub use mcp::{McpCapabilities, McpClient, McpTool};
+pub use mcp_auth::{
+    CachedToken, DeviceCode, McpAuth, OAuthDevice, finish_device_login, form_urlencode, load_cached_token,
+    remove_cached_token, save_cached_token, start_device_login, token_cache_path,
+};
+pub use mcp_cache::{
+    CachedCapabilities, CachedTool, discover_capabilities_cached, load_cached_capabilities, mcp_cache_path,
+    refresh_cached_capabilities, save_cached_capabilities, schema_hash,
+};
+pub use mcp_health::{McpConnection, http_connection};
+pub use mcp_http::{McpHttpClient, reply_message};
+pub use mcp_server::{handle_message, resource_uri, serve_mcp};
+pub use mcp_servers::{McpServerConfig, McpServerSpec, load_mcp_servers, parse_mcp_servers};
+pub use mcp_stdio::{McpServerState, McpServerStatus, McpSupervisor, SupervisorOptions, backoff, supervisor_for};
+pub use memory::{
+    Compaction, SUMMARY_PREFIX, compact_conversation, compaction_range, conversation_tokens, is_context_message, is_summary,
+    memory_threshold, summarize_messages, summary_message,
+};
+pub use mmr::{cosine, mmr_select};
+pub use model_info::{DEFAULT_CONTEXT_WINDOW, context_window, context_window_async, parse_context_length};
+pub use notebook::{NotebookCell, parse_notebook};
+pub use ollama_pool::{BalancePolicy, choose_host, parse_ollama_urls};
+pub use panels::{PanelRegistry, PanelSpec, PanelState, parse_panels, run_panel};
+pub use paths::{DirKind, default_dir, dir_for, dir_kind, legacy_dir, migrate_legacy_dir};
+pub use pipeline::{PIPELINE_STAGES, Stage, parse_pipeline, run_pipeline_async};
+pub use platform::{
+    clipboard_commands, clipboard_context, clipboard_read_commands, copy_to_clipboard, default_shell, editor_command,
+    home_dir, is_absolute_path, open_command, read_clipboard, shell_command, shell_flags, split_command_line,
+};
+pub use prompt_history::{PromptHistory, history_path};
+pub use prompt_lint::{PromptIssue, lint_prompt, wrap_prompt};
+pub use query_expansion::{
+    annotate_broadened, broadened_scope, expand_query, merge_hits, retrieve_broadening, retrieve_for_query,
+};
+pub use quota::{ChunkQuota, QuotaCap};
+pub use replay::{ReplayEntry, load_fixture, reset_replay};
+pub use rerank::{LlmReranker, RerankFuture, Reranker, rerank_hits, reranker_for};
+#[cfg(feature = "local-rerank")]
+pub use rerank::CrossEncoderReranker;
+pub use rest_server::{
+    HttpRequest, answer_json, chat_messages, cross_site_reason, handle_request, index_source_allowed, is_loopback_addr,
+    read_request, rest_answer_config, serve_rest,
+};
+pub use retrieve::{RetrieveOptions, format_hit_list, retrieve};
+pub use retrieve_chunks::{Hit, Payload};
+pub use session::{Session, clean_title, generate_title, list_sessions, sessions_dir};
+pub use session_env::{apply_env_command, describe_env, parse_env_assignment};
+pub use share::{
+    ShareAction, ShareTarget, conversation_title, export_markdown, parse_share_targets,
+    share_conversation,
+};
+pub use snapshot::{
+    SnapshotHeader, SnapshotSummary, create_snapshot, is_native_snapshot, read_snapshot_header,
+    restore_snapshot,
+};
+pub use snippets::{
+    Snippets, complete_snippet, expand_snippets, is_snippet_name, load_snippets, parse_snippet, save_snippets,
+    snippets_path,
+};
+pub use stats::{CollectionStats, collection_stats, format_bytes};
+pub use store_access::{
+    check_writable, corpus_origin, jwt_is_read_only, store_read_only, store_read_only_async,
+};
+pub use store_qdrant::{collection_tuning, ensure_collection, point_id};
+pub use telemetry::{
+    TelemetryLog, clear_telemetry, error_category, export_telemetry, record_error, record_feature,
+    telemetry_log, telemetry_path,
+};
+pub use templates::{
+    Templates, fill_template, load_templates, parse_template, parse_template_var, save_templates, template_placeholders,
+    templates_path,
+};
+pub use time_travel::{
+    SnapshotEntry, find_snapshot, list_snapshots, parse_as_of, snapshot_entry, snapshots_dir, with_snapshot,
+};
+pub use timestamps::{
+    DateStyle, date_style, format_local, format_local_time, format_time, now_secs, parse_rfc3339, parse_utc_offset,
+    rfc3339, rfc3339_millis, utc_offset,
+};
+pub use tokens::{estimate_tokens, truncate_to_tokens};
+pub use tool::{Tool, tool_spec};
+pub use tool_filter::{ToolFilter, glob_match};
+pub use tool_schema::validate_tool_args;
+pub use trace::{start_trace, trace_event, traces_dir};
+pub use usage::{
+    Usage, budget_exceeded, current_usage, daily_usage, estimate_cost, record_call, record_daily_usage, reset_usage,
+    take_usage,
+};
+pub use viewer::{Viewer, ViewerKind, answer_html, parse_viewers, plain_text, viewer_command, write_view_file};
+pub use web::{
+    Heading, WebPage, anchor_for_chunk, html_to_text, is_url, page_headings, resolve_url,
+    sitemap_locations,
+};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Instant;
+
+use build_prompt::{build_prompt_without_context, format_display_context_from_hits, no_relevant_context_message};
+use chunk_text::{chunk_text, TextChunk};
+use embed_chunks::embed_texts_async;
+use best_of::generate_best_of_async;
+use extractive::extract_quotes_async;
+use generate::{generate_answer_async, generate_answer_stream};
+use git_range::git_range_files;
+use git_source::{clone_repo, collection_for_repo, repo_files};
+use model_info::context_window_async;
+use notebook::{is_notebook, parse_notebook};
+use query_expansion::retrieval_broadening_async;
+use retrieve_chunks::Retrieval;
+use runtime::{block_on, try_block_on};
+use scan_files::scan_files_with_stats;
+use store_qdrant::{
+    collection_info_async, delete_collection_async, delete_points_async, ensure_collection_async, point_id,
+    store_points_async, Point, PointPayload,
+};
+use web::fetch_web_sources;
+
+/// Index a directory or URL; the stats list files skipped as generated.
+pub fn index_corpus(cfg: &Config, source: Option<&str>) -> Result<ScanStats, String> {
+    block_on(index_corpus_async(cfg, source))
+}
+
+pub async fn index_corpus_async(cfg: &Config, source: Option<&str>) -> Result<ScanStats, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: indexing is disabled.".to_string());
+    }
+    check_writable(cfg, "indexing")?;
+    let (files, headings, stats) = match source {
+        Some(url) if is_url(url) => {
+            let pages = fetch_web_sources(cfg, url).await?;
+            let headings = pages.iter().map(|p| (p.url.clone(), p.headings.clone())).collect();
+            let stats = ScanStats {
+                indexed: pages.len(),
+                ..ScanStats::default()
+            };
+            (pages.into_iter().map(|p| (p.url, p.text)).collect(), headings, stats)
+        }
+        _ => {
+            let (files, stats) = scan_files_with_stats(cfg, source);
+            (files, HashMap::new(), stats)
+        }
+    };
+    let run = index_documents(cfg, files, None, headings).await?;
+    Ok(stats.with_run(run))
+}
+
+/// Shallow-clone a git repository and index it into its own collection,
+/// named from the repo. Returns the collection name and what was capped or
+/// quarantined.
+pub fn index_git_repo(cfg: &Config, url: &str) -> Result<(String, ScanStats), String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: indexing is disabled.".to_string());
+    }
+    check_writable(cfg, "indexing")?;
+    let repo = clone_repo(url)?;
+    let mut repo_cfg = cfg.clone();
+    repo_cfg.collection = collection_for_repo(url);
+    let files = repo_files(&repo_cfg, url, &repo);
+    let stats = block_on(index_documents(&repo_cfg, files, Some(&repo.commit), HashMap::new()))?;
+    Ok((repo_cfg.collection, stats))
+}
+
+/// Index only the files changed in `range` (e.g. `v1.2..HEAD`) of the
+/// repository holding `source` into `cfg.collection`, which should come
+/// from `collection_for_git_range`. The collection is rebuilt each run, so
+/// it never holds files from an earlier range.
+pub async fn index_git_range_async(cfg: &Config, source: Option<&str>, range: &str) -> Result<ScanStats, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: indexing is disabled.".to_string());
+    }
+    check_writable(cfg, "indexing")?;
+    let (files, stats) = git_range_files(cfg, source.unwrap_or(&cfg.source_dir), range)?;
+    if collection_info_async(cfg).await?.is_some() {
+        delete_collection_async(cfg).await?;
+    }
+    let manifest = manifest_path(cfg);
+    if manifest.exists() {
+        std::fs::remove_file(&manifest).map_err(|e| format!("Failed to remove {}: {}", manifest.display(), e))?;
+    }
+    let run = index_documents(cfg, files, None, HashMap::new()).await?;
+    Ok(stats.with_run(run))
+}
+
+/// Index the files quarantined in the manifest again: local files are read
+/// from disk and web pages fetched anew. Files from `aicli index --git` are
+/// skipped, and files that no longer exist leave the quarantine.
+pub fn retry_quarantined(cfg: &Config) -> Result<ScanStats, String> {
+    block_on(retry_quarantined_async(cfg))
+}
+
+pub async fn retry_quarantined_async(cfg: &Config) -> Result<ScanStats, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: indexing is disabled.".to_string());
+    }
+    check_writable(cfg, "indexing")?;
+    let mut manifest = IndexManifest::load(cfg)?;
+    let mut stats = ScanStats::default();
+    let mut files = Vec::new();
+    let mut headings = HashMap::new();
+    let mut gone = Vec::new();
+    for (path, entry) in &manifest.quarantine {
+        if entry.commit.is_some() {
+            stats.skipped.push((path.clone(), "from a git clone; run `aicli index --git` again".to_string()));
+            continue;
+        }
+        match reread_source(cfg, path).await {
+            Ok(sources) => {
+                for (source, text, page_headings) in sources {
+                    headings.insert(source.clone(), page_headings);
+                    files.push((source, text));
+                }
+            }
+            Err(reason) => {
+                stats.skipped.push((path.clone(), reason.to_string()));
+                if !is_url(path) {
+                    gone.push(path.clone());
+                }
+            }
+        }
+    }
+    if !gone.is_empty() {
+        for path in &gone {
+            manifest.quarantine.remove(path);
+        }
+        manifest.save(cfg)?;
+    }
+    let retried: Vec<String> = files.iter().map(|(path, _)| path.clone()).collect();
+    let run = index_documents(cfg, files, None, headings).await?;
+    // The run may also re-index (and quarantine) sources of a dropped
+    // collection, so count only the retried files that made it in.
+    let manifest = IndexManifest::load(cfg)?;
+    let indexed = retried
+        .iter()
+        .filter(|path| manifest.files.contains_key(*path) && !manifest.quarantine.contains_key(*path))
+        .count();
+    Ok(ScanStats {
+        indexed,
+        ..stats.with_run(run)
+    })
+}
+
+/// Read an indexed source again: a local file from disk, or a web page
+/// fetched anew without following its links. Each source comes back as
+/// its path, text, and headings; the error is the reason it was skipped.
+async fn reread_source(cfg: &Config, path: &str) -> Result<Vec<(String, String, Vec<Heading>)>, &'static str> {
+    if !is_url(path) {
+        let text = std::fs::read_to_string(path).map_err(|_| "source missing")?;
+        return Ok(vec![(path.to_string(), text, Vec::new())]);
+    }
+    let mut page_cfg = cfg.clone();
+    page_cfg.crawl_depth = 0;
+    let pages = fetch_web_sources(&page_cfg, path).await.map_err(|_| "fetch failed")?;
+    Ok(pages.into_iter().map(|page| (page.url, page.text, page.headings)).collect())
+}
+
+/// Embed and store `files`, returning the files cut short by chunk quotas,
+/// those quarantined because their embeddings kept failing, and sources of
+/// a dropped collection that could not be read again.
+async fn index_documents(
+    cfg: &Config,
+    files: Vec<(String, String)>,
+    commit: Option<&str>,
+    mut headings: HashMap<String, Vec<Heading>>,
+) -> Result<ScanStats, String> {
+    let mut stats = ScanStats::default();
     if files.is_empty() {
-        return Ok(());
+        return Ok(stats);
     }
+    let mut quota = ChunkQuota::new(cfg);
+    // Chunks each file was allowed, so a retry is not charged twice.
+    let mut allowed_chunks: HashMap<String, usize> = HashMap::new();
+    let mut failed: Vec<(String, String)> = Vec::new();
+    let mut retries_left = cfg.embed_retries;
 
-    let mut next_id: i64 = 1;
     let mut collection_ready = false;
+    // Point ids are derived from chunk content, so entries for files outside
+    // this run stay valid; only re-indexed files are replaced.
+    let mut manifest = IndexManifest::load(cfg)?;
+    manifest.collection = cfg.collection.clone();
+    manifest.distance = cfg.distance.clone();
 
-    for (path, text) in files {
-        let chunks = chunk_text(&text, cfg);
-        if chunks.is_empty() {
+    let mut queue: VecDeque<(String, String)> = files.into();
+    let mut indexed: HashSet<String> = HashSet::new();
+    loop {
+        let Some((path, text)) = queue.pop_front() else {
+            // Give files whose embeddings failed another pass once the
+            // rest are stored.
+            if failed.is_empty() || retries_left == 0 {
+                break;
+            }
+            retries_left -= 1;
+            queue.extend(failed.drain(..));
             continue;
-        }
-        let vectors = embed_texts(cfg, &chunks)?;
-        if vectors.is_empty() {
+        };
+        indexed.insert(path.clone());
+        let mut chunks = chunks_for_file(cfg, &path, &text);
+        let allowed = match allowed_chunks.get(&path) {
+            Some(&allowed) => allowed,
+            None => {
+                let (allowed, cap) = quota.allow(&path, chunks.len());
+                stats.capped.extend(cap);
+                allowed_chunks.insert(path.clone(), allowed);
+                allowed
+            }
+        };
+        chunks.truncate(allowed);
+        if chunks.is_empty() {
             continue;
         }
+        let texts: Vec<String> = chunks.iter().map(|(c, _)| c.text.clone()).collect();
+        // A failed batch quarantines the file instead of ending the run; its
+        // earlier points, if any, stay until a retry replaces them.
+        let embedded = match embed_texts_async(cfg, &texts).await {
+            Ok(vectors) if vectors.len() == texts.len() => Ok(vectors),
+            Ok(vectors) => Err(format!(
+                "{} returned {} embeddings for {} chunks",
+                cfg.embed_model,
+                vectors.len(),
+                texts.len()
+            )),
+            Err(err) => Err(err),
+        };
+        let vectors = match embedded {
+            Ok(vectors) => vectors,
+            Err(error) => {
+                let entry = QuarantinedFile {
+                    chunks: texts.len(),
+                    error,
+                    failed_at: timestamps::now_secs(),
+                    commit: commit.map(|c| c.to_string()),
+                };
+                manifest.quarantine.insert(path.clone(), entry);
+                manifest.save(cfg)?;
+                failed.push((path, text));
+                continue;
+            }
+        };
         if !collection_ready {
-            ensure_collection(cfg, vectors[0].len())?;
+            if ensure_collection_async(cfg, vectors[0].len()).await? {
+                // The embedding model changed and the old collection was
+                // dropped: queue every other source it held so the rebuilt
+                // index covers the same ones. A git clone is indexed whole,
+                // so blobs of an earlier clone need nothing.
+                let previous = std::mem::take(&mut manifest.files);
+                let queued: HashSet<String> = queue.iter().map(|(p, _)| p.clone()).collect();
+                for old_path in previous.into_keys() {
+                    if commit.is_some() || indexed.contains(&old_path) || queued.contains(&old_path) {
+                        continue;
+                    }
+                    match reread_source(cfg, &old_path).await {
+                        Ok(sources) => {
+                            for (source, text, page_headings) in sources {
+                                headings.insert(source.clone(), page_headings);
+                                queue.push_back((source, text));
+                            }
+                        }
+                        Err(reason) => stats
+                            .skipped
+                            .push((old_path, format!("dropped with the old collection, {}", reason))),
+                    }
+                }
+            }
+            manifest.vector_size = vectors[0].len();
             collection_ready = true;
         }
+        // An embedding model swapped mid-run (or a host in the pool running
+        // another model) would otherwise fail inside the upsert.
+        if let Some(vector) = vectors.iter().find(|v| v.len() != manifest.vector_size) {
+            return Err(format!(
+                "{} produced {}-dimensional vectors for {} after {}-dimensional ones earlier in this run. Check that every Ollama host serves the same OLLAMA_EMBED_MODEL.",
+                cfg.embed_model,
+                vector.len(),
+                path,
+                manifest.vector_size
+            ));
+        }
 
+        // Web pages and git blobs are cited by URL; pages also get the
+        // nearest heading anchor so the link lands on the right section.
+        let url = is_url(&path).then(|| path.clone());
+        let page_headings = headings.get(&path).map(Vec::as_slice).unwrap_or(&[]);
+        let params = cfg.chunk_params(&path);
         let mut points = Vec::new();
-        for (idx, (chunk, vector)) in chunks.iter().cloned().zip(vectors).enumerate() {
+        for (idx, ((chunk, cell), vector)) in chunks.into_iter().zip(vectors).enumerate() {
             points.push(Point {
-                id: next_id,
+                id: point_id(&path, idx, &chunk.text),
                 vector,
                 payload: PointPayload {
                     path: path.clone(),
+                    url: url.clone(),
+                    anchor: anchor_for_chunk(&text, page_headings, chunk.offset),
                     index: idx,
-                    chunk,
+                    chunk: chunk.text,
+                    before: chunk.before,
+                    after: chunk.after,
+                    cell_index: cell.as_ref().map(|(i, _)| *i),
+                    cell_type: cell.map(|(_, t)| t),
+                    commit: commit.map(|c| c.to_string()),
+                    doc_type: match cell.as_ref() {
+                        Some((_, t)) if t == "markdown" => "docs".to_string(),
+                        _ => classify_chunk(&path, &chunk.text).to_string(),
+                    },
+                    chunk_size: params.size,
+                    chunk_overlap: params.overlap,
                 },
             });
-            next_id += 1;
         }
-        store_points(cfg, &points)?;
+        store_points_async(cfg, &points).await?;
+        let point_ids: Vec<serde_json::Value> = points.iter().map(|p| p.id.clone().into()).collect();
+        // Chunks that changed or disappeared since the last run.
+        if let Some(previous) = manifest.files.get(&path) {
+            let stale: Vec<serde_json::Value> = previous
+                .point_ids
+                .iter()
+                .filter(|id| !point_ids.contains(id))
+                .cloned()
+                .collect();
+            delete_points_async(cfg, &stale).await?;
+        }
+        manifest.files.insert(
+            path.clone(),
+            ManifestFile {
+                chunks: points.len(),
+                point_ids,
+            },
+        );
+        manifest.quarantine.remove(&path);
+        manifest.save(cfg)?;
     }
 
-    Ok(())
+    stats.quarantined = failed.into_iter().map(|(path, _)| path).collect();
+    Ok(stats)
+}
+
+/// Chunks for one file, with `(cell index, cell type)` for notebook cells.
+/// Notebooks are chunked per cell so the raw JSON is never embedded.
+fn chunks_for_file(cfg: &Config, path: &str, text: &str) -> Vec<(TextChunk, Option<(usize, String)>)> {
+    let params = cfg.chunk_params(path);
+    if !is_notebook(path) {
+        return chunk_text(text, params, cfg).into_iter().map(|c| (c, None)).collect();
+    }
+    let Ok(cells) = parse_notebook(text) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for cell in cells {
+        for chunk in chunk_text(&cell.source, params, cfg) {
+            out.push((chunk, Some((cell.index, cell.cell_type.clone()))));
+        }
+    }
+    out
 }
 
 pub fn answer_query(cfg: &Config, question: &str) -> Result<(String, String), String> {
-    answer_query_hybrid(cfg, question)
+    answer_query_hybrid(cfg, question, &AgentOptions::from_config(cfg))
+}
+
+pub fn answer_query_with_history(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+) -> Result<(String, String), String> {
+    answer_query_hybrid_with_history(cfg, history, question, &AgentOptions::from_config(cfg))
+}
+
+/// Like `answer_query_with_history`, returning sources, prompt, model, and
+/// timing. `RAG_ANSWER_PIPELINE` picks the agent loop or the extractive
+/// quote-then-synthesize pipeline.
+pub fn answer_query_detailed(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+) -> Result<AnswerResult, String> {
+    answer_query_detailed_with_events(cfg, history, question, &AgentOptions::from_config(cfg), None)
+}
+
+/// `answer_query_detailed` that reports each agent step to `events`. The
+/// extractive pipeline has no agent loop and sends nothing.
+pub fn answer_query_detailed_with_events(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+    events: Option<std::sync::mpsc::Sender<AgentEvent>>,
+) -> Result<AnswerResult, String> {
+    let mut result = if is_extractive(cfg) {
+        block_on(answer_query_extractive_detailed_async(cfg, history, question))?
+    } else {
+        answer_query_hybrid_with_events(cfg, history, question, options, events)?
+    };
+    let checks = check_answer_code(cfg, &result.answer);
+    result.answer = annotate_code_checks(result.answer, &checks);
+    Ok(result)
+}
+
+fn is_extractive(cfg: &Config) -> bool {
+    cfg.answer_pipeline.eq_ignore_ascii_case("extractive")
+}
+
+/// Two-stage answer: pull verbatim quotes out of the retrieved chunks, then
+/// answer from those quotes only. The context pane shows the quotes.
+pub async fn answer_query_extractive_detailed_async(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+) -> Result<AnswerResult, String> {
+    let started = Instant::now();
+    let deadline = Deadline::from_config(cfg);
+    let cfg = &deadline.limit_config(cfg);
+    context_window_async(cfg).await;
+    let Some(found) = deadline.run(retrieval_broadening_async(cfg, question)).await else {
+        return Ok(timed_out_result(cfg, &deadline, started, String::new(), &[], Vec::new()));
+    };
+    let Retrieval { hits, broadened, rerank_time, rerank_error } = found?;
+    let quotes = if hits.is_empty() {
+        Vec::new()
+    } else {
+        match deadline.run(extract_quotes_async(cfg, question, &hits)).await {
+            Some(quotes) => quotes?,
+            None => {
+                let context = format_display_context_from_hits(&hits);
+                return Ok(AnswerResult {
+                    rerank_time,
+                    rerank_error,
+                    ..timed_out_result(cfg, &deadline, started, context, &hits, Vec::new())
+                });
+            }
+        }
+    };
+    if quotes.is_empty() {
+        return Ok(AnswerResult {
+            context: if hits.is_empty() { no_relevant_context_message(cfg) } else { format_quotes(&quotes) },
+            answer: annotate_broadened(
+                "I could not find passages in the indexed documents that answer this question.".to_string(),
+                broadened.as_deref(),
+            ),
+            hits: hits.iter().map(SourceHit::from).collect(),
+            prompt: Vec::new(),
+            model: cfg.chat_model.clone(),
+            elapsed: started.elapsed(),
+            rerank_time,
+            rerank_error,
+            details: None,
+            usage: current_usage(),
+        });
+    }
+    let mut prompt = build_synthesis_prompt(cfg, question, &quotes);
+    let user_turn = prompt.pop();
+    prompt.extend(history.iter().cloned());
+    prompt.extend(user_turn);
+    let Some(answer) = deadline.run(generate_best_of_async(cfg, &prompt)).await else {
+        return Ok(AnswerResult {
+            rerank_time,
+            rerank_error,
+            ..timed_out_result(cfg, &deadline, started, format_quotes(&quotes), &hits, prompt)
+        });
+    };
+    let answer = annotate_broadened(answer?, broadened.as_deref());
+    Ok(AnswerResult {
+        context: format_quotes(&quotes),
+        answer,
+        hits: hits.iter().map(SourceHit::from).collect(),
+        prompt,
+        model: cfg.chat_model.clone(),
+        elapsed: started.elapsed(),
+        rerank_time,
+        rerank_error,
+        details: None,
+        usage: current_usage(),
+    })
+}
+
+pub async fn answer_query_async(cfg: &Config, question: &str) -> Result<(String, String), String> {
+    let result = answer_query_detailed_async(cfg, &[], question).await?;
+    Ok((result.context, result.answer))
+}
+
+/// Async counterpart of `answer_query_detailed`. The agent loop drives the
+/// blocking MCP client, so it runs on tokio's blocking pool.
+pub async fn answer_query_detailed_async(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+) -> Result<AnswerResult, String> {
+    let cfg = cfg.clone();
+    let history = history.to_vec();
+    let question = question.to_string();
+    tokio::task::spawn_blocking(move || answer_query_detailed(&cfg, &history, &question))
+        .await
+        .map_err(|e| format!("Answer task failed: {}", e))?
 }
 
 pub fn answer_query_classic(cfg: &Config, question: &str) -> Result<(String, String), String> {
-    let query_vec = embed_query(cfg, question)?;
-    let hits = retrieve_top(cfg, &query_vec)?;
+    let result = answer_query_classic_detailed(cfg, question)?;
+    Ok((result.context, result.answer))
+}
+
+pub fn answer_query_classic_detailed(cfg: &Config, question: &str) -> Result<AnswerResult, String> {
+    block_on(answer_query_classic_detailed_async(cfg, question))
+}
+
+pub async fn answer_query_classic_detailed_async(
+    cfg: &Config,
+    question: &str,
+) -> Result<AnswerResult, String> {
+    let started = Instant::now();
+    let deadline = Deadline::from_config(cfg);
+    let cfg = &deadline.limit_config(cfg);
+    // Prompt budgeting reads the cached window synchronously; detect it first.
+    context_window_async(cfg).await;
+    let Some(found) = deadline.run(retrieval_broadening_async(cfg, question)).await else {
+        return Ok(timed_out_result(cfg, &deadline, started, String::new(), &[], Vec::new()));
+    };
+    let Retrieval { hits, broadened, rerank_time, rerank_error } = found?;
+    if hits.is_empty() {
+        let context = no_relevant_context_message(cfg);
+        let mut prompt = Vec::new();
+        let answer = if cfg.empty_context_fallback {
+            prompt = build_prompt_without_context(cfg, question);
+            match deadline.run(generate_answer_async(cfg, &prompt)).await {
+                Some(answer) => answer?,
+                None => {
+                    return Ok(AnswerResult {
+                        rerank_time,
+                        rerank_error,
+                        ..timed_out_result(cfg, &deadline, started, context, &[], prompt)
+                    })
+                }
+            }
+        } else {
+            "I could not find anything relevant to this question in the indexed documents.".to_string()
+        };
+        return Ok(AnswerResult {
+            context,
+            answer: annotate_broadened(answer, broadened.as_deref()),
+            hits: Vec::new(),
+            prompt,
+            model: cfg.chat_model.clone(),
+            elapsed: started.elapsed(),
+            rerank_time,
+            rerank_error,
+            details: None,
+            usage: current_usage(),
+        });
+    }
     let (messages, context) = build_prompt_with_context(cfg, question, &hits);
-    let answer = generate_answer(cfg, &messages)?;
-    Ok((context, answer))
+    let Some(answer) = deadline.run(generate_best_of_async(cfg, &messages)).await else {
+        return Ok(AnswerResult {
+            rerank_time,
+            rerank_error,
+            ..timed_out_result(cfg, &deadline, started, context, &hits, messages)
+        });
+    };
+    let answer = annotate_broadened(answer?, broadened.as_deref());
+    let checks = check_answer_code(cfg, &answer);
+    let answer = annotate_code_checks(answer, &checks);
+    Ok(AnswerResult {
+        context,
+        answer,
+        hits: hits.iter().map(SourceHit::from).collect(),
+        prompt: messages,
+        model: cfg.chat_model.clone(),
+        elapsed: started.elapsed(),
+        rerank_time,
+        rerank_error,
+        details: None,
+        usage: current_usage(),
+    })
+}
+
+// What a query that ran out of time returns: the context gathered so far
+// and the timeout message as the answer.
+fn timed_out_result(
+    cfg: &Config,
+    deadline: &Deadline,
+    started: Instant,
+    context: String,
+    hits: &[Hit],
+    prompt: Vec<Message>,
+) -> AnswerResult {
+    AnswerResult {
+        context: if context.is_empty() { "(no context gathered before the timeout)".to_string() } else { context },
+        answer: deadline.timeout_message(),
+        hits: hits.iter().map(SourceHit::from).collect(),
+        prompt,
+        model: cfg.chat_model.clone(),
+        elapsed: started.elapsed(),
+        rerank_time: None,
+        rerank_error: None,
+        details: None,
+        usage: current_usage(),
+    }
+}
+
+// Streamed answers get the broadening note as a final token.
+fn stream_broadened_note(answer: String, note: Option<&str>, on_token: &mut dyn FnMut(&str) -> bool) -> String {
+    if note.is_none() {
+        return answer;
+    }
+    let kept = answer.trim_end().len();
+    let annotated = annotate_broadened(answer, note);
+    on_token(&annotated[kept..]);
+    annotated
+}
+
+// Streamed answers get the `RAG_VERIFY_CODE` results as a final token.
+fn stream_code_checks(cfg: &Config, answer: String, on_token: &mut dyn FnMut(&str) -> bool) -> String {
+    let checks = check_answer_code(cfg, &answer);
+    if checks.is_empty() {
+        return answer;
+    }
+    let kept = answer.trim_end().len();
+    let annotated = annotate_code_checks(answer, &checks);
+    on_token(&annotated[kept..]);
+    annotated
+}
+
+/// `answer_query_streaming_with_events` with the agent options from the
+/// config and no step events.
+pub fn answer_query_streaming(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    on_token: &mut dyn FnMut(&str) -> bool,
+) -> Result<AnswerResult, String> {
+    answer_query_streaming_with_events(cfg, history, question, &AgentOptions::from_config(cfg), None, on_token)
+}
+
+/// Answer `question` through `on_token`. RAG-only mode and the extractive
+/// pipeline stream a retrieval-grounded answer as it is generated. In the
+/// other modes the agent may call tools before it answers, so it runs as
+/// `answer_query_detailed_with_events` does and its answer is sent whole.
+pub fn answer_query_streaming_with_events(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+    events: Option<std::sync::mpsc::Sender<AgentEvent>>,
+    on_token: &mut dyn FnMut(&str) -> bool,
+) -> Result<AnswerResult, String> {
+    if is_extractive(cfg) || options.mode == AgentMode::RagOnly {
+        return stream_grounded_answer(cfg, history, question, on_token);
+    }
+    let result = answer_query_detailed_with_events(cfg, history, question, options, events)?;
+    on_token(&result.answer);
+    Ok(result)
+}
+
+/// Retrieval-grounded answer streamed through `on_token`; returning `false`
+/// stops generation and the partial answer is returned as the result.
+/// `history` sits between the system prompt and the question, so a follow-up
+/// such as "shorter, focus on error handling" refines the previous answer.
+fn stream_grounded_answer(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    on_token: &mut dyn FnMut(&str) -> bool,
+) -> Result<AnswerResult, String> {
+    let started = Instant::now();
+    let deadline = Deadline::from_config(cfg);
+    let cfg = &deadline.limit_config(cfg);
+    let found = match try_block_on(deadline.run(retrieval_broadening_async(cfg, question)))? {
+        Some(found) => found?,
+        None => {
+            let result = timed_out_result(cfg, &deadline, started, String::new(), &[], Vec::new());
+            on_token(&result.answer);
+            return Ok(result);
+        }
+    };
+    let Retrieval { hits, broadened, rerank_time, rerank_error } = found;
+    let (mut prompt, context) = if is_extractive(cfg) && !hits.is_empty() {
+        let quotes = match try_block_on(deadline.run(extract_quotes_async(cfg, question, &hits)))? {
+            Some(quotes) => quotes?,
+            None => {
+                let context = format_display_context_from_hits(&hits);
+                let result = AnswerResult {
+                    rerank_time,
+                    rerank_error,
+                    ..timed_out_result(cfg, &deadline, started, context, &hits, Vec::new())
+                };
+                on_token(&result.answer);
+                return Ok(result);
+            }
+        };
+        if quotes.is_empty() {
+            let answer = "I could not find passages in the indexed documents that answer this question.".to_string();
+            on_token(&answer);
+            let answer = stream_broadened_note(answer, broadened.as_deref(), on_token);
+            return Ok(AnswerResult {
+                context: format_quotes(&quotes),
+                answer,
+                hits: hits.iter().map(SourceHit::from).collect(),
+                prompt: Vec::new(),
+                model: cfg.chat_model.clone(),
+                elapsed: started.elapsed(),
+                rerank_time,
+                rerank_error,
+                details: None,
+                usage: current_usage(),
+            });
+        }
+        (build_synthesis_prompt(cfg, question, &quotes), format_quotes(&quotes))
+    } else if hits.is_empty() {
+        if history.is_empty() && !cfg.empty_context_fallback {
+            let answer = "I could not find anything relevant to this question in the indexed documents.".to_string();
+            on_token(&answer);
+            let answer = stream_broadened_note(answer, broadened.as_deref(), on_token);
+            return Ok(AnswerResult {
+                context: no_relevant_context_message(cfg),
+                answer,
+                hits: Vec::new(),
+                prompt: Vec::new(),
+                model: cfg.chat_model.clone(),
+                elapsed: started.elapsed(),
+                rerank_time,
+                rerank_error,
+                details: None,
+                usage: current_usage(),
+            });
+        }
+        (build_prompt_without_context(cfg, question), no_relevant_context_message(cfg))
+    } else {
+        build_prompt_with_context(cfg, question, &hits)
+    };
+    let user_turn = prompt.pop();
+    prompt.extend(history.iter().cloned());
+    prompt.extend(user_turn);
+    // Past the deadline the stream is stopped and what arrived is kept.
+    let mut streamed = String::new();
+    let result = {
+        let mut guarded = |piece: &str| {
+            streamed.push_str(piece);
+            on_token(piece) && !deadline.expired()
+        };
+        generate_answer_stream(cfg, &prompt, &mut guarded)
+    };
+    let answer = if deadline.expired() {
+        let note = format!("\n\n{}", deadline.timeout_message());
+        on_token(&note);
+        format!("{}{}", streamed.trim_end(), note)
+    } else {
+        let answer = stream_broadened_note(result?, broadened.as_deref(), on_token);
+        stream_code_checks(cfg, answer, on_token)
+    };
+    Ok(AnswerResult {
+        context,
+        answer,
+        hits: hits.iter().map(SourceHit::from).collect(),
+        prompt,
+        model: cfg.chat_model.clone(),
+        elapsed: started.elapsed(),
+        rerank_time,
+        rerank_error,
+        details: None,
+        usage: current_usage(),
+    })
 }
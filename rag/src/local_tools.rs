@@ -0,0 +1,486 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::http::{get_unredirected_async, HttpOptions};
+use crate::mcp::McpTool;
+use crate::runtime::block_on;
+use crate::tool::{tool_spec, Tool};
+use crate::web::{html_to_text, is_url, resolve_url};
+
+const READ_MAX_LINES: usize = 200;
+const GREP_MAX_RESULTS: usize = 50;
+const OUTPUT_MAX_CHARS: usize = 16_000;
+const SHELL_TIMEOUT: Duration = Duration::from_secs(30);
+const FETCH_MAX_REDIRECTS: usize = 5;
+const SKIP_DIRS: &[&str] = &[".git", "target", "node_modules", ".venv", "__pycache__"];
+
+pub const LOCAL_TOOL_NAMES: &[&str] = &["read_file", "grep", "shell", "fetch_url"];
+
+/// Tools the agent can use without an MCP server. Which ones are offered
+/// comes from `AICLI_LOCAL_TOOLS`; file access and shell path arguments
+/// stay inside the sandbox root (see `sandbox_root`) and any
+/// `AICLI_SANDBOX_EXTRA` directories, and `shell` only runs programs named
+/// in `AICLI_SHELL_ALLOW`. Tools registered with `register` come first and
+/// replace a built-in tool of the same name.
+#[derive(Clone, Debug, Default)]
+pub struct LocalToolRegistry {
+    custom: Vec<Arc<dyn Tool>>,
+    enabled: Vec<String>,
+    workspace: PathBuf,
+    extra_roots: Vec<PathBuf>,
+    shell_allow: Vec<String>,
+    fetch_allow: Vec<String>,
+    /// `/env` overrides the shell tool runs with.
+    env: BTreeMap<String, String>,
+    safe_mode: bool,
+    http: Option<HttpOptions>,
+}
+
+impl LocalToolRegistry {
+    pub fn from_config(cfg: &Config) -> Self {
+        let enabled = LOCAL_TOOL_NAMES
+            .iter()
+            .filter(|name| cfg.local_tools.iter().any(|t| t == *name))
+            // Safe mode never offers a shell or a way to send data out.
+            .filter(|name| !(cfg.safe_mode && matches!(**name, "shell" | "fetch_url")))
+            .map(|name| name.to_string())
+            .collect();
+        Self {
+            custom: Vec::new(),
+            enabled,
+            workspace: sandbox_root(cfg),
+            extra_roots: cfg.sandbox_extra.iter().map(|dir| canonical(&expand_home(dir))).collect(),
+            shell_allow: cfg.shell_allow.clone(),
+            fetch_allow: cfg.fetch_allow.clone(),
+            env: cfg.session_env.clone(),
+            safe_mode: cfg.safe_mode,
+            http: Some(HttpOptions::standard(cfg)),
+        }
+    }
+
+    /// Add a Rust tool, replacing any registered tool with the same name.
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.custom.retain(|t| t.name() != tool.name());
+        self.custom.push(tool);
+    }
+
+    /// Drop the built-in tools whose names appear in `names`. Registered
+    /// tools are kept.
+    pub fn without(mut self, names: &[String]) -> Self {
+        self.enabled.retain(|t| !names.contains(t));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.custom.is_empty() && self.enabled.is_empty()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.registered(name).is_some() || self.enabled.iter().any(|t| t == name)
+    }
+
+    fn registered(&self, name: &str) -> Option<&Arc<dyn Tool>> {
+        self.custom.iter().find(|t| t.name() == name)
+    }
+
+    /// Name, description, and input schema of each registered and enabled
+    /// tool, in the shape MCP servers report them.
+    pub fn specs(&self) -> Vec<McpTool> {
+        let builtin = self
+            .enabled
+            .iter()
+            .filter(|name| self.registered(name).is_none())
+            .filter_map(|name| self.spec(name));
+        self.custom.iter().map(|t| tool_spec(t.as_ref())).chain(builtin).collect()
+    }
+
+    fn spec(&self, name: &str) -> Option<McpTool> {
+        let (description, schema) = match name {
+            "read_file" => (
+                format!("Read a text file in the workspace. Returns up to {} numbered lines.", READ_MAX_LINES),
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string" },
+                        "start_line": { "type": "integer" },
+                        "max_lines": { "type": "integer" }
+                    },
+                    "required": ["path"],
+                    "additionalProperties": false
+                }),
+            ),
+            "grep" => (
+                "Search workspace files for lines containing a text (case-insensitive). Returns path:line: text.".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string" },
+                        "path": { "type": "string" },
+                        "max_results": { "type": "integer" }
+                    },
+                    "required": ["pattern"],
+                    "additionalProperties": false
+                }),
+            ),
+            "shell" => (
+                format!(
+                    "Run a command in the workspace without a shell (no pipes or redirects). Allowed programs: {}.",
+                    if self.shell_allow.is_empty() { "(none)".to_string() } else { self.shell_allow.join(", ") }
+                ),
+                json!({
+                    "type": "object",
+                    "properties": { "command": { "type": "string" } },
+                    "required": ["command"],
+                    "additionalProperties": false
+                }),
+            ),
+            "fetch_url" => (
+                "Fetch an http(s) URL and return its visible text.".to_string(),
+                json!({
+                    "type": "object",
+                    "properties": { "url": { "type": "string" } },
+                    "required": ["url"],
+                    "additionalProperties": false
+                }),
+            ),
+            _ => return None,
+        };
+        Some(McpTool {
+            name: name.to_string(),
+            description,
+            input_schema: schema,
+        })
+    }
+
+    /// Run an enabled tool. Output longer than 16k characters is cut.
+    pub fn call(&self, name: &str, args: &Value) -> Result<String, String> {
+        if let Some(tool) = self.registered(name) {
+            return tool.execute(args).map(cap_output);
+        }
+        if !self.has(name) {
+            return Err(format!("Local tool '{}' is not enabled (AICLI_LOCAL_TOOLS)", name));
+        }
+        let text = |key: &str| args.get(key).and_then(Value::as_str).unwrap_or_default().trim().to_string();
+        let number = |key: &str| args.get(key).and_then(Value::as_u64).map(|n| n as usize);
+        let output = match name {
+            "read_file" => self.read_file(&text("path"), number("start_line"), number("max_lines")),
+            "grep" => self.grep(&text("pattern"), &text("path"), number("max_results")),
+            "shell" => self.shell(&text("command")),
+            "fetch_url" => self.fetch_url(&text("url")),
+            _ => Err(format!("Unknown local tool '{}'", name)),
+        }?;
+        Ok(cap_output(output))
+    }
+
+    /// The directory file tools and shell commands run in.
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// `path` resolved inside the sandbox. Relative paths start at the
+    /// workspace; `..`, absolute paths, and symlinks that lead out of the
+    /// workspace and the extra roots are refused.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let requested = expand_home(path);
+        let joined = if requested.is_absolute() {
+            requested
+        } else {
+            self.workspace.join(requested)
+        };
+        let resolved = canonical(&joined);
+        let inside = std::iter::once(&self.workspace)
+            .chain(&self.extra_roots)
+            .any(|root| resolved.starts_with(root));
+        if !inside {
+            return Err(format!("{} is outside the workspace {}", path, self.workspace.display()));
+        }
+        Ok(resolved)
+    }
+
+    fn read_file(&self, path: &str, start_line: Option<usize>, max_lines: Option<usize>) -> Result<String, String> {
+        if path.is_empty() {
+            return Err("read_file requires a path".to_string());
+        }
+        let resolved = self.resolve(path)?;
+        let content = fs::read_to_string(&resolved).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let start = start_line.unwrap_or(1).max(1);
+        let max = max_lines.unwrap_or(READ_MAX_LINES).clamp(1, READ_MAX_LINES);
+        let total = content.lines().count();
+        let mut out: Vec<String> = content
+            .lines()
+            .enumerate()
+            .skip(start - 1)
+            .take(max)
+            .map(|(i, line)| format!("{:>5}  {}", i + 1, line))
+            .collect();
+        if start - 1 + out.len() < total {
+            out.push(format!("... ({} lines total; pass start_line to read more)", total));
+        }
+        Ok(out.join("\n"))
+    }
+
+    fn grep(&self, pattern: &str, path: &str, max_results: Option<usize>) -> Result<String, String> {
+        if pattern.is_empty() {
+            return Err("grep requires a pattern".to_string());
+        }
+        let root = if path.is_empty() { self.workspace.clone() } else { self.resolve(path)? };
+        let needle = pattern.to_lowercase();
+        let limit = max_results.unwrap_or(GREP_MAX_RESULTS).clamp(1, GREP_MAX_RESULTS);
+        let mut out = Vec::new();
+        let walker = WalkDir::new(&root).into_iter().filter_entry(|e| {
+            e.depth() == 0 || !SKIP_DIRS.iter().any(|d| e.file_name() == *d)
+        });
+        'files: for entry in walker.filter_map(Result::ok).filter(|e| e.file_type().is_file()) {
+            // Binary and unreadable files are skipped.
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let shown = entry.path().strip_prefix(&self.workspace).unwrap_or(entry.path());
+            for (i, line) in content.lines().enumerate() {
+                if line.to_lowercase().contains(&needle) {
+                    out.push(format!("{}:{}: {}", shown.display(), i + 1, line.trim()));
+                    if out.len() == limit {
+                        out.push(format!("... (stopped at {} matches)", limit));
+                        break 'files;
+                    }
+                }
+            }
+        }
+        if out.is_empty() {
+            return Ok(format!("No matches for '{}'", pattern));
+        }
+        Ok(out.join("\n"))
+    }
+
+    fn shell(&self, command: &str) -> Result<String, String> {
+        if self.safe_mode {
+            return Err("Safe mode is on: shell commands are disabled.".to_string());
+        }
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| "shell requires a command".to_string())?;
+        let args: Vec<&str> = parts.collect();
+        if !self.shell_allow.iter().any(|p| p == program) {
+            return Err(format!(
+                "'{}' is not an allowed program (AICLI_SHELL_ALLOW: {})",
+                program,
+                if self.shell_allow.is_empty() { "empty".to_string() } else { self.shell_allow.join(", ") }
+            ));
+        }
+        // Any argument could name a file, so each must resolve inside the
+        // sandbox; `resolve` follows symlinks, so a link out is caught too.
+        let mut options_done = false;
+        for arg in &args {
+            if options_done || !arg.starts_with('-') || *arg == "-" {
+                self.resolve(arg)?;
+            } else if *arg == "--" {
+                options_done = true;
+            } else {
+                for value in flag_values(arg) {
+                    self.resolve(value)?;
+                }
+            }
+        }
+        let mut child = Command::new(program)
+            .args(&args)
+            .current_dir(&self.workspace)
+            .envs(&self.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        // Drain the pipes while waiting so a chatty command cannot block on a full pipe.
+        let drain = |pipe: Option<Box<dyn Read + Send>>| {
+            std::thread::spawn(move || {
+                let mut text = String::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_string(&mut text);
+                }
+                text
+            })
+        };
+        let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+        let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) => break status,
+                None if started.elapsed() > SHELL_TIMEOUT => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} timed out after {}s", program, SHELL_TIMEOUT.as_secs()));
+                }
+                None => std::thread::sleep(Duration::from_millis(50)),
+            }
+        };
+        let mut output = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        if !stderr.trim().is_empty() {
+            output.push_str(&format!("\n[stderr]\n{}", stderr));
+        }
+        Ok(format!("[exit {}]\n{}", status.code().unwrap_or(-1), output.trim_end()))
+    }
+
+    /// Follows redirects itself so every hop gets the same host check.
+    fn fetch_url(&self, url: &str) -> Result<String, String> {
+        if !is_url(url) {
+            return Err(format!("fetch_url needs an http(s) URL, got '{}'", url));
+        }
+        let opts = self.http.clone().ok_or_else(|| "fetch_url is not configured".to_string())?;
+        let mut url = url.to_string();
+        for _ in 0..=FETCH_MAX_REDIRECTS {
+            check_public_host(&url, &self.fetch_allow)?;
+            let resp = block_on(get_unredirected_async(&url, &opts))?;
+            if (300..400).contains(&resp.status) {
+                let location = resp.header("location").unwrap_or_default();
+                url = resolve_url(&url, location)
+                    .ok_or_else(|| format!("GET {} redirected to '{}', which is not an http(s) URL", url, location))?;
+                continue;
+            }
+            if !resp.is_success() {
+                return Err(format!("GET {} failed: {} {}", url, resp.status, resp.body));
+            }
+            let looks_html = resp.body.trim_start().starts_with('<');
+            return Ok(if looks_html { html_to_text(&resp.body) } else { resp.body });
+        }
+        Err(format!("GET {} redirected more than {} times", url, FETCH_MAX_REDIRECTS))
+    }
+}
+
+/// Refuse `url` when its host resolves to an address that is not on the
+/// public internet, so text injected into the agent cannot reach Qdrant,
+/// Ollama, aicli's own REST server, or anything else on this machine or
+/// network. Hosts in `allow` (`AICLI_FETCH_ALLOW`) are exempt.
+fn check_public_host(url: &str, allow: &[String]) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    let host = parsed.host_str().unwrap_or_default().trim_matches(['[', ']']).to_ascii_lowercase();
+    if allow.contains(&host) {
+        return Ok(());
+    }
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addrs = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Cannot resolve {}: {}", host, e))?;
+    for addr in addrs {
+        if !is_public(addr.ip()) {
+            return Err(format!(
+                "fetch_url refuses {}: it resolves to {}, which is not a public address. Add the host to AICLI_FETCH_ALLOW to allow it.",
+                host,
+                addr.ip()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            // 100.64.0.0/10 is carrier-grade NAT, shared like private space.
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || shared)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                // fc00::/7 is unique local, fe80::/10 link-local.
+                !(v6.is_loopback() || v6.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The default sandbox root: `AICLI_WORKSPACE` when set, else the git root
+/// enclosing `RAG_SOURCE_DIR`, else `RAG_SOURCE_DIR` itself. Canonical, so
+/// containment checks compare real paths.
+pub fn sandbox_root(cfg: &Config) -> PathBuf {
+    if !cfg.workspace_dir.trim().is_empty() {
+        return canonical(&expand_home(cfg.workspace_dir.trim()));
+    }
+    let source = canonical(&expand_home(&cfg.source_dir));
+    source
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .unwrap_or(source)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+        _ if path == "~" => std::env::var("HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(path)),
+        _ => PathBuf::from(path),
+    }
+}
+
+// The parts of a flag that could be a file attached to it: the value after
+// `=` (`--out=../x`), whatever follows a short flag (`-C/etc`), and anything
+// from where a path seems to start (`--file/etc/shadow`), since a long flag's
+// name cannot be told from its value.
+fn flag_values(flag: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    if let Some((_, value)) = flag.split_once('=') {
+        values.push(value);
+    }
+    if let Some(attached) = flag.get(2..).filter(|_| !flag.starts_with("--")) {
+        values.push(attached);
+    }
+    let path_start = ["/", "\\", "~", ".."].iter().filter_map(|marker| flag.find(marker)).min();
+    if let Some(start) = path_start {
+        values.push(&flag[start..]);
+    }
+    values.retain(|value| !value.is_empty());
+    values
+}
+
+/// `path` with symlinks resolved as far as it exists, the way the OS would
+/// resolve it. The missing tail is appended with `..` handled lexically, so
+/// a file that does not exist yet is judged by where it would land.
+fn canonical(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let components: Vec<Component> = absolute.components().collect();
+    let mut existing = components.len();
+    while existing > 0 && !components[..existing].iter().collect::<PathBuf>().exists() {
+        existing -= 1;
+    }
+    let prefix: PathBuf = components[..existing].iter().collect();
+    let mut out = prefix.canonicalize().unwrap_or(prefix);
+    for component in &components[existing..] {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn cap_output(text: String) -> String {
+    if text.chars().count() <= OUTPUT_MAX_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(OUTPUT_MAX_CHARS).collect();
+    format!("{}\n... (output truncated)", cut)
+}
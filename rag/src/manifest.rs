@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// What the indexer believes is stored in a collection. Written as files are
+/// indexed so interrupted runs still leave an accurate record.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IndexManifest {
+    pub collection: String,
+    #[serde(default)]
+    pub vector_size: usize,
+    #[serde(default)]
+    pub distance: String,
+    #[serde(default)]
+    pub files: BTreeMap<String, ManifestFile>,
+    /// Files whose chunks could not be embedded, by path. They keep whatever
+    /// an earlier run stored until `aicli index retry-failed` succeeds.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub quarantine: BTreeMap<String, QuarantinedFile>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub chunks: usize,
+    /// UUIDs for current indexes; integers in manifests from before point
+    /// ids were derived from chunk content.
+    #[serde(default)]
+    pub point_ids: Vec<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    /// Chunks that were waiting for embeddings.
+    pub chunks: usize,
+    pub error: String,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub failed_at: u64,
+    /// Set for files from `aicli index --git`, which only a new clone can
+    /// retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl IndexManifest {
+    pub fn load(cfg: &Config) -> Result<Self, String> {
+        let path = manifest_path(cfg);
+        if !path.exists() {
+            return Ok(Self {
+                collection: cfg.collection.clone(),
+                distance: cfg.distance.clone(),
+                ..Self::default()
+            });
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse manifest {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, cfg: &Config) -> Result<(), String> {
+        let path = manifest_path(cfg);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write manifest {}: {}", path.display(), e))
+    }
+}
+
+pub fn manifest_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir)
+        .join("manifests")
+        .join(format!("{}.json", cfg.collection))
+}
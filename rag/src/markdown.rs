@@ -0,0 +1,335 @@
+/// Terminal-friendly form of a markdown answer: pipe tables become
+/// box-drawn tables and simple math (`x^2`, `H_2O`, `$a_{ij} \le 10^{-3}$`)
+/// uses Unicode super/subscripts and symbols. Fenced code and inline code
+/// are left untouched.
+pub fn render_markdown(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut in_fence = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if in_fence {
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+        if is_table_row(line) && lines.get(i + 1).is_some_and(|l| parse_alignments(l).is_some()) {
+            let header = split_row(line);
+            let aligns = parse_alignments(lines[i + 1]).unwrap_or_default();
+            let mut rows = Vec::new();
+            i += 2;
+            while i < lines.len() && is_table_row(lines[i]) {
+                rows.push(split_row(lines[i]));
+                i += 1;
+            }
+            out.extend(draw_table(&header, &aligns, &rows));
+            continue;
+        }
+        out.push(render_inline(line));
+        i += 1;
+    }
+    let mut rendered = out.join("\n");
+    if text.ends_with('\n') {
+        rendered.push('\n');
+    }
+    rendered
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+fn is_table_row(line: &str) -> bool {
+    let t = line.trim();
+    t.starts_with('|') && t.len() > 1
+}
+
+fn split_row(line: &str) -> Vec<String> {
+    let t = line.trim();
+    let t = t.strip_prefix('|').unwrap_or(t);
+    let t = t.strip_suffix('|').unwrap_or(t);
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = t.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells.iter().map(|c| render_inline(c.trim())).collect()
+}
+
+// `|---|:--:|--:|`; `None` when the line is not a separator row.
+fn parse_alignments(line: &str) -> Option<Vec<Align>> {
+    if !is_table_row(line) {
+        return None;
+    }
+    let t = line.trim().trim_matches('|');
+    t.split('|')
+        .map(|cell| {
+            let c = cell.trim();
+            let dashes = c.trim_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|ch| ch == '-') {
+                return None;
+            }
+            Some(match (c.starts_with(':'), c.ends_with(':')) {
+                (true, true) => Align::Center,
+                (false, true) => Align::Right,
+                _ => Align::Left,
+            })
+        })
+        .collect()
+}
+
+fn draw_table(header: &[String], aligns: &[Align], rows: &[Vec<String>]) -> Vec<String> {
+    let columns = std::iter::once(header.len())
+        .chain(rows.iter().map(Vec::len))
+        .max()
+        .unwrap_or(0);
+    let mut widths = vec![1; columns];
+    for row in std::iter::once(header).chain(rows.iter().map(Vec::as_slice)) {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let rule = |left: &str, mid: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}", left, parts.join(mid), right)
+    };
+    let row_line = |row: &[String], header: bool| {
+        let cells: Vec<String> = (0..columns)
+            .map(|c| {
+                let text = row.get(c).map(String::as_str).unwrap_or("");
+                let align = if header { Align::Center } else { aligns.get(c).copied().unwrap_or_default() };
+                format!(" {} ", pad(text, widths[c], align))
+            })
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let mut out = vec![rule("┌", "┬", "┐"), row_line(header, true), rule("├", "┼", "┤")];
+    out.extend(rows.iter().map(|row| row_line(row, false)));
+    out.push(rule("└", "┴", "┘"));
+    out
+}
+
+fn pad(text: &str, width: usize, align: Align) -> String {
+    let gap = width.saturating_sub(text.chars().count());
+    let (left, right) = match align {
+        Align::Left => (0, gap),
+        Align::Right => (gap, 0),
+        Align::Center => (gap / 2, gap - gap / 2),
+    };
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+// Math outside inline code spans; `$...$` spans also get their symbols
+// replaced and their delimiters dropped.
+fn render_inline(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    for (n, part) in line.split('`').enumerate() {
+        if n > 0 {
+            out.push('`');
+        }
+        if n % 2 == 1 {
+            out.push_str(part);
+        } else {
+            out.push_str(&render_math_spans(part));
+        }
+    }
+    out
+}
+
+fn render_math_spans(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        let after = &rest[start + 1..];
+        let Some(len) = after.find('$') else { break };
+        let inner = &after[..len];
+        // `$HOME` and prices stay as written; math has an operator in it.
+        if inner.is_empty() || inner.starts_with(' ') || !inner.contains(['^', '_', '\\', '=']) {
+            out.push_str(&scripts(&rest[..start + 1]));
+            rest = after;
+            continue;
+        }
+        out.push_str(&scripts(&rest[..start]));
+        out.push_str(&scripts(&symbols(inner)));
+        rest = &after[len + 1..];
+    }
+    out.push_str(&scripts(rest));
+    out
+}
+
+const SYMBOLS: &[(&str, &str)] = &[
+    ("\\times", "×"),
+    ("\\cdot", "·"),
+    ("\\div", "÷"),
+    ("\\pm", "±"),
+    ("\\leq", "≤"),
+    ("\\le", "≤"),
+    ("\\geq", "≥"),
+    ("\\ge", "≥"),
+    ("\\neq", "≠"),
+    ("\\ne", "≠"),
+    ("\\approx", "≈"),
+    ("\\infty", "∞"),
+    ("\\sum", "∑"),
+    ("\\prod", "∏"),
+    ("\\sqrt", "√"),
+    ("\\rightarrow", "→"),
+    ("\\to", "→"),
+    ("\\alpha", "α"),
+    ("\\beta", "β"),
+    ("\\gamma", "γ"),
+    ("\\delta", "δ"),
+    ("\\epsilon", "ε"),
+    ("\\lambda", "λ"),
+    ("\\mu", "μ"),
+    ("\\pi", "π"),
+    ("\\sigma", "σ"),
+    ("\\theta", "θ"),
+];
+
+fn symbols(math: &str) -> String {
+    let mut out = String::with_capacity(math.len());
+    let mut rest = math;
+    'outer: while !rest.is_empty() {
+        if rest.starts_with('\\') {
+            for (name, symbol) in SYMBOLS {
+                if let Some(tail) = rest.strip_prefix(name) {
+                    if !tail.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                        out.push_str(symbol);
+                        rest = tail;
+                        continue 'outer;
+                    }
+                }
+            }
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// `^2`, `^-3`, `^{n+1}`, `H_2O`, `_{ij}` become Unicode scripts when every
+// character has one. A bare `_2` only counts after a one- or two-letter
+// base, so `version_2` and `snake_case` are left alone.
+fn scripts(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let prev_is_base = i > 0 && (chars[i - 1].is_alphanumeric() || matches!(chars[i - 1], ')' | ']' | '}'));
+        if (c == '^' || c == '_') && prev_is_base {
+            let to_script = if c == '^' { superscript } else { subscript };
+            let base = chars[..i].iter().rev().take_while(|c| c.is_alphanumeric()).count();
+            let braced = chars.get(i + 1) == Some(&'{');
+            if let Some((body, used)) = script_body(&chars[i + 1..], c == '^').filter(|_| c == '^' || braced || base <= 2) {
+                if let Some(converted) = body.chars().map(to_script).collect::<Option<String>>() {
+                    out.push_str(&converted);
+                    i += 1 + used;
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+// The script text after `^`/`_` and how many characters it spans.
+fn script_body(chars: &[char], sign_allowed: bool) -> Option<(String, usize)> {
+    if chars.first() == Some(&'{') {
+        let close = chars.iter().position(|&c| c == '}')?;
+        let body: String = chars[1..close].iter().collect();
+        return (!body.is_empty()).then_some((body, close + 1));
+    }
+    let sign = usize::from(sign_allowed && matches!(chars.first(), Some('-' | '+')));
+    let digits = chars[sign..].iter().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    // `x_1b` is an identifier, not a subscript; `H_2O` is a formula.
+    if chars.get(sign + digits).is_some_and(|c| c.is_lowercase() || *c == '_') {
+        return None;
+    }
+    Some((chars[..sign + digits].iter().collect(), sign + digits))
+}
+
+fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'i' => 'ⁱ',
+        'n' => 'ⁿ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'k' => 'ᵏ',
+        'm' => 'ᵐ',
+        'T' => 'ᵀ',
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'x' => 'ₓ',
+        _ => return None,
+    })
+}
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::http::{send_raw, HttpOptions};
+use crate::platform::shell_command;
+
+// Fixed namespace for token cache file names.
+const TOKEN_NAMESPACE: Uuid = Uuid::from_u128(0x8c1e_52a0_7d3f_4b19_a6c4_e05b_93d2_17f8);
+// A token command's output is reused for this long unless the server
+// rejects it first.
+const COMMAND_TOKEN_TTL: Duration = Duration::from_secs(300);
+// Cached OAuth tokens this close to expiry are refreshed before use.
+const EXPIRY_MARGIN_SECS: u64 = 30;
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// How aicli authenticates to a remote MCP server. Credentials are only
+/// sent to that server's endpoint.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum McpAuth {
+    #[default]
+    None,
+    /// `MCP_AUTH_TOKEN`: a static bearer token.
+    Token(String),
+    /// `MCP_AUTH_TOKEN_COMMAND`: a shell command that prints a bearer token,
+    /// run again when the server rejects the last one.
+    TokenCommand(String),
+    /// OAuth 2.0 device authorization grant, signed in once with
+    /// `aicli mcp login`; tokens are cached and refreshed.
+    Device(OAuthDevice),
+}
+
+/// `MCP_OAUTH_DEVICE_URL`, `MCP_OAUTH_TOKEN_URL`, `MCP_OAUTH_CLIENT_ID`, and
+/// `MCP_OAUTH_SCOPE`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OAuthDevice {
+    pub device_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    pub scope: String,
+}
+
+impl McpAuth {
+    /// The first method configured: a token, then a token command, then the
+    /// device flow (which needs its device URL, token URL, and client id).
+    pub fn from_parts(token: &str, token_command: &str, device: OAuthDevice) -> Self {
+        if !token.trim().is_empty() {
+            McpAuth::Token(token.trim().to_string())
+        } else if !token_command.trim().is_empty() {
+            McpAuth::TokenCommand(token_command.trim().to_string())
+        } else if [&device.device_url, &device.token_url, &device.client_id]
+            .iter()
+            .all(|v| !v.trim().is_empty())
+        {
+            McpAuth::Device(device)
+        } else {
+            McpAuth::None
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, McpAuth::None)
+    }
+
+    /// Whether a rejected token can be replaced by asking again.
+    pub fn can_refresh(&self) -> bool {
+        matches!(self, McpAuth::TokenCommand(_) | McpAuth::Device(_))
+    }
+
+    /// The bearer token to send. `refresh` skips cached tokens, after the
+    /// server rejected the last one.
+    pub fn bearer_token(
+        &self,
+        shell: &str,
+        data_dir: &str,
+        opts: &HttpOptions,
+        refresh: bool,
+    ) -> Result<String, String> {
+        match self {
+            McpAuth::None => Err("No MCP authentication is configured".to_string()),
+            McpAuth::Token(token) => Ok(token.clone()),
+            McpAuth::TokenCommand(command) => command_token(shell, command, refresh),
+            McpAuth::Device(device) => device_token(device, data_dir, opts, refresh),
+        }
+    }
+}
+
+fn command_token(shell: &str, command: &str, refresh: bool) -> Result<String, String> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+    let tokens = TOKENS.get_or_init(|| Mutex::new(HashMap::new()));
+    if !refresh {
+        if let Some((token, at)) = tokens.lock().ok().and_then(|t| t.get(command).cloned()) {
+            if at.elapsed() < COMMAND_TOKEN_TTL {
+                return Ok(token);
+            }
+        }
+    }
+    let output = shell_command(shell, command)
+        .output()
+        .map_err(|e| format!("Failed to run MCP_AUTH_TOKEN_COMMAND: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("MCP_AUTH_TOKEN_COMMAND exited with {}: {}", output.status, stderr));
+    }
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if token.is_empty() {
+        return Err("MCP_AUTH_TOKEN_COMMAND printed no token".to_string());
+    }
+    if let Ok(mut t) = tokens.lock() {
+        t.insert(command.to_string(), (token.clone(), Instant::now()));
+    }
+    Ok(token)
+}
+
+/// An OAuth token saved by `aicli mcp login`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CachedToken {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Unix seconds, stored as RFC3339; `None` when the server did not say.
+    #[serde(default, with = "crate::timestamps::rfc3339_secs_opt")]
+    pub expires_at: Option<u64>,
+}
+
+impl CachedToken {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now + EXPIRY_MARGIN_SECS)
+    }
+}
+
+/// `$AICLI_DATA_DIR/mcp_tokens/<hash of the token URL, client, and scope>.json`.
+pub fn token_cache_path(data_dir: &str, device: &OAuthDevice) -> PathBuf {
+    let identity = format!("{} {} {}", device.token_url.trim(), device.client_id.trim(), device.scope.trim());
+    let key = Uuid::new_v5(&TOKEN_NAMESPACE, identity.as_bytes()).simple().to_string();
+    PathBuf::from(data_dir).join("mcp_tokens").join(format!("{}.json", key))
+}
+
+pub fn load_cached_token(data_dir: &str, device: &OAuthDevice) -> Option<CachedToken> {
+    let text = fs::read_to_string(token_cache_path(data_dir, device)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Save `token`, readable only by the current user where the platform
+/// supports it.
+pub fn save_cached_token(data_dir: &str, device: &OAuthDevice, token: &CachedToken) -> Result<PathBuf, String> {
+    let path = token_cache_path(data_dir, device);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(token).map_err(|e| e.to_string())?;
+    let write_err = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Created private, so the token is never readable by others, even for
+    // a moment.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).map_err(write_err)?;
+    // A file left by an older version keeps its mode when reopened.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600)).map_err(write_err)?;
+    }
+    file.write_all(text.as_bytes()).map_err(write_err)?;
+    Ok(path)
+}
+
+/// Forget the cached token. `false` when there was none.
+pub fn remove_cached_token(data_dir: &str, device: &OAuthDevice) -> Result<bool, String> {
+    let path = token_cache_path(data_dir, device);
+    if !path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+fn device_token(device: &OAuthDevice, data_dir: &str, opts: &HttpOptions, refresh: bool) -> Result<String, String> {
+    let not_signed_in = || "Not signed in to the MCP server: run `aicli mcp login`".to_string();
+    let cached = load_cached_token(data_dir, device).ok_or_else(not_signed_in)?;
+    if !refresh && !cached.is_expired(unix_now()) {
+        return Ok(cached.access_token);
+    }
+    let refresh_token = cached.refresh_token.ok_or_else(|| {
+        "The MCP server's token expired and cannot be refreshed: run `aicli mcp login`".to_string()
+    })?;
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+        ("client_id", device.client_id.as_str()),
+    ];
+    if !device.scope.trim().is_empty() {
+        form.push(("scope", device.scope.trim()));
+    }
+    let token = match token_request(&device.token_url, &form, opts)? {
+        TokenReply::Token(mut token) => {
+            // Servers may keep the refresh token and only send a new access token.
+            token.refresh_token.get_or_insert(refresh_token);
+            token
+        }
+        TokenReply::Error(error) => {
+            return Err(format!("Refreshing the MCP token failed ({}): run `aicli mcp login`", error));
+        }
+    };
+    save_cached_token(data_dir, device, &token)?;
+    Ok(token.access_token)
+}
+
+/// What `aicli mcp login` shows the user while it waits.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// The verification URI with the code filled in, when offered.
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// Ask the authorization server for a device code to show the user.
+pub fn start_device_login(device: &OAuthDevice, opts: &HttpOptions) -> Result<DeviceCode, String> {
+    let mut form = vec![("client_id", device.client_id.as_str())];
+    if !device.scope.trim().is_empty() {
+        form.push(("scope", device.scope.trim()));
+    }
+    let reply = send_raw(
+        "POST",
+        &device.device_url,
+        Some(("application/x-www-form-urlencoded", form_urlencode(&form).as_bytes())),
+        &with_json_accept(opts),
+    )?;
+    if !reply.is_success() {
+        return Err(format!("Device authorization failed: {} {}", reply.status, reply.body));
+    }
+    let value: Value = serde_json::from_str(&reply.body)
+        .map_err(|e| format!("Device authorization returned invalid JSON: {} | {}", e, reply.body))?;
+    let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let number = |key: &str| value.get(key).and_then(Value::as_u64);
+    Ok(DeviceCode {
+        device_code: text("device_code").ok_or("Device authorization returned no device_code")?,
+        user_code: text("user_code").ok_or("Device authorization returned no user_code")?,
+        // Some providers still use the draft's `verification_url`.
+        verification_uri: text("verification_uri")
+            .or_else(|| text("verification_url"))
+            .ok_or("Device authorization returned no verification_uri")?,
+        verification_uri_complete: text("verification_uri_complete"),
+        expires_in: number("expires_in").unwrap_or(600),
+        interval: number("interval").unwrap_or(5),
+    })
+}
+
+/// Poll the token endpoint until the user approves `code`, then cache the
+/// token. Returns where it was saved.
+pub fn finish_device_login(
+    device: &OAuthDevice,
+    code: &DeviceCode,
+    data_dir: &str,
+    opts: &HttpOptions,
+) -> Result<PathBuf, String> {
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = Duration::from_secs(code.interval.max(1));
+    let form = [
+        ("grant_type", DEVICE_GRANT),
+        ("device_code", code.device_code.as_str()),
+        ("client_id", device.client_id.as_str()),
+    ];
+    loop {
+        if Instant::now() >= deadline {
+            return Err("The device code expired before it was approved".to_string());
+        }
+        std::thread::sleep(interval);
+        match token_request(&device.token_url, &form, opts)? {
+            TokenReply::Token(token) => return save_cached_token(data_dir, device, &token),
+            TokenReply::Error(error) if error == "authorization_pending" => {}
+            TokenReply::Error(error) if error == "slow_down" => interval += Duration::from_secs(5),
+            TokenReply::Error(error) if error == "access_denied" => return Err("Sign-in was denied".to_string()),
+            TokenReply::Error(error) => return Err(format!("Sign-in failed: {}", error)),
+        }
+    }
+}
+
+enum TokenReply {
+    Token(CachedToken),
+    /// The OAuth `error` code, such as `authorization_pending`.
+    Error(String),
+}
+
+fn token_request(url: &str, form: &[(&str, &str)], opts: &HttpOptions) -> Result<TokenReply, String> {
+    let reply = send_raw(
+        "POST",
+        url,
+        Some(("application/x-www-form-urlencoded", form_urlencode(form).as_bytes())),
+        &with_json_accept(opts),
+    )?;
+    let value: Value = serde_json::from_str(&reply.body)
+        .map_err(|_| format!("Token request failed: {} {}", reply.status, reply.body))?;
+    if let Some(error) = value.get("error").and_then(Value::as_str) {
+        return Ok(TokenReply::Error(error.to_string()));
+    }
+    let access_token = value
+        .get("access_token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("Token request failed: {} {}", reply.status, reply.body))?;
+    Ok(TokenReply::Token(CachedToken {
+        access_token: access_token.to_string(),
+        refresh_token: value.get("refresh_token").and_then(Value::as_str).map(str::to_string),
+        expires_at: value.get("expires_in").and_then(Value::as_u64).map(|secs| unix_now() + secs),
+    }))
+}
+
+// GitHub and others answer form-encoded unless JSON is asked for.
+fn with_json_accept(opts: &HttpOptions) -> HttpOptions {
+    let mut opts = opts.clone();
+    opts.headers.push(("Accept".to_string(), "application/json".to_string()));
+    opts
+}
+
+/// `application/x-www-form-urlencoded` body for `pairs`.
+pub fn form_urlencode(pairs: &[(&str, &str)]) -> String {
+    let encode = |text: &str| {
+        let mut out = String::new();
+        for byte in text.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+                b' ' => out.push('+'),
+                _ => out.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        out
+    };
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::mcp::{McpCapabilities, McpClient, McpTool};
+
+// Fixed namespace for cache keys and schema hashes.
+const CACHE_NAMESPACE: Uuid = Uuid::from_u128(0x2f7b_91c4_05de_4a63_b8e2_7c1d_40a9_e356);
+
+/// Capabilities of one MCP server as last discovered, stored under
+/// `$AICLI_CACHE_DIR/mcp_cache/`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CachedCapabilities {
+    /// Hash of the server identity the capabilities were discovered from;
+    /// the identity itself is not stored, since it names commands and
+    /// arguments that may carry secrets.
+    #[serde(default)]
+    pub key: String,
+    #[serde(with = "crate::timestamps::rfc3339_secs")]
+    pub saved_at: u64,
+    pub tools: Vec<CachedTool>,
+    pub prompts: Vec<String>,
+    pub resources: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CachedTool {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+    /// Hash of `input_schema`, so a refresh can tell whether anything changed.
+    pub schema_hash: String,
+}
+
+impl CachedCapabilities {
+    pub fn from_capabilities(server: &str, caps: &McpCapabilities) -> Self {
+        Self {
+            key: cache_key(server),
+            saved_at: unix_secs(),
+            tools: caps
+                .tool_specs
+                .iter()
+                .map(|t| CachedTool {
+                    name: t.name.clone(),
+                    description: t.description.clone(),
+                    input_schema: t.input_schema.clone(),
+                    schema_hash: schema_hash(&t.input_schema),
+                })
+                .collect(),
+            prompts: caps.prompts.clone(),
+            resources: caps.resources.clone(),
+        }
+    }
+
+    pub fn to_capabilities(&self) -> McpCapabilities {
+        let tool_specs: Vec<McpTool> = self
+            .tools
+            .iter()
+            .map(|t| McpTool {
+                name: t.name.clone(),
+                description: t.description.clone(),
+                input_schema: t.input_schema.clone(),
+            })
+            .collect();
+        McpCapabilities {
+            tools: tool_specs.iter().map(|t| t.name.clone()).collect(),
+            tool_specs,
+            prompts: self.prompts.clone(),
+            resources: self.resources.clone(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Same tools (by name, description, and schema hash), prompts, and
+    /// resources.
+    pub fn same_as(&self, other: &CachedCapabilities) -> bool {
+        let tools = |c: &CachedCapabilities| -> Vec<(String, String, String)> {
+            c.tools
+                .iter()
+                .map(|t| (t.name.clone(), t.description.clone(), t.schema_hash.clone()))
+                .collect()
+        };
+        tools(self) == tools(other) && self.prompts == other.prompts && self.resources == other.resources
+    }
+}
+
+pub fn schema_hash(schema: &Value) -> String {
+    Uuid::new_v5(&CACHE_NAMESPACE, schema.to_string().as_bytes()).simple().to_string()
+}
+
+fn cache_key(server: &str) -> String {
+    Uuid::new_v5(&CACHE_NAMESPACE, server.as_bytes()).simple().to_string()
+}
+
+fn cache_file(cfg: &Config, key: &str) -> PathBuf {
+    PathBuf::from(&cfg.cache_dir).join("mcp_cache").join(format!("{}.json", key))
+}
+
+/// `$AICLI_CACHE_DIR/mcp_cache/<hash of the server identity>.json`.
+pub fn mcp_cache_path(cfg: &Config, server: &str) -> PathBuf {
+    cache_file(cfg, &cache_key(server))
+}
+
+/// The cached capabilities of `server`. Files written before entries were
+/// keyed by hash have no key and are ignored, then replaced on discovery.
+pub fn load_cached_capabilities(cfg: &Config, server: &str) -> Option<CachedCapabilities> {
+    let key = cache_key(server);
+    let text = fs::read_to_string(cache_file(cfg, &key)).ok()?;
+    let cached: CachedCapabilities = serde_json::from_str(&text).ok()?;
+    (cached.key == key).then_some(cached)
+}
+
+pub fn save_cached_capabilities(cfg: &Config, cached: &CachedCapabilities) -> Result<(), String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: MCP capabilities are not cached.".to_string());
+    }
+    let path = cache_file(cfg, &cached.key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(cached).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Capabilities for `mcp`, served from the cache when `MCP_CACHE` is on and
+/// one exists. A cached answer is refreshed once per process on a
+/// background thread, so the next query sees any change; without a cache,
+/// discovery runs now and its result is saved. Discovery that reports
+/// errors is never cached. Safe mode reads the cache but neither refreshes
+/// nor writes it. The cache keeps every tool the server offers; the tool
+/// allowlist and denylist are applied on the way out.
+pub fn discover_capabilities_cached(cfg: &Config, mcp: &McpClient) -> McpCapabilities {
+    let Some(server) = mcp.server_identity().filter(|_| cfg.mcp_cache) else {
+        return mcp.discover_capabilities();
+    };
+    let mut caps = match load_cached_capabilities(cfg, &server) {
+        Some(cached) => {
+            if !cfg.safe_mode && start_refresh(&server) {
+                let cfg = cfg.clone();
+                let mcp = mcp.clone();
+                let previous = cached.clone();
+                std::thread::spawn(move || refresh_cached_capabilities(&cfg, &mcp, &server, &previous));
+            }
+            cached.to_capabilities()
+        }
+        None => {
+            let caps = mcp.discover_all_capabilities();
+            if caps.diagnostics.is_empty() && !cfg.safe_mode {
+                let _ = save_cached_capabilities(cfg, &CachedCapabilities::from_capabilities(&server, &caps));
+            }
+            caps
+        }
+    };
+    mcp.tool_filter().apply(&mut caps);
+    caps
+}
+
+/// Discover now and update the cache when the result differs from
+/// `previous`. Returns whether the cache was written.
+pub fn refresh_cached_capabilities(cfg: &Config, mcp: &McpClient, server: &str, previous: &CachedCapabilities) -> bool {
+    let caps = mcp.discover_all_capabilities();
+    if !caps.diagnostics.is_empty() {
+        return false;
+    }
+    let fresh = CachedCapabilities::from_capabilities(server, &caps);
+    if fresh.same_as(previous) {
+        return false;
+    }
+    save_cached_capabilities(cfg, &fresh).is_ok()
+}
+
+// One background refresh per server per process.
+fn start_refresh(server: &str) -> bool {
+    static REFRESHED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    REFRESHED
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .map(|mut servers| servers.insert(server.to_string()))
+        .unwrap_or(false)
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
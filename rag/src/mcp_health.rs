@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::mcp_stdio::{backoff, SupervisorOptions};
+
+/// Whether an MCP server can be reached right now. The agent is told when
+/// a server is in trouble so it stops choosing that server's tools.
+#[derive(Clone, Debug, PartialEq)]
+pub enum McpConnection {
+    /// Not contacted yet in this process.
+    Unknown,
+    Up,
+    /// The last attempt failed; the next call connects again.
+    Reconnecting(String),
+    /// Out of retries. Calls fail at once until the reason clears.
+    Down(String),
+}
+
+impl McpConnection {
+    /// A line for the agent prompt about `server` (empty for the only
+    /// server), or `None` while nothing is wrong.
+    pub fn prompt_note(&self, server: &str) -> Option<String> {
+        let which = match server {
+            "" => "The MCP server".to_string(),
+            name => format!("MCP server '{}'", name),
+        };
+        match self {
+            McpConnection::Unknown | McpConnection::Up => None,
+            McpConnection::Reconnecting(reason) => Some(format!(
+                "{} lost its connection ({}). aicli reconnects on the next call; if that fails too, stop using it.",
+                which, reason
+            )),
+            McpConnection::Down(reason) => Some(format!(
+                "{} is down: {}. Do not choose tool, prompt, or resource actions for it; \
+                 use retrieve, other tools, or final.",
+                which, reason
+            )),
+        }
+    }
+}
+
+// Consecutive failed connection attempts to one HTTP server.
+struct Health {
+    failures: u32,
+    last_error: String,
+    last_failure: Instant,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Health>> {
+    static HEALTH: OnceLock<Mutex<HashMap<String, Health>>> = OnceLock::new();
+    HEALTH.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The state of the HTTP server known as `key`. After more than
+/// `max_restarts` failed attempts in a row it is down until the backoff
+/// for the next attempt has passed, when one call may try again.
+pub fn http_connection(key: &str, options: SupervisorOptions) -> McpConnection {
+    let all = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let Some(health) = all.get(key) else {
+        return McpConnection::Unknown;
+    };
+    if health.failures == 0 {
+        return McpConnection::Up;
+    }
+    let wait = backoff(health.failures - 1);
+    let elapsed = health.last_failure.elapsed();
+    if health.failures > options.max_restarts && elapsed < wait {
+        return McpConnection::Down(format!(
+            "{} failed connection attempts, last: {}; next try in {}s",
+            health.failures,
+            health.last_error,
+            (wait - elapsed).as_secs() + 1
+        ));
+    }
+    McpConnection::Reconnecting(health.last_error.clone())
+}
+
+fn record(key: &str, error: Option<&str>) {
+    let mut all = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let health = all.entry(key.to_string()).or_insert(Health {
+        failures: 0,
+        last_error: String::new(),
+        last_failure: Instant::now(),
+    });
+    match error {
+        Some(error) => {
+            health.failures += 1;
+            health.last_error = error.to_string();
+            health.last_failure = Instant::now();
+        }
+        None => health.failures = 0,
+    }
+}
+
+/// Connect to the HTTP server known as `key` with `connect`, trying again
+/// after a transport failure up to `max_restarts` times with the stdio
+/// restart backoff. Nothing has been sent when `connect` fails, so even a
+/// tool call is safe to retry. Fails at once while the server is down.
+pub(crate) fn connect_with_retries<T>(
+    key: &str,
+    options: SupervisorOptions,
+    mut connect: impl FnMut() -> Result<T, String>,
+) -> Result<T, String> {
+    if let McpConnection::Down(reason) = http_connection(key, options) {
+        return Err(format!("MCP server is down ({})", reason));
+    }
+    let mut attempt = 0;
+    loop {
+        match connect() {
+            Ok(value) => {
+                record(key, None);
+                return Ok(value);
+            }
+            Err(err) => {
+                record(key, Some(&err));
+                if attempt >= options.max_restarts {
+                    return Err(err);
+                }
+                std::thread::sleep(backoff(attempt));
+                attempt += 1;
+            }
+        }
+    }
+}
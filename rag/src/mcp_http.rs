@@ -0,0 +1,249 @@
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::http::{send_raw, HttpOptions, RawResponse};
+use crate::mcp_auth::McpAuth;
+use crate::mcp_health::connect_with_retries;
+use crate::mcp_stdio::SupervisorOptions;
+
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// A remote MCP server that needs credentials, spoken to over Streamable
+/// HTTP: every JSON-RPC message is POSTed to the endpoint with the bearer
+/// token, and replies come back as JSON or as a short event stream. Like
+/// other HTTP servers it is connected to per call.
+#[derive(Clone, Debug)]
+pub struct McpHttpClient {
+    endpoint: String,
+    auth: McpAuth,
+    shell: String,
+    data_dir: String,
+    http: HttpOptions,
+    retry: SupervisorOptions,
+}
+
+enum CallError {
+    /// The server answered 401; a fresh token may help.
+    Unauthorized(String),
+    Other(String),
+}
+
+impl From<String> for CallError {
+    fn from(err: String) -> Self {
+        CallError::Other(err)
+    }
+}
+
+impl McpHttpClient {
+    pub fn new(cfg: &Config, endpoint: &str, auth: McpAuth) -> Self {
+        Self {
+            endpoint: endpoint.trim().to_string(),
+            auth,
+            shell: cfg.shell.clone(),
+            data_dir: cfg.data_dir.clone(),
+            // A retried tools/call could run the tool twice.
+            http: HttpOptions {
+                retries: 0,
+                ..HttpOptions::standard(cfg)
+            },
+            retry: SupervisorOptions::from_config(cfg),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.with_session(|session| session.request(method, params.clone()))
+    }
+
+    /// Every item under `key` across the pages of a `*/list` method.
+    pub fn list(&self, method: &str, key: &str) -> Result<Vec<Value>, String> {
+        self.with_session(|session| {
+            let mut items = Vec::new();
+            let mut cursor: Option<Value> = None;
+            loop {
+                let params = match &cursor {
+                    Some(cursor) => json!({ "cursor": cursor }),
+                    None => json!({}),
+                };
+                let page = session.request(method, params)?;
+                items.extend(page.get(key).and_then(Value::as_array).cloned().unwrap_or_default());
+                cursor = page.get("nextCursor").filter(|c| !c.is_null()).cloned();
+                if cursor.is_none() {
+                    return Ok(items);
+                }
+            }
+        })
+    }
+
+    // Open a session, run `f`, and close it. A rejected token is replaced
+    // once when the auth method can produce a new one. Opening the session
+    // is retried while the server cannot be reached.
+    fn with_session<T>(&self, mut f: impl FnMut(&mut HttpSession) -> Result<T, CallError>) -> Result<T, String> {
+        let mut refresh = false;
+        let key = format!("http {}", self.endpoint);
+        loop {
+            let token = self.auth.bearer_token(&self.shell, &self.data_dir, &self.http, refresh)?;
+            let mut session = HttpSession {
+                client: self,
+                token,
+                session_id: None,
+                protocol_version: None,
+                next_id: 1,
+            };
+            // A 401 means the server is there; only other failures are retried.
+            let opened = connect_with_retries(&key, self.retry, || match session.initialize() {
+                Err(CallError::Other(err)) => Err(err),
+                other => Ok(other),
+            });
+            let result = match opened {
+                Ok(Ok(())) => f(&mut session),
+                Ok(Err(err)) => Err(err),
+                Err(err) => Err(CallError::Other(err)),
+            };
+            session.close();
+            match result {
+                Ok(value) => return Ok(value),
+                Err(CallError::Unauthorized(_)) if !refresh && self.auth.can_refresh() => refresh = true,
+                Err(CallError::Unauthorized(err)) | Err(CallError::Other(err)) => return Err(err),
+            }
+        }
+    }
+}
+
+struct HttpSession<'a> {
+    client: &'a McpHttpClient,
+    token: String,
+    session_id: Option<String>,
+    protocol_version: Option<String>,
+    next_id: u64,
+}
+
+impl HttpSession<'_> {
+    fn initialize(&mut self) -> Result<(), CallError> {
+        let result = self.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "aicli", "version": env!("CARGO_PKG_VERSION") }
+            }),
+        )?;
+        self.protocol_version = result
+            .get("protocolVersion")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        self.post(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))?;
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, CallError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let reply = self.post(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        if method == "initialize" {
+            self.session_id = reply.header("mcp-session-id").map(str::to_string);
+        }
+        let message = reply_message(&reply, id)
+            .ok_or_else(|| format!("MCP server sent no reply to {}: {}", method, reply.body))?;
+        if let Some(error) = message.get("error") {
+            let text = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+            return Err(CallError::Other(text.to_string()));
+        }
+        Ok(message.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    fn post(&self, message: &Value) -> Result<RawResponse, CallError> {
+        let body = message.to_string();
+        let reply = send_raw(
+            "POST",
+            &self.client.endpoint,
+            Some(("application/json", body.as_bytes())),
+            &self.options(),
+        )?;
+        match reply.status {
+            401 => {
+                let challenge = reply.header("www-authenticate").unwrap_or_default();
+                Err(CallError::Unauthorized(format!(
+                    "MCP server {} rejected the credentials (401 {})",
+                    self.client.endpoint,
+                    challenge.trim()
+                )))
+            }
+            403 => Err(CallError::Other(format!(
+                "MCP server {} refused access (403): {}",
+                self.client.endpoint,
+                reply.body.trim()
+            ))),
+            status if !reply.is_success() => Err(CallError::Other(format!(
+                "MCP server {} answered {}: {}",
+                self.client.endpoint,
+                status,
+                reply.body.trim()
+            ))),
+            _ => Ok(reply),
+        }
+    }
+
+    fn options(&self) -> HttpOptions {
+        let mut opts = self.client.http.clone();
+        opts.headers.push(("Authorization".to_string(), format!("Bearer {}", self.token)));
+        opts.headers.push(("Accept".to_string(), "application/json, text/event-stream".to_string()));
+        if let Some(id) = &self.session_id {
+            opts.headers.push(("Mcp-Session-Id".to_string(), id.clone()));
+        }
+        if let Some(version) = &self.protocol_version {
+            opts.headers.push(("MCP-Protocol-Version".to_string(), version.clone()));
+        }
+        opts
+    }
+
+    // Sessions left open expire on the server, so a failed close is ignored.
+    fn close(&self) {
+        if self.session_id.is_some() {
+            let _ = send_raw("DELETE", &self.client.endpoint, None, &self.options());
+        }
+    }
+}
+
+/// The JSON-RPC message answering request `id` in a Streamable HTTP reply:
+/// a JSON object or batch, or the `data:` of the events in an event stream.
+pub fn reply_message(reply: &RawResponse, id: u64) -> Option<Value> {
+    let is_stream = reply
+        .header("content-type")
+        .is_some_and(|t| t.to_ascii_lowercase().starts_with("text/event-stream"));
+    let messages: Vec<Value> = if is_stream {
+        sse_data(&reply.body)
+            .iter()
+            .filter_map(|data| serde_json::from_str(data).ok())
+            .collect()
+    } else {
+        match serde_json::from_str(&reply.body).ok()? {
+            Value::Array(batch) => batch,
+            single => vec![single],
+        }
+    };
+    messages.into_iter().find(|m| m.get("id").and_then(Value::as_u64) == Some(id))
+}
+
+// The data of each server-sent event, with multi-line data joined.
+fn sse_data(body: &str) -> Vec<String> {
+    let mut events = Vec::new();
+    let mut data: Vec<&str> = Vec::new();
+    for line in body.lines() {
+        if line.is_empty() {
+            if !data.is_empty() {
+                events.push(data.join("\n"));
+                data.clear();
+            }
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    if !data.is_empty() {
+        events.push(data.join("\n"));
+    }
+    events
+}
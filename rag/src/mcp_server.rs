@@ -0,0 +1,241 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+
+use crate::config::Config;
+use crate::manifest::IndexManifest;
+use crate::retrieve::{format_hit_list, retrieve, RetrieveOptions};
+use crate::store_qdrant::{scroll_points_for_path, StoredPoint};
+use crate::web::is_url;
+
+// MCP protocol versions this server speaks, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+// Resources per `resources/list` page.
+const RESOURCE_PAGE: usize = 100;
+// Hits `search_corpus` returns when the caller gives no `top_k`.
+const DEFAULT_SEARCH_TOP_K: usize = 8;
+
+/// Serve the index as an MCP server: newline-delimited JSON-RPC read from
+/// `input`, replies written to `output`, until `input` ends. Exposes the
+/// `search_corpus` and `answer_question` tools and every indexed file as a
+/// resource. Nothing else may write to `output`.
+pub fn serve_mcp(cfg: &Config, input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+    for line in input.lines() {
+        let line = line.map_err(|e| format!("Failed to read request: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle_message(cfg, &message),
+            Err(e) => Some(error_reply(Value::Null, -32700, &format!("Parse error: {}", e))),
+        };
+        if let Some(reply) = reply {
+            writeln!(output, "{}", reply).map_err(|e| format!("Failed to write reply: {}", e))?;
+            output.flush().map_err(|e| format!("Failed to write reply: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// The reply to one JSON-RPC message, or `None` for a notification.
+pub fn handle_message(cfg: &Config, message: &Value) -> Option<Value> {
+    let method = message.get("method").and_then(Value::as_str).unwrap_or_default();
+    let id = message.get("id").cloned()?;
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tool_list() })),
+        "tools/call" => Ok(call_tool(cfg, &params)),
+        "resources/list" => list_resources(cfg, &params),
+        "resources/read" => read_resource(cfg, &params),
+        _ => return Some(error_reply(id, -32601, &format!("Method not found: {}", method))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_reply(id, -32602, &e),
+    })
+}
+
+fn error_reply(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+// The client's protocol version when this server speaks it, else the
+// newest one it does; the client disconnects if it cannot use that.
+fn initialize(params: &Value) -> Value {
+    let version = params
+        .get("protocolVersion")
+        .and_then(Value::as_str)
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {}, "resources": {} },
+        "serverInfo": { "name": "aicli", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn tool_list() -> Value {
+    json!([
+        {
+            "name": "search_corpus",
+            "description": "Search the local index and list the best-matching chunks with their file paths and scores.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "What to search for." },
+                    "top_k": { "type": "integer", "description": "How many hits to return." }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "answer_question",
+            "description": "Answer a question from the local index, citing the files the answer came from.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string", "description": "The question to answer." }
+                },
+                "required": ["question"]
+            }
+        }
+    ])
+}
+
+// Tool failures are results with `isError`, so the calling model sees them.
+fn call_tool(cfg: &Config, params: &Value) -> Value {
+    let name = params.get("name").and_then(Value::as_str).unwrap_or_default();
+    let args = params.get("arguments").cloned().unwrap_or(Value::Null);
+    let text = |key: &str| args.get(key).and_then(Value::as_str).map(str::trim).unwrap_or_default();
+    let outcome = match name {
+        "search_corpus" if text("query").is_empty() => Err("\"query\" is required".to_string()),
+        "search_corpus" => {
+            let options = RetrieveOptions {
+                top_k: Some(
+                    args.get("top_k")
+                        .and_then(Value::as_u64)
+                        .map(|k| k as usize)
+                        .unwrap_or(DEFAULT_SEARCH_TOP_K),
+                ),
+                ..Default::default()
+            };
+            retrieve(cfg, text("query"), &options).map(|hits| search_text(&hits))
+        }
+        "answer_question" if text("question").is_empty() => Err("\"question\" is required".to_string()),
+        "answer_question" => crate::answer_query_detailed(cfg, &[], text("question")).map(|result| {
+            let sources = result.sources_text();
+            if sources.is_empty() {
+                result.answer
+            } else {
+                format!("{}\n\nSources:\n{}", result.answer, sources)
+            }
+        }),
+        _ => Err(format!("Unknown tool: {}", name)),
+    };
+    match outcome {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+        Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+    }
+}
+
+// The hit list followed by each hit's chunk text.
+fn search_text(hits: &[crate::retrieve_chunks::Hit]) -> String {
+    let mut out = format_hit_list(hits);
+    for (i, hit) in hits.iter().enumerate() {
+        let chunk = hit.payload.as_ref().and_then(|p| p.chunk.as_deref()).unwrap_or_default();
+        out.push_str(&format!("\n\n[{}]\n{}", i + 1, chunk.trim()));
+    }
+    out
+}
+
+/// The resource URI for an indexed path: the URL itself for web pages and
+/// git blobs, `file://` and the absolute path for local files.
+pub fn resource_uri(path: &str) -> String {
+    if is_url(path) {
+        return path.to_string();
+    }
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.into());
+    format!("file://{}", absolute.display())
+}
+
+fn list_resources(cfg: &Config, params: &Value) -> Result<Value, String> {
+    let manifest = IndexManifest::load(cfg)?;
+    let start = match params.get("cursor").and_then(Value::as_str) {
+        Some(cursor) => cursor.parse::<usize>().map_err(|_| format!("Invalid cursor: {}", cursor))?,
+        None => 0,
+    };
+    let resources: Vec<Value> = manifest
+        .files
+        .iter()
+        .skip(start)
+        .take(RESOURCE_PAGE)
+        .map(|(path, file)| {
+            json!({
+                "uri": resource_uri(path),
+                "name": path,
+                "description": format!("{} chunks in {}", file.chunks, cfg.collection),
+                "mimeType": "text/plain",
+            })
+        })
+        .collect();
+    let mut result = json!({ "resources": resources });
+    if start + RESOURCE_PAGE < manifest.files.len() {
+        result["nextCursor"] = json!((start + RESOURCE_PAGE).to_string());
+    }
+    Ok(result)
+}
+
+// Local files are read from disk as they are now; other sources are put
+// back together from their indexed chunks.
+fn read_resource(cfg: &Config, params: &Value) -> Result<Value, String> {
+    let uri = params.get("uri").and_then(Value::as_str).ok_or("\"uri\" is required")?;
+    let manifest = IndexManifest::load(cfg)?;
+    let path = manifest
+        .files
+        .keys()
+        .find(|path| resource_uri(path) == uri)
+        .ok_or_else(|| format!("Not an indexed resource: {}", uri))?;
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) if !is_url(path) => text,
+        _ => join_chunks(&scroll_points_for_path(cfg, path)?),
+    };
+    Ok(json!({ "contents": [{ "uri": uri, "mimeType": "text/plain", "text": text }] }))
+}
+
+// Chunks in index order, without the text each one repeats from the chunk
+// before it (at most its `chunk_overlap` characters, and only within one
+// notebook cell). Chunks that share no text go on separate lines.
+fn join_chunks(points: &[StoredPoint]) -> String {
+    let mut text = String::new();
+    let mut previous_cell = None;
+    for payload in points.iter().filter_map(|p| p.payload.as_ref()) {
+        let Some(chunk) = payload.get("chunk").and_then(Value::as_str) else {
+            continue;
+        };
+        let cell = payload.get("cell_index").and_then(Value::as_u64);
+        let overlap = match payload.get("chunk_overlap").and_then(Value::as_u64) {
+            Some(overlap) if cell == previous_cell => overlap as usize,
+            _ => 0,
+        };
+        previous_cell = cell;
+        let shared = shared_len(&text, chunk, overlap);
+        if shared == 0 && !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&chunk[shared..]);
+    }
+    text
+}
+
+// Bytes of the longest end of `text`, at most `max_chars` characters, that
+// `chunk` starts with.
+fn shared_len(text: &str, chunk: &str, max_chars: usize) -> usize {
+    text.char_indices()
+        .rev()
+        .take(max_chars)
+        .map(|(i, _)| &text[i..])
+        .filter(|end| chunk.starts_with(end))
+        .last()
+        .map_or(0, str::len)
+}
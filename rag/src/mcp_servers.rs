@@ -0,0 +1,104 @@
+use serde_json::Value;
+
+use crate::mcp_auth::McpAuth;
+
+/// One server from an `mcpServers` config file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub spec: McpServerSpec,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum McpServerSpec {
+    /// Started by aicli with `env` added to its environment.
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    },
+    /// A remote server; an `Authorization: Bearer` header becomes its token.
+    Http { url: String, auth: McpAuth },
+}
+
+/// Parse an `mcp.json` in the shape Claude Desktop and Cursor use:
+/// `{"mcpServers": {"name": {"command": ..., "args": [...], "env": {...}}}}`,
+/// or `{"url": ..., "headers": {"Authorization": "Bearer ..."}}` for a
+/// remote server. Servers with `"disabled": true` are skipped. Servers come
+/// back in name order.
+pub fn parse_mcp_servers(text: &str) -> Result<Vec<McpServerConfig>, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+    let servers = root
+        .get("mcpServers")
+        .and_then(Value::as_object)
+        .ok_or("expected an \"mcpServers\" object")?;
+    let mut out = Vec::new();
+    for (name, entry) in servers {
+        if name.trim().is_empty() || name.contains('/') {
+            return Err(format!("server name '{}' must be non-empty and must not contain '/'", name));
+        }
+        if entry.get("disabled").and_then(Value::as_bool).unwrap_or(false) {
+            continue;
+        }
+        let spec = server_spec(entry).map_err(|e| format!("server '{}': {}", name, e))?;
+        out.push(McpServerConfig {
+            name: name.clone(),
+            spec,
+        });
+    }
+    Ok(out)
+}
+
+/// Read and parse the file named by `MCP_CONFIG`.
+pub fn load_mcp_servers(path: &str) -> Result<Vec<McpServerConfig>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read MCP config {}: {}", path, e))?;
+    parse_mcp_servers(&text).map_err(|e| format!("Invalid MCP config {}: {}", path, e))
+}
+
+fn server_spec(entry: &Value) -> Result<McpServerSpec, String> {
+    let text = |key: &str| entry.get(key).and_then(Value::as_str).map(str::trim).unwrap_or_default();
+    if !text("url").is_empty() {
+        let mut auth = McpAuth::None;
+        for (header, value) in entry.get("headers").and_then(Value::as_object).into_iter().flatten() {
+            let value = value.as_str().unwrap_or_default().trim();
+            match value.strip_prefix("Bearer ") {
+                Some(token) if header.eq_ignore_ascii_case("authorization") => {
+                    auth = McpAuth::Token(token.trim().to_string());
+                }
+                _ => return Err(format!("unsupported header '{}'; only \"Authorization: Bearer\" is sent", header)),
+            }
+        }
+        return Ok(McpServerSpec::Http {
+            url: text("url").to_string(),
+            auth,
+        });
+    }
+    if text("command").is_empty() {
+        return Err("needs a \"command\" or a \"url\"".to_string());
+    }
+    let args = match entry.get("args") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|a| a.as_str().map(str::to_string).ok_or("\"args\" must be strings"))
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("\"args\" must be an array".to_string()),
+    };
+    let env = match entry.get("env") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Object(vars)) => vars
+            .iter()
+            .map(|(k, v)| match v {
+                Value::String(s) => Ok((k.clone(), s.clone())),
+                Value::Number(_) | Value::Bool(_) => Ok((k.clone(), v.to_string())),
+                _ => Err(format!("env '{}' must be a string", k)),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("\"env\" must be an object".to_string()),
+    };
+    Ok(McpServerSpec::Stdio {
+        command: text("command").to_string(),
+        args,
+        env,
+    })
+}
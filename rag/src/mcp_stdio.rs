@@ -0,0 +1,499 @@
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+// Lines of the server's stderr kept for diagnostics, and how many of the
+// last ones go into error messages.
+const STDERR_LINES: usize = 20;
+const STDERR_IN_ERRORS: usize = 5;
+const INIT_TIMEOUT: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+// How long a failed request waits to see whether the server is exiting.
+const EXIT_GRACE: Duration = Duration::from_millis(500);
+const FIRST_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// `MCP_MAX_RESTARTS` and `MCP_HEARTBEAT_SECS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SupervisorOptions {
+    pub max_restarts: u32,
+    /// How often a running server is pinged; zero turns the heartbeat off.
+    pub heartbeat: Duration,
+}
+
+impl SupervisorOptions {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            max_restarts: cfg.mcp_max_restarts,
+            heartbeat: Duration::from_secs(cfg.mcp_heartbeat_secs),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum McpServerState {
+    /// Not needed yet; servers start on first use.
+    NotStarted,
+    Running,
+    /// Exited or stopped answering; restarted on the next call.
+    Exited,
+    /// Out of restarts. Calls fail until aicli is restarted.
+    Failed,
+}
+
+/// What the supervisor knows about a stdio MCP server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpServerStatus {
+    pub state: McpServerState,
+    pub restarts: u32,
+    pub max_restarts: u32,
+    /// Exit status or reason of the last time the server went away.
+    pub last_exit: Option<String>,
+    pub stderr_tail: Vec<String>,
+}
+
+impl McpServerStatus {
+    fn new(max_restarts: u32) -> Self {
+        Self {
+            state: McpServerState::NotStarted,
+            restarts: 0,
+            max_restarts,
+            last_exit: None,
+            stderr_tail: Vec::new(),
+        }
+    }
+
+    /// A few words for the status bar, or `None` while nothing went wrong.
+    pub fn summary(&self) -> Option<String> {
+        match self.state {
+            McpServerState::Failed => Some("MCP down".to_string()),
+            McpServerState::Exited => Some("MCP exited".to_string()),
+            _ if self.restarts > 0 => Some(format!("MCP restarts: {}", self.restarts)),
+            _ => None,
+        }
+    }
+
+    /// Lines for `McpCapabilities.diagnostics`, empty while nothing went wrong.
+    pub fn diagnostics(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.restarts == 0 && self.last_exit.is_none() && self.state != McpServerState::Failed {
+            return lines;
+        }
+        if self.restarts > 0 || self.state == McpServerState::Failed {
+            lines.push(format!(
+                "stdio server restarted {} of {} times{}",
+                self.restarts,
+                self.max_restarts,
+                if self.state == McpServerState::Failed { "; gave up" } else { "" }
+            ));
+        }
+        if let Some(exit) = &self.last_exit {
+            lines.push(format!("last exit: {}", exit));
+        }
+        if !self.stderr_tail.is_empty() {
+            lines.push(format!("stderr: {}", self.stderr_tail.join(" | ")));
+        }
+        lines
+    }
+}
+
+/// One running stdio MCP server speaking newline-delimited JSON-RPC.
+struct StdioSession {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    // Disconnects once the stderr reader has seen the end of the pipe.
+    stderr_closed: Receiver<()>,
+    next_id: u64,
+}
+
+impl StdioSession {
+    fn start(
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stderr: Arc<Mutex<VecDeque<String>>>,
+    ) -> Result<Self, String> {
+        let mut child = Command::new(command.trim())
+            .args(args)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start MCP server '{}': {}", command, e))?;
+        let stdin = child.stdin.take().ok_or("MCP server stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("MCP server stdout unavailable")?;
+        let (stderr_open, stderr_closed) = mpsc::channel::<()>();
+        if let Some(pipe) = child.stderr.take() {
+            std::thread::spawn(move || {
+                let _open = stderr_open;
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let Ok(mut tail) = stderr.lock() else {
+                        break;
+                    };
+                    if tail.len() == STDERR_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            });
+        }
+        let (tx, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                // Servers may log to stdout; anything that is not JSON is skipped.
+                if let Ok(message) = serde_json::from_str::<Value>(line.trim()) {
+                    if tx.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        let mut session = Self {
+            child,
+            stdin,
+            messages,
+            stderr_closed,
+            next_id: 1,
+        };
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "aicli", "version": env!("CARGO_PKG_VERSION") }
+        });
+        if let Err(e) = session.request("initialize", params, INIT_TIMEOUT) {
+            // A server that crashes on startup usually says why on stderr;
+            // wait for it to exit so its status and last lines are in.
+            return Err(match session.wait_exit(EXIT_GRACE) {
+                Some(exit) => format!("MCP initialize failed: {}; server exited ({})", e, exit),
+                None => format!("MCP initialize failed: {}", e),
+            });
+        }
+        session.send(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))?;
+        Ok(session)
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("Failed to write to MCP server: {}", e))
+    }
+
+    fn request(&mut self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let reply = self.exchange(method, params, timeout)?;
+        if let Some(error) = reply.get("error") {
+            return Err(format!(
+                "{} (code {})",
+                error.get("message").and_then(Value::as_str).unwrap_or("unknown error"),
+                error.get("code").cloned().unwrap_or(Value::Null)
+            ));
+        }
+        Ok(reply.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    // Send one request and wait for the message answering it, replying to
+    // requests the server makes in the meantime.
+    fn exchange(&mut self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let message = match self.messages.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("no reply to {} after {}s", method, timeout.as_secs()));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format!("MCP server closed its output during {}", method));
+                }
+            };
+            if let Some(asked) = message.get("method").and_then(Value::as_str) {
+                if let Some(request_id) = message.get("id") {
+                    let reply = if asked == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                    } else {
+                        let error = json!({ "code": -32601, "message": "Method not found" });
+                        json!({ "jsonrpc": "2.0", "id": request_id, "error": error })
+                    };
+                    self.send(&reply)?;
+                }
+                continue;
+            }
+            if message.get("id").and_then(Value::as_u64) == Some(id) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// The exit status once the server has exited, waiting up to `within`
+    /// for it, and then briefly for its last stderr lines.
+    fn wait_exit(&mut self, within: Duration) -> Option<String> {
+        let deadline = Instant::now() + within;
+        let exit = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break status.to_string(),
+                Err(e) => break e.to_string(),
+                Ok(None) if Instant::now() >= deadline => return None,
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let _ = self.stderr_closed.recv_timeout(Duration::from_millis(200));
+        Some(exit)
+    }
+}
+
+impl Drop for StdioSession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Supervised {
+    session: Option<StdioSession>,
+    started: bool,
+}
+
+/// Keeps one stdio MCP server running for the whole process: it is started
+/// on first use, restarted with exponential backoff when it exits or stops
+/// answering the heartbeat `ping`, and given up on after `max_restarts`.
+pub struct McpSupervisor {
+    command: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    options: SupervisorOptions,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    inner: Mutex<Supervised>,
+    // Kept apart from `inner` so reading it never waits on a running call.
+    status: Mutex<McpServerStatus>,
+}
+
+impl McpSupervisor {
+    fn new(command: &str, args: &[String], env: &[(String, String)], options: SupervisorOptions) -> Self {
+        Self {
+            command: command.to_string(),
+            args: args.to_vec(),
+            env: env.to_vec(),
+            options,
+            stderr: Arc::new(Mutex::new(VecDeque::new())),
+            inner: Mutex::new(Supervised { session: None, started: false }),
+            status: Mutex::new(McpServerStatus::new(options.max_restarts)),
+        }
+    }
+
+    pub fn status(&self) -> McpServerStatus {
+        let mut status = self
+            .status
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_else(|_| McpServerStatus::new(self.options.max_restarts));
+        status.stderr_tail = self.stderr_tail();
+        status
+    }
+
+    /// Send `method` to the server, starting or restarting it first when
+    /// needed. A server that dies during the call is not asked again, so a
+    /// tool call never runs twice; the next call restarts it.
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let mut inner = self.inner.lock().map_err(|_| "MCP supervisor lock poisoned".to_string())?;
+        self.ensure_running(&mut inner)?;
+        let Some(session) = inner.session.as_mut() else {
+            return Err("MCP server is not running".to_string());
+        };
+        let err = match session.request(method, params, REQUEST_TIMEOUT) {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+        match session.wait_exit(EXIT_GRACE) {
+            Some(exit) => {
+                self.record_exit(&mut inner, exit);
+                Err(format!("{}; {}", err, self.exit_details()))
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Every item of a paged list method such as `tools/list`.
+    pub fn list(&self, method: &str, key: &str) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        let mut cursor: Option<Value> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page = self.request(method, params)?;
+            items.extend(page.get(key).and_then(Value::as_array).cloned().unwrap_or_default());
+            cursor = page.get("nextCursor").filter(|c| !c.is_null()).cloned();
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn ensure_running(&self, inner: &mut Supervised) -> Result<(), String> {
+        match inner.session.as_mut().map(|s| s.wait_exit(Duration::ZERO)) {
+            Some(None) => return Ok(()),
+            Some(Some(exit)) => self.record_exit(inner, exit),
+            None => {}
+        }
+        let status = self.status();
+        if status.state == McpServerState::Failed {
+            return Err(format!("MCP server '{}' is down; {}", self.command, self.exit_details()));
+        }
+        let restarts = status.restarts;
+        if inner.started {
+            if restarts >= self.options.max_restarts {
+                self.set_status(|s| s.state = McpServerState::Failed);
+                return Err(format!(
+                    "MCP server '{}' gave up after {} restarts; {}",
+                    self.command,
+                    restarts,
+                    self.exit_details()
+                ));
+            }
+            std::thread::sleep(backoff(restarts));
+            self.set_status(|s| s.restarts += 1);
+        }
+        inner.started = true;
+        match StdioSession::start(&self.command, &self.args, &self.env, self.stderr.clone()) {
+            Ok(session) => {
+                inner.session = Some(session);
+                self.set_status(|s| s.state = McpServerState::Running);
+                Ok(())
+            }
+            Err(err) => {
+                self.set_status(|s| {
+                    s.state = McpServerState::Exited;
+                    s.last_exit = Some(err.clone());
+                });
+                Err(with_stderr(err, &self.stderr_tail()))
+            }
+        }
+    }
+
+    // Ping a running server, and restart it when it has exited or does not
+    // answer. Any reply counts, even an error.
+    fn heartbeat(&self) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let Some(session) = inner.session.as_mut() else {
+            return;
+        };
+        let problem = match session.wait_exit(Duration::ZERO) {
+            Some(exit) => Some(exit),
+            None => session
+                .exchange("ping", json!({}), PING_TIMEOUT)
+                .err()
+                .map(|err| format!("heartbeat failed: {}", err)),
+        };
+        if let Some(problem) = problem {
+            self.record_exit(&mut inner, problem);
+            let _ = self.ensure_running(&mut inner);
+        }
+    }
+
+    fn record_exit(&self, inner: &mut Supervised, exit: String) {
+        inner.session = None;
+        self.set_status(|s| {
+            s.state = McpServerState::Exited;
+            s.last_exit = Some(exit.clone());
+        });
+    }
+
+    fn set_status(&self, update: impl FnOnce(&mut McpServerStatus)) {
+        if let Ok(mut status) = self.status.lock() {
+            update(&mut status);
+        }
+    }
+
+    fn stderr_tail(&self) -> Vec<String> {
+        self.stderr.lock().map(|t| t.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn exit_details(&self) -> String {
+        let status = self.status();
+        let details = format!("server exited ({})", status.last_exit.as_deref().unwrap_or("unknown status"));
+        with_stderr(details, &status.stderr_tail)
+    }
+}
+
+// `message` followed by the server's last few stderr lines, if it wrote any.
+fn with_stderr(message: String, tail: &[String]) -> String {
+    if tail.is_empty() {
+        return message;
+    }
+    let last = &tail[tail.len().saturating_sub(STDERR_IN_ERRORS)..];
+    format!("{}; stderr: {}", message, last.join(" | "))
+}
+
+/// Wait before restart number `restarts + 1`: 0.5s, doubling up to 30s.
+pub fn backoff(restarts: u32) -> Duration {
+    FIRST_BACKOFF.saturating_mul(1u32 << restarts.min(16)).min(MAX_BACKOFF)
+}
+
+fn supervisors() -> &'static Mutex<HashMap<String, Arc<McpSupervisor>>> {
+    static SUPERVISORS: OnceLock<Mutex<HashMap<String, Arc<McpSupervisor>>>> = OnceLock::new();
+    SUPERVISORS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Servers started with different environments are different servers.
+fn supervisor_key(command: &str, args: &[String], env: &[(String, String)]) -> String {
+    let mut key = format!("{} {}", command.trim(), args.join(" "));
+    for (name, value) in env {
+        key.push_str(&format!("\0{}={}", name, value));
+    }
+    key
+}
+
+/// The process-wide supervisor for this server command line and extra
+/// environment, created (and its heartbeat started) on first use. The
+/// server itself starts with the first request.
+pub fn supervisor_for(
+    command: &str,
+    args: &[String],
+    env: &[(String, String)],
+    options: SupervisorOptions,
+) -> Arc<McpSupervisor> {
+    let mut all = supervisors().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = all.get(&supervisor_key(command, args, env)) {
+        return existing.clone();
+    }
+    let supervisor = Arc::new(McpSupervisor::new(command, args, env, options));
+    if !options.heartbeat.is_zero() {
+        let weak: Weak<McpSupervisor> = Arc::downgrade(&supervisor);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(options.heartbeat);
+            match weak.upgrade() {
+                Some(supervisor) => supervisor.heartbeat(),
+                None => break,
+            }
+        });
+    }
+    all.insert(supervisor_key(command, args, env), supervisor.clone());
+    supervisor
+}
+
+/// Status of the supervised server for this command line and environment,
+/// if it has been used in this process.
+pub fn supervisor_status(command: &str, args: &[String], env: &[(String, String)]) -> Option<McpServerStatus> {
+    let all = supervisors().lock().ok()?;
+    all.get(&supervisor_key(command, args, env)).map(|s| s.status())
+}
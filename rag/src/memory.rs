@@ -0,0 +1,204 @@
+use std::ops::Range;
+
+use crate::build_prompt::Message;
+use crate::config::{Config, GenerationOptions};
+use crate::generate::generate_answer_with;
+use crate::model_info::context_window;
+use crate::tokens::{estimate_tokens, truncate_to_tokens};
+
+/// First line of the system message that stands in for summarized turns.
+pub const SUMMARY_PREFIX: &str = "Summary of earlier conversation:";
+
+// Role and chat-template tokens each message costs on top of its text.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// Smallest share of the summarizer prompt a single old message is cut to.
+const MIN_MESSAGE_TOKENS: usize = 64;
+
+/// What one compaction did, for the agent trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compaction {
+    pub messages: usize,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    pub summary: String,
+}
+
+/// Estimated prompt size of `messages`.
+pub fn conversation_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|m| estimate_tokens(&m.content) + MESSAGE_OVERHEAD_TOKENS)
+        .sum()
+}
+
+/// `RAG_MEMORY_MAX_TOKENS`, or three quarters of the model's context window
+/// so the controller still has room to answer.
+pub fn memory_threshold(cfg: &Config) -> usize {
+    cfg.memory_max_tokens.unwrap_or_else(|| context_window(cfg) * 3 / 4)
+}
+
+pub fn is_summary(message: &Message) -> bool {
+    message.role == "system" && message.content.starts_with(SUMMARY_PREFIX)
+}
+
+/// Tool results, system context, and the assistant's own turns (the
+/// controller's JSON decisions): the messages compaction may summarize. The
+/// user's turns are always kept as written.
+pub fn is_context_message(message: &Message) -> bool {
+    message.role != "user"
+}
+
+/// The span a compaction works on: everything after the leading system
+/// prompts except the `keep_recent` newest, including an earlier summary so
+/// it is folded into the new one. `None` when it holds fewer than two
+/// context messages.
+pub fn compaction_range(messages: &[Message], keep_recent: usize) -> Option<Range<usize>> {
+    let start = messages
+        .iter()
+        .take_while(|m| m.role == "system" && !is_summary(m))
+        .count();
+    let end = messages.len().saturating_sub(keep_recent).max(start);
+    let context = messages[start..end].iter().filter(|m| is_context_message(m)).count();
+    (context >= 2).then_some(start..end)
+}
+
+/// The system message that replaces summarized turns. Pinned facts are
+/// listed verbatim after the summary, whatever the summarizer kept.
+pub fn summary_message(summary: &str, pinned: &[String]) -> Message {
+    let mut content = format!("{}\n{}", SUMMARY_PREFIX, summary.trim());
+    if !pinned.is_empty() {
+        content.push_str("\n\nPinned facts:");
+        for fact in pinned {
+            content.push_str(&format!("\n- {}", fact));
+        }
+    }
+    Message {
+        role: "system".to_string(),
+        content,
+    }
+}
+
+/// Ask the chat model for a summary of `messages` focused on `question`,
+/// each message cut so the whole transcript fits in `max_tokens`.
+pub fn summarize_messages(
+    cfg: &Config,
+    messages: &[Message],
+    question: &str,
+    pinned: &[String],
+    max_tokens: usize,
+) -> Result<String, String> {
+    let per_message = (max_tokens / messages.len().max(1)).max(MIN_MESSAGE_TOKENS);
+    let transcript = messages
+        .iter()
+        .map(|m| format!("[{}] {}", m.role, truncate_to_tokens(&m.content, per_message)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let keep = if pinned.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n\nThese facts are pinned and must stay true in the summary:\n{}",
+            pinned.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
+        )
+    };
+    let request = vec![
+        Message {
+            role: "system".to_string(),
+            content: "You compress an assistant's working memory. Summarize the retrieved context and tool results below in a few short paragraphs or bullets. Keep every fact that could help answer the user's question with its source path or tool name, decisions made, and open questions. Drop repetition and raw output that led nowhere.".to_string(),
+        },
+        Message {
+            role: "user".to_string(),
+            content: format!(
+                "User question: {}\n\n{}{}\n\nReturn only the summary.",
+                question, transcript, keep
+            ),
+        },
+    ];
+    let deterministic = GenerationOptions {
+        temperature: Some(0.0),
+        ..GenerationOptions::default()
+    };
+    let summary = generate_answer_with(cfg, &request, &deterministic)?;
+    if summary.trim().is_empty() {
+        return Err("Model returned an empty summary".to_string());
+    }
+    Ok(summary.trim().to_string())
+}
+
+/// When `conversation` is over `memory_threshold`, replace each run of older
+/// context messages between the user's turns with a summary in its place,
+/// so the conversation keeps its order. The system prompts, the user's
+/// questions, and the `RAG_MEMORY_KEEP_RECENT` newest messages stay as they
+/// are; pinned facts follow the newest summary. `None` when the
+/// conversation fits or there is too little to summarize.
+pub fn compact_conversation(
+    cfg: &Config,
+    conversation: &mut Vec<Message>,
+    pinned: &[String],
+) -> Result<Option<Compaction>, String> {
+    let threshold = memory_threshold(cfg);
+    let tokens_before = conversation_tokens(conversation);
+    if tokens_before <= threshold {
+        return Ok(None);
+    }
+    let Some(range) = compaction_range(conversation, cfg.memory_keep_recent) else {
+        return Ok(None);
+    };
+    let runs = context_runs(&conversation[range.clone()]);
+    if runs.is_empty() {
+        return Ok(None);
+    }
+    let question = conversation
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.clone())
+        .unwrap_or_default();
+    // Summarize every run before changing anything, so a failure keeps the
+    // conversation whole.
+    let budget = threshold / runs.len().max(1);
+    let mut summaries = Vec::new();
+    for run in &runs {
+        let context = &conversation[range.start + run.start..range.start + run.end];
+        summaries.push(summarize_messages(cfg, context, &question, pinned, budget)?);
+    }
+    let mut compacted = Vec::with_capacity(conversation.len());
+    compacted.extend_from_slice(&conversation[..range.start]);
+    let mut next = 0;
+    for (i, (run, summary)) in runs.iter().zip(&summaries).enumerate() {
+        compacted.extend_from_slice(&conversation[range.start + next..range.start + run.start]);
+        let last = i + 1 == runs.len();
+        compacted.push(summary_message(summary, if last { pinned } else { &[] }));
+        next = run.end;
+    }
+    compacted.extend_from_slice(&conversation[range.start + next..]);
+    *conversation = compacted;
+    Ok(Some(Compaction {
+        messages: runs.iter().map(|run| run.len()).sum(),
+        tokens_before,
+        tokens_after: conversation_tokens(conversation),
+        summary: summaries.join("\n\n"),
+    }))
+}
+
+// The spans of consecutive context messages in `messages`, skipping a lone
+// earlier summary, which would only be summarized again.
+fn context_runs(messages: &[Message]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, message) in messages.iter().enumerate() {
+        match (is_context_message(message), start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                runs.push(from..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        runs.push(from..messages.len());
+    }
+    runs.retain(|run| run.len() > 1 || !is_summary(&messages[run.start]));
+    runs
+}
@@ -0,0 +1,59 @@
+use crate::retrieve_chunks::Hit;
+
+/// Pick `k` hits from `candidates` using Maximal Marginal Relevance.
+/// `lambda` = 1.0 is pure relevance, 0.0 is pure diversity. Hits without a
+/// stored vector keep their relevance score and are never penalised.
+pub fn mmr_select(query: &[f32], candidates: Vec<Hit>, k: usize, lambda: f32) -> Vec<Hit> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let relevance: Vec<f32> = candidates
+        .iter()
+        .map(|h| h.vector.as_deref().map(|v| cosine(query, v)).unwrap_or(h.score))
+        .collect();
+
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut selected: Vec<usize> = Vec::new();
+    while selected.len() < k && !remaining.is_empty() {
+        let mut best_pos = 0;
+        let mut best_score = f32::NEG_INFINITY;
+        for (pos, &idx) in remaining.iter().enumerate() {
+            let redundancy = selected
+                .iter()
+                .filter_map(|&s| match (&candidates[idx].vector, &candidates[s].vector) {
+                    (Some(a), Some(b)) => Some(cosine(a, b)),
+                    _ => None,
+                })
+                .fold(0.0f32, f32::max);
+            let score = lambda * relevance[idx] - (1.0 - lambda) * redundancy;
+            if score > best_score {
+                best_score = score;
+                best_pos = pos;
+            }
+        }
+        selected.push(remaining.remove(best_pos));
+    }
+
+    let mut slots: Vec<Option<Hit>> = candidates.into_iter().map(Some).collect();
+    selected
+        .into_iter()
+        .filter_map(|idx| slots[idx].take())
+        .collect()
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let mut dot = 0.0f32;
+    let mut na = 0.0f32;
+    let mut nb = 0.0f32;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0.0 || nb == 0.0 {
+        0.0
+    } else {
+        dot / (na.sqrt() * nb.sqrt())
+    }
+}
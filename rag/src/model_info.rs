@@ -0,0 +1,92 @@
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::Config;
+use crate::http::{post_json_async, HttpOptions};
+use crate::ollama_pool::{host_urls, pick_ollama_host};
+use crate::runtime::try_block_on;
+
+pub const DEFAULT_CONTEXT_WINDOW: usize = 4096;
+// Models advertise their trained maximum (often 128k); allocating that much KV
+// cache by default would exhaust memory on most machines.
+const MAX_AUTO_CONTEXT_WINDOW: usize = 32_768;
+
+fn cache() -> &'static Mutex<HashMap<String, usize>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn cache_key(host: &str, model: &str) -> String {
+    format!("{}|{}", host, model)
+}
+
+// The override or an already-detected value; never touches the network, so
+// it is safe to call from async code once the cache is warm. The prompt is
+// built before a host is picked for the answer, so with several hosts this
+// is the smallest window detected on any of them.
+fn known_context_window(cfg: &Config) -> Option<usize> {
+    if let Some(n) = cfg.generation.num_ctx {
+        return Some(n);
+    }
+    let cache = cache().lock().ok()?;
+    host_urls(cfg)
+        .into_iter()
+        .filter_map(|host| cache.get(&cache_key(host, &cfg.chat_model)).copied())
+        .min()
+}
+
+/// Context window for the configured chat model: `RAG_NUM_CTX` when set,
+/// otherwise the value reported by `/api/show`, cached per host and model.
+pub fn context_window(cfg: &Config) -> usize {
+    known_context_window(cfg)
+        .unwrap_or_else(|| try_block_on(context_window_async(cfg)).unwrap_or(DEFAULT_CONTEXT_WINDOW))
+}
+
+pub async fn context_window_async(cfg: &Config) -> usize {
+    if let Some(n) = known_context_window(cfg) {
+        return n;
+    }
+    let host = pick_ollama_host(cfg).await;
+    let detected = detect_context_window(cfg, host.url())
+        .await
+        .map(|n| n.min(MAX_AUTO_CONTEXT_WINDOW))
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW);
+    if let Ok(mut c) = cache().lock() {
+        c.insert(cache_key(host.url(), &cfg.chat_model), detected);
+    }
+    detected
+}
+
+async fn detect_context_window(cfg: &Config, host: &str) -> Result<usize, String> {
+    if cfg.llm_provider.eq_ignore_ascii_case("replay") {
+        return Err("context detection is not available for the replay provider".to_string());
+    }
+    let url = format!("{}/api/show", host);
+    let body = json!({ "model": cfg.chat_model });
+    let res = post_json_async::<Value, _>(&url, &body, &HttpOptions::for_ollama(cfg)).await?;
+    parse_context_length(&res).ok_or_else(|| format!("No context length reported for {}", cfg.chat_model))
+}
+
+/// Read the context length from an `/api/show` response. An explicit
+/// `num_ctx` in the modelfile parameters wins over the architecture maximum.
+pub fn parse_context_length(show: &Value) -> Option<usize> {
+    if let Some(params) = show.get("parameters").and_then(|p| p.as_str()) {
+        for line in params.lines() {
+            let mut parts = line.split_whitespace();
+            if parts.next() == Some("num_ctx") {
+                if let Some(n) = parts.next().and_then(|v| v.parse().ok()) {
+                    return Some(n);
+                }
+            }
+        }
+    }
+    show.get("model_info")
+        .and_then(|info| info.as_object())
+        .and_then(|info| {
+            info.iter()
+                .find(|(k, _)| k.ends_with(".context_length"))
+                .and_then(|(_, v)| v.as_u64())
+        })
+        .map(|n| n as usize)
+}
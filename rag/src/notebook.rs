@@ -0,0 +1,45 @@
+use serde_json::Value;
+
+/// One code or markdown cell from a Jupyter notebook.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotebookCell {
+    pub index: usize,
+    pub cell_type: String,
+    pub source: String,
+}
+
+pub fn is_notebook(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".ipynb")
+}
+
+/// Extract code and markdown cells. Outputs and raw cells are skipped since
+/// they are mostly noise for retrieval.
+pub fn parse_notebook(text: &str) -> Result<Vec<NotebookCell>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid notebook JSON: {}", e))?;
+    let cells = value
+        .get("cells")
+        .and_then(|c| c.as_array())
+        .ok_or_else(|| "Notebook has no cells array".to_string())?;
+
+    let mut out = Vec::new();
+    for (index, cell) in cells.iter().enumerate() {
+        let cell_type = cell.get("cell_type").and_then(|t| t.as_str()).unwrap_or("");
+        if cell_type != "code" && cell_type != "markdown" {
+            continue;
+        }
+        let source = match cell.get("source") {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(lines)) => lines.iter().filter_map(|l| l.as_str()).collect::<String>(),
+            _ => String::new(),
+        };
+        if source.trim().is_empty() {
+            continue;
+        }
+        out.push(NotebookCell {
+            index,
+            cell_type: cell_type.to_string(),
+            source,
+        });
+    }
+    Ok(out)
+}
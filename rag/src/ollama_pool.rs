@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::task::JoinSet;
+
+use crate::config::Config;
+use crate::http::{get_json_async, HttpOptions};
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How requests are spread over the hosts in `OLLAMA_URL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BalancePolicy {
+    RoundRobin,
+    LeastPending,
+}
+
+impl BalancePolicy {
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "least-pending" | "least_pending" | "least" => BalancePolicy::LeastPending,
+            _ => BalancePolicy::RoundRobin,
+        }
+    }
+}
+
+/// Index of the host to use. Unhealthy hosts are skipped unless every host
+/// is unhealthy, in which case all are candidates again. `turn` is a
+/// running counter: it picks the host for round-robin and breaks ties for
+/// least-pending.
+pub fn choose_host(policy: BalancePolicy, pending: &[usize], healthy: &[bool], turn: usize) -> usize {
+    let mut candidates: Vec<usize> = (0..pending.len()).filter(|&i| healthy.get(i).copied().unwrap_or(true)).collect();
+    if candidates.is_empty() {
+        candidates = (0..pending.len()).collect();
+    }
+    if candidates.is_empty() {
+        return 0;
+    }
+    match policy {
+        BalancePolicy::RoundRobin => candidates[turn % candidates.len()],
+        BalancePolicy::LeastPending => {
+            let len = candidates.len();
+            let least = candidates.iter().map(|&i| pending[i]).min().unwrap_or(0);
+            (0..len)
+                .map(|k| candidates[(turn + k) % len])
+                .find(|&i| pending[i] == least)
+                .unwrap_or(candidates[0])
+        }
+    }
+}
+
+#[derive(Default)]
+struct HostState {
+    pending: AtomicUsize,
+    /// Result and time of the last health check; `None` forces a new check.
+    health: Mutex<Option<(bool, Instant)>>,
+}
+
+fn hosts() -> &'static Mutex<HashMap<String, Arc<HostState>>> {
+    static HOSTS: OnceLock<Mutex<HashMap<String, Arc<HostState>>>> = OnceLock::new();
+    HOSTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn host_state(url: &str) -> Arc<HostState> {
+    let mut hosts = hosts().lock().unwrap_or_else(|e| e.into_inner());
+    hosts.entry(url.to_string()).or_default().clone()
+}
+
+fn next_turn() -> usize {
+    static TURN: AtomicUsize = AtomicUsize::new(0);
+    TURN.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A host chosen for one request. It counts as pending until dropped.
+pub(crate) struct OllamaHost {
+    url: String,
+    state: Arc<HostState>,
+}
+
+impl OllamaHost {
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Mark the host for a fresh health check before it is picked again.
+    pub(crate) fn failed(&self) {
+        if let Ok(mut health) = self.state.health.lock() {
+            *health = None;
+        }
+    }
+}
+
+impl Drop for OllamaHost {
+    fn drop(&mut self) {
+        self.state.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Pick an Ollama host for the next request. With a single configured host
+/// this is free; with several, hosts whose health check is older than
+/// `OLLAMA_HEALTH_INTERVAL` are probed together via `/api/version` first.
+pub(crate) async fn pick_ollama_host(cfg: &Config) -> OllamaHost {
+    let urls = host_urls(cfg);
+    let states: Vec<Arc<HostState>> = urls.iter().map(|u| host_state(u)).collect();
+    let idx = if urls.len() == 1 {
+        0
+    } else {
+        let healthy = check_health(cfg, &urls, &states).await;
+        let pending: Vec<usize> = states.iter().map(|s| s.pending.load(Ordering::Relaxed)).collect();
+        choose_host(BalancePolicy::parse(&cfg.ollama_balance), &pending, &healthy, next_turn())
+    };
+    let state = states[idx].clone();
+    state.pending.fetch_add(1, Ordering::Relaxed);
+    OllamaHost {
+        url: urls[idx].to_string(),
+        state,
+    }
+}
+
+// Health of each host. Hosts checked within `OLLAMA_HEALTH_INTERVAL` keep
+// their last result; the rest are probed at once, so a down host delays a
+// request by one `HEALTH_TIMEOUT` at most, however many hosts there are.
+/// Every configured host: the `OLLAMA_URL` pool, or the single URL.
+pub(crate) fn host_urls(cfg: &Config) -> Vec<&str> {
+    if cfg.ollama_urls.is_empty() {
+        vec![cfg.ollama_url.as_str()]
+    } else {
+        cfg.ollama_urls.iter().map(String::as_str).collect()
+    }
+}
+
+async fn check_health(cfg: &Config, urls: &[&str], states: &[Arc<HostState>]) -> Vec<bool> {
+    let interval = Duration::from_secs(cfg.ollama_health_interval_secs);
+    let opts = HttpOptions {
+        timeout: HEALTH_TIMEOUT,
+        retries: 0,
+        ..HttpOptions::for_ollama(cfg)
+    };
+    let mut healthy = vec![true; urls.len()];
+    let mut probes = JoinSet::new();
+    for (i, (url, state)) in urls.iter().zip(states).enumerate() {
+        let cached = state.health.lock().ok().and_then(|h| *h);
+        if let Some((ok, checked)) = cached {
+            if checked.elapsed() < interval {
+                healthy[i] = ok;
+                continue;
+            }
+        }
+        let url = format!("{}/api/version", url);
+        let opts = opts.clone();
+        let state = state.clone();
+        probes.spawn(async move {
+            let ok = get_json_async::<Value>(&url, &opts).await.is_ok();
+            if let Ok(mut health) = state.health.lock() {
+                *health = Some((ok, Instant::now()));
+            }
+            (i, ok)
+        });
+    }
+    while let Some(joined) = probes.join_next().await {
+        if let Ok((i, ok)) = joined {
+            healthy[i] = ok;
+        }
+    }
+    healthy
+}
+
+/// Split a comma-separated `OLLAMA_URL` into base URLs.
+pub fn parse_ollama_urls(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|u| u.trim().trim_end_matches('/').to_string())
+        .filter(|u| !u.is_empty())
+        .collect()
+}
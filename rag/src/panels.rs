@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::deadline::call_with_timeout;
+use crate::platform::shell_command;
+
+// Longest a panel command may run before its panel shows an error.
+const PANEL_TIMEOUT: Duration = Duration::from_secs(30);
+// Lines of output kept per panel.
+const PANEL_MAX_LINES: usize = 200;
+
+/// A read-only TUI panel filled by an external command, such as CI status
+/// or a ticket queue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanelSpec {
+    pub name: String,
+    /// Shell command whose stdout is the panel's text.
+    pub command: String,
+    /// How often a shown panel is refreshed; zero runs it once per showing.
+    pub refresh: Duration,
+}
+
+/// Parse `AICLI_PANELS`: `name=<seconds>:<shell command>` entries separated
+/// by `;`, e.g. `ci=60:gh run list --limit 5; jira=300:jira issue list --plain`.
+pub fn parse_panels(raw: &str) -> Vec<PanelSpec> {
+    let mut out = Vec::new();
+    for entry in raw.split(';') {
+        let Some((name, spec)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some((secs, command)) = spec.split_once(':') else {
+            continue;
+        };
+        let Ok(secs) = secs.trim().parse::<u64>() else {
+            continue;
+        };
+        let (name, command) = (name.trim(), command.trim());
+        if !name.is_empty() && !command.is_empty() {
+            out.push(PanelSpec {
+                name: name.to_string(),
+                command: command.to_string(),
+                refresh: Duration::from_secs(secs),
+            });
+        }
+    }
+    out
+}
+
+/// Run a panel's command: its stdout without terminal escapes, cut to the
+/// first 200 lines. A failing command's stderr becomes the error. Refused
+/// in safe mode, like shell commands.
+pub fn run_panel(cfg: &Config, panel: &PanelSpec) -> Result<String, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: panel commands are disabled.".to_string());
+    }
+    let mut command = shell_command(&cfg.shell, &panel.command);
+    command.env("AICLI_PANEL", &panel.name);
+    let name = panel.name.clone();
+    let output = call_with_timeout(Some(PANEL_TIMEOUT), move || {
+        command
+            .output()
+            .map_err(|e| format!("Failed to run panel {}: {}", name, e))
+    })?;
+    if !output.status.success() {
+        let stderr = strip_escapes(&String::from_utf8_lossy(&output.stderr));
+        return Err(format!("{} ({})", stderr.trim(), output.status));
+    }
+    let text = strip_escapes(&String::from_utf8_lossy(&output.stdout));
+    Ok(text.lines().take(PANEL_MAX_LINES).collect::<Vec<_>>().join("\n"))
+}
+
+// Drop ANSI escape sequences and other control characters, which would
+// garble the TUI. Newlines and tabs are kept.
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\u{1b}' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                // Parameters, then one final byte in `@`..=`~`.
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            } else {
+                chars.next();
+            }
+        } else if ch == '\n' || ch == '\t' || !ch.is_control() {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// One registered panel and what it last showed.
+#[derive(Clone, Debug)]
+pub struct PanelState {
+    pub spec: PanelSpec,
+    pub visible: bool,
+    pub output: Option<Result<String, String>>,
+    pub updated: Option<Instant>,
+    pub running: bool,
+}
+
+/// The panels from `AICLI_PANELS`, in order. All start hidden; the TUI
+/// toggles them and asks which shown panels are due for a refresh.
+#[derive(Clone, Debug, Default)]
+pub struct PanelRegistry {
+    panels: Vec<PanelState>,
+}
+
+impl PanelRegistry {
+    pub fn new(specs: &[PanelSpec]) -> Self {
+        Self {
+            panels: specs
+                .iter()
+                .map(|spec| PanelState {
+                    spec: spec.clone(),
+                    visible: false,
+                    output: None,
+                    updated: None,
+                    running: false,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn panels(&self) -> &[PanelState] {
+        &self.panels
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.panels.is_empty()
+    }
+
+    pub fn visible(&self) -> impl Iterator<Item = &PanelState> {
+        self.panels.iter().filter(|p| p.visible)
+    }
+
+    /// Show or hide panel `name`; the new visibility, or `None` for an
+    /// unknown name. A panel shown again is refreshed right away.
+    pub fn toggle(&mut self, name: &str) -> Option<bool> {
+        let panel = self.panels.iter_mut().find(|p| p.spec.name == name)?;
+        panel.visible = !panel.visible;
+        if panel.visible {
+            panel.updated = None;
+        }
+        Some(panel.visible)
+    }
+
+    /// Shown panels that have not run yet or whose refresh interval has
+    /// passed, marked as running until `update` gets their output.
+    pub fn due(&mut self, now: Instant) -> Vec<PanelSpec> {
+        let mut due = Vec::new();
+        for panel in self.panels.iter_mut().filter(|p| p.visible && !p.running) {
+            let stale = match panel.updated {
+                None => true,
+                Some(at) => !panel.spec.refresh.is_zero() && now.duration_since(at) >= panel.spec.refresh,
+            };
+            if stale {
+                panel.running = true;
+                due.push(panel.spec.clone());
+            }
+        }
+        due
+    }
+
+    pub fn update(&mut self, name: &str, output: Result<String, String>, now: Instant) {
+        if let Some(panel) = self.panels.iter_mut().find(|p| p.spec.name == name) {
+            panel.output = Some(output);
+            panel.updated = Some(now);
+            panel.running = false;
+        }
+    }
+}
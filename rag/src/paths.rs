@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+use crate::config::Config;
+use crate::platform::home_dir;
+
+/// Which directory an entry of the aicli layout lives in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirKind {
+    /// `AICLI_CONFIG_DIR`: settings made by hand or from the TUI.
+    Config,
+    /// `AICLI_DATA_DIR`: sessions, manifests, snapshots, history, and logs.
+    Data,
+    /// `AICLI_CACHE_DIR`: anything that is rebuilt when deleted.
+    Cache,
+}
+
+const CONFIG_ENTRIES: &[&str] = &["collections.json", "snippets.json", "templates.json"];
+const CACHE_ENTRIES: &[&str] = &["answer_cache", "mcp_cache", "summaries"];
+
+// Left in the legacy directory once its contents have been moved, so the
+// move is tried only once.
+const MIGRATED_MARKER: &str = "MIGRATED";
+
+/// Where the file or directory `name`, directly under one of the aicli
+/// directories, belongs.
+pub fn dir_kind(name: &str) -> DirKind {
+    if CONFIG_ENTRIES.contains(&name) {
+        DirKind::Config
+    } else if CACHE_ENTRIES.contains(&name) {
+        DirKind::Cache
+    } else {
+        DirKind::Data
+    }
+}
+
+pub fn dir_for(cfg: &Config, kind: DirKind) -> PathBuf {
+    PathBuf::from(match kind {
+        DirKind::Config => &cfg.config_dir,
+        DirKind::Data => &cfg.data_dir,
+        DirKind::Cache => &cfg.cache_dir,
+    })
+}
+
+/// `~/.aicli`, which held everything before the config, data, and cache
+/// directories were split.
+pub fn legacy_dir() -> PathBuf {
+    home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".aicli")
+}
+
+/// The platform's directory of `kind` for aicli: `~/.config/aicli`,
+/// `~/.local/share/aicli`, and `~/.cache/aicli` on Linux (or under
+/// `XDG_CONFIG_HOME`, `XDG_DATA_HOME`, and `XDG_CACHE_HOME`),
+/// `~/Library/...` on macOS, and `%APPDATA%`/`%LOCALAPPDATA%` on Windows.
+/// `legacy_dir` when there is no home directory to put them in.
+pub fn default_dir(kind: DirKind) -> String {
+    let Some(dirs) = ProjectDirs::from("", "", "aicli") else {
+        return legacy_dir().to_string_lossy().to_string();
+    };
+    let dir = match kind {
+        DirKind::Config => dirs.config_dir(),
+        DirKind::Data => dirs.data_dir(),
+        DirKind::Cache => dirs.cache_dir(),
+    };
+    dir.to_string_lossy().to_string()
+}
+
+/// Move what `legacy` holds into the configured directories, each entry to
+/// the one `dir_kind` gives it, and return where they went. Runs once: a
+/// `MIGRATED` file is left behind, along with any entry whose new place is
+/// already taken. Nothing moves when `legacy` is itself one of the
+/// configured directories.
+pub fn migrate_legacy_dir(cfg: &Config, legacy: &Path) -> Result<Vec<PathBuf>, String> {
+    let configured = [DirKind::Config, DirKind::Data, DirKind::Cache].map(|kind| dir_for(cfg, kind));
+    if !legacy.is_dir() || legacy.join(MIGRATED_MARKER).exists() || configured.iter().any(|dir| dir == legacy) {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(legacy).map_err(|e| format!("Failed to read {}: {}", legacy.display(), e))?;
+    let mut moved = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", legacy.display(), e))?;
+        let name = entry.file_name();
+        let target = dir_for(cfg, dir_kind(&name.to_string_lossy())).join(&name);
+        if target.exists() {
+            continue;
+        }
+        move_path(&entry.path(), &target)?;
+        moved.push(target);
+    }
+    let note = format!(
+        "aicli moved these files to:\nconfig: {}\ndata: {}\ncache: {}\n",
+        cfg.config_dir, cfg.data_dir, cfg.cache_dir
+    );
+    fs::write(legacy.join(MIGRATED_MARKER), note)
+        .map_err(|e| format!("Failed to write {}: {}", legacy.join(MIGRATED_MARKER).display(), e))?;
+    Ok(moved)
+}
+
+// Rename, or copy and delete when `to` is on another filesystem.
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_path(from, to)?;
+    let removed = if from.is_dir() { fs::remove_dir_all(from) } else { fs::remove_file(from) };
+    removed.map_err(|e| format!("Failed to remove {}: {}", from.display(), e))
+}
+
+fn copy_path(from: &Path, to: &Path) -> Result<(), String> {
+    let copy_err = |e: std::io::Error| format!("Failed to copy {} to {}: {}", from.display(), to.display(), e);
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ()).map_err(copy_err);
+    }
+    fs::create_dir_all(to).map_err(copy_err)?;
+    for entry in fs::read_dir(from).map_err(copy_err)? {
+        let entry = entry.map_err(copy_err)?;
+        copy_path(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::attachments::search_attachments_async;
+use crate::config::Config;
+use crate::doc_type::{boost_doc_types, doc_types_for_query};
+use crate::embed_query::embed_query_async;
+use crate::mmr::mmr_select;
+use crate::query_expansion::{expand_query_async, merge_hits};
+use crate::rerank::{add_rerank_time, rerank_hits, reranker_for};
+use crate::retrieve_chunks::{
+    filter_by_paths, filter_by_score, lower_is_better, search_points_async, Hit, Retrieval,
+};
+
+/// One step of a `RAG_PIPELINE` retrieval pipeline. A stage left without a
+/// parameter takes it from the matching setting (`RAG_QUERY_EXPANSION`,
+/// `RAG_MIN_SCORE`, `RAG_FOCUS_PATHS`, `RAG_RERANKER`, `RAG_DOC_TYPE_BOOST`,
+/// `RAG_MMR_LAMBDA`, `RAG_TOP_K`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stage {
+    /// Rewrite the question into more queries: `hyde` or `multi`.
+    Expand(Option<String>),
+    /// Embed each query and fetch this many nearest chunks (default four
+    /// times `top_k`), plus attached files, merged by best score.
+    Search(Option<usize>),
+    MinScore(Option<f32>),
+    /// Keep hits whose path contains one of these.
+    Focus(Vec<String>),
+    /// Rescore with `llm` or `local`. A failed rerank keeps the order.
+    Rerank(Option<String>),
+    /// Add this weight to hits whose doc type the question asks about.
+    Boost(Option<f32>),
+    /// Diversify with Maximal Marginal Relevance at this lambda.
+    Mmr(Option<f32>),
+    /// Keep the first `k` hits.
+    Top(Option<usize>),
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Expand(_) => "expand",
+            Stage::Search(_) => "search",
+            Stage::MinScore(_) => "min_score",
+            Stage::Focus(_) => "focus",
+            Stage::Rerank(_) => "rerank",
+            Stage::Boost(_) => "boost",
+            Stage::Mmr(_) => "mmr",
+            Stage::Top(_) => "top",
+        }
+    }
+}
+
+/// The stage names `RAG_PIPELINE` accepts, in their usual order.
+pub const PIPELINE_STAGES: &[&str] = &["expand", "search", "min_score", "focus", "rerank", "boost", "mmr", "top"];
+
+/// Parse `RAG_PIPELINE`: comma-separated stages, each a name with optional
+/// parameters in parentheses, e.g. `expand(multi), search(40), rerank(llm),
+/// mmr(0.5), top(8)`. There must be exactly one `search`; `expand` goes
+/// before it and every other stage after it.
+pub fn parse_pipeline(raw: &str) -> Result<Vec<Stage>, String> {
+    let mut stages = Vec::new();
+    for spec in split_top_level(raw) {
+        let (name, params) = match spec.split_once('(') {
+            Some((name, rest)) => {
+                let inner = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("RAG_PIPELINE stage '{}' is missing ')'", spec))?;
+                let params: Vec<String> = inner
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                (name.trim(), params)
+            }
+            None => (spec.as_str(), Vec::new()),
+        };
+        stages.push(parse_stage(&name.to_ascii_lowercase(), params)?);
+    }
+    let searches = stages.iter().filter(|s| matches!(s, Stage::Search(_))).count();
+    if searches != 1 {
+        return Err(format!("RAG_PIPELINE needs exactly one search stage, found {}", searches));
+    }
+    let search_at = stages.iter().position(|s| matches!(s, Stage::Search(_))).unwrap_or(0);
+    for (i, stage) in stages.iter().enumerate() {
+        match stage {
+            Stage::Expand(_) if i > search_at => {
+                return Err("RAG_PIPELINE: expand must come before search".to_string());
+            }
+            Stage::Expand(_) | Stage::Search(_) => {}
+            other if i < search_at => {
+                return Err(format!("RAG_PIPELINE: {} must come after search", other.name()));
+            }
+            _ => {}
+        }
+    }
+    Ok(stages)
+}
+
+fn parse_stage(name: &str, params: Vec<String>) -> Result<Stage, String> {
+    let single = |params: &[String]| -> Result<Option<String>, String> {
+        match params {
+            [] => Ok(None),
+            [one] => Ok(Some(one.clone())),
+            _ => Err(format!("RAG_PIPELINE stage {} takes one parameter", name)),
+        }
+    };
+    let number = |params: &[String]| -> Result<Option<f32>, String> {
+        single(params)?
+            .map(|p| p.parse().map_err(|_| format!("RAG_PIPELINE stage {}: '{}' is not a number", name, p)))
+            .transpose()
+    };
+    let count = |params: &[String]| -> Result<Option<usize>, String> {
+        single(params)?
+            .map(|p| match p.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("RAG_PIPELINE stage {}: '{}' is not a positive count", name, p)),
+            })
+            .transpose()
+    };
+    match name {
+        "expand" => {
+            let mode = single(&params)?.map(|m| m.to_ascii_lowercase());
+            if let Some(mode) = mode.as_deref().filter(|m| !matches!(*m, "hyde" | "multi")) {
+                return Err(format!("RAG_PIPELINE stage expand: unknown mode '{}' (hyde or multi)", mode));
+            }
+            Ok(Stage::Expand(mode))
+        }
+        "search" => Ok(Stage::Search(count(&params)?)),
+        "min_score" => Ok(Stage::MinScore(number(&params)?)),
+        "focus" => Ok(Stage::Focus(params)),
+        "rerank" => Ok(Stage::Rerank(single(&params)?)),
+        "boost" => Ok(Stage::Boost(number(&params)?)),
+        "mmr" => Ok(Stage::Mmr(number(&params)?)),
+        "top" => Ok(Stage::Top(count(&params)?)),
+        other => Err(format!(
+            "Unknown RAG_PIPELINE stage '{}' (expected one of: {})",
+            other,
+            PIPELINE_STAGES.join(", ")
+        )),
+    }
+}
+
+// Split on commas outside parentheses, so `focus(src/, docs/)` stays whole.
+fn split_top_level(raw: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    for ch in raw.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    parts.push(current);
+    parts
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Retrieve for `query` by running the `RAG_PIPELINE` stages in order. Ends
+/// with `top_k` hits unless the pipeline has its own `top` stage.
+pub async fn run_pipeline_async(cfg: &Config, query: &str) -> Result<Vec<Hit>, String> {
+    Ok(run_pipeline_retrieval_async(cfg, query).await?.hits)
+}
+
+/// `run_pipeline_async` with the time spent in `rerank` stages.
+pub(crate) async fn run_pipeline_retrieval_async(cfg: &Config, query: &str) -> Result<Retrieval, String> {
+    let stages = parse_pipeline(&cfg.retrieval_pipeline)?;
+    let with_vector = stages.iter().any(|s| matches!(s, Stage::Mmr(_)));
+    let mut queries = vec![query.to_string()];
+    let mut query_vector: Vec<f32> = Vec::new();
+    let mut hits: Vec<Hit> = Vec::new();
+    let mut retrieval = Retrieval::default();
+    for stage in &stages {
+        match stage {
+            Stage::Expand(mode) => {
+                let mut expand_cfg = cfg.clone();
+                if let Some(mode) = mode {
+                    expand_cfg.query_expansion = mode.clone();
+                }
+                queries = expand_query_async(&expand_cfg, query).await;
+            }
+            Stage::Search(limit) => {
+                let limit = limit.unwrap_or(cfg.top_k * 4);
+                let mut found = Vec::new();
+                for q in &queries {
+                    let vector = embed_query_async(cfg, q).await?;
+                    if vector.is_empty() {
+                        continue;
+                    }
+                    found.extend(search_points_async(cfg, &vector, limit, with_vector, None).await?);
+                    found.extend(search_attachments_async(cfg, &vector, limit).await?);
+                    if query_vector.is_empty() {
+                        query_vector = vector;
+                    }
+                }
+                hits = merge_hits(cfg, found, usize::MAX);
+            }
+            Stage::MinScore(min) => hits = filter_by_score(cfg, hits, min.or(cfg.min_score)),
+            Stage::Focus(paths) => {
+                let paths = if paths.is_empty() { &cfg.focus_paths } else { paths };
+                hits = filter_by_paths(hits, paths);
+            }
+            Stage::Rerank(kind) => {
+                let mut rerank_cfg = cfg.clone();
+                if let Some(kind) = kind {
+                    rerank_cfg.reranker = kind.clone();
+                }
+                if let Some(reranker) = reranker_for(&rerank_cfg)? {
+                    match rerank_hits(reranker.as_ref(), query, &mut hits).await {
+                        Ok(elapsed) => retrieval.rerank_time = add_rerank_time(retrieval.rerank_time, Some(elapsed)),
+                        Err(err) => retrieval.rerank_error = Some(err),
+                    }
+                }
+            }
+            Stage::Boost(weight) => {
+                let weight = weight.unwrap_or(cfg.doc_type_boost);
+                let doc_types = doc_types_for_query(query);
+                hits = boost_doc_types(hits, &doc_types, weight, lower_is_better(cfg));
+            }
+            Stage::Mmr(lambda) => {
+                let lambda = lambda.or(cfg.mmr_lambda).unwrap_or(0.5);
+                let k = hits.len();
+                hits = mmr_select(&query_vector, hits, k, lambda);
+            }
+            Stage::Top(k) => hits.truncate(k.unwrap_or(cfg.top_k)),
+        }
+    }
+    if !stages.iter().any(|s| matches!(s, Stage::Top(_))) {
+        hits.truncate(cfg.top_k);
+    }
+    retrieval.hits = hits;
+    Ok(retrieval)
+}
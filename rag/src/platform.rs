@@ -0,0 +1,211 @@
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// `AICLI_SHELL` when unset: `%COMSPEC%` (normally `cmd.exe`) on Windows,
+/// `sh` elsewhere.
+pub fn default_shell() -> String {
+    if cfg!(windows) {
+        env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+    } else {
+        "sh".to_string()
+    }
+}
+
+/// The flags that make `shell` run one command line: `/C` for cmd,
+/// `-NoProfile -Command` for PowerShell, `-c` for POSIX shells.
+pub fn shell_flags(shell: &str) -> &'static [&'static str] {
+    let name = Path::new(shell.trim())
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        "cmd" => &["/C"],
+        "powershell" | "pwsh" => &["-NoProfile", "-Command"],
+        _ => &["-c"],
+    }
+}
+
+/// A `Command` that runs `line` through `shell`.
+pub fn shell_command(shell: &str, line: &str) -> Command {
+    let mut command = Command::new(shell.trim());
+    command.args(shell_flags(shell)).arg(line);
+    command
+}
+
+/// Open `target` (a file or URL) with the desktop's default application:
+/// `open` on macOS, `start` on Windows, `xdg-open` elsewhere.
+pub fn open_command(target: &str) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(target);
+        command
+    } else if cfg!(windows) {
+        // `start` is a cmd builtin; the empty string is the window title.
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(target);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(target);
+        command
+    }
+}
+
+/// Clipboard writers to try in order, each reading the text on stdin.
+pub fn clipboard_commands() -> Vec<Vec<&'static str>> {
+    if cfg!(target_os = "macos") {
+        vec![vec!["pbcopy"]]
+    } else if cfg!(windows) {
+        vec![vec!["clip"]]
+    } else {
+        vec![
+            vec!["wl-copy"],
+            vec!["xclip", "-selection", "clipboard"],
+            vec!["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+/// Put `text` on the system clipboard with the first writer that works.
+pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let mut tried = Vec::new();
+    for argv in clipboard_commands() {
+        let Ok(mut child) = Command::new(argv[0])
+            .args(&argv[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            tried.push(argv[0]);
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        match child.wait() {
+            Ok(status) if status.success() => return Ok(()),
+            _ => tried.push(argv[0]),
+        }
+    }
+    Err(format!("No clipboard program worked (tried {})", tried.join(", ")))
+}
+
+/// Clipboard readers to try in order, each writing the text to stdout.
+pub fn clipboard_read_commands() -> Vec<Vec<&'static str>> {
+    if cfg!(target_os = "macos") {
+        vec![vec!["pbpaste"]]
+    } else if cfg!(windows) {
+        vec![vec!["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"]]
+    } else {
+        vec![
+            vec!["wl-paste", "--no-newline"],
+            vec!["xclip", "-selection", "clipboard", "-o"],
+            vec!["xsel", "--clipboard", "--output"],
+        ]
+    }
+}
+
+/// The text on the system clipboard, from the first reader that works.
+pub fn read_clipboard() -> Result<String, String> {
+    let mut tried = Vec::new();
+    for argv in clipboard_read_commands() {
+        match Command::new(argv[0]).args(&argv[1..]).stdin(Stdio::null()).output() {
+            Ok(out) if out.status.success() => return Ok(String::from_utf8_lossy(&out.stdout).to_string()),
+            _ => tried.push(argv[0]),
+        }
+    }
+    Err(format!("No clipboard program worked (tried {})", tried.join(", ")))
+}
+
+/// Clipboard text as a block for the next question, or an error when it
+/// holds nothing. Text over `max_bytes` keeps its last lines, where an
+/// error or the end of a log usually is.
+pub fn clipboard_context(text: &str, max_bytes: usize) -> Result<String, String> {
+    let text = text.trim_matches(['\n', '\r']);
+    if text.trim().is_empty() {
+        return Err("The clipboard is empty.".to_string());
+    }
+    let kept = if text.len() > max_bytes {
+        let mut start = text.len() - max_bytes;
+        while !text.is_char_boundary(start) {
+            start += 1;
+        }
+        // Start at a line when one begins in the kept part.
+        let mid_line = text.as_bytes()[start - 1] != b'\n';
+        match text[start..].find('\n') {
+            Some(i) if mid_line && i + 1 < text.len() - start => &text[start + i + 1..],
+            _ => &text[start..],
+        }
+    } else {
+        text
+    };
+    let note = if kept.len() < text.len() {
+        format!(" (last {} of {} bytes)", kept.len(), text.len())
+    } else {
+        String::new()
+    };
+    Ok(format!("----- CLIPBOARD{} -----\n{}\n----- END CLIPBOARD -----", note, kept))
+}
+
+/// The editor to launch: `$VISUAL`, then `$EDITOR` (either may carry
+/// arguments, e.g. `code --wait`), then Notepad on Windows or `vi`.
+pub fn editor_command() -> Vec<String> {
+    for var in ["VISUAL", "EDITOR"] {
+        let words = split_command_line(&env::var(var).unwrap_or_default());
+        if !words.is_empty() {
+            return words;
+        }
+    }
+    vec![if cfg!(windows) { "notepad" } else { "vi" }.to_string()]
+}
+
+/// Split a command line on whitespace, keeping double-quoted words (such as
+/// `"C:\Program Files\Editor\editor.exe"`) together.
+pub fn split_command_line(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for ch in line.chars() {
+        match ch {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        words.push(word);
+    }
+    words
+}
+
+/// `$HOME`, or `%USERPROFILE%` on Windows.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Whether `path` is absolute on any supported OS: `/x`, `C:\x`, `C:/x`, or
+/// a `\\server\share` UNC path, so Windows paths are recognized in text
+/// written on Unix and the other way round.
+pub fn is_absolute_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/');
+    path.starts_with('/') || path.starts_with("\\\\") || drive || Path::new(path).is_absolute()
+}
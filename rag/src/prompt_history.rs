@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Everything typed into the TUI prompt box, oldest first, kept in
+/// `$AICLI_DATA_DIR/history.json` across runs.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PromptHistory {
+    #[serde(default)]
+    pub entries: Vec<String>,
+}
+
+pub fn history_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("history.json")
+}
+
+impl PromptHistory {
+    /// The saved history; empty when there is none or it cannot be read.
+    pub fn load(cfg: &Config) -> Self {
+        fs::read_to_string(history_path(cfg))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the history to disk. In safe mode it stays in memory only.
+    pub fn save(&self, cfg: &Config) -> Result<(), String> {
+        if cfg.safe_mode {
+            return Ok(());
+        }
+        let path = history_path(cfg);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Add `prompt` as the newest entry, moving it there if it was typed
+    /// before, and drop the oldest entries beyond `max`.
+    pub fn push(&mut self, prompt: &str, max: usize) {
+        let prompt = prompt.trim();
+        if prompt.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != prompt);
+        self.entries.push(prompt.to_string());
+        let excess = self.entries.len().saturating_sub(max);
+        self.entries.drain(..excess);
+    }
+
+    /// The entry before `pos`, or the newest one when not browsing yet.
+    pub fn older(&self, pos: Option<usize>) -> Option<usize> {
+        match pos {
+            None => self.entries.len().checked_sub(1),
+            Some(pos) => pos.checked_sub(1),
+        }
+    }
+
+    /// The entry after `pos`; `None` past the newest, back at the draft.
+    pub fn newer(&self, pos: usize) -> Option<usize> {
+        (pos + 1 < self.entries.len()).then_some(pos + 1)
+    }
+
+    /// The newest entry containing `query`, ignoring case, that is older
+    /// than `before` (any entry when `None`).
+    pub fn search(&self, query: &str, before: Option<usize>) -> Option<usize> {
+        let query = query.to_lowercase();
+        let end = before.unwrap_or(self.entries.len()).min(self.entries.len());
+        self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.to_lowercase().contains(&query))
+    }
+}
@@ -0,0 +1,118 @@
+/// A prompt that usually wastes a generation, found before it is sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PromptIssue {
+    /// Nothing but spaces and punctuation.
+    Empty,
+    /// A lone file path, `@file`, or URL with nothing asked about it.
+    OnlyPath(String),
+    /// Mostly pasted log output, and no question anywhere.
+    PastedLog { log_lines: usize, lines: usize },
+}
+
+// Fewer lines than this are too short to call a paste.
+const MIN_LOG_LINES: usize = 5;
+
+const LOG_LEVELS: &[&str] = &["TRACE", "DEBUG", "INFO", "WARN", "WARNING", "ERROR", "FATAL", "CRITICAL", "PANIC"];
+
+const QUESTION_WORDS: &[&str] = &[
+    "how", "why", "what", "when", "where", "which", "who", "can", "could", "should", "is", "are", "does", "do",
+    "explain", "fix", "help", "summarize", "find",
+];
+
+/// Check `prompt` for the usual ways a question fails; `None` when it looks
+/// fine.
+pub fn lint_prompt(prompt: &str) -> Option<PromptIssue> {
+    let trimmed = prompt.trim();
+    if !trimmed.chars().any(char::is_alphanumeric) {
+        return Some(PromptIssue::Empty);
+    }
+    if !trimmed.contains(char::is_whitespace) && looks_like_path(trimmed) {
+        return Some(PromptIssue::OnlyPath(trimmed.to_string()));
+    }
+    let lines: Vec<&str> = trimmed.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < MIN_LOG_LINES || trimmed.contains('?') {
+        return None;
+    }
+    let log_lines = lines.iter().filter(|l| is_log_line(l)).count();
+    let asks = lines.iter().filter(|l| !is_log_line(l)).any(|l| {
+        let first = l.split_whitespace().next().unwrap_or_default().to_lowercase();
+        QUESTION_WORDS.contains(&first.trim_end_matches([',', ':']))
+    });
+    // At least 90% log.
+    (log_lines * 10 >= lines.len() * 9 && !asks).then_some(PromptIssue::PastedLog { log_lines, lines: lines.len() })
+}
+
+fn looks_like_path(token: &str) -> bool {
+    let token = token.strip_prefix('@').unwrap_or(token);
+    if token.starts_with("http://") || token.starts_with("https://") {
+        return true;
+    }
+    let name = token.rsplit(['/', '\\']).next().unwrap_or(token);
+    token.contains(['/', '\\'])
+        || name.rsplit_once('.').is_some_and(|(stem, ext)| {
+            !stem.is_empty()
+                && (1..=5).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.chars().any(|c| c.is_ascii_alphabetic())
+        })
+}
+
+// A timestamped or levelled log line, or a stack frame.
+fn is_log_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    let starts_with_date = trimmed.len() >= 10
+        && trimmed.as_bytes()[..4].iter().all(u8::is_ascii_digit)
+        && matches!(trimmed.as_bytes()[4], b'-' | b'/');
+    let starts_with_time = trimmed.len() >= 8
+        && trimmed.as_bytes()[..2].iter().all(u8::is_ascii_digit)
+        && trimmed.as_bytes()[2] == b':';
+    let levelled = trimmed
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .take(6)
+        .any(|word| LOG_LEVELS.contains(&word));
+    let frame = trimmed.starts_with("at ")
+        || trimmed.starts_with("File \"")
+        || trimmed.starts_with("Traceback")
+        || trimmed.starts_with("thread '")
+        || trimmed.starts_with("note: run with")
+        || (trimmed.starts_with('#') && trimmed[1..].starts_with(|c: char| c.is_ascii_digit()));
+    starts_with_date || starts_with_time || levelled || frame || trimmed.starts_with('[')
+}
+
+impl PromptIssue {
+    /// A short note for the answer pane saying what looks wrong and what to
+    /// do instead.
+    pub fn hint(&self) -> String {
+        let anyway = "Press Enter again to send it as it is.";
+        match self {
+            PromptIssue::Empty => {
+                "That prompt has no words in it. Type a question, such as \"How is auth configured?\"".to_string()
+            }
+            PromptIssue::OnlyPath(path) => {
+                format!("That is only a path. Ask something about it, such as \"What does {} do?\" {}", path, anyway)
+            }
+            PromptIssue::PastedLog { log_lines, lines } => format!(
+                "That looks like pasted log output ({} of {} lines) with no question. \
+                 Add one, such as \"Why does this fail?\" {}",
+                log_lines, lines, anyway
+            ),
+        }
+    }
+}
+
+/// `prompt` turned into a question for `issue`, or `None` when there is
+/// nothing to ask (an empty prompt).
+pub fn wrap_prompt(prompt: &str, issue: &PromptIssue) -> Option<String> {
+    match issue {
+        PromptIssue::Empty => None,
+        PromptIssue::OnlyPath(path) => Some(format!(
+            "Explain what {} is for: its purpose, its main parts, and how it fits into the project.",
+            path
+        )),
+        PromptIssue::PastedLog { .. } => Some(format!(
+            "Here is some log output:\n```\n{}\n```\n\
+             What went wrong, what is the most likely cause, and how do I fix it?",
+            prompt.trim()
+        )),
+    }
+}
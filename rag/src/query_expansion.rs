@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use crate::build_prompt::Message;
+use crate::config::Config;
+use crate::embed_query::embed_query_async;
+use crate::generate::generate_answer_async;
+use crate::pipeline::run_pipeline_retrieval_async;
+use crate::rerank::sort_by_rerank_score;
+use crate::retrieve_chunks::{Hit, Retrieval, lower_is_better, retrieve_top_async, sort_by_score};
+use crate::runtime::{block_on, try_block_on};
+
+/// Embed `query` (and any expansions selected by `RAG_QUERY_EXPANSION`),
+/// retrieve for each, and merge the hits by best score. A `RAG_PIPELINE`
+/// replaces these fixed steps with its own stages.
+pub fn retrieve_for_query(cfg: &Config, query: &str) -> Result<Vec<Hit>, String> {
+    block_on(retrieve_for_query_async(cfg, query))
+}
+
+pub async fn retrieve_for_query_async(cfg: &Config, query: &str) -> Result<Vec<Hit>, String> {
+    Ok(retrieval_for_query_async(cfg, query).await?.hits)
+}
+
+pub(crate) async fn retrieval_for_query_async(cfg: &Config, query: &str) -> Result<Retrieval, String> {
+    if !cfg.retrieval_pipeline.trim().is_empty() {
+        return run_pipeline_retrieval_async(cfg, query).await;
+    }
+    let queries = expand_query_async(cfg, query).await;
+    let mut merged = Retrieval::default();
+    for q in &queries {
+        let vector = embed_query_async(cfg, q).await?;
+        let found = retrieve_top_async(cfg, query, &vector).await?;
+        merged.add_rerank(&found);
+        merged.hits.extend(found.hits);
+    }
+    if queries.len() > 1 {
+        merged.hits = merge_hits(cfg, std::mem::take(&mut merged.hits), cfg.top_k);
+    }
+    Ok(merged)
+}
+
+/// Like `retrieve_for_query`, but when focus paths or the minimum score
+/// leave no hits, retry once without them and with twice the `top_k`. The
+/// returned note says what was relaxed, for annotating the answer.
+pub fn retrieve_broadening(cfg: &Config, query: &str) -> Result<(Vec<Hit>, Option<String>), String> {
+    block_on(retrieve_broadening_async(cfg, query))
+}
+
+pub async fn retrieve_broadening_async(cfg: &Config, query: &str) -> Result<(Vec<Hit>, Option<String>), String> {
+    let found = retrieval_broadening_async(cfg, query).await?;
+    Ok((found.hits, found.broadened))
+}
+
+/// `retrieve_broadening_async` with the rerank time and error of both tries.
+pub(crate) async fn retrieval_broadening_async(cfg: &Config, query: &str) -> Result<Retrieval, String> {
+    let found = retrieval_for_query_async(cfg, query).await?;
+    if !found.hits.is_empty() {
+        return Ok(found);
+    }
+    let Some((broad, note)) = broadened_scope(cfg) else {
+        return Ok(found);
+    };
+    let mut retried = retrieval_for_query_async(&broad, query).await?;
+    retried.add_rerank(&found);
+    retried.broadened = Some(note);
+    Ok(retried)
+}
+
+/// A copy of `cfg` with the retrieval filters relaxed, and a note naming
+/// them. `None` when `RAG_BROADEN_ON_EMPTY` is off or nothing narrows the
+/// search, since a wider `top_k` alone cannot turn up a first hit.
+pub fn broadened_scope(cfg: &Config) -> Option<(Config, String)> {
+    if !cfg.broaden_on_empty || (cfg.focus_paths.is_empty() && cfg.min_score.is_none()) {
+        return None;
+    }
+    let mut relaxed = Vec::new();
+    if !cfg.focus_paths.is_empty() {
+        relaxed.push(format!("focus {}", cfg.focus_paths.join(", ")));
+    }
+    if let Some(min) = cfg.min_score {
+        relaxed.push(format!("minimum score {}", min));
+    }
+    let mut broad = cfg.clone();
+    broad.focus_paths.clear();
+    broad.min_score = None;
+    broad.top_k = cfg.top_k.max(1) * 2;
+    let note = format!(
+        "Scope broadened: nothing matched with {}, so the search was repeated without {} and with top_k {}.",
+        relaxed.join(" and "),
+        if relaxed.len() == 1 { "it" } else { "them" },
+        broad.top_k
+    );
+    Some((broad, note))
+}
+
+/// `answer` followed by the broadening note, if any.
+pub fn annotate_broadened(answer: String, note: Option<&str>) -> String {
+    match note {
+        Some(note) => format!("{}\n\n_{}_", answer.trim_end(), note),
+        None => answer,
+    }
+}
+
+/// The original query first, followed by model-generated expansions. Expansion
+/// failures are not fatal; retrieval falls back to the original query alone.
+pub fn expand_query(cfg: &Config, query: &str) -> Vec<String> {
+    try_block_on(expand_query_async(cfg, query)).unwrap_or_else(|_| vec![query.to_string()])
+}
+
+pub async fn expand_query_async(cfg: &Config, query: &str) -> Vec<String> {
+    let mut out = vec![query.to_string()];
+    match cfg.query_expansion.trim().to_ascii_lowercase().as_str() {
+        "hyde" => {
+            let prompt = format!(
+                "Write a short, plausible passage from the project's source or documentation that answers the question below. Do not mention that it is hypothetical.\n\nQuestion: {}",
+                query
+            );
+            if let Ok(passage) = generate_answer_async(cfg, &single_user_message(prompt)).await {
+                if !passage.trim().is_empty() {
+                    out.push(passage.trim().to_string());
+                }
+            }
+        }
+        "multi" => {
+            let prompt = format!(
+                "Rewrite the question below as 3 different search queries that use different wording. Return one query per line with no numbering or extra text.\n\nQuestion: {}",
+                query
+            );
+            if let Ok(text) = generate_answer_async(cfg, &single_user_message(prompt)).await {
+                out.extend(parse_paraphrases(&text, query).into_iter().take(3));
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+fn parse_paraphrases(text: &str, original: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let cleaned = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*'))
+            .trim()
+            .trim_matches('"')
+            .trim();
+        if cleaned.is_empty() || cleaned.eq_ignore_ascii_case(original.trim()) {
+            continue;
+        }
+        if !out.iter().any(|q| q.eq_ignore_ascii_case(cleaned)) {
+            out.push(cleaned.to_string());
+        }
+    }
+    out
+}
+
+/// One hit per chunk, keeping the best score under the collection's distance
+/// metric, best first and at most `limit` of them.
+pub fn merge_hits(cfg: &Config, hits: Vec<Hit>, limit: usize) -> Vec<Hit> {
+    let lower_is_better = lower_is_better(cfg);
+    let mut best: HashMap<(String, usize), Hit> = HashMap::new();
+    let mut order: Vec<(String, usize)> = Vec::new();
+    for hit in hits {
+        let payload = hit.payload.as_ref();
+        let key = (
+            payload.and_then(|p| p.path.clone()).unwrap_or_default(),
+            payload.and_then(|p| p.index).unwrap_or(usize::MAX),
+        );
+        match best.get_mut(&key) {
+            Some(existing) if lower_is_better && existing.score <= hit.score => {}
+            Some(existing) if !lower_is_better && existing.score >= hit.score => {}
+            Some(existing) => *existing = hit,
+            None => {
+                order.push(key.clone());
+                best.insert(key, hit);
+            }
+        }
+    }
+    let mut merged: Vec<Hit> = order.into_iter().filter_map(|k| best.remove(&k)).collect();
+    // Every query's hits were reranked against the original question, so
+    // their reranker scores compare across queries.
+    if merged.iter().any(|h| h.rerank_score.is_some()) {
+        sort_by_rerank_score(&mut merged);
+    } else {
+        sort_by_score(cfg, &mut merged);
+    }
+    merged.truncate(limit);
+    merged
+}
+
+fn single_user_message(content: String) -> Vec<Message> {
+    vec![Message {
+        role: "user".to_string(),
+        content,
+    }]
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use crate::config::Config;
+
+/// A file that stored fewer chunks than it produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuotaCap {
+    pub path: String,
+    pub kept: usize,
+    pub wanted: usize,
+    pub reason: &'static str,
+}
+
+/// Running chunk counts for one indexing run, checked against
+/// `RAG_MAX_CHUNKS_PER_FILE`, `RAG_MAX_CHUNKS_PER_DIR`, and
+/// `RAG_MAX_CHUNKS_TOTAL`. Files are charged in the order they are indexed.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkQuota {
+    per_file: Option<usize>,
+    per_dir: Option<usize>,
+    total: Option<usize>,
+    used: usize,
+    dirs: HashMap<String, usize>,
+}
+
+impl ChunkQuota {
+    pub fn new(cfg: &Config) -> Self {
+        Self::with_limits(cfg.max_chunks_per_file, cfg.max_chunks_per_dir, cfg.max_chunks_total)
+    }
+
+    pub fn with_limits(per_file: Option<usize>, per_dir: Option<usize>, total: Option<usize>) -> Self {
+        Self {
+            per_file,
+            per_dir,
+            total,
+            ..Self::default()
+        }
+    }
+
+    /// How many of the `wanted` chunks of `path` may be stored, charging them
+    /// against the quotas, and the cap that applied when it is fewer.
+    pub fn allow(&mut self, path: &str, wanted: usize) -> (usize, Option<QuotaCap>) {
+        let dir = parent_dir(path);
+        let dir_used = self.dirs.get(&dir).copied().unwrap_or(0);
+        let limits = [
+            (self.per_file, "per-file quota"),
+            (self.per_dir.map(|n| n.saturating_sub(dir_used)), "per-directory quota"),
+            (self.total.map(|n| n.saturating_sub(self.used)), "total quota"),
+        ];
+        let mut kept = wanted;
+        let mut reason = None;
+        for (limit, name) in limits {
+            if let Some(limit) = limit.filter(|&l| l < kept) {
+                kept = limit;
+                reason = Some(name);
+            }
+        }
+        self.used += kept;
+        *self.dirs.entry(dir).or_insert(0) += kept;
+        let cap = reason.map(|reason| QuotaCap {
+            path: path.to_string(),
+            kept,
+            wanted,
+            reason,
+        });
+        (kept, cap)
+    }
+}
+
+// Directory part of a path or URL; quotas group files by their direct parent.
+fn parent_dir(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    match path.rsplit_once('/') {
+        Some((dir, _)) => dir.to_string(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// One recorded model response. `kind` is `chat` for plain answers and
+/// `json` for controller decisions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayEntry {
+    pub kind: String,
+    pub response: String,
+}
+
+fn cursors() -> &'static Mutex<HashMap<String, usize>> {
+    static CURSORS: OnceLock<Mutex<HashMap<String, usize>>> = OnceLock::new();
+    CURSORS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn load_fixture(path: &str) -> Result<Vec<ReplayEntry>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read replay fixture {}: {}", path, e))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = serde_json::from_str::<serde_json::Value>(line)
+            .map_err(|e| format!("Invalid replay entry at {}:{}: {}", path, i + 1, e))?;
+        // Agent traces mix model responses with other events; only the
+        // responses are replayed.
+        if let Some(kind) = value.get("kind").and_then(|k| k.as_str()) {
+            if !kind.eq_ignore_ascii_case("chat") && !kind.eq_ignore_ascii_case("json") {
+                continue;
+            }
+        }
+        let entry = serde_json::from_value::<ReplayEntry>(value)
+            .map_err(|e| format!("Invalid replay entry at {}:{}: {}", path, i + 1, e))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Serve the next canned response from the fixture. Responses are consumed in
+/// order, and a kind mismatch is reported so diverging flows are easy to spot.
+pub fn next_response(path: &str, kind: &str) -> Result<String, String> {
+    if path.trim().is_empty() {
+        return Err("Replay provider requires RAG_REPLAY_FILE".to_string());
+    }
+    let entries = load_fixture(path)?;
+    let mut cursors = cursors().lock().map_err(|_| "Replay cursor lock poisoned".to_string())?;
+    let cursor = cursors.entry(path.to_string()).or_insert(0);
+    let entry = entries
+        .get(*cursor)
+        .ok_or_else(|| format!("Replay fixture {} exhausted after {} responses", path, cursor))?;
+    if !entry.kind.eq_ignore_ascii_case(kind) {
+        return Err(format!(
+            "Replay fixture {} out of sync at entry {}: expected {}, found {}",
+            path,
+            *cursor + 1,
+            kind,
+            entry.kind
+        ));
+    }
+    *cursor += 1;
+    Ok(entry.response.clone())
+}
+
+pub fn reset_replay(path: &str) {
+    if let Ok(mut cursors) = cursors().lock() {
+        cursors.remove(path);
+    }
+}
+
+/// Append a live response to the transcript so it can be replayed later.
+pub fn record_response(path: &str, kind: &str, response: &str) -> Result<(), String> {
+    let entry = ReplayEntry {
+        kind: kind.to_string(),
+        response: response.to_string(),
+    };
+    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open transcript {}: {}", path, e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write transcript {}: {}", path, e))
+}
@@ -0,0 +1,202 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::build_prompt::Message;
+use crate::config::Config;
+use crate::generate::generate_json_async;
+use crate::retrieve_chunks::Hit;
+
+pub type RerankFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<f32>, String>> + Send + 'a>>;
+
+/// Rescores retrieved passages against the query. Implementations return one
+/// score per document, in input order; higher is more relevant.
+pub trait Reranker: Send + Sync {
+    fn name(&self) -> &str;
+    fn score<'a>(&'a self, query: &'a str, documents: &'a [String]) -> RerankFuture<'a>;
+}
+
+/// Asks the chat model to grade each passage from 0 to 10.
+pub struct LlmReranker {
+    cfg: Config,
+}
+
+impl LlmReranker {
+    pub fn new(cfg: &Config) -> Self {
+        Self { cfg: cfg.clone() }
+    }
+}
+
+#[derive(Deserialize)]
+struct LlmScores {
+    scores: Vec<f32>,
+}
+
+impl Reranker for LlmReranker {
+    fn name(&self) -> &str {
+        "llm"
+    }
+
+    fn score<'a>(&'a self, query: &'a str, documents: &'a [String]) -> RerankFuture<'a> {
+        Box::pin(async move {
+            let passages = documents
+                .iter()
+                .enumerate()
+                .map(|(i, d)| format!("[{}]\n{}", i + 1, d))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let messages = vec![Message {
+                role: "user".to_string(),
+                content: format!(
+                    "Rate how relevant each passage is to the question on a scale from 0 (irrelevant) to 10 (directly answers it).\nReturn JSON: {{\"scores\": [..]}} with exactly {} numbers in passage order.\n\nQuestion: {}\n\n{}",
+                    documents.len(),
+                    query,
+                    passages
+                ),
+            }];
+            let raw = generate_json_async(&self.cfg, &messages).await?;
+            let parsed = serde_json::from_str::<LlmScores>(&raw)
+                .map_err(|e| format!("Reranker returned invalid JSON: {} | {}", e, raw))?;
+            if parsed.scores.len() != documents.len() {
+                return Err(format!(
+                    "Reranker returned {} scores for {} passages",
+                    parsed.scores.len(),
+                    documents.len()
+                ));
+            }
+            Ok(parsed.scores)
+        })
+    }
+}
+
+/// Local cross-encoder (bge-reranker via fastembed/ONNX Runtime). The model
+/// is downloaded on first use and kept loaded for the process.
+#[cfg(feature = "local-rerank")]
+pub struct CrossEncoderReranker {
+    model: std::sync::Arc<std::sync::Mutex<fastembed::TextRerank>>,
+}
+
+#[cfg(feature = "local-rerank")]
+impl CrossEncoderReranker {
+    pub fn new(model_name: &str) -> Result<Self, String> {
+        use fastembed::{RerankInitOptions, RerankerModel, TextRerank};
+        let model = match model_name.trim().to_ascii_lowercase().as_str() {
+            "" | "bge-reranker-base" => RerankerModel::BGERerankerBase,
+            "bge-reranker-v2-m3" => RerankerModel::BGERerankerV2M3,
+            "jina-reranker-v1-turbo-en" => RerankerModel::JINARerankerV1TurboEn,
+            "jina-reranker-v2-base-multilingual" => RerankerModel::JINARerankerV2BaseMultiligual,
+            other => return Err(format!("Unknown local reranker model: {}", other)),
+        };
+        let model = TextRerank::try_new(RerankInitOptions::new(model))
+            .map_err(|e| format!("Failed to load reranker model: {}", e))?;
+        Ok(Self {
+            model: std::sync::Arc::new(std::sync::Mutex::new(model)),
+        })
+    }
+}
+
+#[cfg(feature = "local-rerank")]
+impl Reranker for CrossEncoderReranker {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn score<'a>(&'a self, query: &'a str, documents: &'a [String]) -> RerankFuture<'a> {
+        // ONNX inference is CPU-bound; run it on the blocking pool so it
+        // does not stall the runtime's workers.
+        let model = self.model.clone();
+        let query = query.to_string();
+        let documents = documents.to_vec();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                let docs: Vec<&str> = documents.iter().map(|d| d.as_str()).collect();
+                let model = model.lock().map_err(|_| "Reranker model lock poisoned".to_string())?;
+                let results = model
+                    .rerank(query.as_str(), docs, false, None)
+                    .map_err(|e| format!("Local rerank failed: {}", e))?;
+                let mut scores = vec![f32::MIN; documents.len()];
+                for r in results {
+                    if let Some(slot) = scores.get_mut(r.index) {
+                        *slot = r.score;
+                    }
+                }
+                Ok(scores)
+            })
+            .await
+            .map_err(|e| format!("Local rerank task failed: {}", e))?
+        })
+    }
+}
+
+/// The reranker selected by `RAG_RERANKER` (`llm` or `local`), if any.
+pub fn reranker_for(cfg: &Config) -> Result<Option<std::sync::Arc<dyn Reranker>>, String> {
+    match cfg.reranker.trim().to_ascii_lowercase().as_str() {
+        "" | "off" | "none" => Ok(None),
+        "llm" => Ok(Some(std::sync::Arc::new(LlmReranker::new(cfg)))),
+        "local" => local_reranker(cfg).map(Some),
+        other => Err(format!("Unknown RAG_RERANKER: {}", other)),
+    }
+}
+
+#[cfg(feature = "local-rerank")]
+fn local_reranker(cfg: &Config) -> Result<std::sync::Arc<dyn Reranker>, String> {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex, OnceLock};
+    // Loading the ONNX model takes seconds; keep one per model name.
+    static MODELS: OnceLock<Mutex<HashMap<String, Arc<dyn Reranker>>>> = OnceLock::new();
+    let models = MODELS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut models = models.lock().map_err(|_| "Reranker cache lock poisoned".to_string())?;
+    if let Some(model) = models.get(&cfg.rerank_model) {
+        return Ok(model.clone());
+    }
+    let model: Arc<dyn Reranker> = Arc::new(CrossEncoderReranker::new(&cfg.rerank_model)?);
+    models.insert(cfg.rerank_model.clone(), model.clone());
+    Ok(model)
+}
+
+#[cfg(not(feature = "local-rerank"))]
+fn local_reranker(_cfg: &Config) -> Result<std::sync::Arc<dyn Reranker>, String> {
+    Err("RAG_RERANKER=local needs aicli built with the `local-rerank` feature".to_string())
+}
+
+/// Reorder `hits` by the reranker's scores, best first. The scores go in
+/// `rerank_score`; `score` keeps the vector score that `min_score` and
+/// merging compare. Returns the time the reranker took. On error `hits` is
+/// left in its vector order.
+pub async fn rerank_hits(reranker: &dyn Reranker, query: &str, hits: &mut [Hit]) -> Result<Duration, String> {
+    if hits.is_empty() {
+        return Ok(Duration::ZERO);
+    }
+    let started = Instant::now();
+    let documents: Vec<String> = hits
+        .iter()
+        .map(|h| h.payload.as_ref().and_then(|p| p.chunk.clone()).unwrap_or_default())
+        .collect();
+    let scores = reranker.score(query, &documents).await?;
+    let elapsed = started.elapsed();
+    if scores.len() != hits.len() {
+        return Err(format!("Reranker returned {} scores for {} passages", scores.len(), hits.len()));
+    }
+    for (hit, score) in hits.iter_mut().zip(scores) {
+        hit.rerank_score = Some(score);
+    }
+    sort_by_rerank_score(hits);
+    Ok(elapsed)
+}
+
+/// Reranked hits first, by reranker score; the rest keep their order.
+pub(crate) fn sort_by_rerank_score(hits: &mut [Hit]) {
+    hits.sort_by(|a, b| match (a.rerank_score, b.rerank_score) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// Sum of two optional rerank times; `None` when neither retrieval reranked.
+pub(crate) fn add_rerank_time(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    a.into_iter().chain(b).reduce(|a, b| a + b)
+}
@@ -0,0 +1,661 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::agent::{AgentMode, AgentOptions};
+use crate::answer::AnswerResult;
+use crate::build_prompt::Message;
+use crate::config::Config;
+use crate::stats::collection_stats;
+use crate::store_access::check_writable;
+use crate::timestamps::{now_secs, rfc3339};
+
+// Largest request body accepted; questions and index requests are small.
+const MAX_BODY_BYTES: usize = 1 << 20;
+
+// Connections waiting for a free worker, per worker, before new ones get a
+// 503.
+const QUEUE_PER_WORKER: usize = 4;
+
+// The models `/v1/models` lists and the agent mode each runs; `aicli` uses
+// `RAG_AGENT_MODE`.
+const CHAT_MODELS: [(&str, Option<AgentMode>); 4] = [
+    ("aicli", None),
+    ("aicli-rag-only", Some(AgentMode::RagOnly)),
+    ("aicli-hybrid", Some(AgentMode::Hybrid)),
+    ("aicli-mcp-only", Some(AgentMode::McpOnly)),
+];
+
+/// One HTTP request as `serve_rest` reads it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// The path without its query string.
+    pub path: String,
+    /// Header names in lowercase, with their values.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// The value of the header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Serve the library over HTTP on `addr` (e.g. `127.0.0.1:8080`) with
+/// `AICLI_SERVE_WORKERS` worker threads, until the process is stopped:
+///
+/// - `POST /index` `{"source": "docs/"}` indexes a directory or URL
+///   (`RAG_SOURCE_DIR` when omitted);
+/// - `POST /query` `{"question": ..., "history": [...], "stream": true}`
+///   answers from the index alone;
+/// - `POST /agent` takes the same body plus an optional `"mode"` and runs
+///   the agent, with MCP tools only with `AICLI_SERVE_MCP_TOOLS` and local
+///   tools only with `AICLI_SERVE_LOCAL_TOOLS`;
+/// - `GET /stats` describes the collection;
+/// - `POST /v1/chat/completions` and `GET /v1/models` speak the OpenAI chat
+///   API, so chat UIs can use aicli as a model.
+///
+/// With `"stream": true` the answer comes as server-sent events: `token`
+/// (or `step` for `/agent`) events, then one `answer` event.
+///
+/// Listening on anything but a loopback address needs `AICLI_SERVE_TOKEN`.
+pub fn serve_rest(cfg: &Config, addr: &str) -> Result<(), String> {
+    if cfg.serve_token.is_empty() && !is_loopback_addr(addr) {
+        return Err(format!(
+            "Set AICLI_SERVE_TOKEN to serve on {}: other machines can reach it, and requests need a token.",
+            addr
+        ));
+    }
+    let listener = TcpListener::bind(addr).map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    let (queue, connections) = mpsc::sync_channel::<TcpStream>(cfg.serve_workers * QUEUE_PER_WORKER);
+    let connections = Arc::new(Mutex::new(connections));
+    for _ in 0..cfg.serve_workers {
+        let cfg = cfg.clone();
+        let connections = Arc::clone(&connections);
+        std::thread::spawn(move || loop {
+            // The lock is held only while waiting, not while serving.
+            let next = match connections.lock() {
+                Ok(connections) => connections.recv(),
+                Err(_) => return,
+            };
+            match next {
+                Ok(stream) => handle_connection(&cfg, stream),
+                Err(_) => return,
+            }
+        });
+    }
+    let timeout = Some(Duration::from_secs(cfg.serve_timeout_secs.max(1)));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_read_timeout(timeout);
+        let _ = stream.set_write_timeout(timeout);
+        if let Err(TrySendError::Full(mut stream)) = queue.try_send(stream) {
+            let busy = json!({ "error": "The server is busy; try again shortly" });
+            let _ = write_json(cfg, &mut stream, 503, &busy);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `addr` (`host:port`, or a bare host as in a `Host` header) only
+/// accepts connections from this machine.
+pub fn is_loopback_addr(addr: &str) -> bool {
+    let host = match addr.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(bracketed),
+        None => addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(addr),
+    };
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Why a browser may not have sent `request`, if it looks cross-site: an
+/// `Origin` other than `AICLI_SERVE_CORS_ORIGIN`, or, without
+/// `AICLI_SERVE_TOKEN`, a `Host` that is not loopback (DNS rebinding).
+pub fn cross_site_reason(cfg: &Config, request: &HttpRequest) -> Option<String> {
+    if let Some(origin) = request.header("origin") {
+        let allowed = cfg.serve_cors_origin == "*" || origin.eq_ignore_ascii_case(&cfg.serve_cors_origin);
+        if !allowed {
+            return Some(format!("Requests from {} are not allowed (AICLI_SERVE_CORS_ORIGIN)", origin));
+        }
+    }
+    let host = request.header("host").filter(|host| cfg.serve_token.is_empty() && !is_loopback_addr(host))?;
+    Some(format!("Host {} is not this machine; set AICLI_SERVE_TOKEN to serve other names", host))
+}
+
+/// Whether the REST `/index` endpoint may index `source`: a path inside one
+/// of `AICLI_SERVE_INDEX_ROOTS` (`RAG_SOURCE_DIR` when none are set), or a
+/// URL under one of its `http(s)://` prefixes.
+pub fn index_source_allowed(cfg: &Config, source: &str) -> bool {
+    let roots: Vec<&str> = if cfg.serve_index_roots.is_empty() {
+        vec![cfg.source_dir.as_str()]
+    } else {
+        cfg.serve_index_roots.iter().map(String::as_str).collect()
+    };
+    let is_url = |text: &str| text.starts_with("http://") || text.starts_with("https://");
+    if is_url(source) {
+        return roots.iter().filter(|root| is_url(root)).any(|root| {
+            let root = root.trim_end_matches('/');
+            source == root || source.starts_with(&format!("{}/", root))
+        });
+    }
+    let Ok(path) = Path::new(source).canonicalize() else {
+        return false;
+    };
+    roots
+        .iter()
+        .filter(|root| !is_url(root))
+        .filter_map(|root| Path::new(root).canonicalize().ok())
+        .any(|root| path.starts_with(root))
+}
+
+fn handle_connection(cfg: &Config, mut stream: TcpStream) {
+    let request = match stream.try_clone() {
+        Ok(read_half) => read_request(&mut BufReader::new(read_half)),
+        Err(e) => Err(e.to_string()),
+    };
+    // The connection is closed either way, so a failed write has no one to
+    // report to.
+    let _ = match request {
+        Ok(request) => handle_request(cfg, &request, &mut stream),
+        Err(e) => write_json(cfg, &mut stream, 400, &json!({ "error": e })),
+    };
+}
+
+/// Read one request: the request line, headers, and a body of at most
+/// 1 MiB, sent with `Content-Length` or `Transfer-Encoding: chunked`.
+pub fn read_request(reader: &mut impl BufRead) -> Result<HttpRequest, String> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(format!("Malformed request line: {}", line.trim()));
+    };
+    let path = target.split(['?', '#']).next().unwrap_or(target).to_string();
+    let method = method.to_ascii_uppercase();
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        let read = reader
+            .read_line(&mut header)
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        if read == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let mut request = HttpRequest { method, path, headers, body: Vec::new() };
+    let chunked = request
+        .header("transfer-encoding")
+        .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"));
+    request.body = if chunked {
+        read_chunked_body(reader)?
+    } else {
+        let length = match request.header("content-length") {
+            Some(value) => value.parse().map_err(|_| format!("Invalid Content-Length: {}", value))?,
+            None => 0usize,
+        };
+        if length > MAX_BODY_BYTES {
+            return Err(format!("Request body over {} bytes", MAX_BODY_BYTES));
+        }
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| format!("Failed to read request body: {}", e))?;
+        body
+    };
+    Ok(request)
+}
+
+// A `Transfer-Encoding: chunked` body, refused once it passes the size cap.
+fn read_chunked_body(reader: &mut impl BufRead) -> Result<Vec<u8>, String> {
+    let read_err = |e: std::io::Error| format!("Failed to read request body: {}", e);
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(read_err)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| format!("Invalid chunk size: {}", size))?;
+        if size == 0 {
+            // Skip any trailers up to the blank line that ends the body.
+            loop {
+                let mut trailer = String::new();
+                if reader.read_line(&mut trailer).map_err(read_err)? == 0 || trailer.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        if body.len() + size > MAX_BODY_BYTES {
+            return Err(format!("Request body over {} bytes", MAX_BODY_BYTES));
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).map_err(read_err)?;
+        let mut crlf = String::new();
+        reader.read_line(&mut crlf).map_err(read_err)?;
+    }
+}
+
+/// Route `request` and write the whole response to `out`. Requests that look
+/// cross-site are refused, and POST bodies must be sent as
+/// `application/json`, which a page cannot do without a CORS preflight. With
+/// `AICLI_SERVE_TOKEN`, everything but a CORS preflight needs
+/// `Authorization: Bearer <token>`.
+pub fn handle_request(cfg: &Config, request: &HttpRequest, out: &mut impl Write) -> std::io::Result<()> {
+    if request.method == "OPTIONS" {
+        return write_preflight(cfg, out);
+    }
+    if let Some(reason) = cross_site_reason(cfg, request) {
+        return write_json(cfg, out, 403, &json!({ "error": reason }));
+    }
+    let json_body = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"));
+    if request.method == "POST" && !json_body {
+        let error = "POST bodies must be sent as Content-Type: application/json";
+        return write_json(cfg, out, 415, &json!({ "error": error }));
+    }
+    if !cfg.serve_token.is_empty() {
+        let token = request.header("authorization").and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| tokens_match(token.trim(), &cfg.serve_token)) {
+            return write_json(cfg, out, 401, &json!({ "error": "A valid bearer token is required" }));
+        }
+    }
+    let body = if request.body.is_empty() {
+        Ok(json!({}))
+    } else {
+        serde_json::from_slice::<Value>(&request.body).map_err(|e| format!("Invalid JSON body: {}", e))
+    };
+    let body = match body {
+        Ok(body) if body.is_object() => body,
+        Ok(_) => return write_json(cfg, out, 400, &json!({ "error": "The body must be a JSON object" })),
+        Err(e) => return write_json(cfg, out, 400, &json!({ "error": e })),
+    };
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/stats") => match collection_stats(cfg) {
+            Ok(stats) => write_json(
+                cfg,
+                out,
+                200,
+                &json!({
+                    "collection": stats.collection,
+                    "points": stats.points,
+                    "files": stats.files,
+                    "vector_size": stats.vector_size,
+                    "distance": stats.distance,
+                    "disk_bytes": stats.disk_bytes,
+                }),
+            ),
+            Err(e) => write_json(cfg, out, 500, &json!({ "error": e })),
+        },
+        ("POST", "/index") => index(cfg, &body, out),
+        ("POST", "/query") => answer(cfg, &body, false, out),
+        ("POST", "/agent") => answer(cfg, &body, true, out),
+        ("GET", "/v1/models") => {
+            let created = now_secs();
+            let models: Vec<Value> = CHAT_MODELS
+                .iter()
+                .map(|(id, _)| json!({ "id": id, "object": "model", "created": created, "owned_by": "aicli" }))
+                .collect();
+            write_json(cfg, out, 200, &json!({ "object": "list", "data": models }))
+        }
+        ("POST", "/v1/chat/completions") => chat_completion(cfg, &body, out),
+        (_, "/stats" | "/index" | "/query" | "/agent" | "/v1/models" | "/v1/chat/completions") => {
+            let error = format!("{} is not allowed on {}", request.method, request.path);
+            write_json(cfg, out, 405, &json!({ "error": error }))
+        }
+        _ => write_json(cfg, out, 404, &json!({ "error": format!("No route for {}", request.path) })),
+    }
+}
+
+fn index(cfg: &Config, body: &Value, out: &mut impl Write) -> std::io::Result<()> {
+    if cfg.safe_mode {
+        return write_json(cfg, out, 403, &json!({ "error": "Safe mode is on: indexing is disabled." }));
+    }
+    if let Err(err) = check_writable(cfg, "indexing") {
+        return write_json(cfg, out, 403, &json!({ "error": err }));
+    }
+    let source = body.get("source").and_then(Value::as_str).filter(|s| !s.trim().is_empty());
+    if let Some(source) = source.filter(|source| !index_source_allowed(cfg, source)) {
+        let error = format!("{} is outside the directories aicli serve may index (AICLI_SERVE_INDEX_ROOTS)", source);
+        return write_json(cfg, out, 403, &json!({ "error": error }));
+    }
+    match crate::index_corpus(cfg, source) {
+        Ok(stats) => {
+            let skipped: Vec<Value> = stats
+                .skipped
+                .iter()
+                .map(|(path, reason)| json!({ "path": path, "reason": reason }))
+                .collect();
+            let summary = stats.summary();
+            write_json(
+                cfg,
+                out,
+                200,
+                &json!({
+                    "collection": cfg.collection,
+                    "indexed": stats.indexed,
+                    "skipped": skipped,
+                    "summary": summary,
+                }),
+            )
+        }
+        Err(e) => write_json(cfg, out, 500, &json!({ "error": e })),
+    }
+}
+
+/// The configuration answers for REST clients run with: without
+/// `AICLI_SERVE_LOCAL_TOOLS`, the agent gets no local tools, so a client
+/// cannot read files or run commands on this machine, and without
+/// `AICLI_SERVE_MCP_TOOLS` no MCP servers, so it cannot call their tools.
+pub fn rest_answer_config(cfg: &Config) -> Config {
+    let mut cfg = cfg.clone();
+    if !cfg.serve_local_tools {
+        cfg.local_tools.clear();
+    }
+    if !cfg.serve_mcp_tools {
+        cfg.mcp_config.clear();
+        cfg.mcp_url.clear();
+        cfg.mcp_command.clear();
+    }
+    cfg
+}
+
+// `/query` answers from the index alone; `/agent` runs the agent in the
+// requested mode (`RAG_AGENT_MODE` by default).
+fn answer(cfg: &Config, body: &Value, agent: bool, out: &mut impl Write) -> std::io::Result<()> {
+    let cfg = &rest_answer_config(cfg);
+    let question = body.get("question").and_then(Value::as_str).unwrap_or_default().trim().to_string();
+    if question.is_empty() {
+        return write_json(cfg, out, 400, &json!({ "error": "\"question\" is required" }));
+    }
+    let history: Vec<Message> = match body.get("history") {
+        Some(history) => match serde_json::from_value(history.clone()) {
+            Ok(history) => history,
+            Err(e) => return write_json(cfg, out, 400, &json!({ "error": format!("Invalid history: {}", e) })),
+        },
+        None => Vec::new(),
+    };
+    let mut options = AgentOptions::from_config(cfg);
+    options.mode = match body.get("mode").and_then(Value::as_str) {
+        _ if !agent => AgentMode::RagOnly,
+        Some(mode) => match AgentMode::parse(mode) {
+            Some(mode) => mode,
+            None => return write_json(cfg, out, 400, &json!({ "error": format!("Unknown mode: {}", mode) })),
+        },
+        None => options.mode,
+    };
+    let stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+    if !stream {
+        return match crate::answer_query_detailed_with_events(cfg, &history, &question, &options, None) {
+            Ok(result) => write_json(cfg, out, 200, &answer_json(&result)),
+            Err(e) => write_json(cfg, out, 500, &json!({ "error": e })),
+        };
+    }
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n{}Connection: close\r\n\r\n",
+        cors_headers(cfg)
+    )?;
+    let result = if agent {
+        agent_events(cfg, &history, &question, &options, out)
+    } else {
+        // Stop generating once the client has gone away.
+        let mut on_token = |piece: &str| write_event(out, "token", &json!({ "text": piece })).is_ok();
+        crate::answer_query_streaming_with_events(cfg, &history, &question, &options, None, &mut on_token)
+    };
+    match result {
+        Ok(result) => write_event(out, "answer", &answer_json(&result)),
+        Err(e) => write_event(out, "error", &json!({ "error": e })),
+    }
+}
+
+// Run the agent on another thread and send each step as it happens.
+fn agent_events(
+    cfg: &Config,
+    history: &[Message],
+    question: &str,
+    options: &AgentOptions,
+    out: &mut impl Write,
+) -> Result<AnswerResult, String> {
+    let (events, steps) = mpsc::channel();
+    std::thread::scope(|scope| {
+        let worker =
+            scope.spawn(|| crate::answer_query_detailed_with_events(cfg, history, question, options, Some(events)));
+        // Ends when the agent finishes and drops its sender.
+        for step in steps {
+            let _ = write_event(out, "step", &json!({ "text": step.to_string() }));
+        }
+        worker.join().unwrap_or_else(|_| Err("The agent panicked".to_string()))
+    })
+}
+
+// Errors in the OpenAI shape, which chat clients know how to show.
+fn openai_error(cfg: &Config, out: &mut impl Write, status: u16, message: &str) -> std::io::Result<()> {
+    let kind = if status < 500 { "invalid_request_error" } else { "server_error" };
+    write_json(cfg, out, status, &json!({ "error": { "message": message, "type": kind } }))
+}
+
+/// The question and history in an OpenAI `messages` array: the last user
+/// message and the user and assistant turns before it. System messages are
+/// dropped, since aicli brings its own, and content given as parts keeps
+/// only its text parts.
+pub fn chat_messages(messages: &Value) -> Result<(Vec<Message>, String), String> {
+    let messages = messages.as_array().ok_or("\"messages\" must be an array")?;
+    let mut turns = Vec::new();
+    for message in messages {
+        let role = message.get("role").and_then(Value::as_str).unwrap_or_default();
+        let content = match message.get("content") {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .filter(|part| part.get("type").and_then(Value::as_str) == Some("text"))
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        };
+        if matches!(role, "user" | "assistant") && !content.trim().is_empty() {
+            turns.push(Message { role: role.to_string(), content });
+        }
+    }
+    match turns.pop() {
+        Some(last) if last.role == "user" => Ok((turns, last.content.trim().to_string())),
+        _ => Err("The last message must be a user message".to_string()),
+    }
+}
+
+// `/v1/chat/completions`: answer the last user message as `/agent` would,
+// in the mode the model name picks, with the sources after the answer.
+fn chat_completion(cfg: &Config, body: &Value, out: &mut impl Write) -> std::io::Result<()> {
+    let cfg = &rest_answer_config(cfg);
+    let model = body.get("model").and_then(Value::as_str).unwrap_or(CHAT_MODELS[0].0);
+    let Some((_, mode)) = CHAT_MODELS.iter().find(|(id, _)| *id == model) else {
+        return openai_error(cfg, out, 404, &format!("The model '{}' does not exist", model));
+    };
+    let (history, question) = match chat_messages(body.get("messages").unwrap_or(&Value::Null)) {
+        Ok(parsed) => parsed,
+        Err(e) => return openai_error(cfg, out, 400, &e),
+    };
+    let mut options = AgentOptions::from_config(cfg);
+    if let Some(mode) = mode {
+        options.mode = *mode;
+    }
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let id = format!("chatcmpl-{:x}", nanos);
+    let created = now_secs();
+    let stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+    if !stream {
+        let result = match crate::answer_query_detailed_with_events(cfg, &history, &question, &options, None) {
+            Ok(result) => result,
+            Err(e) => return openai_error(cfg, out, 500, &e),
+        };
+        return write_json(
+            cfg,
+            out,
+            200,
+            &json!({
+                "id": id,
+                "object": "chat.completion",
+                "created": created,
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": chat_content(&result) },
+                    "finish_reason": "stop",
+                }],
+                "usage": {
+                    "prompt_tokens": result.usage.prompt_tokens,
+                    "completion_tokens": result.usage.completion_tokens,
+                    "total_tokens": result.usage.total_tokens(),
+                },
+            }),
+        );
+    }
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n{}Connection: close\r\n\r\n",
+        cors_headers(cfg)
+    )?;
+    let chunk = |out: &mut dyn Write, delta: Value, finish: Option<&str>| {
+        let data = json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }],
+        });
+        write!(out, "data: {}\n\n", data).and_then(|_| out.flush())
+    };
+    chunk(out, json!({ "role": "assistant", "content": "" }), None)?;
+    // Only answers from the index alone stream token by token; the agent's
+    // answer is sent whole once it is done.
+    let result = if options.mode == AgentMode::RagOnly {
+        let mut on_token = |piece: &str| chunk(out, json!({ "content": piece }), None).is_ok();
+        crate::answer_query_streaming_with_events(cfg, &history, &question, &options, None, &mut on_token)
+            .map(|result| (result, false))
+    } else {
+        crate::answer_query_detailed_with_events(cfg, &history, &question, &options, None).map(|result| (result, true))
+    };
+    match result {
+        Ok((result, whole)) => {
+            let content = chat_content(&result);
+            let rest = if whole { content.as_str() } else { &content[result.answer.len().min(content.len())..] };
+            if !rest.is_empty() {
+                chunk(out, json!({ "content": rest }), None)?;
+            }
+            chunk(out, json!({}), Some("stop"))?;
+        }
+        Err(e) => write!(out, "data: {}\n\n", json!({ "error": { "message": e, "type": "server_error" } }))?,
+    }
+    out.write_all(b"data: [DONE]\n\n")?;
+    out.flush()
+}
+
+// The answer with its sources listed after it, as a chat UI shows them.
+fn chat_content(result: &AnswerResult) -> String {
+    let sources = result.sources_text();
+    if sources.is_empty() {
+        result.answer.clone()
+    } else {
+        format!("{}\n\nSources:\n{}", result.answer, sources)
+    }
+}
+
+/// The JSON body for an answer: its text, sources, model, timing, and when
+/// it finished as RFC3339.
+pub fn answer_json(result: &AnswerResult) -> Value {
+    let sources: Vec<Value> = result
+        .hits
+        .iter()
+        .map(|hit| {
+            json!({
+                "path": hit.path,
+                "url": hit.url,
+                "chunk_index": hit.chunk_index,
+                "score": hit.score,
+            })
+        })
+        .collect();
+    json!({
+        "answer": result.answer,
+        "sources": sources,
+        "model": result.model,
+        "elapsed_ms": result.elapsed.as_millis() as u64,
+        "answered_at": rfc3339(now_secs()),
+    })
+}
+
+fn write_json(cfg: &Config, out: &mut impl Write, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        cors_headers(cfg),
+        body
+    )?;
+    out.flush()
+}
+
+// The answer to a CORS preflight, which browsers send without credentials.
+fn write_preflight(cfg: &Config, out: &mut impl Write) -> std::io::Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 204 No Content\r\nAllow: GET, POST, OPTIONS\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        cors_headers(cfg)
+    )?;
+    out.flush()
+}
+
+// Header lines letting `AICLI_SERVE_CORS_ORIGIN` call the server from a
+// browser; none when it is unset.
+fn cors_headers(cfg: &Config) -> String {
+    if cfg.serve_cors_origin.is_empty() {
+        return String::new();
+    }
+    format!(
+        "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type\r\nVary: Origin\r\n",
+        cfg.serve_cors_origin
+    )
+}
+
+// Compare without stopping at the first difference, so the time taken does
+// not reveal how much of a guessed token was right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn write_event(out: &mut impl Write, event: &str, data: &Value) -> std::io::Result<()> {
+    write!(out, "event: {}\ndata: {}\n\n", event, data)?;
+    out.flush()
+}
@@ -0,0 +1,46 @@
+use crate::config::Config;
+use crate::query_expansion::retrieve_for_query;
+use crate::retrieve_chunks::Hit;
+
+/// Per-call overrides for `retrieve`; unset fields fall back to `Config`.
+#[derive(Clone, Debug, Default)]
+pub struct RetrieveOptions {
+    pub top_k: Option<usize>,
+    pub min_score: Option<f32>,
+    pub focus_paths: Option<Vec<String>>,
+}
+
+/// Ranked hits for `question` without any generation step.
+pub fn retrieve(cfg: &Config, question: &str, options: &RetrieveOptions) -> Result<Vec<Hit>, String> {
+    let mut cfg = cfg.clone();
+    if let Some(top_k) = options.top_k {
+        cfg.top_k = top_k;
+    }
+    if options.min_score.is_some() {
+        cfg.min_score = options.min_score;
+    }
+    if let Some(focus) = &options.focus_paths {
+        cfg.focus_paths = focus.clone();
+    }
+    retrieve_for_query(&cfg, question)
+}
+
+/// One line per hit: rank, score, path, and chunk index.
+pub fn format_hit_list(hits: &[Hit]) -> String {
+    if hits.is_empty() {
+        return "(no hits)".to_string();
+    }
+    hits.iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            let payload = hit.payload.as_ref();
+            let path = payload.and_then(|p| p.path.as_deref()).unwrap_or("unknown");
+            let index = payload
+                .and_then(|p| p.index)
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            format!("{:>2}. {:.3}  {} (chunk {})", i + 1, hit.score, path, index)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,18 +1,60 @@
+use std::time::Duration;
+
 use serde::Deserialize;
 
+use crate::attachments::search_attachments_async;
 use crate::config::Config;
-use crate::http::post_json;
+use crate::distance::{check_dimension_async, check_distance_async};
+use crate::doc_type::{boost_doc_types, doc_types_for_query};
+use crate::http::{post_json_async, HttpOptions};
+use crate::mmr::mmr_select;
+use crate::rerank::{add_rerank_time, rerank_hits, reranker_for};
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct Hit {
+    #[serde(default)]
+    pub score: f32,
     pub payload: Option<Payload>,
+    #[serde(default)]
+    pub vector: Option<Vec<f32>>,
+    /// The reranker's score, higher is better, once the hit was reranked.
+    #[serde(skip)]
+    pub rerank_score: Option<f32>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct Payload {
     pub path: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub anchor: Option<String>,
     pub index: Option<usize>,
     pub chunk: Option<String>,
+    #[serde(default)]
+    pub before: Option<String>,
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    pub cell_index: Option<usize>,
+    #[serde(default)]
+    pub cell_type: Option<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub doc_type: Option<String>,
+}
+
+impl Payload {
+    /// What a citation should show: the source URL (with `#anchor` when the
+    /// chunk sits under a known heading) for web content, else the path.
+    pub fn citation(&self) -> Option<String> {
+        match (&self.url, &self.anchor) {
+            (Some(url), Some(anchor)) => Some(format!("{}#{}", url, anchor)),
+            (Some(url), None) => Some(url.clone()),
+            _ => self.path.clone(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -30,21 +72,127 @@ struct QueryRequest<'a> {
     query: &'a [f32],
     limit: usize,
     with_payload: bool,
+    with_vector: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_threshold: Option<f32>,
 }
 
-pub fn retrieve_top(cfg: &Config, vector: &[f32]) -> Result<Vec<Hit>, String> {
+/// What one retrieval found, with the time the reranker took and why it
+/// failed, if one ran.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Retrieval {
+    pub hits: Vec<Hit>,
+    /// Set when nothing matched and the search was repeated more broadly.
+    pub broadened: Option<String>,
+    pub rerank_time: Option<Duration>,
+    pub rerank_error: Option<String>,
+}
+
+impl Retrieval {
+    /// Fold in the reranking done by a later retrieval, keeping these hits.
+    pub fn add_rerank(&mut self, other: &Retrieval) {
+        self.rerank_time = add_rerank_time(self.rerank_time, other.rerank_time);
+        if self.rerank_error.is_none() {
+            self.rerank_error = other.rerank_error.clone();
+        }
+    }
+}
+
+/// Nearest chunks to `vector`. `query` is the text the reranker, when one is
+/// configured, scores the candidates against.
+pub(crate) async fn retrieve_top_async(cfg: &Config, query: &str, vector: &[f32]) -> Result<Retrieval, String> {
     if vector.is_empty() {
-        return Ok(vec![]);
+        return Ok(Retrieval::default());
+    }
+    let reranker = reranker_for(cfg)?;
+    let doc_types = if cfg.doc_type_boost > 0.0 { doc_types_for_query(query) } else { Vec::new() };
+    // Reranking, MMR, doc-type boosts, and focus filters need a wider candidate pool to choose from.
+    let widen = reranker.is_some() || cfg.mmr_lambda.is_some() || !cfg.focus_paths.is_empty() || !doc_types.is_empty();
+    let limit = if widen { cfg.top_k * 4 } else { cfg.top_k };
+    let hits = search_points_async(cfg, vector, limit, cfg.mmr_lambda.is_some(), cfg.min_score).await?;
+    let mut hits = filter_by_paths(filter_by_score(cfg, hits, cfg.min_score), &cfg.focus_paths);
+    // Attached files are searched whatever the focus paths say.
+    let attached = filter_by_score(cfg, search_attachments_async(cfg, vector, limit).await?, cfg.min_score);
+    if !attached.is_empty() {
+        hits.extend(attached);
+        sort_by_score(cfg, &mut hits);
     }
+    let mut retrieval = Retrieval::default();
+    if let Some(reranker) = &reranker {
+        // A failed rerank is not fatal; the vector order still stands.
+        match rerank_hits(reranker.as_ref(), query, &mut hits).await {
+            Ok(elapsed) => retrieval.rerank_time = Some(elapsed),
+            Err(err) => retrieval.rerank_error = Some(err),
+        }
+    }
+    hits = boost_doc_types(hits, &doc_types, cfg.doc_type_boost, lower_is_better(cfg));
+    retrieval.hits = match cfg.mmr_lambda {
+        Some(lambda) => mmr_select(vector, hits, cfg.top_k, lambda),
+        None => hits.into_iter().take(cfg.top_k).collect(),
+    };
+    Ok(retrieval)
+}
+
+/// One Qdrant query for the `limit` nearest points to `vector`, checked
+/// against the collection's distance and dimension first.
+pub(crate) async fn search_points_async(
+    cfg: &Config,
+    vector: &[f32],
+    limit: usize,
+    with_vector: bool,
+    score_threshold: Option<f32>,
+) -> Result<Vec<Hit>, String> {
+    check_distance_async(cfg).await?;
+    check_dimension_async(cfg, vector.len()).await?;
     let url = format!("{}/collections/{}/points/query", cfg.qdrant_url, cfg.collection);
     let req = QueryRequest {
         query: vector,
-        limit: cfg.top_k,
+        limit,
         with_payload: true,
+        with_vector,
+        score_threshold,
     };
-    let res = post_json::<QueryResponse, _>(&url, &req)?;
+    let res = post_json_async::<QueryResponse, _>(&url, &req, &HttpOptions::for_qdrant(cfg)).await?;
     Ok(res
         .result
         .map(|r| r.points)
         .unwrap_or_default())
 }
+
+/// Hits whose path contains one of `paths`; all of them when it is empty.
+pub(crate) fn filter_by_paths(hits: Vec<Hit>, paths: &[String]) -> Vec<Hit> {
+    if paths.is_empty() {
+        return hits;
+    }
+    hits.into_iter()
+        .filter(|h| {
+            let path = h.payload.as_ref().and_then(|p| p.path.as_deref()).unwrap_or("");
+            paths.iter().any(|f| path.contains(f.as_str()))
+        })
+        .collect()
+}
+
+// Qdrant already applies `score_threshold`, but older servers ignore it, so
+// the threshold is enforced here as well. Distance metrics score lower-is-better.
+pub(crate) fn filter_by_score(cfg: &Config, hits: Vec<Hit>, min: Option<f32>) -> Vec<Hit> {
+    let Some(min) = min else {
+        return hits;
+    };
+    let lower_is_better = lower_is_better(cfg);
+    hits.into_iter()
+        .filter(|h| if lower_is_better { h.score <= min } else { h.score >= min })
+        .collect()
+}
+
+/// Best hits first under the collection's distance metric.
+pub(crate) fn sort_by_score(cfg: &Config, hits: &mut [Hit]) {
+    if lower_is_better(cfg) {
+        hits.sort_by(|a, b| a.score.total_cmp(&b.score));
+    } else {
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    }
+}
+
+pub(crate) fn lower_is_better(cfg: &Config) -> bool {
+    matches!(cfg.distance.to_ascii_lowercase().as_str(), "euclid" | "manhattan")
+}
@@ -0,0 +1,44 @@
+use std::future::Future;
+use std::sync::OnceLock;
+
+use tokio::runtime::{Builder, Handle, Runtime, RuntimeFlavor};
+
+// One long-lived runtime backs every blocking wrapper. The shared HTTP client
+// pools connections on the runtime that opened them, so a runtime per call
+// would leave the pool holding dead connections.
+fn runtime() -> Result<&'static Runtime, String> {
+    static RUNTIME: OnceLock<Result<Runtime, String>> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            Builder::new_multi_thread()
+                .worker_threads(2)
+                .thread_name("rag-blocking")
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to build the rag runtime: {}", e))
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+/// Drive `fut` to completion from synchronous code. Called from a task on a
+/// multi-threaded tokio runtime, the worker is handed over to other tasks
+/// with `block_in_place` while `fut` runs. A current-thread runtime cannot
+/// do that, so there the call fails; use the `_async` functions instead.
+pub(crate) fn try_block_on<F: Future>(fut: F) -> Result<F::Output, String> {
+    let runtime = runtime()?;
+    match Handle::try_current() {
+        Err(_) => Ok(runtime.block_on(fut)),
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Ok(tokio::task::block_in_place(|| runtime.block_on(fut)))
+        }
+        Ok(_) => Err(
+            "Blocking call made on a current-thread tokio runtime; use the async function instead".to_string(),
+        ),
+    }
+}
+
+/// `try_block_on` for futures that fail with a message of their own.
+pub(crate) fn block_on<T, F: Future<Output = Result<T, String>>>(fut: F) -> Result<T, String> {
+    try_block_on(fut)?
+}
@@ -4,10 +4,17 @@ use std::path::Path;
 use walkdir::WalkDir;
 
 use crate::config::Config;
+use crate::generated::{generated_reason, keep_generated, ScanStats};
 
 pub fn scan_files(cfg: &Config, source_dir: Option<&str>) -> Vec<(String, String)> {
+    scan_files_with_stats(cfg, source_dir).0
+}
+
+/// Like `scan_files`, also reporting which files were skipped as generated.
+pub fn scan_files_with_stats(cfg: &Config, source_dir: Option<&str>) -> (Vec<(String, String)>, ScanStats) {
     let base = source_dir.unwrap_or(&cfg.source_dir);
     let mut results = Vec::new();
+    let mut stats = ScanStats::default();
 
     let walker = WalkDir::new(base).into_iter().filter_entry(|e| {
         let name = e.file_name().to_string_lossy();
@@ -31,13 +38,21 @@ pub fn scan_files(cfg: &Config, source_dir: Option<&str>) -> Vec<(String, String
         if text.trim().is_empty() {
             continue;
         }
-        results.push((path.to_string_lossy().to_string(), text));
+        let path = path.to_string_lossy().to_string();
+        if cfg.skip_generated && !keep_generated(&path, &cfg.keep_generated) {
+            if let Some(reason) = generated_reason(&path, &text) {
+                stats.skipped.push((path, reason.to_string()));
+                continue;
+            }
+        }
+        results.push((path, text));
     }
 
-    results
+    stats.indexed = results.len();
+    (results, stats)
 }
 
-fn is_text_file(path: &Path, exts: &[String]) -> bool {
+pub(crate) fn is_text_file(path: &Path, exts: &[String]) -> bool {
     let lower = path.to_string_lossy().to_lowercase();
     exts.iter().any(|ext| lower.ends_with(ext))
 }
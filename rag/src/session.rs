@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::build_prompt::Message;
+use crate::config::{Config, GenerationOptions};
+use crate::generate::generate_answer_with;
+use crate::usage::Usage;
+
+/// Everything needed to reopen a conversation where it was left off.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    /// Short description shown in the session list and the TUI, generated
+    /// after the first exchange or set with `/rename`.
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub conversation: Vec<Message>,
+    #[serde(default)]
+    pub pinned: Vec<String>,
+    #[serde(default)]
+    pub focus: Vec<String>,
+    #[serde(default)]
+    pub last_context: Option<String>,
+    #[serde(default)]
+    pub last_answer: Option<String>,
+    /// Output of the last shell command run in the TUI.
+    #[serde(default)]
+    pub last_command_output: Option<String>,
+    /// Lines scrolled in the context and answer panes when it was saved.
+    #[serde(default)]
+    pub context_scroll: usize,
+    #[serde(default)]
+    pub answer_scroll: usize,
+    /// The collection in use when it was saved; reopening the session
+    /// switches back to it.
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Variables set with `/env`, applied again when the session reopens.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Agent mode label (`hybrid`, `rag-only`, `mcp-only`); sessions saved
+    /// without one leave the mode unchanged.
+    #[serde(default)]
+    pub agent_mode: Option<String>,
+    /// The agent's steps for the last answer, as shown in the trace pane.
+    #[serde(default)]
+    pub agent_trace: Vec<String>,
+    /// The last plan of the `plan` strategy.
+    #[serde(default)]
+    pub agent_plan: Vec<String>,
+    #[serde(default)]
+    pub usage: Usage,
+    #[serde(default, with = "crate::timestamps::rfc3339_secs")]
+    pub updated_at: u64,
+}
+
+impl Session {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn load(cfg: &Config, name: &str) -> Result<Option<Self>, String> {
+        let path = session_path(cfg, name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read session {}: {}", path.display(), e))?;
+        let session = serde_json::from_str::<Self>(&text)
+            .map_err(|e| format!("Failed to parse session {}: {}", path.display(), e))?;
+        Ok(Some(session))
+    }
+
+    pub fn load_or_new(cfg: &Config, name: &str) -> Result<Self, String> {
+        Ok(Self::load(cfg, name)?.unwrap_or_else(|| Self::new(name)))
+    }
+
+    pub fn save(&mut self, cfg: &Config) -> Result<(), String> {
+        if cfg.safe_mode {
+            return Err("Safe mode is on: sessions are not saved.".to_string());
+        }
+        let path = session_path(cfg, &self.name)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        self.updated_at = unix_now();
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write session {}: {}", path.display(), e))
+    }
+
+    pub fn record_turn(&mut self, question: &str, context: &str, answer: &str) {
+        self.conversation.push(Message {
+            role: "user".to_string(),
+            content: question.to_string(),
+        });
+        self.conversation.push(Message {
+            role: "assistant".to_string(),
+            content: answer.to_string(),
+        });
+        self.last_context = Some(context.to_string());
+        self.last_answer = Some(answer.to_string());
+    }
+}
+
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Ask `RAG_TITLE_MODEL` for a few-word title summarizing the conversation.
+pub fn generate_title(cfg: &Config, conversation: &[Message]) -> Result<String, String> {
+    let transcript = conversation
+        .iter()
+        .take(2)
+        .map(|m| format!("{}: {}", m.role, m.content.chars().take(1_000).collect::<String>()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: format!(
+            "Write a title of at most six words for this conversation. Reply with the title only, no quotes or punctuation at the end.\n\n{}",
+            transcript
+        ),
+    }];
+    let mut title_cfg = cfg.clone();
+    title_cfg.chat_model = cfg.title_model.clone();
+    let options = GenerationOptions {
+        temperature: Some(0.2),
+        ..GenerationOptions::default()
+    };
+    let title = clean_title(&generate_answer_with(&title_cfg, &messages, &options)?);
+    if title.is_empty() {
+        return Err("Title model returned an empty title".to_string());
+    }
+    Ok(title)
+}
+
+/// First line of a model reply without quotes, a `Title:` prefix, or
+/// trailing punctuation, cut to a length that fits a title bar.
+pub fn clean_title(raw: &str) -> String {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+    let line = line
+        .strip_prefix("Title:")
+        .or_else(|| line.strip_prefix("title:"))
+        .unwrap_or(line);
+    let line = line
+        .trim()
+        .trim_matches(|c| matches!(c, '"' | '\'' | '*' | '#' | '`'))
+        .trim_end_matches(['.', '!', ':'])
+        .trim();
+    let mut title: String = line.chars().take(MAX_TITLE_CHARS).collect();
+    if line.chars().count() > MAX_TITLE_CHARS {
+        title = title.trim_end().to_string();
+        title.push_str("...");
+    }
+    title
+}
+
+/// Session names, most recently updated first.
+pub fn list_sessions(cfg: &Config) -> Result<Vec<Session>, String> {
+    let dir = sessions_dir(cfg);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut sessions = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Ok(session) = serde_json::from_str::<Session>(&text) {
+            sessions.push(session);
+        }
+    }
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(sessions)
+}
+
+pub fn sessions_dir(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("sessions")
+}
+
+fn session_path(cfg: &Config, name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.');
+    if !valid {
+        return Err(format!(
+            "Invalid session name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        ));
+    }
+    Ok(sessions_dir(cfg).join(format!("{}.json", name)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
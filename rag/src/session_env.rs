@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+/// `KEY=VALUE` as a name and a value. Names are ASCII letters, digits, and
+/// `_`, and do not start with a digit; the value may be empty.
+pub fn parse_env_assignment(text: &str) -> Result<(String, String), String> {
+    let (name, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("Expected KEY=VALUE, got '{}'", text))?;
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+    if !valid {
+        return Err(format!("Invalid variable name '{}'", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// The variables as `KEY=VALUE` words, or `(none)`.
+pub fn describe_env(vars: &BTreeMap<String, String>) -> String {
+    if vars.is_empty() {
+        return "(none)".to_string();
+    }
+    vars.iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Apply `/env` arguments, split with quotes removed, to `vars`: none lists
+/// them, `KEY=VALUE..` sets them, `clear` drops all, and `clear KEY..` the
+/// named ones. Returns what to tell the user; nothing changes on an error.
+pub fn apply_env_command(vars: &mut BTreeMap<String, String>, args: &[String]) -> Result<String, String> {
+    match args {
+        [] => Ok(format!("Session env: {}", describe_env(vars))),
+        [clear] if clear == "clear" => {
+            let count = vars.len();
+            vars.clear();
+            Ok(format!("Cleared {} session variable{}.", count, if count == 1 { "" } else { "s" }))
+        }
+        [clear, names @ ..] if clear == "clear" => {
+            for name in names {
+                vars.remove(name);
+            }
+            Ok(format!("Session env: {}", describe_env(vars)))
+        }
+        assignments => {
+            let parsed = assignments
+                .iter()
+                .map(|a| parse_env_assignment(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            vars.extend(parsed);
+            Ok(format!("Session env: {}", describe_env(vars)))
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::Write;
+use std::process::Stdio;
+
+use serde_json::json;
+
+use crate::build_prompt::Message;
+use crate::config::Config;
+use crate::http::{post_text, HttpOptions};
+use crate::platform::shell_command;
+use crate::timestamps::{format_local, now_secs, rfc3339};
+
+/// Where a conversation can be shared: a shell command that reads the
+/// Markdown export on stdin, or a webhook that receives it as JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShareAction {
+    Command(String),
+    Webhook(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShareTarget {
+    pub name: String,
+    pub action: ShareAction,
+}
+
+/// Parse `AICLI_SHARE_TARGETS`: `name=cmd:<shell command>` or
+/// `name=webhook:<url>` entries separated by `;`.
+pub fn parse_share_targets(raw: &str) -> Vec<ShareTarget> {
+    let mut out = Vec::new();
+    for entry in raw.split(';') {
+        let Some((name, spec)) = entry.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        let spec = spec.trim();
+        let action = if let Some(cmd) = spec.strip_prefix("cmd:") {
+            ShareAction::Command(cmd.trim().to_string())
+        } else if let Some(url) = spec.strip_prefix("webhook:") {
+            ShareAction::Webhook(url.trim().to_string())
+        } else {
+            continue;
+        };
+        if !name.is_empty() {
+            out.push(ShareTarget {
+                name: name.to_string(),
+                action,
+            });
+        }
+    }
+    out
+}
+
+/// Title for a shared conversation: the first question, cut to one line.
+pub fn conversation_title(conversation: &[Message]) -> String {
+    let first = conversation
+        .iter()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.lines().next().unwrap_or_default().trim().to_string())
+        .unwrap_or_default();
+    if first.is_empty() {
+        return "aicli conversation".to_string();
+    }
+    let mut title: String = first.chars().take(80).collect();
+    if first.chars().count() > 80 {
+        title.push_str("...");
+    }
+    title
+}
+
+/// The conversation as Markdown, one section per turn.
+pub fn export_markdown(title: &str, conversation: &[Message]) -> String {
+    let mut out = format!("# {}\n", title);
+    for message in conversation {
+        let heading = match message.role.as_str() {
+            "user" => "Question",
+            "assistant" => "Answer",
+            _ => continue,
+        };
+        out.push_str(&format!("\n## {}\n\n{}\n", heading, message.content.trim()));
+    }
+    out
+}
+
+/// Send the exported conversation to `target`, ending with when it was
+/// exported in local time. Commands get the Markdown on stdin and the title
+/// in `AICLI_SHARE_TITLE`; webhooks get `{"title", "body", "text",
+/// "exported_at"}` (`text` is what Slack displays, `exported_at` is RFC3339).
+pub fn share_conversation(
+    cfg: &Config,
+    target: &ShareTarget,
+    conversation: &[Message],
+) -> Result<String, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: sharing is disabled.".to_string());
+    }
+    let title = conversation_title(conversation);
+    let now = now_secs();
+    let body = format!("{}\n_Exported {}_\n", export_markdown(&title, conversation), format_local(cfg, now));
+    match &target.action {
+        ShareAction::Command(cmd) => run_share_command(&cfg.shell, cmd, &title, &body),
+        ShareAction::Webhook(url) => {
+            let payload = json!({ "title": title, "body": body, "text": body, "exported_at": rfc3339(now) });
+            // Slack answers a bare "ok", so the response is not parsed.
+            post_text(url, &payload, &HttpOptions::standard(cfg))?;
+            Ok(format!("Shared to {}.", target.name))
+        }
+    }
+}
+
+fn run_share_command(shell: &str, cmd: &str, title: &str, body: &str) -> Result<String, String> {
+    let mut child = shell_command(shell, cmd)
+        .env("AICLI_SHARE_TITLE", title)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run share command: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|e| format!("Failed to write to share command: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Share command failed: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!("Share command exited with {}: {}", output.status, stderr));
+    }
+    Ok(if stdout.is_empty() { "Shared.".to_string() } else { stdout })
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::distance::forget_collection;
+use crate::http::{delete_json_async, get_bytes, get_json, post_bytes, HttpOptions};
+use crate::manifest::IndexManifest;
+use crate::runtime::block_on;
+use crate::store_access::check_writable;
+use crate::store_qdrant::{
+    collection_info, create_collection, delete_collection, restore_points, scroll_points_with_vectors, StoredPoint,
+};
+
+// Snapshots of large collections take a while to build and transfer.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+const RESTORE_BATCH: usize = 256;
+const MULTIPART_BOUNDARY: &str = "aicli-snapshot-boundary-7f3c9a";
+
+/// First line of a JSONL snapshot: what the points were embedded with.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub collection: String,
+    pub vector_size: usize,
+    pub distance: String,
+    pub embed_model: String,
+    pub points: usize,
+}
+
+/// Where a snapshot was written or read from, and how many points it held.
+/// Qdrant's native snapshots do not report a point count.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotSummary {
+    pub path: PathBuf,
+    pub points: Option<usize>,
+    pub native: bool,
+}
+
+/// Files ending in `.snapshot` use Qdrant's snapshot API; anything else is
+/// a portable JSONL dump of every point with its vector and payload.
+pub fn is_native_snapshot(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "snapshot")
+}
+
+/// Save the configured collection to `path`, or to
+/// `$AICLI_DATA_DIR/snapshots/<collection>-<unix time>.jsonl` when none is
+/// given. The index manifest is written next to it so a restored index
+/// keeps tracking which files it holds.
+pub fn create_snapshot(cfg: &Config, path: Option<&str>) -> Result<SnapshotSummary, String> {
+    let info = collection_info(cfg)?.ok_or_else(|| format!("Collection {} does not exist", cfg.collection))?;
+    let path = path.map(PathBuf::from).unwrap_or_else(|| default_snapshot_path(cfg));
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let native = is_native_snapshot(&path);
+    let points = if native {
+        download_native_snapshot(cfg, &path)?;
+        None
+    } else {
+        let points = scroll_points_with_vectors(cfg)?;
+        let header = SnapshotHeader {
+            collection: cfg.collection.clone(),
+            vector_size: info.vector_size,
+            distance: info.distance,
+            embed_model: cfg.embed_model.clone(),
+            points: points.len(),
+        };
+        write_jsonl(&path, &header, &points)?;
+        Some(points.len())
+    };
+
+    let manifest = IndexManifest::load(cfg)?;
+    let text = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    let manifest_file = manifest_sidecar(&path);
+    fs::write(&manifest_file, text)
+        .map_err(|e| format!("Failed to write manifest {}: {}", manifest_file.display(), e))?;
+
+    Ok(SnapshotSummary { path, points, native })
+}
+
+/// Load a snapshot into the configured collection. A collection that
+/// already holds points is only overwritten when `replace` is set.
+pub fn restore_snapshot(cfg: &Config, path: &str, replace: bool) -> Result<SnapshotSummary, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: snapshot restore is disabled.".to_string());
+    }
+    check_writable(cfg, "snapshot restore")?;
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Err(format!("Snapshot {} does not exist", path.display()));
+    }
+    if let Some(existing) = collection_info(cfg)? {
+        if existing.points_count > 0 && !replace {
+            return Err(format!(
+                "Collection {} already has {} points; pass --replace to overwrite it",
+                cfg.collection, existing.points_count
+            ));
+        }
+    }
+
+    let native = is_native_snapshot(&path);
+    let points = if native {
+        upload_native_snapshot(cfg, &path)?;
+        None
+    } else {
+        Some(restore_jsonl(cfg, &path)?)
+    };
+    forget_collection(cfg);
+
+    let manifest_file = manifest_sidecar(&path);
+    if let Ok(text) = fs::read_to_string(&manifest_file) {
+        let mut manifest = serde_json::from_str::<IndexManifest>(&text)
+            .map_err(|e| format!("Failed to parse manifest {}: {}", manifest_file.display(), e))?;
+        manifest.collection = cfg.collection.clone();
+        manifest.save(cfg)?;
+    }
+
+    Ok(SnapshotSummary { path, points, native })
+}
+
+/// Read the header line of a JSONL snapshot.
+pub fn read_snapshot_header(path: &Path) -> Result<SnapshotHeader, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut first = String::new();
+    BufReader::new(file)
+        .read_line(&mut first)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&first).map_err(|e| format!("{} is not an aicli snapshot: {}", path.display(), e))
+}
+
+fn default_snapshot_path(cfg: &Config) -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    PathBuf::from(&cfg.data_dir)
+        .join("snapshots")
+        .join(format!("{}-{}.jsonl", cfg.collection, now))
+}
+
+fn manifest_sidecar(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".manifest.json");
+    PathBuf::from(name)
+}
+
+fn write_jsonl(path: &Path, header: &SnapshotHeader, points: &[StoredPoint]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = BufWriter::new(file);
+    let write_err = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+    let header = serde_json::to_string(header).map_err(|e| e.to_string())?;
+    writeln!(out, "{}", header).map_err(write_err)?;
+    for point in points {
+        let line = serde_json::to_string(point).map_err(|e| e.to_string())?;
+        writeln!(out, "{}", line).map_err(write_err)?;
+    }
+    out.flush().map_err(write_err)
+}
+
+fn restore_jsonl(cfg: &Config, path: &Path) -> Result<usize, String> {
+    let header = read_snapshot_header(path)?;
+    if !header.embed_model.is_empty() && header.embed_model != cfg.embed_model {
+        return Err(format!(
+            "Snapshot was embedded with {} but OLLAMA_EMBED_MODEL is {}; queries would not match its vectors",
+            header.embed_model, cfg.embed_model
+        ));
+    }
+    let mut target = cfg.clone();
+    target.distance = header.distance.clone();
+    if collection_info(cfg)?.is_some() {
+        delete_collection(cfg)?;
+    }
+    create_collection(&target, header.vector_size)?;
+
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut restored = 0;
+    let mut batch: Vec<StoredPoint> = Vec::with_capacity(RESTORE_BATCH);
+    for (n, line) in BufReader::new(file).lines().enumerate().skip(1) {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let point = serde_json::from_str::<StoredPoint>(&line)
+            .map_err(|e| format!("{} line {}: {}", path.display(), n + 1, e))?;
+        batch.push(point);
+        if batch.len() == RESTORE_BATCH {
+            restore_points(&target, &batch)?;
+            restored += batch.len();
+            batch.clear();
+        }
+    }
+    restore_points(&target, &batch)?;
+    Ok(restored + batch.len())
+}
+
+#[derive(Deserialize)]
+struct SnapshotCreated {
+    result: SnapshotDescription,
+}
+
+#[derive(Deserialize)]
+struct SnapshotDescription {
+    name: String,
+}
+
+fn snapshot_options(cfg: &Config) -> HttpOptions {
+    HttpOptions {
+        timeout: SNAPSHOT_TIMEOUT,
+        ..HttpOptions::for_qdrant(cfg)
+    }
+}
+
+// Qdrant writes the snapshot on the server; fetch it so it can be copied to
+// another machine, then drop the server-side copy.
+fn download_native_snapshot(cfg: &Config, path: &Path) -> Result<(), String> {
+    let base = format!("{}/collections/{}/snapshots", cfg.qdrant_url, cfg.collection);
+    let opts = snapshot_options(cfg);
+    let text = post_bytes(&format!("{}?wait=true", base), "application/json", b"", &opts)?;
+    let created = serde_json::from_str::<SnapshotCreated>(&text)
+        .map_err(|e| format!("Unexpected snapshot response: {} | {}", e, text))?;
+    let url = format!("{}/{}", base, created.result.name);
+    let bytes = get_bytes(&url, &opts)?;
+    fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let _ = block_on(delete_json_async::<Value>(&url, &opts));
+    Ok(())
+}
+
+fn upload_native_snapshot(cfg: &Config, path: &Path) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("collection.snapshot");
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"snapshot\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        MULTIPART_BOUNDARY, name
+    )
+    .into_bytes();
+    body.extend_from_slice(&bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+    let url = format!(
+        "{}/collections/{}/snapshots/upload?priority=snapshot&wait=true",
+        cfg.qdrant_url, cfg.collection
+    );
+    let content_type = format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY);
+    post_bytes(&url, &content_type, &body, &snapshot_options(cfg))?;
+    // Confirm the collection came back before reporting success.
+    let info_url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
+    get_json::<Value>(&info_url, &HttpOptions::for_qdrant(cfg)).map(|_| ())
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Reusable prompt text, keyed by the name typed after `;;` in the input.
+pub type Snippets = BTreeMap<String, String>;
+
+pub fn snippets_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.config_dir).join("snippets.json")
+}
+
+pub fn load_snippets(cfg: &Config) -> Result<Snippets, String> {
+    let path = snippets_path(cfg);
+    if !path.exists() {
+        return Ok(Snippets::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn save_snippets(cfg: &Config, snippets: &Snippets) -> Result<(), String> {
+    let path = snippets_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(snippets).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Names are letters, digits, `-`, and `_`.
+pub fn is_snippet_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_name_char)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Parse the manager's `name = text` form.
+pub fn parse_snippet(line: &str) -> Result<(String, String), String> {
+    let (name, text) = line
+        .split_once('=')
+        .ok_or_else(|| "Expected: name = snippet text".to_string())?;
+    let name = name.trim().trim_start_matches(";;");
+    if !is_snippet_name(name) {
+        return Err(format!("Invalid snippet name '{}': use letters, digits, - and _", name));
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(format!("Snippet '{}' has no text", name));
+    }
+    Ok((name.to_string(), text.to_string()))
+}
+
+/// Replace every `;;name` with its snippet. Unknown names are left as typed.
+pub fn expand_snippets(input: &str, snippets: &Snippets) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(pos) = rest.find(";;") {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 2..];
+        let len = after.find(|c: char| !is_name_char(c)).unwrap_or(after.len());
+        match snippets.get(&after[..len]) {
+            Some(text) if len > 0 => out.push_str(text),
+            _ => out.push_str(&rest[pos..pos + 2 + len]),
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Tab completion for a `;;prefix` ending at byte offset `cursor`: the byte
+/// range of the prefix and the longest name all matching snippets share.
+/// `None` when the cursor is not on a snippet name or nothing matches.
+pub fn complete_snippet(input: &str, cursor: usize, snippets: &Snippets) -> Option<(usize, String)> {
+    let before = input.get(..cursor)?;
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name_char(*c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(cursor);
+    if !before[..start].ends_with(";;") {
+        return None;
+    }
+    let prefix = &before[start..];
+    let mut matches = snippets.keys().filter(|name| name.starts_with(prefix));
+    let first = matches.next()?.clone();
+    let common = matches.fold(first, |common, name| {
+        common
+            .chars()
+            .zip(name.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    });
+    Some((start, common))
+}
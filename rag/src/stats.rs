@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::http::{get_json, HttpOptions};
+use crate::store_qdrant::{collection_info, scroll_paths};
+
+/// What a collection actually holds, as reported by Qdrant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectionStats {
+    pub collection: String,
+    pub points: u64,
+    pub vector_size: usize,
+    pub distance: String,
+    pub files: usize,
+    /// Sum of segment disk usage from Qdrant telemetry; `None` when the
+    /// server does not report it.
+    pub disk_bytes: Option<u64>,
+}
+
+impl fmt::Display for CollectionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Collection:  {}", self.collection)?;
+        writeln!(f, "Points:      {}", self.points)?;
+        writeln!(f, "Files:       {}", self.files)?;
+        writeln!(f, "Vector size: {}", self.vector_size)?;
+        writeln!(f, "Distance:    {}", self.distance)?;
+        match self.disk_bytes {
+            Some(bytes) => write!(f, "Disk usage:  {}", format_bytes(bytes)),
+            None => write!(f, "Disk usage:  (not reported)"),
+        }
+    }
+}
+
+/// Point count, vector parameters, distinct source files, and disk usage of
+/// the configured collection.
+pub fn collection_stats(cfg: &Config) -> Result<CollectionStats, String> {
+    let info = collection_info(cfg)?.ok_or_else(|| format!("Collection {} does not exist", cfg.collection))?;
+    let files: HashSet<String> = scroll_paths(cfg)?.into_iter().collect();
+    Ok(CollectionStats {
+        collection: cfg.collection.clone(),
+        points: info.points_count,
+        vector_size: info.vector_size,
+        distance: info.distance,
+        files: files.len(),
+        disk_bytes: disk_usage(cfg),
+    })
+}
+
+// Segment sizes only appear in detailed telemetry, which some deployments
+// disable; missing data is not an error.
+fn disk_usage(cfg: &Config) -> Option<u64> {
+    let url = format!("{}/telemetry?details_level=3", cfg.qdrant_url);
+    let telemetry = get_json::<Value>(&url, &HttpOptions::for_qdrant(cfg)).ok()?;
+    let collections = telemetry.pointer("/result/collections/collections")?.as_array()?;
+    let collection = collections
+        .iter()
+        .find(|c| c.get("id").and_then(|v| v.as_str()) == Some(cfg.collection.as_str()))?;
+    let mut total = None;
+    sum_disk_usage(collection, &mut total);
+    total
+}
+
+fn sum_disk_usage(value: &Value, total: &mut Option<u64>) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                if key == "disk_usage_bytes" {
+                    if let Some(n) = v.as_u64() {
+                        *total = Some(total.unwrap_or(0) + n);
+                    }
+                } else {
+                    sum_disk_usage(v, total);
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| sum_disk_usage(v, total)),
+        _ => {}
+    }
+}
+
+/// `1536` → `1.5 KiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,98 @@
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::http::{send_raw_async, HttpOptions};
+use crate::runtime::block_on;
+
+/// Whether `api_key` is a Qdrant JWT that can only read `collection`: its
+/// `access` claim is `r`, or none of its per-collection rules grants `rw` on
+/// `collection`. Plain API keys say nothing and give false.
+pub fn jwt_is_read_only(api_key: &str, collection: &str) -> bool {
+    let Some(claims) = jwt_claims(api_key) else {
+        return false;
+    };
+    match claims.get("access") {
+        Some(Value::String(access)) => access == "r",
+        Some(Value::Array(rules)) => !rules.iter().any(|rule| {
+            rule.get("collection").and_then(Value::as_str) == Some(collection)
+                && rule.get("access").and_then(Value::as_str) == Some("rw")
+        }),
+        _ => false,
+    }
+}
+
+fn jwt_claims(token: &str) -> Option<Value> {
+    let mut parts = token.split('.');
+    let (_header, payload, _signature) = (parts.next()?, parts.next()?, parts.next()?);
+    serde_json::from_slice(&base64url_decode(payload)?).ok()
+}
+
+fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut bits, mut count) = (0u32, 0u32);
+    for byte in text.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Some(out)
+}
+
+/// Whether Qdrant refuses writes to `cfg.collection`: `QDRANT_READ_ONLY`, a
+/// read-only JWT, or a 403 for deleting no points, which changes nothing
+/// when writes are allowed.
+pub async fn store_read_only_async(cfg: &Config) -> Result<bool, String> {
+    if cfg.qdrant_read_only || jwt_is_read_only(&cfg.qdrant_api_key, &cfg.collection) {
+        return Ok(true);
+    }
+    let url = format!("{}/collections/{}/points/delete", cfg.qdrant_url, cfg.collection);
+    let body = json!({ "points": [] }).to_string();
+    let opts = HttpOptions::for_qdrant(cfg);
+    let resp = send_raw_async("POST", &url, Some(("application/json", body.as_bytes())), &opts).await?;
+    Ok(resp.status == 403)
+}
+
+pub fn store_read_only(cfg: &Config) -> Result<bool, String> {
+    block_on(store_read_only_async(cfg))
+}
+
+/// An error saying `action` is disabled when the index is read-only.
+pub fn check_writable(cfg: &Config, action: &str) -> Result<(), String> {
+    if cfg.qdrant_read_only {
+        return Err(format!("The index at {} is read-only: {} is disabled.", cfg.qdrant_url, action));
+    }
+    Ok(())
+}
+
+/// Where the index lives, for the status bar: `local` for a Qdrant on this
+/// machine, else `remote <host>`, followed by `read-only` when it is.
+pub fn corpus_origin(cfg: &Config) -> String {
+    let authority = cfg.qdrant_url.split_once("://").map(|(_, rest)| rest).unwrap_or(&cfg.qdrant_url);
+    let host = authority.split(['/', '?', '#']).next().unwrap_or_default();
+    let name = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    let origin = if matches!(name, "localhost" | "127.0.0.1" | "::1" | "0.0.0.0" | "") {
+        "local".to_string()
+    } else {
+        format!("remote {}", host)
+    };
+    if cfg.qdrant_read_only {
+        format!("{} read-only", origin)
+    } else {
+        origin
+    }
+}
@@ -1,29 +1,75 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
 
 use crate::config::Config;
-use crate::http::{get_json, put_json};
+use crate::distance::{dimension_mismatch_message, distance_mismatch_message, forget_collection};
+use crate::http::{
+    delete_json_async, patch_json_async, post_json, post_json_async, put_json, put_json_async, send_raw_async,
+    HttpOptions,
+};
+use crate::runtime::block_on;
 
 #[derive(Serialize)]
 struct CreateCollection {
     vectors: VectorParams,
+    #[serde(flatten)]
+    tuning: Map<String, Value>,
 }
 
 #[derive(Serialize)]
 struct VectorParams {
     size: usize,
     distance: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    on_disk: bool,
 }
 
 #[derive(Serialize)]
 pub struct PointPayload {
     pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
     pub index: usize,
     pub chunk: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub before: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub after: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// `code`, `docs`, `config`, `test`, or `changelog`; see `classify_chunk`.
+    pub doc_type: String,
+    /// Chunk size and overlap this file was split with.
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+}
+
+// Fixed namespace for chunk point ids; changing it would re-key every index.
+const POINT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6a1c_3f0e_9b2d_4e57_8c41_d2f5_a7e0_b913);
+
+/// Stable point id for a chunk: a UUIDv5 of the path, the chunk's position,
+/// and its text. Re-indexing unchanged content upserts the same points, and
+/// an edited chunk gets a new id instead of overwriting an unrelated one.
+pub fn point_id(path: &str, index: usize, chunk: &str) -> String {
+    let mut name = Vec::with_capacity(path.len() + chunk.len() + 24);
+    name.extend_from_slice(path.as_bytes());
+    name.push(0);
+    name.extend_from_slice(index.to_string().as_bytes());
+    name.push(0);
+    name.extend_from_slice(chunk.as_bytes());
+    Uuid::new_v5(&POINT_ID_NAMESPACE, &name).to_string()
 }
 
 #[derive(Serialize)]
 pub struct Point {
-    pub id: i64,
+    pub id: String,
     pub vector: Vec<f32>,
     pub payload: PointPayload,
 }
@@ -38,28 +84,372 @@ struct QdrantResponse {
     _result: Option<serde_json::Value>,
 }
 
-pub fn ensure_collection(cfg: &Config, vector_size: usize) -> Result<(), String> {
-    let url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
-    let exists = get_json::<serde_json::Value>(&url).is_ok();
-    if exists {
-        return Ok(());
+/// Create the collection if it is missing. An existing collection with a
+/// different vector size or distance is an error unless
+/// `cfg.recreate_collection` is set, in which case it is dropped and
+/// created again; returns whether that happened, since every stored point
+/// is gone afterwards.
+pub async fn ensure_collection_async(cfg: &Config, vector_size: usize) -> Result<bool, String> {
+    let Some(info) = collection_info_async(cfg).await? else {
+        create_collection_async(cfg, vector_size).await?;
+        ensure_payload_indexes_async(cfg, &[]).await?;
+        forget_collection(cfg);
+        return Ok(false);
+    };
+    let same_size = info.vector_size == 0 || info.vector_size == vector_size;
+    let same_distance = info.distance.is_empty() || info.distance.eq_ignore_ascii_case(&cfg.distance);
+    if same_size && same_distance {
+        update_collection_tuning_async(cfg).await?;
+        ensure_payload_indexes_async(cfg, &info.indexed_fields).await?;
+        return Ok(false);
+    }
+    if !cfg.recreate_collection {
+        return Err(if same_size {
+            distance_mismatch_message(cfg, &info.distance)
+        } else {
+            dimension_mismatch_message(cfg, info.vector_size, vector_size)
+        });
+    }
+    delete_collection_async(cfg).await?;
+    create_collection_async(cfg, vector_size).await?;
+    ensure_payload_indexes_async(cfg, &[]).await?;
+    forget_collection(cfg);
+    Ok(true)
+}
+
+pub fn ensure_collection(cfg: &Config, vector_size: usize) -> Result<bool, String> {
+    block_on(ensure_collection_async(cfg, vector_size))
+}
+
+#[derive(Serialize)]
+struct CreateFieldIndex<'a> {
+    field_name: &'a str,
+    field_schema: &'a str,
+}
+
+/// Create the `RAG_PAYLOAD_INDEXES` (always including `path`) that are not
+/// in `existing`, so filtered queries do not scan every payload.
+async fn ensure_payload_indexes_async(cfg: &Config, existing: &[String]) -> Result<(), String> {
+    let url = format!("{}/collections/{}/index?wait=true", cfg.qdrant_url, cfg.collection);
+    for (field, schema) in &cfg.payload_indexes {
+        if existing.contains(field) {
+            continue;
+        }
+        let body = CreateFieldIndex {
+            field_name: field,
+            field_schema: schema,
+        };
+        put_json_async::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg))
+            .await
+            .map_err(|e| format!("Failed to index payload field {}: {}", field, e))?;
     }
+    Ok(())
+}
+
+/// Create the collection with `cfg.distance`; fails if it already exists.
+pub async fn create_collection_async(cfg: &Config, vector_size: usize) -> Result<(), String> {
+    let url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
     let body = CreateCollection {
         vectors: VectorParams {
             size: vector_size,
             distance: cfg.distance.clone(),
+            on_disk: cfg.qdrant_vectors_on_disk,
         },
+        tuning: collection_tuning(cfg)?,
     };
-    let _ = put_json::<QdrantResponse, _>(&url, &body)?;
+    let _ = put_json_async::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg)).await?;
     Ok(())
 }
 
-pub fn store_points(cfg: &Config, points: &[Point]) -> Result<(), String> {
+/// Memory/accuracy settings for a new collection: `on_disk_payload`,
+/// `hnsw_config`, and `quantization_config`, each present only when the
+/// matching `QDRANT_*` variable is set.
+pub fn collection_tuning(cfg: &Config) -> Result<Map<String, Value>, String> {
+    let mut tuning = Map::new();
+    if cfg.qdrant_on_disk_payload {
+        tuning.insert("on_disk_payload".to_string(), Value::Bool(true));
+    }
+    let mut hnsw = Map::new();
+    if let Some(m) = cfg.qdrant_hnsw_m {
+        hnsw.insert("m".to_string(), json!(m));
+    }
+    if let Some(ef) = cfg.qdrant_hnsw_ef_construct {
+        hnsw.insert("ef_construct".to_string(), json!(ef));
+    }
+    if cfg.qdrant_vectors_on_disk {
+        hnsw.insert("on_disk".to_string(), Value::Bool(true));
+    }
+    if !hnsw.is_empty() {
+        tuning.insert("hnsw_config".to_string(), Value::Object(hnsw));
+    }
+    if let Some(quantization) = quantization_config(cfg)? {
+        tuning.insert("quantization_config".to_string(), quantization);
+    }
+    Ok(tuning)
+}
+
+/// `QDRANT_QUANTIZATION` as Qdrant's `quantization_config`. Quantized
+/// vectors stay in RAM while the originals may live on disk.
+fn quantization_config(cfg: &Config) -> Result<Option<Value>, String> {
+    match cfg.qdrant_quantization.trim().to_ascii_lowercase().as_str() {
+        "" | "none" | "off" => Ok(None),
+        "scalar" => Ok(Some(json!({
+            "scalar": { "type": "int8", "quantile": cfg.qdrant_quantile, "always_ram": true }
+        }))),
+        "product" => Ok(Some(json!({
+            "product": { "compression": cfg.qdrant_pq_compression, "always_ram": true }
+        }))),
+        "binary" => Ok(Some(json!({ "binary": { "always_ram": true } }))),
+        other => Err(format!(
+            "Unknown QDRANT_QUANTIZATION {:?}; expected none, scalar, product, or binary",
+            other
+        )),
+    }
+}
+
+// Apply the configured tuning to an existing collection. Qdrant rebuilds
+// indexes in the background, so nothing is sent when nothing is configured.
+async fn update_collection_tuning_async(cfg: &Config) -> Result<(), String> {
+    let mut update = collection_tuning(cfg)?;
+    if update.is_empty() {
+        return Ok(());
+    }
+    if let Some(on_disk) = update.remove("on_disk_payload") {
+        update.insert("params".to_string(), json!({ "on_disk_payload": on_disk }));
+    }
+    let url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
+    let _ = patch_json_async::<QdrantResponse, _>(&url, &update, &HttpOptions::for_qdrant(cfg)).await?;
+    Ok(())
+}
+
+pub fn create_collection(cfg: &Config, vector_size: usize) -> Result<(), String> {
+    block_on(create_collection_async(cfg, vector_size))
+}
+
+pub fn delete_collection(cfg: &Config) -> Result<(), String> {
+    block_on(delete_collection_async(cfg))
+}
+
+pub async fn delete_collection_async(cfg: &Config) -> Result<(), String> {
+    let url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
+    let _ = delete_json_async::<QdrantResponse>(&url, &HttpOptions::for_qdrant(cfg)).await?;
+    Ok(())
+}
+
+pub async fn store_points_async(cfg: &Config, points: &[Point]) -> Result<(), String> {
     if points.is_empty() {
         return Ok(());
     }
     let url = format!("{}/collections/{}/points", cfg.qdrant_url, cfg.collection);
     let body = UpsertPoints { points };
-    let _ = put_json::<QdrantResponse, _>(&url, &body)?;
+    let Err(err) = put_json_async::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg)).await else {
+        return Ok(());
+    };
+    // Qdrant's "Vector dimension error" does not say which side changed.
+    if err.to_ascii_lowercase().contains("dimension") {
+        if let Ok(Some(info)) = collection_info_async(cfg).await {
+            if let Some(point) = points.iter().find(|p| p.vector.len() != info.vector_size) {
+                return Err(dimension_mismatch_message(cfg, info.vector_size, point.vector.len()));
+            }
+        }
+    }
+    Err(err)
+}
+
+/// Vector size, distance, point count, and indexed payload fields of an
+/// existing collection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectionInfo {
+    pub vector_size: usize,
+    pub distance: String,
+    pub points_count: u64,
+    /// Payload fields that already have an index.
+    pub indexed_fields: Vec<String>,
+}
+
+pub fn collection_info(cfg: &Config) -> Result<Option<CollectionInfo>, String> {
+    block_on(collection_info_async(cfg))
+}
+
+pub async fn collection_info_async(cfg: &Config) -> Result<Option<CollectionInfo>, String> {
+    let url = format!("{}/collections/{}", cfg.qdrant_url, cfg.collection);
+    let resp = send_raw_async("GET", &url, None, &HttpOptions::for_qdrant(cfg)).await?;
+    // Only a missing collection means "not created yet"; anything else is a
+    // Qdrant that cannot be asked, and must not look like an empty store.
+    if resp.status == 404 {
+        return Ok(None);
+    }
+    if !resp.is_success() {
+        return Err(format!("GET {} failed: {} {}", url, resp.status, resp.body));
+    }
+    let res: Value = serde_json::from_str(&resp.body)
+        .map_err(|e| format!("GET {} decode failed: {} | {}", url, e, resp.body))?;
+    let result = res.get("result").ok_or("Collection info missing result")?;
+    let vectors = result.pointer("/config/params/vectors").ok_or("Collection info missing vector params")?;
+    Ok(Some(CollectionInfo {
+        vector_size: vectors.get("size").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        distance: vectors
+            .get("distance")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        points_count: result.get("points_count").and_then(|v| v.as_u64()).unwrap_or(0),
+        indexed_fields: result
+            .get("payload_schema")
+            .and_then(|v| v.as_object())
+            .map(|schema| schema.keys().cloned().collect())
+            .unwrap_or_default(),
+    }))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct StoredPoint {
+    pub id: serde_json::Value,
+    #[serde(default)]
+    pub payload: Option<serde_json::Value>,
+    #[serde(default)]
+    pub vector: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct ScrollRequest {
+    limit: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<serde_json::Value>,
+    /// `true`, or a list of payload keys to return.
+    with_payload: serde_json::Value,
+    with_vector: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ScrollResponse {
+    result: ScrollResult,
+}
+
+#[derive(Deserialize)]
+struct ScrollResult {
+    points: Vec<StoredPoint>,
+    #[serde(default)]
+    next_page_offset: Option<serde_json::Value>,
+}
+
+/// Every point in the collection with its payload, paged through `scroll`.
+pub fn scroll_points(cfg: &Config) -> Result<Vec<StoredPoint>, String> {
+    scroll_all(cfg, serde_json::Value::Bool(true), false, None)
+}
+
+/// The points stored for one indexed `path`, in chunk order.
+pub fn scroll_points_for_path(cfg: &Config, path: &str) -> Result<Vec<StoredPoint>, String> {
+    let filter = serde_json::json!({ "must": [{ "key": "path", "match": { "value": path } }] });
+    let mut points = scroll_all(cfg, serde_json::Value::Bool(true), false, Some(filter))?;
+    points.sort_by_key(|p| p.payload.as_ref().and_then(|v| v.get("index")?.as_u64()).unwrap_or(0));
+    Ok(points)
+}
+
+/// Like `scroll_points`, with each point's stored vector.
+pub fn scroll_points_with_vectors(cfg: &Config) -> Result<Vec<StoredPoint>, String> {
+    scroll_all(cfg, serde_json::Value::Bool(true), true, None)
+}
+
+/// The `path` payload of every point, one entry per point.
+pub fn scroll_paths(cfg: &Config) -> Result<Vec<String>, String> {
+    let points = scroll_all(cfg, serde_json::json!(["path"]), false, None)?;
+    Ok(points
+        .into_iter()
+        .filter_map(|p| p.payload?.get("path")?.as_str().map(str::to_string))
+        .collect())
+}
+
+fn scroll_all(
+    cfg: &Config,
+    with_payload: serde_json::Value,
+    with_vector: bool,
+    filter: Option<serde_json::Value>,
+) -> Result<Vec<StoredPoint>, String> {
+    let url = format!("{}/collections/{}/points/scroll", cfg.qdrant_url, cfg.collection);
+    let mut out = Vec::new();
+    let mut offset = None;
+    loop {
+        let body = ScrollRequest {
+            limit: 256,
+            offset: offset.take(),
+            with_payload: with_payload.clone(),
+            with_vector,
+            filter: filter.clone(),
+        };
+        let res = post_json::<ScrollResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg))?;
+        out.extend(res.result.points);
+        match res.result.next_page_offset {
+            Some(next) if !next.is_null() => offset = Some(next),
+            _ => break,
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Serialize)]
+struct DeletePoints<'a> {
+    points: &'a [serde_json::Value],
+}
+
+pub fn delete_points(cfg: &Config, ids: &[serde_json::Value]) -> Result<(), String> {
+    block_on(delete_points_async(cfg, ids))
+}
+
+pub async fn delete_points_async(cfg: &Config, ids: &[serde_json::Value]) -> Result<(), String> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let url = format!("{}/collections/{}/points/delete?wait=true", cfg.qdrant_url, cfg.collection);
+    let body = DeletePoints { points: ids };
+    let _ = post_json_async::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg)).await?;
+    Ok(())
+}
+
+/// Delete every point whose `path` payload is one of `paths`.
+pub fn delete_points_by_paths(cfg: &Config, paths: &[String]) -> Result<(), String> {
+    let url = format!("{}/collections/{}/points/delete?wait=true", cfg.qdrant_url, cfg.collection);
+    for batch in paths.chunks(256) {
+        let body = serde_json::json!({
+            "filter": { "must": [{ "key": "path", "match": { "any": batch } }] }
+        });
+        let _ = post_json::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg))?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct UpsertStored<'a> {
+    points: Vec<StoredPointBody<'a>>,
+}
+
+#[derive(Serialize)]
+struct StoredPointBody<'a> {
+    id: &'a serde_json::Value,
+    vector: &'a serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<&'a serde_json::Value>,
+}
+
+/// Upsert points read back by `scroll_points_with_vectors`, unchanged.
+pub fn restore_points(cfg: &Config, points: &[StoredPoint]) -> Result<(), String> {
+    let url = format!("{}/collections/{}/points?wait=true", cfg.qdrant_url, cfg.collection);
+    for batch in points.chunks(256) {
+        let body = UpsertStored {
+            points: batch
+                .iter()
+                .filter_map(|p| {
+                    Some(StoredPointBody {
+                        id: &p.id,
+                        vector: p.vector.as_ref()?,
+                        payload: p.payload.as_ref(),
+                    })
+                })
+                .collect(),
+        };
+        let _ = put_json::<QdrantResponse, _>(&url, &body, &HttpOptions::for_qdrant(cfg))?;
+    }
     Ok(())
 }
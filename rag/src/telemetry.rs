@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::timestamps::rfc3339;
+
+/// Opt-in local counters: how often each feature was used and which kinds
+/// of errors happened. Only names and categories are stored, never
+/// questions, answers, paths, or error messages.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryLog {
+    /// Unix time of the first recorded event.
+    #[serde(default, with = "crate::timestamps::rfc3339_secs")]
+    pub since: u64,
+    #[serde(default)]
+    pub features: BTreeMap<String, u64>,
+    /// Keyed by `<area>:<category>`, e.g. `query:timeout`.
+    #[serde(default)]
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// Count one use of `feature` when `AICLI_TELEMETRY` is on.
+pub fn record_feature(cfg: &Config, feature: &str) {
+    if cfg.telemetry {
+        let _ = update(cfg, |log| *log.features.entry(feature.to_string()).or_default() += 1);
+    }
+}
+
+/// Count one error in `area`, keeping only its category.
+pub fn record_error(cfg: &Config, area: &str, err: &str) {
+    if cfg.telemetry {
+        let key = format!("{}:{}", area, error_category(err));
+        let _ = update(cfg, |log| *log.errors.entry(key).or_default() += 1);
+    }
+}
+
+/// A coarse, content-free label for an error message.
+pub fn error_category(err: &str) -> &'static str {
+    let lower = err.to_ascii_lowercase();
+    if lower.contains("safe mode") {
+        "safe_mode"
+    } else if lower.contains("timed out") || lower.contains("timeout") {
+        "timeout"
+    } else if lower.contains("connect") || lower.contains("connection") || lower.contains("dns") {
+        "connection"
+    } else if lower.contains(" 401 ") || lower.contains(" 403 ") || lower.contains("unauthorized") {
+        "auth"
+    } else if lower.contains(" 404 ") || lower.contains("does not exist") || lower.contains("not found") {
+        "not_found"
+    } else if lower.contains(" failed: 5") {
+        "server_error"
+    } else if lower.contains("parse") || lower.contains("decode") || lower.contains("invalid json") {
+        "parse"
+    } else if lower.contains("budget") {
+        "budget"
+    } else if lower.contains("mismatch") || lower.contains("distance") || lower.contains("vector size") {
+        "index_mismatch"
+    } else if lower.contains("failed to read") || lower.contains("failed to write") || lower.contains("failed to create") {
+        "io"
+    } else {
+        "other"
+    }
+}
+
+pub fn telemetry_log(cfg: &Config) -> Result<TelemetryLog, String> {
+    let path = telemetry_path(cfg);
+    if !path.exists() {
+        return Ok(TelemetryLog::default());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Copy the counters to `path` (default `$AICLI_DATA_DIR/telemetry-export-<unix time>.json`)
+/// for review and sharing. Nothing is ever sent automatically.
+pub fn export_telemetry(cfg: &Config, path: Option<&str>) -> Result<PathBuf, String> {
+    let log = telemetry_log(cfg)?;
+    let path = path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(&cfg.data_dir).join(format!("telemetry-export-{}.json", unix_now())));
+    let report = serde_json::json!({
+        "aicli_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "since": rfc3339(log.since),
+        "exported_at": rfc3339(unix_now()),
+        "features": log.features,
+        "errors": log.errors,
+    });
+    let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+pub fn clear_telemetry(cfg: &Config) -> Result<(), String> {
+    let path = telemetry_path(cfg);
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+pub fn telemetry_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("telemetry.json")
+}
+
+fn update(cfg: &Config, apply: impl FnOnce(&mut TelemetryLog)) -> Result<(), String> {
+    let mut log = telemetry_log(cfg)?;
+    if log.since == 0 {
+        log.since = unix_now();
+    }
+    apply(&mut log);
+    let path = telemetry_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(&log).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
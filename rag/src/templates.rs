@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+/// Questions with `{placeholder}`s, keyed by name, e.g.
+/// `errors = summarize errors in service {service} since {date}`.
+pub type Templates = BTreeMap<String, String>;
+
+pub fn templates_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.config_dir).join("templates.json")
+}
+
+pub fn load_templates(cfg: &Config) -> Result<Templates, String> {
+    let path = templates_path(cfg);
+    if !path.exists() {
+        return Ok(Templates::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn save_templates(cfg: &Config, templates: &Templates) -> Result<(), String> {
+    let path = templates_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(templates).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+/// Parse `/template name = text`. The text needs at least one placeholder;
+/// without one, a snippet does the job.
+pub fn parse_template(line: &str) -> Result<(String, String), String> {
+    let (name, text) = line
+        .split_once('=')
+        .ok_or_else(|| "Expected: name = question with {placeholders}".to_string())?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(format!("Invalid template name '{}': use letters, digits, - and _", name));
+    }
+    let text = text.trim();
+    if template_placeholders(text).is_empty() {
+        return Err(format!("Template '{}' has no {{placeholder}}; use a snippet instead", name));
+    }
+    Ok((name.to_string(), text.to_string()))
+}
+
+// Each `{name}` in `text`: its byte range and name. Braces around anything
+// else, such as JSON, are left alone.
+fn placeholder_spans(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find('{').map(|i| from + i) {
+        let name_len = text[open + 1..].find(|c: char| !is_name_char(c)).unwrap_or(text.len() - open - 1);
+        let close = open + 1 + name_len;
+        if name_len > 0 && text[close..].starts_with('}') {
+            out.push((open, close + 1, &text[open + 1..close]));
+            from = close + 1;
+        } else {
+            from = open + 1;
+        }
+    }
+    out
+}
+
+/// The placeholders in `text`, each once, in the order they first appear.
+pub fn template_placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, _, name) in placeholder_spans(text) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// `text` with every placeholder replaced by its value. Fails naming the
+/// placeholders that have no value, or a value that names none.
+pub fn fill_template(text: &str, values: &BTreeMap<String, String>) -> Result<String, String> {
+    let names = template_placeholders(text);
+    let missing: Vec<&str> = names
+        .iter()
+        .filter(|n| !values.contains_key(*n))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing values for: {}", missing.join(", ")));
+    }
+    if let Some(extra) = values.keys().find(|k| !names.contains(k)) {
+        return Err(format!("Unknown placeholder: {} (expected {})", extra, names.join(", ")));
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, name) in placeholder_spans(text) {
+        out.push_str(&text[last..start]);
+        out.push_str(&values[name]);
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    Ok(out)
+}
+
+/// Parse a `--var name=value` argument.
+pub fn parse_template_var(arg: &str) -> Result<(String, String), String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("Expected name=value, got '{}'", arg))?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(format!("Invalid placeholder name '{}'", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
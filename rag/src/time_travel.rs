@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::config::Config;
+use crate::distance::forget_collection;
+use crate::manifest::manifest_path;
+use crate::snapshot::{is_native_snapshot, read_snapshot_header, restore_snapshot};
+use crate::store_qdrant::delete_collection;
+use crate::timestamps::{parse_rfc3339, utc_offset};
+
+/// A saved snapshot that questions can be asked against.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotEntry {
+    pub path: PathBuf,
+    pub collection: String,
+    /// Unix seconds it was taken: the time in a default file name
+    /// (`<collection>-<unix time>.jsonl`), else when the file was written.
+    pub created_at: u64,
+}
+
+pub fn snapshots_dir(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("snapshots")
+}
+
+/// Describe the snapshot at `path`; `None` for manifests and other files.
+pub fn snapshot_entry(path: &Path) -> Option<SnapshotEntry> {
+    let name = path.file_name()?.to_str()?;
+    let native = is_native_snapshot(path);
+    if name.ends_with(".manifest.json") || !(native || name.ends_with(".jsonl")) {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (prefix, stamp) = match stem.rsplit_once('-') {
+        Some((prefix, stamp)) => (prefix, stamp.parse::<u64>().ok()),
+        None => (stem, None),
+    };
+    let collection = match native {
+        true => prefix.to_string(),
+        false => read_snapshot_header(path).ok()?.collection,
+    };
+    let created_at = stamp.or_else(|| {
+        let modified = fs::metadata(path).ok()?.modified().ok()?;
+        modified.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+    })?;
+    Some(SnapshotEntry {
+        path: path.to_path_buf(),
+        collection,
+        created_at,
+    })
+}
+
+/// Snapshots of `cfg.collection` in `$AICLI_DATA_DIR/snapshots`, oldest
+/// first.
+pub fn list_snapshots(cfg: &Config) -> Vec<SnapshotEntry> {
+    let Ok(entries) = fs::read_dir(snapshots_dir(cfg)) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<SnapshotEntry> = entries
+        .filter_map(|entry| snapshot_entry(&entry.ok()?.path()))
+        .filter(|snapshot| snapshot.collection == cfg.collection)
+        .collect();
+    snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.path.cmp(&b.path)));
+    snapshots
+}
+
+/// The end of `spec` in Unix seconds: a date such as `2024-06-01` means the
+/// last second of that day in `AICLI_TIME_ZONE`; an RFC3339 time is exact.
+pub fn parse_as_of(cfg: &Config, spec: &str) -> Option<u64> {
+    let spec = spec.trim();
+    if spec.len() == 10 {
+        let end_of_day = parse_rfc3339(&format!("{}T23:59:59Z", spec))? as i64;
+        return u64::try_from(end_of_day - utc_offset(cfg) as i64).ok();
+    }
+    parse_rfc3339(spec)
+}
+
+/// The snapshot `spec` names: a snapshot file, or the newest snapshot of
+/// `cfg.collection` taken no later than a date or time (see `parse_as_of`).
+pub fn find_snapshot(cfg: &Config, spec: &str) -> Result<SnapshotEntry, String> {
+    let path = Path::new(spec);
+    if path.is_file() {
+        return snapshot_entry(path).ok_or_else(|| format!("{} is not an aicli snapshot", spec));
+    }
+    let cutoff = parse_as_of(cfg, spec).ok_or_else(|| {
+        format!("Expected a date like 2024-06-01, an RFC3339 time, or a snapshot file, got '{}'", spec)
+    })?;
+    list_snapshots(cfg)
+        .into_iter()
+        .rev()
+        .find(|snapshot| snapshot.created_at <= cutoff)
+        .ok_or_else(|| {
+            format!("No snapshot of {} was taken by {}; see `aicli snapshot list`", cfg.collection, spec)
+        })
+}
+
+/// Load `snapshot` into a temporary collection, run `query` against it, and
+/// drop the collection again whether or not the query succeeds. Attached
+/// files and the answer cache are left out so only the snapshot answers.
+pub fn with_snapshot<T>(
+    cfg: &Config,
+    snapshot: &SnapshotEntry,
+    query: impl FnOnce(&Config) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut snapshot_cfg = cfg.clone();
+    snapshot_cfg.collection = format!("{}_asof_{}", snapshot.collection, snapshot.created_at);
+    snapshot_cfg.attachments.clear();
+    snapshot_cfg.answer_cache = false;
+    if let Ok(header) = read_snapshot_header(&snapshot.path) {
+        snapshot_cfg.distance = header.distance;
+    }
+    let restored = restore_snapshot(&snapshot_cfg, &snapshot.path.to_string_lossy(), true);
+    let result = restored.and_then(|_| query(&snapshot_cfg));
+    let _ = delete_collection(&snapshot_cfg);
+    forget_collection(&snapshot_cfg);
+    let _ = fs::remove_file(manifest_path(&snapshot_cfg));
+    result
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Deserializer, Serializer};
+use serde_json::Value;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+
+/// How a locale writes dates; times are 24-hour except in `MonthFirst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateStyle {
+    /// `2026-10-16 14:03`: C/POSIX, Japanese, Chinese, Korean, Swedish.
+    Iso,
+    /// `10/16/2026 2:03 PM`: United States English.
+    MonthFirst,
+    /// `16/10/2026 14:03`: British English, French, Spanish, Italian, ...
+    DayFirst,
+    /// `16.10.2026 14:03`: German, Russian, Polish, Finnish, ...
+    DayFirstDot,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian year, month, and day.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day as u32)
+}
+
+// The inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Seconds since the epoch as year, month, day, hour, minute, and second.
+fn fields(secs: i64) -> (i64, u32, u32, u32, u32, u32) {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let time = secs.rem_euclid(86_400);
+    (year, month, day, (time / 3600) as u32, (time % 3600 / 60) as u32, (time % 60) as u32)
+}
+
+/// Unix seconds as an RFC3339 UTC time, e.g. `2026-10-16T12:03:09Z`.
+pub fn rfc3339(secs: u64) -> String {
+    let (year, month, day, hour, minute, second) = fields(secs as i64);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+}
+
+/// Unix milliseconds as an RFC3339 UTC time with milliseconds, e.g.
+/// `2026-10-16T12:03:09.250Z`.
+pub fn rfc3339_millis(millis: u64) -> String {
+    let seconds = rfc3339(millis / 1000);
+    format!("{}.{:03}Z", seconds.trim_end_matches('Z'), millis % 1000)
+}
+
+/// Unix seconds from an RFC3339 time such as `2026-10-16T14:03:09+02:00`
+/// or `2026-10-16 12:03:09.5Z`; fractions of a second are dropped. `None`
+/// for anything else or a time before 1970.
+pub fn parse_rfc3339(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<u32>().ok();
+    let bytes = text.as_bytes();
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    if !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut rest = &text[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+        if digits == 0 {
+            return None;
+        }
+        rest = &fraction[digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 => parse_utc_offset(rest)?,
+        _ => return None,
+    };
+    let days = days_from_civil(year as i64, month, day);
+    let secs = days * 86_400 + (hour * 3600 + minute * 60 + second) as i64 - offset as i64;
+    u64::try_from(secs).ok()
+}
+
+/// Seconds east of UTC for `+02:00`, `-0530`, `+02`, `Z`, or `UTC`.
+pub fn parse_utc_offset(text: &str) -> Option<i32> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("z") || text.eq_ignore_ascii_case("utc") {
+        return Some(0);
+    }
+    let sign = match text.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: String = text[1..].chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) || !matches!(digits.len(), 2 | 4) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = if digits.len() == 4 { digits[2..].parse().ok()? } else { 0 };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The date style for a POSIX locale name such as `en_US.UTF-8` or `de_DE`.
+pub fn date_style(locale: &str) -> DateStyle {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let (language, region) = name.split_once(['_', '-']).unwrap_or((name, ""));
+    match (language.to_ascii_lowercase().as_str(), region.to_ascii_uppercase().as_str()) {
+        ("en", "US" | "PH") => DateStyle::MonthFirst,
+        ("en" | "fr" | "es" | "it" | "pt" | "nl" | "el" | "ga" | "ca" | "vi" | "id" | "ms" | "ar" | "he", _) => {
+            DateStyle::DayFirst
+        }
+        ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "da" | "tr" | "uk" | "ro" | "et" | "lv", _) => {
+            DateStyle::DayFirstDot
+        }
+        _ => DateStyle::Iso,
+    }
+}
+
+/// Format Unix seconds for people: in the zone `offset` seconds east of
+/// UTC, in `style`, with the time only when `with_date` is false.
+pub fn format_time(secs: u64, offset: i32, style: DateStyle, with_date: bool) -> String {
+    let (year, month, day, hour, minute, _) = fields(secs as i64 + offset as i64);
+    let time = match style {
+        DateStyle::MonthFirst => {
+            let half = if hour < 12 { "AM" } else { "PM" };
+            format!("{}:{:02} {}", (hour + 11) % 12 + 1, minute, half)
+        }
+        _ => format!("{:02}:{:02}", hour, minute),
+    };
+    if !with_date {
+        return time;
+    }
+    match style {
+        DateStyle::Iso => format!("{:04}-{:02}-{:02} {}", year, month, day, time),
+        DateStyle::MonthFirst => format!("{:02}/{:02}/{:04} {}", month, day, year, time),
+        DateStyle::DayFirst => format!("{:02}/{:02}/{:04} {}", day, month, year, time),
+        DateStyle::DayFirstDot => format!("{:02}.{:02}.{:04} {}", day, month, year, time),
+    }
+}
+
+/// Seconds east of UTC for `AICLI_TIME_ZONE`: `UTC`, a fixed offset such as
+/// `+05:30`, or `local` (the default) for the system's current offset.
+pub fn utc_offset(cfg: &Config) -> i32 {
+    parse_utc_offset(&cfg.time_zone).unwrap_or_else(local_offset)
+}
+
+// The system's current UTC offset, asked once; UTC when it cannot be read.
+fn local_offset() -> i32 {
+    static OFFSET: OnceLock<i32> = OnceLock::new();
+    *OFFSET.get_or_init(|| {
+        let output = if cfg!(windows) {
+            Command::new("powershell")
+                .args(["-NoProfile", "-Command", "(Get-Date).ToString('zzz')"])
+                .output()
+        } else {
+            Command::new("date").arg("+%z").output()
+        };
+        output
+            .ok()
+            .filter(|out| out.status.success())
+            .and_then(|out| parse_utc_offset(&String::from_utf8_lossy(&out.stdout)))
+            .unwrap_or(0)
+    })
+}
+
+/// Unix seconds as a local date and time in the `AICLI_LOCALE` style.
+pub fn format_local(cfg: &Config, secs: u64) -> String {
+    format_time(secs, utc_offset(cfg), date_style(&cfg.locale), true)
+}
+
+/// Unix seconds as a local time of day in the `AICLI_LOCALE` style.
+pub fn format_local_time(cfg: &Config, secs: u64) -> String {
+    format_time(secs, utc_offset(cfg), date_style(&cfg.locale), false)
+}
+
+// A stored time as Unix seconds: an RFC3339 string, or the plain number
+// files written before times were RFC3339.
+fn stored_secs(value: Value) -> Result<Option<u64>, String> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(n) => n.as_u64().map(Some).ok_or_else(|| format!("invalid time: {}", n)),
+        Value::String(s) => parse_rfc3339(&s).map(Some).ok_or_else(|| format!("invalid RFC3339 time: {}", s)),
+        other => Err(format!("invalid time: {}", other)),
+    }
+}
+
+/// `#[serde(with = ...)]` for Unix seconds stored as RFC3339.
+pub(crate) mod rfc3339_secs {
+    use super::*;
+    use serde::de::Error as _;
+
+    pub fn serialize<S: Serializer>(secs: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&rfc3339(*secs))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        stored_secs(Value::deserialize(deserializer)?)
+            .map(Option::unwrap_or_default)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// `#[serde(with = ...)]` for optional Unix seconds stored as RFC3339.
+pub(crate) mod rfc3339_secs_opt {
+    use super::*;
+    use serde::de::Error as _;
+
+    pub fn serialize<S: Serializer>(secs: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match secs {
+            Some(secs) => serializer.serialize_str(&rfc3339(*secs)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        stored_secs(Value::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,47 @@
+// BPE vocabularies split English words and identifiers into pieces of about
+// four characters; punctuation and symbols are mostly a token each.
+const CHARS_PER_WORD_TOKEN: usize = 4;
+
+/// Approximate token count in the style of tiktoken: runs of ASCII letters
+/// and digits cost one token per four characters, every other non-space
+/// character (punctuation, CJK, emoji) costs one.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut counter = TokenCounter::default();
+    text.chars().map(|c| counter.cost(c)).sum()
+}
+
+/// The longest prefix of `text` estimated at no more than `max_tokens`,
+/// cut at a line break when one is available.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> &str {
+    let mut counter = TokenCounter::default();
+    let mut used = 0usize;
+    let mut last_break = 0usize;
+    for (idx, c) in text.char_indices() {
+        let cost = counter.cost(c);
+        if used + cost > max_tokens {
+            return if last_break > 0 { &text[..last_break] } else { &text[..idx] };
+        }
+        used += cost;
+        if c == '\n' {
+            last_break = idx;
+        }
+    }
+    text
+}
+
+#[derive(Default)]
+struct TokenCounter {
+    run: usize,
+}
+
+impl TokenCounter {
+    /// Tokens added by appending `c`.
+    fn cost(&mut self, c: char) -> usize {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            self.run += 1;
+            return usize::from(self.run % CHARS_PER_WORD_TOKEN == 1);
+        }
+        self.run = 0;
+        usize::from(!c.is_whitespace())
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::mcp::McpTool;
+
+/// A tool implemented in Rust by a library user. Registered tools are
+/// offered to the agent next to the built-in and MCP tools, and a
+/// `tool` action naming one runs `execute` instead of calling MCP.
+///
+/// ```ignore
+/// struct Clock;
+///
+/// impl Tool for Clock {
+///     fn name(&self) -> &str {
+///         "clock"
+///     }
+///     fn execute(&self, _args: &Value) -> Result<String, String> {
+///         Ok(format!("{:?}", std::time::SystemTime::now()))
+///     }
+/// }
+///
+/// let options = AgentOptions { tools: vec![Arc::new(Clock)], ..AgentOptions::from_config(&cfg) };
+/// ```
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Shown to the model with the tool's name.
+    fn description(&self) -> &str {
+        ""
+    }
+
+    /// JSON schema for the arguments; calls are checked against it before
+    /// `execute` runs. Any object is accepted by default.
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object" })
+    }
+
+    /// Run the tool. The text returned goes back to the model as the tool
+    /// result; an error is reported to it the same way an MCP failure is.
+    fn execute(&self, args: &Value) -> Result<String, String>;
+}
+
+impl fmt::Debug for dyn Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tool({})", self.name())
+    }
+}
+
+/// The tool in the shape MCP servers describe theirs.
+pub fn tool_spec(tool: &dyn Tool) -> McpTool {
+    McpTool {
+        name: tool.name().to_string(),
+        description: tool.description().to_string(),
+        input_schema: tool.input_schema(),
+    }
+}
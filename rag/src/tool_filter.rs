@@ -0,0 +1,73 @@
+use crate::config::Config;
+use crate::mcp::McpCapabilities;
+
+/// Which MCP server tools the agent may see and call, from
+/// `MCP_TOOL_ALLOWLIST` and `MCP_TOOL_DENYLIST` (comma-separated globs where
+/// `*` matches any run of characters and `?` one character). A tool must
+/// match the allowlist, when one is set, and must not match the denylist.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToolFilter {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl ToolFilter {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            allow: cfg.mcp_tool_allowlist.clone(),
+            deny: cfg.mcp_tool_denylist.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    pub fn permits(&self, tool: &str) -> bool {
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|p| glob_match(p, tool));
+        allowed && !self.deny.iter().any(|p| glob_match(p, tool))
+    }
+
+    /// Drop the tools it does not permit. Prompts and resources are kept.
+    pub fn apply(&self, caps: &mut McpCapabilities) {
+        if self.is_empty() {
+            return;
+        }
+        caps.tools.retain(|name| self.permits(name));
+        caps.tool_specs.retain(|tool| self.permits(&tool.name));
+    }
+
+    /// What the model is told when it asks for a tool that is filtered out.
+    pub fn blocked_message(&self, tool: &str) -> String {
+        format!(
+            "MCP tool '{}' is not allowed in this session (MCP_TOOL_ALLOWLIST / MCP_TOOL_DENYLIST). Use one of the listed tools, retrieve, or final.",
+            tool
+        )
+    }
+}
+
+/// Whole-string glob match of `text` against `pattern`, where `*` matches
+/// any run of characters and `?` exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it is matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,79 @@
+use serde_json::Value;
+
+/// Check tool arguments against the tool's JSON input schema before the
+/// call goes out. Covers what MCP servers use in practice: `type`,
+/// `required`, `properties`, `additionalProperties: false`, `enum`, and
+/// `items`. Other keywords are not enforced.
+pub fn validate_tool_args(schema: &Value, args: &Value) -> Result<(), String> {
+    validate_at(schema, args, "arguments")
+}
+
+fn validate_at(schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| has_type(value, t)) {
+            return Err(format!("{} should be {}, got {}", at, allowed.join(" or "), type_name(value)));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let listed: Vec<String> = options.iter().map(Value::to_string).collect();
+            return Err(format!("{} should be one of {}", at, listed.join(", ")));
+        }
+    }
+    if let Some(obj) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for key in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            if let Some(key) = key.as_str() {
+                if !obj.contains_key(key) {
+                    return Err(format!("{} is missing required field \"{}\"", at, key));
+                }
+            }
+        }
+        let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+        for (key, field) in obj {
+            match properties.and_then(|p| p.get(key)) {
+                Some(sub) => validate_at(sub, field, &format!("{}.{}", at, key))?,
+                None if closed => return Err(format!("{} has unknown field \"{}\"", at, key)),
+                None => {}
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            validate_at(items, item, &format!("{}[{}]", at, i))?;
+        }
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
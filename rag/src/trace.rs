@@ -0,0 +1,50 @@
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::timestamps::rfc3339_millis;
+
+/// Create `$AICLI_DATA_DIR/traces/<unix millis>.jsonl` for one agent run
+/// when `RAG_TRACE` is on. Safe mode writes no traces.
+pub fn start_trace(cfg: &Config) -> Result<Option<PathBuf>, String> {
+    if cfg.safe_mode || !cfg.trace {
+        return Ok(None);
+    }
+    let dir = traces_dir(cfg);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.jsonl", unix_millis()));
+    fs::write(&path, "").map_err(|e| format!("Failed to create trace {}: {}", path.display(), e))?;
+    Ok(Some(path))
+}
+
+pub fn traces_dir(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("traces")
+}
+
+/// Append one `{"kind": ..., "at": <RFC3339>, "at_ms": ..., ...fields}`
+/// line. Model responses use the kinds `json` and `chat` with a `response`
+/// field, so a trace doubles as a replay fixture for
+/// `RAG_LLM_PROVIDER=replay`.
+pub fn trace_event(path: &Path, kind: &str, fields: Value) -> Result<(), String> {
+    let at_ms = unix_millis();
+    let mut line = json!({ "kind": kind, "at": rfc3339_millis(at_ms), "at_ms": at_ms });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    let text = serde_json::to_string(&line).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open trace {}: {}", path.display(), e))?;
+    writeln!(file, "{}", text).map_err(|e| format!("Failed to write trace {}: {}", path.display(), e))
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
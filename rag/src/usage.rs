@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::timestamps::civil_from_days;
+
+/// Token counts and estimated cost for one or more model calls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.calls += other.calls;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// What was used after `earlier`, a reading of the same meter.
+    pub fn since(&self, earlier: &Usage) -> Usage {
+        Usage {
+            calls: self.calls.saturating_sub(earlier.calls),
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self.completion_tokens.saturating_sub(earlier.completion_tokens),
+            cost_usd: (self.cost_usd - earlier.cost_usd).max(0.0),
+        }
+    }
+}
+
+// A query runs start to finish on one blocking thread, so a thread-local meter
+// attributes calls to the right query without threading it through every API.
+thread_local! {
+    static CURRENT: RefCell<Usage> = RefCell::new(Usage::default());
+}
+
+/// Count one model call toward this thread's query. Custom tools that call
+/// a model themselves can report it here.
+pub fn record_call(cfg: &Config, prompt_tokens: u64, completion_tokens: u64) {
+    let cost = estimate_cost(cfg, prompt_tokens, completion_tokens);
+    CURRENT.with(|u| {
+        u.borrow_mut().add(&Usage {
+            calls: 1,
+            prompt_tokens,
+            completion_tokens,
+            cost_usd: cost,
+        })
+    });
+}
+
+pub fn reset_usage() {
+    CURRENT.with(|u| *u.borrow_mut() = Usage::default());
+}
+
+pub fn take_usage() -> Usage {
+    CURRENT.with(|u| std::mem::take(&mut *u.borrow_mut()))
+}
+
+/// This thread's meter so far, without resetting it.
+pub fn current_usage() -> Usage {
+    CURRENT.with(|u| *u.borrow())
+}
+
+// Credit calls made on a helper thread to this thread's query.
+pub(crate) fn add_usage(usage: &Usage) {
+    CURRENT.with(|u| u.borrow_mut().add(usage));
+}
+
+/// Estimated USD cost from the `RAG_PRICE_TABLE` entry for the chat model.
+/// Models without an entry (local Ollama models) cost nothing.
+pub fn estimate_cost(cfg: &Config, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+    match cfg.price_table.get(&cfg.chat_model) {
+        Some(price) => {
+            (prompt_tokens as f64 * price.input_per_mtok + completion_tokens as f64 * price.output_per_mtok)
+                / 1_000_000.0
+        }
+        None => 0.0,
+    }
+}
+
+/// Add `usage` to today's ledger entry and return the updated daily total.
+/// Nothing is written in safe mode.
+pub fn record_daily_usage(cfg: &Config, usage: &Usage) -> Result<Usage, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: usage is not logged.".to_string());
+    }
+    let mut ledger = daily_usage(cfg)?;
+    let today = ledger.entry(today()).or_default();
+    today.add(usage);
+    let total = *today;
+    let path = ledger_path(cfg);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(&ledger).map_err(|e| e.to_string())?;
+    fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(total)
+}
+
+/// Per-day usage totals keyed by `YYYY-MM-DD` (UTC).
+pub fn daily_usage(cfg: &Config) -> Result<BTreeMap<String, Usage>, String> {
+    let path = ledger_path(cfg);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn budget_exceeded(cfg: &Config, today_total: &Usage) -> bool {
+    cfg.daily_budget_usd
+        .map(|budget| today_total.cost_usd >= budget)
+        .unwrap_or(false)
+}
+
+fn ledger_path(cfg: &Config) -> PathBuf {
+    PathBuf::from(&cfg.data_dir).join("usage.json")
+}
+
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    civil_date((secs / 86_400) as i64)
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian `YYYY-MM-DD`.
+pub(crate) fn civil_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
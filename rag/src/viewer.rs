@@ -0,0 +1,324 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::markdown::render_markdown;
+use crate::platform::{open_command, shell_command};
+
+/// What a viewer gets and how it runs. A pager takes over the terminal
+/// while the TUI waits; the others are started in the background.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewerKind {
+    /// The Markdown answer, for `less`, `glow`, an editor, ...
+    Pager,
+    /// The answer rendered as an HTML page, for a browser.
+    Html,
+    /// The answer as plain text without Markdown syntax, for a TTS command.
+    Speak,
+}
+
+impl ViewerKind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ViewerKind::Pager => "md",
+            ViewerKind::Html => "html",
+            ViewerKind::Speak => "txt",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewer {
+    pub name: String,
+    pub kind: ViewerKind,
+    /// Shell command; `{file}` is replaced by the quoted path, which is
+    /// otherwise appended. Empty opens the file with the desktop default.
+    pub command: String,
+}
+
+impl Viewer {
+    /// Used by `/view` when `AICLI_VIEWERS` is unset: the rendered answer
+    /// in the default browser.
+    pub fn browser() -> Self {
+        Viewer {
+            name: "browser".to_string(),
+            kind: ViewerKind::Html,
+            command: String::new(),
+        }
+    }
+}
+
+/// Parse `AICLI_VIEWERS`: `name=kind:<shell command>` entries separated by
+/// `;`, where kind is `pager`, `html`, or `speak`, e.g.
+/// `less=pager:less -R; web=html:; say=speak:say -f {file}`.
+pub fn parse_viewers(raw: &str) -> Vec<Viewer> {
+    let mut out = Vec::new();
+    for entry in raw.split(';') {
+        let Some((name, spec)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some((kind, command)) = spec.split_once(':') else {
+            continue;
+        };
+        let kind = match kind.trim().to_ascii_lowercase().as_str() {
+            "pager" => ViewerKind::Pager,
+            "html" => ViewerKind::Html,
+            "speak" => ViewerKind::Speak,
+            _ => continue,
+        };
+        let name = name.trim();
+        if !name.is_empty() {
+            out.push(Viewer {
+                name: name.to_string(),
+                kind,
+                command: command.trim().to_string(),
+            });
+        }
+    }
+    out
+}
+
+/// Write `answer` to a new temp file in the form `viewer` expects.
+pub fn write_view_file(viewer: &Viewer, title: &str, answer: &str) -> Result<PathBuf, String> {
+    let body = match viewer.kind {
+        ViewerKind::Pager => answer.to_string(),
+        ViewerKind::Html => answer_html(title, answer),
+        ViewerKind::Speak => plain_text(answer),
+    };
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!(
+        "aicli-answer-{}-{}.{}",
+        std::process::id(),
+        stamp,
+        viewer.kind.extension()
+    ));
+    std::fs::write(&path, body).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The command that shows `path` with `viewer`. The path is also in
+/// `AICLI_VIEW_FILE`. Refused in safe mode, like share commands.
+pub fn viewer_command(cfg: &Config, viewer: &Viewer, path: &Path) -> Result<Command, String> {
+    if cfg.safe_mode {
+        return Err("Safe mode is on: external viewers are disabled.".to_string());
+    }
+    let file = path.to_string_lossy().to_string();
+    let mut command = if viewer.command.is_empty() {
+        open_command(&file)
+    } else if viewer.command.contains("{file}") {
+        shell_command(&cfg.shell, &viewer.command.replace("{file}", &quote_path(&file)))
+    } else {
+        shell_command(&cfg.shell, &format!("{} {}", viewer.command, quote_path(&file)))
+    };
+    command.env("AICLI_VIEW_FILE", &file);
+    Ok(command)
+}
+
+fn quote_path(path: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+/// The answer as a standalone HTML page: headings, lists, paragraphs,
+/// fenced code, inline code, bold, and links. Tables are box-drawn as in
+/// the TUI and kept preformatted.
+pub fn answer_html(title: &str, answer: &str) -> String {
+    let mut body = String::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut list: Option<&str> = None;
+    let mut lines = answer.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let is_item = list_item(trimmed).is_some();
+        let is_block = trimmed.starts_with("```") || trimmed.starts_with('#') || trimmed.starts_with('|');
+        if trimmed.is_empty() || is_block || is_item {
+            flush_paragraph(&mut body, &mut paragraph);
+        }
+        if !is_item {
+            if let Some(tag) = list.take() {
+                body.push_str(&format!("</{}>\n", tag));
+            }
+        }
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            let mut code = Vec::new();
+            for inner in lines.by_ref() {
+                if inner.trim_start().starts_with("```") {
+                    break;
+                }
+                code.push(escape_html(inner));
+            }
+            let class = match lang.trim() {
+                "" => String::new(),
+                lang => format!(" class=\"language-{}\"", escape_html(lang)),
+            };
+            body.push_str(&format!("<pre><code{}>{}</code></pre>\n", class, code.join("\n")));
+        } else if trimmed.starts_with('|') {
+            let mut table = vec![line.to_string()];
+            while let Some(next) = lines.peek().filter(|l| l.trim_start().starts_with('|')) {
+                table.push(next.to_string());
+                lines.next();
+            }
+            body.push_str(&format!("<pre>{}</pre>\n", escape_html(&render_markdown(&table.join("\n")))));
+        } else if let Some((level, text)) = heading(trimmed) {
+            body.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline_html(text)));
+        } else if let Some((tag, text)) = list_item(trimmed) {
+            if list != Some(tag) {
+                if let Some(open) = list.take() {
+                    body.push_str(&format!("</{}>\n", open));
+                }
+                body.push_str(&format!("<{}>\n", tag));
+                list = Some(tag);
+            }
+            body.push_str(&format!("<li>{}</li>\n", inline_html(text)));
+        } else if !trimmed.is_empty() {
+            paragraph.push(inline_html(trimmed));
+        }
+    }
+    flush_paragraph(&mut body, &mut paragraph);
+    if let Some(tag) = list {
+        body.push_str(&format!("</{}>\n", tag));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ max-width: 48rem; margin: 2rem auto; padding: 0 1rem; font-family: sans-serif; line-height: 1.5; }}\n\
+         pre {{ background: #f4f4f4; padding: 0.75rem; overflow-x: auto; }}\n\
+         </style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+fn flush_paragraph(body: &mut String, paragraph: &mut Vec<String>) {
+    if !paragraph.is_empty() {
+        body.push_str(&format!("<p>{}</p>\n", paragraph.join("\n")));
+        paragraph.clear();
+    }
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+fn list_item(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(text) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(("ul", text));
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(text) = line[digits..].strip_prefix(". ") {
+            return Some(("ol", text));
+        }
+    }
+    None
+}
+
+// `code`, **bold**, and [text](url), with everything else escaped.
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                out.push_str(&format!("<code>{}</code>", escape_html(&after[..end])));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**") {
+                out.push_str(&format!("<strong>{}</strong>", inline_html(&after[..end])));
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some((label, url, len)) = link(after) {
+                out.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(url), inline_html(label)));
+                rest = &after[len..];
+                continue;
+            }
+        }
+        let ch = rest.chars().next().unwrap_or_default();
+        out.push_str(&escape_html(&ch.to_string()));
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
+
+// `label](url)` at the start of `text`: the label, the url, and the length.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let after = &text[close + 2..];
+    let end = after.find(')')?;
+    Some((&text[..close], &after[..end], close + 2 + end + 1))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The answer without Markdown syntax, for reading aloud: fences, heading
+/// marks, emphasis, and inline code backticks are dropped, links keep
+/// their text, and table rows become comma-separated cells.
+pub fn plain_text(answer: &str) -> String {
+    let mut out = Vec::new();
+    for line in answer.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            continue;
+        }
+        if trimmed.starts_with('|') {
+            let cells: Vec<&str> = trimmed
+                .trim_matches('|')
+                .split('|')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .collect();
+            if cells.iter().all(|c| c.chars().all(|ch| matches!(ch, '-' | ':'))) {
+                continue;
+            }
+            out.push(cells.join(", "));
+            continue;
+        }
+        let text = heading(trimmed).map(|(_, text)| text).unwrap_or(line);
+        let text = list_item(text.trim()).map(|(_, item)| item).unwrap_or(text);
+        out.push(strip_inline(text));
+    }
+    let mut text = out.join("\n");
+    if answer.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+fn strip_inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some((label, _, len)) = link(after) {
+                out.push_str(label);
+                rest = &after[len..];
+                continue;
+            }
+        }
+        let ch = rest.chars().next().unwrap_or_default();
+        if !matches!(ch, '`' | '*') {
+            out.push(ch);
+        }
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
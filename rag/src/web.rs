@@ -0,0 +1,329 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::config::Config;
+use crate::http::{get_text_async, HttpOptions};
+
+pub fn is_url(source: &str) -> bool {
+    let lower = source.trim().to_ascii_lowercase();
+    lower.starts_with("https://") || lower.starts_with("http://")
+}
+
+/// A fetched page: its URL, visible text, and the headings that carry an
+/// `id`, in document order, so chunks can be cited with a `#fragment`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebPage {
+    pub url: String,
+    pub text: String,
+    pub headings: Vec<Heading>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    pub id: String,
+    pub text: String,
+}
+
+impl WebPage {
+    fn from_html(url: String, html: &str) -> Option<Self> {
+        let text = html_to_text(html);
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            url,
+            text,
+            headings: page_headings(html),
+        })
+    }
+}
+
+/// Fetch `start` as pages of visible text. A `sitemap.xml` is expanded into
+/// its `<loc>` entries; otherwise links are followed breadth-first up to
+/// `RAG_CRAWL_DEPTH`, staying on the same host unless configured otherwise.
+pub async fn fetch_web_sources(cfg: &Config, start: &str) -> Result<Vec<WebPage>, String> {
+    let start = start.trim();
+    let opts = HttpOptions::standard(cfg);
+    if start.to_ascii_lowercase().ends_with(".xml") {
+        let xml = get_text_async(start, &opts).await?;
+        let mut out = Vec::new();
+        for loc in sitemap_locations(&xml).into_iter().take(cfg.crawl_max_pages) {
+            if let Ok(html) = get_text_async(&loc, &opts).await {
+                if let Some(page) = WebPage::from_html(loc, &html) {
+                    out.push(page);
+                }
+            }
+        }
+        return Ok(out);
+    }
+
+    let start_host = host_of(start).unwrap_or_default();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<(String, usize)> = VecDeque::new();
+    let mut out = Vec::new();
+    queue.push_back((strip_fragment(start), 0));
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if out.len() >= cfg.crawl_max_pages || !seen.insert(url.clone()) {
+            continue;
+        }
+        let html = match get_text_async(&url, &opts).await {
+            Ok(html) => html,
+            // The start page failing is an error; dead links further down are not.
+            Err(err) if depth == 0 => return Err(err),
+            Err(_) => continue,
+        };
+        if depth < cfg.crawl_depth {
+            for link in extract_links(&url, &html) {
+                let same_host = host_of(&link).map(|h| h == start_host).unwrap_or(false);
+                if (same_host || !cfg.crawl_same_domain) && !seen.contains(&link) {
+                    queue.push_back((link, depth + 1));
+                }
+            }
+        }
+        if let Some(page) = WebPage::from_html(url, &html) {
+            out.push(page);
+        }
+    }
+    Ok(out)
+}
+
+/// Strip scripts, styles, and page chrome (nav, header, footer, aside) and
+/// return the remaining visible text with collapsed whitespace.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = html.to_string();
+    for tag in ["script", "style", "noscript", "nav", "header", "footer", "aside", "svg"] {
+        text = remove_elements(&text, tag);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    let mut name_done = false;
+    let mut tag_name = String::new();
+    for c in text.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                name_done = false;
+                tag_name.clear();
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag_name.trim_start_matches('/').to_ascii_lowercase();
+                if matches!(
+                    name.as_str(),
+                    "p" | "div" | "br" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "pre" | "section" | "article"
+                ) {
+                    out.push('\n');
+                }
+            }
+            _ if in_tag => {
+                if c.is_whitespace() || (c == '/' && !tag_name.is_empty()) {
+                    name_done = true;
+                } else if !name_done {
+                    tag_name.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    let decoded = decode_entities(&out);
+    decoded
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `<h1>`..`<h6>` elements with an `id` attribute, with their text collapsed
+/// the same way `html_to_text` does.
+pub fn page_headings(html: &str) -> Vec<Heading> {
+    let lower = html.to_ascii_lowercase();
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find("<h") {
+        let start = pos + rel;
+        pos = start + 2;
+        let level = lower[pos..].chars().next();
+        if !matches!(level, Some('1'..='6')) {
+            continue;
+        }
+        let Some(tag_end) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        let close = format!("</h{}", level.unwrap_or('1'));
+        let Some(close_at) = lower[tag_end..].find(&close).map(|i| tag_end + i) else {
+            break;
+        };
+        pos = close_at;
+        let Some(id) = attribute(&html[start..tag_end], "id") else {
+            continue;
+        };
+        let text = html_to_text(&html[tag_end + 1..close_at]).replace('\n', " ");
+        if !text.is_empty() {
+            out.push(Heading { id, text });
+        }
+    }
+    out
+}
+
+/// The id of the last heading that starts at or before the byte offset
+/// `chunk_offset` in `page_text`, where a chunk of the page begins.
+pub fn anchor_for_chunk(page_text: &str, headings: &[Heading], chunk_offset: usize) -> Option<String> {
+    let mut anchor = None;
+    let mut search_from = 0;
+    for heading in headings {
+        let Some(rel) = page_text[search_from..].find(&heading.text) else {
+            continue;
+        };
+        let at = search_from + rel;
+        if at > chunk_offset {
+            break;
+        }
+        anchor = Some(heading.id.clone());
+        search_from = at + heading.text.len();
+    }
+    anchor
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find(name) {
+        let start = pos + rel;
+        pos = start + name.len();
+        let preceded_by_space = lower[..start].ends_with(|c: char| c.is_whitespace());
+        let rest = lower[pos..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let value_at = tag.len() - rest.len() + 1;
+        let value = tag[value_at..].trim_start();
+        let quote = value.chars().next()?;
+        let value = if quote == '"' || quote == '\'' {
+            value[1..].split(quote).next()?
+        } else {
+            value.split(|c: char| c.is_whitespace() || c == '>').next()?
+        };
+        return (!value.is_empty()).then(|| value.to_string());
+    }
+    None
+}
+
+fn remove_elements(html: &str, tag: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut out = String::with_capacity(html.len());
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find(&open) {
+        let start = pos + rel;
+        // Make sure `<nav` did not match `<navbar-thing`.
+        let next = lower[start + open.len()..].chars().next();
+        if !matches!(next, Some('>') | Some(' ') | Some('\n') | Some('\t') | Some('/')) {
+            out.push_str(&html[pos..start + open.len()]);
+            pos = start + open.len();
+            continue;
+        }
+        out.push_str(&html[pos..start]);
+        match lower[start..].find(&close) {
+            Some(end_rel) => pos = start + end_rel + close.len(),
+            None => {
+                pos = html.len();
+                break;
+            }
+        }
+    }
+    out.push_str(&html[pos..]);
+    out
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn sitemap_locations(xml: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<loc>") {
+        let after = &rest[start + 5..];
+        let Some(end) = after.find("</loc>") else {
+            break;
+        };
+        let loc = decode_entities(after[..end].trim());
+        if !loc.is_empty() {
+            out.push(loc);
+        }
+        rest = &after[end + 6..];
+    }
+    out
+}
+
+fn extract_links(base: &str, html: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find("href=") {
+        let start = pos + rel + 5;
+        pos = start;
+        let Some(quote) = html[start..].chars().next() else {
+            break;
+        };
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let Some(end) = html[start + 1..].find(quote) else {
+            break;
+        };
+        let href = &html[start + 1..start + 1 + end];
+        if let Some(url) = resolve_url(base, href) {
+            out.push(url);
+        }
+    }
+    out
+}
+
+pub fn resolve_url(base: &str, href: &str) -> Option<String> {
+    let href = decode_entities(href.trim());
+    if href.is_empty() || href.starts_with('#') {
+        return None;
+    }
+    let lower = href.to_ascii_lowercase();
+    if lower.starts_with("mailto:") || lower.starts_with("javascript:") || lower.starts_with("tel:") {
+        return None;
+    }
+    if is_url(&href) {
+        return Some(strip_fragment(&href));
+    }
+    let scheme_end = base.find("://")? + 3;
+    let scheme = &base[..scheme_end - 3];
+    let origin_end = base[scheme_end..].find('/').map(|i| scheme_end + i).unwrap_or(base.len());
+    let origin = &base[..origin_end];
+    let joined = if let Some(rest) = href.strip_prefix("//") {
+        format!("{}://{}", scheme, rest)
+    } else if href.starts_with('/') {
+        format!("{}{}", origin, href)
+    } else {
+        let dir_end = base[origin_end..].rfind('/').map(|i| origin_end + i + 1);
+        match dir_end {
+            Some(end) => format!("{}{}", &base[..end], href),
+            None => format!("{}/{}", origin, href),
+        }
+    };
+    Some(strip_fragment(&joined))
+}
+
+fn strip_fragment(url: &str) -> String {
+    url.split('#').next().unwrap_or(url).to_string()
+}
+
+fn host_of(url: &str) -> Option<String> {
+    let rest = &url[url.find("://")? + 3..];
+    let host = rest.split(['/', '?', '#']).next()?;
+    Some(host.to_ascii_lowercase())
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use std::time::Duration;
+
+use rag::{
+    access_log, access_log_path, access_report, cited_hits, record_access, AnswerResult, Config, FinalDetails,
+    IndexManifest, ManifestFile, SourceHit, Usage,
+};
+
+fn temp_config(name: &str, enabled: bool) -> Config {
+    let mut cfg = common::temp_config(&format!("access-{}", name));
+    cfg.collection = "docs".to_string();
+    cfg.access_log = enabled;
+    cfg
+}
+
+fn hit(path: &str, chunk: usize) -> SourceHit {
+    SourceHit {
+        path: path.to_string(),
+        url: None,
+        score: 0.8,
+        chunk_index: Some(chunk),
+        text: String::new(),
+    }
+}
+
+fn answer(text: &str, hits: Vec<SourceHit>, details: Option<FinalDetails>) -> AnswerResult {
+    AnswerResult {
+        context: String::new(),
+        answer: text.to_string(),
+        hits,
+        prompt: Vec::new(),
+        model: "test".to_string(),
+        elapsed: Duration::ZERO,
+        rerank_time: None,
+        rerank_error: None,
+        details,
+        usage: Usage::default(),
+    }
+}
+
+#[test]
+fn citations_come_from_sources_used_or_answer_markers() {
+    let hits = vec![hit("a.md", 0), hit("b.md", 3), hit("a.md", 1)];
+    assert_eq!(cited_hits(&answer("Retries twice [3], see [1] and [1]. Not [9] or [x].", hits.clone(), None)), vec![0, 2]);
+    let details = FinalDetails {
+        sources_used: vec![2],
+        ..FinalDetails::default()
+    };
+    assert_eq!(cited_hits(&answer("Retries twice [3].", hits, Some(details))), vec![1]);
+}
+
+#[test]
+fn nothing_is_recorded_when_disabled() {
+    let cfg = temp_config("off", false);
+    record_access(&cfg, &answer("[1]", vec![hit("a.md", 0)], None)).unwrap();
+    assert_eq!(access_log(&cfg).unwrap().questions, 0);
+}
+
+#[test]
+fn nothing_is_recorded_in_safe_mode() {
+    let mut cfg = temp_config("safe", true);
+    cfg.safe_mode = true;
+    record_access(&cfg, &answer("[1]", vec![hit("a.md", 0)], None)).unwrap();
+    assert!(!access_log_path(&cfg).exists());
+}
+
+#[test]
+fn counts_questions_per_file_and_chunk_and_reports_unused_files() {
+    let cfg = temp_config("on", true);
+    record_access(&cfg, &answer("See [1] and [2].", vec![hit("a.md", 0), hit("a.md", 1), hit("b.md", 0)], None)).unwrap();
+    record_access(&cfg, &answer("From [2].", vec![hit("b.md", 0), hit("a.md", 0)], None)).unwrap();
+    record_access(&cfg, &answer("Nothing cited.", vec![hit("b.md", 0)], None)).unwrap();
+
+    let log = access_log(&cfg).unwrap();
+    assert_eq!(log.questions, 3);
+    let a = &log.files["a.md"];
+    // Two chunks of a.md were cited in one answer: one question for the file.
+    assert_eq!((a.retrieved, a.cited), (2, 2));
+    assert_eq!((a.chunks[&0].retrieved, a.chunks[&0].cited), (2, 2));
+    assert_eq!((a.chunks[&1].retrieved, a.chunks[&1].cited), (1, 1));
+    let b = &log.files["b.md"];
+    assert_eq!((b.retrieved, b.cited), (3, 0));
+    assert!(b.last_retrieved >= log.since && b.last_cited.is_none());
+    let ranked: Vec<&str> = log.most_useful().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(ranked, vec!["a.md", "b.md"]);
+
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    for path in ["a.md", "b.md", "never.md"] {
+        manifest.files.insert(path.to_string(), ManifestFile::default());
+    }
+    manifest.save(&cfg).unwrap();
+    let report = access_report(&cfg, 1).unwrap();
+    assert!(report[0].starts_with("3 answers recorded for docs since "));
+    assert!(report.iter().any(|line| line.ends_with("a.md")));
+    assert!(!report.iter().any(|line| line.ends_with(" b.md")));
+    assert!(report.contains(&"Never retrieved (1 of 3 indexed files):".to_string()));
+    assert_eq!(report.last().map(String::as_str), Some("  never.md"));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn concurrent_answers_all_count() {
+    let cfg = temp_config("concurrent", true);
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    record_access(&cfg, &answer("See [1].", vec![hit("a.md", 0)], None)).unwrap();
+                }
+            });
+        }
+    });
+    let log = access_log(&cfg).unwrap();
+    assert_eq!(log.questions, 40);
+    assert_eq!(log.files["a.md"].cited, 40);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn a_torn_log_reads_as_empty_and_is_replaced() {
+    let cfg = temp_config("torn", true);
+    let path = access_log_path(&cfg);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, r#"{"since": "2026-01-01T00:00:00Z", "questions": 3, "fil"#).unwrap();
+    assert_eq!(access_log(&cfg).unwrap().questions, 0);
+    record_access(&cfg, &answer("See [1].", vec![hit("a.md", 0)], None)).unwrap();
+    assert_eq!(access_log(&cfg).unwrap().questions, 1);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::replay_config;
+use rag::{AgentMode, AgentOptions, AgentState, Config, McpClient, run_agent};
+
+#[test]
+fn parses_and_cycles_modes() {
+    assert_eq!(AgentMode::parse("hybrid"), Some(AgentMode::Hybrid));
+    assert_eq!(AgentMode::parse(" RAG_only "), Some(AgentMode::RagOnly));
+    assert_eq!(AgentMode::parse("mcp"), Some(AgentMode::McpOnly));
+    assert_eq!(AgentMode::parse("both"), None);
+    assert_eq!(AgentMode::Hybrid.next().next().next(), AgentMode::Hybrid);
+    assert_eq!(AgentMode::McpOnly.label(), "mcp-only");
+}
+
+#[test]
+fn options_default_from_config() {
+    let mut cfg = Config::from_env();
+    cfg.agent_mode = "rag".to_string();
+    assert_eq!(AgentOptions::from_config(&cfg).mode, AgentMode::RagOnly);
+    cfg.agent_mode = "unknown".to_string();
+    assert_eq!(AgentOptions::from_config(&cfg).mode, AgentMode::Hybrid);
+}
+
+#[test]
+fn mcp_only_mode_refuses_retrieval() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"retrieve\",\"arguments\":{\"query\":\"retry\"}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("mode", fixture);
+
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(5);
+    state.mode = AgentMode::McpOnly;
+    state.append_user("how are retries handled?".to_string());
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "done");
+    assert!(state.hits.is_empty());
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content.contains("retrieve is disabled")));
+}
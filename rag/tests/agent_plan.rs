@@ -0,0 +1,82 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::replay_config;
+use rag::{
+    parse_plan, run_agent_with_plan, AgentEvent, AgentOptions, AgentState, AgentStrategy, Config,
+    McpClient, Tool,
+};
+use serde_json::Value;
+
+struct Add;
+
+impl Tool for Add {
+    fn name(&self) -> &str {
+        "add"
+    }
+
+    fn execute(&self, args: &Value) -> Result<String, String> {
+        Ok((args["a"].as_i64().unwrap_or_default() + args["b"].as_i64().unwrap_or_default()).to_string())
+    }
+}
+
+#[test]
+fn reads_plans_in_several_shapes() {
+    assert_eq!(parse_plan(r#"{"plan": ["Find the retry code", "Find the timeout"]}"#), vec![
+        "Find the retry code",
+        "Find the timeout"
+    ]);
+    assert_eq!(parse_plan(r#"{"steps": [{"step": "Only one"}]}"#), vec!["Only one"]);
+    assert_eq!(parse_plan("1. First\n2) Second\nnot a step\n- Third"), vec!["First", "Second", "Third"]);
+    assert_eq!(parse_plan(r#"["a","b","c","d","e","f"]"#).len(), 5);
+    assert!(parse_plan("{}").is_empty());
+}
+
+#[test]
+fn strategy_comes_from_config() {
+    let mut cfg = Config::from_env();
+    cfg.agent_strategy = "plan".to_string();
+    assert_eq!(AgentOptions::from_config(&cfg).strategy, AgentStrategy::Plan);
+    cfg.agent_strategy = "bogus".to_string();
+    assert_eq!(AgentOptions::from_config(&cfg).strategy, AgentStrategy::Loop);
+}
+
+#[test]
+fn runs_each_step_then_synthesizes() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"plan\":[\"Add 1 and 2\",\"Add 3 and 4\"]}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"add\",\"arguments\":{\"a\":1,\"b\":2}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"1 + 2 = 3\"}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"3 + 4 = 7\"}"}"#,
+        "\n",
+        r#"{"kind":"chat","response":"The sums are 3 and 7."}"#,
+        "\n",
+    );
+    let mut cfg = replay_config("plan", fixture);
+    cfg.best_of = 1;
+
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(6);
+    state.events = Some(tx);
+    state.register_tool(Arc::new(Add));
+    state.append_user("What are 1 + 2 and 3 + 4?".to_string());
+
+    assert_eq!(run_agent_with_plan(&mut state, &cfg, &mcp).unwrap(), "The sums are 3 and 7.");
+    assert_eq!(state.current_step, 1);
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content == "Finding for step 2 (Add 3 and 4): 3 + 4 = 7"));
+    assert!(state.last_prompt.iter().any(|m| m.content.contains("Finding: 1 + 2 = 3")));
+    drop(state);
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert_eq!(events.first(), Some(&AgentEvent::Planned {
+        steps: vec!["Add 1 and 2".to_string(), "Add 3 and 4".to_string()]
+    }));
+    assert_eq!(events.iter().filter(|e| matches!(e, AgentEvent::Final { .. })).count(), 1);
+}
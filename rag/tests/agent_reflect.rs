@@ -0,0 +1,64 @@
+mod common;
+
+use rag::{parse_reflection, run_agent, AgentEvent, AgentState, Config, McpClient};
+
+fn replay_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = common::replay_config(name, fixture);
+    cfg.agent_reflect = true;
+    cfg
+}
+
+fn run(cfg: &Config) -> (String, Vec<AgentEvent>) {
+    let mcp = McpClient::from_config(cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(1);
+    state.events = Some(tx);
+    state.append_user("Where is Paris?".to_string());
+    let answer = run_agent(&mut state, cfg, &mcp).expect("replayed run should finish");
+    drop(state);
+    (answer, rx.iter().collect())
+}
+
+#[test]
+fn reads_reflection_verdicts() {
+    assert_eq!(parse_reflection(r#"{"verdict":"confirm"}"#), None);
+    assert_eq!(parse_reflection(r#"{"verdict":"Revise","answer":" Better. "}"#), Some("Better.".to_string()));
+    assert_eq!(parse_reflection(r#"{"verdict":"revise","answer":""}"#), None);
+    assert_eq!(parse_reflection("not json"), None);
+}
+
+#[test]
+fn a_revision_replaces_the_final_answer() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"Paris is in Italy.\"}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"verdict\":\"revise\",\"answer\":\"Paris is in France.\"}"}"#,
+        "\n",
+    );
+    let (answer, events) = run(&replay_config("revise", fixture));
+    assert_eq!(answer, "Paris is in France.");
+    assert!(events.contains(&AgentEvent::DecisionMade { step: 0, action: "reflect: revised".to_string() }));
+    assert_eq!(events.last(), Some(&AgentEvent::Final { answer, forced: false }));
+}
+
+#[test]
+fn a_confirmed_or_unreadable_check_keeps_the_answer() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"Paris is in France.\"}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"verdict\":\"confirm\"}"}"#,
+        "\n",
+    );
+    let (answer, events) = run(&replay_config("confirm", fixture));
+    assert_eq!(answer, "Paris is in France.");
+    assert!(events.contains(&AgentEvent::DecisionMade { step: 0, action: "reflect: confirmed".to_string() }));
+
+    // The fixture holds no reflection reply, so the check fails and is skipped.
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"Paris is in France.\"}"}"#,
+        "\n",
+    );
+    let (answer, events) = run(&replay_config("missing", fixture));
+    assert_eq!(answer, "Paris is in France.");
+    assert!(events.iter().any(|e| matches!(e, AgentEvent::Error { message, .. } if message.starts_with("reflection failed"))));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use rag::{decision_schema, run_agent, AgentEvent, AgentState, Config, McpClient};
+
+fn replay_config(name: &str, fixture: &str, repairs: usize) -> Config {
+    let mut cfg = common::replay_config(name, fixture);
+    cfg.agent_reflect = false;
+    cfg.agent_max_repairs = repairs;
+    cfg
+}
+
+#[test]
+fn a_repaired_reply_does_not_cost_a_step() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"final\"}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("fixed", fixture, 2);
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(3);
+    state.events = Some(tx);
+    state.append_user("hello".to_string());
+
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "done");
+    drop(state.events.take());
+    assert_eq!(state.current_step, 0);
+    assert_eq!(state.repairs, 1);
+    // The broken reply and the correction request stay out of the conversation.
+    assert_eq!(state.conversation.len(), 1);
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert_eq!(
+        events[0],
+        AgentEvent::RepairAttempted { step: 0, attempt: 1, error: "final action requires answer".to_string() }
+    );
+    assert_eq!(events[1], AgentEvent::DecisionMade { step: 0, action: "final".to_string() });
+}
+
+#[test]
+fn the_step_is_lost_when_repairs_run_out() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"not json at all"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"dance\"}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("exhausted", fixture, 1);
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(3);
+    state.append_user("hello".to_string());
+
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "done");
+    assert_eq!(state.current_step, 1);
+    assert_eq!(state.repairs, 1);
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content.contains("Invalid controller JSON output: unknown action: dance")));
+}
+
+#[test]
+fn the_schema_lists_every_action() {
+    let schema = decision_schema();
+    assert_eq!(schema["required"], serde_json::json!(["action"]));
+    assert_eq!(
+        schema["properties"]["action"]["enum"],
+        serde_json::json!(["retrieve", "tool", "prompt", "resource", "final"])
+    );
+}
+
+#[test]
+fn each_action_alternative_requires_its_fields() {
+    let schema = decision_schema();
+    let alternatives = schema["anyOf"].as_array().unwrap();
+    let required = |action: &str| {
+        alternatives
+            .iter()
+            .find(|alt| alt["properties"]["action"]["const"] == action)
+            .map(|alt| alt["required"].clone())
+            .unwrap_or_else(|| panic!("no alternative for {}", action))
+    };
+    assert_eq!(alternatives.len(), 5);
+    assert_eq!(required("retrieve"), serde_json::json!(["action", "arguments"]));
+    assert_eq!(required("tool"), serde_json::json!(["action", "name", "arguments"]));
+    assert_eq!(required("prompt"), serde_json::json!(["action", "name", "arguments"]));
+    assert_eq!(required("resource"), serde_json::json!(["action", "uri"]));
+    assert_eq!(required("final"), serde_json::json!(["action", "answer"]));
+    let retrieve = alternatives.iter().find(|alt| alt["properties"]["action"]["const"] == "retrieve").unwrap();
+    assert_eq!(retrieve["properties"]["arguments"]["required"], serde_json::json!(["query"]));
+}
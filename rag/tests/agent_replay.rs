@@ -0,0 +1,85 @@
+mod common;
+
+use rag::{AgentEvent, AgentState, Config, McpClient, run_agent};
+
+fn replay_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = common::replay_config(name, fixture);
+    // Invalid replies lose their step here; repairs are covered in agent_repair.rs.
+    cfg.agent_max_repairs = 0;
+    cfg
+}
+
+#[test]
+fn replays_controller_decisions_in_order() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"not json at all"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"greet\",\"arguments\":{}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("order", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(5);
+    state.append_user("hello".to_string());
+
+    let answer = run_agent(&mut state, &cfg, &mcp).expect("replayed run should finish");
+    assert_eq!(answer, "done");
+    assert_eq!(state.current_step, 2);
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content.contains("Invalid controller JSON output")));
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content.contains("MCP is unavailable")));
+}
+
+#[test]
+fn reports_exhausted_fixture() {
+    let cfg = replay_config("exhausted", "");
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(3);
+    state.append_user("hello".to_string());
+
+    let err = run_agent(&mut state, &cfg, &mcp).expect_err("empty fixture should fail");
+    assert!(err.contains("exhausted"));
+}
+
+#[test]
+fn emits_events_for_each_step() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"not json at all"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("events", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(5);
+    state.events = Some(tx);
+    state.append_user("hello".to_string());
+
+    run_agent(&mut state, &cfg, &mcp).expect("replayed run should finish");
+    state.events = None;
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert!(matches!(&events[0], AgentEvent::Error { step: 0, message } if message.contains("invalid controller JSON")));
+    assert_eq!(
+        events[1],
+        AgentEvent::DecisionMade {
+            step: 1,
+            action: "final".to_string()
+        }
+    );
+    assert_eq!(
+        events[2],
+        AgentEvent::Final {
+            answer: "done".to_string(),
+            forced: false
+        }
+    );
+    assert_eq!(events[1].to_string(), "step 2: final");
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rag::{call_with_timeout, run_agent, AgentEvent, AgentState, Config, Deadline, McpClient, Tool};
+use serde_json::Value;
+
+struct Stuck;
+
+impl Tool for Stuck {
+    fn name(&self) -> &str {
+        "stuck"
+    }
+
+    fn execute(&self, _args: &Value) -> Result<String, String> {
+        std::thread::sleep(Duration::from_secs(2));
+        Ok("too late".to_string())
+    }
+}
+
+fn replay_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = common::replay_config(name, fixture);
+    cfg.best_of = 1;
+    cfg.agent_reflect = false;
+    cfg
+}
+
+#[test]
+fn slow_calls_give_up_at_the_limit() {
+    let slow = call_with_timeout(Some(Duration::from_millis(50)), || {
+        std::thread::sleep(Duration::from_millis(500));
+        Ok(1)
+    });
+    assert_eq!(slow, Err("timed out after 0.05s".to_string()));
+    assert_eq!(call_with_timeout(None, || Ok(2)), Ok(2));
+}
+
+#[test]
+fn a_stuck_tool_fails_its_step_and_the_run_goes_on() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"stuck\",\"arguments\":{}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"The tool did not answer.\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("step", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(3);
+    state.register_tool(Arc::new(Stuck));
+    state.step_timeout = Some(Duration::from_millis(100));
+    state.append_user("Run the stuck tool.".to_string());
+
+    let answer = run_agent(&mut state, &cfg, &mcp).expect("replayed run should finish");
+    assert_eq!(answer, "The tool did not answer.");
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content == "Tool result [stuck]: Tool call failed for stuck: timed out after 0.1s"));
+}
+
+#[test]
+fn a_spent_budget_forces_the_answer_from_gathered_context() {
+    let fixture = concat!(r#"{"kind":"chat","response":"Caching is off in CI."}"#, "\n");
+    let cfg = replay_config("total", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(5);
+    state.events = Some(tx);
+    state.append_user("Why is the build slow?".to_string());
+    state.append_context("src/build.rs: caches are disabled in CI".to_string());
+    state.budget = Deadline::after_secs(1);
+    std::thread::sleep(Duration::from_millis(1100));
+
+    let answer = run_agent(&mut state, &cfg, &mcp).expect("forced answer should be returned");
+    assert_eq!(answer, "Caching is off in CI.");
+    assert_eq!(state.current_step, 0);
+    drop(state.events.take());
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert!(matches!(&events[0], AgentEvent::Error { message, .. } if message.contains("RAG_AGENT_TOTAL_TIMEOUT")));
+    assert_eq!(events.last(), Some(&AgentEvent::Final { answer, forced: true }));
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::path::Path;
+
+use common::{replay_config, temp_config};
+use rag::{AgentState, Config, McpClient, reset_replay, run_agent, start_trace};
+use serde_json::Value;
+
+fn trace_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = replay_config(name, fixture);
+    cfg.data_dir = temp_config(name).data_dir;
+    cfg.trace = true;
+    cfg
+}
+
+fn read_lines(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .expect("trace should be readable")
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("trace lines should be JSON"))
+        .collect()
+}
+
+#[test]
+fn start_trace_is_off_by_default() {
+    let mut cfg = temp_config("trace-off");
+    cfg.trace = false;
+    assert_eq!(start_trace(&cfg).unwrap(), None);
+    assert!(!Path::new(&cfg.data_dir).join("traces").exists());
+}
+
+#[test]
+fn start_trace_is_off_in_safe_mode() {
+    let mut cfg = temp_config("trace-safe");
+    cfg.trace = true;
+    cfg.safe_mode = true;
+    assert_eq!(start_trace(&cfg).unwrap(), None);
+    assert!(!Path::new(&cfg.data_dir).join("traces").exists());
+}
+
+#[test]
+fn records_messages_decisions_and_responses() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"greet\",\"arguments\":{}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#,
+        "\n",
+    );
+    let cfg = trace_config("trace-run", fixture);
+    let dir = Path::new(&cfg.data_dir);
+    let trace = start_trace(&cfg).unwrap().expect("tracing is on");
+    assert!(trace.starts_with(dir.join("traces")));
+
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(5);
+    state.trace_file = Some(trace.clone());
+    state.append_user("hello".to_string());
+    run_agent(&mut state, &cfg, &mcp).expect("replayed run should finish");
+
+    let lines = read_lines(&trace);
+    let kinds: Vec<&str> = lines.iter().map(|l| l["kind"].as_str().unwrap()).collect();
+    assert_eq!(
+        kinds,
+        ["message", "json", "decision", "message", "json", "decision", "final"]
+    );
+    assert_eq!(lines[0]["content"], "hello");
+    assert!(lines[1]["latency_ms"].is_u64());
+    assert_eq!(lines[2]["action"], "tool greet");
+    assert_eq!(lines[6]["answer"], "done");
+    assert!(lines.iter().all(|l| l["at_ms"].is_u64()));
+
+    // The trace replays the same run offline.
+    let mut replay = cfg.clone();
+    replay.replay_file = trace.to_string_lossy().to_string();
+    replay.trace = false;
+    reset_replay(&replay.replay_file);
+    let mut state = AgentState::new(5);
+    state.append_user("hello".to_string());
+    assert_eq!(run_agent(&mut state, &replay, &mcp).unwrap(), "done");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use rag::{
+    answer_cache_key, answer_cache_path, format_age, lookup_answer, normalize_question, store_answer, CachedAnswer,
+    Config, IndexManifest, ManifestFile,
+};
+
+fn temp_config(name: &str) -> Config {
+    let mut cfg = common::temp_config(&format!("answer-cache-{}", name));
+    cfg.collection = "docs".to_string();
+    cfg.answer_cache = true;
+    cfg.answer_cache_ttl_secs = 3600;
+    cfg
+}
+
+fn answer(text: &str) -> CachedAnswer {
+    CachedAnswer {
+        question: "How do I index?".to_string(),
+        context: "[1] README.md".to_string(),
+        answer: text.to_string(),
+        sources: "[1] README.md (0.91)".to_string(),
+        model: "qwen".to_string(),
+        created_at: 0,
+    }
+}
+
+#[test]
+fn questions_are_normalized() {
+    assert_eq!(normalize_question("  How do I   INDEX?? "), "how do i index");
+    assert_eq!(normalize_question("Why.\n"), "why");
+    let cfg = temp_config("normalize");
+    assert_eq!(
+        answer_cache_key(&cfg, "How do I index?", "hybrid"),
+        answer_cache_key(&cfg, "how do i  index", "hybrid")
+    );
+    assert_ne!(
+        answer_cache_key(&cfg, "How do I index?", "hybrid"),
+        answer_cache_key(&cfg, "How do I index?", "rag-only")
+    );
+}
+
+#[test]
+fn answers_round_trip_until_the_index_changes() {
+    let cfg = temp_config("roundtrip");
+    let key = answer_cache_key(&cfg, "How do I index?", "hybrid");
+    assert!(lookup_answer(&cfg, &key).is_none());
+    store_answer(&cfg, &key, answer("Run aicli index.")).unwrap();
+    let cached = lookup_answer(&cfg, &key).unwrap();
+    assert_eq!(cached.answer, "Run aicli index.");
+    assert!(cached.created_at > 0);
+    assert!(cached.badge().starts_with("cached 0s ago, qwen"));
+
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.files.insert("README.md".to_string(), ManifestFile { chunks: 1, point_ids: Vec::new() });
+    manifest.save(&cfg).unwrap();
+    assert_ne!(answer_cache_key(&cfg, "How do I index?", "hybrid"), key);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn expired_or_disabled_entries_are_not_served() {
+    let mut cfg = temp_config("expiry");
+    let key = answer_cache_key(&cfg, "question", "hybrid");
+    store_answer(&cfg, &key, answer("old")).unwrap();
+    cfg.answer_cache = false;
+    assert!(lookup_answer(&cfg, &key).is_none());
+    cfg.answer_cache = true;
+    cfg.answer_cache_ttl_secs = 0;
+    assert!(lookup_answer(&cfg, &key).is_none());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn safe_mode_neither_stores_nor_serves_answers() {
+    let mut cfg = temp_config("safe");
+    let key = answer_cache_key(&cfg, "question", "hybrid");
+    cfg.safe_mode = true;
+    store_answer(&cfg, &key, answer("unsaved")).unwrap();
+    assert!(!answer_cache_path(&cfg).exists());
+
+    cfg.safe_mode = false;
+    store_answer(&cfg, &key, answer("saved")).unwrap();
+    cfg.safe_mode = true;
+    assert!(lookup_answer(&cfg, &key).is_none());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn ages_are_short() {
+    assert_eq!(format_age(42), "42s");
+    assert_eq!(format_age(300), "5m");
+    assert_eq!(format_age(3 * 3600 + 5), "3h");
+    assert_eq!(format_age(2 * 86_400), "2d");
+}
@@ -0,0 +1,15 @@
+use rag::{attach_file, Config};
+
+#[test]
+fn missing_and_binary_files_cannot_be_attached() {
+    let cfg = Config::from_env();
+    let missing = std::env::temp_dir().join(format!("aicli-attach-missing-{}.txt", std::process::id()));
+    let err = attach_file(&cfg, &missing.to_string_lossy()).unwrap_err();
+    assert!(err.starts_with("Cannot attach"), "{}", err);
+
+    let binary = std::env::temp_dir().join(format!("aicli-attach-binary-{}.bin", std::process::id()));
+    std::fs::write(&binary, [0xff, 0xfe, 0x00, 0x9f]).unwrap();
+    let err = attach_file(&cfg, &binary.to_string_lossy()).unwrap_err();
+    assert!(err.ends_with("not a text file"), "{}", err);
+    let _ = std::fs::remove_file(&binary);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::replay_config;
+use rag::{consensus_pick, draft_options, generate_best_of, parse_choice, Config, Message};
+
+fn question() -> Vec<Message> {
+    vec![Message {
+        role: "user".to_string(),
+        content: "What is the capital of France?".to_string(),
+    }]
+}
+
+#[test]
+fn reads_the_judges_choice() {
+    assert_eq!(parse_choice("2", 3), Some(1));
+    assert_eq!(parse_choice("Draft 3 is best.", 3), Some(2));
+    assert_eq!(parse_choice("Draft 7", 3), None);
+    assert_eq!(parse_choice("the second one", 3), None);
+}
+
+#[test]
+fn consensus_prefers_the_majority_draft() {
+    let drafts = vec![
+        "The capital is Lyon.".to_string(),
+        "Paris is the capital of France.".to_string(),
+        "The capital of France is Paris.".to_string(),
+    ];
+    assert_eq!(consensus_pick(&drafts), 1);
+}
+
+#[test]
+fn drafts_split_the_budget_and_vary_the_seed() {
+    let mut cfg = Config::from_env();
+    cfg.best_of = 4;
+    cfg.best_of_temperature = 1.1;
+    cfg.best_of_budget = Some(2000);
+    cfg.generation.seed = Some(10);
+    let options = draft_options(&cfg, 2);
+    assert_eq!(options.temperature, Some(1.1));
+    assert_eq!(options.num_predict, Some(500));
+    assert_eq!(options.seed, Some(12));
+}
+
+#[test]
+fn falls_back_to_consensus_when_the_verdict_is_unreadable() {
+    let fixture = concat!(
+        r#"{"kind":"chat","response":"Paris is the capital."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"Lyon, probably."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"Paris is the capital."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"They are all fine."}"#,
+        "\n",
+    );
+    let mut cfg = replay_config("select", fixture);
+    cfg.best_of = 3;
+    assert_eq!(generate_best_of(&cfg, &question()).unwrap(), "Paris is the capital.");
+}
+
+#[test]
+fn merge_returns_the_judges_answer() {
+    let fixture = concat!(
+        r#"{"kind":"chat","response":"Paris."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"It is Paris."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"The capital of France is Paris."}"#,
+        "\n",
+    );
+    let mut cfg = replay_config("merge", fixture);
+    cfg.best_of = 2;
+    cfg.best_of_judge = "merge".to_string();
+    assert_eq!(generate_best_of(&cfg, &question()).unwrap(), "The capital of France is Paris.");
+}
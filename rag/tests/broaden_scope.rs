@@ -0,0 +1,46 @@
+use rag::{annotate_broadened, broadened_scope, Config};
+
+fn base_config() -> Config {
+    let mut cfg = Config::from_env();
+    cfg.broaden_on_empty = true;
+    cfg.focus_paths = Vec::new();
+    cfg.min_score = None;
+    cfg.top_k = 5;
+    cfg
+}
+
+#[test]
+fn nothing_to_relax_without_filters() {
+    assert!(broadened_scope(&base_config()).is_none());
+}
+
+#[test]
+fn disabled_policy_never_broadens() {
+    let mut cfg = base_config();
+    cfg.focus_paths = vec!["src/".to_string()];
+    cfg.broaden_on_empty = false;
+    assert!(broadened_scope(&cfg).is_none());
+}
+
+#[test]
+fn drops_focus_and_min_score_and_widens_top_k() {
+    let mut cfg = base_config();
+    cfg.focus_paths = vec!["src/".to_string()];
+    cfg.min_score = Some(0.8);
+    let (broad, note) = broadened_scope(&cfg).expect("filters should be relaxed");
+    assert!(broad.focus_paths.is_empty());
+    assert_eq!(broad.min_score, None);
+    assert_eq!(broad.top_k, 10);
+    assert!(note.contains("focus src/"));
+    assert!(note.contains("minimum score 0.8"));
+    assert!(note.contains("top_k 10"));
+}
+
+#[test]
+fn annotates_only_broadened_answers() {
+    assert_eq!(annotate_broadened("Answer.\n".to_string(), None), "Answer.\n");
+    assert_eq!(
+        annotate_broadened("Answer.\n".to_string(), Some("Scope broadened.")),
+        "Answer.\n\n_Scope broadened._"
+    );
+}
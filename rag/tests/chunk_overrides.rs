@@ -0,0 +1,23 @@
+use rag::{ChunkParams, Config, parse_chunk_overrides};
+
+#[test]
+fn parses_extension_size_and_overlap() {
+    let overrides = parse_chunk_overrides(".md=800/120, rs=1600/240,.TXT=600,bad,.py=x/1");
+    assert_eq!(overrides.len(), 3);
+    assert_eq!(overrides[".md"], ChunkParams { size: 800, overlap: 120 });
+    assert_eq!(overrides[".rs"], ChunkParams { size: 1600, overlap: 240 });
+    assert_eq!(overrides[".txt"], ChunkParams { size: 600, overlap: 100 });
+}
+
+#[test]
+fn picks_parameters_by_extension() {
+    let mut cfg = Config::from_env();
+    cfg.chunk_size = 1200;
+    cfg.chunk_overlap = 200;
+    cfg.chunk_overrides = parse_chunk_overrides(".md=800/120,.rs=400/500");
+    assert_eq!(cfg.chunk_params("docs/Guide.MD"), ChunkParams { size: 800, overlap: 120 });
+    assert_eq!(cfg.chunk_params("src/main.go"), ChunkParams { size: 1200, overlap: 200 });
+    assert_eq!(cfg.chunk_params("Makefile"), ChunkParams { size: 1200, overlap: 200 });
+    // An overlap that would not advance is cut, and that is what gets recorded.
+    assert_eq!(cfg.chunk_params("src/lib.rs"), ChunkParams { size: 400, overlap: 100 });
+}
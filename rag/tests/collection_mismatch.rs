@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use common::{stub_server, temp_config};
+use rag::{ensure_collection, index_corpus, Config};
+use serde_json::json;
+
+const COLLECTION: &str = "mismatch_test";
+
+// A Qdrant whose collection reports `sizes[n]` on the n-th info request
+// (the last size from then on) and `distance`, rejecting every upsert as
+// Qdrant does for vectors of the wrong size. It also embeds every text as
+// a 3-dimensional vector. `deletes` counts dropped collections.
+fn stub(sizes: &'static [usize], distance: &'static str, deletes: Arc<AtomicUsize>) -> String {
+    let collection = format!("/collections/{}", COLLECTION);
+    let infos = AtomicUsize::new(0);
+    stub_server(move |method, path, body| match (method, path) {
+        ("POST", "/api/embed") => {
+            let body: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+            let n = body["input"].as_array().map_or(0, Vec::len);
+            (200, json!({ "embeddings": vec![vec![0.5; 3]; n] }).to_string())
+        }
+        ("GET", p) if p == collection => {
+            let size = sizes[infos.fetch_add(1, Ordering::SeqCst).min(sizes.len() - 1)];
+            let params = json!({ "vectors": { "size": size, "distance": distance } });
+            (200, json!({ "result": { "config": { "params": params }, "points_count": 1 } }).to_string())
+        }
+        ("DELETE", p) if p == collection => {
+            deletes.fetch_add(1, Ordering::SeqCst);
+            (200, r#"{"result":true}"#.to_string())
+        }
+        ("PUT", p) if p.starts_with(&format!("{}/points", collection)) => {
+            (400, r#"{"status":{"error":"Wrong input: Vector dimension error: expected dim: 4, got 3"}}"#.to_string())
+        }
+        _ => (200, r#"{"result":true}"#.to_string()),
+    })
+}
+
+fn mismatch_config(name: &str, url: String) -> Config {
+    let mut cfg = temp_config(&format!("mismatch-{}", name));
+    cfg.qdrant_url = url.clone();
+    cfg.qdrant_api_key = String::new();
+    cfg.qdrant_read_only = false;
+    cfg.ollama_url = url;
+    cfg.ollama_urls = Vec::new();
+    cfg.collection = COLLECTION.to_string();
+    cfg.distance = "Cosine".to_string();
+    cfg.payload_indexes = Vec::new();
+    cfg.http_retries = 0;
+    cfg.embed_retries = 0;
+    cfg.safe_mode = false;
+    cfg.recreate_collection = false;
+    cfg
+}
+
+#[test]
+fn a_collection_with_another_distance_is_reported() {
+    let deletes = Arc::new(AtomicUsize::new(0));
+    let mut cfg = mismatch_config("distance", stub(&[3], "Dot", deletes.clone()));
+    let err = ensure_collection(&cfg, 3).unwrap_err();
+    assert!(err.contains("built with Dot distance but QDRANT_DISTANCE is Cosine"), "{}", err);
+    assert!(err.contains("aicli index migrate --distance Cosine"), "{}", err);
+    assert_eq!(deletes.load(Ordering::SeqCst), 0);
+
+    cfg.recreate_collection = true;
+    assert_eq!(ensure_collection(&cfg, 3), Ok(true));
+    assert_eq!(deletes.load(Ordering::SeqCst), 1);
+
+    cfg.distance = "dot".to_string();
+    assert_eq!(ensure_collection(&cfg, 3), Ok(false));
+    assert_eq!(deletes.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn a_rejected_upsert_names_both_vector_sizes() {
+    // The collection is rebuilt with 4-dimensional vectors between the
+    // check and the upsert.
+    let deletes = Arc::new(AtomicUsize::new(0));
+    let cfg = mismatch_config("upsert", stub(&[3, 4], "Cosine", deletes));
+    let src = std::path::Path::new(&cfg.data_dir).join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("notes.md"), "Notes to embed.").unwrap();
+
+    let err = index_corpus(&cfg, Some(src.to_string_lossy().as_ref())).unwrap_err();
+    assert!(err.contains("stores 4-dimensional vectors"), "{}", err);
+    assert!(err.contains("produces 3-dimensional ones"), "{}", err);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
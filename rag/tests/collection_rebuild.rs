@@ -0,0 +1,167 @@
+mod common;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use common::{stub_server, temp_config};
+use rag::{ensure_collection, index_corpus, retry_quarantined, Config, IndexManifest, ManifestFile, QuarantinedFile};
+use serde_json::{json, Value};
+
+const COLLECTION: &str = "rebuild_test";
+
+// What the stub Qdrant holds: its vector size and the paths of the points
+// written since it was last dropped.
+#[derive(Default)]
+struct Store {
+    vector_size: usize,
+    dropped: bool,
+    stored: Vec<String>,
+}
+
+// One server standing in for Ollama (3-dimensional embeddings, failing for
+// any text that mentions "broken"), Qdrant, and a web page at `/page`.
+fn stub(store: Arc<Mutex<Store>>) -> String {
+    let collection = format!("/collections/{}", COLLECTION);
+    stub_server(move |method, path, body| {
+        let body: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+        let mut store = store.lock().unwrap();
+        match (method, path) {
+            ("POST", "/api/embed") if body.to_string().contains("broken") => {
+                (500, r#"{"error":"model crashed"}"#.to_string())
+            }
+            ("POST", "/api/embed") => {
+                let n = body["input"].as_array().map_or(0, Vec::len);
+                (200, json!({ "embeddings": vec![vec![0.5; 3]; n] }).to_string())
+            }
+            ("GET", "/page") => (200, r#"<h1 id="top">Top</h1><p>Page text.</p>"#.to_string()),
+            ("GET", p) if p == collection => {
+                let params = json!({ "vectors": { "size": store.vector_size, "distance": "Cosine" } });
+                (200, json!({ "result": { "config": { "params": params }, "points_count": 0 } }).to_string())
+            }
+            ("DELETE", p) if p == collection => {
+                store.dropped = true;
+                store.stored.clear();
+                (200, r#"{"result":true}"#.to_string())
+            }
+            ("PUT", p) if p == collection => {
+                store.vector_size = body["vectors"]["size"].as_u64().unwrap_or(0) as usize;
+                (200, r#"{"result":true}"#.to_string())
+            }
+            ("PUT", p) if p.starts_with(&format!("{}/points", collection)) => {
+                for point in body["points"].as_array().into_iter().flatten() {
+                    store.stored.push(point["payload"]["path"].as_str().unwrap_or_default().to_string());
+                }
+                (200, r#"{"result":{}}"#.to_string())
+            }
+            ("GET", _) => (404, r#"{"status":"not found"}"#.to_string()),
+            _ => (200, r#"{"result":{}}"#.to_string()),
+        }
+    })
+}
+
+fn rebuild_config(name: &str, store: &Arc<Mutex<Store>>) -> Config {
+    let mut cfg = temp_config(&format!("rebuild-{}", name));
+    let url = stub(store.clone());
+    cfg.qdrant_url = url.clone();
+    cfg.qdrant_api_key = String::new();
+    cfg.qdrant_read_only = false;
+    cfg.ollama_url = url;
+    cfg.ollama_urls = Vec::new();
+    cfg.collection = COLLECTION.to_string();
+    cfg.distance = "Cosine".to_string();
+    cfg.payload_indexes = Vec::new();
+    cfg.http_retries = 0;
+    cfg.embed_retries = 0;
+    cfg.safe_mode = false;
+    cfg.recreate_collection = true;
+    cfg
+}
+
+fn write(path: &Path, text: &str) -> String {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, text).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn a_new_vector_size_is_detected() {
+    let store = Arc::new(Mutex::new(Store { vector_size: 3, ..Store::default() }));
+    let mut cfg = rebuild_config("detect", &store);
+    assert_eq!(ensure_collection(&cfg, 3), Ok(false));
+    assert!(!store.lock().unwrap().dropped);
+
+    cfg.recreate_collection = false;
+    let err = ensure_collection(&cfg, 4).unwrap_err();
+    assert!(err.contains("stores 3-dimensional vectors"), "{}", err);
+    assert!(err.contains("produces 4-dimensional ones"), "{}", err);
+    assert!(!store.lock().unwrap().dropped);
+
+    cfg.recreate_collection = true;
+    assert_eq!(ensure_collection(&cfg, 4), Ok(true));
+    let store = store.lock().unwrap();
+    assert!(store.dropped);
+    assert_eq!(store.vector_size, 4);
+}
+
+#[test]
+fn a_rebuilt_collection_gets_every_readable_source_again() {
+    let store = Arc::new(Mutex::new(Store { vector_size: 2, ..Store::default() }));
+    let cfg = rebuild_config("requeue", &store);
+    let dir = Path::new(&cfg.data_dir).to_path_buf();
+    let new = write(&dir.join("src/new.md"), "Freshly written notes.");
+    let old = write(&dir.join("kept/old.md"), "Notes indexed with the old model.");
+    let missing = dir.join("kept/missing.md").to_string_lossy().to_string();
+    let page = format!("{}/page", cfg.qdrant_url);
+    let dead = format!("{}/dead", cfg.qdrant_url);
+
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.vector_size = 2;
+    for path in [&old, &missing, &page, &dead] {
+        manifest.files.insert(path.clone(), ManifestFile { chunks: 1, point_ids: vec![json!(1)] });
+    }
+    manifest.save(&cfg).unwrap();
+
+    let src = dir.join("src").to_string_lossy().to_string();
+    let stats = index_corpus(&cfg, Some(src.as_str())).unwrap();
+    let mut stored = store.lock().unwrap().stored.clone();
+    stored.sort();
+    let mut expected = vec![new, old, page];
+    expected.sort();
+    assert_eq!(stored, expected);
+    assert!(stats.skipped.contains(&(missing, "dropped with the old collection, source missing".to_string())));
+    assert!(stats.skipped.contains(&(dead, "dropped with the old collection, fetch failed".to_string())));
+
+    let manifest = IndexManifest::load(&cfg).unwrap();
+    assert_eq!(manifest.vector_size, 3);
+    assert_eq!(manifest.files.len(), 3);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn a_retry_counts_only_the_retried_files_that_were_stored() {
+    let store = Arc::new(Mutex::new(Store { vector_size: 2, ..Store::default() }));
+    let cfg = rebuild_config("retry", &store);
+    let dir = Path::new(&cfg.data_dir).to_path_buf();
+    let retried = write(&dir.join("src/retried.md"), "Notes that failed to embed last time.");
+    let old_a = write(&dir.join("kept/a.md"), "A broken page.");
+    let old_b = write(&dir.join("kept/b.md"), "Another broken page.");
+
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.vector_size = 2;
+    for path in [&old_a, &old_b] {
+        manifest.files.insert(path.clone(), ManifestFile { chunks: 1, point_ids: vec![json!(1)] });
+    }
+    let failed = QuarantinedFile { chunks: 1, error: "connection refused".to_string(), failed_at: 0, commit: None };
+    manifest.quarantine.insert(retried.clone(), failed);
+    manifest.save(&cfg).unwrap();
+
+    // The retry drops the old collection, re-queues its two sources, and
+    // both fail: more files are quarantined than were retried.
+    let stats = retry_quarantined(&cfg).unwrap();
+    assert_eq!(stats.indexed, 1);
+    let mut quarantined = stats.quarantined.clone();
+    quarantined.sort();
+    assert_eq!(quarantined, [old_a, old_b]);
+    assert!(!IndexManifest::load(&cfg).unwrap().quarantine.contains_key(&retried));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use rag::{
+    collection_descriptions, collection_descriptions_path, describe_collection, pick_collection,
+    remove_collection_description, route_question, Config,
+};
+
+fn temp_config(name: &str) -> Config {
+    let mut cfg = common::temp_config(&format!("collection-router-{}", name));
+    cfg.embed_model = "nomic-embed-text".to_string();
+    cfg
+}
+
+fn scores(list: &[(&str, f32)]) -> Vec<(String, f32)> {
+    list.iter().map(|(name, score)| (name.to_string(), *score)).collect()
+}
+
+#[test]
+fn only_a_clear_winner_is_picked() {
+    let clear = scores(&[("api_docs", 0.71), ("runbooks", 0.52)]);
+    assert_eq!(pick_collection(&clear, 0.3, 0.05).as_deref(), Some("api_docs"));
+    let close = scores(&[("api_docs", 0.61), ("runbooks", 0.59)]);
+    assert_eq!(pick_collection(&close, 0.3, 0.05), None);
+    let weak = scores(&[("api_docs", 0.21), ("runbooks", 0.02)]);
+    assert_eq!(pick_collection(&weak, 0.3, 0.05), None);
+    assert_eq!(pick_collection(&scores(&[("only", 0.4)]), 0.3, 0.05).as_deref(), Some("only"));
+    assert_eq!(pick_collection(&[], 0.3, 0.05), None);
+}
+
+#[test]
+fn descriptions_are_stored_and_removed() {
+    let cfg = temp_config("store");
+    let described = |text: &str, model: &str| {
+        serde_json::json!({
+            "description": text,
+            "embed_model": model,
+            "vector": [0.1, 0.2],
+            "described_at": "2026-10-16T12:00:00Z",
+        })
+    };
+    let stored = serde_json::json!({ "collections": {
+        "api_docs": described("HTTP API reference", "nomic-embed-text"),
+        "runbooks": described("On-call runbooks", "other-model"),
+    }});
+    std::fs::create_dir_all(&cfg.data_dir).unwrap();
+    std::fs::write(collection_descriptions_path(&cfg), stored.to_string()).unwrap();
+    let collections = collection_descriptions(&cfg);
+    assert_eq!(collections["api_docs"].description, "HTTP API reference");
+    assert_eq!(collections["api_docs"].described_at, 1_792_152_000);
+
+    // A description from another embedding model cannot be compared, so one
+    // usable collection is not enough to route.
+    assert!(route_question(&cfg, "How do I page the on-call?").unwrap_err().contains("at least two"));
+
+    assert!(remove_collection_description(&cfg, "runbooks").unwrap());
+    assert!(!remove_collection_description(&cfg, "runbooks").unwrap());
+    assert_eq!(collection_descriptions(&cfg).keys().collect::<Vec<_>>(), ["api_docs"]);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn descriptions_are_read_only_in_safe_mode() {
+    let mut cfg = temp_config("safe");
+    cfg.safe_mode = true;
+    assert!(describe_collection(&cfg, "api_docs", "HTTP API reference").unwrap_err().contains("Safe mode"));
+    assert!(remove_collection_description(&cfg, "api_docs").unwrap_err().contains("Safe mode"));
+    assert!(!collection_descriptions_path(&cfg).exists());
+}
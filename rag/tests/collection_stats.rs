@@ -0,0 +1,63 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+use rag::{collection_stats, format_bytes, CollectionStats, Config};
+
+#[test]
+fn formats_bytes_with_binary_units() {
+    assert_eq!(format_bytes(512), "512 B");
+    assert_eq!(format_bytes(1536), "1.5 KiB");
+    assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+#[test]
+fn display_lists_every_field() {
+    let stats = CollectionStats {
+        collection: "aicli_rag_chunks".to_string(),
+        points: 1200,
+        vector_size: 768,
+        distance: "Cosine".to_string(),
+        files: 85,
+        disk_bytes: None,
+    };
+    let text = stats.to_string();
+    assert!(text.contains("Points:      1200"));
+    assert!(text.contains("Files:       85"));
+    assert!(text.contains("Vector size: 768"));
+    assert!(text.ends_with("Disk usage:  (not reported)"));
+}
+
+// Answers every request with `status` and an empty JSON body.
+fn stub_qdrant(status: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let reply = format!("HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}", status);
+            let _ = stream.write_all(reply.as_bytes());
+        }
+    });
+    url
+}
+
+fn stub_config(status: &'static str) -> Config {
+    let mut cfg = Config::from_env();
+    cfg.qdrant_url = stub_qdrant(status);
+    cfg.qdrant_api_key = String::new();
+    cfg.http_retries = 0;
+    cfg
+}
+
+#[test]
+fn only_a_missing_collection_reads_as_absent() {
+    let err = collection_stats(&stub_config("404 Not Found")).unwrap_err();
+    assert!(err.contains("does not exist"), "{}", err);
+
+    for status in ["500 Internal Server Error", "401 Unauthorized"] {
+        let err = collection_stats(&stub_config(status)).unwrap_err();
+        assert!(!err.contains("does not exist"), "{}", err);
+        assert!(err.contains(&status[..3]), "{}", err);
+    }
+}
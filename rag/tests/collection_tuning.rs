@@ -0,0 +1,51 @@
+use rag::{Config, collection_tuning};
+use serde_json::json;
+
+fn base_config() -> Config {
+    let mut cfg = Config::from_env();
+    cfg.qdrant_on_disk_payload = false;
+    cfg.qdrant_vectors_on_disk = false;
+    cfg.qdrant_quantization = String::new();
+    cfg.qdrant_quantile = 0.99;
+    cfg.qdrant_pq_compression = "x16".to_string();
+    cfg.qdrant_hnsw_m = None;
+    cfg.qdrant_hnsw_ef_construct = None;
+    cfg
+}
+
+#[test]
+fn defaults_leave_qdrant_settings_alone() {
+    assert!(collection_tuning(&base_config()).unwrap().is_empty());
+}
+
+#[test]
+fn scalar_quantization_with_on_disk_storage() {
+    let mut cfg = base_config();
+    cfg.qdrant_on_disk_payload = true;
+    cfg.qdrant_vectors_on_disk = true;
+    cfg.qdrant_quantization = "Scalar".to_string();
+    cfg.qdrant_hnsw_m = Some(8);
+    let tuning = collection_tuning(&cfg).unwrap();
+    assert_eq!(tuning["on_disk_payload"], json!(true));
+    assert_eq!(tuning["hnsw_config"], json!({ "m": 8, "on_disk": true }));
+    assert_eq!(tuning["quantization_config"]["scalar"]["type"], json!("int8"));
+    assert_eq!(tuning["quantization_config"]["scalar"]["always_ram"], json!(true));
+}
+
+#[test]
+fn product_quantization_uses_configured_compression() {
+    let mut cfg = base_config();
+    cfg.qdrant_quantization = "product".to_string();
+    cfg.qdrant_pq_compression = "x32".to_string();
+    cfg.qdrant_hnsw_ef_construct = Some(64);
+    let tuning = collection_tuning(&cfg).unwrap();
+    assert_eq!(tuning["quantization_config"], json!({ "product": { "compression": "x32", "always_ram": true } }));
+    assert_eq!(tuning["hnsw_config"], json!({ "ef_construct": 64 }));
+}
+
+#[test]
+fn unknown_quantization_is_an_error() {
+    let mut cfg = base_config();
+    cfg.qdrant_quantization = "int4".to_string();
+    assert!(collection_tuning(&cfg).unwrap_err().contains("QDRANT_QUANTIZATION"));
+}
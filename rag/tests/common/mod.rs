@@ -0,0 +1,101 @@
+// Helpers shared by the integration tests; each test file that uses them
+// declares `mod common;`, so not every file uses every helper.
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use rag::{reset_replay, Config};
+
+/// A config that answers from the replay `fixture`, written to a temp file
+/// named after `name`, with no MCP server and nothing recorded.
+pub fn replay_config(name: &str, fixture: &str) -> Config {
+    let path = std::env::temp_dir().join(format!("aicli-{}-{}.jsonl", name, std::process::id()));
+    std::fs::write(&path, fixture).expect("fixture should be writable");
+    let mut cfg = Config::from_env();
+    cfg.llm_provider = "replay".to_string();
+    cfg.replay_file = path.to_string_lossy().to_string();
+    cfg.record_file = String::new();
+    cfg.mcp_url = String::new();
+    cfg.mcp_command = String::new();
+    reset_replay(&cfg.replay_file);
+    cfg
+}
+
+/// A config whose data, cache, and config directories are one fresh temp
+/// directory named after `name`.
+pub fn temp_config(name: &str) -> Config {
+    let dir = std::env::temp_dir().join(format!("aicli-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut cfg = Config::from_env();
+    cfg.data_dir = dir.to_string_lossy().to_string();
+    cfg.cache_dir = cfg.data_dir.clone();
+    cfg.config_dir = cfg.data_dir.clone();
+    cfg
+}
+
+/// Serve HTTP on a free local port, answering every request with the
+/// `(status, body)` that `handler(method, path, body)` returns. The base
+/// URL comes back; the server lives as long as the test process.
+pub fn stub_server<F>(handler: F) -> String
+where
+    F: Fn(&str, &str, &str) -> (u16, String) + Send + 'static,
+{
+    stub_server_with_headers(move |method, path, body| {
+        let (status, reply) = handler(method, path, body);
+        (status, Vec::new(), reply)
+    })
+}
+
+/// `stub_server` whose replies also carry extra headers, such as a
+/// redirect's `Location`.
+pub fn stub_server_with_headers<F>(handler: F) -> String
+where
+    F: Fn(&str, &str, &str) -> (u16, Vec<(String, String)>, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = respond(stream, &handler);
+        }
+    });
+    url
+}
+
+type Handler = dyn Fn(&str, &str, &str) -> (u16, Vec<(String, String)>, String);
+
+fn respond(mut stream: TcpStream, handler: &Handler) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let (status, headers, reply) = handler(&method, &path, &String::from_utf8_lossy(&body));
+    let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        if status < 400 { "OK" } else { "Error" },
+        reply.len(),
+        headers,
+        reply
+    )?;
+    stream.flush()
+}
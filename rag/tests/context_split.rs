@@ -0,0 +1,79 @@
+use rag::{allocate_context, estimate_tokens, parse_context_split, Config, ContextSplit, Message, TRIMMED_MARKER};
+
+fn message(role: &str, content: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: content.to_string(),
+    }
+}
+
+// A fixed budget of 1000 tokens, so the tests never ask Ollama for the window.
+fn config(split: &str) -> Config {
+    let mut cfg = Config::from_env();
+    cfg.generation.num_ctx = Some(100_000);
+    cfg.context_budget = Some(1000);
+    cfg.context_split = parse_context_split(split);
+    cfg
+}
+
+#[test]
+fn shares_are_scaled_to_one_hundred() {
+    assert_eq!(
+        parse_context_split("chunks=60, pinned=20, memory=20"),
+        Some(ContextSplit { chunks: 60, pinned: 20, memory: 20 })
+    );
+    assert_eq!(
+        parse_context_split("chunks=3,memory=1"),
+        Some(ContextSplit { chunks: 75, pinned: 0, memory: 25 })
+    );
+    assert_eq!(parse_context_split(""), None);
+    assert_eq!(parse_context_split("chunks=0,bogus=5"), None);
+}
+
+#[test]
+fn nothing_to_split_without_a_setting() {
+    let cfg = config("");
+    assert!(allocate_context(&cfg, "question", "pinned", &[]).is_none());
+}
+
+#[test]
+fn long_pinned_files_are_cut_to_their_share() {
+    let cfg = config("chunks=80,pinned=10,memory=10");
+    let pinned = "word ".repeat(500);
+    let split = allocate_context(&cfg, "question", &pinned, &[]).unwrap();
+    assert_eq!(split.allocation.total, 1000);
+    assert!(split.allocation.pinned.trimmed);
+    assert!(split.allocation.pinned.used <= 100);
+    assert!(split.pinned.ends_with(TRIMMED_MARKER));
+}
+
+#[test]
+fn oldest_turns_are_dropped_first_and_a_long_last_turn_is_cut() {
+    let cfg = config("chunks=80,pinned=10,memory=10");
+    let history = vec![
+        message("user", &"old ".repeat(60)),
+        message("assistant", &"older answer ".repeat(20)),
+        message("user", &"recent ".repeat(40)),
+    ];
+    let split = allocate_context(&cfg, "question", "", &history).unwrap();
+    assert_eq!(split.history.len(), 1);
+    assert_eq!(split.history[0].content, history[2].content);
+    assert!(split.allocation.memory.trimmed);
+
+    let history = vec![message("user", &"huge ".repeat(500))];
+    let split = allocate_context(&cfg, "question", "", &history).unwrap();
+    assert_eq!(split.history.len(), 1);
+    assert!(split.history[0].content.ends_with(TRIMMED_MARKER));
+    assert!(estimate_tokens(&split.history[0].content) <= 100);
+}
+
+#[test]
+fn unused_shares_go_to_chunks() {
+    let cfg = config("chunks=60,pinned=20,memory=20");
+    let split = allocate_context(&cfg, "question", "", &[]).unwrap();
+    assert_eq!(split.allocation.chunks.budget, 1000);
+
+    let split = allocate_context(&cfg, "question", &"word ".repeat(500), &[]).unwrap();
+    assert_eq!(split.allocation.chunks.budget, 800);
+    assert!(split.allocation.summary().starts_with("chunks 0/800 | pinned "));
+}
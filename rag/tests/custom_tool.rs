@@ -0,0 +1,92 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::replay_config;
+use rag::{run_agent, AgentEvent, AgentState, Config, LocalToolRegistry, McpClient, Tool};
+use serde_json::{json, Value};
+
+struct Adder;
+
+impl Tool for Adder {
+    fn name(&self) -> &str {
+        "add"
+    }
+
+    fn description(&self) -> &str {
+        "Add two integers."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "a": { "type": "integer" }, "b": { "type": "integer" } },
+            "required": ["a", "b"]
+        })
+    }
+
+    fn execute(&self, args: &Value) -> Result<String, String> {
+        let a = args["a"].as_i64().unwrap_or_default();
+        let b = args["b"].as_i64().unwrap_or_default();
+        Ok((a + b).to_string())
+    }
+}
+
+#[test]
+fn run_agent_dispatches_registered_tools_without_mcp() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"add\",\"arguments\":{\"a\":2}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"add\",\"arguments\":{\"a\":2,\"b\":3}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"5\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("dispatch", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(5);
+    state.events = Some(tx);
+    state.register_tool(Arc::new(Adder));
+    state.append_user("what is 2 + 3?".to_string());
+
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "5");
+    // The first call is missing `b` and never reaches `execute`.
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.content.contains("missing required field \"b\"")));
+    assert!(state.conversation.iter().any(|m| m.content.contains("Tool result [add]: 5")));
+    drop(state);
+    let called: Vec<&str> = rx
+        .iter()
+        .filter_map(|e| match e {
+            AgentEvent::ToolCalled { kind, .. } => Some(kind),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(called, vec!["local"]);
+}
+
+#[test]
+fn registered_tools_come_before_built_ins() {
+    struct Grep;
+    impl Tool for Grep {
+        fn name(&self) -> &str {
+            "grep"
+        }
+        fn execute(&self, _args: &Value) -> Result<String, String> {
+            Ok("custom grep".to_string())
+        }
+    }
+
+    let mut cfg = Config::from_env();
+    cfg.local_tools = vec!["grep".to_string(), "read_file".to_string()];
+    let mut tools = LocalToolRegistry::from_config(&cfg);
+    tools.register(Arc::new(Grep));
+    let names: Vec<String> = tools.specs().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, vec!["grep", "read_file"]);
+    assert_eq!(tools.call("grep", &json!({ "pattern": "x" })).unwrap(), "custom grep");
+    // Server tools shadow built-ins only.
+    assert!(tools.without(&["grep".to_string()]).has("grep"));
+}
@@ -0,0 +1,22 @@
+use rag::normalize_distance;
+
+#[test]
+fn accepts_qdrant_names_in_any_case() {
+    assert_eq!(normalize_distance("Cosine"), Ok("Cosine"));
+    assert_eq!(normalize_distance("dot"), Ok("Dot"));
+    assert_eq!(normalize_distance(" EUCLID "), Ok("Euclid"));
+    assert_eq!(normalize_distance("manhattan"), Ok("Manhattan"));
+}
+
+#[test]
+fn accepts_common_aliases() {
+    assert_eq!(normalize_distance("euclidean"), Ok("Euclid"));
+    assert_eq!(normalize_distance("l2"), Ok("Euclid"));
+    assert_eq!(normalize_distance("ip"), Ok("Dot"));
+}
+
+#[test]
+fn rejects_unknown_metrics() {
+    let err = normalize_distance("hamming").unwrap_err();
+    assert!(err.contains("Cosine, Dot, Euclid, Manhattan"));
+}
@@ -0,0 +1,59 @@
+use rag::{boost_doc_types, classify_chunk, doc_types_for_query, Hit};
+use serde_json::json;
+
+fn hit(path: &str, score: f32, doc_type: Option<&str>) -> Hit {
+    serde_json::from_value(json!({
+        "score": score,
+        "payload": { "path": path, "index": 0, "chunk": "", "doc_type": doc_type },
+    }))
+    .expect("hit should deserialize")
+}
+
+fn paths(hits: &[Hit]) -> Vec<String> {
+    hits.iter()
+        .map(|h| h.payload.as_ref().and_then(|p| p.path.clone()).unwrap_or_default())
+        .collect()
+}
+
+#[test]
+fn classifies_by_path_then_content() {
+    assert_eq!(classify_chunk("src/main.rs", "fn main() {}"), "code");
+    assert_eq!(classify_chunk("rag/tests/quota.rs", "fn main() {}"), "test");
+    assert_eq!(classify_chunk("app/test_models.py", ""), "test");
+    assert_eq!(classify_chunk("web/button.spec.ts", ""), "test");
+    assert_eq!(classify_chunk("src/lib.rs", "#[cfg(test)]\n#[test]\nfn works() {}"), "test");
+    assert_eq!(classify_chunk("Cargo.toml", "[package]"), "config");
+    assert_eq!(classify_chunk(".env.example", "A=1"), "config");
+    assert_eq!(classify_chunk("Dockerfile", "FROM rust"), "config");
+    assert_eq!(classify_chunk("README.md", "# aicli"), "docs");
+    assert_eq!(classify_chunk("docs/setup/index.rs", ""), "docs");
+    assert_eq!(classify_chunk("https://example.com/guide", ""), "docs");
+    assert_eq!(classify_chunk("CHANGELOG.md", "## 0.2.0"), "changelog");
+}
+
+#[test]
+fn questions_imply_doc_types() {
+    assert_eq!(doc_types_for_query("How do I configure the Qdrant URL?"), vec!["config", "docs"]);
+    assert_eq!(doc_types_for_query("What changed in the latest release?"), vec!["changelog"]);
+    assert_eq!(doc_types_for_query("Which tests cover chunking?"), vec!["test"]);
+    assert_eq!(doc_types_for_query("Where is retry implemented?"), vec!["code"]);
+    assert!(doc_types_for_query("latest qdrant").is_empty());
+}
+
+#[test]
+fn boosts_matching_hits_and_resorts() {
+    let hits = vec![
+        hit("src/config.rs", 0.80, Some("code")),
+        hit("README.md", 0.78, Some("docs")),
+        hit("old.rs", 0.79, None),
+    ];
+    let boosted = boost_doc_types(hits.clone(), &["config", "docs"], 0.05, false);
+    assert_eq!(paths(&boosted), vec!["README.md", "src/config.rs", "old.rs"]);
+    assert!((boosted[0].score - 0.83).abs() < 1e-6);
+
+    // Distance metrics rank lower scores first, so the boost subtracts.
+    let distances = vec![hit("a.rs", 0.2, Some("code")), hit("b.md", 0.22, Some("docs"))];
+    assert_eq!(paths(&boost_doc_types(distances, &["docs"], 0.05, true)), vec!["b.md", "a.rs"]);
+
+    assert_eq!(paths(&boost_doc_types(hits, &[], 0.05, false)), vec!["src/config.rs", "README.md", "old.rs"]);
+}
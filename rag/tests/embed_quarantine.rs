@@ -0,0 +1,62 @@
+mod common;
+
+use rag::{retry_quarantined, Config, IndexManifest, QuarantinedFile, ScanStats};
+
+fn temp_config(name: &str) -> Config {
+    let mut cfg = common::temp_config(&format!("embed-quarantine-{}", name));
+    cfg.collection = "quarantine_test".to_string();
+    cfg.safe_mode = false;
+    cfg
+}
+
+fn quarantined(commit: Option<&str>) -> QuarantinedFile {
+    QuarantinedFile {
+        chunks: 3,
+        error: "connection refused".to_string(),
+        failed_at: 1_792_152_000,
+        commit: commit.map(|c| c.to_string()),
+    }
+}
+
+#[test]
+fn summary_points_at_retry_failed() {
+    let stats = ScanStats {
+        quarantined: vec!["src/a.rs".to_string(), "src/b.rs".to_string()],
+        ..ScanStats::default()
+    };
+    assert_eq!(
+        stats.summary(),
+        vec!["quarantined 2 files (embedding failed); run `aicli index retry-failed`".to_string()]
+    );
+}
+
+#[test]
+fn quarantine_is_kept_in_the_manifest() {
+    let cfg = temp_config("manifest");
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.quarantine.insert("src/a.rs".to_string(), quarantined(None));
+    manifest.save(&cfg).unwrap();
+
+    let text = std::fs::read_to_string(rag::manifest_path(&cfg)).unwrap();
+    assert!(text.contains("\"failed_at\": \"2026-10-16T12:00:00Z\""));
+    assert_eq!(IndexManifest::load(&cfg).unwrap().quarantine["src/a.rs"], quarantined(None));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn retry_drops_missing_files_and_skips_git_clones() {
+    let cfg = temp_config("retry");
+    let missing = std::env::temp_dir().join(format!("aicli-missing-{}.rs", std::process::id()));
+    let missing = missing.to_string_lossy().to_string();
+    let blob = "https://github.com/acme/app/blob/abc123/src/lib.rs".to_string();
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.quarantine.insert(missing.clone(), quarantined(None));
+    manifest.quarantine.insert(blob.clone(), quarantined(Some("abc123")));
+    manifest.save(&cfg).unwrap();
+
+    let stats = retry_quarantined(&cfg).unwrap();
+    assert_eq!(stats.indexed, 0);
+    assert!(stats.skipped.contains(&(missing, "source missing".to_string())));
+    assert_eq!(IndexManifest::load(&cfg).unwrap().quarantine.keys().collect::<Vec<_>>(), [&blob]);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
@@ -0,0 +1,50 @@
+use rag::{format_quotes, parse_quotes, Hit};
+use serde_json::json;
+
+fn hits() -> Vec<Hit> {
+    serde_json::from_value(json!([
+        { "score": 0.9, "payload": { "path": "docs/retry.md", "index": 0,
+          "chunk": "Requests are retried twice.\nThe delay doubles   after each attempt." } },
+        { "score": 0.7, "payload": { "path": "docs/auth.md", "index": 2,
+          "chunk": "Tokens expire after one hour." } }
+    ]))
+    .unwrap()
+}
+
+#[test]
+fn keeps_verbatim_quotes_ignoring_whitespace() {
+    let raw = r#"{"quotes": [
+        {"source": 1, "text": "The delay doubles after each attempt."},
+        {"source": 2, "text": "\"Tokens expire after one hour.\""}
+    ]}"#;
+    let quotes = parse_quotes(raw, &hits()).unwrap();
+    assert_eq!(quotes.len(), 2);
+    assert_eq!(quotes[0].citation, "docs/retry.md");
+    assert_eq!(quotes[1].text, "Tokens expire after one hour.");
+}
+
+#[test]
+fn drops_paraphrases_and_unknown_sources() {
+    let raw = r#"{"quotes": [
+        {"source": 1, "text": "Requests are retried three times."},
+        {"source": 5, "text": "Tokens expire after one hour."},
+        {"source": 2, "text": "Tokens expire after one hour."},
+        {"source": 2, "text": "Tokens expire after one hour."}
+    ]}"#;
+    let quotes = parse_quotes(raw, &hits()).unwrap();
+    assert_eq!(quotes.len(), 1);
+    assert_eq!(quotes[0].source, 2);
+}
+
+#[test]
+fn invalid_json_is_an_error() {
+    assert!(parse_quotes("not json", &hits()).is_err());
+}
+
+#[test]
+fn formats_quotes_as_cited_blockquotes() {
+    let raw = r#"{"quotes": [{"source": 2, "text": "Tokens expire after one hour."}]}"#;
+    let quotes = parse_quotes(raw, &hits()).unwrap();
+    assert_eq!(format_quotes(&quotes), "[2] docs/auth.md\n> Tokens expire after one hour.");
+    assert_eq!(format_quotes(&[]), "(no verbatim passages found)");
+}
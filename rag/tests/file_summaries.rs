@@ -0,0 +1,84 @@
+mod common;
+
+use rag::{
+    cached_summary, indexed_path, pending_summaries, summaries_path, summarize_file, summary_key, Config,
+    IndexManifest, ManifestFile, TokenBucket,
+};
+use std::time::{Duration, Instant};
+
+fn temp_config(name: &str) -> Config {
+    let mut cfg = common::temp_config(&format!("file-summaries-{}", name));
+    cfg.collection = "docs".to_string();
+    cfg.chunk_size = 1200;
+    cfg
+}
+
+fn file(chunks: usize, id: &str) -> ManifestFile {
+    ManifestFile { chunks, point_ids: vec![serde_json::json!(id)] }
+}
+
+#[test]
+fn the_bucket_paces_summaries_to_the_budget() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(600, start);
+    assert!(bucket.try_take(400, start));
+    assert!(!bucket.try_take(400, start));
+    // 600 a minute refills 10 a second.
+    assert!(!bucket.try_take(400, start + Duration::from_secs(10)));
+    assert!(bucket.try_take(400, start + Duration::from_secs(30)));
+    // A file costing more than a minute's budget waits for a full bucket.
+    assert!(bucket.try_take(5000, start + Duration::from_secs(200)));
+    assert!(!TokenBucket::new(0, start).try_take(1, start));
+}
+
+#[test]
+fn stale_and_missing_summaries_are_pending() {
+    let cfg = temp_config("pending");
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.files.insert("/repo/src/main.rs".to_string(), file(2, "a"));
+    manifest.files.insert("/repo/README.md".to_string(), file(20, "b"));
+    manifest.save(&cfg).unwrap();
+    assert_eq!(
+        pending_summaries(&cfg),
+        vec![("/repo/README.md".to_string(), 3000), ("/repo/src/main.rs".to_string(), 600)]
+    );
+
+    let summary =
+        |text: &str, key: String| serde_json::json!({ "summary": text, "key": key, "model": "m", "created_at": 1 });
+    let stored = serde_json::json!({ "files": {
+        "/repo/src/main.rs": summary("The entry point.", summary_key(&file(2, "a"))),
+        "/repo/README.md": summary("Old.", summary_key(&file(20, "old"))),
+    }});
+    std::fs::create_dir_all(summaries_path(&cfg).parent().unwrap()).unwrap();
+    std::fs::write(summaries_path(&cfg), stored.to_string()).unwrap();
+    assert_eq!(pending_summaries(&cfg), vec![("/repo/README.md".to_string(), 3000)]);
+    assert_eq!(cached_summary(&cfg, "/repo/src/main.rs").unwrap().summary, "The entry point.");
+    assert!(cached_summary(&cfg, "/repo/README.md").is_none());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn files_are_found_by_a_path_suffix() {
+    let cfg = temp_config("lookup");
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.files.insert("/repo/src/main.rs".to_string(), file(1, "a"));
+    manifest.files.insert("/repo/rag/src/main.rs".to_string(), file(1, "b"));
+    manifest.save(&cfg).unwrap();
+    assert_eq!(indexed_path(&cfg, "rag/src/main.rs").unwrap(), "/repo/rag/src/main.rs");
+    assert_eq!(indexed_path(&cfg, "/repo/src/main.rs").unwrap(), "/repo/src/main.rs");
+    assert!(indexed_path(&cfg, "main.rs").unwrap_err().contains("matches 2"));
+    assert!(indexed_path(&cfg, "lib.rs").is_err());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn summarizing_is_disabled_in_safe_mode() {
+    let mut cfg = temp_config("safe");
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.files.insert("/repo/src/main.rs".to_string(), file(1, "a"));
+    manifest.save(&cfg).unwrap();
+    cfg.safe_mode = true;
+    assert!(summarize_file(&cfg, "/repo/src/main.rs").unwrap_err().contains("Safe mode"));
+    assert!(!summaries_path(&cfg).exists());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
@@ -0,0 +1,48 @@
+use rag::{FinalDetails, parse_final_details};
+
+#[test]
+fn reads_structured_fields_from_final_action() {
+    let raw = r#"{"action":"final","answer":"Use --recreate.","confidence":0.8,"sources_used":[2,1,2],"follow_ups":["How do I migrate distance?"," "]}"#;
+    let details = parse_final_details(raw).expect("fields should validate");
+    assert_eq!(
+        details,
+        FinalDetails {
+            confidence: Some(0.8),
+            sources_used: vec![2, 1],
+            follow_ups: vec!["How do I migrate distance?".to_string()],
+        }
+    );
+}
+
+#[test]
+fn fields_inside_arguments_are_accepted() {
+    let raw = r#"{"action":"final","arguments":{"answer":"4","confidence":1}}"#;
+    assert_eq!(parse_final_details(raw).unwrap().confidence, Some(1.0));
+}
+
+#[test]
+fn plain_final_has_no_details() {
+    let details = parse_final_details(r#"{"action":"final","answer":"4"}"#).unwrap();
+    assert!(details.is_empty());
+    assert!(details.footer_lines().is_empty());
+}
+
+#[test]
+fn rejects_values_outside_the_schema() {
+    assert!(parse_final_details(r#"{"action":"final","answer":"x","confidence":85}"#).is_err());
+    assert!(parse_final_details(r#"{"action":"final","answer":"x","sources_used":["a"]}"#).is_err());
+    assert!(parse_final_details(r#"{"action":"final","answer":"x","follow_ups":"more?"}"#).is_err());
+}
+
+#[test]
+fn footer_lists_confidence_sources_and_follow_ups() {
+    let details = FinalDetails {
+        confidence: Some(0.75),
+        sources_used: vec![1, 3],
+        follow_ups: vec!["What about Dot?".to_string()],
+    };
+    assert_eq!(
+        details.footer_lines(),
+        vec!["Confidence: 75%  |  Sources used: [1] [3]".to_string(), "Follow-up: What about Dot?".to_string()]
+    );
+}
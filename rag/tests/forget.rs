@@ -0,0 +1,38 @@
+use std::collections::BTreeMap;
+
+use rag::{forget_matches, ForgetPlan};
+
+#[test]
+fn patterns_match_whole_paths_or_directory_runs() {
+    assert!(forget_matches("node_modules", "web/node_modules/react/index.js"));
+    assert!(forget_matches("node_modules/", "node_modules/a.js"));
+    assert!(forget_matches("*.min.js", "static/js/app.min.js"));
+    assert!(forget_matches("docs/*.md", "docs/guide.md"));
+    assert!(forget_matches("build", "C:\\repo\\build\\out.txt"));
+    assert!(!forget_matches("node_modules", "src/node_modules_helper.rs"));
+    assert!(!forget_matches("*.min.js", "static/js/app.js"));
+    assert!(!forget_matches("  ", "anything"));
+}
+
+#[test]
+fn summary_lists_the_largest_files_first() {
+    let files: BTreeMap<String, usize> = [("a.js", 2), ("b.js", 9), ("c.js", 5)]
+        .into_iter()
+        .map(|(p, n)| (p.to_string(), n))
+        .collect();
+    let plan = ForgetPlan {
+        pattern: "*.js".to_string(),
+        files,
+    };
+    assert_eq!(plan.points(), 16);
+    assert_eq!(
+        plan.summary(2),
+        "*.js matches 16 points in 3 files:\n  b.js (9)\n  c.js (5)\n  ... and 1 more\n"
+    );
+
+    let empty = ForgetPlan {
+        pattern: "vendor".to_string(),
+        ..ForgetPlan::default()
+    };
+    assert_eq!(empty.summary(10), "No indexed files match vendor.");
+}
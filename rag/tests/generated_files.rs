@@ -0,0 +1,56 @@
+use rag::{generated_reason, ScanStats};
+
+#[test]
+fn lockfiles_and_generated_names_are_skipped() {
+    assert_eq!(generated_reason("./Cargo.lock", "[[package]]\n"), Some("lockfile"));
+    assert_eq!(generated_reason("web/package-lock.json", "{}"), Some("lockfile"));
+    assert_eq!(generated_reason("static/app.min.js", "x"), Some("generated name"));
+    assert_eq!(generated_reason("api/service_pb2.py", "x"), Some("generated name"));
+}
+
+#[test]
+fn generated_marker_near_the_top_is_detected() {
+    let text = "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n";
+    assert_eq!(generated_reason("api/service.go", text), Some("generated marker"));
+    let text = "#![allow(dead_code)]\n// @generated by build.rs\nfn main() {}\n";
+    assert_eq!(generated_reason("src/bindings.rs", text), Some("generated marker"));
+}
+
+#[test]
+fn minified_and_high_entropy_text_is_detected() {
+    let minified = "var a=1;".repeat(1_000);
+    assert_eq!(generated_reason("bundle.js", &minified), Some("minified"));
+
+    let blob: String = (0..4_000u32)
+        .map(|i| char::from(b'!' + ((i * 7919 + i / 3) % 90) as u8))
+        .collect::<Vec<_>>()
+        .chunks(76)
+        .map(|line| line.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert_eq!(generated_reason("fixtures/blob.txt", &blob), Some("high entropy"));
+}
+
+#[test]
+fn ordinary_source_is_kept() {
+    let text = "fn main() {\n    println!(\"hello\");\n}\n".repeat(50);
+    assert_eq!(generated_reason("src/main.rs", &text), None);
+    assert_eq!(generated_reason("README.md", "# Title\n\nSome prose.\n"), None);
+}
+
+#[test]
+fn summary_groups_skips_by_reason() {
+    let stats = ScanStats {
+        indexed: 3,
+        skipped: vec![
+            ("Cargo.lock".to_string(), "lockfile".to_string()),
+            ("yarn.lock".to_string(), "lockfile".to_string()),
+            ("app.min.js".to_string(), "generated name".to_string()),
+        ],
+        ..ScanStats::default()
+    };
+    assert_eq!(
+        stats.summary(),
+        vec!["skipped 1 file (generated name)".to_string(), "skipped 2 files (lockfile)".to_string()]
+    );
+}
@@ -0,0 +1,36 @@
+use rag::GenerationOptions;
+
+#[test]
+fn overrides_replace_only_the_fields_they_set() {
+    let base = GenerationOptions {
+        temperature: Some(0.8),
+        top_p: Some(0.9),
+        num_ctx: Some(8192),
+        seed: None,
+        num_predict: Some(512),
+        stop: vec!["</s>".to_string()],
+    };
+    let overrides = GenerationOptions {
+        temperature: Some(0.0),
+        seed: Some(7),
+        ..GenerationOptions::default()
+    };
+    let merged = base.merged(&overrides);
+    assert_eq!(merged.temperature, Some(0.0));
+    assert_eq!(merged.top_p, Some(0.9));
+    assert_eq!(merged.num_ctx, Some(8192));
+    assert_eq!(merged.seed, Some(7));
+    assert_eq!(merged.num_predict, Some(512));
+    assert_eq!(merged.stop, vec!["</s>".to_string()]);
+}
+
+#[test]
+fn unset_fields_are_not_serialized() {
+    let options = GenerationOptions {
+        temperature: Some(0.2),
+        num_ctx: Some(4096),
+        ..GenerationOptions::default()
+    };
+    let json = serde_json::to_value(&options).unwrap();
+    assert_eq!(json, serde_json::json!({ "temperature": 0.2f32, "num_ctx": 4096 }));
+}
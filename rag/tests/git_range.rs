@@ -0,0 +1,17 @@
+use rag::{collection_for_git_range, parse_git_range};
+
+#[test]
+fn ranges_default_their_end_to_head() {
+    assert_eq!(parse_git_range("v1.2..HEAD").unwrap(), ("v1.2".to_string(), "HEAD".to_string()));
+    assert_eq!(parse_git_range(" v1.2.. ").unwrap(), ("v1.2".to_string(), "HEAD".to_string()));
+    assert_eq!(parse_git_range("main...feature/x").unwrap(), ("main".to_string(), "feature/x".to_string()));
+    assert!(parse_git_range("v1.2").is_err());
+    assert!(parse_git_range("..HEAD").is_err());
+    assert!(parse_git_range("--output=x..HEAD").is_err());
+}
+
+#[test]
+fn delta_collections_sit_next_to_their_base() {
+    assert_eq!(collection_for_git_range("app_rag_chunks", "v1.2..HEAD"), "app_delta_v1_2__HEAD_rag_chunks");
+    assert_eq!(collection_for_git_range("notes", "a...b"), "notes_delta_a___b_rag_chunks");
+}
@@ -0,0 +1,15 @@
+use std::time::Duration;
+
+use rag::retry_delay;
+
+#[test]
+fn retry_delay_doubles_from_base() {
+    assert_eq!(retry_delay(0), Duration::from_millis(250));
+    assert_eq!(retry_delay(1), Duration::from_millis(500));
+    assert_eq!(retry_delay(3), Duration::from_millis(2000));
+}
+
+#[test]
+fn retry_delay_does_not_overflow() {
+    assert!(retry_delay(u32::MAX) >= retry_delay(16));
+}
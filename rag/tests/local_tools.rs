@@ -0,0 +1,184 @@
+mod common;
+
+use common::stub_server_with_headers;
+use rag::{sandbox_root, Config, LocalToolRegistry};
+use serde_json::json;
+
+fn workspace(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("aicli-local-tools-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/main.rs"), "fn main() {\n    retry_request();\n}\n").unwrap();
+    std::fs::write(dir.join("notes.txt"), "Retry twice before giving up.\n").unwrap();
+    dir
+}
+
+fn registry(dir: &std::path::Path, tools: &[&str], shell_allow: &[&str]) -> LocalToolRegistry {
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.workspace_dir = dir.to_string_lossy().to_string();
+    cfg.local_tools = tools.iter().map(|t| t.to_string()).collect();
+    cfg.shell_allow = shell_allow.iter().map(|t| t.to_string()).collect();
+    LocalToolRegistry::from_config(&cfg)
+}
+
+#[test]
+fn reads_files_inside_the_workspace_only() {
+    let dir = workspace("read");
+    let tools = registry(&dir, &["read_file"], &[]);
+
+    let text = tools.call("read_file", &json!({ "path": "src/main.rs", "start_line": 2 })).unwrap();
+    assert!(text.contains("2      retry_request();"), "{}", text);
+    assert!(!text.contains("fn main"), "{}", text);
+
+    let escaped = tools.call("read_file", &json!({ "path": "../outside.txt" })).unwrap_err();
+    assert!(escaped.contains("outside the workspace"), "{}", escaped);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn greps_case_insensitively() {
+    let dir = workspace("grep");
+    let tools = registry(&dir, &["grep"], &[]);
+    let text = tools.call("grep", &json!({ "pattern": "RETRY" })).unwrap();
+    assert!(text.contains("notes.txt:1: Retry twice"), "{}", text);
+    assert!(text.contains("main.rs:2: retry_request();"), "{}", text);
+
+    let none = tools.call("grep", &json!({ "pattern": "timeout" })).unwrap();
+    assert_eq!(none, "No matches for 'timeout'");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn shell_runs_only_allowed_programs() {
+    let dir = workspace("shell");
+    let tools = registry(&dir, &["shell"], &["echo"]);
+    assert_eq!(tools.call("shell", &json!({ "command": "echo hi" })).unwrap(), "[exit 0]\nhi");
+
+    let refused = tools.call("shell", &json!({ "command": "rm -rf src" })).unwrap_err();
+    assert!(refused.contains("'rm' is not an allowed program"), "{}", refused);
+    assert!(dir.join("src/main.rs").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn config_picks_the_offered_tools() {
+    let dir = workspace("config");
+    let tools = registry(&dir, &["read_file", "shell", "bogus"], &[]);
+    let names: Vec<String> = tools.specs().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, vec!["read_file", "shell"]);
+    assert!(!tools.has("grep"));
+    assert!(tools.call("grep", &json!({ "pattern": "x" })).is_err());
+
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = true;
+    cfg.local_tools = vec!["read_file".to_string(), "shell".to_string()];
+    cfg.local_tools.push("fetch_url".to_string());
+    let safe = LocalToolRegistry::from_config(&cfg);
+    assert!(safe.has("read_file"));
+    assert!(!safe.has("shell"));
+    assert!(!safe.has("fetch_url"));
+
+    let shadowed = tools.without(&["read_file".to_string()]);
+    assert!(!shadowed.has("read_file"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sandbox_defaults_to_the_git_root_of_the_source_dir() {
+    let dir = workspace("root");
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    let mut cfg = Config::from_env();
+    cfg.workspace_dir = String::new();
+    cfg.source_dir = dir.join("src").to_string_lossy().to_string();
+    assert_eq!(sandbox_root(&cfg), dir.canonicalize().unwrap());
+
+    cfg.workspace_dir = dir.join("src").to_string_lossy().to_string();
+    assert_eq!(sandbox_root(&cfg), dir.join("src").canonicalize().unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_and_shell_paths_cannot_leave_the_sandbox() {
+    let dir = workspace("symlink");
+    let outside = workspace("symlink-outside");
+    std::os::unix::fs::symlink(&outside, dir.join("escape")).unwrap();
+    let tools = registry(&dir, &["read_file", "shell"], &["cat"]);
+
+    let err = tools.call("read_file", &json!({ "path": "escape/notes.txt" })).unwrap_err();
+    assert!(err.contains("outside the workspace"), "{}", err);
+    assert!(tools.resolve("escape/../notes.txt").is_err());
+    let err = tools.call("shell", &json!({ "command": "cat ../x/notes.txt" })).unwrap_err();
+    assert!(err.contains("outside the workspace"), "{}", err);
+    let err = tools.call("shell", &json!({ "command": "cat /etc/hostname" })).unwrap_err();
+    assert!(err.contains("outside the workspace"), "{}", err);
+    assert!(tools.call("shell", &json!({ "command": "cat notes.txt" })).unwrap().contains("Retry twice"));
+
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.workspace_dir = dir.to_string_lossy().to_string();
+    cfg.local_tools = vec!["read_file".to_string()];
+    cfg.sandbox_extra = vec![outside.to_string_lossy().to_string()];
+    let widened = LocalToolRegistry::from_config(&cfg);
+    assert!(widened.call("read_file", &json!({ "path": "escape/notes.txt" })).is_ok());
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&outside);
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_flag_values_without_equals_are_checked() {
+    let dir = workspace("attached-flags");
+    let tools = registry(&dir, &["shell"], &["cat", "grep", "ls"]);
+    let escapes = ["ls -C/etc", "grep -f/etc/passwd x", "grep --file/etc/shadow x", "cat -- /etc/hostname", "ls -d../"];
+    for command in escapes {
+        let err = tools.call("shell", &json!({ "command": command })).unwrap_err();
+        assert!(err.contains("outside the workspace"), "{}: {}", command, err);
+    }
+    assert!(tools.call("shell", &json!({ "command": "ls -la src" })).unwrap().contains("main.rs"));
+    assert!(tools.call("shell", &json!({ "command": "grep -n retry notes.txt" })).unwrap().starts_with("[exit"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_refuses_relative_symlinks_that_leave_the_sandbox() {
+    let dir = workspace("shell-link");
+    let outside = workspace("shell-link-outside");
+    std::os::unix::fs::symlink(outside.join("notes.txt"), dir.join("link")).unwrap();
+    std::os::unix::fs::symlink("notes.txt", dir.join("inner")).unwrap();
+    let tools = registry(&dir, &["shell"], &["cat"]);
+
+    let err = tools.call("shell", &json!({ "command": "cat link" })).unwrap_err();
+    assert!(err.contains("outside the workspace"), "{}", err);
+    assert!(tools.call("shell", &json!({ "command": "cat inner" })).unwrap().contains("Retry twice"));
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&outside);
+}
+
+#[test]
+fn fetch_url_stays_off_private_hosts_unless_allowed() {
+    let url = stub_server_with_headers(|_, path, _| match path {
+        "/page" => (200, Vec::new(), "<p>Public notes.</p>".to_string()),
+        _ => (302, vec![("Location".to_string(), path.replace("/hop", "http://localhost:6333"))], String::new()),
+    });
+    let dir = workspace("fetch");
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.workspace_dir = dir.to_string_lossy().to_string();
+    cfg.local_tools = vec!["fetch_url".to_string()];
+    cfg.http_retries = 0;
+
+    let tools = LocalToolRegistry::from_config(&cfg);
+    let err = tools.call("fetch_url", &json!({ "url": format!("{}/page", url) })).unwrap_err();
+    assert!(err.contains("not a public address"), "{}", err);
+
+    cfg.fetch_allow = vec!["127.0.0.1".to_string()];
+    let tools = LocalToolRegistry::from_config(&cfg);
+    assert_eq!(tools.call("fetch_url", &json!({ "url": format!("{}/page", url) })).unwrap(), "Public notes.");
+    // Each redirect hop is checked again.
+    let err = tools.call("fetch_url", &json!({ "url": format!("{}/hop/collections", url) })).unwrap_err();
+    assert!(err.contains("fetch_url refuses localhost"), "{}", err);
+    let _ = std::fs::remove_dir_all(&dir);
+}
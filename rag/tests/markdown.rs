@@ -0,0 +1,42 @@
+use rag::render_markdown;
+
+#[test]
+fn draws_tables_with_box_characters_and_alignment() {
+    let text = "| Name | Count |\n|:-----|------:|\n| alpha | 3 |\n| b | 12 |\n";
+    assert_eq!(
+        render_markdown(text),
+        concat!(
+            "┌───────┬───────┐\n",
+            "│ Name  │ Count │\n",
+            "├───────┼───────┤\n",
+            "│ alpha │     3 │\n",
+            "│ b     │    12 │\n",
+            "└───────┴───────┘\n",
+        )
+    );
+}
+
+#[test]
+fn pipes_without_a_separator_row_are_not_a_table() {
+    let text = "| just a line |";
+    assert_eq!(render_markdown(text), text);
+}
+
+#[test]
+fn renders_scripts_and_math_symbols() {
+    assert_eq!(render_markdown("E = mc^2 and H_2O"), "E = mc² and H₂O");
+    assert_eq!(render_markdown("$a_{ij} \\le 10^{-3}$"), "aᵢⱼ ≤ 10⁻³");
+    assert_eq!(render_markdown("$x \\times y = z$"), "x × y = z");
+}
+
+#[test]
+fn leaves_code_identifiers_and_dollars_alone() {
+    for text in [
+        "snake_case and version_2 and x_1b",
+        "`x^2` stays literal",
+        "costs $5 and $10, see $HOME",
+        "```\nlet y = x^2;\n```",
+    ] {
+        assert_eq!(render_markdown(text), text);
+    }
+}
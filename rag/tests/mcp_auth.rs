@@ -0,0 +1,161 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use rag::{
+    form_urlencode, load_cached_token, remove_cached_token, save_cached_token, CachedToken, Config, McpAuth,
+    McpClient, OAuthDevice,
+};
+use serde_json::{json, Value};
+
+fn device() -> OAuthDevice {
+    OAuthDevice {
+        device_url: "https://auth.example.com/device".to_string(),
+        token_url: "https://auth.example.com/token".to_string(),
+        client_id: "aicli".to_string(),
+        scope: "mcp".to_string(),
+    }
+}
+
+#[test]
+fn the_first_configured_method_wins() {
+    assert_eq!(McpAuth::from_parts(" abc ", "print-token", device()), McpAuth::Token("abc".to_string()));
+    assert_eq!(
+        McpAuth::from_parts("", "print-token", device()),
+        McpAuth::TokenCommand("print-token".to_string())
+    );
+    assert_eq!(McpAuth::from_parts("", "", device()), McpAuth::Device(device()));
+    let incomplete = OAuthDevice {
+        client_id: String::new(),
+        ..device()
+    };
+    assert!(McpAuth::from_parts("", "", incomplete).is_none());
+}
+
+#[test]
+fn forms_are_percent_encoded() {
+    assert_eq!(
+        form_urlencode(&[("grant_type", "urn:ietf:params"), ("scope", "read write~")]),
+        "grant_type=urn%3Aietf%3Aparams&scope=read+write~"
+    );
+}
+
+#[test]
+fn cached_tokens_round_trip_and_expire() {
+    let dir = std::env::temp_dir().join(format!("aicli-mcp-auth-{}", std::process::id()));
+    let data_dir = dir.to_string_lossy().to_string();
+    let token = CachedToken {
+        access_token: "at".to_string(),
+        refresh_token: Some("rt".to_string()),
+        expires_at: Some(1_000),
+    };
+    save_cached_token(&data_dir, &device(), &token).unwrap();
+    assert_eq!(load_cached_token(&data_dir, &device()), Some(token.clone()));
+    assert!(token.is_expired(990));
+    assert!(!token.is_expired(900));
+    assert!(remove_cached_token(&data_dir, &device()).unwrap());
+    assert!(!remove_cached_token(&data_dir, &device()).unwrap());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn cached_tokens_are_private_to_the_user() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("aicli-mcp-auth-mode-{}", std::process::id()));
+    let data_dir = dir.to_string_lossy().to_string();
+    let token = CachedToken {
+        access_token: "at".to_string(),
+        refresh_token: None,
+        expires_at: None,
+    };
+    let path = save_cached_token(&data_dir, &device(), &token).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    // A token file left world-readable is tightened when it is rewritten.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    save_cached_token(&data_dir, &device(), &token).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+// Answers MCP over Streamable HTTP when the bearer token is "secret", and
+// with 401 otherwise. `tools/list` replies as an event stream.
+fn serve(listener: TcpListener) {
+    for stream in listener.incoming().flatten() {
+        handle(stream);
+    }
+}
+
+fn handle(mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut authorized = false;
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap_or_default();
+        match name.to_ascii_lowercase().as_str() {
+            "authorization" => authorized = value.trim() == "Bearer secret",
+            "content-length" => length = value.trim().parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let reply = |status: &str, headers: &str, body: &str| {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    };
+    let response = if !authorized {
+        reply("401 Unauthorized", "WWW-Authenticate: Bearer\r\n", "")
+    } else if request_line.starts_with("DELETE") {
+        reply("200 OK", "", "")
+    } else {
+        let message: Value = serde_json::from_slice(&body).unwrap();
+        let id = message["id"].clone();
+        match message["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let result = json!({ "jsonrpc": "2.0", "id": id, "result": { "protocolVersion": "2025-03-26" } });
+                reply(
+                    "200 OK",
+                    "Content-Type: application/json\r\nMcp-Session-Id: s1\r\n",
+                    &result.to_string(),
+                )
+            }
+            "tools/list" => {
+                let result = json!({ "jsonrpc": "2.0", "id": id, "result": { "tools": [{ "name": "greet" }] } });
+                let events = format!("event: message\ndata: {}\n\n", result);
+                reply("200 OK", "Content-Type: text/event-stream\r\n", &events)
+            }
+            _ => reply("202 Accepted", "", ""),
+        }
+    };
+    let _ = stream.write_all(response.as_bytes());
+}
+
+#[test]
+fn authenticated_servers_get_the_bearer_token() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    std::thread::spawn(move || serve(listener));
+
+    let mut cfg = Config::from_env();
+    cfg.mcp_url = url;
+    cfg.mcp_auth = McpAuth::Token("secret".to_string());
+    let client = McpClient::from_config(&cfg);
+    assert_eq!(client.list_tools().unwrap(), vec!["greet".to_string()]);
+
+    cfg.mcp_auth = McpAuth::Token("wrong".to_string());
+    let err = McpClient::from_config(&cfg).list_tools().unwrap_err();
+    assert!(err.contains("401"), "{}", err);
+}
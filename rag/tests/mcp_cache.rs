@@ -0,0 +1,116 @@
+use rag::{
+    discover_capabilities_cached, load_cached_capabilities, mcp_cache_path, save_cached_capabilities, schema_hash,
+    CachedCapabilities, Config, McpCapabilities, McpClient, McpTool,
+};
+use serde_json::json;
+
+fn config(name: &str) -> Config {
+    let dir = std::env::temp_dir().join(format!("aicli-mcp-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut cfg = Config::from_env();
+    cfg.data_dir = dir.to_string_lossy().to_string();
+    cfg.cache_dir = cfg.data_dir.clone();
+    cfg.mcp_url = String::new();
+    cfg.mcp_command = "/nonexistent/mcp-server".to_string();
+    cfg.mcp_args = vec!["--stdio".to_string()];
+    cfg.mcp_cache = true;
+    cfg
+}
+
+fn capabilities() -> McpCapabilities {
+    let greet = McpTool {
+        name: "greet".to_string(),
+        description: "Say hello".to_string(),
+        input_schema: json!({ "type": "object", "properties": { "name": { "type": "string" } } }),
+    };
+    McpCapabilities {
+        tools: vec![greet.name.clone()],
+        tool_specs: vec![greet],
+        prompts: vec!["review-code".to_string()],
+        resources: vec!["config://app".to_string()],
+        diagnostics: Vec::new(),
+    }
+}
+
+#[test]
+fn round_trips_per_server() {
+    let cfg = config("roundtrip");
+    let cached = CachedCapabilities::from_capabilities("stdio server-a", &capabilities());
+    assert_eq!(cached.tools[0].schema_hash, schema_hash(&capabilities().tool_specs[0].input_schema));
+    save_cached_capabilities(&cfg, &cached).unwrap();
+
+    assert_eq!(load_cached_capabilities(&cfg, "stdio server-a"), Some(cached.clone()));
+    assert_eq!(load_cached_capabilities(&cfg, "stdio server-b"), None);
+    assert_ne!(mcp_cache_path(&cfg, "stdio server-a"), mcp_cache_path(&cfg, "stdio server-b"));
+
+    let caps = cached.to_capabilities();
+    assert_eq!(caps.tools, vec!["greet"]);
+    assert_eq!(caps.tool_specs, capabilities().tool_specs);
+    assert!(caps.diagnostics.is_empty());
+
+    let mut changed = capabilities();
+    changed.tool_specs[0].input_schema = json!({ "type": "object" });
+    assert!(!cached.same_as(&CachedCapabilities::from_capabilities("stdio server-a", &changed)));
+    assert!(cached.same_as(&CachedCapabilities::from_capabilities("stdio server-a", &capabilities())));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn serves_the_cache_without_waiting_for_the_server() {
+    let cfg = config("serve");
+    let mcp = McpClient::from_config(&cfg);
+    let server = mcp.server_identity().unwrap();
+    assert_eq!(server, "stdio /nonexistent/mcp-server --stdio");
+
+    // Nothing cached: discovery runs now, fails, and is not saved.
+    let caps = discover_capabilities_cached(&cfg, &mcp);
+    assert!(!caps.diagnostics.is_empty());
+    assert_eq!(load_cached_capabilities(&cfg, &server), None);
+
+    let saved = CachedCapabilities::from_capabilities(&server, &capabilities());
+    save_cached_capabilities(&cfg, &saved).unwrap();
+    let caps = discover_capabilities_cached(&cfg, &mcp);
+    assert_eq!(caps.tools, vec!["greet"]);
+    assert_eq!(caps.resources, vec!["config://app"]);
+
+    let mut off = cfg.clone();
+    off.mcp_cache = false;
+    assert!(discover_capabilities_cached(&off, &mcp).tools.is_empty());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn safe_mode_reads_the_cache_but_never_writes_it() {
+    let mut cfg = config("safe");
+    let mcp = McpClient::from_config(&cfg);
+    let server = mcp.server_identity().unwrap();
+    let saved = CachedCapabilities::from_capabilities(&server, &capabilities());
+    cfg.safe_mode = true;
+    assert!(save_cached_capabilities(&cfg, &saved).unwrap_err().contains("Safe mode"));
+    assert!(!mcp_cache_path(&cfg, &server).exists());
+
+    cfg.safe_mode = false;
+    save_cached_capabilities(&cfg, &saved).unwrap();
+    cfg.safe_mode = true;
+    assert_eq!(discover_capabilities_cached(&cfg, &mcp).tools, vec!["greet"]);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn env_values_stay_out_of_the_identity_and_the_cache_file() {
+    let mut cfg = config("secret");
+    std::fs::create_dir_all(&cfg.data_dir).unwrap();
+    let servers = r#"{"mcpServers": {"gh": {"command": "gh-mcp", "env": {"GITHUB_TOKEN": "ghp_secret123"}}}}"#;
+    let file = std::path::Path::new(&cfg.data_dir).join("mcp.json");
+    std::fs::write(&file, servers).unwrap();
+    cfg.mcp_config = file.to_string_lossy().to_string();
+    cfg.mcp_command = String::new();
+
+    let server = McpClient::from_config(&cfg).server_identity().unwrap();
+    assert_eq!(server, "stdio gh-mcp  GITHUB_TOKEN");
+    save_cached_capabilities(&cfg, &CachedCapabilities::from_capabilities(&server, &capabilities())).unwrap();
+    let written = std::fs::read_to_string(mcp_cache_path(&cfg, &server)).unwrap();
+    assert!(!written.contains("ghp_secret123") && !written.contains("gh-mcp"), "{}", written);
+    assert!(load_cached_capabilities(&cfg, &server).is_some());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
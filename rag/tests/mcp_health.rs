@@ -0,0 +1,39 @@
+use std::net::TcpListener;
+
+use rag::{Config, McpAuth, McpClient, McpConnection};
+
+#[test]
+fn an_unreachable_server_is_marked_down_after_its_retries() {
+    // A port nothing listens on once the listener is dropped.
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut cfg = Config::from_env();
+    cfg.mcp_config = String::new();
+    cfg.mcp_url = format!("http://127.0.0.1:{}/mcp", port);
+    cfg.mcp_auth = McpAuth::Token("secret".to_string());
+    cfg.mcp_max_restarts = 1;
+    cfg.safe_mode = false;
+    cfg.mcp_tool_allowlist.clear();
+    cfg.mcp_tool_denylist.clear();
+    let mcp = McpClient::from_config(&cfg);
+    assert_eq!(mcp.connection(), McpConnection::Unknown);
+
+    assert!(mcp.list_tools().is_err());
+    let McpConnection::Down(reason) = mcp.connection() else {
+        panic!("expected down, got {:?}", mcp.connection());
+    };
+    assert!(reason.starts_with("2 failed connection attempts"), "{}", reason);
+    assert_eq!(mcp.down_reason("greet"), Some(reason));
+
+    let err = mcp.call_tool("greet", serde_json::json!({})).unwrap_err();
+    assert!(err.contains("MCP server is down"), "{}", err);
+}
+
+#[test]
+fn only_troubled_servers_get_a_prompt_note() {
+    assert_eq!(McpConnection::Up.prompt_note(""), None);
+    assert_eq!(McpConnection::Unknown.prompt_note("docs"), None);
+    let note = McpConnection::Down("refused".to_string()).prompt_note("docs").unwrap();
+    assert!(note.starts_with("MCP server 'docs' is down: refused."), "{}", note);
+    let note = McpConnection::Reconnecting("reset".to_string()).prompt_note("").unwrap();
+    assert!(note.starts_with("The MCP server lost its connection (reset)"), "{}", note);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{stub_server, temp_config};
+use rag::{resource_uri, serve_mcp, Config, IndexManifest, ManifestFile};
+use serde_json::{json, Value};
+
+fn exchange(cfg: &Config, requests: &[Value]) -> Vec<Value> {
+    let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+    let mut output = Vec::new();
+    serve_mcp(cfg, input.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn empty_config() -> Config {
+    let mut cfg = Config::from_env();
+    let dir = std::env::temp_dir().join(format!("aicli-mcp-server-{}", std::process::id()));
+    cfg.data_dir = dir.to_string_lossy().to_string();
+    cfg.collection = "mcp_server_test".to_string();
+    cfg
+}
+
+#[test]
+fn initialize_lists_tools_and_skips_notifications() {
+    let replies = exchange(
+        &empty_config(),
+        &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-03-26" } }),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" }),
+        ],
+    );
+    assert_eq!(replies.len(), 3);
+    assert_eq!(replies[0]["result"]["protocolVersion"], "2025-03-26");
+    assert_eq!(replies[0]["result"]["serverInfo"]["name"], "aicli");
+    let names: Vec<&str> = replies[1]["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["search_corpus", "answer_question"]);
+    assert_eq!(replies[2]["id"], 3);
+}
+
+#[test]
+fn initialize_falls_back_to_the_newest_supported_version() {
+    let initialize =
+        |id: u64, params: Value| json!({ "jsonrpc": "2.0", "id": id, "method": "initialize", "params": params });
+    let replies = exchange(
+        &empty_config(),
+        &[initialize(1, json!({ "protocolVersion": "2099-01-01" })), initialize(2, json!({}))],
+    );
+    assert_eq!(replies[0]["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(replies[1]["result"]["protocolVersion"], "2025-06-18");
+}
+
+#[test]
+fn bad_requests_get_errors() {
+    let replies = exchange(
+        &empty_config(),
+        &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "sampling/createMessage" }),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": { "name": "search_corpus", "arguments": {} }
+            }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": { "uri": "file:///nope" } }),
+        ],
+    );
+    assert_eq!(replies[0]["error"]["code"], -32601);
+    assert_eq!(replies[1]["result"]["isError"], true);
+    assert_eq!(replies[2]["error"]["code"], -32602);
+}
+
+#[test]
+fn an_empty_index_has_no_resources() {
+    let replies = exchange(&empty_config(), &[json!({ "jsonrpc": "2.0", "id": 1, "method": "resources/list" })]);
+    assert_eq!(replies[0]["result"]["resources"], json!([]));
+    assert!(replies[0]["result"].get("nextCursor").is_none());
+}
+
+#[test]
+fn urls_are_their_own_uris() {
+    assert_eq!(resource_uri("https://example.com/docs"), "https://example.com/docs");
+    assert_eq!(resource_uri("/srv/notes.md"), "file:///srv/notes.md");
+}
+
+#[test]
+fn pages_are_read_back_without_the_overlap_between_chunks() {
+    // Qdrant returns the chunks out of order; each repeats up to 6
+    // characters of the one before it, less any whitespace trimmed away.
+    let qdrant = stub_server(|_, _, _| {
+        let point = |index: u64, chunk: &str| {
+            json!({ "id": index, "payload": { "path": "https://example.com/guide", "index": index,
+                "chunk": chunk, "chunk_overlap": 6 } })
+        };
+        let points = [point(2, "jumps over the dog."), point(0, "The quick brown"), point(1, "brown fox jumps")];
+        (200, json!({ "result": { "points": points, "next_page_offset": null } }).to_string())
+    });
+    let mut cfg = temp_config("mcp-server-read");
+    cfg.collection = "mcp_server_read".to_string();
+    cfg.qdrant_url = qdrant;
+    cfg.qdrant_api_key = String::new();
+    cfg.http_retries = 0;
+    let mut manifest = IndexManifest::load(&cfg).unwrap();
+    manifest.files.insert("https://example.com/guide".to_string(), ManifestFile { chunks: 3, point_ids: Vec::new() });
+    manifest.save(&cfg).unwrap();
+
+    let replies = exchange(
+        &cfg,
+        &[json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "resources/read",
+            "params": { "uri": "https://example.com/guide" }
+        })],
+    );
+    assert_eq!(replies[0]["result"]["contents"][0]["text"], "The quick brown fox jumps over the dog.");
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use rag::{parse_mcp_servers, Config, McpAuth, McpClient, McpServerSpec};
+use serde_json::json;
+
+#[test]
+fn claude_desktop_configs_are_read() {
+    let servers = parse_mcp_servers(
+        r#"{"mcpServers": {
+            "github": {"command": "npx", "args": ["-y", "server-github"], "env": {"GITHUB_TOKEN": "t", "DEBUG": 1}},
+            "docs": {"url": "https://mcp.example.com/mcp", "headers": {"Authorization": "Bearer abc"}},
+            "old": {"command": "old-server", "disabled": true}
+        }}"#,
+    )
+    .unwrap();
+    assert_eq!(servers.len(), 2);
+    assert_eq!(servers[0].name, "docs");
+    assert_eq!(
+        servers[0].spec,
+        McpServerSpec::Http {
+            url: "https://mcp.example.com/mcp".to_string(),
+            auth: McpAuth::Token("abc".to_string()),
+        }
+    );
+    assert_eq!(
+        servers[1].spec,
+        McpServerSpec::Stdio {
+            command: "npx".to_string(),
+            args: vec!["-y".to_string(), "server-github".to_string()],
+            env: vec![("DEBUG".to_string(), "1".to_string()), ("GITHUB_TOKEN".to_string(), "t".to_string())],
+        }
+    );
+}
+
+#[test]
+fn bad_configs_name_the_problem() {
+    assert!(parse_mcp_servers("{}").unwrap_err().contains("mcpServers"));
+    let err = parse_mcp_servers(r#"{"mcpServers": {"x": {"args": []}}}"#).unwrap_err();
+    assert!(err.contains("server 'x'"), "{}", err);
+    let err = parse_mcp_servers(r#"{"mcpServers": {"a/b": {"command": "c"}}}"#).unwrap_err();
+    assert!(err.contains("a/b"), "{}", err);
+    let err = parse_mcp_servers(r#"{"mcpServers": {"x": {"url": "http://h", "headers": {"X-Key": "k"}}}}"#)
+        .unwrap_err();
+    assert!(err.contains("X-Key"), "{}", err);
+}
+
+// A stdio MCP server whose `greet` tool answers with $GREETING.
+const SERVER: &str = r#"
+import json, os, sys
+for line in sys.stdin:
+    msg = json.loads(line)
+    if "id" not in msg:
+        continue
+    method = msg["method"]
+    if method == "initialize":
+        result = {"protocolVersion": "2024-11-05", "capabilities": {"tools": {}}, "serverInfo": {"name": "fake"}}
+    elif method == "tools/list":
+        result = {"tools": [{"name": "greet", "inputSchema": {"type": "object"}}]}
+    elif method == "tools/call":
+        result = {"content": [{"type": "text", "text": os.environ.get("GREETING", "")}]}
+    else:
+        print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "error": {"code": -32601, "message": "nope"}}), flush=True)
+        continue
+    print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "result": result}), flush=True)
+"#;
+
+#[test]
+fn tools_from_several_servers_are_named_by_server() {
+    let python = if cfg!(windows) { "python" } else { "python3" };
+    if std::process::Command::new(python).arg("--version").output().is_err() {
+        return;
+    }
+    let dir: PathBuf = std::env::temp_dir().join(format!("aicli-mcp-servers-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("server.py");
+    std::fs::write(&script, SERVER).unwrap();
+    let server = |greeting: &str| json!({ "command": python, "args": [script], "env": { "GREETING": greeting } });
+    let config = dir.join("mcp.json");
+    let servers = json!({ "mcpServers": { "en": server("hello"), "fr": server("bonjour") } });
+    std::fs::write(&config, servers.to_string()).unwrap();
+
+    let mut cfg = Config::from_env();
+    cfg.mcp_config = config.to_string_lossy().to_string();
+    cfg.mcp_tool_allowlist.clear();
+    cfg.mcp_tool_denylist.clear();
+    cfg.safe_mode = false;
+    cfg.mcp_heartbeat_secs = 0;
+    let mcp = McpClient::from_config(&cfg);
+    assert_eq!(mcp.server_names(), vec!["en", "fr"]);
+    assert_eq!(mcp.list_tools().unwrap(), vec!["en/greet", "fr/greet"]);
+    let reply = mcp.call_tool("fr/greet", json!({})).unwrap();
+    assert_eq!(reply["content"][0]["text"], "bonjour");
+    assert!(mcp.call_tool("greet", json!({})).unwrap_err().contains("server/name"));
+    assert!(mcp.call_tool("de/greet", json!({})).unwrap_err().contains("Unknown MCP server"));
+
+    cfg.mcp_config = dir.join("missing.json").to_string_lossy().to_string();
+    let err = McpClient::from_config(&cfg).call_tool("en/greet", json!({})).unwrap_err();
+    assert!(err.contains("Failed to read MCP config"), "{}", err);
+    let _ = std::fs::remove_dir_all(dir);
+}
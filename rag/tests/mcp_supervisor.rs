@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rag::{backoff, Config, McpClient, McpConnection, McpServerState};
+
+// A stdio MCP server with an `echo` tool and a `crash` tool that exits.
+const SERVER: &str = r#"
+import json, sys
+for line in sys.stdin:
+    msg = json.loads(line)
+    if "id" not in msg:
+        continue
+    method = msg["method"]
+    if method == "initialize":
+        result = {"protocolVersion": "2024-11-05", "capabilities": {"tools": {}}, "serverInfo": {"name": "fake"}}
+    elif method == "tools/list":
+        result = {"tools": [{"name": "echo", "inputSchema": {"type": "object"}}, {"name": "crash", "inputSchema": {"type": "object"}}]}
+    elif method == "tools/call" and msg["params"]["name"] == "crash":
+        sys.stderr.write("boom: crash requested\n")
+        sys.stderr.flush()
+        sys.exit(3)
+    elif method == "tools/call":
+        result = {"content": [{"type": "text", "text": json.dumps(msg["params"]["arguments"])}]}
+    else:
+        print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "error": {"code": -32601, "message": "nope"}}), flush=True)
+        continue
+    print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "result": result}), flush=True)
+"#;
+
+// A config for a fresh copy of the fake server, or `None` without python3.
+fn config(name: &str, max_restarts: u32) -> Option<Config> {
+    let python = if cfg!(windows) { "python" } else { "python3" };
+    std::process::Command::new(python).arg("--version").output().ok()?;
+    let path: PathBuf = std::env::temp_dir().join(format!("aicli-mcp-{}-{}.py", name, std::process::id()));
+    std::fs::write(&path, SERVER).unwrap();
+    let mut cfg = Config::from_env();
+    cfg.mcp_url = String::new();
+    cfg.mcp_command = python.to_string();
+    cfg.mcp_args = vec![path.to_string_lossy().to_string()];
+    cfg.mcp_tool_allowlist.clear();
+    cfg.mcp_tool_denylist.clear();
+    cfg.safe_mode = false;
+    cfg.mcp_max_restarts = max_restarts;
+    cfg.mcp_heartbeat_secs = 0;
+    Some(cfg)
+}
+
+#[test]
+fn a_crashed_server_is_restarted_on_the_next_call() {
+    let Some(cfg) = config("restart", 2) else {
+        return;
+    };
+    let mcp = McpClient::from_config(&cfg);
+    assert!(mcp.server_status().is_none());
+    assert_eq!(mcp.list_tools().unwrap(), vec!["echo", "crash"]);
+    let echoed = mcp.call_tool("echo", serde_json::json!({ "x": 1 })).unwrap();
+    assert_eq!(echoed["content"][0]["text"], "{\"x\": 1}");
+
+    let err = mcp.call_tool("crash", serde_json::json!({})).unwrap_err();
+    assert!(err.contains("server exited"), "{}", err);
+    assert!(err.contains("boom: crash requested"), "{}", err);
+    assert_eq!(mcp.server_status().unwrap().state, McpServerState::Exited);
+    assert!(matches!(mcp.connection(), McpConnection::Reconnecting(_)));
+
+    assert_eq!(mcp.list_tools().unwrap(), vec!["echo", "crash"]);
+    let status = mcp.server_status().unwrap();
+    assert_eq!(status.state, McpServerState::Running);
+    assert_eq!(status.restarts, 1);
+    assert_eq!(status.summary().as_deref(), Some("MCP restarts: 1"));
+    assert_eq!(mcp.connection(), McpConnection::Up);
+    assert!(mcp.discover_capabilities().diagnostics[0].contains("restarted 1 of 2 times"));
+}
+
+#[test]
+fn the_supervisor_gives_up_after_the_restart_limit() {
+    let Some(cfg) = config("give-up", 0) else {
+        return;
+    };
+    let mcp = McpClient::from_config(&cfg);
+    assert!(mcp.call_tool("crash", serde_json::json!({})).is_err());
+    let err = mcp.list_tools().unwrap_err();
+    assert!(err.contains("gave up after 0 restarts"), "{}", err);
+    let status = mcp.server_status().unwrap();
+    assert_eq!(status.state, McpServerState::Failed);
+    assert_eq!(status.summary().as_deref(), Some("MCP down"));
+    assert!(mcp.down_reason("echo").is_some());
+}
+
+#[test]
+fn a_server_that_dies_on_startup_reports_its_stderr_and_status() {
+    let Some(cfg) = config("startup", 0) else {
+        return;
+    };
+    let script = "import sys\nsys.stderr.write('loading config\\nmissing API_KEY\\n')\nsys.exit(2)\n";
+    std::fs::write(&cfg.mcp_args[0], script).unwrap();
+    let mcp = McpClient::from_config(&cfg);
+    let err = mcp.list_tools().unwrap_err();
+    assert!(err.contains("initialize failed"), "{}", err);
+    assert!(err.contains("exit status: 2"), "{}", err);
+    assert!(err.contains("loading config | missing API_KEY"), "{}", err);
+
+    let diagnostics = mcp.discover_capabilities().diagnostics.join("\n");
+    assert!(diagnostics.contains("last exit: MCP initialize failed"), "{}", diagnostics);
+    assert!(diagnostics.contains("missing API_KEY"), "{}", diagnostics);
+}
+
+#[test]
+fn restarts_back_off_exponentially_up_to_a_cap() {
+    assert_eq!(backoff(0), Duration::from_millis(500));
+    assert_eq!(backoff(1), Duration::from_secs(1));
+    assert_eq!(backoff(3), Duration::from_secs(4));
+    assert_eq!(backoff(10), Duration::from_secs(30));
+    assert_eq!(backoff(40), Duration::from_secs(30));
+}
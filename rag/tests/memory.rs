@@ -0,0 +1,154 @@
+mod common;
+
+use rag::{
+    compact_conversation, compaction_range, conversation_tokens, is_context_message, run_agent,
+    summary_message, AgentEvent, AgentState, Config, McpClient, Message, SUMMARY_PREFIX,
+};
+
+fn message(role: &str, content: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: content.to_string(),
+    }
+}
+
+fn replay_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = common::replay_config(name, fixture);
+    cfg.agent_reflect = false;
+    cfg.memory_compact = true;
+    cfg.memory_max_tokens = Some(40);
+    cfg.memory_keep_recent = 1;
+    cfg
+}
+
+#[test]
+fn picks_older_context_after_the_system_prompt() {
+    let messages = vec![
+        message("system", "You are the controller."),
+        message("user", "Why does the build fail?"),
+        message("tool", "first result"),
+        message("tool", "second result"),
+        message("tool", "third result"),
+    ];
+    assert_eq!(compaction_range(&messages, 1), Some(1..4));
+    // The user question alone with one tool result is not worth a summary.
+    assert_eq!(compaction_range(&messages, 2), None);
+    assert!(is_context_message(&messages[2]));
+    assert!(!is_context_message(&messages[1]));
+
+    // An earlier summary is folded into the next one.
+    let mut compacted = messages.clone();
+    compacted.splice(2..4, [summary_message("Looked at the build.", &[])]);
+    assert_eq!(compaction_range(&compacted, 1), None);
+    assert_eq!(compaction_range(&compacted, 0), Some(1..4));
+    assert!(conversation_tokens(&compacted) < conversation_tokens(&messages) + 10);
+    assert_eq!(conversation_tokens(&[]), 0);
+}
+
+#[test]
+fn pinned_facts_follow_the_summary_verbatim() {
+    let summary = summary_message(" Checked the config. ", &["The target is aarch64.".to_string()]);
+    assert_eq!(summary.role, "system");
+    assert_eq!(
+        summary.content,
+        format!("{}\nChecked the config.\n\nPinned facts:\n- The target is aarch64.", SUMMARY_PREFIX)
+    );
+}
+
+#[test]
+fn long_conversations_are_compacted_before_the_next_step() {
+    let fixture = concat!(
+        r#"{"kind":"chat","response":"The user asked about retries; the client retries twice."}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"It retries twice.\"}"}"#,
+        "\n",
+    );
+    let cfg = replay_config("compact", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(3);
+    state.events = Some(tx);
+    state.append_system("You are the controller.".to_string());
+    state.append_user("How often does the client retry?".to_string());
+    state.append_tool("RAG retrieve for query: retry\nsrc/client.rs: retries twice with backoff".repeat(4));
+    state.append_tool("RAG retrieve for query: backoff\nsrc/backoff.rs: doubles the delay".repeat(4));
+    state.append_context("Retrieved 2 chunks.".to_string());
+    state.append_system("Choose the next action.".to_string());
+    state.pin("The user runs version 2.1.");
+
+    let answer = run_agent(&mut state, &cfg, &mcp).expect("replayed run should finish");
+    drop(state.events.take());
+    assert_eq!(answer, "It retries twice.");
+    assert_eq!(state.conversation.len(), 4);
+    assert_eq!(state.conversation[0].content, "You are the controller.");
+    // The question stays word for word; the tool results it led to are summarized after it.
+    assert_eq!(state.conversation[1].role, "user");
+    assert_eq!(state.conversation[1].content, "How often does the client retry?");
+    assert!(state.conversation[2].content.starts_with(SUMMARY_PREFIX));
+    assert!(state.conversation[2].content.contains("the client retries twice"));
+    assert!(state.conversation[2].content.ends_with("- The user runs version 2.1."));
+    assert_eq!(state.conversation[3].content, "Choose the next action.");
+
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert!(
+        matches!(events.first(), Some(AgentEvent::MemoryCompacted { messages: 3, tokens_before, tokens_after }) if tokens_after < tokens_before),
+        "{:?}",
+        events
+    );
+}
+
+#[test]
+fn a_failed_summary_keeps_the_conversation() {
+    let fixture = concat!(r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"done\"}"}"#, "\n");
+    let cfg = replay_config("failed", fixture);
+    let mcp = McpClient::from_config(&cfg);
+    let mut state = AgentState::new(3);
+    state.append_user("hello".to_string());
+    state.append_tool("a long tool result ".repeat(20));
+    state.append_tool("another long tool result ".repeat(20));
+    state.append_system("Choose the next action.".to_string());
+
+    // The fixture has no chat reply for the summary, so compaction fails and
+    // the controller sees the full conversation.
+    let contents = |state: &AgentState| state.conversation.iter().map(|m| m.content.clone()).collect::<Vec<_>>();
+    let before = contents(&state);
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "done");
+    assert_eq!(contents(&state), before);
+}
+
+#[test]
+fn each_run_of_context_is_summarized_in_place() {
+    let fixture = concat!(
+        r#"{"kind":"chat","response":"Searched for the retry count."}"#,
+        "\n",
+        r#"{"kind":"chat","response":"Read the backoff settings."}"#,
+        "\n",
+    );
+    let cfg = replay_config("order", fixture);
+    let mut conversation = vec![
+        message("system", "You are the controller."),
+        message("user", "How often does the client retry?"),
+        message("assistant", &r#"{"action":"rag","query":"retry count"}"#.repeat(8)),
+        message("tool", &"src/client.rs: retries twice ".repeat(8)),
+        message("user", "And how long does it wait?"),
+        message("tool", &"src/backoff.rs: doubles the delay ".repeat(8)),
+        message("tool", &"src/backoff.rs: starts at 100ms ".repeat(8)),
+        message("system", "Choose the next action."),
+    ];
+    let compaction = compact_conversation(&cfg, &mut conversation, &["Version 2.1.".to_string()])
+        .unwrap()
+        .expect("the conversation is over the limit");
+    assert_eq!(compaction.messages, 4);
+    let roles: Vec<&str> = conversation.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["system", "user", "system", "user", "system", "system"]);
+    assert_eq!(conversation[1].content, "How often does the client retry?");
+    // The controller's own decision is summarized with the tool result it led to.
+    assert!(conversation[2].content.starts_with(SUMMARY_PREFIX));
+    assert!(conversation[2].content.contains("Searched for the retry count."));
+    assert!(!conversation[2].content.contains("Pinned facts"));
+    assert_eq!(conversation[3].content, "And how long does it wait?");
+    assert!(conversation[4].content.contains("Read the backoff settings."));
+    assert!(conversation[4].content.ends_with("- Version 2.1."));
+    assert_eq!(conversation[5].content, "Choose the next action.");
+    assert!(compaction.tokens_after < compaction.tokens_before);
+}
@@ -0,0 +1,44 @@
+use rag::{merge_hits, Config, Hit};
+use serde_json::json;
+
+fn config(distance: &str) -> Config {
+    let mut cfg = Config::from_env();
+    cfg.distance = distance.to_string();
+    cfg
+}
+
+fn hit(path: &str, index: usize, score: f32) -> Hit {
+    serde_json::from_value(json!({
+        "score": score,
+        "payload": { "path": path, "index": index, "chunk": "" },
+    }))
+    .expect("hit should deserialize")
+}
+
+fn summary(hits: &[Hit]) -> Vec<(String, f32)> {
+    hits.iter()
+        .map(|h| (h.payload.as_ref().and_then(|p| p.path.clone()).unwrap_or_default(), h.score))
+        .collect()
+}
+
+#[test]
+fn similarity_keeps_the_highest_score_first() {
+    let hits = vec![hit("a.md", 0, 0.4), hit("b.md", 0, 0.9), hit("a.md", 0, 0.7), hit("c.md", 0, 0.1)];
+    let merged = merge_hits(&config("Cosine"), hits, 2);
+    assert_eq!(summary(&merged), vec![("b.md".to_string(), 0.9), ("a.md".to_string(), 0.7)]);
+}
+
+#[test]
+fn distances_keep_the_lowest_score_first() {
+    let hits = vec![hit("a.md", 0, 0.4), hit("b.md", 0, 0.9), hit("a.md", 0, 0.7), hit("c.md", 0, 0.1)];
+    for distance in ["Euclid", "Manhattan"] {
+        let merged = merge_hits(&config(distance), hits.clone(), 2);
+        assert_eq!(summary(&merged), vec![("c.md".to_string(), 0.1), ("a.md".to_string(), 0.4)], "{}", distance);
+    }
+}
+
+#[test]
+fn chunks_of_one_file_stay_separate() {
+    let hits = vec![hit("a.md", 0, 0.4), hit("a.md", 1, 0.5)];
+    assert_eq!(merge_hits(&config("Cosine"), hits, 10).len(), 2);
+}
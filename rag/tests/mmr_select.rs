@@ -0,0 +1,43 @@
+use rag::{Hit, mmr_select};
+use serde_json::json;
+
+fn hit(path: &str, score: f32, vector: Vec<f32>) -> Hit {
+    serde_json::from_value(json!({
+        "score": score,
+        "payload": { "path": path, "index": 0, "chunk": "" },
+        "vector": vector,
+    }))
+    .expect("hit should deserialize")
+}
+
+fn path_of(hit: &Hit) -> String {
+    hit.payload.as_ref().and_then(|p| p.path.clone()).unwrap_or_default()
+}
+
+#[test]
+fn prefers_diverse_hits_over_near_duplicates() {
+    let query = vec![1.0, 0.0];
+    let candidates = vec![
+        hit("a.rs", 0.99, vec![1.0, 0.0]),
+        hit("a_copy.rs", 0.98, vec![0.99, 0.01]),
+        hit("b.rs", 0.70, vec![0.7, 0.7]),
+    ];
+
+    let picked = mmr_select(&query, candidates, 2, 0.3);
+    let paths: Vec<String> = picked.iter().map(path_of).collect();
+    assert_eq!(paths, vec!["a.rs".to_string(), "b.rs".to_string()]);
+}
+
+#[test]
+fn lambda_one_keeps_relevance_order() {
+    let query = vec![1.0, 0.0];
+    let candidates = vec![
+        hit("a.rs", 0.99, vec![1.0, 0.0]),
+        hit("a_copy.rs", 0.98, vec![0.99, 0.01]),
+        hit("b.rs", 0.70, vec![0.7, 0.7]),
+    ];
+
+    let picked = mmr_select(&query, candidates, 2, 1.0);
+    let paths: Vec<String> = picked.iter().map(path_of).collect();
+    assert_eq!(paths, vec!["a.rs".to_string(), "a_copy.rs".to_string()]);
+}
@@ -0,0 +1,21 @@
+use rag::parse_context_length;
+use serde_json::json;
+
+#[test]
+fn reads_context_length_from_model_info() {
+    let show = json!({
+        "parameters": "stop \"<|im_end|>\"",
+        "model_info": { "general.architecture": "qwen2", "qwen2.context_length": 32768 }
+    });
+    assert_eq!(parse_context_length(&show), Some(32768));
+}
+
+#[test]
+fn prefers_num_ctx_parameter_over_architecture_maximum() {
+    let show = json!({
+        "parameters": "num_ctx 8192\nstop \"<|im_end|>\"",
+        "model_info": { "llama.context_length": 131072 }
+    });
+    assert_eq!(parse_context_length(&show), Some(8192));
+    assert_eq!(parse_context_length(&json!({})), None);
+}
@@ -0,0 +1,29 @@
+use rag::parse_notebook;
+
+#[test]
+fn extracts_code_and_markdown_cells_with_indexes() {
+    let nb = r##"{
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Title\n", "Intro text"]},
+            {"cell_type": "raw", "source": "ignored"},
+            {"cell_type": "code", "source": "print('hi')", "outputs": [{"text": "hi"}]},
+            {"cell_type": "code", "source": []}
+        ],
+        "metadata": {},
+        "nbformat": 4
+    }"##;
+
+    let cells = parse_notebook(nb).expect("notebook should parse");
+    assert_eq!(cells.len(), 2);
+    assert_eq!(cells[0].index, 0);
+    assert_eq!(cells[0].cell_type, "markdown");
+    assert_eq!(cells[0].source, "# Title\nIntro text");
+    assert_eq!(cells[1].index, 2);
+    assert_eq!(cells[1].source, "print('hi')");
+}
+
+#[test]
+fn rejects_json_without_cells() {
+    assert!(parse_notebook(r#"{"metadata":{}}"#).is_err());
+    assert!(parse_notebook("not json").is_err());
+}
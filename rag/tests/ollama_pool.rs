@@ -0,0 +1,42 @@
+use rag::{choose_host, parse_ollama_urls, BalancePolicy};
+
+#[test]
+fn parses_comma_separated_hosts() {
+    assert_eq!(
+        parse_ollama_urls("http://gpu1:11434/, http://gpu2:11434 ,,http://gpu3:11434"),
+        vec!["http://gpu1:11434", "http://gpu2:11434", "http://gpu3:11434"]
+    );
+    assert!(parse_ollama_urls("").is_empty());
+}
+
+#[test]
+fn policy_names() {
+    assert_eq!(BalancePolicy::parse("least-pending"), BalancePolicy::LeastPending);
+    assert_eq!(BalancePolicy::parse("round-robin"), BalancePolicy::RoundRobin);
+    assert_eq!(BalancePolicy::parse("anything"), BalancePolicy::RoundRobin);
+}
+
+#[test]
+fn round_robin_cycles_over_healthy_hosts() {
+    let pending = [0, 0, 0];
+    let healthy = [true, false, true];
+    let picks: Vec<usize> = (0..4)
+        .map(|turn| choose_host(BalancePolicy::RoundRobin, &pending, &healthy, turn))
+        .collect();
+    assert_eq!(picks, vec![0, 2, 0, 2]);
+}
+
+#[test]
+fn least_pending_prefers_idle_hosts() {
+    let healthy = [true, true, true];
+    assert_eq!(choose_host(BalancePolicy::LeastPending, &[3, 1, 2], &healthy, 0), 1);
+    // Ties rotate with the turn counter.
+    assert_eq!(choose_host(BalancePolicy::LeastPending, &[1, 1, 4], &healthy, 0), 0);
+    assert_eq!(choose_host(BalancePolicy::LeastPending, &[1, 1, 4], &healthy, 1), 1);
+}
+
+#[test]
+fn all_unhealthy_falls_back_to_every_host() {
+    let healthy = [false, false];
+    assert_eq!(choose_host(BalancePolicy::RoundRobin, &[0, 0], &healthy, 1), 1);
+}
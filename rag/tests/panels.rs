@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+use rag::{parse_panels, run_panel, Config, PanelRegistry, PanelSpec};
+
+fn panel(name: &str, secs: u64, command: &str) -> PanelSpec {
+    PanelSpec {
+        name: name.to_string(),
+        command: command.to_string(),
+        refresh: Duration::from_secs(secs),
+    }
+}
+
+#[test]
+fn panels_are_parsed_in_order() {
+    assert_eq!(
+        parse_panels("ci=60:gh run list --limit 5; jira = 0 : jira issue list; bad=soon:x; empty=5:"),
+        vec![panel("ci", 60, "gh run list --limit 5"), panel("jira", 0, "jira issue list")]
+    );
+}
+
+#[test]
+fn shown_panels_refresh_when_due() {
+    let mut registry = PanelRegistry::new(&[panel("ci", 60, "true"), panel("once", 0, "true")]);
+    let start = Instant::now();
+    assert!(registry.due(start).is_empty());
+    assert_eq!(registry.toggle("ci"), Some(true));
+    assert_eq!(registry.toggle("once"), Some(true));
+    assert_eq!(registry.toggle("nope"), None);
+
+    let due: Vec<String> = registry.due(start).into_iter().map(|p| p.name).collect();
+    assert_eq!(due, vec!["ci", "once"]);
+    assert!(registry.due(start).is_empty(), "running panels are not started twice");
+    registry.update("ci", Ok("green".to_string()), start);
+    registry.update("once", Ok("done".to_string()), start);
+    assert!(registry.due(start + Duration::from_secs(30)).is_empty());
+    let due: Vec<String> = registry.due(start + Duration::from_secs(61)).into_iter().map(|p| p.name).collect();
+    assert_eq!(due, vec!["ci"]);
+
+    assert_eq!(registry.toggle("once"), Some(false));
+    assert_eq!(registry.visible().count(), 1);
+    assert_eq!(registry.toggle("once"), Some(true));
+    assert_eq!(registry.due(start).len(), 1, "a panel shown again runs again");
+}
+
+#[cfg(unix)]
+#[test]
+fn panel_output_is_cleaned() {
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.shell = "sh".to_string();
+    let text = run_panel(&cfg, &panel("ci", 0, "printf '\\033[32mpassed\\033[0m\\n'; echo $AICLI_PANEL")).unwrap();
+    assert_eq!(text, "passed\nci");
+    let err = run_panel(&cfg, &panel("ci", 0, "echo broken >&2; exit 2")).unwrap_err();
+    assert!(err.starts_with("broken"), "{}", err);
+
+    cfg.safe_mode = true;
+    assert!(run_panel(&cfg, &panel("ci", 0, "echo hi")).unwrap_err().contains("Safe mode"));
+}
@@ -0,0 +1,24 @@
+use rag::parse_headers;
+
+#[test]
+fn parses_name_value_pairs() {
+    let headers = parse_headers("X-Org = team-a, X-Env=prod");
+    assert_eq!(
+        headers,
+        vec![
+            ("X-Org".to_string(), "team-a".to_string()),
+            ("X-Env".to_string(), "prod".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn keeps_equals_in_values_and_skips_malformed_entries() {
+    let headers = parse_headers("Cookie=a=b,garbage,=nameless");
+    assert_eq!(headers, vec![("Cookie".to_string(), "a=b".to_string())]);
+}
+
+#[test]
+fn empty_input_has_no_headers() {
+    assert!(parse_headers("").is_empty());
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rag::{Config, DirKind, dir_kind, migrate_legacy_dir};
+
+fn temp_config(name: &str) -> (Config, PathBuf) {
+    let root = std::env::temp_dir().join(format!("aicli-paths-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let mut cfg = Config::from_env();
+    cfg.config_dir = root.join("config").to_string_lossy().to_string();
+    cfg.data_dir = root.join("data").to_string_lossy().to_string();
+    cfg.cache_dir = root.join("cache").to_string_lossy().to_string();
+    (cfg, root)
+}
+
+#[test]
+fn sorts_entries_into_config_data_and_cache() {
+    assert_eq!(dir_kind("snippets.json"), DirKind::Config);
+    assert_eq!(dir_kind("templates.json"), DirKind::Config);
+    assert_eq!(dir_kind("answer_cache"), DirKind::Cache);
+    assert_eq!(dir_kind("summaries"), DirKind::Cache);
+    assert_eq!(dir_kind("sessions"), DirKind::Data);
+    assert_eq!(dir_kind("history.json"), DirKind::Data);
+}
+
+#[test]
+fn moves_the_legacy_dir_once() {
+    let (cfg, root) = temp_config("migrate");
+    let legacy = root.join("legacy");
+    fs::create_dir_all(legacy.join("sessions")).unwrap();
+    fs::write(legacy.join("sessions/work.json"), "{}").unwrap();
+    fs::write(legacy.join("snippets.json"), "{}").unwrap();
+    fs::create_dir_all(legacy.join("mcp_cache")).unwrap();
+    // Already in its new place, so the legacy copy stays.
+    fs::create_dir_all(&cfg.data_dir).unwrap();
+    fs::write(PathBuf::from(&cfg.data_dir).join("history.json"), "[\"new\"]").unwrap();
+    fs::write(legacy.join("history.json"), "[\"old\"]").unwrap();
+
+    let mut moved = migrate_legacy_dir(&cfg, &legacy).unwrap();
+    moved.sort();
+    let again = migrate_legacy_dir(&cfg, &legacy).unwrap();
+    let session = PathBuf::from(&cfg.data_dir).join("sessions/work.json").exists();
+    let history = fs::read_to_string(PathBuf::from(&cfg.data_dir).join("history.json")).unwrap();
+    let left_behind = legacy.join("history.json").exists();
+    let marked = legacy.join("MIGRATED").exists();
+    let _ = fs::remove_dir_all(&root);
+
+    assert_eq!(
+        moved,
+        vec![
+            root.join("cache/mcp_cache"),
+            root.join("config/snippets.json"),
+            root.join("data/sessions"),
+        ]
+    );
+    assert!(again.is_empty());
+    assert!(session);
+    assert_eq!(history, "[\"new\"]");
+    assert!(left_behind);
+    assert!(marked);
+}
+
+#[test]
+fn leaves_a_legacy_dir_that_is_still_configured() {
+    let (mut cfg, root) = temp_config("configured");
+    let legacy = root.join("legacy");
+    fs::create_dir_all(&legacy).unwrap();
+    fs::write(legacy.join("snippets.json"), "{}").unwrap();
+    cfg.data_dir = legacy.to_string_lossy().to_string();
+    let moved = migrate_legacy_dir(&cfg, &legacy).unwrap();
+    let kept = legacy.join("snippets.json").exists();
+    let _ = fs::remove_dir_all(&root);
+    assert!(moved.is_empty());
+    assert!(kept);
+}
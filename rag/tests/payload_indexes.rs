@@ -0,0 +1,26 @@
+use rag::parse_payload_indexes;
+
+fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+    items.iter().map(|(f, s)| (f.to_string(), s.to_string())).collect()
+}
+
+#[test]
+fn path_is_always_indexed() {
+    assert_eq!(parse_payload_indexes(""), pairs(&[("path", "keyword")]));
+}
+
+#[test]
+fn fields_default_to_keyword_and_accept_a_schema() {
+    assert_eq!(
+        parse_payload_indexes("commit, cell_type:keyword,index:Integer"),
+        pairs(&[("path", "keyword"), ("commit", "keyword"), ("cell_type", "keyword"), ("index", "integer")])
+    );
+}
+
+#[test]
+fn skips_duplicates_and_unknown_schemas() {
+    assert_eq!(
+        parse_payload_indexes("path:text,language:vector,url"),
+        pairs(&[("path", "keyword"), ("url", "keyword")])
+    );
+}
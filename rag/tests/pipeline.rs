@@ -0,0 +1,42 @@
+use rag::{parse_pipeline, Stage};
+
+#[test]
+fn stages_parse_with_and_without_parameters() {
+    let stages = parse_pipeline("expand(multi), search(40), min_score, focus(src/, docs/), rerank(llm), boost(0.2), mmr(0.5), top(8)")
+        .unwrap();
+    assert_eq!(
+        stages,
+        vec![
+            Stage::Expand(Some("multi".to_string())),
+            Stage::Search(Some(40)),
+            Stage::MinScore(None),
+            Stage::Focus(vec!["src/".to_string(), "docs/".to_string()]),
+            Stage::Rerank(Some("llm".to_string())),
+            Stage::Boost(Some(0.2)),
+            Stage::Mmr(Some(0.5)),
+            Stage::Top(Some(8)),
+        ]
+    );
+    assert_eq!(parse_pipeline(" SEARCH ").unwrap(), vec![Stage::Search(None)]);
+}
+
+#[test]
+fn a_pipeline_needs_exactly_one_search() {
+    assert!(parse_pipeline("rerank, top(5)").unwrap_err().contains("exactly one search"));
+    assert!(parse_pipeline("search, search").unwrap_err().contains("found 2"));
+}
+
+#[test]
+fn stages_must_sit_on_the_right_side_of_search() {
+    assert!(parse_pipeline("rerank, search").unwrap_err().contains("rerank must come after search"));
+    assert!(parse_pipeline("search, expand(hyde)").unwrap_err().contains("expand must come before search"));
+}
+
+#[test]
+fn bad_stages_and_parameters_are_reported() {
+    assert!(parse_pipeline("search, compress").unwrap_err().contains("Unknown RAG_PIPELINE stage 'compress'"));
+    assert!(parse_pipeline("search(0)").unwrap_err().contains("not a positive count"));
+    assert!(parse_pipeline("search, mmr(high)").unwrap_err().contains("not a number"));
+    assert!(parse_pipeline("expand(fancy), search").unwrap_err().contains("unknown mode"));
+    assert!(parse_pipeline("search, top(5").unwrap_err().contains("missing ')'"));
+}
@@ -0,0 +1,63 @@
+use rag::{
+    clipboard_commands, clipboard_context, clipboard_read_commands, default_shell, is_absolute_path, shell_command,
+    shell_flags, split_command_line,
+};
+
+#[test]
+fn each_shell_gets_its_own_command_flag() {
+    assert_eq!(shell_flags("sh"), &["-c"]);
+    assert_eq!(shell_flags("/bin/zsh"), &["-c"]);
+    assert_eq!(shell_flags("C:\\Windows\\System32\\cmd.exe"), &["/C"]);
+    assert_eq!(shell_flags("pwsh"), &["-NoProfile", "-Command"]);
+    assert_eq!(shell_flags("powershell.exe"), &["-NoProfile", "-Command"]);
+}
+
+#[test]
+fn the_default_shell_runs_a_command_line() {
+    let output = shell_command(&default_shell(), "echo platform-check").output().expect("shell should start");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("platform-check"));
+}
+
+#[test]
+fn windows_and_unix_paths_are_absolute_everywhere() {
+    assert!(is_absolute_path("/etc/hosts"));
+    assert!(is_absolute_path("C:\\Users\\me\\notes.md"));
+    assert!(is_absolute_path("d:/work/notes.md"));
+    assert!(is_absolute_path("\\\\server\\share\\file.txt"));
+    assert!(!is_absolute_path("docs/notes.md"));
+    assert!(!is_absolute_path("C:relative.txt"));
+}
+
+#[test]
+fn editor_command_lines_keep_quoted_words_together() {
+    assert_eq!(split_command_line("code --wait"), vec!["code", "--wait"]);
+    assert_eq!(
+        split_command_line(r#""C:\Program Files\Notepad++\notepad++.exe" -multiInst"#),
+        vec![r"C:\Program Files\Notepad++\notepad++.exe", "-multiInst"]
+    );
+    assert_eq!(split_command_line(r#"vim """#), vec!["vim", ""]);
+    assert!(split_command_line("   ").is_empty());
+}
+
+#[test]
+fn every_platform_has_a_clipboard_writer() {
+    assert!(clipboard_commands().iter().all(|argv| !argv.is_empty()));
+    assert!(!clipboard_commands().is_empty());
+    assert!(clipboard_read_commands().iter().all(|argv| !argv.is_empty()));
+}
+
+#[test]
+fn long_clipboards_keep_their_last_lines() {
+    let text = "first line\nsecond line\nError: disk full\n";
+    assert_eq!(
+        clipboard_context(text, 1000).unwrap(),
+        "----- CLIPBOARD -----\nfirst line\nsecond line\nError: disk full\n----- END CLIPBOARD -----"
+    );
+    assert_eq!(
+        clipboard_context(text, 20).unwrap(),
+        "----- CLIPBOARD (last 16 of 39 bytes) -----\nError: disk full\n----- END CLIPBOARD -----"
+    );
+    assert!(clipboard_context("ééé", 3).unwrap().contains("\né\n"));
+    assert!(clipboard_context(" \n", 100).is_err());
+}
@@ -0,0 +1,21 @@
+use rag::point_id;
+
+#[test]
+fn same_chunk_gets_the_same_id() {
+    assert_eq!(point_id("src/lib.rs", 0, "fn main() {}"), point_id("src/lib.rs", 0, "fn main() {}"));
+}
+
+#[test]
+fn path_position_and_content_all_change_the_id() {
+    let base = point_id("src/lib.rs", 0, "fn main() {}");
+    assert_ne!(base, point_id("src/main.rs", 0, "fn main() {}"));
+    assert_ne!(base, point_id("src/lib.rs", 1, "fn main() {}"));
+    assert_ne!(base, point_id("src/lib.rs", 0, "fn main() { run() }"));
+}
+
+#[test]
+fn ids_are_version_5_uuids() {
+    let id = point_id("README.md", 3, "# Title");
+    assert_eq!(id.len(), 36);
+    assert_eq!(id.as_bytes()[14], b'5');
+}
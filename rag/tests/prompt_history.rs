@@ -0,0 +1,61 @@
+use rag::{history_path, Config, PromptHistory};
+
+fn history(entries: &[&str]) -> PromptHistory {
+    PromptHistory { entries: entries.iter().map(|e| e.to_string()).collect() }
+}
+
+#[test]
+fn repeated_prompts_move_to_the_end_and_old_ones_drop() {
+    let mut h = history(&["a", "b", "c"]);
+    h.push("  a ", 3);
+    assert_eq!(h, history(&["b", "c", "a"]));
+    h.push("d", 3);
+    assert_eq!(h, history(&["c", "a", "d"]));
+    h.push("   ", 3);
+    assert_eq!(h.entries.len(), 3);
+}
+
+#[test]
+fn browsing_walks_from_the_newest_back() {
+    let h = history(&["a", "b"]);
+    assert_eq!(h.older(None), Some(1));
+    assert_eq!(h.older(Some(1)), Some(0));
+    assert_eq!(h.older(Some(0)), None);
+    assert_eq!(h.newer(0), Some(1));
+    assert_eq!(h.newer(1), None);
+    assert_eq!(PromptHistory::default().older(None), None);
+}
+
+#[test]
+fn search_finds_older_matches_ignoring_case() {
+    let h = history(&["How is AUTH configured?", "git log", "auth tokens expire when?"]);
+    assert_eq!(h.search("auth", None), Some(2));
+    assert_eq!(h.search("auth", Some(2)), Some(0));
+    assert_eq!(h.search("auth", Some(0)), None);
+    assert_eq!(h.search("kubectl", None), None);
+}
+
+#[test]
+fn history_is_saved_and_loaded() {
+    let mut cfg = Config::from_env();
+    cfg.data_dir = std::env::temp_dir()
+        .join(format!("aicli-prompt-history-{}", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+    history(&["one", "two"]).save(&cfg).unwrap();
+    assert!(history_path(&cfg).exists());
+    assert_eq!(PromptHistory::load(&cfg), history(&["one", "two"]));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn safe_mode_keeps_history_off_disk() {
+    let mut cfg = Config::from_env();
+    cfg.data_dir = std::env::temp_dir()
+        .join(format!("aicli-prompt-history-safe-{}", std::process::id()))
+        .to_string_lossy()
+        .to_string();
+    cfg.safe_mode = true;
+    history(&["one"]).save(&cfg).unwrap();
+    assert!(!history_path(&cfg).exists());
+}
@@ -0,0 +1,39 @@
+use rag::{lint_prompt, wrap_prompt, PromptIssue};
+
+const LOG: &str = "2026-10-16T12:00:00Z INFO starting
+2026-10-16T12:00:01Z WARN slow start
+2026-10-16T12:00:02Z ERROR failed to connect
+    at db.connect (db.js:10)
+    at main (index.js:3)";
+
+#[test]
+fn prompts_without_words_are_empty() {
+    assert_eq!(lint_prompt("  ?? "), Some(PromptIssue::Empty));
+    assert_eq!(lint_prompt("..."), Some(PromptIssue::Empty));
+    assert_eq!(wrap_prompt("...", &PromptIssue::Empty), None);
+}
+
+#[test]
+fn lone_paths_and_urls_are_flagged() {
+    for prompt in ["src/main.rs", "@Cargo.toml", "README.md", "https://example.com/docs", "C:\\repo\\lib.rs"] {
+        assert_eq!(lint_prompt(prompt), Some(PromptIssue::OnlyPath(prompt.to_string())), "{}", prompt);
+    }
+    for prompt in ["hello", "e.g.", "3.14", "what does src/main.rs do"] {
+        assert_eq!(lint_prompt(prompt), None, "{}", prompt);
+    }
+    let wrapped = wrap_prompt("@src/auth.rs", &PromptIssue::OnlyPath("@src/auth.rs".to_string())).unwrap();
+    assert!(wrapped.starts_with("Explain what @src/auth.rs is for"));
+}
+
+#[test]
+fn pasted_logs_need_a_question() {
+    assert_eq!(lint_prompt(LOG), Some(PromptIssue::PastedLog { log_lines: 5, lines: 5 }));
+    assert_eq!(lint_prompt(&format!("{}\nwhy?", LOG)), None);
+    assert_eq!(lint_prompt(&format!("Explain this:\n{}", LOG)), None);
+    let prose = "We use Postgres.\nThe app is in Rust.\nIt runs on k8s.\nIt has 3 services.\nDeploys are weekly.";
+    assert_eq!(lint_prompt(prose), None);
+
+    let wrapped = wrap_prompt(LOG, &lint_prompt(LOG).unwrap()).unwrap();
+    assert!(wrapped.contains(&format!("```\n{}\n```", LOG)));
+    assert!(wrapped.ends_with("how do I fix it?"));
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::time::Duration;
+
+use common::replay_config;
+use rag::{run_agent, AgentEvent, AgentState, Config, Deadline, McpClient};
+
+#[test]
+fn zero_seconds_means_no_deadline() {
+    let deadline = Deadline::after_secs(0);
+    assert_eq!(deadline.remaining(), None);
+    assert!(!deadline.expired());
+    assert_eq!(deadline, Deadline::none());
+}
+
+#[test]
+fn backend_timeouts_are_cut_to_the_time_left() {
+    let mut cfg = Config::from_env();
+    cfg.http_timeout_secs = 120;
+    cfg.embed_timeout_secs = 5;
+    cfg.generate_timeout_secs = 300;
+    let limited = Deadline::after_secs(30).limit_config(&cfg);
+    assert!(limited.http_timeout_secs <= 30);
+    assert_eq!(limited.embed_timeout_secs, 5);
+    assert!(limited.generate_timeout_secs <= 30);
+
+    let unlimited = Deadline::none().limit_config(&cfg);
+    assert_eq!(unlimited.generate_timeout_secs, 300);
+}
+
+#[test]
+fn an_expired_agent_run_returns_the_gathered_context() {
+    let cfg = replay_config("query-timeout", "");
+    let mcp = McpClient::from_config(&cfg);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(5);
+    state.events = Some(tx);
+    state.append_user("Why is the build slow?".to_string());
+    state.append_context("src/build.rs: caches are disabled in CI".to_string());
+    state.deadline = Deadline::after_secs(1);
+    std::thread::sleep(Duration::from_millis(1100));
+
+    // The empty fixture would fail any model call; none is made.
+    let answer = run_agent(&mut state, &cfg, &mcp).expect("a timed-out run is not an error");
+    assert!(answer.starts_with("Timed out after 1s"), "{}", answer);
+    assert!(state.context_text().contains("caches are disabled"));
+    drop(state.events.take());
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert!(matches!(events.as_slice(), [AgentEvent::Error { message, .. }] if message == &answer), "{:?}", events);
+}
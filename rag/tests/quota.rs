@@ -0,0 +1,49 @@
+use rag::{ChunkQuota, QuotaCap, ScanStats};
+
+#[test]
+fn no_limits_keeps_everything() {
+    let mut quota = ChunkQuota::with_limits(None, None, None);
+    assert_eq!(quota.allow("src/lib.rs", 500), (500, None));
+}
+
+#[test]
+fn per_file_limit_caps_each_file() {
+    let mut quota = ChunkQuota::with_limits(Some(10), None, None);
+    let (kept, cap) = quota.allow("src/big.rs", 25);
+    assert_eq!(kept, 10);
+    assert_eq!(cap.unwrap().reason, "per-file quota");
+    assert_eq!(quota.allow("src/small.rs", 4), (4, None));
+}
+
+#[test]
+fn per_directory_limit_is_shared_by_siblings() {
+    let mut quota = ChunkQuota::with_limits(None, Some(8), None);
+    assert_eq!(quota.allow("docs/a.md", 5).0, 5);
+    let (kept, cap) = quota.allow("docs/b.md", 5);
+    assert_eq!(kept, 3);
+    assert_eq!(cap.unwrap().reason, "per-directory quota");
+    assert_eq!(quota.allow("src/c.rs", 5), (5, None));
+}
+
+#[test]
+fn total_limit_stops_later_files() {
+    let mut quota = ChunkQuota::with_limits(None, None, Some(6));
+    assert_eq!(quota.allow("a/1.rs", 4).0, 4);
+    assert_eq!(quota.allow("b/2.rs", 4).0, 2);
+    let (kept, cap) = quota.allow("c/3.rs", 4);
+    assert_eq!(kept, 0);
+    assert_eq!(cap.unwrap().reason, "total quota");
+}
+
+#[test]
+fn summary_reports_dropped_chunks() {
+    let stats = ScanStats {
+        indexed: 2,
+        capped: vec![
+            QuotaCap { path: "a.rs".to_string(), kept: 10, wanted: 30, reason: "per-file quota" },
+            QuotaCap { path: "b.rs".to_string(), kept: 10, wanted: 11, reason: "per-file quota" },
+        ],
+        ..ScanStats::default()
+    };
+    assert_eq!(stats.summary(), vec!["capped 2 files (per-file quota), 21 chunks dropped".to_string()]);
+}
@@ -0,0 +1,83 @@
+use rag::{boost_doc_types, rerank_hits, Hit, RerankFuture, Reranker};
+use serde_json::json;
+
+// Scores each passage by its length, or fails with `error`.
+struct StubReranker {
+    error: Option<&'static str>,
+    extra: usize,
+}
+
+impl Reranker for StubReranker {
+    fn name(&self) -> &str {
+        "stub"
+    }
+
+    fn score<'a>(&'a self, _query: &'a str, documents: &'a [String]) -> RerankFuture<'a> {
+        Box::pin(async move {
+            if let Some(err) = self.error {
+                return Err(err.to_string());
+            }
+            Ok(documents.iter().map(|d| d.len() as f32).chain(std::iter::repeat_n(0.0, self.extra)).collect())
+        })
+    }
+}
+
+fn stub() -> StubReranker {
+    StubReranker { error: None, extra: 0 }
+}
+
+fn hit(path: &str, score: f32, chunk: &str, doc_type: Option<&str>) -> Hit {
+    serde_json::from_value(json!({
+        "score": score,
+        "payload": { "path": path, "index": 0, "chunk": chunk, "doc_type": doc_type },
+    }))
+    .expect("hit should deserialize")
+}
+
+fn paths(hits: &[Hit]) -> Vec<String> {
+    hits.iter()
+        .map(|h| h.payload.as_ref().and_then(|p| p.path.clone()).unwrap_or_default())
+        .collect()
+}
+
+fn run<F: std::future::Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(fut)
+}
+
+#[test]
+fn reranking_reorders_without_touching_vector_scores() {
+    let mut hits = vec![hit("a", 0.9, "x", None), hit("b", 0.8, "xxx", None), hit("c", 0.7, "xx", None)];
+    run(rerank_hits(&stub(), "q", &mut hits)).unwrap();
+    assert_eq!(paths(&hits), vec!["b", "c", "a"]);
+    assert_eq!(hits.iter().map(|h| h.score).collect::<Vec<_>>(), vec![0.8, 0.7, 0.9]);
+    assert_eq!(hits.iter().map(|h| h.rerank_score).collect::<Vec<_>>(), vec![Some(3.0), Some(2.0), Some(1.0)]);
+}
+
+#[test]
+fn a_failed_rerank_keeps_the_vector_order() {
+    let mut hits = vec![hit("a", 0.9, "x", None), hit("b", 0.8, "xxx", None)];
+    let failing = StubReranker { error: Some("model offline"), extra: 0 };
+    assert_eq!(run(rerank_hits(&failing, "q", &mut hits)).unwrap_err(), "model offline");
+    assert_eq!(paths(&hits), vec!["a", "b"]);
+    assert!(hits.iter().all(|h| h.rerank_score.is_none()));
+
+    let miscounting = StubReranker { error: None, extra: 1 };
+    assert!(run(rerank_hits(&miscounting, "q", &mut hits)).unwrap_err().contains("3 scores for 2 passages"));
+    assert_eq!(paths(&hits), vec!["a", "b"]);
+}
+
+#[test]
+fn nothing_to_rerank_takes_no_time() {
+    let mut hits: Vec<Hit> = Vec::new();
+    assert!(run(rerank_hits(&stub(), "q", &mut hits)).unwrap().is_zero());
+}
+
+#[test]
+fn doc_type_boosts_apply_to_reranker_scores_once_reranked() {
+    let mut hits = vec![hit("src/a.rs", 0.9, "xxx", Some("code")), hit("README.md", 0.1, "xx", Some("docs"))];
+    run(rerank_hits(&stub(), "q", &mut hits)).unwrap();
+    let boosted = boost_doc_types(hits, &["docs"], 2.0, false);
+    assert_eq!(paths(&boosted), vec!["README.md", "src/a.rs"]);
+    assert_eq!(boosted[0].score, 0.1);
+    assert_eq!(boosted[0].rerank_score, Some(4.0));
+}
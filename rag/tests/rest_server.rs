@@ -0,0 +1,269 @@
+use rag::{
+    chat_messages, cross_site_reason, handle_request, index_source_allowed, is_loopback_addr, read_request,
+    rest_answer_config, Config, HttpRequest, LocalToolRegistry, McpClient,
+};
+use serde_json::Value;
+
+fn request(method: &str, path: &str, body: &str) -> HttpRequest {
+    HttpRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        body: body.as_bytes().to_vec(),
+        ..HttpRequest::default()
+    }
+}
+
+fn with_header(mut request: HttpRequest, name: &str, value: &str) -> HttpRequest {
+    request.headers.push((name.to_string(), value.to_string()));
+    request
+}
+
+// A POST with a JSON body, as a well-behaved client sends it.
+fn post(path: &str, body: &str) -> HttpRequest {
+    with_header(request("POST", path, body), "content-type", "application/json")
+}
+
+fn raw_response(cfg: &Config, request: &HttpRequest) -> String {
+    let mut out = Vec::new();
+    handle_request(cfg, request, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+// The status code and JSON body of a response.
+fn respond(cfg: &Config, request: &HttpRequest) -> (u16, Value) {
+    let mut out = Vec::new();
+    handle_request(cfg, request, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let (head, body) = text.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    assert!(head.contains("Content-Type: application/json"));
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn requests_are_read_with_their_body() {
+    let raw = concat!(
+        "POST /query?verbose=1 HTTP/1.1\r\nHost: localhost\r\ncontent-length: 20\r\n\r\n",
+        "{\"question\": \"why\"}\nextra"
+    );
+    let parsed = read_request(&mut raw.as_bytes()).unwrap();
+    let expected = with_header(request("POST", "/query", "{\"question\": \"why\"}\n"), "host", "localhost");
+    assert_eq!(parsed, with_header(expected, "content-length", "20"));
+    assert_eq!(parsed.header("Content-Length"), Some("20"));
+
+    let get = read_request(&mut "get /stats HTTP/1.1\r\n\r\n".as_bytes()).unwrap();
+    assert_eq!(get.method, "GET");
+    assert_eq!(get.path, "/stats");
+    assert!(get.body.is_empty());
+    assert!(read_request(&mut "\r\n".as_bytes()).is_err());
+    let huge = "POST /index HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n";
+    assert!(read_request(&mut huge.as_bytes()).unwrap_err().contains("over"));
+}
+
+#[test]
+fn bad_requests_are_refused_before_any_work() {
+    let mut cfg = Config::from_env();
+    assert_eq!(respond(&cfg, &request("GET", "/nope", "")).0, 404);
+    assert_eq!(respond(&cfg, &request("GET", "/query", "")).0, 405);
+    assert_eq!(respond(&cfg, &post("/query", "not json")).0, 400);
+    assert_eq!(respond(&cfg, &post("/query", "[1]")).0, 400);
+    let (status, body) = respond(&cfg, &post("/query", "{}"));
+    assert_eq!(status, 400);
+    assert_eq!(body["error"], "\"question\" is required");
+    let (status, body) = respond(&cfg, &post("/agent", r#"{"question": "q", "mode": "chaos"}"#));
+    assert_eq!(status, 400);
+    assert_eq!(body["error"], "Unknown mode: chaos");
+    let (status, _) = respond(&cfg, &post("/query", r#"{"question": "q", "history": "none"}"#));
+    assert_eq!(status, 400);
+
+    cfg.safe_mode = true;
+    assert_eq!(respond(&cfg, &post("/index", r#"{"source": "docs"}"#)).0, 403);
+}
+
+#[test]
+fn chat_messages_become_a_question_and_history() {
+    let messages = serde_json::json!([
+        { "role": "system", "content": "You are helpful." },
+        { "role": "user", "content": "What is aicli?" },
+        { "role": "assistant", "content": "A RAG CLI." },
+        { "role": "user", "content": [{ "type": "text", "text": "How is it configured?" }, { "type": "image_url" }] },
+    ]);
+    let (history, question) = chat_messages(&messages).unwrap();
+    assert_eq!(question, "How is it configured?");
+    let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["user", "assistant"]);
+    assert_eq!(history[1].content, "A RAG CLI.");
+
+    let ends_with_answer =
+        serde_json::json!([{ "role": "user", "content": "q" }, { "role": "assistant", "content": "a" }]);
+    assert!(chat_messages(&ends_with_answer).is_err());
+    assert!(chat_messages(&serde_json::json!("q")).is_err());
+}
+
+#[test]
+fn the_openai_routes_list_models_and_check_requests() {
+    let cfg = Config::from_env();
+    let (status, body) = respond(&cfg, &request("GET", "/v1/models", ""));
+    assert_eq!(status, 200);
+    assert_eq!(body["object"], "list");
+    assert_eq!(body["data"][0]["id"], "aicli");
+    assert!(body["data"].as_array().unwrap().iter().any(|m| m["id"] == "aicli-rag-only"));
+
+    let unknown = r#"{"model": "gpt-4", "messages": []}"#;
+    let (status, body) = respond(&cfg, &post("/v1/chat/completions", unknown));
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    let (status, body) = respond(&cfg, &post("/v1/chat/completions", r#"{"messages": []}"#));
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["message"], "The last message must be a user message");
+    assert_eq!(respond(&cfg, &request("GET", "/v1/chat/completions", "")).0, 405);
+}
+
+#[test]
+fn chunked_bodies_are_read_and_capped() {
+    let raw = concat!(
+        "POST /query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+        "5\r\n{\"que\r\nc;ext=1\r\nstion\": \"x\"}\r\n0\r\nX-Trailer: y\r\n\r\n"
+    );
+    let parsed = read_request(&mut raw.as_bytes()).unwrap();
+    assert_eq!(parsed.body, b"{\"question\": \"x\"}");
+
+    let huge = "POST /query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nFFFFFFF\r\n";
+    assert!(read_request(&mut huge.as_bytes()).unwrap_err().contains("over"));
+    let bad = "POST /query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+    assert!(read_request(&mut bad.as_bytes()).unwrap_err().contains("chunk size"));
+}
+
+#[test]
+fn a_configured_token_is_required() {
+    let mut cfg = Config::from_env();
+    cfg.serve_token = "s3cret".to_string();
+    let (status, body) = respond(&cfg, &request("GET", "/v1/models", ""));
+    assert_eq!(status, 401);
+    assert_eq!(body["error"], "A valid bearer token is required");
+    let wrong = with_header(request("GET", "/v1/models", ""), "authorization", "Bearer s3cre7");
+    assert_eq!(respond(&cfg, &wrong).0, 401);
+    let right = with_header(request("GET", "/v1/models", ""), "authorization", "Bearer s3cret");
+    assert_eq!(respond(&cfg, &right).0, 200);
+}
+
+#[test]
+fn preflights_are_answered_with_the_configured_origin() {
+    let mut cfg = Config::from_env();
+    cfg.serve_token = "s3cret".to_string();
+    let text = raw_response(&cfg, &request("OPTIONS", "/query", ""));
+    assert!(text.starts_with("HTTP/1.1 204 No Content\r\n"));
+    assert!(!text.contains("Access-Control-Allow-Origin"));
+
+    cfg.serve_cors_origin = "https://app.example.com".to_string();
+    let text = raw_response(&cfg, &request("OPTIONS", "/query", ""));
+    assert!(text.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+    assert!(text.contains("Access-Control-Allow-Headers: Authorization, Content-Type\r\n"));
+    // Every other response names the origin too.
+    let text = raw_response(&cfg, &request("GET", "/nope", ""));
+    assert!(text.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+}
+
+#[test]
+fn only_loopback_addresses_count_as_local() {
+    assert!(is_loopback_addr("127.0.0.1:8080"));
+    assert!(is_loopback_addr("localhost:8080"));
+    assert!(is_loopback_addr("[::1]:8080"));
+    assert!(!is_loopback_addr("0.0.0.0:8080"));
+    assert!(!is_loopback_addr("192.168.1.5:8080"));
+    assert!(!is_loopback_addr("[::]:8080"));
+}
+
+#[test]
+fn index_sources_must_be_inside_the_roots() {
+    let root = std::env::temp_dir().join(format!("aicli-rest-roots-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("docs")).unwrap();
+    let mut cfg = Config::from_env();
+    cfg.source_dir = root.join("docs").to_string_lossy().to_string();
+    let inside = index_source_allowed(&cfg, &root.join("docs").to_string_lossy());
+    let escaped = index_source_allowed(&cfg, &root.join("docs/../").to_string_lossy());
+    let etc = index_source_allowed(&cfg, "/etc");
+    let url = index_source_allowed(&cfg, "https://docs.example.com/guide");
+    cfg.serve_index_roots = vec!["https://docs.example.com".to_string()];
+    let url_under_root = index_source_allowed(&cfg, "https://docs.example.com/guide");
+    let lookalike = index_source_allowed(&cfg, "https://docs.example.com.evil.test/");
+    let old_root = index_source_allowed(&cfg, &root.join("docs").to_string_lossy());
+    let refused = respond(&cfg, &post("/index", r#"{"source": "/etc"}"#));
+    let _ = std::fs::remove_dir_all(&root);
+    assert!(inside);
+    assert!(!escaped);
+    assert!(!etc);
+    assert!(!url);
+    assert!(url_under_root);
+    assert!(!lookalike);
+    assert!(!old_root);
+    assert_eq!(refused.0, 403);
+    assert!(refused.1["error"].as_str().unwrap().contains("AICLI_SERVE_INDEX_ROOTS"));
+}
+
+#[test]
+fn rest_agents_get_no_local_tools_unless_allowed() {
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.local_tools = vec!["read_file".to_string(), "grep".to_string(), "shell".to_string()];
+    cfg.serve_local_tools = false;
+    let rest = rest_answer_config(&cfg);
+    assert!(rest.local_tools.is_empty());
+    assert!(LocalToolRegistry::from_config(&rest).is_empty());
+    assert!(!LocalToolRegistry::from_config(&cfg).is_empty());
+
+    cfg.serve_local_tools = true;
+    let rest = rest_answer_config(&cfg);
+    assert_eq!(rest.local_tools, cfg.local_tools);
+    assert!(LocalToolRegistry::from_config(&rest).has("shell"));
+}
+
+#[test]
+fn rest_agents_get_no_mcp_servers_unless_allowed() {
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    cfg.mcp_config = "mcp.json".to_string();
+    cfg.mcp_url = "http://127.0.0.1:9/mcp".to_string();
+    cfg.mcp_command = "mcp-server".to_string();
+    cfg.serve_mcp_tools = false;
+    let rest = rest_answer_config(&cfg);
+    assert!(rest.mcp_config.is_empty() && rest.mcp_url.is_empty() && rest.mcp_command.is_empty());
+    assert!(!McpClient::from_config(&rest).is_enabled());
+
+    cfg.serve_mcp_tools = true;
+    let rest = rest_answer_config(&cfg);
+    assert_eq!(rest.mcp_url, cfg.mcp_url);
+    assert_eq!(rest.mcp_config, cfg.mcp_config);
+}
+
+#[test]
+fn cross_site_requests_are_refused() {
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = true;
+    // A page can send a form-encoded or `text/plain` POST without a preflight.
+    let plain = with_header(request("POST", "/index", "{}"), "content-type", "text/plain");
+    let (status, body) = respond(&cfg, &plain);
+    assert_eq!(status, 415);
+    assert!(body["error"].as_str().unwrap().contains("application/json"));
+    assert_eq!(respond(&cfg, &request("POST", "/index", "")).0, 415);
+    let charset = with_header(request("POST", "/index", "{}"), "content-type", "application/json; charset=utf-8");
+    assert_eq!(respond(&cfg, &charset).0, 403);
+
+    let foreign = with_header(post("/index", "{}"), "origin", "https://evil.example");
+    assert_eq!(respond(&cfg, &foreign).0, 403);
+    assert!(cross_site_reason(&cfg, &foreign).unwrap().contains("AICLI_SERVE_CORS_ORIGIN"));
+    cfg.serve_cors_origin = "https://app.example.com".to_string();
+    let allowed = with_header(request("GET", "/nope", ""), "origin", "https://app.example.com");
+    assert_eq!(cross_site_reason(&cfg, &allowed), None);
+
+    // DNS rebinding: a name that resolves to 127.0.0.1 still sends its own Host.
+    let rebound = with_header(request("GET", "/v1/models", ""), "host", "attacker.example:8080");
+    assert_eq!(respond(&cfg, &rebound).0, 403);
+    for host in ["localhost:8080", "127.0.0.1", "[::1]:8080", "[::1]"] {
+        let local = with_header(request("GET", "/v1/models", ""), "host", host);
+        assert_eq!(respond(&cfg, &local).0, 200, "{}", host);
+    }
+    cfg.serve_token = "s3cret".to_string();
+    let proxied = with_header(rebound, "authorization", "Bearer s3cret");
+    assert_eq!(respond(&cfg, &proxied).0, 200);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::replay_config;
+use rag::{generate_answer_with, GenerationOptions, Message};
+
+fn question() -> Vec<Message> {
+    vec![Message {
+        role: "user".to_string(),
+        content: "What is the capital of France?".to_string(),
+    }]
+}
+
+#[test]
+fn blocking_calls_work_from_a_multi_threaded_runtime_task() {
+    let cfg = replay_config("multi", r#"{"kind":"chat","response":"Paris."}"#);
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(1).build().unwrap();
+    let answer = runtime
+        .block_on(async {
+            let cfg = cfg.clone();
+            tokio::spawn(async move { generate_answer_with(&cfg, &question(), &GenerationOptions::default()) }).await
+        })
+        .expect("task should not panic");
+    assert_eq!(answer.unwrap(), "Paris.");
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
+
+#[test]
+fn blocking_calls_from_a_current_thread_runtime_fail_instead_of_panicking() {
+    let cfg = replay_config("current", r#"{"kind":"chat","response":"Paris."}"#);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let err = runtime
+        .block_on(async { generate_answer_with(&cfg, &question(), &GenerationOptions::default()) })
+        .unwrap_err();
+    assert!(err.contains("current-thread"), "{}", err);
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
+
+#[test]
+fn blocking_calls_work_outside_any_runtime() {
+    let cfg = replay_config("plain", r#"{"kind":"chat","response":"Paris."}"#);
+    assert_eq!(generate_answer_with(&cfg, &question(), &GenerationOptions::default()).unwrap(), "Paris.");
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use rag::{list_sessions, record_daily_usage, sessions_dir, Config, Session, Usage};
+
+fn temp_config(name: &str) -> Config {
+    common::temp_config(&format!("session-{}", name))
+}
+
+#[test]
+fn the_tui_state_is_saved_and_restored() {
+    let cfg = temp_config("state");
+    let mut session = Session::new("bug-1234");
+    session.record_turn("Where is auth configured?", "ctx", "In config.rs.");
+    session.last_command_output = Some("$ cargo test\nok".to_string());
+    session.context_scroll = 12;
+    session.answer_scroll = 3;
+    session.collection = Some("api_docs".to_string());
+    session.env.insert("RUST_LOG".to_string(), "debug".to_string());
+    session.save(&cfg).unwrap();
+
+    let loaded = Session::load(&cfg, "bug-1234").unwrap().unwrap();
+    assert_eq!(loaded.last_command_output.as_deref(), Some("$ cargo test\nok"));
+    assert_eq!((loaded.context_scroll, loaded.answer_scroll), (12, 3));
+    assert_eq!(loaded.collection.as_deref(), Some("api_docs"));
+    assert_eq!(loaded.env["RUST_LOG"], "debug");
+    assert_eq!(loaded.conversation.len(), 2);
+    assert!(Session::load(&cfg, "missing").unwrap().is_none());
+    assert_eq!(list_sessions(&cfg).unwrap()[0].name, "bug-1234");
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn sessions_saved_before_resume_still_load() {
+    let cfg = temp_config("old");
+    std::fs::create_dir_all(sessions_dir(&cfg)).unwrap();
+    let old = r#"{"name": "old", "conversation": [], "last_answer": "hi", "updated_at": 1792152000}"#;
+    std::fs::write(sessions_dir(&cfg).join("old.json"), old).unwrap();
+    let loaded = Session::load(&cfg, "old").unwrap().unwrap();
+    assert_eq!(loaded.last_answer.as_deref(), Some("hi"));
+    assert_eq!((loaded.context_scroll, loaded.answer_scroll), (0, 0));
+    assert!(loaded.collection.is_none() && loaded.last_command_output.is_none());
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
+
+#[test]
+fn safe_mode_saves_neither_the_session_nor_the_usage_log() {
+    let mut cfg = temp_config("safe");
+    cfg.safe_mode = true;
+    let mut session = Session::new("safe");
+    session.record_turn("Where is auth configured?", "ctx", "In config.rs.");
+    assert!(session.save(&cfg).unwrap_err().contains("Safe mode"));
+    let usage = Usage { calls: 1, prompt_tokens: 10, completion_tokens: 5, cost_usd: 0.01 };
+    assert!(record_daily_usage(&cfg, &usage).unwrap_err().contains("Safe mode"));
+    assert!(!std::path::Path::new(&cfg.data_dir).exists());
+}
+
+#[test]
+fn agent_state_round_trips() {
+    let cfg = temp_config("agent");
+    let mut session = Session::new("agent");
+    session.agent_mode = Some("mcp-only".to_string());
+    session.agent_plan = vec!["Find the config loader".to_string(), "Read its defaults".to_string()];
+    session.agent_trace = vec!["step 1: retrieve".to_string(), "  retrieved 4 chunks for \"config\"".to_string()];
+    session.save(&cfg).unwrap();
+
+    let loaded = Session::load(&cfg, "agent").unwrap().unwrap();
+    assert_eq!(loaded.agent_mode.as_deref(), Some("mcp-only"));
+    assert_eq!(loaded.agent_plan, session.agent_plan);
+    assert_eq!(loaded.agent_trace, session.agent_trace);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
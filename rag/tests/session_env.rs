@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+use rag::{apply_env_command, describe_env, parse_env_assignment, split_command_line};
+
+fn env_command(vars: &mut BTreeMap<String, String>, line: &str) -> Result<String, String> {
+    apply_env_command(vars, &split_command_line(line)[1..])
+}
+
+#[test]
+fn assignments_need_a_valid_name() {
+    assert_eq!(parse_env_assignment("RUST_LOG=debug"), Ok(("RUST_LOG".to_string(), "debug".to_string())));
+    assert_eq!(parse_env_assignment("_X=a=b"), Ok(("_X".to_string(), "a=b".to_string())));
+    assert_eq!(parse_env_assignment("EMPTY="), Ok(("EMPTY".to_string(), String::new())));
+    assert!(parse_env_assignment("RUST_LOG").is_err());
+    assert!(parse_env_assignment("=value").is_err());
+    assert!(parse_env_assignment("1X=value").is_err());
+    assert!(parse_env_assignment("MY-VAR=value").is_err());
+}
+
+#[test]
+fn env_commands_set_list_and_clear() {
+    let mut vars = BTreeMap::new();
+    assert_eq!(env_command(&mut vars, "/env").unwrap(), "Session env: (none)");
+    env_command(&mut vars, r#"/env RUST_LOG=debug GREETING="hello world""#).unwrap();
+    assert_eq!(describe_env(&vars), "GREETING=hello world RUST_LOG=debug");
+
+    // One bad assignment leaves every variable as it was.
+    assert!(env_command(&mut vars, "/env A=1 not-a-name=2").is_err());
+    assert!(!vars.contains_key("A"));
+
+    env_command(&mut vars, "/env clear GREETING").unwrap();
+    assert_eq!(describe_env(&vars), "RUST_LOG=debug");
+    assert_eq!(env_command(&mut vars, "/env clear").unwrap(), "Cleared 1 session variable.");
+    assert!(vars.is_empty());
+}
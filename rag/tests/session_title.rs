@@ -0,0 +1,20 @@
+use rag::clean_title;
+
+#[test]
+fn strips_quotes_prefix_and_trailing_punctuation() {
+    assert_eq!(clean_title("\"Fixing the Qdrant timeout.\""), "Fixing the Qdrant timeout");
+    assert_eq!(clean_title("Title: Chunk size tuning"), "Chunk size tuning");
+    assert_eq!(clean_title("\n  **Index migration**\nBecause you asked about..."), "Index migration");
+}
+
+#[test]
+fn long_titles_are_cut() {
+    let title = clean_title(&"word ".repeat(30));
+    assert!(title.ends_with("..."));
+    assert!(title.chars().count() <= 63);
+}
+
+#[test]
+fn empty_reply_gives_empty_title() {
+    assert_eq!(clean_title("   \n"), "");
+}
@@ -0,0 +1,38 @@
+use rag::{Message, ShareAction, conversation_title, export_markdown, parse_share_targets};
+
+fn msg(role: &str, content: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: content.to_string(),
+    }
+}
+
+#[test]
+fn parses_command_and_webhook_targets() {
+    let targets = parse_share_targets(
+        "github=cmd:gh issue create --title \"$AICLI_SHARE_TITLE\" --body-file -; slack=webhook:https://hooks.slack.com/x;bad=ftp:nope",
+    );
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0].name, "github");
+    assert_eq!(
+        targets[0].action,
+        ShareAction::Command("gh issue create --title \"$AICLI_SHARE_TITLE\" --body-file -".to_string())
+    );
+    assert_eq!(targets[1].action, ShareAction::Webhook("https://hooks.slack.com/x".to_string()));
+}
+
+#[test]
+fn exports_turns_as_markdown_sections() {
+    let conversation = vec![
+        msg("system", "ignored"),
+        msg("user", "Why does indexing fail?\nmore detail"),
+        msg("assistant", "Qdrant is not running."),
+    ];
+    let title = conversation_title(&conversation);
+    assert_eq!(title, "Why does indexing fail?");
+    let md = export_markdown(&title, &conversation);
+    assert_eq!(
+        md,
+        "# Why does indexing fail?\n\n## Question\n\nWhy does indexing fail?\nmore detail\n\n## Answer\n\nQdrant is not running.\n"
+    );
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use rag::{SnapshotHeader, is_native_snapshot, read_snapshot_header};
+
+#[test]
+fn snapshot_extension_picks_the_format() {
+    assert!(is_native_snapshot(Path::new("/tmp/docs.snapshot")));
+    assert!(!is_native_snapshot(Path::new("/tmp/docs.jsonl")));
+    assert!(!is_native_snapshot(Path::new("docs")));
+}
+
+#[test]
+fn reads_header_from_first_line() {
+    let path = std::env::temp_dir().join(format!("aicli-snapshot-{}.jsonl", std::process::id()));
+    std::fs::write(
+        &path,
+        "{\"collection\":\"docs\",\"vector_size\":768,\"distance\":\"Cosine\",\"embed_model\":\"nomic-embed-text\",\"points\":1}\n{\"id\":\"a\",\"payload\":{},\"vector\":[0.1]}\n",
+    )
+    .unwrap();
+    let header = read_snapshot_header(&path).expect("header should parse");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(
+        header,
+        SnapshotHeader {
+            collection: "docs".to_string(),
+            vector_size: 768,
+            distance: "Cosine".to_string(),
+            embed_model: "nomic-embed-text".to_string(),
+            points: 1,
+        }
+    );
+}
+
+#[test]
+fn rejects_files_that_are_not_snapshots() {
+    let path = std::env::temp_dir().join(format!("aicli-not-snapshot-{}.jsonl", std::process::id()));
+    std::fs::write(&path, "hello\n").unwrap();
+    let err = read_snapshot_header(&path).unwrap_err();
+    let _ = std::fs::remove_file(&path);
+    assert!(err.contains("is not an aicli snapshot"));
+}
@@ -0,0 +1,52 @@
+use rag::{complete_snippet, expand_snippets, load_snippets, parse_snippet, save_snippets, Config, Snippets};
+
+fn snippets() -> Snippets {
+    let mut s = Snippets::new();
+    s.insert("paths".to_string(), "answer with file paths and line numbers".to_string());
+    s.insert("patch".to_string(), "reply with a unified diff".to_string());
+    s.insert("tests".to_string(), "include tests".to_string());
+    s
+}
+
+#[test]
+fn expands_known_names_and_keeps_unknown_ones() {
+    assert_eq!(
+        expand_snippets("Where is retry handled? ;;paths, ;;nope", &snippets()),
+        "Where is retry handled? answer with file paths and line numbers, ;;nope"
+    );
+    assert_eq!(expand_snippets("a ;; b", &snippets()), "a ;; b");
+}
+
+#[test]
+fn completes_to_the_longest_shared_prefix() {
+    let s = snippets();
+    let input = "fix it ;;pa";
+    assert_eq!(complete_snippet(input, input.len(), &s), Some((9, "pat".to_string())));
+    let input = "fix it ;;te";
+    assert_eq!(complete_snippet(input, input.len(), &s), Some((9, "tests".to_string())));
+    assert_eq!(complete_snippet("fix ;;zz", 8, &s), None);
+    assert_eq!(complete_snippet("no snippet", 10, &s), None);
+}
+
+#[test]
+fn parses_manager_lines() {
+    assert_eq!(
+        parse_snippet(";;tests = include tests "),
+        Ok(("tests".to_string(), "include tests".to_string()))
+    );
+    assert!(parse_snippet("bad name = x").is_err());
+    assert!(parse_snippet("empty =").is_err());
+    assert!(parse_snippet("no separator").is_err());
+}
+
+#[test]
+fn saves_and_loads_from_the_config_dir() {
+    let dir = std::env::temp_dir().join(format!("aicli-snippets-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut cfg = Config::from_env();
+    cfg.config_dir = dir.to_string_lossy().to_string();
+    assert!(load_snippets(&cfg).unwrap().is_empty());
+    save_snippets(&cfg, &snippets()).unwrap();
+    assert_eq!(load_snippets(&cfg).unwrap(), snippets());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{replay_config, stub_server};
+use rag::{answer_query_streaming_with_events, AgentEvent, AgentMode, AgentOptions, AnswerResult, Config, Message};
+
+fn stream_config(name: &str, fixture: &str) -> Config {
+    let mut cfg = replay_config(name, fixture);
+    // Every text embeds as an empty vector, so retrieval finds nothing and
+    // no Qdrant is needed.
+    cfg.ollama_url = stub_server(|_, _, _| (200, r#"{"embeddings":[]}"#.to_string()));
+    cfg.ollama_urls = Vec::new();
+    cfg.http_retries = 0;
+    cfg.answer_pipeline = "agent".to_string();
+    cfg.retrieval_pipeline = String::new();
+    cfg.query_expansion = "off".to_string();
+    cfg.empty_context_fallback = true;
+    cfg
+}
+
+fn options(mode: AgentMode) -> AgentOptions {
+    AgentOptions { mode, ..AgentOptions::default() }
+}
+
+// Stream an answer, stopping once `keep` pieces have arrived.
+fn stream(cfg: &Config, history: &[Message], mode: AgentMode, keep: usize) -> (Vec<String>, AnswerResult) {
+    let mut pieces = Vec::new();
+    let mut on_token = |piece: &str| {
+        pieces.push(piece.to_string());
+        pieces.len() < keep
+    };
+    let question = "What is the capital?";
+    let result = answer_query_streaming_with_events(cfg, history, question, &options(mode), None, &mut on_token)
+        .expect("the stream should finish");
+    (pieces, result)
+}
+
+#[test]
+fn rag_only_mode_streams_the_answer_as_it_is_generated() {
+    let cfg = stream_config("stream-rag", r#"{"kind":"chat","response":"Paris is the capital."}"#);
+    let (pieces, result) = stream(&cfg, &[], AgentMode::RagOnly, usize::MAX);
+    assert_eq!(pieces, ["Paris ", "is ", "the ", "capital."]);
+    assert_eq!(result.answer, "Paris is the capital.");
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
+
+#[test]
+fn stopping_the_stream_keeps_the_partial_answer() {
+    let cfg = stream_config("stream-stop", r#"{"kind":"chat","response":"Paris is the capital."}"#);
+    let (pieces, result) = stream(&cfg, &[], AgentMode::RagOnly, 2);
+    assert_eq!(pieces, ["Paris ", "is "]);
+    assert_eq!(result.answer, "Paris is ");
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
+
+#[test]
+fn a_refinement_is_asked_after_the_previous_turn() {
+    let cfg = stream_config("stream-refine", r#"{"kind":"chat","response":"Paris."}"#);
+    let history = [
+        Message { role: "user".to_string(), content: "What is the capital of France?".to_string() },
+        Message { role: "assistant".to_string(), content: "The capital of France is Paris.".to_string() },
+    ];
+    let (_, result) = stream(&cfg, &history, AgentMode::RagOnly, usize::MAX);
+    assert_eq!(result.answer, "Paris.");
+    let turns: Vec<(&str, &str)> = result.prompt.iter().map(|m| (m.role.as_str(), m.content.as_str())).collect();
+    assert_eq!(turns.len(), 4, "{:?}", turns);
+    assert_eq!(turns[0].0, "system");
+    assert_eq!(turns[1], ("user", "What is the capital of France?"));
+    assert_eq!(turns[2], ("assistant", "The capital of France is Paris."));
+    assert_eq!(turns[3].0, "user");
+    assert!(turns[3].1.ends_with("What is the capital?"), "{}", turns[3].1);
+    let _ = std::fs::remove_file(&cfg.replay_file);
+}
+
+#[test]
+fn other_modes_run_the_agent_and_send_its_answer_whole() {
+    let fixture = r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"From the agent.\"}"}"#;
+    for mode in [AgentMode::Hybrid, AgentMode::McpOnly] {
+        let cfg = stream_config(&format!("stream-{}", mode.label()), fixture);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut pieces = Vec::new();
+        let mut on_token = |piece: &str| {
+            pieces.push(piece.to_string());
+            true
+        };
+        let question = "What is the capital?";
+        let result = answer_query_streaming_with_events(&cfg, &[], question, &options(mode), Some(tx), &mut on_token)
+            .expect("the agent should answer");
+        assert_eq!(pieces, ["From the agent."], "{}", mode.label());
+        assert_eq!(result.answer, "From the agent.");
+        let events: Vec<AgentEvent> = rx.iter().collect();
+        assert!(events.iter().any(|e| matches!(e, AgentEvent::Final { forced: false, .. })), "{:?}", events);
+        let _ = std::fs::remove_file(&cfg.replay_file);
+    }
+}
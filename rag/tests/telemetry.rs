@@ -0,0 +1,44 @@
+mod common;
+
+use rag::{Config, error_category, export_telemetry, record_error, record_feature, telemetry_log};
+
+fn temp_config(name: &str, enabled: bool) -> Config {
+    let mut cfg = common::temp_config(&format!("telemetry-{}", name));
+    cfg.telemetry = enabled;
+    cfg
+}
+
+#[test]
+fn categorizes_errors_without_keeping_the_message() {
+    assert_eq!(error_category("POST http://localhost:11434/api/chat failed: operation timed out"), "timeout");
+    assert_eq!(error_category("error sending request: tcp connect error"), "connection");
+    assert_eq!(error_category("Collection docs does not exist"), "not_found");
+    assert_eq!(error_category("Safe mode is on: indexing is disabled."), "safe_mode");
+    assert_eq!(error_category("something odd"), "other");
+}
+
+#[test]
+fn nothing_is_recorded_when_disabled() {
+    let cfg = temp_config("off", false);
+    record_feature(&cfg, "query.agent");
+    assert!(!std::path::Path::new(&cfg.data_dir).join("telemetry.json").exists());
+}
+
+#[test]
+fn counts_features_and_error_categories_and_exports_them() {
+    let cfg = temp_config("on", true);
+    record_feature(&cfg, "query.agent");
+    record_feature(&cfg, "query.agent");
+    record_error(&cfg, "query", "GET http://q/collections/secret-name failed: 404 Not Found ");
+    let log = telemetry_log(&cfg).unwrap();
+    assert_eq!(log.features.get("query.agent"), Some(&2));
+    assert_eq!(log.errors.get("query:not_found"), Some(&1));
+    assert!(log.since > 0);
+
+    let out = std::path::Path::new(&cfg.data_dir).join("report.json");
+    export_telemetry(&cfg, Some(out.to_str().unwrap())).unwrap();
+    let report = std::fs::read_to_string(&out).unwrap();
+    assert!(report.contains("query:not_found"));
+    assert!(!report.contains("secret-name"));
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+}
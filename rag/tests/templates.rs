@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use rag::{
+    fill_template, load_templates, parse_template, parse_template_var, save_templates,
+    template_placeholders, Config, Templates,
+};
+
+fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn placeholders_are_listed_once_in_order() {
+    assert_eq!(
+        template_placeholders(
+            "errors in {service} since {date}; compare {service} with {\"json\": 1} and {}"
+        ),
+        vec!["service".to_string(), "date".to_string()]
+    );
+}
+
+#[test]
+fn templates_are_filled_from_values() {
+    let text = "summarize errors in service {service} since {date} for {service}";
+    assert_eq!(
+        fill_template(text, &values(&[("service", "billing"), ("date", "monday")])).unwrap(),
+        "summarize errors in service billing since monday for billing"
+    );
+    let err = fill_template(text, &values(&[("service", "billing")])).unwrap_err();
+    assert!(err.contains("date"), "{}", err);
+    let err = fill_template(
+        text,
+        &values(&[("service", "a"), ("date", "b"), ("team", "c")]),
+    )
+    .unwrap_err();
+    assert!(err.contains("team"), "{}", err);
+}
+
+#[test]
+fn definitions_and_vars_are_parsed() {
+    assert_eq!(
+        parse_template(" oncall = errors in {service} ").unwrap(),
+        ("oncall".to_string(), "errors in {service}".to_string())
+    );
+    assert!(parse_template("plain = no placeholders here").is_err());
+    assert!(parse_template("bad name = {x}").is_err());
+    assert_eq!(
+        parse_template_var("date=2024-01-01=x").unwrap(),
+        ("date".to_string(), "2024-01-01=x".to_string())
+    );
+    assert!(parse_template_var("novalue").is_err());
+}
+
+#[test]
+fn templates_round_trip() {
+    let dir = std::env::temp_dir().join(format!("aicli-templates-{}", std::process::id()));
+    let mut cfg = Config::from_env();
+    cfg.config_dir = dir.to_string_lossy().to_string();
+    assert!(load_templates(&cfg).unwrap().is_empty());
+    let mut templates = Templates::new();
+    templates.insert("oncall".to_string(), "errors in {service}".to_string());
+    save_templates(&cfg, &templates).unwrap();
+    assert_eq!(load_templates(&cfg).unwrap(), templates);
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use rag::{Config, find_snapshot, list_snapshots, parse_as_of, parse_rfc3339};
+
+fn config(name: &str) -> Config {
+    let mut cfg = Config::from_env();
+    cfg.data_dir = std::env::temp_dir()
+        .join(format!("aicli-time-travel-{}-{}", name, std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    cfg.collection = "docs".to_string();
+    cfg.time_zone = "UTC".to_string();
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+    std::fs::create_dir_all(PathBuf::from(&cfg.data_dir).join("snapshots")).unwrap();
+    cfg
+}
+
+fn write_snapshot(cfg: &Config, name: &str, collection: &str) -> PathBuf {
+    let path = PathBuf::from(&cfg.data_dir).join("snapshots").join(name);
+    let header = format!(
+        "{{\"collection\":\"{}\",\"vector_size\":3,\"distance\":\"Cosine\",\"embed_model\":\"m\",\"points\":0}}\n",
+        collection
+    );
+    std::fs::write(&path, header).unwrap();
+    path
+}
+
+#[test]
+fn lists_snapshots_of_the_collection_oldest_first() {
+    let cfg = config("list");
+    write_snapshot(&cfg, "docs-1717300000.jsonl", "docs");
+    write_snapshot(&cfg, "docs-1717200000.jsonl", "docs");
+    write_snapshot(&cfg, "other-1717250000.jsonl", "other");
+    std::fs::write(PathBuf::from(&cfg.data_dir).join("snapshots/docs-1717300000.jsonl.manifest.json"), "{}").unwrap();
+    let snapshots = list_snapshots(&cfg);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+    let times: Vec<u64> = snapshots.iter().map(|s| s.created_at).collect();
+    assert_eq!(times, vec![1717200000, 1717300000]);
+    assert!(snapshots.iter().all(|s| s.collection == "docs"));
+}
+
+#[test]
+fn a_date_means_the_end_of_that_day() {
+    let cfg = config("date");
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+    assert_eq!(parse_as_of(&cfg, "2024-06-01"), parse_rfc3339("2024-06-01T23:59:59Z"));
+    assert_eq!(parse_as_of(&cfg, "2024-06-01T12:00:00Z"), parse_rfc3339("2024-06-01T12:00:00Z"));
+    assert_eq!(parse_as_of(&cfg, "last week"), None);
+}
+
+#[test]
+fn finds_the_newest_snapshot_taken_by_the_date() {
+    let cfg = config("find");
+    // 2024-05-31 and 2024-06-02, in UTC.
+    write_snapshot(&cfg, "docs-1717171200.jsonl", "docs");
+    let later = write_snapshot(&cfg, "docs-1717315200.jsonl", "docs");
+    let by_june_first = find_snapshot(&cfg, "2024-06-01").map(|s| s.created_at);
+    let by_june_third = find_snapshot(&cfg, "2024-06-03").map(|s| s.path);
+    let too_early = find_snapshot(&cfg, "2024-01-01");
+    let by_file = find_snapshot(&cfg, &later.to_string_lossy()).map(|s| s.created_at);
+    let _ = std::fs::remove_dir_all(&cfg.data_dir);
+    assert_eq!(by_june_first, Ok(1717171200));
+    assert_eq!(by_june_third, Ok(later));
+    assert!(too_early.unwrap_err().contains("aicli snapshot list"));
+    assert_eq!(by_file, Ok(1717315200));
+}
@@ -0,0 +1,60 @@
+use rag::{
+    date_style, format_time, parse_rfc3339, parse_utc_offset, rfc3339, rfc3339_millis, CachedToken, DateStyle, Session,
+};
+
+// 2026-10-16T12:03:09Z
+const T: u64 = 1_792_152_189;
+
+#[test]
+fn times_round_trip_through_rfc3339() {
+    assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(rfc3339(T), "2026-10-16T12:03:09Z");
+    assert_eq!(rfc3339_millis(T * 1000 + 250), "2026-10-16T12:03:09.250Z");
+    assert_eq!(parse_rfc3339("2026-10-16T12:03:09Z"), Some(T));
+    assert_eq!(parse_rfc3339("2026-10-16T14:03:09+02:00"), Some(T));
+    assert_eq!(parse_rfc3339("2026-10-16 12:03:09.250z"), Some(T));
+    assert_eq!(parse_rfc3339("2024-02-29T00:00:00Z"), Some(1_709_164_800));
+    for secs in [0, 951_782_400, 1_709_164_800, 4_102_444_800] {
+        assert_eq!(parse_rfc3339(&rfc3339(secs)), Some(secs));
+    }
+    assert_eq!(parse_rfc3339("2026-10-16T12:03:09"), None);
+    assert_eq!(parse_rfc3339("2026-13-01T00:00:00Z"), None);
+    assert_eq!(parse_rfc3339("1969-12-31T23:59:59Z"), None);
+}
+
+#[test]
+fn offsets_and_locales_shape_the_display() {
+    assert_eq!(parse_utc_offset("+05:30"), Some(19_800));
+    assert_eq!(parse_utc_offset("-0800\n"), Some(-28_800));
+    assert_eq!(parse_utc_offset("UTC"), Some(0));
+    assert_eq!(parse_utc_offset("local"), None);
+    assert_eq!(date_style("en_US.UTF-8"), DateStyle::MonthFirst);
+    assert_eq!(date_style("en_GB.UTF-8"), DateStyle::DayFirst);
+    assert_eq!(date_style("de_DE@euro"), DateStyle::DayFirstDot);
+    assert_eq!(date_style("ja_JP"), DateStyle::Iso);
+    assert_eq!(date_style(""), DateStyle::Iso);
+
+    assert_eq!(format_time(T, 0, DateStyle::Iso, true), "2026-10-16 12:03");
+    assert_eq!(format_time(T, 7200, DateStyle::DayFirstDot, true), "16.10.2026 14:03");
+    assert_eq!(format_time(T, 3600, DateStyle::DayFirst, true), "16/10/2026 13:03");
+    assert_eq!(format_time(T, -43_200, DateStyle::MonthFirst, true), "10/16/2026 12:03 AM");
+    assert_eq!(format_time(T, 3 * 3600, DateStyle::MonthFirst, false), "3:03 PM");
+}
+
+#[test]
+fn stored_times_are_rfc3339_and_old_numbers_still_load() {
+    let mut session = Session::new("work");
+    session.updated_at = T;
+    let saved = serde_json::to_value(&session).unwrap();
+    assert_eq!(saved["updated_at"], "2026-10-16T12:03:09Z");
+    let loaded: Session = serde_json::from_value(saved).unwrap();
+    assert_eq!(loaded.updated_at, T);
+
+    let old: Session = serde_json::from_str(r#"{"name": "work", "updated_at": 1792152189}"#).unwrap();
+    assert_eq!(old.updated_at, T);
+    let token: CachedToken = serde_json::from_str(r#"{"access_token": "t", "expires_at": 1792152189}"#).unwrap();
+    assert_eq!(token.expires_at, Some(T));
+    assert_eq!(serde_json::to_value(&token).unwrap()["expires_at"], "2026-10-16T12:03:09Z");
+    let token: CachedToken = serde_json::from_str(r#"{"access_token": "t"}"#).unwrap();
+    assert_eq!(token.expires_at, None);
+}
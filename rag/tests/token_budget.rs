@@ -0,0 +1,60 @@
+use rag::{estimate_tokens, fit_hits_to_budget, truncate_to_tokens, Hit, TRIMMED_MARKER};
+use serde_json::json;
+
+fn hit(path: &str, score: f32, chunk: &str) -> Hit {
+    serde_json::from_value(json!({
+        "score": score,
+        "payload": { "path": path, "index": 0, "chunk": chunk }
+    }))
+    .unwrap()
+}
+
+fn paths(hits: &[Hit]) -> Vec<String> {
+    hits.iter()
+        .map(|h| h.payload.as_ref().unwrap().path.clone().unwrap())
+        .collect()
+}
+
+#[test]
+fn estimates_words_by_length_and_symbols_individually() {
+    assert_eq!(estimate_tokens(""), 0);
+    assert_eq!(estimate_tokens("the cat"), 2);
+    assert_eq!(estimate_tokens("tokenization"), 3);
+    assert_eq!(estimate_tokens("fn main() {}"), 6);
+}
+
+#[test]
+fn truncation_prefers_line_breaks() {
+    let text = "first line here\nsecond line here\nthird line here";
+    assert_eq!(truncate_to_tokens(text, 100), text);
+    assert_eq!(truncate_to_tokens(text, 8), "first line here\nsecond line here");
+    assert_eq!(truncate_to_tokens("abcdefghijkl", 2), "abcdefgh");
+}
+
+#[test]
+fn keeps_everything_that_fits_in_input_order() {
+    let hits = vec![hit("a", 0.5, "alpha"), hit("b", 0.9, "beta")];
+    assert_eq!(paths(&fit_hits_to_budget(&hits, 1_000)), vec!["a", "b"]);
+}
+
+#[test]
+fn higher_scores_win_when_space_is_short() {
+    let long = "word ".repeat(200);
+    let hits = vec![
+        hit("low", 0.2, &long),
+        hit("high", 0.9, &long),
+        hit("small", 0.1, "tiny"),
+    ];
+    let kept = fit_hits_to_budget(&hits, 260);
+    assert_eq!(paths(&kept), vec!["high", "small"]);
+}
+
+#[test]
+fn top_hit_is_trimmed_rather_than_dropped() {
+    let long = "line of text\n".repeat(500);
+    let kept = fit_hits_to_budget(&[hit("big", 0.9, &long)], 200);
+    assert_eq!(kept.len(), 1);
+    let chunk = kept[0].payload.as_ref().unwrap().chunk.clone().unwrap();
+    assert!(chunk.ends_with(TRIMMED_MARKER));
+    assert!(estimate_tokens(&chunk) <= 200);
+}
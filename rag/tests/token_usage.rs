@@ -0,0 +1,85 @@
+mod common;
+
+use std::time::Duration;
+
+use common::stub_server;
+use rag::{
+    budget_exceeded, call_with_timeout, current_usage, estimate_cost, generate_answer_with, parse_price_table,
+    record_call, reset_usage, take_usage, Config, GenerationOptions, Message, ModelPrice, Usage,
+};
+
+#[test]
+fn since_is_the_usage_between_two_readings() {
+    let earlier = Usage { calls: 1, prompt_tokens: 100, completion_tokens: 20, cost_usd: 0.5 };
+    let later = Usage { calls: 3, prompt_tokens: 340, completion_tokens: 65, cost_usd: 0.75 };
+    let step = later.since(&earlier);
+    assert_eq!(step.calls, 2);
+    assert_eq!(step.prompt_tokens, 240);
+    assert_eq!(step.completion_tokens, 45);
+    assert_eq!(step.total_tokens(), 285);
+    assert!((step.cost_usd - 0.25).abs() < 1e-9);
+    assert_eq!(earlier.since(&later), Usage::default());
+}
+
+#[test]
+fn calls_on_a_timed_thread_count_toward_the_caller() {
+    let cfg = Config::from_env();
+    reset_usage();
+    record_call(&cfg, 10, 5);
+    let call_cfg = cfg.clone();
+    let answer = call_with_timeout(Some(Duration::from_secs(5)), move || {
+        record_call(&call_cfg, 200, 40);
+        Ok("done")
+    });
+    assert_eq!(answer, Ok("done"));
+    assert_eq!(current_usage().calls, 2);
+    let usage = take_usage();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (210, 45));
+    assert_eq!(current_usage(), Usage::default());
+}
+
+#[test]
+fn price_table_entries_are_usd_per_million_tokens() {
+    let table = parse_price_table(" gpt-4o = 2.5 : 10 ,qwen2.5:7b=0:0,broken,no-output=1,bad=x:1,");
+    assert_eq!(table.len(), 2);
+    assert_eq!(table["gpt-4o"], ModelPrice { input_per_mtok: 2.5, output_per_mtok: 10.0 });
+    assert_eq!(table["qwen2.5:7b"], ModelPrice { input_per_mtok: 0.0, output_per_mtok: 0.0 });
+    assert!(parse_price_table("").is_empty());
+}
+
+#[test]
+fn cost_comes_from_the_chat_models_price() {
+    let mut cfg = Config::from_env();
+    cfg.price_table = parse_price_table("gpt-4o=2.5:10");
+    cfg.chat_model = "gpt-4o".to_string();
+    assert!((estimate_cost(&cfg, 1_000_000, 100_000) - 3.5).abs() < 1e-9);
+    cfg.chat_model = "llama3.2".to_string();
+    assert_eq!(estimate_cost(&cfg, 1_000_000, 100_000), 0.0);
+
+    cfg.daily_budget_usd = Some(1.0);
+    assert!(!budget_exceeded(&cfg, &Usage { cost_usd: 0.99, ..Usage::default() }));
+    assert!(budget_exceeded(&cfg, &Usage { cost_usd: 1.0, ..Usage::default() }));
+    cfg.daily_budget_usd = None;
+    assert!(!budget_exceeded(&cfg, &Usage { cost_usd: 1_000.0, ..Usage::default() }));
+}
+
+#[test]
+fn ollama_token_counts_are_recorded_with_their_cost() {
+    let mut cfg = Config::from_env();
+    cfg.llm_provider = "ollama".to_string();
+    let reply = r#"{"message":{"content":"Paris."},"prompt_eval_count":1200,"eval_count":300}"#;
+    cfg.ollama_url = stub_server(move |_, _, _| (200, reply.to_string()));
+    cfg.ollama_urls = Vec::new();
+    cfg.record_file = String::new();
+    cfg.http_retries = 0;
+    cfg.generation.num_ctx = Some(4096);
+    cfg.chat_model = "priced".to_string();
+    cfg.price_table = parse_price_table("priced=2:8");
+    let question = vec![Message { role: "user".to_string(), content: "Capital of France?".to_string() }];
+
+    reset_usage();
+    assert_eq!(generate_answer_with(&cfg, &question, &GenerationOptions::default()).unwrap(), "Paris.");
+    let usage = take_usage();
+    assert_eq!((usage.calls, usage.prompt_tokens, usage.completion_tokens), (1, 1200, 300));
+    assert!((usage.cost_usd - 0.0048).abs() < 1e-9);
+}
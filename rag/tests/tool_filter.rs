@@ -0,0 +1,88 @@
+mod common;
+
+use common::replay_config;
+use rag::{glob_match, run_agent, AgentEvent, AgentState, McpCapabilities, McpClient, McpTool, ToolFilter};
+use serde_json::json;
+
+fn filter(allow: &[&str], deny: &[&str]) -> ToolFilter {
+    ToolFilter {
+        allow: allow.iter().map(|s| s.to_string()).collect(),
+        deny: deny.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+#[test]
+fn globs_match_whole_tool_names() {
+    assert!(glob_match("git_*", "git_status"));
+    assert!(glob_match("*_delete", "fs_delete"));
+    assert!(glob_match("read_?ile", "read_file"));
+    assert!(!glob_match("git_*", "github_search"));
+    assert!(!glob_match("read_file", "read_files"));
+}
+
+#[test]
+fn the_denylist_wins_over_the_allowlist() {
+    let only_git = filter(&["git_*"], &["git_push*"]);
+    assert!(only_git.permits("git_log"));
+    assert!(!only_git.permits("git_push_force"));
+    assert!(!only_git.permits("shell_exec"));
+    let no_writes = filter(&[], &["*_write", "*_delete"]);
+    assert!(no_writes.permits("fs_read"));
+    assert!(!no_writes.permits("fs_delete"));
+    assert!(ToolFilter::default().permits("anything"));
+}
+
+#[test]
+fn filtered_tools_are_dropped_from_capabilities() {
+    let tool = |name: &str| McpTool {
+        name: name.to_string(),
+        description: String::new(),
+        input_schema: json!({}),
+    };
+    let mut caps = McpCapabilities {
+        tools: vec!["fs_read".to_string(), "fs_delete".to_string()],
+        tool_specs: vec![tool("fs_read"), tool("fs_delete")],
+        prompts: vec!["review".to_string()],
+        resources: vec!["file:///README.md".to_string()],
+        diagnostics: Vec::new(),
+    };
+    filter(&["fs_*"], &["*_delete"]).apply(&mut caps);
+    assert_eq!(caps.tools, vec!["fs_read".to_string()]);
+    assert_eq!(caps.tool_specs.len(), 1);
+    assert_eq!(caps.prompts.len(), 1);
+    assert_eq!(caps.resources.len(), 1);
+}
+
+#[test]
+fn a_denied_tool_is_refused_before_the_server_is_contacted() {
+    let fixture = concat!(
+        r#"{"kind":"json","response":"{\"action\":\"tool\",\"name\":\"fs_delete\",\"arguments\":{\"path\":\"/\"}}"}"#,
+        "\n",
+        r#"{"kind":"json","response":"{\"action\":\"final\",\"answer\":\"I cannot delete files here.\"}"}"#,
+        "\n",
+    );
+    let mut cfg = replay_config("tool-filter", fixture);
+    // Nothing listens here; a call that got through would fail to connect.
+    cfg.mcp_url = "http://127.0.0.1:9/mcp".to_string();
+    cfg.safe_mode = false;
+    cfg.agent_reflect = false;
+    cfg.mcp_tool_allowlist = Vec::new();
+    cfg.mcp_tool_denylist = vec!["*_delete".to_string()];
+
+    let mcp = McpClient::from_config(&cfg);
+    let err = mcp.call_tool("fs_delete", json!({})).unwrap_err();
+    assert!(err.contains("MCP_TOOL_DENYLIST"), "{}", err);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut state = AgentState::new(3);
+    state.events = Some(tx);
+    state.append_user("Delete everything.".to_string());
+    assert_eq!(run_agent(&mut state, &cfg, &mcp).unwrap(), "I cannot delete files here.");
+    drop(state.events.take());
+    assert!(state
+        .conversation
+        .iter()
+        .any(|m| m.role == "system" && m.content.starts_with("MCP tool 'fs_delete' is not allowed")));
+    let events: Vec<AgentEvent> = rx.iter().collect();
+    assert!(!events.iter().any(|e| matches!(e, AgentEvent::ToolCalled { .. })), "{:?}", events);
+}
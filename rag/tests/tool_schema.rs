@@ -0,0 +1,65 @@
+use rag::{validate_tool_args, McpTool};
+use serde_json::json;
+
+fn weather_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "city": { "type": "string" },
+            "units": { "type": "string", "enum": ["metric", "imperial"] },
+            "days": { "type": "integer" }
+        },
+        "required": ["city"],
+        "additionalProperties": false
+    })
+}
+
+#[test]
+fn accepts_matching_arguments() {
+    let args = json!({ "city": "Pune", "units": "metric", "days": 3 });
+    assert_eq!(validate_tool_args(&weather_schema(), &args), Ok(()));
+}
+
+#[test]
+fn reports_the_first_mismatch() {
+    let schema = weather_schema();
+    let missing = validate_tool_args(&schema, &json!({ "units": "metric" })).unwrap_err();
+    assert!(missing.contains("missing required field \"city\""), "{}", missing);
+
+    let wrong_type = validate_tool_args(&schema, &json!({ "city": 42 })).unwrap_err();
+    assert_eq!(wrong_type, "arguments.city should be string, got number");
+
+    let bad_enum = validate_tool_args(&schema, &json!({ "city": "Pune", "units": "kelvin" })).unwrap_err();
+    assert!(bad_enum.contains("arguments.units should be one of"), "{}", bad_enum);
+
+    let extra = validate_tool_args(&schema, &json!({ "city": "Pune", "zip": "411001" })).unwrap_err();
+    assert!(extra.contains("unknown field \"zip\""), "{}", extra);
+
+    let not_object = validate_tool_args(&schema, &json!("Pune")).unwrap_err();
+    assert_eq!(not_object, "arguments should be object, got string");
+}
+
+#[test]
+fn checks_array_items_and_ignores_missing_schema() {
+    let schema = json!({ "type": "object", "properties": { "tags": { "type": "array", "items": { "type": "string" } } } });
+    assert!(validate_tool_args(&schema, &json!({ "tags": ["a", "b"] })).is_ok());
+    let err = validate_tool_args(&schema, &json!({ "tags": ["a", 1] })).unwrap_err();
+    assert_eq!(err, "arguments.tags[1] should be string, got number");
+    assert!(validate_tool_args(&serde_json::Value::Null, &json!({ "anything": true })).is_ok());
+}
+
+#[test]
+fn reads_tool_specs_from_tools_list_entries() {
+    let tool = McpTool::from_value(&json!({
+        "name": "fetch-weather",
+        "description": "Current weather for a city",
+        "inputSchema": weather_schema()
+    }));
+    assert_eq!(tool.name, "fetch-weather");
+    assert_eq!(tool.description, "Current weather for a city");
+    assert_eq!(tool.input_schema, weather_schema());
+
+    let bare = McpTool::from_value(&json!({ "name": "ping" }));
+    assert_eq!(bare.description, "");
+    assert!(bare.input_schema.is_null());
+}
@@ -0,0 +1,47 @@
+use rag::{annotate_code_checks, check_answer_code, check_snippet, extract_snippets, CodeSnippet, Config, SnippetCheck};
+
+const ANSWER: &str = "Use a loop:\n\n```rust\nfor i in 0..3 {\n    println!(\"{}\", i);\n}\n```\n\n```toml\nname = \"x\"\n```\n\n```py\ndef f(:\n    pass\n```\n";
+
+#[test]
+fn only_rust_and_python_blocks_are_extracted() {
+    let snippets = extract_snippets(ANSWER);
+    assert_eq!(snippets.len(), 2);
+    assert_eq!(snippets[0].language, "rust");
+    assert!(snippets[0].code.starts_with("for i in 0..3"));
+    assert_eq!(snippets[1].language, "python");
+    assert_eq!(snippets[1].code, "def f(:\n    pass");
+}
+
+#[test]
+fn checks_are_listed_under_the_answer() {
+    let checks = vec![
+        SnippetCheck { index: 1, language: "rust", result: Ok(()) },
+        SnippetCheck { index: 2, language: "python", result: Err("SyntaxError: invalid syntax (line 1)".to_string()) },
+    ];
+    let annotated = annotate_code_checks("The answer.\n".to_string(), &checks);
+    assert_eq!(
+        annotated,
+        "The answer.\n\nCode check:\n- rust snippet 1: snippet compiles\n- python snippet 2: SyntaxError: invalid syntax (line 1)"
+    );
+    assert_eq!(annotate_code_checks("Plain.".to_string(), &[]), "Plain.");
+}
+
+#[test]
+fn checking_is_off_unless_enabled() {
+    let mut cfg = Config::from_env();
+    cfg.verify_code = false;
+    assert!(check_answer_code(&cfg, ANSWER).is_empty());
+}
+
+#[test]
+fn rust_statements_parse_and_broken_rust_reports_its_line() {
+    let statements = CodeSnippet { language: "rust", code: "let x = 1;\nprintln!(\"{}\", x);".to_string() };
+    // Skipped where rustfmt is not installed.
+    let Some(result) = check_snippet(&statements) else {
+        return;
+    };
+    assert_eq!(result, Ok(()));
+    let broken = CodeSnippet { language: "rust", code: "let a = 1;\nlet = 2;".to_string() };
+    let err = check_snippet(&broken).unwrap().unwrap_err();
+    assert!(err.ends_with("(line 2)"), "{}", err);
+}
@@ -0,0 +1,58 @@
+use rag::{answer_html, parse_viewers, plain_text, viewer_command, write_view_file, Config, Viewer, ViewerKind};
+
+const ANSWER: &str = "# Result\n\nUse **`x < y`** here, see [docs](https://example.com/?a&b).\n\n- one\n- two\n\n\
+```rust\nfn main() {}\n```\n";
+
+#[test]
+fn parses_named_viewers_of_each_kind() {
+    let viewers = parse_viewers("less=pager:less -R; web=html:; say=speak:say -f {file}; bad=other:x; nokind");
+    assert_eq!(viewers.len(), 3);
+    assert_eq!(viewers[0].kind, ViewerKind::Pager);
+    assert_eq!(viewers[0].command, "less -R");
+    assert_eq!(viewers[1].kind, ViewerKind::Html);
+    assert!(viewers[1].command.is_empty());
+    assert_eq!(viewers[2].name, "say");
+}
+
+#[test]
+fn html_escapes_text_and_renders_blocks() {
+    let html = answer_html("a <b>", ANSWER);
+    assert!(html.contains("<title>a &lt;b&gt;</title>"));
+    assert!(html.contains("<h1>Result</h1>"));
+    assert!(html.contains("<strong><code>x &lt; y</code></strong>"));
+    assert!(html.contains("<a href=\"https://example.com/?a&amp;b\">docs</a>"));
+    assert!(html.contains("<ul>\n<li>one</li>\n<li>two</li>\n</ul>"));
+    assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}</code></pre>"));
+}
+
+#[test]
+fn plain_text_drops_markdown_syntax() {
+    assert_eq!(
+        plain_text(ANSWER),
+        "Result\n\nUse x < y here, see docs.\n\none\ntwo\n\nfn main() {}\n"
+    );
+    assert_eq!(plain_text("| a | b |\n|---|:-:|\n| 1 | 2 |"), "a, b\n1, 2");
+}
+
+#[test]
+fn view_files_match_the_viewer_kind() {
+    let viewer = Viewer {
+        name: "say".to_string(),
+        kind: ViewerKind::Speak,
+        command: "say -f {file}".to_string(),
+    };
+    let path = write_view_file(&viewer, "title", ANSWER).unwrap();
+    assert_eq!(path.extension().unwrap(), "txt");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), plain_text(ANSWER));
+
+    let mut cfg = Config::from_env();
+    cfg.safe_mode = false;
+    let command = viewer_command(&cfg, &viewer, &path).unwrap();
+    let line = command.get_args().last().unwrap().to_string_lossy().to_string();
+    assert!(line.starts_with("say -f "));
+    assert!(line.contains(&path.to_string_lossy().to_string()));
+    let _ = std::fs::remove_file(&path);
+
+    cfg.safe_mode = true;
+    assert!(viewer_command(&cfg, &viewer, &path).is_err());
+}
@@ -0,0 +1,96 @@
+use rag::{anchor_for_chunk, html_to_text, page_headings, resolve_url, Heading, Payload};
+use serde_json::json;
+
+fn heading(id: &str, text: &str) -> Heading {
+    Heading { id: id.to_string(), text: text.to_string() }
+}
+
+#[test]
+fn collects_headings_with_an_id() {
+    let html = r#"<html><head><title>Guide</title></head><body>
+        <h1 id="intro">Intro</h1><p>Welcome.</p>
+        <H2 ID='setup' class="x">Set <em>up</em></H2><p>Install it.</p>
+        <h2 data-id="nope">No id</h2>
+        <h3 class=a id=usage>Usage</h3><p>Run it.</p>
+        <h2 id="">Empty</h2>
+        <h4 hidden id = "spaced" >Spaced</h4>
+    </body></html>"#;
+    assert_eq!(
+        page_headings(html),
+        vec![
+            heading("intro", "Intro"),
+            heading("setup", "Set up"),
+            heading("usage", "Usage"),
+            heading("spaced", "Spaced"),
+        ]
+    );
+}
+
+#[test]
+fn an_unclosed_heading_ends_the_scan() {
+    assert_eq!(page_headings(r#"<h1 id="a">A</h1><h2 id="b">B"#), vec![heading("a", "A")]);
+}
+
+#[test]
+fn anchors_follow_the_chunk_offset_not_its_text() {
+    // The same sentence sits under both headings; only the offset tells
+    // which copy a chunk starts at.
+    let page = "Intro\nSee the notes.\nUsage\nSee the notes.";
+    let headings = [heading("intro", "Intro"), heading("usage", "Usage")];
+    let second = page.rfind("See the notes.").unwrap();
+    assert_eq!(anchor_for_chunk(page, &headings, second).as_deref(), Some("usage"));
+    assert_eq!(anchor_for_chunk(page, &headings, 6).as_deref(), Some("intro"));
+    assert_eq!(anchor_for_chunk(page, &headings, page.find("Usage").unwrap()).as_deref(), Some("usage"));
+}
+
+#[test]
+fn text_before_the_first_heading_has_no_anchor() {
+    let page = "Preface.\nIntro\nBody.";
+    let headings = [heading("missing", "Not on the page"), heading("intro", "Intro")];
+    assert_eq!(anchor_for_chunk(page, &headings, 0), None);
+    assert_eq!(anchor_for_chunk(page, &headings, page.len()).as_deref(), Some("intro"));
+    assert_eq!(anchor_for_chunk(page, &[], page.len()), None);
+}
+
+#[test]
+fn citations_prefer_the_url_and_its_anchor() {
+    let payload = |value: serde_json::Value| -> Payload { serde_json::from_value(value).expect("payload") };
+    let page = "https://example.com/guide";
+    assert_eq!(
+        payload(json!({ "path": page, "url": page, "anchor": "usage" })).citation().as_deref(),
+        Some("https://example.com/guide#usage")
+    );
+    assert_eq!(payload(json!({ "path": page, "url": page })).citation().as_deref(), Some(page));
+    assert_eq!(payload(json!({ "path": "src/lib.rs", "anchor": "usage" })).citation().as_deref(), Some("src/lib.rs"));
+    assert_eq!(payload(json!({})).citation(), None);
+}
+
+#[test]
+fn scripts_styles_and_page_chrome_are_not_text() {
+    let html = r#"<html><head><style>body { color: red }</style>
+        <script type="text/javascript">var x = "<p>hidden</p>";</script></head>
+        <body><nav>Menu</nav><SCRIPT>alert(1)</SCRIPT><p>Fish &amp; chips</p><noscript>Enable JS</noscript>
+        <div>Second   line</div><navbar-item>kept</navbar-item></body></html>"#;
+    assert_eq!(html_to_text(html), "Fish & chips\nSecond line\nkept");
+}
+
+#[test]
+fn relative_links_resolve_against_the_page() {
+    let base = "https://docs.example.com/guide/intro.html#top";
+    let resolve = |href| resolve_url(base, href);
+    assert_eq!(resolve("setup.html").as_deref(), Some("https://docs.example.com/guide/setup.html"));
+    assert_eq!(resolve("/api/index.html#x").as_deref(), Some("https://docs.example.com/api/index.html"));
+    assert_eq!(resolve("//cdn.example.com/a.js").as_deref(), Some("https://cdn.example.com/a.js"));
+    assert_eq!(resolve(" https://other.org/page#frag ").as_deref(), Some("https://other.org/page"));
+    assert_eq!(resolve("a&amp;b.html").as_deref(), Some("https://docs.example.com/guide/a&b.html"));
+    assert_eq!(resolve_url("https://example.com", "docs.html").as_deref(), Some("https://example.com/docs.html"));
+    assert_eq!(resolve_url("https://example.com/", "docs.html").as_deref(), Some("https://example.com/docs.html"));
+}
+
+#[test]
+fn fragments_and_non_web_links_are_not_followed() {
+    let base = "https://docs.example.com/guide/";
+    for href in ["", "#top", "mailto:docs@example.com", "JavaScript:void(0)", "tel:+15550100"] {
+        assert_eq!(resolve_url(base, href), None, "{}", href);
+    }
+}
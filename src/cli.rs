@@ -0,0 +1,375 @@
+pub const USAGE: &str = "Usage: aicli [--safe] [--session <name>|--resume <name>] [--attach <file>]... [--git-range <from>..<to>]\n       aicli --template <name> [--var <name>=<value>]...\n       aicli sessions list\n       aicli sessions rename <name> <title>\n       aicli retrieve <question> [--top-k <n>] [--attach <file>]...\n       aicli usage\n       aicli access [--top <n>|clear]\n       aicli telemetry [export [<file>]|clear]\n       aicli stats\n       aicli paths\n       aicli collections [describe <name> <description>|remove <name>|route <question>]\n       aicli snapshot list\n       aicli snapshot create [<file>]\n       aicli snapshot restore <file> [--replace]\n       aicli index [<path|url>] [--depth <n>] [--recreate]\n       aicli index --git <repo-url> [--recreate]\n       aicli index --git-range <from>..<to> [<path>]\n       aicli index retry-failed\n       aicli index verify [--repair]\n       aicli index migrate --distance <Cosine|Dot|Euclid|Manhattan>\n       aicli mcp login|logout\n       aicli serve-mcp\n       aicli serve [--port <n>] [--host <address>]";
+
+/// Files listed by `aicli access` without `--top`.
+pub const DEFAULT_ACCESS_TOP: usize = 20;
+/// Where `aicli serve` listens without `--host` and `--port`: only this
+/// machine can reach it.
+pub const DEFAULT_SERVE_HOST: &str = "127.0.0.1";
+pub const DEFAULT_SERVE_PORT: u16 = 8080;
+
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub session: Option<String>,
+    /// `--resume`: `session` must already exist.
+    pub resume: bool,
+    pub safe: bool,
+    /// Files searched next to the collection for this run only.
+    pub attach: Vec<String>,
+    /// `--git-range`: work on the collection of files changed in a range.
+    pub git_range: Option<String>,
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliCommand {
+    SessionsList,
+    SessionsRename { name: String, title: String },
+    Usage,
+    AccessReport { top: usize },
+    AccessClear,
+    TelemetryShow,
+    TelemetryExport { path: Option<String> },
+    TelemetryClear,
+    Stats,
+    Paths,
+    CollectionsList,
+    CollectionsDescribe { name: String, description: String },
+    CollectionsRemove { name: String },
+    CollectionsRoute { question: String },
+    SnapshotList,
+    SnapshotCreate { path: Option<String> },
+    SnapshotRestore { path: String, replace: bool },
+    Retrieve { question: String, top_k: Option<usize> },
+    Index { source: Option<String>, depth: Option<usize>, git: Option<String>, recreate: bool },
+    IndexRetryFailed,
+    IndexVerify { repair: bool },
+    IndexMigrate { distance: String },
+    McpLogin,
+    McpLogout,
+    ServeMcp,
+    Serve { host: String, port: u16 },
+    Template { name: String, vars: Vec<(String, String)> },
+}
+
+impl CliCommand {
+    /// Name counted by opt-in telemetry; never includes arguments.
+    pub fn feature_name(&self) -> &'static str {
+        match self {
+            CliCommand::SessionsList => "cli.sessions.list",
+            CliCommand::SessionsRename { .. } => "cli.sessions.rename",
+            CliCommand::Usage => "cli.usage",
+            CliCommand::AccessReport { .. } | CliCommand::AccessClear => "cli.access",
+            CliCommand::TelemetryShow | CliCommand::TelemetryExport { .. } | CliCommand::TelemetryClear => {
+                "cli.telemetry"
+            }
+            CliCommand::Stats => "cli.stats",
+            CliCommand::Paths => "cli.paths",
+            CliCommand::CollectionsList
+            | CliCommand::CollectionsDescribe { .. }
+            | CliCommand::CollectionsRemove { .. }
+            | CliCommand::CollectionsRoute { .. } => "cli.collections",
+            CliCommand::SnapshotList => "cli.snapshot.list",
+            CliCommand::SnapshotCreate { .. } => "cli.snapshot.create",
+            CliCommand::SnapshotRestore { .. } => "cli.snapshot.restore",
+            CliCommand::Retrieve { .. } => "cli.retrieve",
+            CliCommand::Index { git: Some(_), .. } => "cli.index.git",
+            CliCommand::Index { .. } => "cli.index",
+            CliCommand::IndexRetryFailed => "cli.index.retry",
+            CliCommand::IndexVerify { .. } => "cli.index.verify",
+            CliCommand::IndexMigrate { .. } => "cli.index.migrate",
+            CliCommand::McpLogin | CliCommand::McpLogout => "cli.mcp.login",
+            CliCommand::ServeMcp => "cli.serve_mcp",
+            CliCommand::Serve { .. } => "cli.serve",
+            CliCommand::Template { .. } => "cli.template",
+        }
+    }
+
+    /// Whether the command can write to the index, so a read-only index is
+    /// worth detecting before it runs.
+    pub fn writes_index(&self) -> bool {
+        matches!(
+            self,
+            CliCommand::Index { .. }
+                | CliCommand::IndexRetryFailed
+                | CliCommand::IndexVerify { repair: true }
+                | CliCommand::IndexMigrate { .. }
+                | CliCommand::SnapshotRestore { .. }
+                | CliCommand::Serve { .. }
+        )
+    }
+}
+
+impl CliArgs {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut out = CliArgs::default();
+        let mut args = args.into_iter();
+        let mut template = None;
+        let mut vars = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--session" | "-s" => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| format!("--session requires a name\n{}", USAGE))?;
+                    out.session = Some(name);
+                }
+                "--resume" => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| format!("--resume requires a session name\n{}", USAGE))?;
+                    out.session = Some(name);
+                    out.resume = true;
+                }
+                "--safe" => out.safe = true,
+                "--git-range" => {
+                    let range = args
+                        .next()
+                        .ok_or_else(|| format!("--git-range requires a range like v1.2..HEAD\n{}", USAGE))?;
+                    out.git_range = Some(range);
+                }
+                "--template" => {
+                    let name = args
+                        .next()
+                        .ok_or_else(|| format!("--template requires a name\n{}", USAGE))?;
+                    template = Some(name);
+                }
+                "--var" => {
+                    let var = args
+                        .next()
+                        .ok_or_else(|| format!("--var requires name=value\n{}", USAGE))?;
+                    vars.push(rag::parse_template_var(&var).map_err(|e| format!("--var: {}\n{}", e, USAGE))?);
+                }
+                "--attach" => {
+                    let file = args
+                        .next()
+                        .ok_or_else(|| format!("--attach requires a file\n{}", USAGE))?;
+                    out.attach.push(file);
+                }
+                "sessions" => match args.next().as_deref() {
+                    Some("list") | None => out.command = Some(CliCommand::SessionsList),
+                    Some("rename") => {
+                        let name = args
+                            .next()
+                            .ok_or_else(|| format!("sessions rename requires a session name\n{}", USAGE))?;
+                        let title = args.by_ref().collect::<Vec<_>>().join(" ");
+                        if title.is_empty() {
+                            return Err(format!("sessions rename requires a title\n{}", USAGE));
+                        }
+                        out.command = Some(CliCommand::SessionsRename { name, title });
+                    }
+                    Some(other) => return Err(format!("Unknown sessions subcommand: {}\n{}", other, USAGE)),
+                },
+                "usage" => out.command = Some(CliCommand::Usage),
+                "access" => match args.next().as_deref() {
+                    None => out.command = Some(CliCommand::AccessReport { top: DEFAULT_ACCESS_TOP }),
+                    Some("--top") => {
+                        let top = args
+                            .next()
+                            .and_then(|v| v.parse().ok())
+                            .ok_or_else(|| format!("--top requires a number\n{}", USAGE))?;
+                        out.command = Some(CliCommand::AccessReport { top });
+                    }
+                    Some("clear") => out.command = Some(CliCommand::AccessClear),
+                    Some(other) => return Err(format!("Unknown access subcommand: {}\n{}", other, USAGE)),
+                },
+                "telemetry" => match args.next().as_deref() {
+                    None | Some("show") => out.command = Some(CliCommand::TelemetryShow),
+                    Some("export") => out.command = Some(CliCommand::TelemetryExport { path: args.next() }),
+                    Some("clear") => out.command = Some(CliCommand::TelemetryClear),
+                    Some(other) => return Err(format!("Unknown telemetry subcommand: {}\n{}", other, USAGE)),
+                },
+                "stats" => out.command = Some(CliCommand::Stats),
+                "paths" => out.command = Some(CliCommand::Paths),
+                "snapshot" => match args.next().as_deref() {
+                    Some("list") => out.command = Some(CliCommand::SnapshotList),
+                    Some("create") => {
+                        let path = args.next();
+                        out.command = Some(CliCommand::SnapshotCreate { path });
+                    }
+                    Some("restore") => {
+                        let mut path = None;
+                        let mut replace = false;
+                        for arg in args.by_ref() {
+                            match arg.as_str() {
+                                "--replace" => replace = true,
+                                flag if flag.starts_with("--") => {
+                                    return Err(format!("Unknown snapshot restore flag: {}\n{}", flag, USAGE));
+                                }
+                                _ => path = Some(arg),
+                            }
+                        }
+                        let path = path.ok_or_else(|| format!("snapshot restore requires a file\n{}", USAGE))?;
+                        out.command = Some(CliCommand::SnapshotRestore { path, replace });
+                    }
+                    other => {
+                        return Err(format!("Unknown snapshot subcommand: {}\n{}", other.unwrap_or(""), USAGE));
+                    }
+                },
+                "retrieve" => {
+                    let mut words = Vec::new();
+                    let mut top_k = None;
+                    while let Some(arg) = args.next() {
+                        if arg == "--top-k" {
+                            let value = args
+                                .next()
+                                .and_then(|v| v.parse().ok())
+                                .ok_or_else(|| format!("--top-k requires a number\n{}", USAGE))?;
+                            top_k = Some(value);
+                        } else if arg == "--attach" {
+                            let file = args
+                                .next()
+                                .ok_or_else(|| format!("--attach requires a file\n{}", USAGE))?;
+                            out.attach.push(file);
+                        } else {
+                            words.push(arg);
+                        }
+                    }
+                    if words.is_empty() {
+                        return Err(format!("retrieve requires a question\n{}", USAGE));
+                    }
+                    out.command = Some(CliCommand::Retrieve {
+                        question: words.join(" "),
+                        top_k,
+                    });
+                }
+                "index" => match args.next().as_deref() {
+                    Some("retry-failed") => out.command = Some(CliCommand::IndexRetryFailed),
+                    Some("verify") => {
+                        let mut repair = false;
+                        for flag in args.by_ref() {
+                            match flag.as_str() {
+                                "--repair" => repair = true,
+                                other => return Err(format!("Unknown index verify flag: {}\n{}", other, USAGE)),
+                            }
+                        }
+                        out.command = Some(CliCommand::IndexVerify { repair });
+                    }
+                    Some("migrate") => {
+                        let mut distance = None;
+                        while let Some(flag) = args.next() {
+                            match flag.as_str() {
+                                "--distance" => {
+                                    let value = args
+                                        .next()
+                                        .ok_or_else(|| format!("--distance requires a metric\n{}", USAGE))?;
+                                    distance = Some(value);
+                                }
+                                other => return Err(format!("Unknown index migrate flag: {}\n{}", other, USAGE)),
+                            }
+                        }
+                        let distance = distance.ok_or_else(|| format!("index migrate requires --distance\n{}", USAGE))?;
+                        out.command = Some(CliCommand::IndexMigrate { distance });
+                    }
+                    first => {
+                        let mut source = None;
+                        let mut depth = None;
+                        let mut git = None;
+                        let mut recreate = false;
+                        let mut pending = first.map(|s| s.to_string());
+                        while let Some(arg) = pending.take().or_else(|| args.next()) {
+                            match arg.as_str() {
+                                "--depth" => {
+                                    let value = args
+                                        .next()
+                                        .and_then(|v| v.parse().ok())
+                                        .ok_or_else(|| format!("--depth requires a number\n{}", USAGE))?;
+                                    depth = Some(value);
+                                }
+                                "--git" => {
+                                    let url = args
+                                        .next()
+                                        .ok_or_else(|| format!("--git requires a repository URL\n{}", USAGE))?;
+                                    git = Some(url);
+                                }
+                                "--recreate" => recreate = true,
+                                "--git-range" => {
+                                    let range = args.next().ok_or_else(|| {
+                                        format!("--git-range requires a range like v1.2..HEAD\n{}", USAGE)
+                                    })?;
+                                    out.git_range = Some(range);
+                                }
+                                flag if flag.starts_with("--") => {
+                                    return Err(format!("Unknown index flag: {}\n{}", flag, USAGE));
+                                }
+                                _ => source = Some(arg),
+                            }
+                        }
+                        out.command = Some(CliCommand::Index { source, depth, git, recreate });
+                    }
+                },
+                "mcp" => match args.next().as_deref() {
+                    Some("login") => out.command = Some(CliCommand::McpLogin),
+                    Some("logout") => out.command = Some(CliCommand::McpLogout),
+                    other => return Err(format!("Unknown mcp subcommand: {}\n{}", other.unwrap_or(""), USAGE)),
+                },
+                "serve-mcp" => out.command = Some(CliCommand::ServeMcp),
+                "serve" => {
+                    let mut host = DEFAULT_SERVE_HOST.to_string();
+                    let mut port = DEFAULT_SERVE_PORT;
+                    while let Some(flag) = args.next() {
+                        match flag.as_str() {
+                            "--port" => {
+                                port = args
+                                    .next()
+                                    .and_then(|v| v.parse().ok())
+                                    .ok_or_else(|| format!("--port requires a port number\n{}", USAGE))?;
+                            }
+                            "--host" => {
+                                host = args
+                                    .next()
+                                    .ok_or_else(|| format!("--host requires an address\n{}", USAGE))?;
+                            }
+                            other => return Err(format!("Unknown serve flag: {}\n{}", other, USAGE)),
+                        }
+                    }
+                    out.command = Some(CliCommand::Serve { host, port });
+                }
+                "collections" => match args.next().as_deref() {
+                    None | Some("list") => out.command = Some(CliCommand::CollectionsList),
+                    Some("describe") => {
+                        let name = args
+                            .next()
+                            .ok_or_else(|| format!("collections describe requires a collection name\n{}", USAGE))?;
+                        let description = args.by_ref().collect::<Vec<_>>().join(" ");
+                        if description.trim().is_empty() {
+                            return Err(format!("collections describe requires a description\n{}", USAGE));
+                        }
+                        out.command = Some(CliCommand::CollectionsDescribe { name, description });
+                    }
+                    Some("remove") => {
+                        let name = args
+                            .next()
+                            .ok_or_else(|| format!("collections remove requires a collection name\n{}", USAGE))?;
+                        out.command = Some(CliCommand::CollectionsRemove { name });
+                    }
+                    Some("route") => {
+                        let question = args.by_ref().collect::<Vec<_>>().join(" ");
+                        if question.trim().is_empty() {
+                            return Err(format!("collections route requires a question\n{}", USAGE));
+                        }
+                        out.command = Some(CliCommand::CollectionsRoute { question });
+                    }
+                    Some(other) => return Err(format!("Unknown collections subcommand: {}\n{}", other, USAGE)),
+                },
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other => {
+                    if let Some(name) = other.strip_prefix("--session=") {
+                        out.session = Some(name.to_string());
+                    } else {
+                        return Err(format!("Unknown argument: {}\n{}", other, USAGE));
+                    }
+                }
+            }
+        }
+        if out.git_range.is_some() && matches!(out.command, Some(CliCommand::Index { git: Some(_), .. })) {
+            return Err(format!("--git-range works on a local checkout, not with --git\n{}", USAGE));
+        }
+        match template {
+            Some(_) if out.command.is_some() => return Err(format!("--template runs on its own\n{}", USAGE)),
+            Some(name) => out.command = Some(CliCommand::Template { name, vars }),
+            None if !vars.is_empty() => return Err(format!("--var needs --template\n{}", USAGE)),
+            None => {}
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,227 @@
+// Layout and cursor movement for the prompt, in terms of graphemes and the
+// terminal columns they fill rather than bytes or chars.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// The input laid out in rows at most `width` terminal columns wide, broken
+// at newlines and between graphemes, so a wide CJK character or an emoji
+// never straddles two rows: each row's starting byte offset and its text. A
+// full last row is followed by an empty one, where the cursor goes next.
+pub fn input_rows(input: &str, width: usize) -> Vec<(usize, &str)> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut line_start = 0;
+    for line in input.split('\n') {
+        let mut row_start = line_start;
+        let mut used = 0;
+        for (i, grapheme) in line.grapheme_indices(true) {
+            let cells = grapheme.width();
+            if used > 0 && used + cells > width {
+                rows.push((row_start, &input[row_start..line_start + i]));
+                row_start = line_start + i;
+                used = 0;
+            }
+            used += cells;
+        }
+        rows.push((row_start, &input[row_start..line_start + line.len()]));
+        if used >= width {
+            rows.push((line_start + line.len(), ""));
+        }
+        line_start += line.len() + 1;
+    }
+    rows
+}
+
+// The row and terminal column of the byte offset `cursor` in `rows`.
+pub fn cursor_position(rows: &[(usize, &str)], cursor: usize) -> (usize, usize) {
+    let row = rows.iter().rposition(|(start, _)| *start <= cursor).unwrap_or(0);
+    let (start, text) = rows[row];
+    let col = text.get(..cursor - start).unwrap_or(text).width();
+    (row, col)
+}
+
+// The byte offset in `row` of the grapheme under terminal column `col`, or
+// the row's end when it is shorter.
+pub fn column_offset(row: &str, col: usize) -> usize {
+    let mut used = 0;
+    for (i, grapheme) in row.grapheme_indices(true) {
+        used += grapheme.width();
+        if used > col {
+            return i;
+        }
+    }
+    row.len()
+}
+
+// The grapheme boundary before the byte offset `cursor`, or 0.
+pub fn prev_grapheme(text: &str, cursor: usize) -> usize {
+    text[..cursor].grapheme_indices(true).next_back().map(|(i, _)| i).unwrap_or(0)
+}
+
+// The grapheme boundary after the byte offset `cursor`, or `cursor` at the end.
+pub fn next_grapheme(text: &str, cursor: usize) -> usize {
+    text[cursor..].graphemes(true).next().map(|g| cursor + g.len()).unwrap_or(cursor)
+}
+
+// Letters, digits, and `_` make up words for Alt+B/F and Ctrl+Left/Right.
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().any(|c| c.is_alphanumeric() || c == '_')
+}
+
+// The start of the word before `cursor`, skipping anything else first.
+pub fn word_start(text: &str, cursor: usize) -> usize {
+    grapheme_start_where(text, cursor, is_word)
+}
+
+// The start of the run of non-space graphemes before `cursor` (Ctrl+W).
+pub fn space_word_start(text: &str, cursor: usize) -> usize {
+    grapheme_start_where(text, cursor, |g| !g.trim().is_empty())
+}
+
+// Walk back from `cursor` over graphemes that are not `in_word`, then over
+// those that are, and return where that run begins.
+fn grapheme_start_where(text: &str, cursor: usize, in_word: impl Fn(&str) -> bool) -> usize {
+    let mut start = 0;
+    for (i, grapheme) in text[..cursor].grapheme_indices(true).rev().skip_while(|(_, g)| !in_word(g)) {
+        if !in_word(grapheme) {
+            break;
+        }
+        start = i;
+    }
+    start
+}
+
+// The end of the word after `cursor`, skipping anything else first.
+pub fn word_end(text: &str, cursor: usize) -> usize {
+    let mut end = cursor;
+    let mut seen_word = false;
+    for (i, grapheme) in text[cursor..].grapheme_indices(true) {
+        match (is_word(grapheme), seen_word) {
+            (true, _) => seen_word = true,
+            (false, true) => break,
+            (false, false) => {}
+        }
+        end = cursor + i + grapheme.len();
+    }
+    end
+}
+
+pub fn line_start(text: &str, cursor: usize) -> usize {
+    text[..cursor].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+pub fn line_end(text: &str, cursor: usize) -> usize {
+    text[cursor..].find('\n').map(|i| cursor + i).unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_break_at_the_width_and_at_newlines() {
+        assert_eq!(input_rows("", 5), vec![(0, "")]);
+        assert_eq!(input_rows("hello world", 5), vec![(0, "hello"), (5, " worl"), (10, "d")]);
+        assert_eq!(input_rows("ab\ncd", 5), vec![(0, "ab"), (3, "cd")]);
+    }
+
+    #[test]
+    fn a_wide_grapheme_that_would_straddle_the_boundary_starts_the_next_row() {
+        assert_eq!(input_rows("ab中c", 3), vec![(0, "ab"), (2, "中c"), (6, "")]);
+        assert_eq!(input_rows("中中", 3), vec![(0, "中"), (3, "中")]);
+    }
+
+    #[test]
+    fn an_exactly_full_last_row_is_followed_by_an_empty_one() {
+        assert_eq!(input_rows("abc", 3), vec![(0, "abc"), (3, "")]);
+        assert_eq!(input_rows("a中", 3), vec![(0, "a中"), (4, "")]);
+        assert_eq!(input_rows("abc\nd", 3), vec![(0, "abc"), (3, ""), (4, "d")]);
+    }
+
+    #[test]
+    fn the_cursor_lands_on_the_row_that_starts_at_or_before_it() {
+        let rows = input_rows("abc", 3);
+        assert_eq!(cursor_position(&rows, 0), (0, 0));
+        assert_eq!(cursor_position(&rows, 2), (0, 2));
+        assert_eq!(cursor_position(&rows, 3), (1, 0));
+
+        let rows = input_rows("ab中c", 3);
+        assert_eq!(cursor_position(&rows, 2), (1, 0));
+        assert_eq!(cursor_position(&rows, 5), (1, 2));
+        assert_eq!(cursor_position(&rows, 6), (2, 0));
+    }
+
+    #[test]
+    fn column_offset_finds_the_grapheme_under_a_column() {
+        assert_eq!(column_offset("a中b", 0), 0);
+        assert_eq!(column_offset("a中b", 1), 1);
+        assert_eq!(column_offset("a中b", 2), 1);
+        assert_eq!(column_offset("a中b", 3), 4);
+        assert_eq!(column_offset("a中b", 10), 5);
+        assert_eq!(column_offset("", 3), 0);
+    }
+
+    #[test]
+    fn grapheme_steps_keep_combining_marks_and_flags_whole() {
+        let text = "e\u{301}🇫🇷x";
+        assert_eq!(next_grapheme(text, 0), 3);
+        assert_eq!(next_grapheme(text, 3), 11);
+        assert_eq!(next_grapheme(text, 12), 12);
+        assert_eq!(prev_grapheme(text, 12), 11);
+        assert_eq!(prev_grapheme(text, 11), 3);
+        assert_eq!(prev_grapheme(text, 3), 0);
+        assert_eq!(prev_grapheme(text, 0), 0);
+    }
+
+    #[test]
+    fn word_start_skips_punctuation_then_takes_the_word() {
+        assert_eq!(word_start("foo.bar", 7), 4);
+        assert_eq!(word_start("foo.bar", 4), 0);
+        assert_eq!(word_start("foo -- bar", 7), 0);
+        assert_eq!(word_start("snake_case", 10), 0);
+        assert_eq!(word_start("...", 3), 0);
+        assert_eq!(word_start("foo", 0), 0);
+    }
+
+    #[test]
+    fn word_end_skips_punctuation_then_takes_the_word() {
+        assert_eq!(word_end("foo.bar", 0), 3);
+        assert_eq!(word_end("foo.bar", 3), 7);
+        assert_eq!(word_end("foo -- bar", 3), 10);
+        assert_eq!(word_end("...", 0), 3);
+        assert_eq!(word_end("foo", 3), 3);
+        assert_eq!(word_end("cafe\u{301} au lait", 0), 6);
+    }
+
+    #[test]
+    fn space_word_start_keeps_punctuation_in_the_word() {
+        assert_eq!(space_word_start("git log --oneline", 17), 8);
+        assert_eq!(space_word_start("git log --oneline", 8), 4);
+        assert_eq!(space_word_start("foo.bar", 7), 0);
+        assert_eq!(space_word_start("foo  ", 5), 0);
+        assert_eq!(space_word_start("foo", 0), 0);
+    }
+
+    #[test]
+    fn grapheme_start_where_takes_the_last_matching_run() {
+        let is_x = |g: &str| g == "x";
+        assert_eq!(grapheme_start_where("axxbxx", 6, is_x), 4);
+        assert_eq!(grapheme_start_where("axxbxx", 4, is_x), 1);
+        assert_eq!(grapheme_start_where("axxb", 1, is_x), 0);
+    }
+
+    #[test]
+    fn line_bounds_stop_at_newlines() {
+        let text = "ab\ncd\nef";
+        assert_eq!(line_start(text, 0), 0);
+        assert_eq!(line_start(text, 2), 0);
+        assert_eq!(line_start(text, 3), 3);
+        assert_eq!(line_start(text, 8), 6);
+        assert_eq!(line_end(text, 0), 2);
+        assert_eq!(line_end(text, 2), 2);
+        assert_eq!(line_end(text, 4), 5);
+        assert_eq!(line_end(text, 8), 8);
+        assert_eq!(line_end("", 0), 0);
+    }
+}
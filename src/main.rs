@@ -1,34 +1,463 @@
+mod cli;
+mod input;
+
+use std::collections::BTreeMap;
 use std::io;
-use std::process::Command;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
-use crossterm::execute;
+use cli::{CliArgs, CliCommand};
+use input::{
+    column_offset, cursor_position, input_rows, line_end, line_start, next_grapheme, prev_grapheme, space_word_start,
+    word_end, word_start,
+};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+use crossterm::style::{Attribute, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::{execute, queue};
+use crossterm::event::{
+    DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use futures::StreamExt;
-use rag::{Config as RagConfig, answer_query};
+use rag::{
+    AgentMode, AgentOptions, AnswerResult, Config as RagConfig, Message, Session, Usage,
+    answer_query_detailed_with_events, list_sessions,
+};
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Constraint, Direction, Layout, Margin};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Direction, Layout, Margin, Rect};
 use ratatui::style::{Color, Modifier, Style};
-use ratatui::widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{
+    Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
+};
 use tokio::sync::mpsc;
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    let args = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+    let mut rag_cfg = RagConfig::from_env();
+    let legacy = rag::legacy_dir();
+    match rag::migrate_legacy_dir(&rag_cfg, &legacy) {
+        Ok(moved) if !moved.is_empty() => {
+            eprintln!("Moved {} entries from {}; `aicli paths` shows where they are now.", moved.len(), legacy.display());
+        }
+        Ok(_) => {}
+        Err(err) => eprintln!("Could not move {}: {}", legacy.display(), err),
+    }
+    if args.safe {
+        rag_cfg.safe_mode = true;
+    }
+    for file in &args.attach {
+        let path = App::resolve_path(file).to_string_lossy().to_string();
+        if !rag_cfg.attachments.contains(&path) {
+            rag_cfg.attachments.push(path);
+        }
+    }
+    if let Some(range) = &args.git_range {
+        if let Err(err) = rag::parse_git_range(range) {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+        rag_cfg.collection = rag::collection_for_git_range(&rag_cfg.collection, range);
+    }
+    if let Some(command) = &args.command {
+        rag::record_feature(&rag_cfg, command.feature_name());
+    }
+    // A central index shared with a read-only key: find out once, so
+    // indexing is refused up front and the status bar says so.
+    if !rag_cfg.qdrant_read_only && args.command.as_ref().is_none_or(CliCommand::writes_index) {
+        rag_cfg.qdrant_read_only = tokio::task::block_in_place(|| rag::store_read_only(&rag_cfg)).unwrap_or(false);
+    }
+
+    if args.command == Some(CliCommand::Paths) {
+        println!("config\t{}", rag_cfg.config_dir);
+        println!("data\t{}", rag_cfg.data_dir);
+        println!("cache\t{}", rag_cfg.cache_dir);
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::SessionsList) {
+        let sessions = list_sessions(&rag_cfg).map_err(io::Error::other)?;
+        if sessions.is_empty() {
+            println!("No saved sessions.");
+        }
+        for session in sessions {
+            println!(
+                "{}\t{}\t{} turns\t{}",
+                session.name,
+                session.title.as_deref().unwrap_or("(untitled)"),
+                session.conversation.len() / 2,
+                rag::format_local(&rag_cfg, session.updated_at)
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::SessionsRename { name, title }) = &args.command {
+        let mut session = Session::load(&rag_cfg, name)
+            .map_err(io::Error::other)?
+            .ok_or_else(|| io::Error::other(format!("No session named {}", name)))?;
+        session.title = Some(rag::clean_title(title));
+        session.save(&rag_cfg).map_err(io::Error::other)?;
+        println!("{}\t{}", session.name, session.title.as_deref().unwrap_or_default());
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::TelemetryShow) {
+        let log = rag::telemetry_log(&rag_cfg).map_err(io::Error::other)?;
+        println!(
+            "Telemetry is {} (AICLI_TELEMETRY). Counts are stored in {} and never sent anywhere.",
+            if rag_cfg.telemetry { "on" } else { "off" },
+            rag::telemetry_path(&rag_cfg).display()
+        );
+        for (feature, count) in &log.features {
+            println!("feature\t{}\t{}", feature, count);
+        }
+        for (error, count) in &log.errors {
+            println!("error\t{}\t{}", error, count);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::TelemetryExport { path }) = &args.command {
+        let path = rag::export_telemetry(&rag_cfg, path.as_deref()).map_err(io::Error::other)?;
+        println!("Wrote telemetry report to {}", path.display());
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::TelemetryClear) {
+        rag::clear_telemetry(&rag_cfg).map_err(io::Error::other)?;
+        println!("Telemetry counts cleared.");
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::Usage) {
+        let days = rag::daily_usage(&rag_cfg).map_err(io::Error::other)?;
+        if days.is_empty() {
+            println!("No usage recorded.");
+        }
+        for (day, usage) in days {
+            println!(
+                "{}\t{} calls\t{} tokens\t${:.4}",
+                day,
+                usage.calls,
+                usage.total_tokens(),
+                usage.cost_usd
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::AccessReport { top }) = args.command {
+        if !rag_cfg.access_log {
+            println!("Access logging is off (RAG_ACCESS_LOG); showing what was recorded while it was on.");
+        }
+        for line in rag::access_report(&rag_cfg, top).map_err(io::Error::other)? {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::AccessClear) {
+        rag::clear_access_log(&rag_cfg).map_err(io::Error::other)?;
+        println!("Access log for {} cleared.", rag_cfg.collection);
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::Stats) {
+        let stats = tokio::task::block_in_place(|| rag::collection_stats(&rag_cfg)).map_err(io::Error::other)?;
+        println!("{}", stats);
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::CollectionsList) {
+        let collections = rag::collection_descriptions(&rag_cfg);
+        if collections.is_empty() {
+            println!("No described collections. Add one with `aicli collections describe <name> <description>`.");
+        }
+        for (name, described) in collections {
+            println!(
+                "{}\t{}\t{}\t{}",
+                name,
+                described.embed_model,
+                rag::format_local(&rag_cfg, described.described_at),
+                described.description
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::CollectionsDescribe { name, description }) = &args.command {
+        tokio::task::block_in_place(|| rag::describe_collection(&rag_cfg, name, description))
+            .map_err(io::Error::other)?;
+        println!("Described {}; questions can now be routed to it.", name);
+        return Ok(());
+    }
+
+    if let Some(CliCommand::CollectionsRemove { name }) = &args.command {
+        if rag::remove_collection_description(&rag_cfg, name).map_err(io::Error::other)? {
+            println!("Removed the description of {}.", name);
+        } else {
+            println!("{} has no description.", name);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::CollectionsRoute { question }) = &args.command {
+        let route = tokio::task::block_in_place(|| rag::route_question(&rag_cfg, question)).map_err(io::Error::other)?;
+        for (name, score) in &route.scores {
+            println!("{}\t{:.3}", name, score);
+        }
+        match route.chosen {
+            Some(name) => println!("Routed to {}.", name),
+            None => println!("No clear match; the TUI would ask which collection to use."),
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Retrieve { question, top_k }) = &args.command {
+        let options = rag::RetrieveOptions {
+            top_k: *top_k,
+            ..Default::default()
+        };
+        let hits = tokio::task::block_in_place(|| rag::retrieve(&rag_cfg, question, &options))
+            .map_err(io::Error::other)?;
+        println!("{}", rag::format_hit_list(&hits));
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Index { source, depth, git, recreate }) = &args.command {
+        if *recreate {
+            rag_cfg.recreate_collection = true;
+        }
+        if let Some(url) = git {
+            let (collection, stats) = tokio::task::block_in_place(|| rag::index_git_repo(&rag_cfg, url))
+                .map_err(io::Error::other)?;
+            println!("Indexed {} into {}", url, collection);
+            for line in stats.summary() {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        if let Some(range) = &args.git_range {
+            let stats = rag::index_git_range_async(&rag_cfg, source.as_deref(), range)
+                .await
+                .map_err(io::Error::other)?;
+            println!("Indexed {} changed files in {} into {}", stats.indexed, range, rag_cfg.collection);
+            for line in stats.summary() {
+                println!("{}", line);
+            }
+            println!("Ask about just these changes with: aicli --git-range {}", range);
+            return Ok(());
+        }
+        let mut cfg = rag_cfg.clone();
+        if let Some(depth) = depth {
+            cfg.crawl_depth = *depth;
+        }
+        let stats = rag::index_corpus_async(&cfg, source.as_deref())
+            .await
+            .map_err(io::Error::other)?;
+        println!("Indexed {} into {}", source.as_deref().unwrap_or(&cfg.source_dir), cfg.collection);
+        for line in stats.summary() {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::IndexRetryFailed) = args.command {
+        let stats = rag::retry_quarantined_async(&rag_cfg).await.map_err(io::Error::other)?;
+        println!("Re-indexed {} quarantined files into {}", stats.indexed, rag_cfg.collection);
+        for line in stats.summary() {
+            println!("{}", line);
+        }
+        for path in &stats.quarantined {
+            println!("still failing: {}", path);
+        }
+        if !stats.quarantined.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::IndexVerify { repair }) = args.command {
+        if repair && rag_cfg.safe_mode {
+            eprintln!("Safe mode is on: --repair is disabled.");
+            std::process::exit(2);
+        }
+        let report = tokio::task::block_in_place(|| rag::verify_index(&rag_cfg, repair))
+            .map_err(io::Error::other)?;
+        println!("Checked {} points in {}", report.points_checked, rag_cfg.collection);
+        for issue in &report.issues {
+            println!("issue: {}", issue);
+        }
+        for action in &report.repaired {
+            println!("repaired: {}", action);
+        }
+        if report.is_healthy() {
+            println!("Index is healthy.");
+        } else if !repair {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::SnapshotList) {
+        let snapshots = rag::list_snapshots(&rag_cfg);
+        if snapshots.is_empty() {
+            println!("No snapshots of {} in {}", rag_cfg.collection, rag::snapshots_dir(&rag_cfg).display());
+        }
+        for snapshot in snapshots {
+            println!("{}  {}", rag::format_local(&rag_cfg, snapshot.created_at), snapshot.path.display());
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::SnapshotCreate { path }) = &args.command {
+        let summary = tokio::task::block_in_place(|| rag::create_snapshot(&rag_cfg, path.as_deref()))
+            .map_err(io::Error::other)?;
+        match summary.points {
+            Some(points) => println!("Saved {} points of {} to {}", points, rag_cfg.collection, summary.path.display()),
+            None => println!("Saved Qdrant snapshot of {} to {}", rag_cfg.collection, summary.path.display()),
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::SnapshotRestore { path, replace }) = &args.command {
+        let summary = tokio::task::block_in_place(|| rag::restore_snapshot(&rag_cfg, path, *replace))
+            .map_err(io::Error::other)?;
+        match summary.points {
+            Some(points) => println!("Restored {} points into {}", points, rag_cfg.collection),
+            None => println!("Restored Qdrant snapshot into {}", rag_cfg.collection),
+        }
+        return Ok(());
+    }
+
+    if matches!(args.command, Some(CliCommand::McpLogin | CliCommand::McpLogout)) {
+        let rag::McpAuth::Device(device) = &rag_cfg.mcp_auth else {
+            eprintln!("Set MCP_OAUTH_DEVICE_URL, MCP_OAUTH_TOKEN_URL, and MCP_OAUTH_CLIENT_ID to sign in.");
+            std::process::exit(2);
+        };
+        if args.command == Some(CliCommand::McpLogout) {
+            let removed = rag::remove_cached_token(&rag_cfg.data_dir, device).map_err(io::Error::other)?;
+            println!("{}", if removed { "Signed out of the MCP server." } else { "Not signed in." });
+            return Ok(());
+        }
+        let opts = rag::HttpOptions::standard(&rag_cfg);
+        let code = tokio::task::block_in_place(|| rag::start_device_login(device, &opts)).map_err(io::Error::other)?;
+        match &code.verification_uri_complete {
+            Some(uri) => println!("Open {} to approve aicli (code {}).", uri, code.user_code),
+            None => println!("Open {} and enter the code {}.", code.verification_uri, code.user_code),
+        }
+        println!("Waiting for approval...");
+        let path = tokio::task::block_in_place(|| rag::finish_device_login(device, &code, &rag_cfg.data_dir, &opts))
+            .map_err(io::Error::other)?;
+        println!("Signed in. Token saved to {}", path.display());
+        return Ok(());
+    }
+
+    if args.command == Some(CliCommand::ServeMcp) {
+        // Stdout carries the protocol; anything else goes to stderr.
+        let stdin = io::stdin();
+        tokio::task::block_in_place(|| rag::serve_mcp(&rag_cfg, stdin.lock(), io::stdout()))
+            .map_err(io::Error::other)?;
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Serve { host, port }) = &args.command {
+        let addr = format!("{}:{}", host, port);
+        eprintln!("Serving {} on http://{} (Ctrl+C to stop)", rag_cfg.collection, addr);
+        tokio::task::block_in_place(|| rag::serve_rest(&rag_cfg, &addr)).map_err(io::Error::other)?;
+        return Ok(());
+    }
+
+    if let Some(CliCommand::Template { name, vars }) = &args.command {
+        let templates = rag::load_templates(&rag_cfg).map_err(io::Error::other)?;
+        let Some(text) = templates.get(name) else {
+            eprintln!("Unknown template: {} (see {})", name, rag::templates_path(&rag_cfg).display());
+            std::process::exit(2);
+        };
+        let values = vars.iter().cloned().collect();
+        let question = match rag::fill_template(text, &values) {
+            Ok(question) => question,
+            Err(err) => {
+                eprintln!("Template {}: {}", name, err);
+                std::process::exit(2);
+            }
+        };
+        let result = tokio::task::block_in_place(|| rag::answer_query_detailed(&rag_cfg, &[], &question))
+            .map_err(io::Error::other)?;
+        println!("{}", result.answer);
+        let sources = result.sources_text();
+        if !sources.is_empty() {
+            println!("\nSources:\n{}", sources);
+        }
+        return Ok(());
+    }
+
+    if let Some(CliCommand::IndexMigrate { distance }) = &args.command {
+        let migration = tokio::task::block_in_place(|| rag::migrate_distance(&rag_cfg, distance))
+            .map_err(io::Error::other)?;
+        if migration.points == 0 && migration.from == migration.to {
+            println!("{} already uses {} distance.", rag_cfg.collection, migration.to);
+        } else {
+            println!(
+                "Rebuilt {} with {} distance (was {}), {} points copied.",
+                rag_cfg.collection, migration.to, migration.from, migration.points
+            );
+            if !migration.to.eq_ignore_ascii_case(&rag_cfg.distance) {
+                println!("Set QDRANT_DISTANCE={} so queries use the new metric.", migration.to);
+            }
+        }
+        return Ok(());
+    }
+
+    let session = match &args.session {
+        Some(name) if args.resume => Some(
+            Session::load(&rag_cfg, name)
+                .map_err(io::Error::other)?
+                .ok_or_else(|| io::Error::other(format!("No session named {}; see `aicli sessions list`", name)))?,
+        ),
+        Some(name) => Some(Session::load_or_new(&rag_cfg, name).map_err(io::Error::other)?),
+        None => None,
+    };
+    // A metric mismatch makes every score meaningless; say so up front
+    // rather than on the first query.
+    let distance_warning = tokio::task::block_in_place(|| rag::check_distance(&rag_cfg)).err();
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
+    enter_editing_modes(&mut stdout)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let rag_cfg = Arc::new(RagConfig::from_env());
-    let mut app = App::new(rag_cfg);
+    let mut app = App::new(rag_cfg, session);
+    if let Some(warning) = distance_warning {
+        app.rag_context = Some(format!("Warning: {}", warning));
+    }
+    // Warm the per-model context window cache so the first query doesn't pay for it.
+    let warm_cfg = app.rag_cfg.clone();
+    tokio::task::spawn_blocking(move || {
+        let _ = rag::context_window(&warm_cfg);
+    });
     let res = run_app(&mut terminal, &mut app).await;
+    // Keep the panes, scrolling, and collection for `--resume`.
+    app.save_session();
 
+    leave_editing_modes(terminal.backend_mut())?;
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
@@ -36,6 +465,24 @@ async fn main() -> io::Result<()> {
     res
 }
 
+// Bracketed paste, so a pasted newline is text rather than Enter, and, where
+// the terminal supports it, key disambiguation so Shift+Enter and Ctrl+Enter
+// are told apart from Enter.
+fn enter_editing_modes(out: &mut impl io::Write) -> io::Result<()> {
+    execute!(out, EnableBracketedPaste)?;
+    if crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
+    Ok(())
+}
+
+fn leave_editing_modes(out: &mut impl io::Write) -> io::Result<()> {
+    if crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        execute!(out, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(out, DisableBracketedPaste)
+}
+
 struct App {
     input: String,
     cursor: usize,
@@ -56,6 +503,112 @@ struct App {
     answer_auto_scroll: bool,
     is_loading: bool,
     spinner_idx: usize,
+    session: Option<Session>,
+    pinned: Vec<String>,
+    session_picker: Option<SessionPicker>,
+    // `AICLI_HISTORY_SIZE`: earlier prompts, the one Alt+Up/Down shows
+    // (`None` while editing the draft), the unsent draft, and the Ctrl+R
+    // search while it is open.
+    history: rag::PromptHistory,
+    history_pos: Option<usize>,
+    history_draft: String,
+    history_search: Option<HistorySearch>,
+    // Width of the input box's text area at the last draw, for moving the
+    // cursor between wrapped rows.
+    input_width: usize,
+    // The prompt `AICLI_PROMPT_LINT=hint` last warned about; Enter on it
+    // again sends it anyway.
+    lint_warned: Option<String>,
+    // Clipboard text pinned by `/paste-context` or Ctrl+V for the next
+    // question only.
+    pasted_context: Option<String>,
+    last_usage: Option<Usage>,
+    // When the last answer arrived, in Unix seconds.
+    last_answer_at: u64,
+    // Running total of the query in flight, from its agent events.
+    live_usage: Option<Usage>,
+    context_allocation: Option<rag::ContextAllocation>,
+    // Everything used since aicli started, for runs without a named session.
+    run_usage: Usage,
+    today_usage: Usage,
+    last_answer_meta: Option<String>,
+    answer_details: Option<rag::FinalDetails>,
+    agent_trace: Vec<String>,
+    // Steps of the last answer's `plan` strategy plan.
+    agent_plan: Vec<String>,
+    // The answer cache key of the question in flight, when its answer may be
+    // cached, and whether the agent called MCP or local tools for it.
+    pending_answer_key: Option<String>,
+    used_tools: bool,
+    // Set by `/nocache`: the next question skips the cache lookup.
+    skip_cache: bool,
+    streaming: bool,
+    stop_stream: Option<Arc<AtomicBool>>,
+    pending_refinement: Option<String>,
+    interrupted_turn: Vec<Message>,
+    share_picker: Option<usize>,
+    // `AICLI_PANELS`, and the `/panels` picker's selection while it is open.
+    panels: rag::PanelRegistry,
+    panel_picker: Option<usize>,
+    // A `/view` pager, run once the event loop can hand it the terminal.
+    pending_viewer: Option<std::process::Command>,
+    // A `/forget` dry run; `y` deletes its points, any other key cancels.
+    pending_forget: Option<rag::ForgetPlan>,
+    snippets: rag::Snippets,
+    snippet_manager: Option<SnippetManager>,
+    templates: rag::Templates,
+    template_picker: Option<TemplatePicker>,
+    agent_mode: AgentMode,
+    // `RAG_BACKGROUND_SUMMARIES`: files still to summarize with their
+    // estimated cost (`None` until read from the manifest), the budget, and
+    // when the user last pressed a key.
+    summary_queue: Option<Vec<(String, usize)>>,
+    summary_bucket: rag::TokenBucket,
+    summarizing: bool,
+    last_input: std::time::Instant,
+    // `RAG_COLLECTION_ROUTER`: the collection chosen for the question being
+    // resubmitted, and the picker shown when routing was unsure.
+    routed_collection: Option<String>,
+    collection_picker: Option<CollectionPicker>,
+}
+
+/// Ctrl+R reverse search: what has been typed and the history entry it
+/// matches.
+struct HistorySearch {
+    query: String,
+    matched: Option<usize>,
+}
+
+/// The `/sessions` popup: saved sessions, most recently used first.
+struct SessionPicker {
+    sessions: Vec<Session>,
+    selected: usize,
+}
+
+/// The `/snippets` popup: a list of snippets, or the `name = text` line
+/// being edited.
+struct SnippetManager {
+    selected: usize,
+    editing: Option<String>,
+    error: Option<String>,
+}
+
+/// The `/templates` popup: pick a template, then type a value for each of
+/// its placeholders in turn. The filled question is submitted after the last.
+struct TemplatePicker {
+    selected: usize,
+    // The chosen template's name and the values given so far.
+    chosen: Option<(String, BTreeMap<String, String>)>,
+    value: String,
+    error: Option<String>,
+}
+
+/// Shown when a question matches several collections about equally well:
+/// the question, and each candidate with its score and description.
+struct CollectionPicker {
+    question: String,
+    choices: Vec<(String, f32, String)>,
+    selected: usize,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -70,22 +623,66 @@ enum OutputFocus {
     Answer,
 }
 
+const SLASH_COMMANDS: &[&str] = &[
+    "/pin", "/unpin", "/focus", "/lang", "/tone", "/retrieve", "/stats", "/rename", "/index", "/share", "/snippets",
+    "/templates", "/template",
+    "/mode", "/attach", "/detach", "/view", "/forget", "/nocache",
+    "/panels", "/summarize", "/paste-context", "/sessions", "/env", "/asof",
+];
+
+// Tallest the input box grows before the prompt scrolls inside it.
+const MAX_INPUT_ROWS: usize = 8;
+
+// Files listed by a `/forget` dry run, largest first.
+const FORGET_PREVIEW_FILES: usize = 10;
+
 enum Response {
-    Rag(Result<(String, String), String>),
-    Index(Result<(), String>),
+    Rag(Result<AnswerResult, String>, Usage),
+    Token(String),
+    Index(Result<rag::ScanStats, String>),
+    Retrieve(Result<String, String>),
+    Stats(Result<String, String>),
+    /// Files now attached, and one line per file for the answer pane.
+    Attach(Vec<String>, String),
+    Share(Result<String, String>),
+    /// A `/forget` dry run, waiting for confirmation.
+    ForgetPlan(Result<rag::ForgetPlan, String>),
+    Forget(Result<usize, String>),
+    Title(String),
+    Agent(rag::AgentEvent),
+    /// How `RAG_CONTEXT_SPLIT` divided the context for the query in flight.
+    Allocation(rag::ContextAllocation),
+    /// New output for the named `AICLI_PANELS` panel.
+    Panel(String, Result<String, String>),
+    /// A `/summarize` result for the answer pane.
+    Summary(Result<String, String>),
+    /// A file summarized in the background, or why it failed.
+    BackgroundSummary(String, Result<(), String>),
+    /// Where `RAG_COLLECTION_ROUTER` would send a question.
+    Route(String, Result<rag::Route, String>),
     Command(String),
 }
 
 impl App {
-    fn new(rag_cfg: Arc<RagConfig>) -> Self {
-        Self {
+    fn new(rag_cfg: RagConfig, session: Option<Session>) -> Self {
+        let snippets = rag::load_snippets(&rag_cfg).unwrap_or_default();
+        let templates = rag::load_templates(&rag_cfg).unwrap_or_default();
+        let agent_mode = AgentOptions::from_config(&rag_cfg).mode;
+        let panels = rag::PanelRegistry::new(&rag_cfg.panels);
+        let summary_bucket = rag::TokenBucket::new(rag_cfg.summary_tokens_per_min, std::time::Instant::now());
+        let history = if rag_cfg.history_size == 0 {
+            rag::PromptHistory::default()
+        } else {
+            rag::PromptHistory::load(&rag_cfg)
+        };
+        let mut app = Self {
             input: String::new(),
             cursor: 0,
             last_submit: None,
             last_command_output: None,
             rag_context: None,
             rag_answer: None,
-            rag_cfg,
+            rag_cfg: Arc::new(rag_cfg),
             input_mode: InputMode::Text,
             output_focus: OutputFocus::Answer,
             context_scroll: 0,
@@ -98,32 +695,227 @@ impl App {
             answer_auto_scroll: false,
             is_loading: false,
             spinner_idx: 0,
+            session: None,
+            pinned: Vec::new(),
+            session_picker: None,
+            history,
+            history_pos: None,
+            history_draft: String::new(),
+            history_search: None,
+            input_width: 0,
+            lint_warned: None,
+            pasted_context: None,
+            last_usage: None,
+            last_answer_at: 0,
+            live_usage: None,
+            context_allocation: None,
+            run_usage: Usage::default(),
+            today_usage: Usage::default(),
+            last_answer_meta: None,
+            answer_details: None,
+            agent_trace: Vec::new(),
+            agent_plan: Vec::new(),
+            pending_answer_key: None,
+            used_tools: false,
+            skip_cache: false,
+            streaming: false,
+            stop_stream: None,
+            pending_refinement: None,
+            interrupted_turn: Vec::new(),
+            share_picker: None,
+            panels,
+            panel_picker: None,
+            pending_viewer: None,
+            pending_forget: None,
+            snippets,
+            snippet_manager: None,
+            templates,
+            template_picker: None,
+            agent_mode,
+            summary_queue: None,
+            summary_bucket,
+            summarizing: false,
+            last_input: std::time::Instant::now(),
+            routed_collection: None,
+            collection_picker: None,
+        };
+        if let Some(session) = session {
+            app.open_session(session);
+        }
+        app
+    }
+
+    /// Make `session` the open session and put the TUI back as it was when
+    /// it was saved: pinned files, focus, collection, panes, scrolling, and
+    /// the agent's mode, trace, and plan.
+    fn open_session(&mut self, session: Session) {
+        let mut cfg = (*self.rag_cfg).clone();
+        if !session.focus.is_empty() {
+            cfg.focus_paths = session.focus.clone();
+        }
+        if let Some(collection) = &session.collection {
+            cfg.collection = collection.clone();
+        }
+        cfg.session_env = session.env.clone();
+        self.rag_cfg = Arc::new(cfg);
+        self.pinned = session.pinned.clone();
+        self.rag_context = session.last_context.clone();
+        self.rag_answer = session.last_answer.clone();
+        self.last_command_output = session.last_command_output.clone();
+        self.context_scroll = session.context_scroll;
+        self.answer_scroll = session.answer_scroll;
+        if let Some(mode) = session.agent_mode.as_deref().and_then(AgentMode::parse) {
+            self.agent_mode = mode;
+        }
+        self.agent_trace = session.agent_trace.clone();
+        self.agent_plan = session.agent_plan.clone();
+        self.context_auto_scroll = false;
+        self.answer_auto_scroll = false;
+        self.session = Some(session);
+    }
+
+    /// Save the open session and continue `session` instead.
+    fn switch_session(&mut self, session: Session) {
+        self.save_session();
+        let mut cfg = (*self.rag_cfg).clone();
+        cfg.focus_paths.clear();
+        self.rag_cfg = Arc::new(cfg);
+        self.interrupted_turn.clear();
+        self.agent_trace.clear();
+        self.agent_plan.clear();
+        self.answer_details = None;
+        self.last_answer_meta = None;
+        self.context_allocation = None;
+        self.summary_queue = None;
+        let name = session.name.clone();
+        self.open_session(session);
+        if self.rag_answer.is_none() {
+            self.rag_answer = Some(format!("Resumed session {}.", name));
+        }
+    }
+
+    fn session_picker_key(&mut self, code: KeyCode) {
+        let Some(picker) = self.session_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Down => picker.selected = (picker.selected + 1).min(picker.sessions.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(mut picker) = self.session_picker.take() {
+                    let session = picker.sessions.swap_remove(picker.selected);
+                    self.switch_session(session);
+                }
+            }
+            KeyCode::Esc => self.session_picker = None,
+            _ => {}
         }
     }
 
     fn insert_char(&mut self, c: char) {
         self.input.insert(self.cursor, c);
-        self.cursor += 1;
+        self.cursor += c.len_utf8();
+    }
+
+    fn insert_str(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.input.insert_str(self.cursor, &text);
+        self.cursor += text.len();
     }
 
+    /// Move the cursor to the same column on the row above or below, in a
+    /// prompt with several lines or wrapped rows. False when there is no
+    /// row that way.
+    fn move_vertical(&mut self, down: bool) -> bool {
+        let rows = input_rows(&self.input, self.input_width);
+        let (row, col) = cursor_position(&rows, self.cursor);
+        let target = if down { row + 1 } else { row.wrapping_sub(1) };
+        let Some((start, text)) = rows.get(target) else {
+            return false;
+        };
+        self.cursor = start + column_offset(text, col);
+        true
+    }
+
+    /// Delete the grapheme before the cursor, so an emoji or an accented
+    /// letter goes in one keypress.
     fn delete_char(&mut self) {
-        if self.cursor == 0 {
-            return;
-        }
-        self.cursor -= 1;
-        self.input.remove(self.cursor);
+        let start = prev_grapheme(&self.input, self.cursor);
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
     }
 
     fn move_left(&mut self) {
-        if self.cursor > 0 {
-            self.cursor -= 1;
-        }
+        self.cursor = prev_grapheme(&self.input, self.cursor);
     }
 
     fn move_right(&mut self) {
-        if self.cursor < self.input.len() {
-            self.cursor += 1;
+        self.cursor = next_grapheme(&self.input, self.cursor);
+    }
+
+    /// Delete the grapheme under the cursor (the Delete key).
+    fn delete_forward(&mut self) {
+        let end = next_grapheme(&self.input, self.cursor);
+        self.input.replace_range(self.cursor..end, "");
+    }
+
+    fn move_word(&mut self, forward: bool) {
+        self.cursor = if forward { word_end(&self.input, self.cursor) } else { word_start(&self.input, self.cursor) };
+    }
+
+    /// Ctrl+W: delete back to the start of the whitespace-separated word
+    /// before the cursor.
+    fn delete_word(&mut self) {
+        let start = space_word_start(&self.input, self.cursor);
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Ctrl+U: delete from the start of the cursor's line to the cursor.
+    fn kill_to_line_start(&mut self) {
+        let start = line_start(&self.input, self.cursor);
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Ctrl+K: delete from the cursor to the end of its line, or join the
+    /// next line when the cursor is already at the end.
+    fn kill_to_line_end(&mut self) {
+        let end = match line_end(&self.input, self.cursor) {
+            end if end == self.cursor => next_grapheme(&self.input, end),
+            end => end,
+        };
+        self.input.replace_range(self.cursor..end, "");
+    }
+
+    /// Complete the `;;name` before the cursor, or expand it in place once
+    /// the name is complete. Returns false when there is nothing to complete,
+    /// so Tab keeps switching modes.
+    fn complete_snippet(&mut self) -> bool {
+        if self.input_mode != InputMode::Text {
+            return false;
         }
+        let Some((start, name)) = rag::complete_snippet(&self.input, self.cursor, &self.snippets) else {
+            return false;
+        };
+        if name.len() > self.cursor - start {
+            self.input.replace_range(start..self.cursor, &name);
+            self.cursor = start + name.len();
+        } else if let Some(text) = self.snippets.get(&name).cloned() {
+            self.input.replace_range(start - 2..self.cursor, &text);
+            self.cursor = start - 2 + text.len();
+        } else {
+            // Several snippets share the prefix; list them.
+            let prefix = &self.input[start..self.cursor];
+            let matches: Vec<String> = self
+                .snippets
+                .keys()
+                .filter(|n| n.starts_with(prefix))
+                .map(|n| format!(";;{}", n))
+                .collect();
+            self.rag_answer = Some(format!("Snippets: {}", matches.join("  ")));
+        }
+        true
     }
 
     fn extract_file_references(input: &str) -> Vec<String> {
@@ -145,90 +937,1130 @@ impl App {
         }
     }
 
-    fn submit(&mut self, tx: mpsc::UnboundedSender<Response>) {
-        if self.input.trim().is_empty() || self.is_loading {
+    fn submit(&mut self, tx: mpsc::UnboundedSender<Response>) {
+        if self.input.trim().is_empty() || self.is_loading {
+            return;
+        }
+
+        let prompt = self.input.clone();
+        self.last_submit = Some(prompt.clone());
+        self.remember_prompt(&prompt);
+
+        match self.input_mode {
+            InputMode::Text => {
+                if prompt.trim_start().starts_with('/') {
+                    self.run_slash_command(prompt.trim(), tx);
+                    self.input.clear();
+                    self.cursor = 0;
+                    return;
+                }
+                let routed = self.routed_collection.take();
+                // A routed question was checked before it was routed.
+                let prompt = if routed.is_some() {
+                    prompt
+                } else {
+                    match self.lint_prompt(prompt) {
+                        Some(prompt) => prompt,
+                        None => return,
+                    }
+                };
+                if routed.is_none()
+                    && self.rag_cfg.collection_router
+                    && rag::collection_descriptions(&self.rag_cfg).len() >= 2
+                {
+                    self.route(prompt, tx);
+                    self.input.clear();
+                    self.cursor = 0;
+                    return;
+                }
+                let mut rag_cfg = RagConfig::clone(&self.rag_cfg);
+                if let Some(collection) = &routed {
+                    rag_cfg.collection = collection.clone();
+                }
+                let prompt = rag::expand_snippets(&prompt, &self.snippets);
+                self.last_submit = Some(prompt.clone());
+                let feature = if self.rag_cfg.stream { "stream" } else { self.rag_cfg.answer_pipeline.as_str() };
+                rag::record_feature(&self.rag_cfg, &format!("query.{}", feature));
+                let mut processed_prompt = prompt.clone();
+                if prompt.contains('@') {
+                    rag::record_feature(&self.rag_cfg, "file_injection");
+                    // File injection mode: read files and append their contents to prompt
+                    let file_refs = Self::extract_file_references(&prompt);
+                    for file in file_refs {
+                        let path = Self::resolve_path(&file);
+                        match std::fs::read_to_string(&path) {
+                            Ok(content) => {
+                                let replacement = format!("----- START FILE {} -----\n{}\n----- END FILE {} -----", file, content, file);
+                                processed_prompt = processed_prompt.replace(&format!("@{}", file), &replacement);
+                            }
+                            Err(e) => {
+                                let replacement = format!("(Could not read file {}: {})", file, e);
+                                processed_prompt = processed_prompt.replace(&format!("@{}", file), &replacement);
+                            }
+                        }
+                    }
+                }
+                let mut pinned = self.pinned_context();
+                if let Some(pasted) = self.pasted_context.take() {
+                    pinned = if pinned.is_empty() { pasted } else { format!("{}\n\n{}", pinned, pasted) };
+                }
+                if self.check_answer_cache(&rag_cfg, &processed_prompt, &pinned, tx.clone()) {
+                    self.input.clear();
+                    self.cursor = 0;
+                    return;
+                }
+
+                self.is_loading = true;
+                self.used_tools = false;
+                self.answer_auto_scroll = true;
+                self.context_auto_scroll = true;
+                self.rag_context = None;
+                self.rag_answer = None;
+                self.agent_trace.clear();
+                self.agent_plan.clear();
+                if let Some(collection) = routed {
+                    self.agent_trace.push(format!("Collection: {}", collection));
+                }
+                self.context_allocation = None;
+
+                // Without a session, a refinement still needs the turn it refines.
+                let interrupted = std::mem::take(&mut self.interrupted_turn);
+                let history = self
+                    .session
+                    .as_ref()
+                    .map(|s| s.conversation.clone())
+                    .unwrap_or(interrupted);
+                // Runs on the worker thread, since sizing the context may ask
+                // Ollama for the model's window.
+                let split_tx = tx.clone();
+                let prepare = move || {
+                    let mut rag_cfg = rag_cfg;
+                    let split = rag::allocate_context(&rag_cfg, &processed_prompt, &pinned, &history);
+                    let (pinned, history) = match split {
+                        Some(split) => {
+                            rag_cfg.context_budget = Some(split.allocation.chunks.budget);
+                            let _ = split_tx.send(Response::Allocation(split.allocation));
+                            (split.pinned, split.history)
+                        }
+                        None => (pinned, history),
+                    };
+                    let question = if pinned.is_empty() {
+                        processed_prompt
+                    } else {
+                        format!("{}\n\n{}", pinned, processed_prompt)
+                    };
+                    (rag_cfg, history, question)
+                };
+                let (event_tx, event_rx) = std::sync::mpsc::channel();
+                let trace_tx = tx.clone();
+                // Ends when the agent drops its sender.
+                tokio::task::spawn_blocking(move || {
+                    for event in event_rx {
+                        let _ = trace_tx.send(Response::Agent(event));
+                    }
+                });
+                let options = AgentOptions { mode: self.agent_mode, ..AgentOptions::from_config(&self.rag_cfg) };
+                if self.rag_cfg.stream {
+                    let stop = Arc::new(AtomicBool::new(false));
+                    self.streaming = true;
+                    self.stop_stream = Some(stop.clone());
+                    tokio::task::spawn_blocking(move || {
+                        let (rag_cfg, history, processed_prompt) = prepare();
+                        rag::reset_usage();
+                        let token_tx = tx.clone();
+                        let mut on_token = |piece: &str| {
+                            let _ = token_tx.send(Response::Token(piece.to_string()));
+                            !stop.load(Ordering::Relaxed)
+                        };
+                        let result = rag::answer_query_streaming_with_events(
+                            &rag_cfg,
+                            &history,
+                            &processed_prompt,
+                            &options,
+                            Some(event_tx),
+                            &mut on_token,
+                        );
+                        let _ = tx.send(Response::Rag(result, rag::take_usage()));
+                    });
+                    self.input.clear();
+                    self.cursor = 0;
+                    return;
+                }
+                tokio::task::spawn_blocking(move || {
+                    let (rag_cfg, history, processed_prompt) = prepare();
+                    rag::reset_usage();
+                    let result =
+                        answer_query_detailed_with_events(&rag_cfg, &history, &processed_prompt, &options, Some(event_tx))
+                            .map_err(|err| err.to_string());
+                    let _ = tx.send(Response::Rag(result, rag::take_usage()));
+                });
+            }
+            InputMode::Command if self.rag_cfg.safe_mode => {
+                self.last_command_output =
+                    Some("Safe mode is on: shell commands are disabled.".to_string());
+                self.answer_auto_scroll = true;
+            }
+            InputMode::Command => {
+                rag::record_feature(&self.rag_cfg, "shell_command");
+                self.is_loading = true;
+                self.answer_auto_scroll = true;
+                let shell = self.rag_cfg.shell.clone();
+                let env = self.rag_cfg.session_env.clone();
+                tokio::task::spawn_blocking(move || {
+                    let _ = tx.send(Response::Command(run_command(&shell, &prompt, &env)));
+                });
+            }
+            
+        }
+
+        self.input.clear();
+        self.cursor = 0;
+    }
+
+    /// Ask the collection router where `question` belongs; the answer comes
+    /// back as `Response::Route`.
+    fn route(&mut self, question: String, tx: mpsc::UnboundedSender<Response>) {
+        self.is_loading = true;
+        self.rag_answer = Some("Choosing a collection...".to_string());
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::route_question(&rag_cfg, &question);
+            let _ = tx.send(Response::Route(question, result));
+        });
+    }
+
+    /// Submit `question` against `collection` without routing it again.
+    fn ask_in(&mut self, collection: String, question: String, tx: mpsc::UnboundedSender<Response>) {
+        self.routed_collection = Some(collection);
+        self.input_mode = InputMode::Text;
+        self.cursor = question.len();
+        self.input = question;
+        self.submit(tx);
+    }
+
+    /// Open the picker for a route the router was unsure of.
+    fn pick_collection(&mut self, question: String, route: rag::Route) {
+        let descriptions = rag::collection_descriptions(&self.rag_cfg);
+        let choices = route
+            .scores
+            .into_iter()
+            .map(|(name, score)| {
+                let description = descriptions.get(&name).map(|d| d.description.clone()).unwrap_or_default();
+                (name, score, description)
+            })
+            .collect();
+        self.rag_answer = Some(format!("Which collection should answer \"{}\"?", question));
+        self.collection_picker = Some(CollectionPicker { question, choices, selected: 0 });
+    }
+
+    fn collection_picker_key(&mut self, code: KeyCode, tx: mpsc::UnboundedSender<Response>) {
+        let Some(picker) = self.collection_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+            KeyCode::Down => picker.selected = (picker.selected + 1).min(picker.choices.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(picker) = self.collection_picker.take() {
+                    let name = picker.choices[picker.selected].0.clone();
+                    self.ask_in(name, picker.question, tx);
+                }
+            }
+            KeyCode::Esc => {
+                if let Some(picker) = self.collection_picker.take() {
+                    // Give the question back so it can be edited or resent.
+                    self.cursor = picker.question.len();
+                    self.input = picker.question;
+                    self.rag_answer = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check `prompt` before it is sent. Returns what to send, or `None` after
+    /// showing a hint, leaving the prompt in the input box to be edited.
+    fn lint_prompt(&mut self, prompt: String) -> Option<String> {
+        if self.rag_cfg.prompt_lint == "off" {
+            return Some(prompt);
+        }
+        let warned = self.lint_warned.take();
+        let Some(issue) = rag::lint_prompt(&rag::expand_snippets(&prompt, &self.snippets)) else {
+            return Some(prompt);
+        };
+        rag::record_feature(&self.rag_cfg, "prompt_lint");
+        if self.rag_cfg.prompt_lint == "wrap" {
+            if let Some(wrapped) = rag::wrap_prompt(&prompt, &issue) {
+                return Some(wrapped);
+            }
+        } else if issue != rag::PromptIssue::Empty && warned.as_deref() == Some(prompt.as_str()) {
+            return Some(prompt);
+        }
+        if issue != rag::PromptIssue::Empty {
+            self.lint_warned = Some(prompt);
+        }
+        self.rag_answer = Some(issue.hint());
+        self.answer_auto_scroll = true;
+        None
+    }
+
+    /// Add `prompt` to the input history and save it (outside safe mode),
+    /// unless `AICLI_HISTORY_SIZE` is 0.
+    fn remember_prompt(&mut self, prompt: &str) {
+        self.history_pos = None;
+        self.history_draft.clear();
+        if self.rag_cfg.history_size == 0 {
+            return;
+        }
+        self.history.push(prompt, self.rag_cfg.history_size);
+        if let Err(err) = self.history.save(&self.rag_cfg) {
+            rag::record_error(&self.rag_cfg, "history", &err);
+        }
+    }
+
+    /// Alt+Up/Down: show the previous or next prompt. Going past the newest
+    /// brings back what was being typed before browsing started.
+    fn recall_history(&mut self, older: bool) {
+        let pos = match (self.history_pos, older) {
+            (current, true) => match self.history.older(current) {
+                Some(pos) => Some(pos),
+                None => return,
+            },
+            (Some(current), false) => self.history.newer(current),
+            (None, false) => return,
+        };
+        if self.history_pos.is_none() {
+            self.history_draft = self.input.clone();
+        }
+        self.history_pos = pos;
+        self.input = match pos {
+            Some(pos) => self.history.entries[pos].clone(),
+            None => std::mem::take(&mut self.history_draft),
+        };
+        self.cursor = self.input.len();
+    }
+
+    fn history_search_key(&mut self, key: &KeyEvent) {
+        let Some(search) = self.history_search.as_mut() else {
+            return;
+        };
+        match key.code {
+            // Again: the next older match.
+            _ if shortcut(key, 'r', 7) => {
+                if let Some(matched) = search.matched {
+                    search.matched = self.history.search(&search.query, Some(matched)).or(Some(matched));
+                }
+            }
+            KeyCode::Char(ch) if !ctrl(key) => {
+                search.query.push(ch);
+                search.matched = self.history.search(&search.query, None);
+            }
+            KeyCode::Backspace => {
+                pop_grapheme(&mut search.query);
+                search.matched = if search.query.is_empty() { None } else { self.history.search(&search.query, None) };
+            }
+            KeyCode::Enter => {
+                if let Some(matched) = search.matched {
+                    self.input = self.history.entries[matched].clone();
+                    self.cursor = self.input.len();
+                    self.history_pos = None;
+                }
+                self.history_search = None;
+            }
+            KeyCode::Esc => self.history_search = None,
+            _ => {}
+        }
+    }
+
+    /// Serve a first question from the answer cache, returning true when it
+    /// was answered. Otherwise remember its key so the answer is stored once
+    /// it arrives. Follow-ups depend on the conversation and are never cached.
+    fn check_answer_cache(
+        &mut self,
+        rag_cfg: &RagConfig,
+        prompt: &str,
+        pinned: &str,
+        tx: mpsc::UnboundedSender<Response>,
+    ) -> bool {
+        self.pending_answer_key = None;
+        let skip = std::mem::take(&mut self.skip_cache);
+        let first_turn =
+            self.interrupted_turn.is_empty() && self.session.as_ref().is_none_or(|s| s.conversation.is_empty());
+        if !first_turn || !rag_cfg.answer_cache {
+            return false;
+        }
+        // Only RAG-only mode streams an answer that differs from the agent's.
+        let streamed = rag_cfg.stream && self.agent_mode == AgentMode::RagOnly;
+        let mode = if streamed { "stream" } else { self.agent_mode.label() };
+        let key = rag::answer_cache_key(rag_cfg, &format!("{}\n\n{}", pinned, prompt), mode);
+        let cached = if skip { None } else { rag::lookup_answer(rag_cfg, &key) };
+        let Some(cached) = cached else {
+            self.pending_answer_key = Some(key);
+            return false;
+        };
+        rag::record_feature(&self.rag_cfg, "answer_cache.hit");
+        self.record_turn(&cached.context, &cached.answer);
+        self.generate_title(tx);
+        self.last_answer_meta = Some(cached.badge());
+        self.answer_details = None;
+        self.agent_trace.clear();
+        self.agent_plan.clear();
+        self.context_allocation = None;
+        self.answer_auto_scroll = true;
+        self.context_auto_scroll = true;
+        self.rag_answer = Some(if cached.sources.is_empty() {
+            cached.answer
+        } else {
+            format!("{}\n\nSources:\n{}", cached.answer, cached.sources)
+        });
+        self.rag_context = Some(cached.context);
+        true
+    }
+
+    /// Stop a streaming answer, keeping what has arrived. With `refine`, the
+    /// current input is sent as a follow-up once the partial answer lands.
+    fn interrupt(&mut self, refine: bool) {
+        let Some(stop) = &self.stop_stream else {
+            return;
+        };
+        if refine && !self.input.trim().is_empty() {
+            self.pending_refinement = Some(std::mem::take(&mut self.input));
+            self.cursor = 0;
+        }
+        stop.store(true, Ordering::Relaxed);
+    }
+
+    fn pinned_context(&self) -> String {
+        self.pinned
+            .iter()
+            .map(|file| match std::fs::read_to_string(Self::resolve_path(file)) {
+                Ok(content) => format!("----- PINNED FILE {} -----\n{}\n----- END PINNED FILE {} -----", file, content, file),
+                Err(e) => format!("(Could not read pinned file {}: {})", file, e),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Pin the clipboard for the next question, within
+    /// `AICLI_CLIPBOARD_MAX_BYTES`.
+    fn paste_context(&mut self) -> String {
+        rag::record_feature(&self.rag_cfg, "paste_context");
+        let max_bytes = self.rag_cfg.clipboard_max_bytes;
+        let pasted = rag::read_clipboard().and_then(|text| rag::clipboard_context(&text, max_bytes));
+        match pasted {
+            Ok(block) => {
+                let lines = block.lines().count().saturating_sub(2);
+                self.pasted_context = Some(block);
+                format!("Pinned the clipboard ({} lines) for the next question. /paste-context clear drops it.", lines)
+            }
+            Err(err) => format!("Error: {}", err),
+        }
+    }
+
+    fn run_slash_command(&mut self, line: &str, tx: mpsc::UnboundedSender<Response>) {
+        let mut parts = line.split_whitespace();
+        let cmd = parts.next().unwrap_or_default();
+        let rest: Vec<String> = parts.map(|s| s.to_string()).collect();
+        if SLASH_COMMANDS.contains(&cmd) {
+            rag::record_feature(&self.rag_cfg, &format!("slash{}", cmd));
+        }
+        let message = match cmd {
+            "/pin" if !rest.is_empty() => {
+                for file in rest {
+                    if !self.pinned.contains(&file) {
+                        self.pinned.push(file);
+                    }
+                }
+                format!("Pinned: {}", self.pinned.join(", "))
+            }
+            "/unpin" => {
+                if rest.is_empty() {
+                    self.pinned.clear();
+                } else {
+                    self.pinned.retain(|f| !rest.contains(f));
+                }
+                format!("Pinned: {}", if self.pinned.is_empty() { "(none)".to_string() } else { self.pinned.join(", ") })
+            }
+            "/attach" if !rest.is_empty() => {
+                self.attach(tx, rest);
+                return;
+            }
+            "/detach" => {
+                let mut cfg = (*self.rag_cfg).clone();
+                let removed: Vec<String> = if rest.is_empty() {
+                    std::mem::take(&mut cfg.attachments)
+                } else {
+                    let paths: Vec<String> =
+                        rest.iter().map(|f| Self::resolve_path(f).to_string_lossy().to_string()).collect();
+                    let (removed, kept) = cfg.attachments.into_iter().partition(|a| paths.contains(a));
+                    cfg.attachments = kept;
+                    removed
+                };
+                for path in &removed {
+                    rag::detach_file(path);
+                }
+                self.rag_cfg = Arc::new(cfg);
+                format!(
+                    "Attached: {}",
+                    if self.rag_cfg.attachments.is_empty() { "(none)".to_string() } else { self.rag_cfg.attachments.join(", ") }
+                )
+            }
+            "/focus" => {
+                let mut cfg = (*self.rag_cfg).clone();
+                cfg.focus_paths = rest;
+                self.rag_cfg = Arc::new(cfg);
+                if self.rag_cfg.focus_paths.is_empty() {
+                    "Focus cleared: retrieving from the whole index.".to_string()
+                } else {
+                    format!("Focus: {}", self.rag_cfg.focus_paths.join(", "))
+                }
+            }
+            "/lang" => {
+                let mut cfg = (*self.rag_cfg).clone();
+                cfg.answer_language = rest.join(" ");
+                self.rag_cfg = Arc::new(cfg);
+                if self.rag_cfg.answer_language.is_empty() {
+                    "Answer language: same as the question.".to_string()
+                } else {
+                    format!("Answer language: {}", self.rag_cfg.answer_language)
+                }
+            }
+            "/tone" => {
+                let mut cfg = (*self.rag_cfg).clone();
+                cfg.answer_tone = rest.join(" ");
+                self.rag_cfg = Arc::new(cfg);
+                if self.rag_cfg.answer_tone.is_empty() {
+                    "Answer tone: default.".to_string()
+                } else {
+                    format!("Answer tone: {}", self.rag_cfg.answer_tone)
+                }
+            }
+            "/mode" if rest.is_empty() => {
+                self.agent_mode = self.agent_mode.next();
+                format!("Agent mode: {}", self.agent_mode.label())
+            }
+            "/mode" => match AgentMode::parse(&rest.join(" ")) {
+                Some(mode) => {
+                    self.agent_mode = mode;
+                    format!("Agent mode: {}", mode.label())
+                }
+                None => "Usage: /mode [hybrid|rag|mcp]".to_string(),
+            },
+            "/retrieve" if !rest.is_empty() => {
+                self.retrieve_only(tx, rest.join(" "));
+                return;
+            }
+            "/stats" => {
+                self.show_stats(tx);
+                return;
+            }
+            "/rename" if !rest.is_empty() => match self.session.as_mut() {
+                Some(session) => {
+                    session.title = Some(rag::clean_title(&rest.join(" ")));
+                    format!("Renamed session to: {}", session.title.as_deref().unwrap_or_default())
+                }
+                None => "No session open: start aicli with --session <name> to keep a title.".to_string(),
+            },
+            "/index" if rest.len() == 1 => {
+                self.index_from(tx, rest.into_iter().next());
+                return;
+            }
+            "/share" if self.rag_cfg.share_targets.is_empty() => {
+                "No share targets configured. Set AICLI_SHARE_TARGETS, e.g. github=cmd:gh issue create --title \"$AICLI_SHARE_TITLE\" --body-file -".to_string()
+            }
+            "/share" if rest.is_empty() => {
+                self.share_picker = Some(0);
+                return;
+            }
+            "/share" => {
+                let name = rest.join(" ");
+                match self.rag_cfg.share_targets.iter().position(|t| t.name == name) {
+                    Some(idx) => {
+                        self.share_to(tx, idx);
+                        return;
+                    }
+                    None => format!("Unknown share target: {}", name),
+                }
+            }
+            "/nocache" if rest.is_empty() => {
+                self.skip_cache = true;
+                "The next question skips the answer cache; its new answer replaces the cached one.".to_string()
+            }
+            "/nocache" => {
+                self.skip_cache = true;
+                self.input = rest.join(" ");
+                self.submit(tx);
+                return;
+            }
+            "/forget" if !rest.is_empty() => {
+                self.plan_forget(tx, rest.join(" "));
+                return;
+            }
+            "/view" => match self.view_answer(&rest.join(" ")) {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(err) => format!("Error: {}", err),
+            },
+            "/panels" if self.panels.is_empty() => {
+                "No panels configured. Set AICLI_PANELS, e.g. ci=60:gh run list --limit 5".to_string()
+            }
+            "/panels" if rest.is_empty() => {
+                self.panel_picker = Some(0);
+                return;
+            }
+            "/panels" => {
+                let name = rest.join(" ");
+                match self.panels.toggle(&name) {
+                    Some(true) => format!("Showing panel {}.", name),
+                    Some(false) => format!("Hid panel {}.", name),
+                    None => format!("Unknown panel: {}", name),
+                }
+            }
+            "/snippets" => {
+                self.snippet_manager = Some(SnippetManager {
+                    selected: 0,
+                    editing: None,
+                    error: None,
+                });
+                return;
+            }
+            "/templates" if self.templates.is_empty() => {
+                "No templates yet. Add one with /template name = summarize errors in {service} since {date}".to_string()
+            }
+            "/templates" => {
+                self.template_picker = Some(TemplatePicker {
+                    selected: 0,
+                    chosen: None,
+                    value: String::new(),
+                    error: None,
+                });
+                return;
+            }
+            "/template" => match rag::parse_template(&rest.join(" ")) {
+                Ok((name, text)) => {
+                    let placeholders = rag::template_placeholders(&text).join(", ");
+                    self.templates.insert(name.clone(), text);
+                    match rag::save_templates(&self.rag_cfg, &self.templates) {
+                        Ok(()) => format!("Saved template {} ({}). Run it from /templates.", name, placeholders),
+                        Err(err) => format!("Error: {}", err),
+                    }
+                }
+                Err(err) => format!("Error: {}", err),
+            },
+            "/paste-context" if rest.first().map(String::as_str) == Some("clear") => {
+                self.pasted_context = None;
+                "Dropped the pasted clipboard.".to_string()
+            }
+            "/paste-context" => self.paste_context(),
+            "/sessions" => match rag::list_sessions(&self.rag_cfg) {
+                Ok(sessions) if sessions.is_empty() => "No saved sessions.".to_string(),
+                Ok(sessions) => {
+                    let current = self.session.as_ref().map(|s| s.name.clone());
+                    let sessions: Vec<Session> =
+                        sessions.into_iter().filter(|s| Some(&s.name) != current.as_ref()).collect();
+                    if sessions.is_empty() {
+                        "No other saved sessions.".to_string()
+                    } else {
+                        self.session_picker = Some(SessionPicker { sessions, selected: 0 });
+                        return;
+                    }
+                }
+                Err(err) => format!("Error: {}", err),
+            },
+            "/env" => {
+                let args = rag::split_command_line(line);
+                let mut cfg = (*self.rag_cfg).clone();
+                match rag::apply_env_command(&mut cfg.session_env, &args[1..]) {
+                    Ok(message) => {
+                        self.rag_cfg = Arc::new(cfg);
+                        message
+                    }
+                    Err(err) => format!("Error: {}", err),
+                }
+            }
+            "/summarize" if rest.len() == 1 => {
+                self.summarize(tx, rest.join(" "));
+                return;
+            }
+            "/asof" if rest.len() >= 2 => {
+                self.ask_as_of(tx, rest[0].clone(), rest[1..].join(" "));
+                return;
+            }
+            _ => "Commands: /pin <file..> | /unpin [file..] | /focus [path..] | /lang [language] | /tone [concise|detailed|bullet] | /retrieve <question> | /attach <file..> | /detach [file..] | /index <path|url> | /stats | /rename <title> | /share [target] | /view [viewer] | /forget <glob> | /nocache [question] | /panels [name] | /snippets | /templates | /template <name> = <text> | /summarize <file> | /paste-context [clear] | /sessions | /env [KEY=VALUE..|clear [KEY..]] | /asof <date|snapshot> <question> | /mode [hybrid|rag|mcp]".to_string(),
+        };
+        self.rag_answer = Some(message);
+        self.answer_auto_scroll = true;
+        self.save_session();
+    }
+
+    fn snippet_manager_key(&mut self, code: KeyCode) {
+        let Some(manager) = self.snippet_manager.as_mut() else {
+            return;
+        };
+        let count = self.snippets.len();
+        if let Some(line) = manager.editing.as_mut() {
+            match code {
+                KeyCode::Char(ch) => line.push(ch),
+                KeyCode::Backspace => {
+                    pop_grapheme(line);
+                }
+                KeyCode::Esc => {
+                    manager.editing = None;
+                    manager.error = None;
+                }
+                KeyCode::Enter => match rag::parse_snippet(line) {
+                    Ok((name, text)) => {
+                        self.snippets.insert(name.clone(), text);
+                        manager.selected = self.snippets.keys().position(|n| *n == name).unwrap_or(0);
+                        manager.editing = None;
+                        manager.error = rag::save_snippets(&self.rag_cfg, &self.snippets).err();
+                    }
+                    Err(err) => manager.error = Some(err),
+                },
+                _ => {}
+            }
+            return;
+        }
+        match code {
+            KeyCode::Up => manager.selected = manager.selected.saturating_sub(1),
+            KeyCode::Down => manager.selected = (manager.selected + 1).min(count.saturating_sub(1)),
+            KeyCode::Char('n') | KeyCode::Char('a') => {
+                manager.editing = Some(String::new());
+                manager.error = None;
+            }
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some((name, text)) = self.snippets.iter().nth(manager.selected) {
+                    manager.editing = Some(format!("{} = {}", name, text));
+                    manager.error = None;
+                }
+            }
+            KeyCode::Delete | KeyCode::Char('d') => {
+                if let Some(name) = self.snippets.keys().nth(manager.selected).cloned() {
+                    self.snippets.remove(&name);
+                    manager.selected = manager.selected.min(self.snippets.len().saturating_sub(1));
+                    manager.error = rag::save_snippets(&self.rag_cfg, &self.snippets).err();
+                }
+            }
+            KeyCode::Esc => self.snippet_manager = None,
+            _ => {}
+        }
+    }
+
+    fn template_picker_key(&mut self, code: KeyCode, tx: mpsc::UnboundedSender<Response>) {
+        let Some(picker) = self.template_picker.as_mut() else {
+            return;
+        };
+        let Some((name, values)) = picker.chosen.as_mut() else {
+            match code {
+                KeyCode::Up => picker.selected = picker.selected.saturating_sub(1),
+                KeyCode::Down => picker.selected = (picker.selected + 1).min(self.templates.len().saturating_sub(1)),
+                KeyCode::Enter => {
+                    if let Some(name) = self.templates.keys().nth(picker.selected).cloned() {
+                        picker.chosen = Some((name, Default::default()));
+                        picker.value.clear();
+                        picker.error = None;
+                    }
+                }
+                KeyCode::Delete | KeyCode::Char('d') => {
+                    if let Some(name) = self.templates.keys().nth(picker.selected).cloned() {
+                        self.templates.remove(&name);
+                        picker.selected = picker.selected.min(self.templates.len().saturating_sub(1));
+                        picker.error = rag::save_templates(&self.rag_cfg, &self.templates).err();
+                    }
+                }
+                KeyCode::Esc => self.template_picker = None,
+                _ => {}
+            }
+            return;
+        };
+        match code {
+            KeyCode::Char(ch) => picker.value.push(ch),
+            KeyCode::Backspace => {
+                pop_grapheme(&mut picker.value);
+            }
+            KeyCode::Esc => {
+                picker.chosen = None;
+                picker.error = None;
+            }
+            KeyCode::Enter => {
+                let text = self.templates.get(name.as_str()).cloned().unwrap_or_default();
+                let next = rag::template_placeholders(&text)
+                    .into_iter()
+                    .find(|p| !values.contains_key(p));
+                if let Some(placeholder) = next {
+                    values.insert(placeholder, std::mem::take(&mut picker.value));
+                }
+                if rag::template_placeholders(&text).iter().all(|p| values.contains_key(p)) {
+                    match rag::fill_template(&text, values) {
+                        Ok(question) => {
+                            self.template_picker = None;
+                            self.input_mode = InputMode::Text;
+                            self.cursor = question.len();
+                            self.input = question;
+                            self.submit(tx);
+                        }
+                        Err(err) => picker.error = Some(err),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The conversation so far: the session's turns, or just the last exchange
+    /// when no session is open.
+    fn conversation_for_export(&self) -> Vec<Message> {
+        if let Some(session) = &self.session {
+            return session.conversation.clone();
+        }
+        match (&self.last_submit, &self.rag_answer) {
+            (Some(question), Some(answer)) => vec![
+                Message {
+                    role: "user".to_string(),
+                    content: question.clone(),
+                },
+                Message {
+                    role: "assistant".to_string(),
+                    content: answer.clone(),
+                },
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    fn share_to(&mut self, tx: mpsc::UnboundedSender<Response>, idx: usize) {
+        self.share_picker = None;
+        let Some(target) = self.rag_cfg.share_targets.get(idx).cloned() else {
+            return;
+        };
+        let conversation = self.conversation_for_export();
+        if conversation.is_empty() {
+            self.rag_answer = Some("Nothing to share yet.".to_string());
+            self.answer_auto_scroll = true;
+            return;
+        }
+        self.is_loading = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::share_conversation(&rag_cfg, &target, &conversation);
+            let _ = tx.send(Response::Share(result));
+        });
+    }
+
+    /// Run the commands of shown panels that are due for a refresh.
+    fn refresh_panels(&mut self, tx: &mpsc::UnboundedSender<Response>) {
+        for panel in self.panels.due(std::time::Instant::now()) {
+            let rag_cfg = self.rag_cfg.clone();
+            let tx = tx.clone();
+            tokio::task::spawn_blocking(move || {
+                let output = rag::run_panel(&rag_cfg, &panel);
+                let _ = tx.send(Response::Panel(panel.name, output));
+            });
+        }
+    }
+
+    /// Write the last answer to a temp file and open it in a viewer from
+    /// `AICLI_VIEWERS` (the first one by default, or the browser when none
+    /// is configured). `None` when a pager is waiting for the terminal.
+    fn view_answer(&mut self, name: &str) -> Result<Option<String>, String> {
+        let conversation = self.conversation_for_export();
+        let Some(answer) = conversation.iter().rev().find(|m| m.role == "assistant") else {
+            return Err("Nothing to view yet.".to_string());
+        };
+        let viewer = if name.is_empty() {
+            self.rag_cfg.viewers.first().cloned().unwrap_or_else(rag::Viewer::browser)
+        } else {
+            self.rag_cfg
+                .viewers
+                .iter()
+                .find(|v| v.name == name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown viewer: {}", name))?
+        };
+        let path = rag::write_view_file(&viewer, &rag::conversation_title(&conversation), &answer.content)?;
+        let mut command = rag::viewer_command(&self.rag_cfg, &viewer, &path)?;
+        if viewer.kind == rag::ViewerKind::Pager {
+            self.pending_viewer = Some(command);
+            return Ok(None);
+        }
+        let mut child = command
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start viewer {}: {}", viewer.name, e))?;
+        std::thread::spawn(move || child.wait());
+        Ok(Some(format!("Opened the answer in {} ({}).", viewer.name, path.display())))
+    }
+
+    fn record_turn(&mut self, context: &str, answer: &str) {
+        let question = self.last_submit.clone().unwrap_or_default();
+        if let Some(session) = self.session.as_mut() {
+            session.record_turn(&question, context, answer);
+        }
+        self.save_session();
+    }
+
+    /// Title an untitled session from its first exchange in the background.
+    fn generate_title(&mut self, tx: mpsc::UnboundedSender<Response>) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        if session.title.is_some() || session.conversation.len() != 2 {
+            return;
+        }
+        let rag_cfg = self.rag_cfg.clone();
+        let conversation = session.conversation.clone();
+        tokio::task::spawn_blocking(move || {
+            let title = rag::generate_title(&rag_cfg, &conversation)
+                .unwrap_or_else(|_| rag::clean_title(&rag::conversation_title(&conversation)));
+            let _ = tx.send(Response::Title(title));
+        });
+    }
+
+    fn record_usage(&mut self, usage: Usage) {
+        self.last_usage = Some(usage);
+        self.last_answer_at = rag::now_secs();
+        self.live_usage = None;
+        self.run_usage.add(&usage);
+        if let Some(session) = self.session.as_mut() {
+            session.usage.add(&usage);
+        }
+        match rag::record_daily_usage(&self.rag_cfg, &usage) {
+            Ok(total) => self.today_usage = total,
+            Err(_) => self.today_usage.add(&usage),
+        }
+    }
+
+    fn usage_status(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(live) = self.live_usage.filter(|_| self.is_loading) {
+            parts.push(format!("Now: {} tok", live.total_tokens()));
+        }
+        let Some(last) = self.last_usage else {
+            return parts.join(" | ");
+        };
+        parts.push(format!(
+            "Last: {} tok ${:.4} at {}",
+            last.total_tokens(),
+            last.cost_usd,
+            rag::format_local_time(&self.rag_cfg, self.last_answer_at)
+        ));
+        let session = self.session.as_ref().map(|s| s.usage).unwrap_or(self.run_usage);
+        parts.push(format!("Session: {} tok ${:.4}", session.total_tokens(), session.cost_usd));
+        parts.push(format!("Today: ${:.4}", self.today_usage.cost_usd));
+        if rag::budget_exceeded(&self.rag_cfg, &self.today_usage) {
+            parts.push("BUDGET EXCEEDED".to_string());
+        }
+        parts.join(" | ")
+    }
+
+    /// Restarts or failure of a supervised stdio MCP server.
+    fn mcp_status(&self) -> Option<String> {
+        rag::McpClient::from_config(&self.rag_cfg)
+            .server_status()
+            .and_then(|status| status.summary())
+    }
+
+    fn save_session(&mut self) {
+        // Safe mode writes nothing to disk; the session lives for this run.
+        if self.rag_cfg.safe_mode {
+            return;
+        }
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        session.pinned = self.pinned.clone();
+        session.focus = self.rag_cfg.focus_paths.clone();
+        session.collection = Some(self.rag_cfg.collection.clone());
+        session.env = self.rag_cfg.session_env.clone();
+        session.last_command_output = self.last_command_output.clone();
+        session.context_scroll = self.context_scroll;
+        session.answer_scroll = self.answer_scroll;
+        session.agent_mode = Some(self.agent_mode.label().to_string());
+        session.agent_trace = self.agent_trace.clone();
+        session.agent_plan = self.agent_plan.clone();
+        if let Err(err) = session.save(&self.rag_cfg) {
+            let note = format!("(Session not saved: {})", err);
+            self.rag_answer = Some(match self.rag_answer.take() {
+                Some(ans) => format!("{}\n\n{}", ans, note),
+                None => note,
+            });
+        }
+    }
+
+    fn retrieve_only(&mut self, tx: mpsc::UnboundedSender<Response>, question: String) {
+        self.is_loading = true;
+        self.context_auto_scroll = true;
+        self.answer_auto_scroll = true;
+        self.rag_context = None;
+        self.rag_answer = None;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::retrieve(&rag_cfg, &question, &rag::RetrieveOptions::default())
+                .map(|hits| rag::format_hit_list(&hits));
+            let _ = tx.send(Response::Retrieve(result));
+        });
+    }
+
+    fn show_stats(&mut self, tx: mpsc::UnboundedSender<Response>) {
+        self.is_loading = true;
+        self.answer_auto_scroll = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::collection_stats(&rag_cfg).map(|stats| stats.to_string());
+            let _ = tx.send(Response::Stats(result));
+        });
+    }
+
+    /// Show the cached summary of an indexed file, or summarize it now.
+    fn summarize(&mut self, tx: mpsc::UnboundedSender<Response>, name: String) {
+        self.is_loading = true;
+        self.answer_auto_scroll = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::indexed_path(&rag_cfg, &name).and_then(|path| {
+                let summary = match rag::cached_summary(&rag_cfg, &path) {
+                    Some(summary) => summary,
+                    None => rag::summarize_file(&rag_cfg, &path)?,
+                };
+                Ok(format!("{} ({}):\n\n{}", path, summary.model, summary.summary))
+            });
+            let _ = tx.send(Response::Summary(result));
+        });
+    }
+
+    /// Answer `question` from the snapshot `spec` names, loaded into a
+    /// temporary collection that is dropped once the answer is in.
+    fn ask_as_of(&mut self, tx: mpsc::UnboundedSender<Response>, spec: String, question: String) {
+        self.is_loading = true;
+        self.answer_auto_scroll = true;
+        self.context_auto_scroll = true;
+        self.pending_answer_key = None;
+        self.rag_context = None;
+        self.agent_trace.clear();
+        self.agent_plan.clear();
+        self.context_allocation = None;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            rag::reset_usage();
+            let result = rag::find_snapshot(&rag_cfg, &spec).and_then(|snapshot| {
+                let mut result = rag::with_snapshot(&rag_cfg, &snapshot, |cfg| {
+                    rag::answer_query_detailed(cfg, &[], &question)
+                })?;
+                let name = snapshot.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                let taken = rag::format_local(&rag_cfg, snapshot.created_at);
+                result.answer = format!("As of snapshot {} ({}):\n\n{}", name, taken, result.answer);
+                Ok(result)
+            });
+            let _ = tx.send(Response::Rag(result, rag::take_usage()));
+        });
+    }
+
+    /// With `RAG_BACKGROUND_SUMMARIES` and outside safe mode, summarize the
+    /// next file that needs it, one at a time, only when no query is
+    /// running, no key has been pressed for `RAG_SUMMARY_IDLE_SECS`, and the
+    /// token budget allows.
+    fn summarize_in_background(&mut self, tx: &mpsc::UnboundedSender<Response>) {
+        if !self.rag_cfg.background_summaries || self.rag_cfg.safe_mode || self.is_loading || self.summarizing {
+            return;
+        }
+        let now = std::time::Instant::now();
+        if now.duration_since(self.last_input) < Duration::from_secs(self.rag_cfg.summary_idle_secs) {
+            return;
+        }
+        let rag_cfg = self.rag_cfg.clone();
+        let queue = self.summary_queue.get_or_insert_with(|| rag::pending_summaries(&rag_cfg));
+        let Some((_, cost)) = queue.first() else {
+            return;
+        };
+        if !self.summary_bucket.try_take(*cost, now) {
+            return;
+        }
+        let (path, _) = queue.remove(0);
+        self.summarizing = true;
+        let tx = tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = rag::summarize_file(&rag_cfg, &path).map(|_| ());
+            let _ = tx.send(Response::BackgroundSummary(path, result));
+        });
+    }
+
+    fn plan_forget(&mut self, tx: mpsc::UnboundedSender<Response>, pattern: String) {
+        self.is_loading = true;
+        self.answer_auto_scroll = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = tx.send(Response::ForgetPlan(rag::plan_forget(&rag_cfg, &pattern)));
+        });
+    }
+
+    fn confirm_forget(&mut self, tx: mpsc::UnboundedSender<Response>, code: KeyCode) {
+        let Some(plan) = self.pending_forget.take() else {
+            return;
+        };
+        if !matches!(code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+            self.rag_answer = Some(format!("Kept everything matching {}.", plan.pattern));
             return;
         }
+        self.is_loading = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = tx.send(Response::Forget(rag::forget(&rag_cfg, &plan)));
+        });
+    }
 
-        let prompt = self.input.clone();
-        self.last_submit = Some(prompt.clone());
-
-        match self.input_mode {
-            InputMode::Text => {
-                if prompt.contains('@') {
-                    // File injection mode: read files and append their contents to prompt
-                    let file_refs = Self::extract_file_references(&prompt);
-                    let mut prompt_with_files = prompt.clone();
-                    for file in file_refs {
-                        let path = Self::resolve_path(&file);
-                        match std::fs::read_to_string(&path) {
-                            Ok(content) => {
-                                let replacement = format!("----- START FILE {} -----\n{}\n----- END FILE {} -----", file, content, file);
-                                prompt_with_files = prompt_with_files.replace(&format!("@{}", file), &replacement);
-                            }
-                            Err(e) => {
-                                let replacement = format!("(Could not read file {}: {})", file, e);
-                                prompt_with_files = prompt_with_files.replace(&format!("@{}", file), &replacement);
-                            }
-                        }
+    /// Embed `files` in the background so this conversation's questions
+    /// search them next to the collection. Nothing is indexed.
+    fn attach(&mut self, tx: mpsc::UnboundedSender<Response>, files: Vec<String>) {
+        self.is_loading = true;
+        self.answer_auto_scroll = true;
+        let rag_cfg = self.rag_cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut attached = Vec::new();
+            let mut lines = Vec::new();
+            for file in files {
+                let path = Self::resolve_path(&file).to_string_lossy().to_string();
+                match rag::attach_file(&rag_cfg, &path) {
+                    Ok(chunks) => {
+                        lines.push(format!("Attached {} ({} chunks) for this conversation.", file, chunks));
+                        attached.push(path);
                     }
-                    self.is_loading = true;
-                    self.answer_auto_scroll = true;
-                    self.context_auto_scroll = true;
-                    self.rag_context = None;
-                    self.rag_answer = None;
-
-                    let rag_cfg = self.rag_cfg.clone();
-                    let processed_prompt = prompt_with_files;
-                    tokio::task::spawn_blocking(move || {
-                        // println!("=== Sending to RAG ===\n{}", processed_prompt);
-                        let result = answer_query(&rag_cfg, &processed_prompt)
-                            .map_err(|err| err.to_string());
-                        // println!("=== RAG Result === {:?}", result);
-                        let _ = tx.send(Response::Rag(result));
-                    });
-                } else {
-                    // Pure RAG mode
-                    self.is_loading = true;
-                    self.answer_auto_scroll = true;
-                    self.context_auto_scroll = true;
-                    self.rag_context = None;
-                    self.rag_answer = None;
-
-                    let rag_cfg = self.rag_cfg.clone();
-                    tokio::task::spawn_blocking(move || {
-                        let result = answer_query(&rag_cfg, &prompt)
-                            .map_err(|err| err.to_string());
-                        let _ = tx.send(Response::Rag(result));
-                    });
+                    Err(err) => lines.push(format!("Error: {}", err)),
                 }
             }
-            InputMode::Command => {
-                self.is_loading = true;
-                self.answer_auto_scroll = true;
-                tokio::task::spawn_blocking(move || {
-                    let _ = tx.send(Response::Command(run_command(&prompt)));
-                });
-            }
-            
-        }
-
-        self.input.clear();
-        self.cursor = 0;
+            let _ = tx.send(Response::Attach(attached, lines.join("\n")));
+        });
     }
 
     fn index_now(&mut self, tx: mpsc::UnboundedSender<Response>) {
+        self.index_from(tx, None);
+    }
+
+    fn index_from(&mut self, tx: mpsc::UnboundedSender<Response>, source: Option<String>) {
         if self.is_loading {
             return;
         }
+        if self.rag_cfg.safe_mode {
+            self.rag_context = Some("Indexing skipped.".to_string());
+            self.rag_answer = Some("Safe mode is on: index mutations are disabled.".to_string());
+            self.answer_auto_scroll = true;
+            return;
+        }
+        if let Err(err) = rag::check_writable(&self.rag_cfg, "indexing") {
+            self.rag_context = Some("Indexing skipped.".to_string());
+            self.rag_answer = Some(err);
+            self.answer_auto_scroll = true;
+            return;
+        }
         self.is_loading = true;
         self.context_auto_scroll = true;
         self.answer_auto_scroll = true;
         self.rag_context = Some("Indexing...".to_string());
         self.rag_answer = Some("Building embeddings and updating Qdrant.".to_string());
         let rag_cfg = self.rag_cfg.clone();
-        tokio::task::spawn_blocking(move || {
-            let result = rag::index_corpus(&rag_cfg, None).map_err(|err| err.to_string());
+        tokio::spawn(async move {
+            let result = rag::index_corpus_async(&rag_cfg, source.as_deref()).await;
             let _ = tx.send(Response::Index(result));
         });
     }
@@ -291,8 +2123,43 @@ impl App {
     }
 }
 
-fn run_command(cmd: &str) -> String {
-    let output = Command::new("sh").arg("-c").arg(cmd).output();
+// A Ctrl chord. Windows reports AltGr as Ctrl+Alt, so characters typed
+// with AltGr on international layouts are not taken for shortcuts.
+fn ctrl(key: &KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && !key.modifiers.contains(KeyModifiers::ALT)
+}
+
+// An Alt chord that is not AltGr (see `ctrl`).
+fn alt(key: &KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::ALT) && !key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+// Every Ctrl shortcut also has a function key, for terminals that keep the
+// chord for themselves (Ctrl+O and Ctrl+T in some macOS and Windows ones).
+fn shortcut(key: &KeyEvent, ch: char, function_key: u8) -> bool {
+    key.code == KeyCode::F(function_key) || (key.code == KeyCode::Char(ch) && ctrl(key))
+}
+
+// Give the terminal to `command` until it exits, then take it back. The
+// outer error is the terminal's; the inner one is the command's.
+fn run_in_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    mut command: std::process::Command,
+) -> io::Result<io::Result<std::process::ExitStatus>> {
+    leave_editing_modes(terminal.backend_mut())?;
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    let status = command.status();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    enter_editing_modes(terminal.backend_mut())?;
+    terminal.clear()?;
+    Ok(status)
+}
+
+fn run_command(shell: &str, cmd: &str, env: &BTreeMap<String, String>) -> String {
+    let output = rag::shell_command(shell, cmd).envs(env).output();
 
     match output {
         Ok(out) => {
@@ -324,45 +2191,33 @@ fn inner_height(area: ratatui::layout::Rect) -> usize {
     area.height.saturating_sub(2) as usize
 }
 
-fn truncate_input(input: &str, cursor: usize, max_width: usize) -> String {
-    if max_width == 0 {
-        return String::new();
-    }
-    let len = input.len();
-    if len <= max_width {
-        return input.to_string();
-    }
-    let cursor = cursor.min(len);
-    let mut start = cursor.saturating_sub(max_width / 2);
-    if start + max_width > len {
-        start = len - max_width;
-    }
-    input[start..start + max_width].to_string()
-}
-
 fn line_count(text: &str) -> usize {
     let count = text.lines().count();
     if count == 0 { 1 } else { count }
 }
 
-fn cursor_x_in_view(input: &str, cursor: usize, max_width: usize) -> usize {
-    if max_width == 0 {
-        return 0;
-    }
-    let len = input.len();
-    if len <= max_width {
-        return cursor.min(len);
-    }
-    let cursor = cursor.min(len);
-    let mut start = cursor.saturating_sub(max_width / 2);
-    if start + max_width > len {
-        start = len - max_width;
-    }
-    cursor.saturating_sub(start).min(max_width)
+fn pop_grapheme(text: &mut String) {
+    text.truncate(prev_grapheme(text, text.len()));
+}
+
+fn dim_context_lines(text: &str) -> Text<'static> {
+    let dim = Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM);
+    Text::from(
+        text.lines()
+            .map(|line| {
+                if line.starts_with(rag::CONTEXT_LINE_MARKER) {
+                    Line::styled(line.to_string(), dim)
+                } else {
+                    Line::raw(line.to_string())
+                }
+            })
+            .collect::<Vec<_>>(),
+    )
 }
 
 fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> io::Result<()> {
     let spinner = ["|", "/", "-", "\\"];
+    let mut links = Vec::new();
 
     terminal.draw(|frame| {
         let title_style = Style::default().fg(Color::Black).add_modifier(Modifier::BOLD);
@@ -374,32 +2229,65 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
         let input_text_style = Style::default().fg(Color::DarkGray);
 
         let area = frame.area();
+        // The input box grows with the prompt, up to `MAX_INPUT_ROWS` rows.
+        let input_row_count = match app.history_search {
+            Some(_) => 1,
+            None => input_rows(&app.input, area.width.saturating_sub(2) as usize).len(),
+        };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Min(8),
-                Constraint::Length(3),
+                Constraint::Length(input_row_count.min(MAX_INPUT_ROWS) as u16 + 2),
                 Constraint::Length(3),
             ])
             .split(area);
+        let panel_count = app.panels.visible().count();
+        let (output_area, panel_area) = if panel_count == 0 {
+            (chunks[0], None)
+        } else {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .split(chunks[0]);
+            (columns[0], Some(columns[1]))
+        };
         let output_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
-            .split(chunks[0]);
+            .split(output_area);
+
+        let footer_lines = match (&app.answer_details, app.input_mode) {
+            (Some(details), InputMode::Text) if !app.is_loading => details.footer_lines(),
+            _ => Vec::new(),
+        };
+        let answer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Length(if footer_lines.is_empty() { 0 } else { footer_lines.len() as u16 + 2 }),
+            ])
+            .split(output_chunks[1]);
+        let answer_area = answer_chunks[0];
 
+        let showing_trace = app.input_mode == InputMode::Text && app.rag_context.is_none() && !app.agent_trace.is_empty();
         let (context_text, answer_text) = match app.input_mode {
             InputMode::Text => (
-                app.rag_context
-                    .as_deref()
-                    .unwrap_or("Context will appear here after you run a query.")
-                    .to_string(),
-                if app.is_loading {
+                if showing_trace {
+                    app.agent_trace.join("\n")
+                } else {
+                    app.rag_context
+                        .as_deref()
+                        .unwrap_or("Context will appear here after you run a query.")
+                        .to_string()
+                },
+                if app.is_loading && !app.streaming {
                     "Loading...".to_string()
                 } else {
                     app.rag_answer
                         .as_deref()
-                        .unwrap_or("Type your prompt below and press Enter.")
-                        .to_string()
+                        .map(rag::render_markdown)
+                        .unwrap_or_else(|| "Type your prompt below and press Enter.".to_string())
                 },
             ),
             InputMode::Command => (
@@ -415,9 +2303,15 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             )
         };
 
-        let context_title = match app.output_focus {
-            OutputFocus::Context => "Context *",
-            OutputFocus::Answer => "Context",
+        let context_title = match (showing_trace, app.output_focus) {
+            (true, OutputFocus::Context) => "Agent trace *",
+            (true, OutputFocus::Answer) => "Agent trace",
+            (false, OutputFocus::Context) => "Context *",
+            (false, OutputFocus::Answer) => "Context",
+        };
+        let context_title = match app.context_allocation.as_ref().filter(|_| !showing_trace) {
+            Some(allocation) => format!("{}  [{}]", context_title, allocation.summary()),
+            None => context_title.to_string(),
         };
 
         let answer_title = match app.input_mode {
@@ -431,10 +2325,14 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
                             _ => "",
                         }
                     )
-                } else if app.output_focus == OutputFocus::Answer {
-                    "Answer *".to_string()
                 } else {
-                    "Answer".to_string()
+                    let meta = app
+                        .last_answer_meta
+                        .as_deref()
+                        .map(|m| format!(" ({})", m))
+                        .unwrap_or_default();
+                    let focus = if app.output_focus == OutputFocus::Answer { " *" } else { "" };
+                    format!("Answer{}{}", meta, focus)
                 }
             }
             InputMode::Command => {
@@ -458,6 +2356,7 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
 
         let context_block = Block::bordered()
             .title(context_title)
+            .title(Line::from(format!(" {} ", app.rag_cfg.collection)).right_aligned())
             .title_style(title_style)
             .border_style(info_border);
         let answer_block = Block::bordered()
@@ -475,7 +2374,7 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             app.context_scroll = app.context_content_len.saturating_sub(app.context_view_height);
         }
 
-        let answer_view_height = inner_height(output_chunks[1]);
+        let answer_view_height = inner_height(answer_area);
         app.answer_content_len = line_count(&answer_text);
         app.answer_view_height = answer_view_height;
         if app.answer_auto_scroll {
@@ -485,7 +2384,7 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             app.answer_scroll = app.answer_content_len.saturating_sub(app.answer_view_height);
         }
 
-        let context = Paragraph::new(context_text)
+        let context = Paragraph::new(dim_context_lines(&context_text))
             .style(info_text_style)
             .scroll((app.context_scroll as u16, 0))
             .wrap(Wrap { trim: true })
@@ -510,7 +2409,14 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             .scroll((app.answer_scroll as u16, 0))
             .wrap(Wrap { trim: true })
             .block(answer_block);
-        frame.render_widget(answer, output_chunks[1]);
+        frame.render_widget(answer, answer_area);
+
+        let text_margin = Margin {
+            vertical: 1,
+            horizontal: 1,
+        };
+        links.extend(find_links(frame.buffer_mut(), output_chunks[0].inner(text_margin)));
+        links.extend(find_links(frame.buffer_mut(), answer_area.inner(text_margin)));
 
         let mut answer_scrollbar = ScrollbarState::new(app.answer_content_len).position(app.answer_scroll);
         let answer_scrollbar_widget = Scrollbar::new(ScrollbarOrientation::VerticalRight)
@@ -518,44 +2424,112 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             .thumb_style(Style::default().fg(Color::Blue));
         frame.render_stateful_widget(
             answer_scrollbar_widget,
-            output_chunks[1].inner(Margin {
+            answer_area.inner(Margin {
                 vertical: 1,
                 horizontal: 0,
             }),
             &mut answer_scrollbar,
         );
 
+        if !footer_lines.is_empty() {
+            let footer = Paragraph::new(footer_lines.join("\n"))
+                .style(help_text_style)
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title("Details").title_style(title_style).border_style(help_border));
+            frame.render_widget(footer, answer_chunks[1]);
+        }
+
         let input_title = match app.input_mode {
-            InputMode::Text => "Prompt (RAG)  [Ctrl+R: Index]",
+            InputMode::Text if app.rag_cfg.safe_mode => "Prompt (RAG)  [SAFE MODE]",
+            InputMode::Text if app.rag_cfg.qdrant_read_only => "Prompt (RAG)  [READ-ONLY INDEX]",
+            InputMode::Text => "Prompt (RAG)  [F2: Index]",
+            InputMode::Command if app.rag_cfg.safe_mode => "Command (Disabled in safe mode)",
             InputMode::Command => "Command (Direct)",
            
         };
+        let input_title = match (app.input_mode, app.agent_mode) {
+            (InputMode::Text, mode) if mode != AgentMode::Hybrid => format!("{}  [{}]", input_title, mode.label()),
+            _ => input_title.to_string(),
+        };
+        let input_title = match (app.input_mode, &app.pasted_context) {
+            (InputMode::Text, Some(_)) => format!("{}  [+clipboard]", input_title),
+            _ => input_title,
+        };
+        let input_title = match app.rag_cfg.session_env.len() {
+            0 => input_title,
+            count => format!("{}  [env: {}]", input_title, count),
+        };
+        let input_title = match app.session.as_ref() {
+            Some(session) => format!(
+                "{}  — {}",
+                input_title,
+                session.title.as_deref().unwrap_or(&session.name)
+            ),
+            None => input_title,
+        };
+        let input_title = match &app.history_search {
+            Some(_) => "History search  [Ctrl+R: older | Enter: use | Esc: cancel]".to_string(),
+            None => input_title,
+        };
+        // A search shows bash's `(reverse-i-search)'query': match` line in
+        // place of the input, with the cursor after the query.
+        let (input_text, input_cursor) = match &app.history_search {
+            Some(search) => {
+                let failed = if search.matched.is_none() && !search.query.is_empty() { "failed " } else { "" };
+                let prefix = format!("({}reverse-i-search)'{}'", failed, search.query);
+                let matched = search.matched.map(|i| app.history.entries[i].as_str()).unwrap_or_default();
+                (format!("{}: {}", prefix, matched), prefix.len())
+            }
+            None => (app.input.clone(), app.cursor),
+        };
         let input_block = Block::bordered()
             .title(input_title)
             .title_style(title_style)
             .border_style(input_border);
-        let input_view = truncate_input(&app.input, app.cursor, inner_width(chunks[1]));
-        let input = Paragraph::new(input_view)
-            .style(input_text_style)
-            .block(input_block)
-            .wrap(Wrap { trim: false });
+        app.input_width = inner_width(chunks[1]);
+        let rows = input_rows(&input_text, app.input_width);
+        let (cursor_row, cursor_col) = cursor_position(&rows, input_cursor);
+        // Keep the cursor's row in view when the prompt is taller than the box.
+        let visible_rows = inner_height(chunks[1]).max(1);
+        let top = (cursor_row + 1).saturating_sub(visible_rows);
+        let input_lines: Vec<Line> = rows
+            .iter()
+            .skip(top)
+            .take(visible_rows)
+            .map(|(_, text)| Line::raw(text.to_string()))
+            .collect();
+        let input = Paragraph::new(input_lines).style(input_text_style).block(input_block);
         frame.render_widget(input, chunks[1]);
 
-        let cursor_x = cursor_x_in_view(&app.input, app.cursor, inner_width(chunks[1]));
-        let x = chunks[1].x + 1 + cursor_x as u16;
-        let y = chunks[1].y + 1;
+        let x = chunks[1].x + 1 + cursor_col as u16;
+        let y = chunks[1].y + 1 + (cursor_row - top) as u16;
         frame.set_cursor_position((x, y));
 
+        let index_status = format!("Index: {}", rag::corpus_origin(&app.rag_cfg));
+        let usage_status = [Some(index_status), app.mcp_status(), Some(app.usage_status())]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let controls_title = if usage_status.is_empty() {
+            "Controls".to_string()
+        } else {
+            format!("Controls  [{}]", usage_status)
+        };
         let help_block = Block::bordered()
-            .title("Controls")
+            .title(controls_title)
             .title_style(title_style)
             .border_style(help_border);
         let help_text = match app.input_mode {
+            InputMode::Text if app.streaming => {
+                "F5/Ctrl+X: Stop | Enter: Stop and send refinement | F3/Ctrl+O: Focus | Up/Down/PgUp/PgDn/Home/End: Scroll | Esc/Ctrl+C: Quit"
+            }
             InputMode::Text => {
-                "Enter: Run RAG | F2/Ctrl+R: Index | Tab: Mode | Ctrl+O: Focus | Up/Down/PgUp/PgDn/Home/End: Scroll | Esc/Ctrl+C: Quit"
+                "Enter: Run RAG | Shift/Alt+Enter: New line | F2: Index | Alt+Up/Down: History | F7/Ctrl+R: Search history | Tab: Mode/;;snippet | F4/Ctrl+T: Agent mode | F3/Ctrl+O: Focus | Up/Down/PgUp/PgDn/Home/End: Scroll | Esc/Ctrl+C: Quit"
             }
             InputMode::Command => {
-                "Enter: Run command | F2/Ctrl+R: Index | Tab: Mode | Ctrl+O: Focus | Up/Down/PgUp/PgDn/Home/End: Scroll | Esc/Ctrl+C: Quit"
+                "Enter: Run command | Shift/Alt+Enter: New line | F2: Index | Alt+Up/Down: History | F7/Ctrl+R: Search history | Tab: Mode | F3/Ctrl+O: Focus | Up/Down/PgUp/PgDn/Home/End: Scroll | Esc/Ctrl+C: Quit"
             }
         };
         let help = Paragraph::new(help_text)
@@ -563,9 +2537,351 @@ fn draw_ui(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             .wrap(Wrap { trim: true })
             .block(help_block);
         frame.render_widget(help, chunks[2]);
+
+        if let Some(panel_area) = panel_area {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Ratio(1, panel_count as u32); panel_count])
+                .split(panel_area);
+            for (panel, row) in app.panels.visible().zip(rows.iter()) {
+                let age = panel
+                    .updated
+                    .map(|at| format!(" ({}s ago)", at.elapsed().as_secs()))
+                    .unwrap_or_default();
+                let running = if panel.running { " …" } else { "" };
+                let (text, style) = match &panel.output {
+                    Some(Ok(text)) => (text.clone(), help_text_style),
+                    Some(Err(err)) => (format!("Error: {}", err), Style::default().fg(Color::Red)),
+                    None => ("Loading...".to_string(), help_text_style),
+                };
+                let block = Block::bordered()
+                    .title(format!("{}{}{}", panel.spec.name, age, running))
+                    .title_style(title_style)
+                    .border_style(help_border);
+                frame.render_widget(Paragraph::new(text).style(style).wrap(Wrap { trim: false }).block(block), *row);
+            }
+        }
+
+        if let Some(selected) = app.panel_picker {
+            let panels = app.panels.panels();
+            let lines: Vec<Line> = panels
+                .iter()
+                .enumerate()
+                .map(|(i, panel)| {
+                    let style = if i == selected {
+                        Style::default().fg(Color::Black).bg(Color::Blue)
+                    } else {
+                        Style::default().fg(Color::Blue)
+                    };
+                    let mark = if panel.visible { "x" } else { " " };
+                    Line::styled(format!(" [{}] {} ", mark, panel.spec.name), style)
+                })
+                .collect();
+            let width = panels
+                .iter()
+                .map(|p| p.spec.name.chars().count() as u16 + 8)
+                .max()
+                .unwrap_or(0)
+                .max(36)
+                .min(area.width);
+            let height = (panels.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::bordered()
+                .title("Panels (Enter: toggle, Esc)")
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).block(block), popup);
+        }
+
+        if let Some(selected) = app.share_picker {
+            let targets = &app.rag_cfg.share_targets;
+            let lines: Vec<Line> = targets
+                .iter()
+                .enumerate()
+                .map(|(i, target)| {
+                    let style = if i == selected {
+                        Style::default().fg(Color::Black).bg(Color::Blue)
+                    } else {
+                        Style::default().fg(Color::Blue)
+                    };
+                    Line::styled(format!(" {} ", target.name), style)
+                })
+                .collect();
+            let width = targets
+                .iter()
+                .map(|t| t.name.chars().count() as u16 + 4)
+                .max()
+                .unwrap_or(0)
+                .max(30)
+                .min(area.width);
+            let height = (targets.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::bordered()
+                .title("Share to (Enter/Esc)")
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).block(block), popup);
+        }
+
+        if let Some(picker) = &app.session_picker {
+            let lines: Vec<Line> = picker
+                .sessions
+                .iter()
+                .enumerate()
+                .map(|(i, session)| {
+                    let style = if i == picker.selected {
+                        Style::default().fg(Color::Black).bg(Color::Blue)
+                    } else {
+                        Style::default().fg(Color::Blue)
+                    };
+                    Line::styled(
+                        format!(
+                            " {}  {}  {} turns  {} ",
+                            session.name,
+                            session.title.as_deref().unwrap_or("(untitled)"),
+                            session.conversation.len() / 2,
+                            rag::format_local(&app.rag_cfg, session.updated_at)
+                        ),
+                        style,
+                    )
+                })
+                .collect();
+            let width = lines.iter().map(|l| l.width() as u16 + 2).max().unwrap_or(0).max(40).min(area.width);
+            let height = (lines.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::bordered()
+                .title("Resume session (Enter/Esc)")
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).block(block), popup);
+        }
+
+        if let Some(picker) = &app.collection_picker {
+            let lines: Vec<Line> = picker
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(i, (name, score, description))| {
+                    let style = if i == picker.selected {
+                        Style::default().fg(Color::Black).bg(Color::Blue)
+                    } else {
+                        Style::default().fg(Color::Blue)
+                    };
+                    let description = description.lines().next().unwrap_or_default();
+                    Line::styled(format!(" {} ({:.2}) {} ", name, score, description), style)
+                })
+                .collect();
+            let width = lines.iter().map(|l| l.width() as u16 + 2).max().unwrap_or(0).max(40).min(area.width);
+            let height = (lines.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::bordered()
+                .title("Which collection? (Enter/Esc)")
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).block(block), popup);
+        }
+
+        if let Some(manager) = &app.snippet_manager {
+            let mut lines: Vec<Line> = if app.snippets.is_empty() {
+                vec![Line::raw(" No snippets yet. Press n to add one.")]
+            } else {
+                app.snippets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, text))| {
+                        let style = if i == manager.selected && manager.editing.is_none() {
+                            Style::default().fg(Color::Black).bg(Color::Blue)
+                        } else {
+                            Style::default().fg(Color::Blue)
+                        };
+                        Line::styled(format!(" ;;{}  {} ", name, text), style)
+                    })
+                    .collect()
+            };
+            if let Some(line) = &manager.editing {
+                lines.push(Line::raw(""));
+                lines.push(Line::styled(format!(" > {}_", line), Style::default().fg(Color::Yellow)));
+            }
+            if let Some(err) = &manager.error {
+                lines.push(Line::styled(format!(" {}", err), Style::default().fg(Color::Red)));
+            }
+            let width = area.width.saturating_sub(4).clamp(30, 100).min(area.width);
+            let height = (lines.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let title = if manager.editing.is_some() {
+                "Snippet: name = text (Enter: save, Esc: cancel)"
+            } else {
+                "Snippets (n: new, Enter/e: edit, d: delete, Esc: close)"
+            };
+            let block = Block::bordered()
+                .title(title)
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), popup);
+        }
+
+        if let Some(picker) = &app.template_picker {
+            let mut lines: Vec<Line> = Vec::new();
+            let title = match &picker.chosen {
+                None => {
+                    for (i, (name, text)) in app.templates.iter().enumerate() {
+                        let style = if i == picker.selected {
+                            Style::default().fg(Color::Black).bg(Color::Blue)
+                        } else {
+                            Style::default().fg(Color::Blue)
+                        };
+                        lines.push(Line::styled(format!(" {}  {} ", name, text), style));
+                    }
+                    "Templates (Enter: use, d: delete, Esc: close)".to_string()
+                }
+                Some((name, values)) => {
+                    let text = app.templates.get(name).cloned().unwrap_or_default();
+                    lines.push(Line::styled(format!(" {}", text), Style::default().fg(Color::Blue)));
+                    lines.push(Line::raw(""));
+                    for placeholder in rag::template_placeholders(&text) {
+                        match values.get(&placeholder) {
+                            Some(value) => lines.push(Line::raw(format!(" {{{}}}: {}", placeholder, value))),
+                            None => {
+                                lines.push(Line::styled(
+                                    format!(" {{{}}}: {}_", placeholder, picker.value),
+                                    Style::default().fg(Color::Yellow),
+                                ));
+                                break;
+                            }
+                        }
+                    }
+                    format!("Template {} (Enter: next, Esc: back)", name)
+                }
+            };
+            if let Some(err) = &picker.error {
+                lines.push(Line::styled(format!(" {}", err), Style::default().fg(Color::Red)));
+            }
+            let width = area.width.saturating_sub(4).clamp(30, 100).min(area.width);
+            let height = (lines.len() as u16 + 2).min(area.height);
+            let popup = Rect {
+                x: area.x + (area.width - width) / 2,
+                y: area.y + (area.height - height) / 2,
+                width,
+                height,
+            };
+            let block = Block::bordered()
+                .title(title)
+                .title_style(title_style)
+                .border_style(info_border);
+            frame.render_widget(Clear, popup);
+            frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), popup);
+        }
     })?;
 
-    Ok(())
+    write_hyperlinks(terminal.backend_mut(), &links)
+}
+
+/// A URL as drawn on screen: where its visible text starts, the text itself,
+/// and the full target (which may continue onto following wrapped rows).
+struct Hyperlink {
+    x: u16,
+    y: u16,
+    text: String,
+    url: String,
+}
+
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '"' | '<' | '>' | '`')
+}
+
+fn find_links(buf: &Buffer, area: Rect) -> Vec<Hyperlink> {
+    let row = |y: u16| -> Vec<char> {
+        (area.left()..area.right())
+            .map(|x| buf[(x, y)].symbol().chars().next().unwrap_or(' '))
+            .collect()
+    };
+    let mut links = Vec::new();
+    for y in area.top()..area.bottom() {
+        let cells = row(y);
+        let mut col = 0;
+        while col < cells.len() {
+            let prefix: String = cells[col..].iter().take(8).collect();
+            if !(prefix.starts_with("https://") || prefix.starts_with("http://")) {
+                col += 1;
+                continue;
+            }
+            let len = cells[col..].iter().take_while(|c| is_url_char(**c)).count();
+            let text: String = cells[col..col + len].iter().collect();
+            let mut url = text.clone();
+            // A URL wider than the pane is wrapped mid-word; follow it down.
+            let mut reaches_edge = col + len == cells.len();
+            let mut next_y = y + 1;
+            while reaches_edge && next_y < area.bottom() {
+                let next = row(next_y);
+                let cont: String = next.iter().take_while(|c| is_url_char(**c)).collect();
+                reaches_edge = cont.len() == next.len();
+                url.push_str(&cont);
+                next_y += 1;
+            }
+            let trimmed = url.trim_end_matches(['.', ',', ';', ':', ')', ']']).to_string();
+            let text_len = text.len().min(trimmed.len());
+            links.push(Hyperlink {
+                x: area.left() + col as u16,
+                y,
+                text: text[..text_len].to_string(),
+                url: trimmed,
+            });
+            col += len;
+        }
+    }
+    links
+}
+
+/// Re-draw URLs over the frame wrapped in OSC-8 escapes so terminals that
+/// support them make citations clickable; others ignore the escapes.
+fn write_hyperlinks(out: &mut impl io::Write, links: &[Hyperlink]) -> io::Result<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    queue!(out, SavePosition)?;
+    for link in links {
+        queue!(
+            out,
+            MoveTo(link.x, link.y),
+            SetForegroundColor(crossterm::style::Color::Blue),
+            SetAttribute(Attribute::Underlined),
+            Print(format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", link.url, link.text)),
+            SetAttribute(Attribute::Reset),
+            ResetColor
+        )?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
 }
 
 async fn run_app(
@@ -586,31 +2902,241 @@ async fn run_app(
                     app.spinner_idx = (app.spinner_idx + 1) % 4;
                     draw_ui(terminal, app)?;
                 }
+                app.refresh_panels(&tx);
+                app.summarize_in_background(&tx);
             }
             maybe_result = rx.recv() => {
                 if let Some(result) = maybe_result {
+                    if let Response::Token(piece) = &result {
+                        app.rag_answer.get_or_insert_with(String::new).push_str(piece);
+                        app.answer_auto_scroll = true;
+                        draw_ui(terminal, app)?;
+                        continue;
+                    }
+                    if let Response::Agent(event) = &result {
+                        match event {
+                            rag::AgentEvent::TokensUsed { total, .. } => app.live_usage = Some(*total),
+                            rag::AgentEvent::ToolCalled { .. } => app.used_tools = true,
+                            rag::AgentEvent::Planned { steps } => app.agent_plan = steps.clone(),
+                            _ => {}
+                        }
+                        app.agent_trace.push(event.to_string());
+                        app.context_auto_scroll = true;
+                        draw_ui(terminal, app)?;
+                        continue;
+                    }
+                    if let Response::Allocation(allocation) = result {
+                        app.context_allocation = Some(allocation);
+                        continue;
+                    }
+                    if let Response::Panel(name, output) = result {
+                        app.panels.update(&name, output, std::time::Instant::now());
+                        draw_ui(terminal, app)?;
+                        continue;
+                    }
+                    if let Response::BackgroundSummary(path, result) = result {
+                        app.summarizing = false;
+                        if let Err(err) = result {
+                            rag::record_error(&app.rag_cfg, "summary", &format!("{}: {}", path, err));
+                        }
+                        continue;
+                    }
+                    if let Response::Title(title) = result {
+                        if let Some(session) = app.session.as_mut() {
+                            session.title.get_or_insert(title);
+                        }
+                        app.save_session();
+                        draw_ui(terminal, app)?;
+                        continue;
+                    }
                     app.is_loading = false;
+                    app.streaming = false;
+                    app.answer_details = None;
+                    let stopped = app
+                        .stop_stream
+                        .take()
+                        .map(|stop| stop.load(Ordering::Relaxed))
+                        .unwrap_or(false);
                     match result {
-                        Response::Rag(res) => match res {
-                            Ok((ctx, ans)) => {
-                                app.rag_context = Some(ctx);
-                                app.rag_answer = Some(ans);
+                        Response::Token(_)
+                        | Response::Title(_)
+                        | Response::Agent(_)
+                        | Response::Allocation(_)
+                        | Response::Panel(..)
+                        | Response::BackgroundSummary(..) => {}
+                        Response::Rag(res, usage) => match res {
+                            Ok(result) => {
+                                if stopped && app.session.is_none() {
+                                    app.interrupted_turn = vec![
+                                        Message {
+                                            role: "user".to_string(),
+                                            content: app.last_submit.clone().unwrap_or_default(),
+                                        },
+                                        Message {
+                                            role: "assistant".to_string(),
+                                            content: result.answer.clone(),
+                                        },
+                                    ];
+                                }
+                                app.record_usage(usage);
+                                let _ = rag::record_access(&app.rag_cfg, &result);
+                                app.record_turn(&result.context, &result.answer);
+                                app.generate_title(tx.clone());
+                                let rerank = match (&result.rerank_time, &result.rerank_error) {
+                                    (_, Some(err)) => format!(", rerank failed: {}", err.lines().next().unwrap_or_default()),
+                                    (Some(t), None) => format!(", rerank {:.2}s", t.as_secs_f32()),
+                                    (None, None) => String::new(),
+                                };
+                                app.last_answer_meta = Some(format!(
+                                    "{}, {:.1}s{}",
+                                    result.model,
+                                    result.elapsed.as_secs_f32(),
+                                    rerank
+                                ));
+                                app.answer_details = result.details.clone();
+                                let sources = result.sources_text();
+                                // Answers that used tools may depend on more than the index.
+                                let cache_key = app.pending_answer_key.take().filter(|_| !stopped && !app.used_tools);
+                                if let Some(key) = cache_key {
+                                    let cached = rag::CachedAnswer {
+                                        question: app.last_submit.clone().unwrap_or_default(),
+                                        context: result.context.clone(),
+                                        answer: result.answer.clone(),
+                                        sources: sources.clone(),
+                                        model: result.model.clone(),
+                                        created_at: 0,
+                                    };
+                                    let _ = rag::store_answer(&app.rag_cfg, &key, cached);
+                                }
+                                let mut answer = result.answer;
+                                if stopped {
+                                    answer.push_str(" [stopped]");
+                                }
+                                app.rag_answer = Some(if sources.is_empty() {
+                                    answer
+                                } else {
+                                    format!("{}\n\nSources:\n{}", answer, sources)
+                                });
+                                if let Some(allocation) = app.context_allocation.as_mut() {
+                                    allocation.chunks.used = rag::estimate_tokens(&result.context);
+                                }
+                                app.rag_context = Some(result.context);
                             }
                             Err(err) => {
+                                rag::record_error(&app.rag_cfg, "query", &err);
                                 app.rag_context = Some(String::new());
                                 app.rag_answer = Some(format!("Error: {}", err));
                             }
                         },
                         Response::Index(res) => match res {
-                            Ok(()) => {
-                                app.rag_context = Some("Indexing complete.".to_string());
+                            Ok(stats) => {
+                                let mut lines = vec![format!("Indexing complete: {} files.", stats.indexed)];
+                                lines.extend(stats.summary());
+                                for (path, reason) in &stats.skipped {
+                                    lines.push(format!("  {} ({})", path, reason));
+                                }
+                                for cap in &stats.capped {
+                                    lines.push(format!("  {} ({} of {} chunks, {})", cap.path, cap.kept, cap.wanted, cap.reason));
+                                }
+                                for path in &stats.quarantined {
+                                    lines.push(format!("  {} (embedding failed)", path));
+                                }
+                                app.rag_context = Some(lines.join("\n"));
                                 app.rag_answer = Some("You can now run a RAG query.".to_string());
+                                app.summary_queue = None;
                             }
                             Err(err) => {
+                                rag::record_error(&app.rag_cfg, "index", &err);
                                 app.rag_context = Some("Indexing failed.".to_string());
                                 app.rag_answer = Some(format!("Error: {}", err));
                             }
                         },
+                        Response::Retrieve(res) => match res {
+                            Ok(list) => {
+                                app.rag_context = Some(list);
+                                app.rag_answer = Some("Retrieve only: no answer was generated.".to_string());
+                            }
+                            Err(err) => {
+                                rag::record_error(&app.rag_cfg, "retrieve", &err);
+                                app.rag_context = Some(String::new());
+                                app.rag_answer = Some(format!("Error: {}", err));
+                            }
+                        },
+                        Response::Attach(attached, message) => {
+                            let mut cfg = (*app.rag_cfg).clone();
+                            for path in attached {
+                                if !cfg.attachments.contains(&path) {
+                                    cfg.attachments.push(path);
+                                }
+                            }
+                            app.rag_cfg = Arc::new(cfg);
+                            app.rag_answer = Some(message);
+                        }
+                        Response::Summary(res) => {
+                            app.rag_answer = Some(match res {
+                                Ok(summary) => summary,
+                                Err(err) => {
+                                    rag::record_error(&app.rag_cfg, "summarize", &err);
+                                    format!("Error: {}", err)
+                                }
+                            });
+                        }
+                        Response::Stats(res) => {
+                            app.rag_answer = Some(match res {
+                                Ok(stats) => stats,
+                                Err(err) => {
+                                    rag::record_error(&app.rag_cfg, "stats", &err);
+                                    format!("Error: {}", err)
+                                }
+                            });
+                        }
+                        Response::Share(res) => {
+                            app.rag_answer = Some(match res {
+                                Ok(message) => message,
+                                Err(err) => {
+                                    rag::record_error(&app.rag_cfg, "share", &err);
+                                    format!("Error: {}", err)
+                                }
+                            });
+                        }
+                        Response::ForgetPlan(res) => {
+                            app.rag_answer = Some(match res {
+                                Ok(plan) if plan.files.is_empty() => plan.summary(FORGET_PREVIEW_FILES),
+                                Ok(plan) => {
+                                    let summary = plan.summary(FORGET_PREVIEW_FILES);
+                                    app.pending_forget = Some(plan);
+                                    format!("{}\nPress y to delete them, any other key to cancel.", summary)
+                                }
+                                Err(err) => {
+                                    rag::record_error(&app.rag_cfg, "forget", &err);
+                                    format!("Error: {}", err)
+                                }
+                            });
+                        }
+                        Response::Forget(res) => {
+                            app.rag_answer = Some(match res {
+                                Ok(points) => {
+                                    app.summary_queue = None;
+                                    format!("Deleted {} points.", points)
+                                }
+                                Err(err) => {
+                                    rag::record_error(&app.rag_cfg, "forget", &err);
+                                    format!("Error: {}", err)
+                                }
+                            });
+                        }
+                        Response::Route(question, res) => match res {
+                            Ok(route) => match route.chosen.clone() {
+                                Some(collection) => app.ask_in(collection, question, tx.clone()),
+                                None => app.pick_collection(question, route),
+                            },
+                            Err(err) => {
+                                // Answer from the current collection rather than not at all.
+                                rag::record_error(&app.rag_cfg, "router", &err);
+                                let collection = app.rag_cfg.collection.clone();
+                                app.ask_in(collection, question, tx.clone());
+                            }
+                        },
                         Response::Command(output) => {
                             app.last_command_output = Some(output);
                         }
@@ -618,30 +3144,111 @@ async fn run_app(
                     }
                     app.context_auto_scroll = true;
                     app.answer_auto_scroll = true;
+                    if let Some(refinement) = app.pending_refinement.take() {
+                        app.cursor = refinement.len();
+                        app.input = refinement;
+                        app.submit(tx.clone());
+                    }
                     draw_ui(terminal, app)?;
                 }
             }
             maybe_event = events.next() => {
+                if let Some(Ok(Event::Key(_))) = &maybe_event {
+                    app.last_input = std::time::Instant::now();
+                }
                 match maybe_event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.snippet_manager.is_some() => {
+                        app.snippet_manager_key(key.code);
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.template_picker.is_some() => {
+                        app.template_picker_key(key.code, tx.clone());
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.pending_forget.is_some() => {
+                        app.confirm_forget(tx.clone(), key.code);
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.panel_picker.is_some() => {
+                        let count = app.panels.panels().len();
+                        let selected = app.panel_picker.unwrap_or(0);
+                        match key.code {
+                            KeyCode::Up => app.panel_picker = Some(selected.saturating_sub(1)),
+                            KeyCode::Down => app.panel_picker = Some((selected + 1).min(count.saturating_sub(1))),
+                            KeyCode::Enter | KeyCode::Char(' ') => {
+                                if let Some(name) = app.panels.panels().get(selected).map(|p| p.spec.name.clone()) {
+                                    app.panels.toggle(&name);
+                                    app.refresh_panels(&tx);
+                                }
+                            }
+                            KeyCode::Esc => app.panel_picker = None,
+                            _ => {}
+                        }
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.history_search.is_some() => {
+                        app.history_search_key(&key);
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.session_picker.is_some() => {
+                        app.session_picker_key(key.code);
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.collection_picker.is_some() => {
+                        app.collection_picker_key(key.code, tx.clone());
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press && app.share_picker.is_some() => {
+                        let count = app.rag_cfg.share_targets.len();
+                        let selected = app.share_picker.unwrap_or(0);
+                        match key.code {
+                            KeyCode::Up => app.share_picker = Some(selected.saturating_sub(1)),
+                            KeyCode::Down => app.share_picker = Some((selected + 1).min(count.saturating_sub(1))),
+                            KeyCode::Enter => app.share_to(tx.clone(), selected),
+                            KeyCode::Esc => app.share_picker = None,
+                            _ => {}
+                        }
+                        draw_ui(terminal, app)?;
+                    }
                     Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
                         match key.code {
-                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
-                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.index_now(tx.clone()),
+                            KeyCode::Char('c') if ctrl(&key) => return Ok(()),
                             KeyCode::F(2) => app.index_now(tx.clone()),
+                            _ if shortcut(&key, 'r', 7) => {
+                                app.history_search = Some(HistorySearch { query: String::new(), matched: None });
+                            }
                             KeyCode::Esc => return Ok(()),
+                            _ if shortcut(&key, 'x', 5) => app.interrupt(false),
+                            KeyCode::Enter if key.modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) => {
+                                app.insert_char('\n');
+                            }
+                            KeyCode::Enter if app.streaming => app.interrupt(true),
                             KeyCode::Enter => app.submit(tx.clone()),
+                            KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => app.recall_history(true),
+                            KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => app.recall_history(false),
+                            KeyCode::Up if app.move_vertical(false) => {}
+                            KeyCode::Down if app.move_vertical(true) => {}
                             KeyCode::Up => app.scroll_up(1),
                             KeyCode::Down => app.scroll_down(1),
                             KeyCode::PageUp => app.scroll_up(app.focused_view_height().max(1)),
                             KeyCode::PageDown => app.scroll_down(app.focused_view_height().max(1)),
                             KeyCode::Home => app.scroll_to_start(),
                             KeyCode::End => app.scroll_to_end(),
-                            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            _ if shortcut(&key, 'v', 6) && app.input_mode == InputMode::Text => {
+                                app.rag_answer = Some(app.paste_context());
+                                app.answer_auto_scroll = true;
+                            }
+                            _ if shortcut(&key, 't', 4) => {
+                                app.agent_mode = app.agent_mode.next();
+                                app.rag_answer = Some(format!("Agent mode: {}", app.agent_mode.label()));
+                            }
+                            _ if shortcut(&key, 'o', 3) => {
                                 app.output_focus = match app.output_focus {
                                     OutputFocus::Context => OutputFocus::Answer,
                                     OutputFocus::Answer => OutputFocus::Context,
                                 };
                             }
+                            KeyCode::Tab if app.complete_snippet() => {}
                             KeyCode::Tab => {
                                 app.input_mode = match app.input_mode {
                                     InputMode::Text => InputMode::Command,
@@ -652,12 +3259,41 @@ async fn run_app(
                                 app.context_auto_scroll = true;
                                 app.answer_auto_scroll = true;
                             }
+                            KeyCode::Left if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                                app.move_word(false);
+                            }
+                            KeyCode::Right if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                                app.move_word(true);
+                            }
                             KeyCode::Left => app.move_left(),
                             KeyCode::Right => app.move_right(),
                             KeyCode::Backspace => app.delete_char(),
+                            KeyCode::Delete => app.delete_forward(),
+                            KeyCode::Char('b') if alt(&key) => app.move_word(false),
+                            KeyCode::Char('f') if alt(&key) => app.move_word(true),
+                            KeyCode::Char('a') if ctrl(&key) => app.cursor = line_start(&app.input, app.cursor),
+                            KeyCode::Char('e') if ctrl(&key) => app.cursor = line_end(&app.input, app.cursor),
+                            KeyCode::Char('w') if ctrl(&key) => app.delete_word(),
+                            KeyCode::Char('u') if ctrl(&key) => app.kill_to_line_start(),
+                            KeyCode::Char('k') if ctrl(&key) => app.kill_to_line_end(),
                             KeyCode::Char(ch) => app.insert_char(ch),
                             _ => {}
                         }
+                        if let Some(command) = app.pending_viewer.take() {
+                            // Crossterm's reader would compete with the pager for keystrokes.
+                            drop(events);
+                            let status = run_in_terminal(terminal, command)?;
+                            events = EventStream::new();
+                            match status {
+                                Ok(status) if status.success() => {}
+                                Ok(status) => app.rag_answer = Some(format!("Viewer exited with {}.", status)),
+                                Err(err) => app.rag_answer = Some(format!("Error: Failed to start viewer: {}", err)),
+                            }
+                        }
+                        draw_ui(terminal, app)?;
+                    }
+                    Some(Ok(Event::Paste(text))) if app.history_search.is_none() => {
+                        app.insert_str(&text);
                         draw_ui(terminal, app)?;
                     }
                     Some(Ok(_)) => {}
@@ -668,3 +3304,112 @@ async fn run_app(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_input(input: &str, cursor: usize) -> App {
+        let mut cfg = RagConfig::from_env();
+        cfg.history_size = 0;
+        let mut app = App::new(cfg, None);
+        app.input = input.to_string();
+        app.cursor = cursor;
+        app
+    }
+
+    fn kill_to_line_end(input: &str, cursor: usize) -> (String, usize) {
+        let mut app = app_with_input(input, cursor);
+        app.kill_to_line_end();
+        (app.input, app.cursor)
+    }
+
+    #[test]
+    fn ctrl_k_kills_to_the_end_of_the_line() {
+        assert_eq!(kill_to_line_end("ab\ncd", 1), ("a\ncd".to_string(), 1));
+        assert_eq!(kill_to_line_end("ab\ncd", 0), ("\ncd".to_string(), 0));
+        assert_eq!(kill_to_line_end("ab\ncd", 4), ("ab\nc".to_string(), 4));
+    }
+
+    #[test]
+    fn ctrl_k_at_the_end_of_a_line_joins_the_next_one() {
+        assert_eq!(kill_to_line_end("ab\ncd", 2), ("abcd".to_string(), 2));
+        assert_eq!(kill_to_line_end("ab\n\ncd", 3), ("ab\ncd".to_string(), 3));
+        assert_eq!(kill_to_line_end("ab", 2), ("ab".to_string(), 2));
+        assert_eq!(kill_to_line_end("", 0), (String::new(), 0));
+    }
+
+    fn buffer(rows: &[&str], width: u16) -> Buffer {
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, rows.len() as u16));
+        for (y, row) in rows.iter().enumerate() {
+            buf.set_string(0, y as u16, row, Style::default());
+        }
+        buf
+    }
+
+    #[test]
+    fn finds_links_and_trims_trailing_punctuation() {
+        let buf = buffer(&["see https://a.io/x.", "or http://b.io, ok"], 20);
+        let links = find_links(&buf, buf.area);
+        let found: Vec<_> = links.iter().map(|l| (l.x, l.y, l.text.as_str(), l.url.as_str())).collect();
+        assert_eq!(found, vec![(4, 0, "https://a.io/x", "https://a.io/x"), (3, 1, "http://b.io", "http://b.io")]);
+    }
+
+    #[test]
+    fn follows_a_link_wrapped_at_the_pane_edge() {
+        let buf = buffer(&["see https://example.", "com/a#b). done"], 20);
+        let links = find_links(&buf, buf.area);
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].x, links[0].y), (4, 0));
+        assert_eq!(links[0].text, "https://example.");
+        assert_eq!(links[0].url, "https://example.com/a#b");
+    }
+
+    #[test]
+    fn a_reopened_session_restores_the_agent_state() {
+        let mut cfg = RagConfig::from_env();
+        cfg.history_size = 0;
+        cfg.safe_mode = false;
+        cfg.data_dir = std::env::temp_dir()
+            .join(format!("aicli-tui-session-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let mut app = App::new(cfg.clone(), Some(Session::new("agent")));
+        app.agent_mode = AgentMode::McpOnly;
+        app.agent_trace = vec!["step 1: tool".to_string()];
+        app.agent_plan = vec!["List the open tickets".to_string()];
+        app.save_session();
+
+        let session = Session::load(&cfg, "agent").unwrap().unwrap();
+        let reopened = App::new(cfg.clone(), Some(session));
+        assert_eq!(reopened.agent_mode, AgentMode::McpOnly);
+        assert_eq!(reopened.agent_trace, vec!["step 1: tool"]);
+        assert_eq!(reopened.agent_plan, vec!["List the open tickets"]);
+        let _ = std::fs::remove_dir_all(&cfg.data_dir);
+    }
+
+    #[test]
+    fn links_are_found_only_inside_the_area() {
+        let buf = buffer(&["https://a.io | https://b.io"], 27);
+        let links = find_links(&buf, Rect::new(14, 0, 13, 1));
+        let found: Vec<_> = links.iter().map(|l| (l.x, l.url.as_str())).collect();
+        assert_eq!(found, vec![(15, "https://b.io")]);
+    }
+
+    #[test]
+    fn hyperlinks_are_wrapped_in_osc_8() {
+        let mut out = Vec::new();
+        write_hyperlinks(&mut out, &[]).unwrap();
+        assert!(out.is_empty());
+
+        let link = Hyperlink {
+            x: 4,
+            y: 0,
+            text: "https://example.".to_string(),
+            url: "https://example.com/a".to_string(),
+        };
+        write_hyperlinks(&mut out, &[link]).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.contains("\x1b]8;;https://example.com/a\x1b\\https://example.\x1b]8;;\x1b\\"), "{:?}", written);
+    }
+}